@@ -0,0 +1,408 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::client::cover_traffic_stream::LoopCoverTrafficStream;
+use crate::client::delivery_status::DeliveryStatusSender;
+use crate::client::events::ClientEventReceiver;
+use crate::client::gateway_failover::{
+    connect_to_gateway, create_bandwidth_controller, setup_gateway_failover, GatewayFailover,
+};
+use crate::client::inbound_messages::{InputMessage, InputMessageReceiver, InputMessageSender};
+use crate::client::key_manager::KeyManager;
+use crate::client::mix_traffic::{BatchMixMessageSender, MixTrafficController};
+use crate::client::outbound_journal::DiskOutboundJournal;
+use crate::client::real_messages_control;
+use crate::client::real_messages_control::{OutQueueLength, RealMessagesController};
+use crate::client::received_buffer::{
+    message_receiver_from_config, ReceivedBufferRequestReceiver, ReceivedBufferRequestSender,
+    ReceivedMessagesBufferController,
+};
+use crate::client::redirects::{load_own_redirect, KnownRedirects};
+use crate::client::reply_key_storage::ReplyKeyStorage;
+use crate::client::self_address::SelfAddress;
+use crate::client::topology_control::{
+    TopologyAccessor, TopologyRefresher, TopologyRefresherConfig,
+};
+use crate::client::topology_verification::TopologyVerification;
+use crate::config::ClientCoreConfigTrait;
+use crate::error::ClientCoreError;
+use credential_storage::PersistentStorage;
+use futures::channel::mpsc;
+use gateway_client::bandwidth::BandwidthController;
+use gateway_client::{
+    AcknowledgementReceiver, AcknowledgementSender, GatewayClient, MixnetMessageReceiver,
+    MixnetMessageSender,
+};
+use log::*;
+use nymsphinx::params::PacketSize;
+use std::sync::Arc;
+use task::{ShutdownListener, ShutdownNotifier};
+
+/// Handles to the running components shared by all the clients, which the particular client
+/// (like the native or the socks5 one) attaches its own interface to.
+pub struct BaseClient {
+    /// Full address of this client, which changes if the client fails over to another gateway.
+    pub self_address: SelfAddress,
+
+    /// Channel used for transforming 'raw' messages into sphinx packets and sending them
+    /// through the mix network.
+    pub input_sender: InputMessageSender,
+
+    /// Channel used for announcing the receiver of the reconstructed messages to the received
+    /// messages buffer.
+    pub received_buffer_request_sender: ReceivedBufferRequestSender,
+
+    /// Number of real packets waiting to be sent out, used for applying backpressure on streams.
+    pub out_queue_length: OutQueueLength,
+
+    /// Current view of the network topology, kept up to date by the topology refresher.
+    pub topology_accessor: TopologyAccessor,
+
+    /// Storage of the keys of the reply blocks sent by this client.
+    pub reply_key_storage: ReplyKeyStorage,
+
+    /// Channel used for notifying about changes in the client state, such as its address.
+    /// It is only available if the gateway failover is enabled.
+    pub client_events: Option<ClientEventReceiver>,
+
+    /// Notifier used for gracefully stopping all the started tasks.
+    pub shutdown: ShutdownNotifier,
+}
+
+/// Starts the components shared by all the clients in the right order: the topology refresher,
+/// the received messages buffer, the gateway connection (with the optional failover),
+/// the real traffic controller and the loop cover traffic stream.
+pub struct BaseClientBuilder<'a, C> {
+    config: &'a C,
+    key_manager: KeyManager,
+    self_address: SelfAddress,
+    known_redirects: KnownRedirects,
+    delivery_status_sender: Option<DeliveryStatusSender>,
+}
+
+impl<'a, C: ClientCoreConfigTrait> BaseClientBuilder<'a, C> {
+    pub fn new(config: &'a C, key_manager: KeyManager, self_address: SelfAddress) -> Self {
+        BaseClientBuilder {
+            config,
+            key_manager,
+            self_address,
+            known_redirects: KnownRedirects::new(),
+            delivery_status_sender: None,
+        }
+    }
+
+    /// Makes the real traffic controller report the delivery status of the sent messages
+    /// that have request ids attached.
+    #[must_use]
+    pub fn with_delivery_status_sender(mut self, sender: DeliveryStatusSender) -> Self {
+        self.delivery_status_sender = Some(sender);
+        self
+    }
+
+    // future constantly pumping loop cover traffic at some specified average rate
+    // the pumped traffic goes to the MixTrafficController
+    fn start_cover_traffic_stream(
+        &self,
+        topology_accessor: TopologyAccessor,
+        mix_tx: BatchMixMessageSender,
+        shutdown: ShutdownListener,
+    ) {
+        info!("Starting loop cover traffic stream...");
+        let config = self.config.get_base();
+
+        let mut stream = LoopCoverTrafficStream::new(
+            self.key_manager.ack_key(),
+            config.get_average_ack_delay(),
+            config.get_average_packet_delay(),
+            config.get_loop_cover_traffic_average_delay(),
+            mix_tx,
+            self.self_address.clone(),
+            topology_accessor,
+        );
+
+        if config.get_use_extended_packet_size() {
+            stream.set_custom_packet_size(PacketSize::ExtendedPacket)
+        }
+
+        stream.set_num_mix_hops(config.get_num_mix_hops());
+
+        stream.start_with_shutdown(shutdown);
+    }
+
+    fn start_real_traffic_controller(
+        &mut self,
+        topology_accessor: TopologyAccessor,
+        reply_key_storage: ReplyKeyStorage,
+        ack_receiver: AcknowledgementReceiver,
+        input_receiver: InputMessageReceiver,
+        mix_sender: BatchMixMessageSender,
+        shutdown: ShutdownListener,
+    ) -> Result<OutQueueLength, ClientCoreError> {
+        let config = self.config.get_base();
+        let mut controller_config = real_messages_control::Config::new(
+            self.key_manager.ack_key(),
+            config.get_ack_wait_multiplier(),
+            config.get_ack_wait_addition(),
+            config.get_average_ack_delay(),
+            config.get_message_sending_average_delay(),
+            config.get_average_packet_delay(),
+            config.get_disabled_main_poisson_packet_distribution(),
+            self.self_address.clone(),
+        );
+
+        if config.get_use_extended_packet_size() {
+            controller_config.set_custom_packet_size(PacketSize::ExtendedPacket)
+        }
+
+        controller_config.set_num_mix_hops(config.get_num_mix_hops());
+        if let Some(redundancy) = config.get_erasure_coding_redundancy() {
+            controller_config.set_erasure_coding_redundancy(redundancy)
+        }
+        if let Some(maximum) = config.get_maximum_retransmissions() {
+            controller_config.set_maximum_retransmissions(maximum)
+        }
+        if let Some(timeout) = config.get_message_timeout() {
+            controller_config.set_message_timeout(timeout)
+        }
+        if config.get_use_outbound_journal() {
+            let journal = DiskOutboundJournal::new(config.get_outbound_journal_directory())?;
+            controller_config.set_outbound_journal(Arc::new(journal));
+        }
+        controller_config.set_known_redirects(self.known_redirects.clone());
+        if let Some(redirect) = load_own_redirect(config.get_gateway_redirect_file()) {
+            controller_config.set_own_redirect(redirect);
+        }
+        if let Some(delivery_status_sender) = self.delivery_status_sender.take() {
+            controller_config.set_delivery_status_sender(delivery_status_sender);
+        }
+
+        info!("Starting real traffic stream...");
+
+        let controller = RealMessagesController::new(
+            controller_config,
+            ack_receiver,
+            input_receiver,
+            mix_sender,
+            topology_accessor,
+            reply_key_storage,
+        );
+        let out_queue_length = controller.out_queue_length();
+        controller.start_with_shutdown(shutdown);
+        Ok(out_queue_length)
+    }
+
+    // buffer controlling all messages fetched from provider
+    // required so that other components would be able to use them (say the websocket)
+    fn start_received_messages_buffer_controller(
+        &self,
+        query_receiver: ReceivedBufferRequestReceiver,
+        mixnet_receiver: MixnetMessageReceiver,
+        reply_key_storage: ReplyKeyStorage,
+        shutdown: ShutdownListener,
+    ) -> Result<(), ClientCoreError> {
+        let message_receiver = message_receiver_from_config(self.config.get_base())?;
+
+        info!("Starting received messages buffer controller...");
+        ReceivedMessagesBufferController::new(
+            self.key_manager.encryption_keypair(),
+            query_receiver,
+            mixnet_receiver,
+            message_receiver,
+            self.known_redirects.clone(),
+            reply_key_storage,
+        )
+        .start_with_shutdown(shutdown);
+        Ok(())
+    }
+
+    async fn start_gateway_client(
+        &self,
+        mixnet_message_sender: MixnetMessageSender,
+        ack_sender: AcknowledgementSender,
+        bandwidth_controller: BandwidthController<PersistentStorage>,
+        gateway_failover: Option<&mut GatewayFailover>,
+        shutdown: ShutdownListener,
+    ) -> Result<GatewayClient, ClientCoreError> {
+        let config = self.config.get_base();
+        if config.get_gateway_id().is_empty()
+            || config.get_gateway_owner().is_empty()
+            || config.get_gateway_listener().is_empty()
+        {
+            return Err(ClientCoreError::MissingGatewayDetails);
+        }
+
+        connect_to_gateway(
+            config,
+            &self.key_manager,
+            mixnet_message_sender,
+            ack_sender,
+            bandwidth_controller,
+            gateway_failover,
+            shutdown,
+        )
+        .await
+    }
+
+    // future responsible for periodically polling directory server and updating
+    // the current global view of topology
+    async fn start_topology_refresher(
+        &self,
+        topology_accessor: TopologyAccessor,
+        shutdown: ShutdownListener,
+    ) -> Result<(), ClientCoreError> {
+        let config = self.config.get_base();
+        // the version of the shared client code is what determines the compatible nodes,
+        // regardless of which particular client is running it
+        let topology_refresher_config = TopologyRefresherConfig::new(
+            config.get_validator_api_endpoints(),
+            config.get_topology_refresh_rate(),
+            env!("CARGO_PKG_VERSION").to_string(),
+        )
+        .with_mix_hops(config.get_num_mix_hops())
+        .with_topology_verification(TopologyVerification::try_from_config(config)?);
+        let mut topology_refresher =
+            TopologyRefresher::new(topology_refresher_config, topology_accessor);
+        // before returning, block entire runtime to refresh the current network view so that any
+        // components depending on topology would see a non-empty view
+        info!("Obtaining initial network topology");
+        topology_refresher.refresh().await;
+
+        if !topology_refresher.is_topology_routable().await {
+            error!(
+                "The current network topology seem to be insufficient to route any packets through \
+                - check if enough nodes and a gateway are online"
+            );
+            return Err(ClientCoreError::InsufficientNetworkTopology);
+        }
+
+        info!("Starting topology refresher...");
+        topology_refresher.start_with_shutdown(shutdown);
+        Ok(())
+    }
+
+    // controller for sending sphinx packets to mixnet (either real traffic or cover traffic)
+    // TODO: if we want to send control messages to gateway_client, this CAN'T take the ownership
+    // over it. Perhaps GatewayClient needs to be thread-shareable or have some channel for
+    // requests?
+    fn start_mix_traffic_controller(
+        gateway_client: GatewayClient,
+        gateway_failover: Option<GatewayFailover>,
+        shutdown: ShutdownListener,
+    ) -> BatchMixMessageSender {
+        info!("Starting mix traffic controller...");
+        let (mut mix_traffic_controller, mix_tx) = MixTrafficController::new(gateway_client);
+        if let Some(gateway_failover) = gateway_failover {
+            mix_traffic_controller = mix_traffic_controller.with_failover(gateway_failover);
+        }
+        mix_traffic_controller.start_with_shutdown(shutdown);
+        mix_tx
+    }
+
+    pub async fn start_base(mut self) -> Result<BaseClient, ClientCoreError> {
+        // channels for inter-component communication
+        // TODO: make the channels be internally created by the relevant components
+        // rather than creating them here, so say for example the buffer controller would create the request channels
+        // and would allow anyone to clone the sender channel
+
+        // unwrapped_sphinx_sender is the transmitter of mixnet messages received from the gateway
+        // unwrapped_sphinx_receiver is the receiver for said messages - used by ReceivedMessagesBuffer
+        let (mixnet_messages_sender, mixnet_messages_receiver) = mpsc::unbounded();
+
+        // used for announcing connection or disconnection of a channel for pushing re-assembled messages to
+        let (received_buffer_request_sender, received_buffer_request_receiver) = mpsc::unbounded();
+
+        // channels responsible for controlling real messages
+        let (input_sender, input_receiver) = mpsc::unbounded::<InputMessage>();
+
+        // channels responsible for controlling ack messages
+        let (ack_sender, ack_receiver) = mpsc::unbounded();
+        let shared_topology_accessor = TopologyAccessor::new();
+
+        let reply_key_storage =
+            ReplyKeyStorage::load(self.config.get_base().get_reply_encryption_key_store_path())
+                .map_err(ClientCoreError::ReplyKeyStorageError)?;
+
+        // Shutdown notifier for signalling tasks to stop
+        let shutdown = ShutdownNotifier::default();
+
+        // the components are started in very specific order. Unless you know what you are doing,
+        // do not change that.
+        self.start_topology_refresher(shared_topology_accessor.clone(), shutdown.subscribe())
+            .await?;
+        self.start_received_messages_buffer_controller(
+            received_buffer_request_receiver,
+            mixnet_messages_receiver,
+            reply_key_storage.clone(),
+            shutdown.subscribe(),
+        )?;
+
+        let bandwidth_controller = create_bandwidth_controller(self.config.get_base()).await?;
+
+        let (mut gateway_failover, client_events) = match setup_gateway_failover(
+            self.config,
+            self.self_address.clone(),
+            shared_topology_accessor.clone(),
+            mixnet_messages_sender.clone(),
+            ack_sender.clone(),
+            bandwidth_controller.clone(),
+            shutdown.subscribe(),
+        ) {
+            Some((gateway_failover, client_events)) => {
+                (Some(gateway_failover), Some(client_events))
+            }
+            None => (None, None),
+        };
+
+        let gateway_client = self
+            .start_gateway_client(
+                mixnet_messages_sender,
+                ack_sender,
+                bandwidth_controller,
+                gateway_failover.as_mut(),
+                shutdown.subscribe(),
+            )
+            .await?;
+
+        // The sphinx_message_sender is the transmitter for any component generating sphinx packets
+        // that are to be sent to the mixnet. They are used by cover traffic stream and real
+        // traffic stream.
+        // The MixTrafficController then sends the actual traffic
+        let sphinx_message_sender = Self::start_mix_traffic_controller(
+            gateway_client,
+            gateway_failover,
+            shutdown.subscribe(),
+        );
+
+        let out_queue_length = self.start_real_traffic_controller(
+            shared_topology_accessor.clone(),
+            reply_key_storage.clone(),
+            ack_receiver,
+            input_receiver,
+            sphinx_message_sender.clone(),
+            shutdown.subscribe(),
+        )?;
+
+        if !self
+            .config
+            .get_base()
+            .get_disabled_loop_cover_traffic_stream()
+        {
+            self.start_cover_traffic_stream(
+                shared_topology_accessor.clone(),
+                sphinx_message_sender,
+                shutdown.subscribe(),
+            );
+        }
+
+        Ok(BaseClient {
+            self_address: self.self_address,
+            input_sender,
+            received_buffer_request_sender,
+            out_queue_length,
+            topology_accessor: shared_topology_accessor,
+            reply_key_storage,
+            client_events,
+            shutdown,
+        })
+    }
+}
@@ -2,15 +2,15 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::client::mix_traffic::BatchMixMessageSender;
+use crate::client::self_address::SelfAddress;
 use crate::client::topology_control::TopologyAccessor;
 use crate::spawn_future;
 use futures::task::{Context, Poll};
 use futures::{Future, Stream, StreamExt};
 use log::*;
 use nymsphinx::acknowledgements::AckKey;
-use nymsphinx::addressing::clients::Recipient;
 use nymsphinx::cover::generate_loop_cover_packet;
-use nymsphinx::params::PacketSize;
+use nymsphinx::params::{PacketSize, DEFAULT_NUM_MIX_HOPS};
 use nymsphinx::utils::sample_poisson_duration;
 use rand::{rngs::OsRng, CryptoRng, Rng};
 use std::pin::Pin;
@@ -53,7 +53,7 @@ where
     mix_tx: BatchMixMessageSender,
 
     /// Represents full address of this client.
-    our_full_destination: Recipient,
+    our_full_destination: SelfAddress,
 
     /// Instance of a cryptographically secure random number generator.
     rng: R,
@@ -63,6 +63,9 @@ where
 
     /// Predefined packet size used for the loop cover messages.
     packet_size: PacketSize,
+
+    /// Number of mix hops each loop cover packet (and its ack) is going to take.
+    num_mix_hops: u8,
 }
 
 impl<R> Stream for LoopCoverTrafficStream<R>
@@ -114,7 +117,7 @@ impl LoopCoverTrafficStream<OsRng> {
         average_packet_delay: Duration,
         average_cover_message_sending_delay: Duration,
         mix_tx: BatchMixMessageSender,
-        our_full_destination: Recipient,
+        our_full_destination: SelfAddress,
         topology_access: TopologyAccessor,
     ) -> Self {
         let rng = OsRng;
@@ -136,6 +139,7 @@ impl LoopCoverTrafficStream<OsRng> {
             rng,
             topology_access,
             packet_size: Default::default(),
+            num_mix_hops: DEFAULT_NUM_MIX_HOPS,
         }
     }
 
@@ -143,17 +147,23 @@ impl LoopCoverTrafficStream<OsRng> {
         self.packet_size = packet_size;
     }
 
+    pub fn set_num_mix_hops(&mut self, num_mix_hops: u8) {
+        self.num_mix_hops = num_mix_hops;
+    }
+
     async fn on_new_message(&mut self) {
         trace!("next cover message!");
 
         // TODO for way down the line: in very rare cases (during topology update) we might have
         // to wait a really tiny bit before actually obtaining the permit hence messing with our
         // poisson delay, but is it really a problem?
+        let our_full_destination = self.our_full_destination.get();
         let topology_permit = self.topology_access.get_read_permit().await;
         // the ack is sent back to ourselves (and then ignored)
         let topology_ref_option = topology_permit.try_get_valid_topology_ref(
-            &self.our_full_destination,
-            Some(&self.our_full_destination),
+            &our_full_destination,
+            Some(&our_full_destination),
+            self.num_mix_hops,
         );
         if topology_ref_option.is_none() {
             warn!("No valid topology detected - won't send any loop cover message this time");
@@ -165,10 +175,11 @@ impl LoopCoverTrafficStream<OsRng> {
             &mut self.rng,
             topology_ref,
             &self.ack_key,
-            &self.our_full_destination,
+            &our_full_destination,
             self.average_ack_delay,
             self.average_packet_delay,
             self.packet_size,
+            self.num_mix_hops,
         )
         .expect("Somehow failed to generate a loop cover message with a valid topology");
 
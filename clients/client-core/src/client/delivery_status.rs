@@ -0,0 +1,61 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use futures::channel::mpsc;
+use std::time::Duration;
+
+/// Identifier attached by the application to an [`InputMessage`](super::inbound_messages::InputMessage)
+/// in order to receive [`DeliveryStatusUpdate`]s about it.
+pub type DeliveryRequestId = u64;
+
+pub type DeliveryStatusSender = mpsc::UnboundedSender<DeliveryStatusUpdate>;
+pub type DeliveryStatusReceiver = mpsc::UnboundedReceiver<DeliveryStatusUpdate>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeliveryStatus {
+    /// Every fragment of the message has been sent to the mix network at least once.
+    Sent,
+
+    /// Every fragment of the message (or, if erasure coded, enough of them for the recipient
+    /// to reconstruct it) has been acknowledged.
+    Acknowledged,
+
+    /// A fragment of the message has not been acknowledged despite reaching the maximum number
+    /// of retransmissions, so the whole message has been abandoned.
+    GaveUp,
+
+    /// The message has not been acknowledged before its deadline, so it has been abandoned.
+    Expired,
+}
+
+impl DeliveryStatus {
+    /// Indicates whether no further updates are going to be emitted for the message.
+    pub fn is_final(&self) -> bool {
+        !matches!(self, DeliveryStatus::Sent)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeliveryStatusUpdate {
+    pub request_id: DeliveryRequestId,
+    pub status: DeliveryStatus,
+}
+
+impl DeliveryStatusUpdate {
+    pub fn new(request_id: DeliveryRequestId, status: DeliveryStatus) -> Self {
+        DeliveryStatusUpdate { request_id, status }
+    }
+}
+
+/// Bounds on the effort the client puts into delivering a particular message.
+/// Unset values fall back to the client-wide configuration.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DeliveryLimits {
+    /// Number of times any fragment of the message can be retransmitted before
+    /// the whole message is abandoned.
+    pub maximum_retransmissions: Option<usize>,
+
+    /// Amount of time, since the message has been prepared for sending, after which it is
+    /// abandoned if it still has not been acknowledged.
+    pub timeout: Option<Duration>,
+}
@@ -0,0 +1,20 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::config::GatewayEndpoint;
+use futures::channel::mpsc;
+use nymsphinx::addressing::clients::Recipient;
+
+pub type ClientEventSender = mpsc::UnboundedSender<ClientEvent>;
+pub type ClientEventReceiver = mpsc::UnboundedReceiver<ClientEvent>;
+
+/// Changes in the state of the running client that the embedding application should know about.
+#[derive(Clone, Debug)]
+pub enum ClientEvent {
+    /// The client has switched to a different gateway, and thus its address has changed.
+    /// The new gateway configuration should be persisted so that it would be used after restart.
+    GatewayChanged {
+        gateway: GatewayEndpoint,
+        address: Recipient,
+    },
+}
@@ -0,0 +1,583 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::client::events::{ClientEvent, ClientEventReceiver, ClientEventSender};
+use crate::client::key_manager::KeyManager;
+use crate::client::self_address::SelfAddress;
+use crate::client::topology_control::TopologyAccessor;
+use crate::config::persistence::key_pathfinder::ClientKeyPathfinder;
+use crate::config::{ClientCoreConfigTrait, Config, GatewayEndpoint};
+use crate::error::ClientCoreError;
+use crate::gateway_selection::GatewayRanking;
+use crate::spawn_future;
+use config::NymConfig;
+use credential_storage::PersistentStorage;
+use futures::channel::mpsc;
+use futures::StreamExt;
+use gateway_client::bandwidth::BandwidthController;
+use gateway_client::{AcknowledgementSender, GatewayClient, MixnetMessageSender};
+use log::*;
+use nymsphinx::addressing::clients::Recipient;
+use nymsphinx::addressing::nodes::NodeIdentity;
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::Duration;
+use task::ShutdownListener;
+use topology::gateway;
+
+/// Number of consecutive topology refreshes the gateway has to be missing from
+/// for it to be assumed to be gone.
+const MISSING_FROM_TOPOLOGY_THRESHOLD: usize = 3;
+
+/// Delay before the first reattempt to connect to the configured gateway at startup.
+/// It's doubled with every subsequent failure, up to [`MAX_RECONNECTION_BACKOFF`].
+const INITIAL_RECONNECTION_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RECONNECTION_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Responsible for moving the client to a different gateway once the current one is assumed
+/// to be gone for good, i.e. it has been missing from several consecutive network topologies
+/// or we have failed to send anything to it too many times in a row.
+///
+/// The alternatives are tried in the order of the gateway ranking created during `init`
+/// (if any), followed by the remaining gateways of the current topology in random order.
+/// The client registers with the chosen gateway, stores the new shared keys and emits
+/// [`ClientEvent::GatewayChanged`] so that the new configuration could be persisted.
+pub struct GatewayFailover {
+    /// Number of consecutive failures to send packets to the gateway after which
+    /// it is assumed to be gone.
+    failure_threshold: usize,
+
+    /// Path to the file containing the ranking of gateways, if one has been created.
+    ranking_file: PathBuf,
+
+    key_pathfinder: ClientKeyPathfinder,
+    self_address: SelfAddress,
+    topology_access: TopologyAccessor,
+
+    // all of the below are required for constructing `GatewayClient` for the new gateway
+    mixnet_message_sender: MixnetMessageSender,
+    ack_sender: AcknowledgementSender,
+    bandwidth_controller: Option<BandwidthController<PersistentStorage>>,
+    response_timeout: Duration,
+    disabled_credentials_mode: bool,
+    shutdown: ShutdownListener,
+
+    event_sender: ClientEventSender,
+
+    /// Gateways that have already failed us, so that we would not go back to them.
+    failed_gateways: HashSet<String>,
+
+    /// Number of consecutive topology refreshes the current gateway was missing from.
+    missing_from_topology: usize,
+
+    /// Number of topology updates at the time of the last presence check, so that each refresh
+    /// would only be accounted for once.
+    checked_topology_updates: Option<u64>,
+}
+
+impl GatewayFailover {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new<T: NymConfig>(
+        config: &Config<T>,
+        self_address: SelfAddress,
+        topology_access: TopologyAccessor,
+        mixnet_message_sender: MixnetMessageSender,
+        ack_sender: AcknowledgementSender,
+        bandwidth_controller: Option<BandwidthController<PersistentStorage>>,
+        event_sender: ClientEventSender,
+        shutdown: ShutdownListener,
+    ) -> Self {
+        GatewayFailover {
+            failure_threshold: config.get_gateway_failover_threshold(),
+            ranking_file: config.get_gateway_ranking_file(),
+            key_pathfinder: ClientKeyPathfinder::new_from_config(config),
+            self_address,
+            topology_access,
+            mixnet_message_sender,
+            ack_sender,
+            bandwidth_controller,
+            response_timeout: config.get_gateway_response_timeout(),
+            disabled_credentials_mode: config.get_disabled_credentials_mode(),
+            shutdown,
+            event_sender,
+            failed_gateways: HashSet::new(),
+            missing_from_topology: 0,
+            checked_topology_updates: None,
+        }
+    }
+
+    /// Determines, based on the number of consecutive sending failures and the current network
+    /// topology, whether the specified gateway should be abandoned.
+    pub(crate) async fn should_fail_over(
+        &mut self,
+        gateway: &NodeIdentity,
+        consecutive_failures: usize,
+    ) -> bool {
+        let (listed_gateways, topology_updates) = self
+            .topology_access
+            .current_gateways_with_update_count()
+            .await;
+        if self.checked_topology_updates != Some(topology_updates) {
+            self.checked_topology_updates = Some(topology_updates);
+            self.missing_from_topology =
+                count_topology_absence(gateway, &listed_gateways, self.missing_from_topology);
+        }
+
+        is_gateway_gone(
+            gateway,
+            consecutive_failures,
+            self.failure_threshold,
+            self.missing_from_topology,
+        )
+    }
+
+    async fn candidates(&self, current_gateway: &NodeIdentity) -> Vec<GatewayEndpoint> {
+        let listed_gateways = self.topology_access.current_gateways().await;
+        let ranked = match GatewayRanking::load(&self.ranking_file) {
+            Ok(ranking) => ranking
+                .gateways()
+                .iter()
+                .map(|ranked| ranked.endpoint.clone())
+                .collect(),
+            Err(err) => {
+                debug!("No gateway ranking available ({err}), using the topology instead");
+                Vec::new()
+            }
+        };
+
+        order_candidates(
+            ranked,
+            &listed_gateways,
+            &current_gateway.to_base58_string(),
+            &self.failed_gateways,
+        )
+    }
+
+    async fn connect(&self, endpoint: &GatewayEndpoint) -> Result<GatewayClient, ClientCoreError> {
+        let mut key_manager = KeyManager::load_keys(&self.key_pathfinder)?;
+        let gateway_identity = NodeIdentity::from_base58_string(&endpoint.gateway_id)?;
+
+        // we don't have any shared keys with the new gateway, so the client is going to register
+        let mut gateway_client = GatewayClient::new(
+            endpoint.gateway_listener.clone(),
+            key_manager.identity_keypair(),
+            gateway_identity,
+            endpoint.gateway_owner.clone(),
+            None,
+            self.mixnet_message_sender.clone(),
+            self.ack_sender.clone(),
+            self.response_timeout,
+            self.bandwidth_controller.clone(),
+            Some(self.shutdown.clone()),
+        );
+        gateway_client.set_disabled_credentials_mode(self.disabled_credentials_mode);
+
+        let shared_keys = gateway_client.authenticate_and_start().await?;
+        key_manager.insert_gateway_shared_key(shared_keys);
+        key_manager.store_keys(&self.key_pathfinder)?;
+
+        let address = Recipient::new(
+            *key_manager.identity_keypair().public_key(),
+            *key_manager.encryption_keypair().public_key(),
+            gateway_identity,
+        );
+        self.self_address.update(address);
+
+        let event = ClientEvent::GatewayChanged {
+            gateway: endpoint.clone(),
+            address,
+        };
+        if self.event_sender.unbounded_send(event).is_err() {
+            warn!("Nobody is listening for the client events - the new gateway configuration won't be saved");
+        }
+
+        Ok(gateway_client)
+    }
+
+    /// Abandons the current gateway and returns a client connected to the first alternative
+    /// that we managed to register with.
+    pub(crate) async fn fail_over(
+        &mut self,
+        current_gateway: &NodeIdentity,
+    ) -> Result<GatewayClient, ClientCoreError> {
+        self.failed_gateways
+            .insert(current_gateway.to_base58_string());
+
+        for candidate in self.candidates(current_gateway).await {
+            info!(
+                "Attempting to fail over to gateway {}",
+                candidate.gateway_id
+            );
+            match self.connect(&candidate).await {
+                Ok(gateway_client) => {
+                    self.missing_from_topology = 0;
+                    info!(
+                        "Switched to gateway {}. The address of this client is now: {}",
+                        candidate.gateway_id,
+                        self.self_address.get()
+                    );
+                    return Ok(gateway_client);
+                }
+                Err(err) => {
+                    warn!(
+                        "Failed to switch to gateway {}: {err}",
+                        candidate.gateway_id
+                    );
+                    self.failed_gateways.insert(candidate.gateway_id);
+                }
+            }
+        }
+
+        Err(ClientCoreError::NoReachableGateways)
+    }
+}
+
+/// Creates the controller of the bandwidth credentials presented to the gateways.
+pub async fn create_bandwidth_controller<T: NymConfig>(
+    config: &Config<T>,
+) -> Result<BandwidthController<PersistentStorage>, ClientCoreError> {
+    let storage = credential_storage::initialise_storage(config.get_database_path()).await;
+
+    #[cfg(feature = "coconut")]
+    let bandwidth_controller =
+        BandwidthController::new(storage, config.get_validator_api_endpoints());
+    #[cfg(not(feature = "coconut"))]
+    let bandwidth_controller = BandwidthController::new(storage)?;
+
+    Ok(bandwidth_controller)
+}
+
+/// Creates the gateway failover, unless it's disabled, and starts the task persisting the
+/// configuration of the gateways the client moves to.
+/// Returns the failover alongside the channel notifying about the client events.
+#[allow(clippy::too_many_arguments)]
+pub fn setup_gateway_failover<C: ClientCoreConfigTrait>(
+    config: &C,
+    self_address: SelfAddress,
+    topology_access: TopologyAccessor,
+    mixnet_message_sender: MixnetMessageSender,
+    ack_sender: AcknowledgementSender,
+    bandwidth_controller: BandwidthController<PersistentStorage>,
+    shutdown: ShutdownListener,
+) -> Option<(GatewayFailover, ClientEventReceiver)> {
+    if config.get_base().get_disabled_gateway_failover() {
+        return None;
+    }
+
+    let (event_sender, event_receiver) = mpsc::unbounded();
+    let (user_event_sender, user_event_receiver) = mpsc::unbounded();
+    start_client_event_handler::<C>(
+        config.get_base().get_id(),
+        event_receiver,
+        user_event_sender,
+    );
+
+    let gateway_failover = GatewayFailover::new(
+        config.get_base(),
+        self_address,
+        topology_access,
+        mixnet_message_sender,
+        ack_sender,
+        Some(bandwidth_controller),
+        event_sender,
+        shutdown,
+    );
+    Some((gateway_failover, user_event_receiver))
+}
+
+/// Connects to the configured gateway. If the gateway failover is available, failed connection
+/// attempts are retried with an increasing delay, until either the client manages to connect
+/// or the gateway is assumed to be gone, in which case the client is moved to one of
+/// the alternative gateways instead.
+#[allow(clippy::too_many_arguments)]
+pub async fn connect_to_gateway<T: NymConfig>(
+    config: &Config<T>,
+    key_manager: &KeyManager,
+    mixnet_message_sender: MixnetMessageSender,
+    ack_sender: AcknowledgementSender,
+    bandwidth_controller: BandwidthController<PersistentStorage>,
+    mut gateway_failover: Option<&mut GatewayFailover>,
+    shutdown: ShutdownListener,
+) -> Result<GatewayClient, ClientCoreError> {
+    let gateway_identity = NodeIdentity::from_base58_string(config.get_gateway_id())?;
+
+    let mut consecutive_failures = 0;
+    let mut backoff = INITIAL_RECONNECTION_BACKOFF;
+    loop {
+        let mut gateway_client = GatewayClient::new(
+            config.get_gateway_listener(),
+            key_manager.identity_keypair(),
+            gateway_identity,
+            config.get_gateway_owner(),
+            Some(key_manager.gateway_shared_key()),
+            mixnet_message_sender.clone(),
+            ack_sender.clone(),
+            config.get_gateway_response_timeout(),
+            Some(bandwidth_controller.clone()),
+            Some(shutdown.clone()),
+        );
+        gateway_client.set_disabled_credentials_mode(config.get_disabled_credentials_mode());
+
+        let err = match gateway_client.authenticate_and_start().await {
+            Ok(_) => return Ok(gateway_client),
+            Err(err) => err,
+        };
+        let gateway_failover = match gateway_failover.as_deref_mut() {
+            Some(gateway_failover) => gateway_failover,
+            None => return Err(err.into()),
+        };
+
+        consecutive_failures += 1;
+        if gateway_failover
+            .should_fail_over(&gateway_identity, consecutive_failures)
+            .await
+        {
+            warn!("Could not connect to gateway {gateway_identity}: {err}. Trying the alternative gateways");
+            return gateway_failover.fail_over(&gateway_identity).await;
+        }
+
+        warn!("Could not connect to gateway {gateway_identity}: {err}. Retrying in {backoff:?}");
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_RECONNECTION_BACKOFF);
+    }
+}
+
+// persists the configuration changes implied by the client events before passing them on
+fn start_client_event_handler<C: ClientCoreConfigTrait>(
+    id: String,
+    mut event_receiver: ClientEventReceiver,
+    user_event_sender: ClientEventSender,
+) {
+    spawn_future(async move {
+        while let Some(event) = event_receiver.next().await {
+            match &event {
+                ClientEvent::GatewayChanged { gateway, address } => {
+                    info!("The address of this client has changed to: {address}");
+                    persist_gateway_endpoint::<C>(&id, gateway.clone())
+                }
+            }
+            // it's fine if nobody is interested in the events
+            let _ = user_event_sender.unbounded_send(event);
+        }
+    });
+}
+
+fn persist_gateway_endpoint<C: ClientCoreConfigTrait>(id: &str, gateway_endpoint: GatewayEndpoint) {
+    let mut config = match C::load_from_file(Some(id)) {
+        Ok(config) => config,
+        Err(err) => {
+            error!("Failed to load the config file to update the gateway: {err}");
+            return;
+        }
+    };
+    config
+        .get_base_mut()
+        .with_gateway_endpoint(gateway_endpoint);
+    if let Err(err) = config.save_to_file(None) {
+        error!("Failed to save the config file with the new gateway: {err}");
+    }
+}
+
+/// Updates the number of consecutive topology refreshes the gateway was missing from.
+fn count_topology_absence(
+    gateway: &NodeIdentity,
+    listed_gateways: &[gateway::Node],
+    missing_from_topology: usize,
+) -> usize {
+    // without any topology we can't really tell either way
+    if listed_gateways.is_empty() {
+        return missing_from_topology;
+    }
+
+    if listed_gateways
+        .iter()
+        .any(|listed| &listed.identity_key == gateway)
+    {
+        0
+    } else {
+        warn!("Our gateway {gateway} is not present in the current network topology");
+        missing_from_topology + 1
+    }
+}
+
+fn is_gateway_gone(
+    gateway: &NodeIdentity,
+    consecutive_failures: usize,
+    failure_threshold: usize,
+    missing_from_topology: usize,
+) -> bool {
+    if consecutive_failures >= failure_threshold {
+        return true;
+    }
+
+    if missing_from_topology >= MISSING_FROM_TOPOLOGY_THRESHOLD {
+        warn!("Our gateway {gateway} is no longer present in the network topology");
+        return true;
+    }
+    false
+}
+
+/// Orders the alternatives to the current gateway: the ranked gateways go first, in the order
+/// of the ranking, followed by the remaining gateways of the topology in random order.
+/// The current gateway and the ones that have already failed us are skipped, and so are
+/// the gateways missing from the topology (unless there's no topology at all).
+fn order_candidates(
+    ranked: Vec<GatewayEndpoint>,
+    listed_gateways: &[gateway::Node],
+    current_gateway: &str,
+    failed_gateways: &HashSet<String>,
+) -> Vec<GatewayEndpoint> {
+    let is_listed = |gateway_id: &str| {
+        listed_gateways.is_empty()
+            || listed_gateways
+                .iter()
+                .any(|listed| listed.identity_key.to_base58_string() == gateway_id)
+    };
+
+    let mut unranked: Vec<GatewayEndpoint> = listed_gateways
+        .iter()
+        .cloned()
+        .map(Into::into)
+        .filter(|endpoint: &GatewayEndpoint| {
+            !ranked
+                .iter()
+                .any(|ranked| ranked.gateway_id == endpoint.gateway_id)
+        })
+        .collect();
+    unranked.shuffle(&mut thread_rng());
+
+    ranked
+        .into_iter()
+        .chain(unranked.into_iter())
+        .filter(|endpoint| {
+            endpoint.gateway_id != current_gateway
+                && !failed_gateways.contains(&endpoint.gateway_id)
+                && is_listed(&endpoint.gateway_id)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::asymmetric::{encryption, identity};
+    use rand::rngs::OsRng;
+
+    fn node() -> gateway::Node {
+        let mut rng = OsRng;
+        gateway::Node {
+            owner: "n1owner".to_string(),
+            stake: 0,
+            location: "Neuchâtel, Switzerland".to_string(),
+            host: "1.2.3.4".parse().unwrap(),
+            mix_host: "1.2.3.4:1789".parse().unwrap(),
+            clients_port: 9000,
+            identity_key: *identity::KeyPair::new(&mut rng).public_key(),
+            sphinx_key: *encryption::KeyPair::new(&mut rng).public_key(),
+            pending_sphinx_key: None,
+            version: "1.1.0".to_string(),
+        }
+    }
+
+    fn ids(endpoints: &[GatewayEndpoint]) -> Vec<String> {
+        endpoints
+            .iter()
+            .map(|endpoint| endpoint.gateway_id.clone())
+            .collect()
+    }
+
+    #[test]
+    fn ranked_candidates_go_first_in_the_ranking_order() {
+        let listed: Vec<_> = (0..5).map(|_| node()).collect();
+        let ranked: Vec<GatewayEndpoint> = vec![listed[3].clone().into(), listed[1].clone().into()];
+
+        let candidates = order_candidates(ranked, &listed, "", &HashSet::new());
+        let candidates = ids(&candidates);
+        assert_eq!(candidates.len(), 5);
+        assert_eq!(candidates[0], listed[3].identity_key.to_base58_string());
+        assert_eq!(candidates[1], listed[1].identity_key.to_base58_string());
+
+        let mut unranked = candidates[2..].to_vec();
+        unranked.sort();
+        let mut expected: Vec<_> = [&listed[0], &listed[2], &listed[4]]
+            .iter()
+            .map(|node| node.identity_key.to_base58_string())
+            .collect();
+        expected.sort();
+        assert_eq!(unranked, expected);
+    }
+
+    #[test]
+    fn current_failed_and_unlisted_gateways_are_not_candidates() {
+        let listed: Vec<_> = (0..4).map(|_| node()).collect();
+        let unlisted = node();
+        let ranked: Vec<GatewayEndpoint> = vec![
+            unlisted.clone().into(),
+            listed[0].clone().into(),
+            listed[1].clone().into(),
+        ];
+        let current = listed[0].identity_key.to_base58_string();
+        let failed = [listed[2].identity_key.to_base58_string()]
+            .into_iter()
+            .collect();
+
+        let candidates = ids(&order_candidates(ranked, &listed, &current, &failed));
+        assert_eq!(
+            candidates,
+            vec![
+                listed[1].identity_key.to_base58_string(),
+                listed[3].identity_key.to_base58_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn ranked_candidates_are_used_without_topology() {
+        let ranked: Vec<GatewayEndpoint> = vec![node().into(), node().into()];
+
+        let candidates = order_candidates(ranked.clone(), &[], "", &HashSet::new());
+        assert_eq!(candidates, ranked);
+    }
+
+    #[test]
+    fn gateway_is_abandoned_after_too_many_failures() {
+        let gateway = node().identity_key;
+
+        assert!(!is_gateway_gone(&gateway, 0, 3, 0));
+        assert!(!is_gateway_gone(&gateway, 2, 3, 0));
+        assert!(is_gateway_gone(&gateway, 3, 3, 0));
+    }
+
+    #[test]
+    fn gateway_is_abandoned_once_it_is_missing_from_several_topologies() {
+        let gateway = node();
+        let without_gateway = [node()];
+        let with_gateway = [node(), gateway.clone()];
+
+        let mut missing = 0;
+        for _ in 1..MISSING_FROM_TOPOLOGY_THRESHOLD {
+            missing = count_topology_absence(&gateway.identity_key, &without_gateway, missing);
+            assert!(!is_gateway_gone(&gateway.identity_key, 0, 3, missing));
+        }
+
+        // a single topology listing the gateway again resets the count
+        missing = count_topology_absence(&gateway.identity_key, &with_gateway, missing);
+        assert_eq!(missing, 0);
+
+        for _ in 0..MISSING_FROM_TOPOLOGY_THRESHOLD {
+            missing = count_topology_absence(&gateway.identity_key, &without_gateway, missing);
+        }
+        assert!(is_gateway_gone(&gateway.identity_key, 0, 3, missing));
+    }
+
+    #[test]
+    fn empty_topology_does_not_affect_the_gateway_absence() {
+        let gateway = node().identity_key;
+
+        assert_eq!(count_topology_absence(&gateway, &[], 0), 0);
+        assert_eq!(count_topology_absence(&gateway, &[], 2), 2);
+        assert_eq!(count_topology_absence(&gateway, &[node()], 2), 3);
+    }
+}
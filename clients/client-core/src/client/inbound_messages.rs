@@ -1,6 +1,9 @@
+use crate::client::delivery_status::{DeliveryLimits, DeliveryRequestId};
+use crate::client::real_messages_control::QueueReservation;
 use futures::channel::mpsc;
 use nymsphinx::addressing::clients::Recipient;
 use nymsphinx::anonymous_replies::ReplySurb;
+use std::time::Duration;
 
 pub type InputMessageSender = mpsc::UnboundedSender<InputMessage>;
 pub type InputMessageReceiver = mpsc::UnboundedReceiver<InputMessage>;
@@ -11,10 +14,18 @@ pub enum InputMessage {
         recipient: Recipient,
         data: Vec<u8>,
         with_reply_surb: bool,
+        request_id: Option<DeliveryRequestId>,
+        limits: DeliveryLimits,
+
+        /// Packets of the message accounted for in the out queue length before the message
+        /// gets split into the actual packets.
+        queue_reservation: Option<QueueReservation>,
     },
     Reply {
         reply_surb: ReplySurb,
         data: Vec<u8>,
+        request_id: Option<DeliveryRequestId>,
+        limits: DeliveryLimits,
     },
 }
 
@@ -24,10 +35,63 @@ impl InputMessage {
             recipient,
             data,
             with_reply_surb,
+            request_id: None,
+            limits: Default::default(),
+            queue_reservation: None,
         }
     }
 
     pub fn new_reply(reply_surb: ReplySurb, data: Vec<u8>) -> Self {
-        InputMessage::Reply { reply_surb, data }
+        InputMessage::Reply {
+            reply_surb,
+            data,
+            request_id: None,
+            limits: Default::default(),
+        }
+    }
+
+    /// Requests [`DeliveryStatusUpdate`](crate::client::delivery_status::DeliveryStatusUpdate)s
+    /// about this message to be emitted under the provided id.
+    #[must_use]
+    pub fn with_request_id(mut self, id: DeliveryRequestId) -> Self {
+        match &mut self {
+            InputMessage::Fresh { request_id, .. } | InputMessage::Reply { request_id, .. } => {
+                *request_id = Some(id)
+            }
+        }
+        self
+    }
+
+    /// Keeps the reservation in the out queue until the message is split into packets.
+    #[must_use]
+    pub(crate) fn with_queue_reservation(mut self, reservation: QueueReservation) -> Self {
+        if let InputMessage::Fresh {
+            queue_reservation, ..
+        } = &mut self
+        {
+            *queue_reservation = Some(reservation)
+        }
+        self
+    }
+
+    fn limits_mut(&mut self) -> &mut DeliveryLimits {
+        match self {
+            InputMessage::Fresh { limits, .. } | InputMessage::Reply { limits, .. } => limits,
+        }
+    }
+
+    /// Abandons the message once any of its fragments had to be retransmitted more than
+    /// the specified number of times. Replies are never retransmitted.
+    #[must_use]
+    pub fn with_maximum_retransmissions(mut self, maximum: usize) -> Self {
+        self.limits_mut().maximum_retransmissions = Some(maximum);
+        self
+    }
+
+    /// Abandons the message if it has not been acknowledged within the specified time.
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.limits_mut().timeout = Some(timeout);
+        self
     }
 }
@@ -1,10 +1,15 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::client::gateway_failover::GatewayFailover;
 use crate::spawn_future;
+use gateway_client::error::GatewayClientError;
 use gateway_client::GatewayClient;
 use log::*;
 use nymsphinx::forwarding::packet::MixPacket;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Duration;
 
 pub type BatchMixMessageSender = tokio::sync::mpsc::Sender<Vec<MixPacket>>;
 pub type BatchMixMessageReceiver = tokio::sync::mpsc::Receiver<Vec<MixPacket>>;
@@ -13,6 +18,11 @@ pub type BatchMixMessageReceiver = tokio::sync::mpsc::Receiver<Vec<MixPacket>>;
 pub const MIX_MESSAGE_RECEIVER_BUFFER_SIZE: usize = 32;
 const MAX_FAILURE_COUNT: usize = 100;
 
+/// How often the controller checks whether the current gateway is still present
+/// in the network topology (if the gateway failover is enabled).
+#[cfg(not(target_arch = "wasm32"))]
+const GATEWAY_PRESENCE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
 pub struct MixTrafficController {
     // TODO: most likely to be replaced by some higher level construct as
     // later on gateway_client will need to be accessible by other entities
@@ -22,6 +32,10 @@ pub struct MixTrafficController {
     // TODO: this is temporary work-around.
     // in long run `gateway_client` will be moved away from `MixTrafficController` anyway.
     consecutive_gateway_failure_count: usize,
+
+    /// If set, the controller moves to a different gateway once the current one seems to be gone.
+    #[cfg(not(target_arch = "wasm32"))]
+    failover: Option<GatewayFailover>,
 }
 
 impl MixTrafficController {
@@ -33,27 +47,116 @@ impl MixTrafficController {
                 gateway_client,
                 mix_rx: sphinx_message_receiver,
                 consecutive_gateway_failure_count: 0,
+                #[cfg(not(target_arch = "wasm32"))]
+                failover: None,
             },
             sphinx_message_sender,
         )
     }
 
-    async fn on_messages(&mut self, mut mix_packets: Vec<MixPacket>) {
-        debug_assert!(!mix_packets.is_empty());
+    /// Allows the controller to move the client to a different gateway once the current
+    /// one seems to be gone.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[must_use]
+    pub fn with_failover(mut self, failover: GatewayFailover) -> Self {
+        self.failover = Some(failover);
+        self
+    }
+
+    /// Returns whether the client has moved to a different gateway.
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn try_fail_over(&mut self) -> bool {
+        let failover = match self.failover.as_mut() {
+            Some(failover) => failover,
+            None => return false,
+        };
+
+        let current_gateway = self.gateway_client.gateway_identity();
+        if !failover
+            .should_fail_over(&current_gateway, self.consecutive_gateway_failure_count)
+            .await
+        {
+            return false;
+        }
+
+        match failover.fail_over(&current_gateway).await {
+            Ok(gateway_client) => {
+                self.gateway_client = gateway_client;
+                self.consecutive_gateway_failure_count = 0;
+                true
+            }
+            Err(err) => {
+                error!("Failed to move to a different gateway: {err}");
+                false
+            }
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    async fn try_fail_over(&mut self) -> bool {
+        false
+    }
 
-        let result = if mix_packets.len() == 1 {
+    /// If the client might move to a different gateway, it keeps copies of the packets
+    /// being sent, so that they could be resent through the new gateway.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn retain_for_resending(&self, mix_packets: &[MixPacket]) -> Vec<Vec<u8>> {
+        if self.failover.is_none() {
+            return Vec::new();
+        }
+        mix_packets.iter().map(MixPacket::to_bytes).collect()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn retain_for_resending(&self, _mix_packets: &[MixPacket]) -> Vec<Vec<u8>> {
+        Vec::new()
+    }
+
+    async fn send(&mut self, mut mix_packets: Vec<MixPacket>) -> Result<(), GatewayClientError> {
+        if mix_packets.len() == 1 {
             let mix_packet = mix_packets.pop().unwrap();
             self.gateway_client.send_mix_packet(mix_packet).await
         } else {
             self.gateway_client
                 .batch_send_mix_packets(mix_packets)
                 .await
-        };
+        }
+    }
 
-        match result {
+    async fn resend(&mut self, retained: Vec<Vec<u8>>) {
+        let mix_packets: Vec<_> = retained
+            .iter()
+            .filter_map(|packet| MixPacket::try_from_bytes(packet).ok())
+            .collect();
+        if mix_packets.is_empty() {
+            return;
+        }
+
+        info!(
+            "Resending {} sphinx packet(s) through the new gateway",
+            mix_packets.len()
+        );
+        if let Err(err) = self.send(mix_packets).await {
+            error!(
+                "Failed to resend sphinx packet(s) through the new gateway - {:?}",
+                err
+            );
+            self.consecutive_gateway_failure_count += 1;
+        }
+    }
+
+    async fn on_messages(&mut self, mix_packets: Vec<MixPacket>) {
+        debug_assert!(!mix_packets.is_empty());
+
+        let retained = self.retain_for_resending(&mix_packets);
+        match self.send(mix_packets).await {
             Err(e) => {
                 error!("Failed to send sphinx packet(s) to the gateway! - {:?}", e);
                 self.consecutive_gateway_failure_count += 1;
+                if self.try_fail_over().await {
+                    self.resend(retained).await;
+                    return;
+                }
                 if self.consecutive_gateway_failure_count == MAX_FAILURE_COUNT {
                     // todo: in the future this should initiate a 'graceful' shutdown or try
                     // to reconnect?
@@ -72,6 +175,7 @@ impl MixTrafficController {
         spawn_future(async move {
             debug!("Started MixTrafficController with graceful shutdown support");
 
+            let mut presence_check = tokio::time::interval(GATEWAY_PRESENCE_CHECK_INTERVAL);
             while !shutdown.is_shutdown() {
                 tokio::select! {
                     mix_packets = self.mix_rx.recv() => match mix_packets {
@@ -83,6 +187,10 @@ impl MixTrafficController {
                             break;
                         }
                     },
+                    _ = presence_check.tick() => {
+                        // moves the client away from the gateway that's no longer in the topology
+                        self.try_fail_over().await;
+                    }
                     _ = shutdown.recv() => {
                         log::trace!("MixTrafficController: Received shutdown");
                     }
@@ -1,14 +1,25 @@
 use std::sync::atomic::AtomicBool;
 
+#[cfg(all(not(target_arch = "wasm32"), feature = "reply-surb"))]
+pub mod base_client;
 pub mod cover_traffic_stream;
+pub mod delivery_status;
+pub mod events;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod gateway_failover;
 pub mod inbound_messages;
 pub mod key_manager;
 pub mod mix_traffic;
+pub mod outbound_journal;
 pub mod real_messages_control;
 pub mod received_buffer;
+pub mod redirects;
 #[cfg(feature = "reply-surb")]
 pub mod reply_key_storage;
+pub mod self_address;
+pub mod streams;
 pub mod topology_control;
+pub mod topology_verification;
 
 // This is *NOT* used to signal shutdown.
 // It's critical that we don't have any tasks finishing early, this is an additional safety check
@@ -0,0 +1,365 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::client::delivery_status::DeliveryLimits;
+use crate::client::inbound_messages::InputMessage;
+use log::*;
+use nymsphinx::addressing::clients::Recipient;
+use std::convert::TryInto;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Identifier of a message stored in an [`OutboundJournal`].
+pub type JournalEntryId = u64;
+
+/// Storage for sent messages that have not yet been fully acknowledged, so that they could be
+/// sent again, through fresh routes, after the client restarts.
+///
+/// Only fresh messages are journaled - replies are bound to the route of their reply SURB,
+/// which can't be used again.
+pub trait OutboundJournal: Send + Sync {
+    /// Persists the message, returning the id under which it has been stored.
+    fn record(
+        &self,
+        recipient: &Recipient,
+        data: &[u8],
+        with_reply_surb: bool,
+        limits: DeliveryLimits,
+    ) -> io::Result<JournalEntryId>;
+
+    /// Removes the message once no further attempts are going to be made to deliver it.
+    fn remove(&self, entry: JournalEntryId) -> io::Result<()>;
+
+    /// Loads all stored messages, in the order they were recorded.
+    fn load(&self) -> io::Result<Vec<(JournalEntryId, InputMessage)>>;
+}
+
+/// Runs the blocking journal operation on the threads dedicated to blocking tasks, so that
+/// waiting for the disk would not stall the other tasks of the client.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn run_blocking<T, F>(operation: F) -> io::Result<T>
+where
+    F: FnOnce() -> io::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(operation)
+        .await
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn run_blocking<T, F>(operation: F) -> io::Result<T>
+where
+    F: FnOnce() -> io::Result<T>,
+{
+    operation()
+}
+
+/// Like [`run_blocking`], but without waiting for the operation to complete.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn spawn_blocking<F>(operation: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    tokio::task::spawn_blocking(operation);
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn spawn_blocking<F>(operation: F)
+where
+    F: FnOnce(),
+{
+    operation()
+}
+
+const PARTIAL_FILE_EXTENSION: &str = "partial";
+
+// stored in place of limits that were not set
+const NO_LIMIT: u64 = u64::MAX;
+
+const ENTRY_HEADER_LEN: usize = 1 + 2 * std::mem::size_of::<u64>() + Recipient::LEN;
+
+// WITH_REPLY_SURB || MAXIMUM_RETRANSMISSIONS || TIMEOUT_MILLIS || RECIPIENT || DATA
+fn encode_entry(
+    recipient: &Recipient,
+    data: &[u8],
+    with_reply_surb: bool,
+    limits: DeliveryLimits,
+) -> Vec<u8> {
+    let maximum_retransmissions = limits
+        .maximum_retransmissions
+        .map(|maximum| maximum as u64)
+        .unwrap_or(NO_LIMIT);
+    let timeout = limits
+        .timeout
+        .map(|timeout| timeout.as_millis() as u64)
+        .unwrap_or(NO_LIMIT);
+
+    std::iter::once(with_reply_surb as u8)
+        .chain(maximum_retransmissions.to_be_bytes().iter().cloned())
+        .chain(timeout.to_be_bytes().iter().cloned())
+        .chain(recipient.to_bytes().iter().cloned())
+        .chain(data.iter().cloned())
+        .collect()
+}
+
+fn decode_entry(bytes: &[u8]) -> Option<InputMessage> {
+    if bytes.len() < ENTRY_HEADER_LEN {
+        return None;
+    }
+
+    let with_reply_surb = match bytes[0] {
+        0 => false,
+        1 => true,
+        _ => return None,
+    };
+    let maximum_retransmissions = u64::from_be_bytes(bytes[1..9].try_into().unwrap());
+    let timeout = u64::from_be_bytes(bytes[9..17].try_into().unwrap());
+    let recipient =
+        Recipient::try_from_bytes(bytes[17..ENTRY_HEADER_LEN].try_into().unwrap()).ok()?;
+    let data = bytes[ENTRY_HEADER_LEN..].to_vec();
+
+    let mut message = InputMessage::new_fresh(recipient, data, with_reply_surb);
+    if maximum_retransmissions != NO_LIMIT {
+        message = message.with_maximum_retransmissions(maximum_retransmissions as usize);
+    }
+    if timeout != NO_LIMIT {
+        message = message.with_timeout(Duration::from_millis(timeout));
+    }
+    Some(message)
+}
+
+/// `OutboundJournal` keeping each message in a separate file named after its entry id,
+/// i.e. `<directory>/<entry_id>`.
+#[derive(Debug)]
+pub struct DiskOutboundJournal {
+    directory: PathBuf,
+    next_entry_id: AtomicU64,
+}
+
+impl DiskOutboundJournal {
+    /// Creates new instance of the journal, creating the directory if it does not exist yet.
+    pub fn new<P: Into<PathBuf>>(directory: P) -> io::Result<Self> {
+        let directory = directory.into();
+        fs::create_dir_all(&directory)?;
+
+        let journal = DiskOutboundJournal {
+            directory,
+            next_entry_id: AtomicU64::new(0),
+        };
+
+        // carry on numbering after the entries left over from the previous run
+        let next_entry_id = journal
+            .stored_entries()?
+            .last()
+            .map(|entry| entry + 1)
+            .unwrap_or_default();
+        journal
+            .next_entry_id
+            .store(next_entry_id, Ordering::Relaxed);
+
+        Ok(journal)
+    }
+
+    fn entry_path(&self, entry: JournalEntryId) -> PathBuf {
+        self.directory.join(entry.to_string())
+    }
+
+    // makes sure the creation, renaming or removal of the entries survives a crash
+    #[cfg(unix)]
+    fn sync_directory(&self) -> io::Result<()> {
+        File::open(&self.directory)?.sync_all()
+    }
+
+    // directories can't be opened for syncing on other platforms
+    #[cfg(not(unix))]
+    fn sync_directory(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    // returns sorted ids of all stored entries
+    fn stored_entries(&self) -> io::Result<Vec<JournalEntryId>> {
+        let mut entries = Vec::new();
+        for dir_entry in fs::read_dir(&self.directory)? {
+            let path = dir_entry?.path();
+            if path.extension().is_some() {
+                // leftover of an interrupted write
+                continue;
+            }
+
+            match path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map(str::parse)
+            {
+                Some(Ok(entry)) => entries.push(entry),
+                _ => warn!("unexpected entry {:?} in the outbound journal", path),
+            }
+        }
+
+        entries.sort_unstable();
+        Ok(entries)
+    }
+}
+
+impl OutboundJournal for DiskOutboundJournal {
+    fn record(
+        &self,
+        recipient: &Recipient,
+        data: &[u8],
+        with_reply_surb: bool,
+        limits: DeliveryLimits,
+    ) -> io::Result<JournalEntryId> {
+        let entry = self.next_entry_id.fetch_add(1, Ordering::Relaxed);
+
+        // write to a temporary file first so that we would never end up with a partially
+        // written message in case of a crash
+        let entry_path = self.entry_path(entry);
+        let partial_path = entry_path.with_extension(PARTIAL_FILE_EXTENSION);
+        let mut file = File::create(&partial_path)?;
+        file.write_all(&encode_entry(recipient, data, with_reply_surb, limits))?;
+        file.sync_all()?;
+        fs::rename(partial_path, entry_path)?;
+        self.sync_directory()?;
+
+        Ok(entry)
+    }
+
+    fn remove(&self, entry: JournalEntryId) -> io::Result<()> {
+        match fs::remove_file(self.entry_path(entry)) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err),
+            Ok(()) => self.sync_directory(),
+        }
+    }
+
+    fn load(&self) -> io::Result<Vec<(JournalEntryId, InputMessage)>> {
+        let mut messages = Vec::new();
+        for entry in self.stored_entries()? {
+            let entry_path = self.entry_path(entry);
+            match decode_entry(&fs::read(&entry_path)?) {
+                Some(message) => messages.push((entry, message)),
+                None => {
+                    warn!(
+                        "malformed outbound journal entry at {:?} - removing it",
+                        entry_path
+                    );
+                    fs::remove_file(entry_path)?;
+                }
+            }
+        }
+
+        Ok(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recipient() -> Recipient {
+        Recipient::try_from_base58_string("CytBseW6yFXUMzz4SGAKdNLGR7q3sJLLYxyBGvutNEQV.4QXYyEVc5fUDjmmi8PrHN9tdUFV4PCvSJE1278cHyvoe@4sBbL1ngf1vtNqykydQKTFh26sQCw888GpUqvPvyNB4f").unwrap()
+    }
+
+    fn assert_fresh(
+        message: &InputMessage,
+        expected_data: &[u8],
+        expected_with_reply_surb: bool,
+        expected_limits: DeliveryLimits,
+    ) {
+        match message {
+            InputMessage::Fresh {
+                recipient: stored_recipient,
+                data,
+                with_reply_surb,
+                request_id,
+                limits,
+                ..
+            } => {
+                assert_eq!(stored_recipient.to_string(), recipient().to_string());
+                assert_eq!(data.as_slice(), expected_data);
+                assert_eq!(*with_reply_surb, expected_with_reply_surb);
+                assert!(request_id.is_none());
+                assert_eq!(*limits, expected_limits);
+            }
+            _ => panic!("journaled message is not a fresh message"),
+        }
+    }
+
+    #[test]
+    fn recorded_messages_can_be_loaded_back_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal = DiskOutboundJournal::new(dir.path()).unwrap();
+
+        let limits = DeliveryLimits {
+            maximum_retransmissions: Some(3),
+            timeout: Some(Duration::from_secs(60)),
+        };
+        let first = journal
+            .record(&recipient(), b"foo", true, Default::default())
+            .unwrap();
+        let second = journal.record(&recipient(), b"bar", false, limits).unwrap();
+
+        let loaded = journal.load().unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].0, first);
+        assert_fresh(&loaded[0].1, b"foo", true, Default::default());
+        assert_eq!(loaded[1].0, second);
+        assert_fresh(&loaded[1].1, b"bar", false, limits);
+    }
+
+    #[test]
+    fn removed_messages_are_not_loaded() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal = DiskOutboundJournal::new(dir.path()).unwrap();
+
+        let first = journal
+            .record(&recipient(), b"foo", false, Default::default())
+            .unwrap();
+        let second = journal
+            .record(&recipient(), b"bar", false, Default::default())
+            .unwrap();
+        journal.remove(first).unwrap();
+        // removing it again is not an error
+        journal.remove(first).unwrap();
+
+        let loaded = journal.load().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].0, second);
+    }
+
+    #[test]
+    fn journal_survives_recreating_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal = DiskOutboundJournal::new(dir.path()).unwrap();
+        let first = journal
+            .record(&recipient(), b"foo", false, Default::default())
+            .unwrap();
+        drop(journal);
+
+        let journal = DiskOutboundJournal::new(dir.path()).unwrap();
+        let second = journal
+            .record(&recipient(), b"bar", false, Default::default())
+            .unwrap();
+        assert!(second > first);
+
+        let loaded = journal.load().unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_fresh(&loaded[0].1, b"foo", false, Default::default());
+        assert_fresh(&loaded[1].1, b"bar", false, Default::default());
+    }
+
+    #[test]
+    fn malformed_and_partial_entries_are_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("7"), b"foomp").unwrap();
+        fs::write(dir.path().join("8.partial"), b"foomp").unwrap();
+
+        let journal = DiskOutboundJournal::new(dir.path()).unwrap();
+        assert!(journal.load().unwrap().is_empty());
+        assert!(!dir.path().join("7").exists());
+    }
+}
@@ -44,16 +44,15 @@ impl AcknowledgementListener {
             }
         };
 
-        // if we received an ack for cover message or a reply there will be nothing to remove,
+        // if we received an ack for cover message there will be nothing to remove,
         // because nothing was inserted in the first place
         if frag_id == COVER_FRAG_ID {
             trace!("Received an ack for a cover message - no need to do anything");
             return;
         } else if frag_id.is_reply() {
-            info!("Received an ack for a reply message - no need to do anything! (don't know what to do!)");
-            // TODO: probably there will need to be some extra procedure here, something to notify
-            // user that his reply reached the recipient (since we got an ack)
-            return;
+            // there's nothing pending for replies, but the controller might be tracking
+            // their delivery status
+            debug!("Received an ack for a reply message");
         }
 
         trace!("Received {} from the mix network", frag_id);
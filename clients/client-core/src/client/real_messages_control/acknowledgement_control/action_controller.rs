@@ -1,7 +1,12 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use super::message_tracker::{MessageId, MessageTracker};
 use super::PendingAcknowledgement;
+use crate::client::delivery_status::{
+    DeliveryLimits, DeliveryRequestId, DeliveryStatus, DeliveryStatusSender,
+};
+use crate::client::outbound_journal::{JournalEntryId, OutboundJournal};
 use crate::client::real_messages_control::acknowledgement_control::RetransmissionRequestSender;
 use futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
 use futures::StreamExt;
@@ -15,6 +20,11 @@ use std::time::Duration;
 
 pub(crate) type ActionSender = UnboundedSender<Action>;
 
+/// Deadline of the replies whose delivery is tracked if neither the reply nor the client
+/// specified one. Acknowledgements of replies are the only signal of their delivery and they
+/// might never arrive, so their tracking can't be open-ended.
+pub(crate) const DEFAULT_REPLY_DEADLINE: Duration = Duration::from_secs(5 * 60);
+
 // The actual data being sent off as well as potential key to the delay queue
 type PendingAckEntry = (Arc<PendingAcknowledgement>, Option<QueueKey>);
 
@@ -24,9 +34,19 @@ type PendingAckEntry = (Arc<PendingAcknowledgement>, Option<QueueKey>);
 // - start a retransmission timer for sending the packet into the network (on either first try or retransmission)
 // - update the internal sphinx delay of an expired packet
 pub(crate) enum Action {
-    /// Inserts new `PendingAcknowledgement`s into the 'shared' state.
+    /// Inserts new `PendingAcknowledgement`s, all belonging to the same message, into the 'shared' state.
+    /// Initiated by `InputMessageListener`
+    InsertPending(
+        Vec<PendingAcknowledgement>,
+        Option<DeliveryRequestId>,
+        DeliveryLimits,
+        Option<JournalEntryId>,
+    ),
+
+    /// Starts tracking delivery of a reply, which, unlike other messages, is never retransmitted.
+    /// The tracking always has a deadline, `DEFAULT_REPLY_DEADLINE` unless specified otherwise.
     /// Initiated by `InputMessageListener`
-    InsertPending(Vec<PendingAcknowledgement>),
+    TrackReply(FragmentIdentifier, DeliveryRequestId, DeliveryLimits),
 
     /// Removes given `PendingAcknowledgement` from the 'shared' state. Also cancels the retransmission timer.
     /// Initiated by `AcknowledgementListener`
@@ -44,8 +64,21 @@ pub(crate) enum Action {
 }
 
 impl Action {
-    pub(crate) fn new_insert(pending_acks: Vec<PendingAcknowledgement>) -> Self {
-        Action::InsertPending(pending_acks)
+    pub(crate) fn new_insert(
+        pending_acks: Vec<PendingAcknowledgement>,
+        request_id: Option<DeliveryRequestId>,
+        limits: DeliveryLimits,
+        journal_entry: Option<JournalEntryId>,
+    ) -> Self {
+        Action::InsertPending(pending_acks, request_id, limits, journal_entry)
+    }
+
+    pub(crate) fn new_track_reply(
+        reply_id: FragmentIdentifier,
+        request_id: DeliveryRequestId,
+        limits: DeliveryLimits,
+    ) -> Self {
+        Action::TrackReply(reply_id, request_id, limits)
     }
 
     pub(crate) fn new_remove(frag_id: FragmentIdentifier) -> Self {
@@ -68,6 +101,14 @@ pub(super) struct Config {
 
     /// Given ack timeout in the form a * BASE_DELAY + b, it specifies the multiplier `a`
     ack_wait_multiplier: f64,
+
+    /// If set, specifies the number of times a fragment is retransmitted before the whole
+    /// message it belongs to is abandoned, unless the message specifies its own limit.
+    maximum_retransmissions: Option<usize>,
+
+    /// If set, specifies the amount of time after which a message that still has not been
+    /// acknowledged is abandoned, unless the message specifies its own timeout.
+    message_timeout: Option<Duration>,
 }
 
 impl Config {
@@ -75,8 +116,29 @@ impl Config {
         Config {
             ack_wait_addition,
             ack_wait_multiplier,
+            maximum_retransmissions: None,
+            message_timeout: None,
         }
     }
+
+    pub(super) fn with_maximum_retransmissions(mut self, maximum: Option<usize>) -> Self {
+        self.maximum_retransmissions = maximum;
+        self
+    }
+
+    pub(super) fn with_message_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.message_timeout = timeout;
+        self
+    }
+}
+
+/// Acknowledgement progress of an erasure coded `FragmentSet`.
+struct ErasureCodedSet {
+    /// Number of fragments the recipient needs to receive in order to reconstruct the set.
+    required: usize,
+
+    /// Number of fragments of the set that got acknowledged so far.
+    acknowledged: usize,
 }
 
 pub(super) struct ActionController {
@@ -94,6 +156,17 @@ pub(super) struct ActionController {
     /// retransmitted if their timer fires up.
     pending_acks_timers: NonExhaustiveDelayQueue<FragmentIdentifier>,
 
+    /// Sets that were sent alongside parity fragments. Once enough of their fragments got
+    /// acknowledged, the remaining ones are redundant and are no longer retransmitted.
+    erasure_coded_sets: HashMap<i32, ErasureCodedSet>,
+
+    /// Keeps track of which fragments belong to which message and reports their delivery status.
+    message_tracker: MessageTracker,
+
+    /// DelayQueue with deadlines of the tracked messages. The entries are not removed once
+    /// the message gets acknowledged, instead they are ignored when they fire.
+    message_deadlines: NonExhaustiveDelayQueue<MessageId>,
+
     /// Channel for receiving `Action`s from other modules.
     incoming_actions: UnboundedReceiver<Action>,
 
@@ -105,6 +178,8 @@ impl ActionController {
     pub(super) fn new(
         config: Config,
         retransmission_sender: RetransmissionRequestSender,
+        delivery_status_sender: Option<DeliveryStatusSender>,
+        outbound_journal: Option<Arc<dyn OutboundJournal>>,
     ) -> (Self, ActionSender) {
         let (sender, receiver) = mpsc::unbounded();
         (
@@ -112,6 +187,9 @@ impl ActionController {
                 config,
                 pending_acks_data: HashMap::new(),
                 pending_acks_timers: NonExhaustiveDelayQueue::new(),
+                erasure_coded_sets: HashMap::new(),
+                message_tracker: MessageTracker::new(delivery_status_sender, outbound_journal),
+                message_deadlines: NonExhaustiveDelayQueue::new(),
                 incoming_actions: receiver,
                 retransmission_sender,
             },
@@ -119,11 +197,33 @@ impl ActionController {
         )
     }
 
-    fn handle_insert(&mut self, pending_acks: Vec<PendingAcknowledgement>) {
+    fn handle_insert(
+        &mut self,
+        pending_acks: Vec<PendingAcknowledgement>,
+        request_id: Option<DeliveryRequestId>,
+        limits: DeliveryLimits,
+        journal_entry: Option<JournalEntryId>,
+    ) {
+        let fragments: Vec<_> = pending_acks
+            .iter()
+            .map(|pending_ack| pending_ack.message_chunk.fragment_identifier())
+            .collect();
+        let timeout = limits.timeout.or(self.config.message_timeout);
+        self.start_tracking(request_id, limits, timeout, journal_entry, fragments);
+
         for pending_ack in pending_acks {
             let frag_id = pending_ack.message_chunk.fragment_identifier();
             trace!("{} is inserted", frag_id);
 
+            if pending_ack.message_chunk.is_parity() {
+                self.erasure_coded_sets
+                    .entry(frag_id.set_id())
+                    .or_insert_with(|| ErasureCodedSet {
+                        required: pending_ack.message_chunk.total_fragments() as usize,
+                        acknowledged: 0,
+                    });
+            }
+
             if self
                 .pending_acks_data
                 .insert(frag_id, (Arc::new(pending_ack), None))
@@ -134,8 +234,56 @@ impl ActionController {
         }
     }
 
+    fn start_tracking(
+        &mut self,
+        request_id: Option<DeliveryRequestId>,
+        limits: DeliveryLimits,
+        timeout: Option<Duration>,
+        journal_entry: Option<JournalEntryId>,
+        fragments: Vec<FragmentIdentifier>,
+    ) {
+        let maximum_retransmissions = limits
+            .maximum_retransmissions
+            .or(self.config.maximum_retransmissions);
+        let message_id = self.message_tracker.insert_message(
+            request_id,
+            maximum_retransmissions,
+            journal_entry,
+            fragments,
+        );
+
+        if let (Some(message_id), Some(timeout)) = (message_id, timeout) {
+            self.message_deadlines.insert(message_id, timeout);
+        }
+    }
+
+    fn handle_track_reply(
+        &mut self,
+        reply_id: FragmentIdentifier,
+        request_id: DeliveryRequestId,
+        limits: DeliveryLimits,
+    ) {
+        trace!("{} is going to be tracked", reply_id);
+        let timeout = limits
+            .timeout
+            .or(self.config.message_timeout)
+            .unwrap_or(DEFAULT_REPLY_DEADLINE);
+        self.start_tracking(
+            Some(request_id),
+            limits,
+            Some(timeout),
+            None,
+            vec![reply_id],
+        );
+    }
+
     fn handle_start_timer(&mut self, frag_id: FragmentIdentifier) {
         trace!("{} is starting its timer", frag_id);
+        self.message_tracker.fragment_sent(frag_id);
+        if frag_id.is_reply() {
+            // replies are never retransmitted, so there's no timer to start
+            return;
+        }
 
         if let Some((pending_ack_data, queue_key)) = self.pending_acks_data.get_mut(&frag_id) {
             if queue_key.is_some() {
@@ -159,6 +307,11 @@ impl ActionController {
 
     fn handle_remove(&mut self, frag_id: FragmentIdentifier) {
         trace!("{} is getting removed", frag_id);
+        self.message_tracker.fragment_acknowledged(frag_id);
+        if frag_id.is_reply() {
+            // replies never had any pending acks to begin with
+            return;
+        }
 
         match self.pending_acks_data.remove(&frag_id) {
             None => {
@@ -182,10 +335,89 @@ impl ActionController {
                         frag_id
                     );
                 }
+                self.handle_acknowledged_erasure_coded_fragment(frag_id.set_id());
             }
         }
     }
 
+    // if the set was erasure coded, the recipient is able to reconstruct it from any `k` of its
+    // fragments, so once that many got acknowledged, there's no point in retransmitting the rest
+    fn handle_acknowledged_erasure_coded_fragment(&mut self, set_id: i32) {
+        let set = match self.erasure_coded_sets.get_mut(&set_id) {
+            Some(set) => set,
+            None => return,
+        };
+        set.acknowledged += 1;
+        if set.acknowledged < set.required {
+            return;
+        }
+        self.erasure_coded_sets.remove(&set_id);
+
+        let redundant: Vec<_> = self
+            .pending_acks_data
+            .keys()
+            .filter(|frag_id| frag_id.set_id() == set_id)
+            .copied()
+            .collect();
+
+        if !redundant.is_empty() {
+            debug!(
+                "set {} can already be reconstructed by the recipient - not waiting for the remaining {} acks",
+                set_id,
+                redundant.len()
+            );
+        }
+
+        for frag_id in redundant {
+            self.message_tracker.fragment_acknowledged(frag_id);
+            if let Some((_, Some(queue_key))) = self.pending_acks_data.remove(&frag_id) {
+                self.pending_acks_timers.remove(&queue_key);
+            }
+        }
+    }
+
+    // drops pending acks of an abandoned message, so that none of them is ever retransmitted again
+    fn drop_pending_acks(&mut self, abandoned: Vec<FragmentIdentifier>) {
+        for frag_id in abandoned {
+            self.erasure_coded_sets.remove(&frag_id.set_id());
+            if let Some((_, Some(queue_key))) = self.pending_acks_data.remove(&frag_id) {
+                self.pending_acks_timers.remove(&queue_key);
+            }
+        }
+    }
+
+    // the fragment could not be delivered despite all the retransmissions, so there's no point
+    // in trying to deliver any other part of the message it belongs to
+    fn handle_exhausted_retransmissions(&mut self, frag_id: FragmentIdentifier) {
+        warn!(
+            "{} has not been acknowledged despite being retransmitted the maximum number of times. Giving up on the whole message",
+            frag_id
+        );
+        let mut abandoned = self
+            .message_tracker
+            .abandon(frag_id, DeliveryStatus::GaveUp);
+        // make sure the fragment itself is gone even if the message was somehow no longer tracked
+        abandoned.push(frag_id);
+        self.drop_pending_acks(abandoned);
+    }
+
+    // note: when the entry expires it's automatically removed from message_deadlines
+    fn handle_expired_message_deadline(&mut self, expired_deadline: Expired<MessageId>) {
+        let message_id = expired_deadline.into_inner();
+        trace!("deadline of message {} has passed", message_id);
+
+        let abandoned = self
+            .message_tracker
+            .abandon_message(message_id, DeliveryStatus::Expired);
+        if !abandoned.is_empty() {
+            warn!(
+                "{} fragments of a message have not been acknowledged before its deadline. Abandoning it",
+                abandoned.len()
+            );
+            self.drop_pending_acks(abandoned);
+        }
+    }
+
     // initiated basically as a first step of retransmission. At first data has its delay updated
     // (as new sphinx packet was created with new expected delivery time)
     fn handle_update_delay(&mut self, frag_id: FragmentIdentifier, delay: SphinxDelay) {
@@ -197,6 +429,7 @@ impl ActionController {
             // was dropped hence this unwrap is safe.
             let mut inner_data = Arc::try_unwrap(pending_ack_data).unwrap();
             inner_data.update_delay(delay);
+            inner_data.retransmissions += 1;
 
             self.pending_acks_data
                 .insert(frag_id, (Arc::new(inner_data), queue_key));
@@ -224,6 +457,14 @@ impl ActionController {
                 panic!("Ack expired before it was even scheduled!")
             }
             *queue_key = None;
+
+            if let Some(maximum) = self.message_tracker.retransmission_limit(frag_id) {
+                if pending_ack_data.retransmissions >= maximum {
+                    self.handle_exhausted_retransmissions(frag_id);
+                    return;
+                }
+            }
+
             // downgrading an arc and then upgrading vs cloning is difference of 30ns vs 15ns
             // so it's literally a NO difference while it might prevent us from unnecessarily
             // resending data (in maybe 1 in 1 million cases, but it's something)
@@ -238,7 +479,12 @@ impl ActionController {
 
     fn process_action(&mut self, action: Action) {
         match action {
-            Action::InsertPending(pending_acks) => self.handle_insert(pending_acks),
+            Action::InsertPending(pending_acks, request_id, limits, journal_entry) => {
+                self.handle_insert(pending_acks, request_id, limits, journal_entry)
+            }
+            Action::TrackReply(reply_id, request_id, limits) => {
+                self.handle_track_reply(reply_id, request_id, limits)
+            }
             Action::RemovePending(frag_id) => self.handle_remove(frag_id),
             Action::StartTimer(frag_id) => self.handle_start_timer(frag_id),
             Action::UpdateDelay(frag_id, delay) => self.handle_update_delay(frag_id, delay),
@@ -267,6 +513,13 @@ impl ActionController {
                         break;
                     }
                 },
+                expired_deadline = self.message_deadlines.next() => match expired_deadline {
+                    Some(expired_deadline) => self.handle_expired_message_deadline(expired_deadline),
+                    None => {
+                        log::trace!("ActionController: Stopping since deadline channel closed");
+                        break;
+                    }
+                },
                 _ = shutdown.recv() => {
                     log::trace!("ActionController: Received shutdown");
                 }
@@ -283,8 +536,202 @@ impl ActionController {
         loop {
             tokio::select! {
                 action = self.incoming_actions.next() => self.process_action(action.unwrap()),
-                expired_ack = self.pending_acks_timers.next() => self.handle_expired_ack_timer(expired_ack.unwrap())
+                expired_ack = self.pending_acks_timers.next() => self.handle_expired_ack_timer(expired_ack.unwrap()),
+                expired_deadline = self.message_deadlines.next() => self.handle_expired_message_deadline(expired_deadline.unwrap())
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::delivery_status::{DeliveryStatusReceiver, DeliveryStatusUpdate};
+    use crate::client::real_messages_control::acknowledgement_control::RetransmissionRequestReceiver;
+    use nymsphinx::addressing::clients::Recipient;
+    use nymsphinx::chunking::split_into_sets;
+    use rand::rngs::OsRng;
+
+    fn test_controller(
+        config: Config,
+    ) -> (
+        ActionController,
+        RetransmissionRequestReceiver,
+        DeliveryStatusReceiver,
+    ) {
+        let (retransmission_sender, retransmission_receiver) = mpsc::unbounded();
+        let (status_sender, status_receiver) = mpsc::unbounded();
+        let (controller, _) =
+            ActionController::new(config, retransmission_sender, Some(status_sender), None);
+        (controller, retransmission_receiver, status_receiver)
+    }
+
+    fn pending_acks(message_len: usize) -> Vec<PendingAcknowledgement> {
+        let recipient = Recipient::try_from_base58_string("CytBseW6yFXUMzz4SGAKdNLGR7q3sJLLYxyBGvutNEQV.4QXYyEVc5fUDjmmi8PrHN9tdUFV4PCvSJE1278cHyvoe@4sBbL1ngf1vtNqykydQKTFh26sQCw888GpUqvPvyNB4f").unwrap();
+
+        split_into_sets(&mut OsRng, &vec![42; message_len], 100)
+            .into_iter()
+            .flatten()
+            .map(|fragment| {
+                PendingAcknowledgement::new(fragment, SphinxDelay::new_from_nanos(1000), recipient)
+            })
+            .collect()
+    }
+
+    fn limits(maximum_retransmissions: Option<usize>, timeout: Option<Duration>) -> DeliveryLimits {
+        DeliveryLimits {
+            maximum_retransmissions,
+            timeout,
+        }
+    }
+
+    // inserts the message and pretends all of its fragments got sent
+    fn send_message(
+        controller: &mut ActionController,
+        pending_acks: Vec<PendingAcknowledgement>,
+        request_id: DeliveryRequestId,
+        limits: DeliveryLimits,
+    ) -> Vec<FragmentIdentifier> {
+        let fragments: Vec<_> = pending_acks
+            .iter()
+            .map(|pending_ack| pending_ack.message_chunk.fragment_identifier())
+            .collect();
+
+        controller.process_action(Action::new_insert(
+            pending_acks,
+            Some(request_id),
+            limits,
+            None,
+        ));
+        for frag_id in &fragments {
+            controller.process_action(Action::new_start_timer(*frag_id));
+        }
+        fragments
+    }
+
+    fn received_updates(receiver: &mut DeliveryStatusReceiver) -> Vec<DeliveryStatusUpdate> {
+        let mut updates = Vec::new();
+        while let Ok(Some(update)) = receiver.try_next() {
+            updates.push(update)
+        }
+        updates
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unacknowledged_message_expires_at_its_deadline() {
+        let config = Config::new(Duration::from_secs(60), 1.0);
+        let (mut controller, _, mut status_receiver) = test_controller(config);
+
+        let expiring = send_message(
+            &mut controller,
+            pending_acks(250),
+            1,
+            limits(None, Some(Duration::from_secs(10))),
+        );
+        assert!(expiring.len() > 1);
+        let acknowledged = send_message(
+            &mut controller,
+            pending_acks(10),
+            2,
+            limits(None, Some(Duration::from_secs(5))),
+        );
+
+        controller.process_action(Action::new_remove(expiring[0]));
+        controller.process_action(Action::new_remove(acknowledged[0]));
+        assert_eq!(
+            received_updates(&mut status_receiver),
+            vec![
+                DeliveryStatusUpdate::new(1, DeliveryStatus::Sent),
+                DeliveryStatusUpdate::new(2, DeliveryStatus::Sent),
+                DeliveryStatusUpdate::new(2, DeliveryStatus::Acknowledged),
+            ]
+        );
+
+        // deadline of the acknowledged message has no effect
+        let deadline = controller.message_deadlines.next().await.unwrap();
+        controller.handle_expired_message_deadline(deadline);
+        assert!(received_updates(&mut status_receiver).is_empty());
+        assert_eq!(controller.pending_acks_data.len(), expiring.len() - 1);
+
+        let deadline = controller.message_deadlines.next().await.unwrap();
+        controller.handle_expired_message_deadline(deadline);
+        assert_eq!(
+            received_updates(&mut status_receiver),
+            vec![DeliveryStatusUpdate::new(1, DeliveryStatus::Expired)]
+        );
+        assert!(controller.pending_acks_data.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unacknowledged_reply_expires_at_the_default_deadline() {
+        let config = Config::new(Duration::from_secs(60), 1.0);
+        let (mut controller, _, mut status_receiver) = test_controller(config);
+
+        let reply_id = FragmentIdentifier::new_reply(&mut OsRng);
+        controller.process_action(Action::new_track_reply(reply_id, 1, limits(None, None)));
+        controller.process_action(Action::new_start_timer(reply_id));
+        assert_eq!(
+            received_updates(&mut status_receiver),
+            vec![DeliveryStatusUpdate::new(1, DeliveryStatus::Sent)]
+        );
+
+        let before = tokio::time::Instant::now();
+        let deadline = controller.message_deadlines.next().await.unwrap();
+        assert!(before.elapsed() >= DEFAULT_REPLY_DEADLINE);
+        controller.handle_expired_message_deadline(deadline);
+        assert_eq!(
+            received_updates(&mut status_receiver),
+            vec![DeliveryStatusUpdate::new(1, DeliveryStatus::Expired)]
+        );
+
+        // a late acknowledgement no longer has any effect
+        controller.process_action(Action::new_remove(reply_id));
+        assert!(received_updates(&mut status_receiver).is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn message_is_abandoned_after_maximum_retransmissions() {
+        // the client-wide limit is overridden by the message
+        let config = Config::new(Duration::from_secs(1), 1.0).with_maximum_retransmissions(Some(5));
+        let (mut controller, mut retransmission_receiver, mut status_receiver) =
+            test_controller(config);
+
+        let fragments = send_message(&mut controller, pending_acks(150), 1, limits(Some(1), None));
+        assert_eq!(fragments.len(), 2);
+
+        // both fragments time out and are requested to be retransmitted
+        for _ in 0..2 {
+            let expired = controller.pending_acks_timers.next().await.unwrap();
+            controller.handle_expired_ack_timer(expired);
+        }
+        let retransmitted = retransmission_receiver.try_next().unwrap().unwrap();
+        let ignored = retransmission_receiver.try_next().unwrap().unwrap();
+
+        // but only one of them actually gets retransmitted
+        let frag_id = retransmitted
+            .upgrade()
+            .unwrap()
+            .message_chunk
+            .fragment_identifier();
+        drop(retransmitted);
+        controller.process_action(Action::new_update_delay(
+            frag_id,
+            SphinxDelay::new_from_nanos(1000),
+        ));
+        controller.process_action(Action::new_start_timer(frag_id));
+
+        // and when it times out again, the whole message is abandoned
+        let expired = controller.pending_acks_timers.next().await.unwrap();
+        controller.handle_expired_ack_timer(expired);
+        assert!(retransmission_receiver.try_next().is_err());
+        assert!(ignored.upgrade().is_none());
+        assert!(controller.pending_acks_data.is_empty());
+        assert_eq!(
+            received_updates(&mut status_receiver),
+            vec![
+                DeliveryStatusUpdate::new(1, DeliveryStatus::Sent),
+                DeliveryStatusUpdate::new(1, DeliveryStatus::GaveUp),
+            ]
+        );
+    }
+}
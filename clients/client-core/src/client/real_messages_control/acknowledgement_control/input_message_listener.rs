@@ -4,21 +4,40 @@
 use super::action_controller::{Action, ActionSender};
 use super::PendingAcknowledgement;
 use crate::client::{
+    delivery_status::{DeliveryLimits, DeliveryRequestId},
     inbound_messages::{InputMessage, InputMessageReceiver},
+    outbound_journal::{run_blocking, JournalEntryId, OutboundJournal},
     real_messages_control::real_traffic_stream::{BatchRealMessageSender, RealMessage},
+    redirects::{encode_redirect_notice, is_redirect_expired, KnownRedirects},
+    self_address::SelfAddress,
     topology_control::TopologyAccessor,
 };
 use futures::StreamExt;
+use gateway_client::GatewayRedirect;
 use log::*;
 use nymsphinx::anonymous_replies::ReplySurb;
 use nymsphinx::preparer::MessagePreparer;
 use nymsphinx::{acknowledgements::AckKey, addressing::clients::Recipient};
 use rand::{CryptoRng, Rng};
+use std::collections::HashSet;
 use std::sync::Arc;
 
 #[cfg(feature = "reply-surb")]
 use crate::client::reply_key_storage::ReplyKeyStorage;
 
+// how often the journaled messages that couldn't be sent due to invalid topology are retried
+#[cfg(not(target_arch = "wasm32"))]
+const TOPOLOGY_RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+// kind of the fresh message to be sent through the mix network
+#[derive(Debug, Clone, Copy)]
+enum FreshMessageKind {
+    // message meant for the application of the recipient
+    Application { with_reply_surb: bool },
+    // message meant for the client of the recipient itself, such as the redirect notice
+    Control,
+}
+
 /// Module responsible for dealing with the received messages: splitting them, creating acknowledgements,
 /// putting everything into sphinx packets, etc.
 /// It also makes an initial sending attempt for said messages.
@@ -27,12 +46,19 @@ where
     R: CryptoRng + Rng,
 {
     ack_key: Arc<AckKey>,
-    ack_recipient: Recipient,
+    ack_recipient: SelfAddress,
     input_receiver: InputMessageReceiver,
     message_preparer: MessagePreparer<R>,
     action_sender: ActionSender,
     real_message_sender: BatchRealMessageSender,
     topology_access: TopologyAccessor,
+    journal: Option<Arc<dyn OutboundJournal>>,
+    // journaled messages that couldn't be sent since the network topology was invalid
+    awaiting_topology: Vec<(JournalEntryId, InputMessage)>,
+    known_redirects: KnownRedirects,
+    own_redirect: Option<GatewayRedirect>,
+    // identities of the recipients that were already sent the redirect record of this client
+    notified_recipients: HashSet<[u8; 32]>,
     #[cfg(feature = "reply-surb")]
     reply_key_storage: ReplyKeyStorage,
 }
@@ -46,12 +72,13 @@ where
     #[allow(clippy::too_many_arguments)]
     pub(super) fn new(
         ack_key: Arc<AckKey>,
-        ack_recipient: Recipient,
+        ack_recipient: SelfAddress,
         input_receiver: InputMessageReceiver,
         message_preparer: MessagePreparer<R>,
         action_sender: ActionSender,
         real_message_sender: BatchRealMessageSender,
         topology_access: TopologyAccessor,
+        journal: Option<Arc<dyn OutboundJournal>>,
         #[cfg(feature = "reply-surb")] reply_key_storage: ReplyKeyStorage,
     ) -> Self {
         InputMessageListener {
@@ -62,15 +89,144 @@ where
             action_sender,
             real_message_sender,
             topology_access,
+            journal,
+            awaiting_topology: Vec::new(),
+            known_redirects: Default::default(),
+            own_redirect: None,
+            notified_recipients: HashSet::new(),
             #[cfg(feature = "reply-surb")]
             reply_key_storage,
         }
     }
 
+    pub(super) fn with_redirects(
+        mut self,
+        known_redirects: KnownRedirects,
+        own_redirect: Option<GatewayRedirect>,
+    ) -> Self {
+        self.known_redirects = known_redirects;
+        self.own_redirect = own_redirect;
+        self
+    }
+
+    // the first message to any recipient is preceded by the redirect record of this client
+    // (if it has migrated and the record has not expired yet), so that the recipient could
+    // reach it at its new gateway
+    async fn notify_about_redirect(&mut self, recipient: Recipient) {
+        let redirect = match &self.own_redirect {
+            Some(redirect) => redirect,
+            None => return,
+        };
+        if is_redirect_expired(redirect) {
+            info!("The redirect record of this client has expired and is no longer announced");
+            self.own_redirect = None;
+            self.notified_recipients.clear();
+            return;
+        }
+        if recipient.identity() == redirect.client_identity()
+            || !self
+                .notified_recipients
+                .insert(recipient.identity().to_bytes())
+        {
+            return;
+        }
+
+        let notice = encode_redirect_notice(redirect);
+        if let Some(real_messages) = self
+            .handle_fresh_message(
+                recipient,
+                notice,
+                FreshMessageKind::Control,
+                None,
+                Default::default(),
+                None,
+            )
+            .await
+        {
+            self.real_message_sender
+                .unbounded_send(real_messages)
+                .unwrap();
+        } else {
+            // try again with the next message
+            self.notified_recipients
+                .remove(&recipient.identity().to_bytes());
+        }
+    }
+
+    async fn record_in_journal(&self, msg: &InputMessage) -> Option<JournalEntryId> {
+        let journal = Arc::clone(self.journal.as_ref()?);
+        match msg {
+            InputMessage::Fresh {
+                recipient,
+                data,
+                with_reply_surb,
+                limits,
+                ..
+            } => {
+                let (recipient, data, with_reply_surb, limits) =
+                    (*recipient, data.clone(), *with_reply_surb, *limits);
+                let recorded = run_blocking(move || {
+                    journal.record(&recipient, &data, with_reply_surb, limits)
+                })
+                .await;
+                match recorded {
+                    Ok(entry) => Some(entry),
+                    Err(err) => {
+                        warn!(
+                            "failed to record the message in the outbound journal - {}. It won't be resent after a restart",
+                            err
+                        );
+                        None
+                    }
+                }
+            }
+            // replies are bound to their reply surbs and can't be resent through fresh routes
+            InputMessage::Reply { .. } => None,
+        }
+    }
+
+    // resends, through fresh routes, all messages that were not acknowledged before the client
+    // got shut down
+    async fn replay_journal(&mut self) {
+        let journal = match &self.journal {
+            Some(journal) => Arc::clone(journal),
+            None => return,
+        };
+        let messages = match run_blocking(move || journal.load()).await {
+            Ok(messages) => messages,
+            Err(err) => {
+                error!("failed to load the outbound journal - {}", err);
+                return;
+            }
+        };
+
+        if !messages.is_empty() {
+            info!(
+                "resending {} messages that were not acknowledged before the last shutdown",
+                messages.len()
+            );
+        }
+        for (entry, message) in messages {
+            self.process_input_message(message, Some(entry)).await;
+        }
+    }
+
     // we require topology for replies to generate surb_acks
-    async fn handle_reply(&mut self, reply_surb: ReplySurb, data: Vec<u8>) -> Option<RealMessage> {
+    async fn handle_reply(
+        &mut self,
+        reply_surb: ReplySurb,
+        data: Vec<u8>,
+        request_id: Option<DeliveryRequestId>,
+        limits: DeliveryLimits,
+    ) -> Option<RealMessage> {
+        let ack_recipient = self.ack_recipient.get();
+        self.message_preparer.set_sender_address(ack_recipient);
         let topology_permit = self.topology_access.get_read_permit().await;
-        let topology = match topology_permit.try_get_valid_topology_ref(&self.ack_recipient, None) {
+        let topology = match topology_permit.try_get_valid_topology_ref(
+            &ack_recipient,
+            None,
+            self.message_preparer.num_mix_hops(),
+        ) {
             Some(topology_ref) => topology_ref,
             None => {
                 warn!("Could not process the message - the network topology is invalid");
@@ -84,9 +240,12 @@ where
             .await
         {
             Ok((mix_packet, reply_id)) => {
-                // TODO: later probably write pending ack here
-                // and deal with them....
-                // ... somehow
+                // replies are not retransmitted, but we can still report whether they got delivered
+                if let Some(request_id) = request_id {
+                    self.action_sender
+                        .unbounded_send(Action::new_track_reply(reply_id, request_id, limits))
+                        .unwrap();
+                }
                 Some(RealMessage::new(mix_packet, reply_id))
             }
             Err(err) => {
@@ -102,24 +261,52 @@ where
         &mut self,
         recipient: Recipient,
         content: Vec<u8>,
-        with_reply_surb: bool,
+        kind: FreshMessageKind,
+        request_id: Option<DeliveryRequestId>,
+        limits: DeliveryLimits,
+        journal_entry: Option<JournalEntryId>,
     ) -> Option<Vec<RealMessage>> {
+        let ack_recipient = self.ack_recipient.get();
+        self.message_preparer.set_sender_address(ack_recipient);
         let topology_permit = self.topology_access.get_read_permit().await;
-        let topology = match topology_permit
-            .try_get_valid_topology_ref(&self.ack_recipient, Some(&recipient))
-        {
+        let topology = match topology_permit.try_get_valid_topology_ref(
+            &ack_recipient,
+            Some(&recipient),
+            self.message_preparer.num_mix_hops(),
+        ) {
             Some(topology_ref) => topology_ref,
             None => {
                 warn!("Could not process the message - the network topology is invalid");
+                // the journaled messages are retried once the topology recovers
+                if let (Some(journal_entry), FreshMessageKind::Application { with_reply_surb }) =
+                    (journal_entry, kind)
+                {
+                    let message = InputMessage::Fresh {
+                        recipient,
+                        data: content,
+                        with_reply_surb,
+                        request_id,
+                        limits,
+                        queue_reservation: None,
+                    };
+                    self.awaiting_topology.push((journal_entry, message));
+                }
                 return None;
             }
         };
 
         // split the message, attach optional reply surb
-        let (split_message, reply_key) = self
-            .message_preparer
-            .prepare_and_split_message(content, with_reply_surb, topology)
-            .expect("somehow the topology was invalid after all!");
+        let (split_message, reply_key) = match kind {
+            FreshMessageKind::Application { with_reply_surb } => self
+                .message_preparer
+                .prepare_and_split_message(content, with_reply_surb, topology)
+                .expect("somehow the topology was invalid after all!"),
+            FreshMessageKind::Control => (
+                self.message_preparer
+                    .prepare_and_split_control_message(content),
+                None,
+            ),
+        };
 
         #[cfg(feature = "reply-surb")]
         if let Some(reply_key) = reply_key {
@@ -157,24 +344,58 @@ where
 
         // tells the controller to put this into the hashmap
         self.action_sender
-            .unbounded_send(Action::new_insert(pending_acks))
+            .unbounded_send(Action::new_insert(
+                pending_acks,
+                request_id,
+                limits,
+                journal_entry,
+            ))
             .unwrap();
 
         Some(real_messages)
     }
 
     async fn on_input_message(&mut self, msg: InputMessage) {
+        let journal_entry = self.record_in_journal(&msg).await;
+        self.process_input_message(msg, journal_entry).await
+    }
+
+    async fn process_input_message(
+        &mut self,
+        msg: InputMessage,
+        journal_entry: Option<JournalEntryId>,
+    ) {
+        // the reservation (if any) is released only once the actual packets are queued
+        let mut _queue_reservation = None;
         let real_messages = match msg {
             InputMessage::Fresh {
                 recipient,
                 data,
                 with_reply_surb,
+                request_id,
+                limits,
+                queue_reservation,
             } => {
-                self.handle_fresh_message(recipient, data, with_reply_surb)
-                    .await
+                _queue_reservation = queue_reservation;
+                let recipient = self.known_redirects.update_recipient(recipient);
+                self.notify_about_redirect(recipient).await;
+                self.handle_fresh_message(
+                    recipient,
+                    data,
+                    FreshMessageKind::Application { with_reply_surb },
+                    request_id,
+                    limits,
+                    journal_entry,
+                )
+                .await
             }
-            InputMessage::Reply { reply_surb, data } => self
-                .handle_reply(reply_surb, data)
+            InputMessage::Reply {
+                reply_surb,
+                data,
+                request_id,
+                limits,
+            } => self
+                .handle_reply(reply_surb, data, request_id, limits)
                 .await
                 .map(|message| vec![message]),
         };
@@ -188,12 +409,25 @@ where
         }
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn retry_awaiting_topology(&mut self) {
+        // anything that still can't be sent is going to end up back in the list
+        for (entry, message) in std::mem::take(&mut self.awaiting_topology) {
+            self.process_input_message(message, Some(entry)).await;
+        }
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     pub(super) async fn run_with_shutdown(&mut self, mut shutdown: task::ShutdownListener) {
         debug!("Started InputMessageListener with graceful shutdown support");
+        self.replay_journal().await;
 
+        let mut topology_retry = tokio::time::interval(TOPOLOGY_RETRY_INTERVAL);
         while !shutdown.is_shutdown() {
             tokio::select! {
+                _ = topology_retry.tick(), if !self.awaiting_topology.is_empty() => {
+                    self.retry_awaiting_topology().await;
+                },
                 input_msg = self.input_receiver.next() => match input_msg {
                     Some(input_msg) => {
                         self.on_input_message(input_msg).await;
@@ -215,6 +449,7 @@ where
     #[cfg(target_arch = "wasm32")]
     pub(super) async fn run(&mut self) {
         debug!("Started InputMessageListener without graceful shutdown support");
+        self.replay_journal().await;
         while let Some(input_msg) = self.input_receiver.next().await {
             self.on_input_message(input_msg).await;
         }
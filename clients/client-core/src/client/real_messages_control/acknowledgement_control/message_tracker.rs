@@ -0,0 +1,372 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::client::delivery_status::{
+    DeliveryRequestId, DeliveryStatus, DeliveryStatusSender, DeliveryStatusUpdate,
+};
+use crate::client::outbound_journal::{spawn_blocking, JournalEntryId, OutboundJournal};
+use log::*;
+use nymsphinx::chunking::fragment::FragmentIdentifier;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+pub(super) type MessageId = u64;
+
+/// Fragments of a single sent message that are still in flight.
+struct TrackedMessage {
+    request_id: Option<DeliveryRequestId>,
+
+    /// Number of times any fragment of the message can be retransmitted
+    /// before the message is abandoned.
+    maximum_retransmissions: Option<usize>,
+
+    /// Entry of the message in the outbound journal, if it has been journaled.
+    journal_entry: Option<JournalEntryId>,
+
+    /// Fragments that have not yet been sent to the mix network even once.
+    unsent: HashSet<FragmentIdentifier>,
+
+    /// Fragments that are still waiting for their acknowledgements.
+    unacknowledged: HashSet<FragmentIdentifier>,
+}
+
+/// Groups `PendingAcknowledgement`s by the message they belong to, so that the progress of the
+/// message as a whole could be reported back to the application and so that the whole message
+/// could be abandoned once any of its fragments could not be delivered.
+pub(super) struct MessageTracker {
+    next_message_id: MessageId,
+    messages: HashMap<MessageId, TrackedMessage>,
+    fragments: HashMap<FragmentIdentifier, MessageId>,
+
+    /// Channel for notifying the application about the progress of messages it attached
+    /// request ids to.
+    status_sender: Option<DeliveryStatusSender>,
+
+    /// Journal the messages are removed from once no further attempts to deliver them are made.
+    journal: Option<Arc<dyn OutboundJournal>>,
+}
+
+impl MessageTracker {
+    pub(super) fn new(
+        status_sender: Option<DeliveryStatusSender>,
+        journal: Option<Arc<dyn OutboundJournal>>,
+    ) -> Self {
+        MessageTracker {
+            next_message_id: 0,
+            messages: HashMap::new(),
+            fragments: HashMap::new(),
+            status_sender,
+            journal,
+        }
+    }
+
+    fn remove_from_journal(&self, journal_entry: Option<JournalEntryId>) {
+        if let (Some(journal_entry), Some(journal)) = (journal_entry, &self.journal) {
+            let journal = Arc::clone(journal);
+            spawn_blocking(move || {
+                if let Err(err) = journal.remove(journal_entry) {
+                    warn!(
+                        "failed to remove message {} from the outbound journal - {}",
+                        journal_entry, err
+                    )
+                }
+            })
+        }
+    }
+
+    fn notify(&self, request_id: Option<DeliveryRequestId>, status: DeliveryStatus) {
+        if let (Some(request_id), Some(status_sender)) = (request_id, &self.status_sender) {
+            trace!(
+                "message {} has changed its status to {:?}",
+                request_id,
+                status
+            );
+            if status_sender
+                .unbounded_send(DeliveryStatusUpdate::new(request_id, status))
+                .is_err()
+            {
+                debug!("Nobody is listening for the delivery status updates");
+            }
+        }
+    }
+
+    /// Starts tracking a message consisting of the provided fragments.
+    pub(super) fn insert_message(
+        &mut self,
+        request_id: Option<DeliveryRequestId>,
+        maximum_retransmissions: Option<usize>,
+        journal_entry: Option<JournalEntryId>,
+        fragments: Vec<FragmentIdentifier>,
+    ) -> Option<MessageId> {
+        if fragments.is_empty() {
+            // there's nothing left to deliver
+            self.remove_from_journal(journal_entry);
+            return None;
+        }
+
+        let message_id = self.next_message_id;
+        self.next_message_id += 1;
+
+        for frag_id in &fragments {
+            self.fragments.insert(*frag_id, message_id);
+        }
+        self.messages.insert(
+            message_id,
+            TrackedMessage {
+                request_id,
+                maximum_retransmissions,
+                journal_entry,
+                unsent: fragments.iter().copied().collect(),
+                unacknowledged: fragments.into_iter().collect(),
+            },
+        );
+
+        Some(message_id)
+    }
+
+    /// Returns the number of times the fragment can be retransmitted, if there's any limit.
+    pub(super) fn retransmission_limit(&self, frag_id: FragmentIdentifier) -> Option<usize> {
+        self.fragments
+            .get(&frag_id)
+            .and_then(|message_id| self.messages.get(message_id))
+            .and_then(|message| message.maximum_retransmissions)
+    }
+
+    /// Marks the fragment as having been sent to the mix network.
+    pub(super) fn fragment_sent(&mut self, frag_id: FragmentIdentifier) {
+        let message = match self
+            .fragments
+            .get(&frag_id)
+            .and_then(|message_id| self.messages.get_mut(message_id))
+        {
+            Some(message) => message,
+            None => return,
+        };
+
+        // we only care about the first time the fragment was sent, not about its retransmissions
+        if message.unsent.remove(&frag_id) && message.unsent.is_empty() {
+            let request_id = message.request_id;
+            self.notify(request_id, DeliveryStatus::Sent)
+        }
+    }
+
+    /// Marks the fragment as no longer requiring acknowledgement, either because it has
+    /// been acknowledged or because the recipient no longer needs it.
+    pub(super) fn fragment_acknowledged(&mut self, frag_id: FragmentIdentifier) {
+        let message_id = match self.fragments.remove(&frag_id) {
+            Some(message_id) => message_id,
+            None => return,
+        };
+        let message = match self.messages.get_mut(&message_id) {
+            Some(message) => message,
+            None => return,
+        };
+
+        let request_id = message.request_id;
+        let all_sent = message.unsent.remove(&frag_id) && message.unsent.is_empty();
+        message.unacknowledged.remove(&frag_id);
+        let all_acknowledged = message.unacknowledged.is_empty();
+
+        if all_sent {
+            self.notify(request_id, DeliveryStatus::Sent)
+        }
+        if all_acknowledged {
+            if let Some(message) = self.messages.remove(&message_id) {
+                self.remove_from_journal(message.journal_entry);
+            }
+            self.notify(request_id, DeliveryStatus::Acknowledged)
+        }
+    }
+
+    /// Stops tracking the message the fragment belongs to and returns all of its fragments
+    /// that are still waiting for acknowledgements. If the message has already been
+    /// acknowledged (or abandoned), nothing is returned.
+    pub(super) fn abandon(
+        &mut self,
+        frag_id: FragmentIdentifier,
+        status: DeliveryStatus,
+    ) -> Vec<FragmentIdentifier> {
+        match self.fragments.get(&frag_id) {
+            Some(message_id) => self.abandon_message(*message_id, status),
+            None => Vec::new(),
+        }
+    }
+
+    /// Stops tracking the message and returns all of its fragments that are still waiting
+    /// for acknowledgements. If the message has already been acknowledged (or abandoned),
+    /// nothing is returned.
+    pub(super) fn abandon_message(
+        &mut self,
+        message_id: MessageId,
+        status: DeliveryStatus,
+    ) -> Vec<FragmentIdentifier> {
+        debug_assert!(status.is_final() && status != DeliveryStatus::Acknowledged);
+
+        let message = match self.messages.remove(&message_id) {
+            Some(message) => message,
+            None => return Vec::new(),
+        };
+
+        for pending in &message.unacknowledged {
+            self.fragments.remove(pending);
+        }
+        // the message is not going to be retried after a restart either
+        self.remove_from_journal(message.journal_entry);
+        self.notify(message.request_id, status);
+
+        message.unacknowledged.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::delivery_status::{DeliveryLimits, DeliveryStatusReceiver};
+    use crate::client::inbound_messages::InputMessage;
+    use futures::channel::mpsc;
+    use futures::StreamExt;
+    use nymsphinx::addressing::clients::Recipient;
+    use std::io;
+    use std::sync::Mutex;
+
+    struct RemovalRecordingJournal {
+        removed: Mutex<mpsc::UnboundedSender<JournalEntryId>>,
+    }
+
+    impl OutboundJournal for RemovalRecordingJournal {
+        fn record(
+            &self,
+            _: &Recipient,
+            _: &[u8],
+            _: bool,
+            _: DeliveryLimits,
+        ) -> io::Result<JournalEntryId> {
+            unimplemented!()
+        }
+
+        fn remove(&self, entry: JournalEntryId) -> io::Result<()> {
+            self.removed.lock().unwrap().unbounded_send(entry).unwrap();
+            Ok(())
+        }
+
+        fn load(&self) -> io::Result<Vec<(JournalEntryId, InputMessage)>> {
+            Ok(Vec::new())
+        }
+    }
+
+    fn frag_id(set_id: i32, position: u8) -> FragmentIdentifier {
+        let set_id_bytes = set_id.to_be_bytes();
+        FragmentIdentifier::try_from_bytes([
+            set_id_bytes[0],
+            set_id_bytes[1],
+            set_id_bytes[2],
+            set_id_bytes[3],
+            position,
+        ])
+        .unwrap()
+    }
+
+    fn fragments(set_id: i32, count: u8) -> Vec<FragmentIdentifier> {
+        (1..=count)
+            .map(|position| frag_id(set_id, position))
+            .collect()
+    }
+
+    fn received_updates(receiver: &mut DeliveryStatusReceiver) -> Vec<DeliveryStatusUpdate> {
+        let mut updates = Vec::new();
+        while let Ok(Some(update)) = receiver.try_next() {
+            updates.push(update)
+        }
+        updates
+    }
+
+    #[test]
+    fn reports_progress_of_the_whole_message() {
+        let (sender, mut receiver) = mpsc::unbounded();
+        let mut tracker = MessageTracker::new(Some(sender), None);
+        let message = fragments(1, 3);
+        tracker.insert_message(Some(42), None, None, message.clone());
+        // messages without request ids are tracked, but never reported
+        tracker.insert_message(None, None, None, fragments(2, 1));
+
+        tracker.fragment_sent(message[0]);
+        tracker.fragment_sent(message[1]);
+        tracker.fragment_acknowledged(message[0]);
+        tracker.fragment_sent(frag_id(2, 1));
+        assert!(received_updates(&mut receiver).is_empty());
+
+        tracker.fragment_sent(message[2]);
+        // retransmissions do not change anything
+        tracker.fragment_sent(message[2]);
+        assert_eq!(
+            received_updates(&mut receiver),
+            vec![DeliveryStatusUpdate::new(42, DeliveryStatus::Sent)]
+        );
+
+        tracker.fragment_acknowledged(message[1]);
+        tracker.fragment_acknowledged(message[2]);
+        // duplicate acks are ignored
+        tracker.fragment_acknowledged(message[2]);
+        assert_eq!(
+            received_updates(&mut receiver),
+            vec![DeliveryStatusUpdate::new(42, DeliveryStatus::Acknowledged)]
+        );
+    }
+
+    #[test]
+    fn giving_up_abandons_all_remaining_fragments() {
+        let (sender, mut receiver) = mpsc::unbounded();
+        let mut tracker = MessageTracker::new(Some(sender), None);
+        let message = fragments(1, 3);
+        tracker.insert_message(Some(42), Some(3), None, message.clone());
+        assert_eq!(tracker.retransmission_limit(message[1]), Some(3));
+
+        tracker.fragment_sent(message[0]);
+        tracker.fragment_acknowledged(message[0]);
+
+        let mut abandoned = tracker.abandon(message[1], DeliveryStatus::GaveUp);
+        abandoned.sort_by_key(|frag_id| frag_id.to_bytes());
+        assert_eq!(abandoned, vec![message[1], message[2]]);
+        assert_eq!(
+            received_updates(&mut receiver),
+            vec![DeliveryStatusUpdate::new(42, DeliveryStatus::GaveUp)]
+        );
+
+        // late acks of the abandoned message are ignored
+        tracker.fragment_acknowledged(message[2]);
+        assert!(received_updates(&mut receiver).is_empty());
+        assert!(tracker
+            .abandon(message[2], DeliveryStatus::Expired)
+            .is_empty());
+        assert!(tracker.retransmission_limit(message[1]).is_none());
+    }
+
+    #[tokio::test]
+    async fn finished_messages_are_removed_from_the_journal() {
+        let (removed_sender, mut removed) = mpsc::unbounded();
+        let journal = Arc::new(RemovalRecordingJournal {
+            removed: Mutex::new(removed_sender),
+        });
+        let mut tracker = MessageTracker::new(None, Some(journal));
+        let acknowledged = fragments(1, 2);
+        let abandoned = fragments(2, 2);
+        tracker.insert_message(None, None, Some(7), acknowledged.clone());
+        tracker.insert_message(None, None, Some(8), abandoned.clone());
+        // messages that were not journaled are never removed from it
+        tracker.insert_message(None, None, None, fragments(3, 1));
+
+        tracker.fragment_acknowledged(acknowledged[0]);
+        tracker.fragment_acknowledged(abandoned[0]);
+
+        // the removals happen in the background
+        tracker.fragment_acknowledged(acknowledged[1]);
+        assert_eq!(removed.next().await, Some(7));
+
+        tracker.abandon(abandoned[1], DeliveryStatus::Expired);
+        tracker.abandon(frag_id(3, 1), DeliveryStatus::GaveUp);
+        assert_eq!(removed.next().await, Some(8));
+
+        drop(tracker);
+        assert!(removed.next().await.is_none());
+    }
+}
@@ -8,12 +8,16 @@ use self::{
     sent_notification_listener::SentNotificationListener,
 };
 use super::real_traffic_stream::BatchRealMessageSender;
-use crate::client::{inbound_messages::InputMessageReceiver, topology_control::TopologyAccessor};
+use crate::client::{
+    delivery_status::DeliveryStatusSender, inbound_messages::InputMessageReceiver,
+    outbound_journal::OutboundJournal, redirects::KnownRedirects, self_address::SelfAddress,
+    topology_control::TopologyAccessor,
+};
 use crate::spawn_future;
 use futures::channel::mpsc;
-use gateway_client::AcknowledgementReceiver;
+use gateway_client::{AcknowledgementReceiver, GatewayRedirect};
 use log::*;
-use nymsphinx::params::PacketSize;
+use nymsphinx::params::{PacketSize, DEFAULT_NUM_MIX_HOPS};
 use nymsphinx::{
     acknowledgements::AckKey,
     addressing::clients::Recipient,
@@ -33,6 +37,7 @@ use crate::client::reply_key_storage::ReplyKeyStorage;
 mod acknowledgement_listener;
 mod action_controller;
 mod input_message_listener;
+mod message_tracker;
 mod retransmission_request_listener;
 mod sent_notification_listener;
 
@@ -56,6 +61,9 @@ pub(crate) struct PendingAcknowledgement {
     message_chunk: Fragment,
     delay: SphinxDelay,
     recipient: Recipient,
+
+    /// Number of times the `Fragment` has been retransmitted so far.
+    retransmissions: usize,
 }
 
 impl PendingAcknowledgement {
@@ -65,6 +73,7 @@ impl PendingAcknowledgement {
             message_chunk,
             delay,
             recipient,
+            retransmissions: 0,
         }
     }
 
@@ -91,6 +100,9 @@ pub(super) struct AcknowledgementControllerConnectors {
 
     /// Channel used for receiving acknowledgements from the mix network.
     ack_receiver: AcknowledgementReceiver,
+
+    /// Channel used for notifying the application about the delivery status of its messages.
+    delivery_status_sender: Option<DeliveryStatusSender>,
 }
 
 impl AcknowledgementControllerConnectors {
@@ -99,12 +111,14 @@ impl AcknowledgementControllerConnectors {
         input_receiver: InputMessageReceiver,
         sent_notifier: SentPacketNotificationReceiver,
         ack_receiver: AcknowledgementReceiver,
+        delivery_status_sender: Option<DeliveryStatusSender>,
     ) -> Self {
         AcknowledgementControllerConnectors {
             real_message_sender,
             input_receiver,
             sent_notifier,
             ack_receiver,
+            delivery_status_sender,
         }
     }
 }
@@ -125,6 +139,31 @@ pub(super) struct Config {
 
     /// Predefined packet size used for the encapsulated messages.
     packet_size: PacketSize,
+
+    /// Number of mix hops each sent packet is going to go through.
+    num_mix_hops: u8,
+
+    /// If set, the sent messages are erasure coded with the specified ratio of parity
+    /// to data fragments.
+    erasure_coding_redundancy: Option<f64>,
+
+    /// If set, specifies the number of times a fragment is retransmitted before the whole
+    /// message it belongs to is abandoned.
+    maximum_retransmissions: Option<usize>,
+
+    /// If set, specifies the amount of time after which a message that still has not been
+    /// acknowledged is abandoned.
+    message_timeout: Option<Duration>,
+
+    /// If set, the fresh messages are kept in the journal until they're either acknowledged
+    /// or abandoned, so that they could be resent after a restart.
+    outbound_journal: Option<Arc<dyn OutboundJournal>>,
+
+    /// Redirect records of other clients, used to update the addresses of the recipients.
+    known_redirects: KnownRedirects,
+
+    /// If set, the redirect record of this client is announced to all of its recipients.
+    own_redirect: Option<GatewayRedirect>,
 }
 
 impl Config {
@@ -140,6 +179,13 @@ impl Config {
             average_ack_delay,
             average_packet_delay,
             packet_size: Default::default(),
+            num_mix_hops: DEFAULT_NUM_MIX_HOPS,
+            erasure_coding_redundancy: None,
+            maximum_retransmissions: None,
+            message_timeout: None,
+            outbound_journal: None,
+            known_redirects: Default::default(),
+            own_redirect: None,
         }
     }
 
@@ -147,6 +193,41 @@ impl Config {
         self.packet_size = packet_size;
         self
     }
+
+    pub fn with_mix_hops(mut self, hops: u8) -> Self {
+        self.num_mix_hops = hops;
+        self
+    }
+
+    pub fn with_erasure_coding(mut self, redundancy: Option<f64>) -> Self {
+        self.erasure_coding_redundancy = redundancy;
+        self
+    }
+
+    pub fn with_maximum_retransmissions(mut self, maximum: Option<usize>) -> Self {
+        self.maximum_retransmissions = maximum;
+        self
+    }
+
+    pub fn with_message_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.message_timeout = timeout;
+        self
+    }
+
+    pub fn with_outbound_journal(mut self, journal: Option<Arc<dyn OutboundJournal>>) -> Self {
+        self.outbound_journal = journal;
+        self
+    }
+
+    pub fn with_redirects(
+        mut self,
+        known_redirects: KnownRedirects,
+        own_redirect: Option<GatewayRedirect>,
+    ) -> Self {
+        self.known_redirects = known_redirects;
+        self.own_redirect = own_redirect;
+        self
+    }
 }
 
 pub(super) struct AcknowledgementController<R>
@@ -170,24 +251,34 @@ where
         rng: R,
         topology_access: TopologyAccessor,
         ack_key: Arc<AckKey>,
-        ack_recipient: Recipient,
+        ack_recipient: SelfAddress,
         connectors: AcknowledgementControllerConnectors,
         #[cfg(feature = "reply-surb")] reply_key_storage: ReplyKeyStorage,
     ) -> Self {
         let (retransmission_tx, retransmission_rx) = mpsc::unbounded();
 
         let action_config =
-            action_controller::Config::new(config.ack_wait_addition, config.ack_wait_multiplier);
-        let (action_controller, action_sender) =
-            ActionController::new(action_config, retransmission_tx);
+            action_controller::Config::new(config.ack_wait_addition, config.ack_wait_multiplier)
+                .with_maximum_retransmissions(config.maximum_retransmissions)
+                .with_message_timeout(config.message_timeout);
+        let (action_controller, action_sender) = ActionController::new(
+            action_config,
+            retransmission_tx,
+            connectors.delivery_status_sender,
+            config.outbound_journal.clone(),
+        );
 
-        let message_preparer = MessagePreparer::new(
+        let mut message_preparer = MessagePreparer::new(
             rng,
-            ack_recipient,
+            ack_recipient.get(),
             config.average_packet_delay,
             config.average_ack_delay,
         )
-        .with_custom_real_message_packet_size(config.packet_size);
+        .with_custom_real_message_packet_size(config.packet_size)
+        .with_mix_hops(config.num_mix_hops);
+        if let Some(redundancy) = config.erasure_coding_redundancy {
+            message_preparer = message_preparer.with_erasure_coding(redundancy);
+        }
 
         // will listen for any acks coming from the network
         let acknowledgement_listener = AcknowledgementListener::new(
@@ -199,15 +290,17 @@ where
         // will listen for any new messages from the client
         let input_message_listener = InputMessageListener::new(
             Arc::clone(&ack_key),
-            ack_recipient,
+            ack_recipient.clone(),
             connectors.input_receiver,
             message_preparer.clone(),
             action_sender.clone(),
             connectors.real_message_sender.clone(),
             topology_access.clone(),
+            config.outbound_journal,
             #[cfg(feature = "reply-surb")]
             reply_key_storage,
-        );
+        )
+        .with_redirects(config.known_redirects, config.own_redirect);
 
         // will listen for any ack timeouts and trigger retransmission
         let retransmission_request_listener = RetransmissionRequestListener::new(
@@ -6,12 +6,13 @@ use super::PendingAcknowledgement;
 use super::RetransmissionRequestReceiver;
 use crate::client::{
     real_messages_control::real_traffic_stream::{BatchRealMessageSender, RealMessage},
+    self_address::SelfAddress,
     topology_control::TopologyAccessor,
 };
 use futures::StreamExt;
 use log::*;
+use nymsphinx::acknowledgements::AckKey;
 use nymsphinx::preparer::MessagePreparer;
-use nymsphinx::{acknowledgements::AckKey, addressing::clients::Recipient};
 use rand::{CryptoRng, Rng};
 use std::sync::{Arc, Weak};
 
@@ -21,7 +22,7 @@ where
     R: CryptoRng + Rng,
 {
     ack_key: Arc<AckKey>,
-    ack_recipient: Recipient,
+    ack_recipient: SelfAddress,
     message_preparer: MessagePreparer<R>,
     action_sender: ActionSender,
     real_message_sender: BatchRealMessageSender,
@@ -36,7 +37,7 @@ where
     #[allow(clippy::too_many_arguments)]
     pub(super) fn new(
         ack_key: Arc<AckKey>,
-        ack_recipient: Recipient,
+        ack_recipient: SelfAddress,
         message_preparer: MessagePreparer<R>,
         action_sender: ActionSender,
         real_message_sender: BatchRealMessageSender,
@@ -66,10 +67,14 @@ where
         let chunk_clone = timed_out_ack.message_chunk.clone();
         let frag_id = chunk_clone.fragment_identifier();
 
+        let ack_recipient = self.ack_recipient.get();
+        self.message_preparer.set_sender_address(ack_recipient);
         let topology_permit = self.topology_access.get_read_permit().await;
-        let topology_ref = match topology_permit
-            .try_get_valid_topology_ref(&self.ack_recipient, Some(packet_recipient))
-        {
+        let topology_ref = match topology_permit.try_get_valid_topology_ref(
+            &ack_recipient,
+            Some(packet_recipient),
+            self.message_preparer.num_mix_hops(),
+        ) {
             Some(topology_ref) => topology_ref,
             None => {
                 warn!("Could not retransmit the packet - the network topology is invalid");
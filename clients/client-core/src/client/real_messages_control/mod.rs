@@ -6,20 +6,21 @@
 // OUTPUT: MixMessage to mix traffic
 
 use self::{
-    acknowledgement_control::AcknowledgementController, real_traffic_stream::OutQueueControl,
+    acknowledgement_control::AcknowledgementController,
+    real_traffic_stream::{BatchRealMessageSender, OutQueueControl},
 };
 use crate::client::real_messages_control::acknowledgement_control::AcknowledgementControllerConnectors;
 use crate::client::{
-    inbound_messages::InputMessageReceiver, mix_traffic::BatchMixMessageSender,
-    topology_control::TopologyAccessor,
+    delivery_status::DeliveryStatusSender, inbound_messages::InputMessageReceiver,
+    mix_traffic::BatchMixMessageSender, outbound_journal::OutboundJournal,
+    redirects::KnownRedirects, self_address::SelfAddress, topology_control::TopologyAccessor,
 };
 use crate::spawn_future;
 use futures::channel::mpsc;
-use gateway_client::AcknowledgementReceiver;
+use gateway_client::{AcknowledgementReceiver, GatewayRedirect};
 use log::*;
 use nymsphinx::acknowledgements::AckKey;
-use nymsphinx::addressing::clients::Recipient;
-use nymsphinx::params::PacketSize;
+use nymsphinx::params::{PacketSize, DEFAULT_NUM_MIX_HOPS};
 use rand::{rngs::OsRng, CryptoRng, Rng};
 use std::sync::Arc;
 use std::time::Duration;
@@ -28,8 +29,11 @@ use std::time::Duration;
 use crate::client::reply_key_storage::ReplyKeyStorage;
 
 mod acknowledgement_control;
+mod out_queue_length;
 mod real_traffic_stream;
 
+pub use out_queue_length::{OutQueueLength, QueueReservation};
+
 // TODO: ack_key and self_recipient shouldn't really be part of this config
 pub struct Config {
     /// Key used to decrypt contents of received SURBAcks
@@ -42,7 +46,7 @@ pub struct Config {
     ack_wait_multiplier: f64,
 
     /// Address of `this` client.
-    self_recipient: Recipient,
+    self_recipient: SelfAddress,
 
     /// Average delay between sending subsequent packets from this client.
     average_message_sending_delay: Duration,
@@ -59,6 +63,36 @@ pub struct Config {
 
     /// Predefined packet size used for the encapsulated messages.
     packet_size: PacketSize,
+
+    /// Number of mix hops each sent packet is going to go through.
+    num_mix_hops: u8,
+
+    /// If set, the sent messages are erasure coded with the specified ratio of parity
+    /// to data fragments.
+    erasure_coding_redundancy: Option<f64>,
+
+    /// If set, specifies the number of times a fragment is retransmitted before the whole
+    /// message it belongs to is abandoned.
+    maximum_retransmissions: Option<usize>,
+
+    /// If set, specifies the amount of time after which a message that still has not been
+    /// acknowledged is abandoned.
+    message_timeout: Option<Duration>,
+
+    /// If set, the delivery status of messages with attached request ids is reported
+    /// through this channel.
+    delivery_status_sender: Option<DeliveryStatusSender>,
+
+    /// If set, the fresh messages are kept in this journal until they're either acknowledged
+    /// or abandoned, so that they would be resent after the client restarts.
+    outbound_journal: Option<Arc<dyn OutboundJournal>>,
+
+    /// Redirect records of other clients, used to update the addresses of the recipients.
+    known_redirects: KnownRedirects,
+
+    /// If set, the redirect record of this client is sent to every recipient it's messaging,
+    /// so that they would learn about the gateway it has migrated to.
+    own_redirect: Option<GatewayRedirect>,
 }
 
 impl Config {
@@ -72,7 +106,7 @@ impl Config {
         average_message_sending_delay: Duration,
         average_packet_delay_duration: Duration,
         disable_main_poisson_packet_distribution: bool,
-        self_recipient: Recipient,
+        self_recipient: SelfAddress,
     ) -> Self {
         Config {
             ack_key,
@@ -84,12 +118,52 @@ impl Config {
             average_ack_delay_duration,
             disable_main_poisson_packet_distribution,
             packet_size: Default::default(),
+            num_mix_hops: DEFAULT_NUM_MIX_HOPS,
+            erasure_coding_redundancy: None,
+            maximum_retransmissions: None,
+            message_timeout: None,
+            delivery_status_sender: None,
+            outbound_journal: None,
+            known_redirects: Default::default(),
+            own_redirect: None,
         }
     }
 
     pub fn set_custom_packet_size(&mut self, packet_size: PacketSize) {
         self.packet_size = packet_size;
     }
+
+    pub fn set_num_mix_hops(&mut self, hops: u8) {
+        self.num_mix_hops = hops;
+    }
+
+    pub fn set_erasure_coding_redundancy(&mut self, redundancy: f64) {
+        self.erasure_coding_redundancy = Some(redundancy);
+    }
+
+    pub fn set_maximum_retransmissions(&mut self, maximum: usize) {
+        self.maximum_retransmissions = Some(maximum);
+    }
+
+    pub fn set_message_timeout(&mut self, timeout: Duration) {
+        self.message_timeout = Some(timeout);
+    }
+
+    pub fn set_delivery_status_sender(&mut self, sender: DeliveryStatusSender) {
+        self.delivery_status_sender = Some(sender);
+    }
+
+    pub fn set_outbound_journal(&mut self, journal: Arc<dyn OutboundJournal>) {
+        self.outbound_journal = Some(journal);
+    }
+
+    pub fn set_known_redirects(&mut self, known_redirects: KnownRedirects) {
+        self.known_redirects = known_redirects;
+    }
+
+    pub fn set_own_redirect(&mut self, redirect: GatewayRedirect) {
+        self.own_redirect = Some(redirect);
+    }
 }
 
 pub struct RealMessagesController<R>
@@ -98,6 +172,7 @@ where
 {
     out_queue_control: OutQueueControl<R>,
     ack_control: AcknowledgementController<R>,
+    queue_length: OutQueueLength,
 }
 
 // obviously when we finally make shared rng that is on 'higher' level, this should become
@@ -113,7 +188,10 @@ impl RealMessagesController<OsRng> {
     ) -> Self {
         let rng = OsRng;
 
+        let queue_length = OutQueueLength::default();
         let (real_message_sender, real_message_receiver) = mpsc::unbounded();
+        let real_message_sender =
+            BatchRealMessageSender::new(real_message_sender, queue_length.clone());
         let (sent_notifier_tx, sent_notifier_rx) = mpsc::unbounded();
 
         let ack_controller_connectors = AcknowledgementControllerConnectors::new(
@@ -121,6 +199,7 @@ impl RealMessagesController<OsRng> {
             input_receiver,
             sent_notifier_rx,
             ack_receiver,
+            config.delivery_status_sender,
         );
 
         let ack_control_config = acknowledgement_control::Config::new(
@@ -129,14 +208,20 @@ impl RealMessagesController<OsRng> {
             config.average_ack_delay_duration,
             config.average_packet_delay_duration,
         )
-        .with_custom_packet_size(config.packet_size);
+        .with_custom_packet_size(config.packet_size)
+        .with_mix_hops(config.num_mix_hops)
+        .with_erasure_coding(config.erasure_coding_redundancy)
+        .with_maximum_retransmissions(config.maximum_retransmissions)
+        .with_message_timeout(config.message_timeout)
+        .with_outbound_journal(config.outbound_journal)
+        .with_redirects(config.known_redirects, config.own_redirect);
 
         let ack_control = AcknowledgementController::new(
             ack_control_config,
             rng,
             topology_access.clone(),
             Arc::clone(&config.ack_key),
-            config.self_recipient,
+            config.self_recipient.clone(),
             ack_controller_connectors,
             #[cfg(feature = "reply-surb")]
             reply_key_storage,
@@ -148,7 +233,8 @@ impl RealMessagesController<OsRng> {
             config.average_message_sending_delay,
             config.disable_main_poisson_packet_distribution,
         )
-        .with_custom_cover_packet_size(config.packet_size);
+        .with_custom_cover_packet_size(config.packet_size)
+        .with_mix_hops(config.num_mix_hops);
 
         let out_queue_control = OutQueueControl::new(
             out_queue_config,
@@ -159,14 +245,22 @@ impl RealMessagesController<OsRng> {
             rng,
             config.self_recipient,
             topology_access,
+            queue_length.clone(),
         );
 
         RealMessagesController {
             out_queue_control,
             ack_control,
+            queue_length,
         }
     }
 
+    /// Returns handle to the number of real packets waiting to be sent out, which can be used
+    /// for applying backpressure, for example by the `MixnetStreamWriter`.
+    pub fn out_queue_length(&self) -> OutQueueLength {
+        self.queue_length.clone()
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     pub fn start_with_shutdown(self, shutdown: task::ShutdownListener) {
         let mut out_queue_control = self.out_queue_control;
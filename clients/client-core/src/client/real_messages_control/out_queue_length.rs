@@ -0,0 +1,122 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use futures::task::{Context, Poll, Waker};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Shared counter of real packets that were already prepared and pushed to the `OutQueueControl`,
+/// but not yet sent out to the mix network. It allows producers of data to apply backpressure
+/// instead of queueing arbitrary amounts of packets in memory.
+#[derive(Clone, Default, Debug)]
+pub struct OutQueueLength {
+    inner: Arc<OutQueueLengthInner>,
+}
+
+#[derive(Default, Debug)]
+struct OutQueueLengthInner {
+    queued: AtomicUsize,
+
+    /// Tasks waiting for the queue to get shorter.
+    waiters: Mutex<Vec<Waker>>,
+}
+
+impl OutQueueLength {
+    /// Returns the number of packets currently waiting to be sent out.
+    pub fn current(&self) -> usize {
+        self.inner.queued.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn increase(&self, packets: usize) {
+        self.inner.queued.fetch_add(packets, Ordering::SeqCst);
+    }
+
+    pub(crate) fn decrease(&self, packets: usize) {
+        // it's impossible to decrease it below zero as we always increase it before
+        // the packets are pushed to the queue
+        self.inner.queued.fetch_sub(packets, Ordering::SeqCst);
+
+        let waiters = std::mem::take(&mut *self.inner.waiters.lock().unwrap());
+        for waiter in waiters {
+            waiter.wake()
+        }
+    }
+
+    /// Accounts for packets of a message that has been queued for sending, but that is yet to be
+    /// split into the actual packets. They're accounted for until the reservation is dropped.
+    pub(crate) fn reserve(&self, packets: usize) -> QueueReservation {
+        self.increase(packets);
+        QueueReservation {
+            queue_length: self.clone(),
+            packets,
+        }
+    }
+
+    /// Resolves once there are fewer than `threshold` packets waiting to be sent out.
+    pub fn poll_below(&self, cx: &mut Context<'_>, threshold: usize) -> Poll<()> {
+        if self.current() < threshold {
+            return Poll::Ready(());
+        }
+
+        self.inner.waiters.lock().unwrap().push(cx.waker().clone());
+
+        // check again in case the queue got shorter before we registered our waker
+        if self.current() < threshold {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Packets of a not yet prepared message that are already accounted for in the `OutQueueLength`,
+/// so that the producers of data couldn't queue arbitrary amounts of it before any of it gets
+/// turned into packets. The reservation is released once it's dropped.
+#[derive(Debug)]
+pub struct QueueReservation {
+    queue_length: OutQueueLength,
+    packets: usize,
+}
+
+impl Drop for QueueReservation {
+    fn drop(&mut self) {
+        self.queue_length.decrease(self.packets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::task::noop_waker;
+
+    #[test]
+    fn is_ready_only_when_below_threshold() {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let queue_length = OutQueueLength::default();
+        assert!(queue_length.poll_below(&mut cx, 2).is_ready());
+
+        queue_length.increase(2);
+        assert!(queue_length.poll_below(&mut cx, 2).is_pending());
+
+        queue_length.decrease(1);
+        assert!(queue_length.poll_below(&mut cx, 2).is_ready());
+        assert_eq!(queue_length.current(), 1);
+    }
+
+    #[test]
+    fn reservations_are_released_once_dropped() {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let queue_length = OutQueueLength::default();
+        let reservation = queue_length.reserve(3);
+        assert_eq!(queue_length.current(), 3);
+        assert!(queue_length.poll_below(&mut cx, 3).is_pending());
+
+        drop(reservation);
+        assert_eq!(queue_length.current(), 0);
+        assert!(queue_length.poll_below(&mut cx, 3).is_ready());
+    }
+}
@@ -1,19 +1,20 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use super::OutQueueLength;
 use crate::client::mix_traffic::BatchMixMessageSender;
 use crate::client::real_messages_control::acknowledgement_control::SentPacketNotificationSender;
+use crate::client::self_address::SelfAddress;
 use crate::client::topology_control::TopologyAccessor;
 use futures::channel::mpsc;
 use futures::task::{Context, Poll};
 use futures::{Future, Stream, StreamExt};
 use log::*;
 use nymsphinx::acknowledgements::AckKey;
-use nymsphinx::addressing::clients::Recipient;
 use nymsphinx::chunking::fragment::FragmentIdentifier;
 use nymsphinx::cover::generate_loop_cover_packet;
 use nymsphinx::forwarding::packet::MixPacket;
-use nymsphinx::params::PacketSize;
+use nymsphinx::params::{PacketSize, DEFAULT_NUM_MIX_HOPS};
 use nymsphinx::utils::sample_poisson_duration;
 use rand::{CryptoRng, Rng};
 use std::collections::VecDeque;
@@ -61,6 +62,9 @@ pub(crate) struct Config {
 
     /// Predefined packet size used for the loop cover messages.
     cover_packet_size: PacketSize,
+
+    /// Number of mix hops the loop cover messages are going to go through.
+    num_mix_hops: u8,
 }
 
 impl Config {
@@ -76,6 +80,7 @@ impl Config {
             average_message_sending_delay,
             disable_poisson_packet_distribution,
             cover_packet_size: Default::default(),
+            num_mix_hops: DEFAULT_NUM_MIX_HOPS,
         }
     }
 
@@ -83,6 +88,11 @@ impl Config {
         self.cover_packet_size = packet_size;
         self
     }
+
+    pub fn with_mix_hops(mut self, hops: u8) -> Self {
+        self.num_mix_hops = hops;
+        self
+    }
 }
 
 struct SendingDelayController {
@@ -214,7 +224,7 @@ where
     real_receiver: BatchRealMessageReceiver,
 
     /// Represents full address of this client.
-    our_full_destination: Recipient,
+    our_full_destination: SelfAddress,
 
     /// Instance of a cryptographically secure random number generator.
     rng: R,
@@ -224,6 +234,9 @@ where
 
     /// Buffer containing all real messages received. It is first exhausted before more are pulled.
     received_buffer: VecDeque<RealMessage>,
+
+    /// Number of real messages that were queued, but not yet sent out.
+    queue_length: OutQueueLength,
 }
 
 pub(crate) struct RealMessage {
@@ -242,9 +255,41 @@ impl RealMessage {
 
 // messages are already prepared, etc. the real point of it is to forward it to mix_traffic
 // after sufficient delay
-pub(crate) type BatchRealMessageSender = mpsc::UnboundedSender<Vec<RealMessage>>;
 type BatchRealMessageReceiver = mpsc::UnboundedReceiver<Vec<RealMessage>>;
 
+/// Channel used for pushing prepared real messages to the `OutQueueControl`, which also keeps
+/// track of how many of them are yet to be sent out.
+#[derive(Clone)]
+pub(crate) struct BatchRealMessageSender {
+    inner: mpsc::UnboundedSender<Vec<RealMessage>>,
+    queue_length: OutQueueLength,
+}
+
+impl BatchRealMessageSender {
+    pub(crate) fn new(
+        inner: mpsc::UnboundedSender<Vec<RealMessage>>,
+        queue_length: OutQueueLength,
+    ) -> Self {
+        BatchRealMessageSender {
+            inner,
+            queue_length,
+        }
+    }
+
+    pub(crate) fn unbounded_send(
+        &self,
+        messages: Vec<RealMessage>,
+    ) -> Result<(), mpsc::TrySendError<Vec<RealMessage>>> {
+        let count = messages.len();
+        // increase the counter first so that it would never go below zero
+        self.queue_length.increase(count);
+        self.inner.unbounded_send(messages).map_err(|err| {
+            self.queue_length.decrease(count);
+            err
+        })
+    }
+}
+
 pub(crate) enum StreamMessage {
     Cover,
     Real(Box<RealMessage>),
@@ -264,8 +309,9 @@ where
         mix_tx: BatchMixMessageSender,
         real_receiver: BatchRealMessageReceiver,
         rng: R,
-        our_full_destination: Recipient,
+        our_full_destination: SelfAddress,
         topology_access: TopologyAccessor,
+        queue_length: OutQueueLength,
     ) -> Self {
         OutQueueControl {
             config,
@@ -282,6 +328,7 @@ where
             rng,
             topology_access,
             received_buffer: VecDeque::with_capacity(0), // we won't be putting any data into this guy directly
+            queue_length,
         }
     }
 
@@ -301,11 +348,13 @@ where
                 // TODO for way down the line: in very rare cases (during topology update) we might have
                 // to wait a really tiny bit before actually obtaining the permit hence messing with our
                 // poisson delay, but is it really a problem?
+                let our_full_destination = self.our_full_destination.get();
                 let topology_permit = self.topology_access.get_read_permit().await;
                 // the ack is sent back to ourselves (and then ignored)
                 let topology_ref_option = topology_permit.try_get_valid_topology_ref(
-                    &self.our_full_destination,
-                    Some(&self.our_full_destination),
+                    &our_full_destination,
+                    Some(&our_full_destination),
+                    self.config.num_mix_hops,
                 );
                 if topology_ref_option.is_none() {
                     warn!(
@@ -320,10 +369,11 @@ where
                         &mut self.rng,
                         topology_ref,
                         &self.ack_key,
-                        &self.our_full_destination,
+                        &our_full_destination,
                         self.config.average_ack_delay,
                         self.config.average_packet_delay,
                         self.config.cover_packet_size,
+                        self.config.num_mix_hops,
                     )
                     .expect(
                         "Somehow failed to generate a loop cover message with a valid topology",
@@ -332,6 +382,7 @@ where
                 )
             }
             StreamMessage::Real(real_message) => {
+                self.queue_length.decrease(1);
                 (real_message.mix_packet, Some(real_message.fragment_id))
             }
         };
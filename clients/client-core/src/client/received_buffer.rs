@@ -1,16 +1,26 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::client::outbound_journal::run_blocking;
+use crate::client::redirects::KnownRedirects;
+use crate::config::Config;
+use crate::error::ClientCoreError;
 use crate::spawn_future;
+use config::NymConfig;
 use crypto::asymmetric::encryption;
 use futures::channel::mpsc;
 use futures::lock::Mutex;
 use futures::StreamExt;
 use gateway_client::MixnetMessageReceiver;
 use log::*;
-use nymsphinx::receiver::{MessageReceiver, MessageRecoveryError, ReconstructedMessage};
+use nymsphinx::chunking::backend::DiskReconstructionBackend;
+use nymsphinx::chunking::fragment::Fragment;
+use nymsphinx::chunking::reconstruction::MessageReconstructor;
+use nymsphinx::receiver::{
+    MessageReceiver, MessageRecoveryError, ReconstructedMessage, RecoveredMessage,
+};
 use std::collections::HashSet;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex as StdMutex};
 
 #[cfg(feature = "reply-surb")]
 use crate::client::reply_key_storage::ReplyKeyStorage;
@@ -30,13 +40,35 @@ pub type ReceivedBufferRequestReceiver = mpsc::UnboundedReceiver<ReceivedBufferM
 pub type ReconstructedMessagesSender = mpsc::UnboundedSender<Vec<ReconstructedMessage>>;
 pub type ReconstructedMessagesReceiver = mpsc::UnboundedReceiver<Vec<ReconstructedMessage>>;
 
+/// Creates the `MessageReceiver` used for reconstructing received messages, with the message
+/// reconstruction configured according to the provided client configuration.
+/// If disk-backed reconstruction is enabled, it resumes reconstruction of all partially received
+/// messages stored in the reconstruction buffer directory.
+pub fn message_receiver_from_config<T: NymConfig>(
+    config: &Config<T>,
+) -> Result<MessageReceiver, ClientCoreError> {
+    let mut reconstructor = MessageReconstructor::new()
+        .with_memory_budget(config.get_reconstruction_memory_budget())
+        .with_stale_set_timeout(config.get_incomplete_message_timeout());
+
+    if config.get_use_disk_backed_reconstruction() {
+        let backend = DiskReconstructionBackend::new(config.get_reconstruction_buffer_directory())?;
+        reconstructor = reconstructor.with_backend(Arc::new(backend))?;
+    }
+
+    Ok(MessageReceiver::new()
+        .with_mix_hops(config.get_num_mix_hops())
+        .with_reconstructor(reconstructor))
+}
+
 struct ReceivedMessagesBufferInner {
     messages: Vec<ReconstructedMessage>,
     local_encryption_keypair: Arc<encryption::KeyPair>,
 
     // TODO: looking how it 'looks' here, perhaps `MessageReceiver` should be renamed to something
     // else instead.
-    message_receiver: MessageReceiver,
+    // shared with the blocking tasks inserting the received fragments
+    message_receiver: Arc<StdMutex<MessageReceiver>>,
     message_sender: Option<ReconstructedMessagesSender>,
 
     // TODO: this will get cleared upon re-running the client
@@ -46,9 +78,13 @@ struct ReceivedMessagesBufferInner {
 }
 
 impl ReceivedMessagesBufferInner {
-    fn process_received_fragment(&mut self, raw_fragment: Vec<u8>) -> Option<ReconstructedMessage> {
-        let fragment_data = match self
+    fn recover_received_fragment(&self, raw_fragment: Vec<u8>) -> Option<Fragment> {
+        let message_receiver = self
             .message_receiver
+            .lock()
+            .expect("message receiver lock got poisoned");
+
+        let fragment_data = match message_receiver
             .recover_plaintext(self.local_encryption_keypair.private_key(), raw_fragment)
         {
             Err(e) => {
@@ -63,7 +99,7 @@ impl ReceivedMessagesBufferInner {
             return None;
         }
 
-        let fragment = match self.message_receiver.recover_fragment(&fragment_data) {
+        let fragment = match message_receiver.recover_fragment(&fragment_data) {
             Err(e) => {
                 warn!("failed to recover fragment from raw data: {:?}. The whole underlying message might be corrupted and unrecoverable!", e);
                 return None;
@@ -76,8 +112,51 @@ impl ReceivedMessagesBufferInner {
             return None;
         }
 
+        Some(fragment)
+    }
+
+    async fn insert_received_fragments(
+        &mut self,
+        fragments: Vec<Fragment>,
+        known_redirects: &KnownRedirects,
+    ) -> Vec<ReconstructedMessage> {
+        if fragments.is_empty() {
+            return Vec::new();
+        }
+
+        // the reconstructor might be persisting the fragments on disk, so they're inserted
+        // outside the async executor
+        let message_receiver = Arc::clone(&self.message_receiver);
+        let insertion_results = run_blocking(move || {
+            let mut message_receiver = message_receiver
+                .lock()
+                .expect("message receiver lock got poisoned");
+            Ok(fragments
+                .into_iter()
+                .map(|fragment| message_receiver.insert_new_fragment(fragment))
+                .collect::<Vec<_>>())
+        })
+        .await;
+
+        match insertion_results {
+            Ok(results) => results
+                .into_iter()
+                .filter_map(|result| self.handle_insertion_result(result, known_redirects))
+                .collect(),
+            Err(err) => {
+                error!("failed to insert the received fragments - {}", err);
+                Vec::new()
+            }
+        }
+    }
+
+    fn handle_insertion_result(
+        &mut self,
+        insertion_result: Result<Option<(RecoveredMessage, Vec<i32>)>, MessageRecoveryError>,
+        known_redirects: &KnownRedirects,
+    ) -> Option<ReconstructedMessage> {
         // if we returned an error the underlying message is malformed in some way
-        match self.message_receiver.insert_new_fragment(fragment) {
+        match insertion_result {
             Err(err) => match err {
                 MessageRecoveryError::MalformedReconstructedMessage(message_sets) => {
                     // TODO: should we really insert reconstructed sets? could this be abused for some attack?
@@ -94,14 +173,23 @@ impl ReceivedMessagesBufferInner {
                 ),
             },
             Ok(reconstruction_result) => match reconstruction_result {
-                Some((reconstructed_message, used_sets)) => {
+                Some((recovered_message, used_sets)) => {
                     for set_id in used_sets {
                         if !self.recently_reconstructed.insert(set_id) {
                             // or perhaps we should even panic at this point?
                             error!("Reconstructed another message containing already used set id!")
                         }
                     }
-                    Some(reconstructed_message)
+                    match recovered_message {
+                        RecoveredMessage::Application(reconstructed_message) => {
+                            Some(reconstructed_message)
+                        }
+                        RecoveredMessage::Control(control_message) => {
+                            // control messages are meant for the client itself and never reach the application
+                            known_redirects.absorb_notice(&control_message);
+                            None
+                        }
+                    }
                 }
                 None => None,
             },
@@ -115,6 +203,10 @@ impl ReceivedMessagesBufferInner {
 struct ReceivedMessagesBuffer {
     inner: Arc<Mutex<ReceivedMessagesBufferInner>>,
 
+    /// Redirect records announced by other clients, which are consumed here rather than
+    /// being delivered to the application.
+    known_redirects: KnownRedirects,
+
     /// Storage containing keys to all [`ReplySURB`]s ever sent out that we did not receive back.
     // There's no need to put it behind a Mutex since it's already properly concurrent
     #[cfg(feature = "reply-surb")]
@@ -124,16 +216,19 @@ struct ReceivedMessagesBuffer {
 impl ReceivedMessagesBuffer {
     fn new(
         local_encryption_keypair: Arc<encryption::KeyPair>,
+        message_receiver: MessageReceiver,
+        known_redirects: KnownRedirects,
         #[cfg(feature = "reply-surb")] reply_key_storage: ReplyKeyStorage,
     ) -> Self {
         ReceivedMessagesBuffer {
             inner: Arc::new(Mutex::new(ReceivedMessagesBufferInner {
                 messages: Vec::new(),
                 local_encryption_keypair,
-                message_receiver: MessageReceiver::new(),
+                message_receiver: Arc::new(StdMutex::new(message_receiver)),
                 message_sender: None,
                 recently_reconstructed: HashSet::new(),
             })),
+            known_redirects,
             #[cfg(feature = "reply-surb")]
             reply_key_storage,
         }
@@ -214,6 +309,7 @@ impl ReceivedMessagesBuffer {
         );
 
         let mut completed_messages = Vec::new();
+        let mut fragments = Vec::new();
         let mut inner_guard = self.inner.lock().await;
 
         // first check if this is a reply or a chunked message
@@ -250,18 +346,23 @@ impl ReceivedMessagesBuffer {
                     }
                 } else {
                     // otherwise - it's a 'normal' message
-                    if let Some(completed_message) = inner_guard.process_received_fragment(msg) {
-                        completed_messages.push(completed_message)
+                    if let Some(fragment) = inner_guard.recover_received_fragment(msg) {
+                        fragments.push(fragment)
                     }
                 }
             }
 
             #[cfg(not(feature = "reply-surb"))]
-            if let Some(completed_message) = inner_guard.process_received_fragment(msg) {
-                completed_messages.push(completed_message)
+            if let Some(fragment) = inner_guard.recover_received_fragment(msg) {
+                fragments.push(fragment)
             }
         }
 
+        let reconstructed_messages = inner_guard
+            .insert_received_fragments(fragments, &self.known_redirects)
+            .await;
+        completed_messages.extend(reconstructed_messages);
+
         if !completed_messages.is_empty() {
             if let Some(sender) = &inner_guard.message_sender {
                 trace!("Sending reconstructed messages to announced sender");
@@ -413,10 +514,14 @@ impl ReceivedMessagesBufferController {
         local_encryption_keypair: Arc<encryption::KeyPair>,
         query_receiver: ReceivedBufferRequestReceiver,
         mixnet_packet_receiver: MixnetMessageReceiver,
+        message_receiver: MessageReceiver,
+        known_redirects: KnownRedirects,
         #[cfg(feature = "reply-surb")] reply_key_storage: ReplyKeyStorage,
     ) -> Self {
         let received_buffer = ReceivedMessagesBuffer::new(
             local_encryption_keypair,
+            message_receiver,
+            known_redirects,
             #[cfg(feature = "reply-surb")]
             reply_key_storage,
         );
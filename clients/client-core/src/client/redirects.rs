@@ -0,0 +1,282 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::client::topology_verification::current_unix_timestamp;
+use gateway_client::GatewayRedirect;
+use log::*;
+use nymsphinx::addressing::clients::Recipient;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::{io, path::Path};
+
+/// Prefix of the control messages carrying a redirect record of their sender, which distinguishes
+/// them from any other kind of control message.
+const REDIRECT_NOTICE_PREFIX: &[u8] = b"NYM-REDIRECT-NOTICE-V1";
+
+/// Period, since its creation, during which the redirect record is announced to the peers
+/// of its client. Afterwards they are expected to know the new address already, so any older
+/// records received are ignored.
+pub const REDIRECT_ANNOUNCEMENT_PERIOD: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Maximum number of redirect records of other clients kept in memory. Since anyone can create
+/// a valid record for a fresh identity, once it's reached the oldest records get dropped.
+const MAX_KNOWN_REDIRECTS: usize = 1024;
+
+/// Checks whether the redirect record is past its announcement period.
+pub fn is_redirect_expired(redirect: &GatewayRedirect) -> bool {
+    let expires_at = redirect
+        .issued_at()
+        .saturating_add(REDIRECT_ANNOUNCEMENT_PERIOD.as_secs());
+    expires_at <= current_unix_timestamp() as u64
+}
+
+/// Creates the control message informing its recipient that the sender has moved
+/// to a different gateway.
+pub fn encode_redirect_notice(redirect: &GatewayRedirect) -> Vec<u8> {
+    REDIRECT_NOTICE_PREFIX
+        .iter()
+        .copied()
+        .chain(redirect.to_bytes())
+        .collect()
+}
+
+/// Attempts to recover a redirect record from a received control message. Returns `None` if the
+/// message is not a redirect notice or if the record it carries has not been signed by its client.
+pub fn decode_redirect_notice(message: &[u8]) -> Option<GatewayRedirect> {
+    let record = message.strip_prefix(REDIRECT_NOTICE_PREFIX)?;
+    match GatewayRedirect::try_from_bytes(record) {
+        Ok(redirect) if redirect.verify().is_ok() => Some(redirect),
+        Ok(_) => {
+            warn!("Received a redirect notice with an invalid signature");
+            None
+        }
+        Err(err) => {
+            warn!("Received a malformed redirect notice - {err}");
+            None
+        }
+    }
+}
+
+/// Loads the redirect record this client published when it last migrated to a different
+/// gateway, if it has ever done so.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_own_redirect<P: AsRef<Path>>(path: P) -> Option<GatewayRedirect> {
+    let encoded = match std::fs::read_to_string(path) {
+        Ok(encoded) => encoded,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return None,
+        Err(err) => {
+            error!("Failed to read the gateway redirect record - {err}");
+            return None;
+        }
+    };
+    GatewayRedirect::try_from_base58_string(encoded.trim())
+        .map_err(|err| error!("The stored gateway redirect record is malformed - {err}"))
+        .ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn store_own_redirect<P: AsRef<Path>>(path: P, redirect: &GatewayRedirect) -> io::Result<()> {
+    std::fs::write(path, redirect.to_base58_string())
+}
+
+/// Shared collection of the verified redirect records received from other clients, used to
+/// update their addresses before sending them any messages.
+#[derive(Clone, Debug, Default)]
+pub struct KnownRedirects {
+    inner: Arc<RwLock<HashMap<[u8; 32], GatewayRedirect>>>,
+}
+
+impl KnownRedirects {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Stores the verified record, unless a more recent one of the same client is already known.
+    pub fn insert(&self, redirect: GatewayRedirect) {
+        let client = redirect.client_identity().to_bytes();
+        let mut guard = self
+            .inner
+            .write()
+            .expect("known redirects lock got poisoned");
+
+        if let Some(known) = guard.get(&client) {
+            if known.issued_at() >= redirect.issued_at() {
+                return;
+            }
+        } else if guard.len() >= MAX_KNOWN_REDIRECTS {
+            let oldest = guard
+                .iter()
+                .min_by_key(|(_, known)| known.issued_at())
+                .map(|(client, _)| *client);
+            if let Some(oldest) = oldest {
+                guard.remove(&oldest);
+            }
+        }
+
+        debug!(
+            "Client {} has moved to gateway {}",
+            redirect.client_identity().to_base58_string(),
+            redirect.new_gateway().to_base58_string()
+        );
+        guard.insert(client, redirect);
+    }
+
+    /// Stores the record carried by the received control message, as long as it's a valid
+    /// redirect notice that has not expired yet. Returns whether the record got stored.
+    pub fn absorb_notice(&self, control_message: &[u8]) -> bool {
+        if !control_message.starts_with(REDIRECT_NOTICE_PREFIX) {
+            warn!("Received an unsupported control message");
+            return false;
+        }
+        match decode_redirect_notice(control_message) {
+            Some(redirect) if is_redirect_expired(&redirect) => {
+                debug!(
+                    "Ignoring the expired redirect record of client {}",
+                    redirect.client_identity().to_base58_string()
+                );
+                false
+            }
+            Some(redirect) => {
+                self.insert(redirect);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the current address of the recipient, taking into account the gateway
+    /// it might have moved to.
+    pub fn update_recipient(&self, recipient: Recipient) -> Recipient {
+        let guard = self
+            .inner
+            .read()
+            .expect("known redirects lock got poisoned");
+        guard
+            .get(&recipient.identity().to_bytes())
+            .and_then(|redirect| redirect.updated_recipient(&recipient))
+            .unwrap_or(recipient)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::asymmetric::{encryption, identity};
+    use rand::rngs::OsRng;
+    use std::net::SocketAddr;
+
+    fn now() -> u64 {
+        current_unix_timestamp() as u64
+    }
+
+    fn redirect(
+        client_keys: &identity::KeyPair,
+        old_gateway: &identity::KeyPair,
+        issued_at: u64,
+    ) -> GatewayRedirect {
+        let mut rng = OsRng;
+        let new_gateway = identity::KeyPair::new(&mut rng);
+        let new_gateway_sphinx = encryption::KeyPair::new(&mut rng);
+        let mix_address: SocketAddr = "127.0.0.1:1789".parse().unwrap();
+
+        GatewayRedirect::new(
+            client_keys,
+            *old_gateway.public_key(),
+            *new_gateway.public_key(),
+            *new_gateway_sphinx.public_key(),
+            mix_address.into(),
+            issued_at,
+        )
+    }
+
+    #[test]
+    fn redirect_notice_roundtrip() {
+        let mut rng = OsRng;
+        let client_keys = identity::KeyPair::new(&mut rng);
+        let old_gateway = identity::KeyPair::new(&mut rng);
+        let redirect = redirect(&client_keys, &old_gateway, 1667304000);
+
+        let notice = encode_redirect_notice(&redirect);
+        let recovered = decode_redirect_notice(&notice).unwrap();
+        assert_eq!(redirect.to_bytes(), recovered.to_bytes());
+
+        assert!(decode_redirect_notice(&redirect.to_bytes()).is_none());
+        let mut tampered = notice;
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(decode_redirect_notice(&tampered).is_none());
+    }
+
+    #[test]
+    fn redirects_expire_after_the_announcement_period() {
+        let mut rng = OsRng;
+        let client_keys = identity::KeyPair::new(&mut rng);
+        let old_gateway = identity::KeyPair::new(&mut rng);
+        let period = REDIRECT_ANNOUNCEMENT_PERIOD.as_secs();
+
+        assert!(!is_redirect_expired(&redirect(
+            &client_keys,
+            &old_gateway,
+            now()
+        )));
+        assert!(!is_redirect_expired(&redirect(
+            &client_keys,
+            &old_gateway,
+            now() - period + 60
+        )));
+        assert!(is_redirect_expired(&redirect(
+            &client_keys,
+            &old_gateway,
+            now() - period
+        )));
+
+        let known_redirects = KnownRedirects::new();
+        let expired = redirect(&client_keys, &old_gateway, now() - period - 1);
+        assert!(!known_redirects.absorb_notice(&encode_redirect_notice(&expired)));
+    }
+
+    #[test]
+    fn recipients_are_updated_with_the_most_recent_redirect() {
+        let mut rng = OsRng;
+        let client_keys = identity::KeyPair::new(&mut rng);
+        let client_encryption_keys = encryption::KeyPair::new(&mut rng);
+        let old_gateway = identity::KeyPair::new(&mut rng);
+        let recipient = Recipient::new(
+            *client_keys.public_key(),
+            *client_encryption_keys.public_key(),
+            *old_gateway.public_key(),
+        );
+
+        let known_redirects = KnownRedirects::new();
+        assert!(!known_redirects.absorb_notice(b"hello"));
+        assert_eq!(
+            known_redirects.update_recipient(recipient).to_bytes(),
+            recipient.to_bytes()
+        );
+
+        let newer = redirect(&client_keys, &old_gateway, now());
+        let older = redirect(&client_keys, &old_gateway, now() - 1);
+        let mut tampered = encode_redirect_notice(&older);
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(!known_redirects.absorb_notice(&tampered));
+        assert_eq!(
+            known_redirects.update_recipient(recipient).to_bytes(),
+            recipient.to_bytes()
+        );
+
+        assert!(known_redirects.absorb_notice(&encode_redirect_notice(&newer)));
+        assert!(known_redirects.absorb_notice(&encode_redirect_notice(&older)));
+
+        let updated = known_redirects.update_recipient(recipient);
+        assert_eq!(updated.gateway(), newer.new_gateway());
+        assert_eq!(updated.identity(), recipient.identity());
+        assert_eq!(
+            updated.encryption_key().to_bytes(),
+            client_encryption_keys.public_key().to_bytes()
+        );
+    }
+}
@@ -0,0 +1,35 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use nymsphinx::addressing::clients::Recipient;
+use std::sync::{Arc, RwLock};
+
+/// Shared view of the full address of this client. While the identity and encryption keys of the
+/// client never change, the gateway part of the address does if the client fails over
+/// to a different gateway.
+#[derive(Clone, Debug)]
+pub struct SelfAddress {
+    inner: Arc<RwLock<Recipient>>,
+}
+
+impl SelfAddress {
+    pub fn new(address: Recipient) -> Self {
+        SelfAddress {
+            inner: Arc::new(RwLock::new(address)),
+        }
+    }
+
+    pub fn get(&self) -> Recipient {
+        *self.inner.read().expect("self address lock got poisoned")
+    }
+
+    pub(crate) fn update(&self, address: Recipient) {
+        *self.inner.write().expect("self address lock got poisoned") = address;
+    }
+}
+
+impl From<Recipient> for SelfAddress {
+    fn from(address: Recipient) -> Self {
+        SelfAddress::new(address)
+    }
+}
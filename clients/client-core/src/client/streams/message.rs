@@ -0,0 +1,178 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use ordered_buffer::OrderedMessage;
+use rand::{CryptoRng, RngCore};
+use std::convert::TryInto;
+use std::fmt::{self, Display, Formatter};
+
+/// Prefix distinguishing stream messages from any other data sent through the mixnet.
+const STREAM_MAGIC: [u8; 6] = *b"NYMSTR";
+
+/// Version of the stream message format.
+const STREAM_PROTOCOL_VERSION: u8 = 1;
+
+const MAGIC_LEN: usize = STREAM_MAGIC.len();
+const VERSION_LEN: usize = 1;
+const STREAM_ID_LEN: usize = 8;
+const KIND_LEN: usize = 1;
+const INDEX_LEN: usize = 8;
+const PREFIX_LEN: usize = MAGIC_LEN + VERSION_LEN;
+const HEADER_LEN: usize = PREFIX_LEN + STREAM_ID_LEN + KIND_LEN + INDEX_LEN;
+
+/// Identifier of a stream, chosen at random by its writer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StreamId(u64);
+
+impl StreamId {
+    pub fn new_random<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        StreamId(rng.next_u64())
+    }
+}
+
+impl Display for StreamId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub(crate) enum StreamMessageKind {
+    /// Next chunk of the stream data.
+    Data = 0,
+
+    /// Indicates the writer has closed the stream. It carries no data.
+    Close = 1,
+}
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum StreamMessageError {
+    #[error("the received data is too short to be a stream message")]
+    TooShort,
+
+    #[error("the received data is not a stream message")]
+    NotAStream,
+
+    #[error("stream messages of version {0} are not supported")]
+    UnsupportedVersion(u8),
+
+    #[error("{0} is not a valid stream message kind")]
+    UnknownKind(u8),
+}
+
+/// Single mixnet message carrying a part of a stream.
+/// The output format is:
+/// | 6 bytes magic | 1 byte version | 8 bytes stream id | 1 byte kind | 8 bytes index | data... |
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct StreamMessage {
+    pub(crate) stream_id: StreamId,
+    pub(crate) kind: StreamMessageKind,
+    pub(crate) chunk: OrderedMessage,
+}
+
+impl StreamMessage {
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        STREAM_MAGIC
+            .into_iter()
+            .chain(std::iter::once(STREAM_PROTOCOL_VERSION))
+            .chain(self.stream_id.0.to_be_bytes().into_iter())
+            .chain(std::iter::once(self.kind as u8))
+            .chain(self.chunk.into_bytes().into_iter())
+            .collect()
+    }
+
+    pub(crate) fn try_from_bytes(data: &[u8]) -> Result<Self, StreamMessageError> {
+        if !data.starts_with(&STREAM_MAGIC) {
+            return Err(StreamMessageError::NotAStream);
+        }
+        if data.len() < HEADER_LEN {
+            return Err(StreamMessageError::TooShort);
+        }
+        if data[MAGIC_LEN] != STREAM_PROTOCOL_VERSION {
+            return Err(StreamMessageError::UnsupportedVersion(data[MAGIC_LEN]));
+        }
+
+        let data = &data[PREFIX_LEN..];
+        let stream_id = StreamId(u64::from_be_bytes(
+            data[..STREAM_ID_LEN].try_into().unwrap(),
+        ));
+        let kind = match data[STREAM_ID_LEN] {
+            0 => StreamMessageKind::Data,
+            1 => StreamMessageKind::Close,
+            n => return Err(StreamMessageError::UnknownKind(n)),
+        };
+        // we've already checked the length, so this can't fail
+        let chunk = OrderedMessage::try_from_bytes(data[STREAM_ID_LEN + KIND_LEN..].to_vec())
+            .map_err(|_| StreamMessageError::TooShort)?;
+
+        Ok(StreamMessage {
+            stream_id,
+            kind,
+            chunk,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_be_converted_to_and_from_bytes() {
+        let message = StreamMessage {
+            stream_id: StreamId(42),
+            kind: StreamMessageKind::Data,
+            chunk: OrderedMessage {
+                data: vec![1, 2, 3],
+                index: 123,
+            },
+        };
+
+        let bytes = message.clone().into_bytes();
+        assert_eq!(bytes.len(), HEADER_LEN + 3);
+        assert_eq!(StreamMessage::try_from_bytes(&bytes).unwrap(), message);
+    }
+
+    #[test]
+    fn recovery_fails_for_malformed_data() {
+        let mut bytes = STREAM_MAGIC.to_vec();
+        bytes.push(STREAM_PROTOCOL_VERSION);
+        bytes.resize(HEADER_LEN - 1, 0);
+        assert_eq!(
+            StreamMessage::try_from_bytes(&bytes),
+            Err(StreamMessageError::TooShort)
+        );
+
+        bytes.push(0);
+        bytes[PREFIX_LEN + STREAM_ID_LEN] = 42;
+        assert_eq!(
+            StreamMessage::try_from_bytes(&bytes),
+            Err(StreamMessageError::UnknownKind(42))
+        );
+
+        bytes[MAGIC_LEN] = STREAM_PROTOCOL_VERSION + 1;
+        assert_eq!(
+            StreamMessage::try_from_bytes(&bytes),
+            Err(StreamMessageError::UnsupportedVersion(
+                STREAM_PROTOCOL_VERSION + 1
+            ))
+        );
+    }
+
+    #[test]
+    fn plain_messages_are_not_stream_messages() {
+        // it looks exactly like the stream message would have looked like without the prefix
+        let mut bytes = vec![0; HEADER_LEN + 10];
+        bytes[STREAM_ID_LEN] = StreamMessageKind::Data as u8;
+        assert_eq!(
+            StreamMessage::try_from_bytes(&bytes),
+            Err(StreamMessageError::NotAStream)
+        );
+
+        assert_eq!(
+            StreamMessage::try_from_bytes(b"hello world, this is a regular message"),
+            Err(StreamMessageError::NotAStream)
+        );
+    }
+}
@@ -0,0 +1,39 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Streaming interface on top of regular mixnet messages.
+//!
+//! Rather than requiring the whole payload to be available as a single `Vec<u8>`,
+//! `MixnetStreamWriter` splits the written data into chunks, each sent as a separate mixnet
+//! message, while waiting for the out queue to drain whenever too many packets are already
+//! waiting to be sent. On the other side, `StreamReceiver` puts the received chunks back in order
+//! and makes them available through `MixnetStreamReader` as soon as each of them is reconstructed.
+//!
+//! Each chunk is sent in the following format:
+//! | 6 bytes magic | 1 byte version | 8 bytes stream id | 1 byte kind | 8 bytes index | data... |
+//!
+//! The magic prefix makes sure regular messages are never mistaken for parts of a stream.
+//!
+//! Streams are opt-in: the application has to pass the messages it receives to the `StreamReceiver`,
+//! which returns back everything that is not part of a stream.
+
+use std::time::Duration;
+
+mod message;
+mod receiver;
+mod writer;
+
+pub use message::{StreamId, StreamMessageError};
+pub use receiver::{IncomingStreams, MixnetStreamReader, StreamReceiver};
+pub use writer::MixnetStreamWriter;
+
+/// Default number of bytes sent in a single mixnet message of a stream.
+pub const DEFAULT_STREAM_CHUNK_SIZE: usize = 32 * 1024;
+
+/// Default maximum number of real packets waiting to be sent out before stream writers
+/// stop accepting more data.
+pub const DEFAULT_MAX_QUEUED_PACKETS: usize = 256;
+
+/// Default amount of time after which a received stream that has not received any new chunks
+/// is assumed to have been abandoned by its writer.
+pub const DEFAULT_STREAM_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
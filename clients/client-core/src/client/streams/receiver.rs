@@ -0,0 +1,365 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use super::message::{StreamId, StreamMessage, StreamMessageKind};
+use super::DEFAULT_STREAM_IDLE_TIMEOUT;
+use crate::client::received_buffer::{ReconstructedMessagesReceiver, ReconstructedMessagesSender};
+use futures::channel::mpsc;
+use futures::io::AsyncRead;
+use futures::ready;
+use futures::task::{Context, Poll};
+use futures::StreamExt;
+use log::*;
+use nymsphinx::receiver::ReconstructedMessage;
+use ordered_buffer::OrderedMessageBuffer;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::io;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
+/// Upper bound on how often the `StreamReceiver` looks for idle streams.
+const IDLE_STREAMS_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Channel announcing new streams opened by remote writers.
+pub type IncomingStreams = mpsc::UnboundedReceiver<MixnetStreamReader>;
+
+/// Receiving half of a stream. It yields the data as soon as all preceding chunks are received
+/// and reaches EOF once the remote writer closes the stream. If the stream is abandoned, i.e. no
+/// new chunks are received for too long, reading fails with `io::ErrorKind::TimedOut`.
+pub struct MixnetStreamReader {
+    stream_id: StreamId,
+    data_receiver: mpsc::UnboundedReceiver<io::Result<Vec<u8>>>,
+
+    /// Chunk that is currently being read alongside the number of bytes already consumed.
+    current: Vec<u8>,
+    position: usize,
+}
+
+impl MixnetStreamReader {
+    fn new(
+        stream_id: StreamId,
+        data_receiver: mpsc::UnboundedReceiver<io::Result<Vec<u8>>>,
+    ) -> Self {
+        MixnetStreamReader {
+            stream_id,
+            data_receiver,
+            current: Vec::new(),
+            position: 0,
+        }
+    }
+
+    pub fn stream_id(&self) -> StreamId {
+        self.stream_id
+    }
+}
+
+impl AsyncRead for MixnetStreamReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        loop {
+            if this.position < this.current.len() {
+                let available = &this.current[this.position..];
+                let read = available.len().min(buf.len());
+                buf[..read].copy_from_slice(&available[..read]);
+                this.position += read;
+                return Poll::Ready(Ok(read));
+            }
+
+            match ready!(this.data_receiver.poll_next_unpin(cx)) {
+                Some(Ok(data)) => {
+                    this.current = data;
+                    this.position = 0;
+                }
+                Some(Err(err)) => return Poll::Ready(Err(err)),
+                // the stream got closed
+                None => return Poll::Ready(Ok(0)),
+            }
+        }
+    }
+}
+
+struct IncomingStream {
+    buffer: OrderedMessageBuffer,
+
+    /// Index of the closing message, once it's received.
+    close_index: Option<u64>,
+    data_sender: mpsc::UnboundedSender<io::Result<Vec<u8>>>,
+
+    /// Time at which the last chunk of the stream has been received.
+    last_update: Instant,
+}
+
+/// Demultiplexes received messages into the streams they belong to.
+pub struct StreamReceiver {
+    streams: HashMap<StreamId, IncomingStream>,
+    new_streams: mpsc::UnboundedSender<MixnetStreamReader>,
+
+    /// Amount of time after which a stream that has not received any new chunks is assumed
+    /// to have been abandoned by its writer and is removed.
+    idle_timeout: Duration,
+
+    /// Time of the last check for the idle streams.
+    last_idle_check: Instant,
+}
+
+impl StreamReceiver {
+    pub fn new() -> (Self, IncomingStreams) {
+        let (new_streams, incoming_streams) = mpsc::unbounded();
+        (
+            StreamReceiver {
+                streams: HashMap::new(),
+                new_streams,
+                idle_timeout: DEFAULT_STREAM_IDLE_TIMEOUT,
+                last_idle_check: Instant::now(),
+            },
+            incoming_streams,
+        )
+    }
+
+    #[must_use]
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Removes all streams that have not received any chunks within the idle timeout,
+    /// returning the number of removed streams.
+    pub fn remove_idle_streams(&mut self) -> usize {
+        let now = Instant::now();
+        self.last_idle_check = now;
+
+        let idle_timeout = self.idle_timeout;
+        let idle: Vec<_> = self
+            .streams
+            .iter()
+            .filter(|(_, stream)| now.duration_since(stream.last_update) >= idle_timeout)
+            .map(|(stream_id, _)| *stream_id)
+            .collect();
+
+        for stream_id in &idle {
+            if let Some(stream) = self.streams.remove(stream_id) {
+                // let the reader know the stream is incomplete rather than just closing it
+                let err = io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("stream {} has not received any data in time", stream_id),
+                );
+                stream.data_sender.unbounded_send(Err(err)).ok();
+            }
+        }
+        idle.len()
+    }
+
+    /// Checks for the idle streams if enough time has passed since the previous check.
+    fn maybe_remove_idle_streams(&mut self) {
+        let check_interval = self.idle_timeout.min(IDLE_STREAMS_CHECK_INTERVAL);
+        if self.last_idle_check.elapsed() < check_interval {
+            return;
+        }
+
+        let removed = self.remove_idle_streams();
+        if removed > 0 {
+            warn!(
+                "removed {} streams that did not receive any data in {:?}",
+                removed, self.idle_timeout
+            )
+        }
+    }
+
+    /// Passes the reconstructed message to its stream. If it's not a stream message,
+    /// it's returned back to the caller.
+    pub fn handle_message(
+        &mut self,
+        message: ReconstructedMessage,
+    ) -> Option<ReconstructedMessage> {
+        self.maybe_remove_idle_streams();
+        match StreamMessage::try_from_bytes(&message.message) {
+            Ok(stream_message) => {
+                self.handle_stream_message(stream_message);
+                None
+            }
+            Err(_) => Some(message),
+        }
+    }
+
+    fn handle_stream_message(&mut self, message: StreamMessage) {
+        let stream_id = message.stream_id;
+        let stream = match self.streams.entry(stream_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                debug!("received new stream {}", stream_id);
+                let (data_sender, data_receiver) = mpsc::unbounded();
+                if self
+                    .new_streams
+                    .unbounded_send(MixnetStreamReader::new(stream_id, data_receiver))
+                    .is_err()
+                {
+                    warn!(
+                        "nothing is accepting new streams - stream {} is going to be ignored",
+                        stream_id
+                    )
+                }
+                entry.insert(IncomingStream {
+                    buffer: OrderedMessageBuffer::new(),
+                    close_index: None,
+                    data_sender,
+                    last_update: Instant::now(),
+                })
+            }
+        };
+        stream.last_update = Instant::now();
+
+        if message.kind == StreamMessageKind::Close {
+            stream.close_index = Some(message.chunk.index);
+        }
+        stream.buffer.write(message.chunk);
+
+        let contiguous = match stream.buffer.read() {
+            Some(contiguous) => contiguous,
+            None => return,
+        };
+        if !contiguous.data.is_empty()
+            && stream
+                .data_sender
+                .unbounded_send(Ok(contiguous.data))
+                .is_err()
+        {
+            trace!("the reader of stream {} is gone", stream_id)
+        }
+
+        // `last_index` is the index of the next expected message
+        if let Some(close_index) = stream.close_index {
+            if contiguous.last_index > close_index {
+                debug!("stream {} got closed", stream_id);
+                // dropping the sender signals EOF to the reader
+                self.streams.remove(&stream_id);
+            }
+        }
+    }
+
+    /// Processes all messages reconstructed by the client, passing the ones that are not part of
+    /// any stream to the optional sender.
+    pub async fn run(
+        mut self,
+        mut reconstructed_receiver: ReconstructedMessagesReceiver,
+        other_messages: Option<ReconstructedMessagesSender>,
+    ) {
+        while let Some(messages) = reconstructed_receiver.next().await {
+            let other: Vec<_> = messages
+                .into_iter()
+                .filter_map(|message| self.handle_message(message))
+                .collect();
+
+            if other.is_empty() {
+                continue;
+            }
+            match &other_messages {
+                Some(sender) => {
+                    if sender.unbounded_send(other).is_err() {
+                        warn!("the receiver of non-stream messages is gone")
+                    }
+                }
+                None => debug!("dropping {} received non-stream messages", other.len()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::io::AsyncReadExt;
+    use ordered_buffer::OrderedMessage;
+
+    fn stream_message(index: u64, kind: StreamMessageKind, data: &[u8]) -> ReconstructedMessage {
+        let message = StreamMessage {
+            stream_id: StreamId::new_random(&mut rand::rngs::OsRng),
+            kind,
+            chunk: OrderedMessage {
+                data: data.to_vec(),
+                index,
+            },
+        };
+        ReconstructedMessage {
+            message: message.into_bytes(),
+            reply_surb: None,
+        }
+    }
+
+    fn with_stream_id(
+        message: &ReconstructedMessage,
+        other: ReconstructedMessage,
+    ) -> ReconstructedMessage {
+        // magic, version and the stream id
+        let mut bytes = other.message;
+        bytes[..15].copy_from_slice(&message.message[..15]);
+        ReconstructedMessage {
+            message: bytes,
+            reply_surb: None,
+        }
+    }
+
+    #[test]
+    fn out_of_order_chunks_are_read_in_order() {
+        let (mut receiver, mut incoming) = StreamReceiver::new();
+
+        let first = stream_message(0, StreamMessageKind::Data, b"hello ");
+        let second = with_stream_id(&first, stream_message(1, StreamMessageKind::Data, b"world"));
+        let close = with_stream_id(&first, stream_message(2, StreamMessageKind::Close, &[]));
+
+        assert!(receiver.handle_message(close).is_none());
+        assert!(receiver.handle_message(second).is_none());
+        assert!(receiver.handle_message(first).is_none());
+        assert!(receiver.streams.is_empty());
+
+        let mut reader = incoming.try_next().unwrap().unwrap();
+        let mut received = Vec::new();
+        futures::executor::block_on(reader.read_to_end(&mut received)).unwrap();
+        assert_eq!(received, b"hello world");
+    }
+
+    #[test]
+    fn non_stream_messages_are_returned() {
+        let (mut receiver, _incoming) = StreamReceiver::new();
+        let message = ReconstructedMessage {
+            message: vec![1, 2, 3],
+            reply_surb: None,
+        };
+        assert!(receiver.handle_message(message).is_some());
+
+        // regular message that happens to be long enough and to have a valid 'kind' byte
+        let mut data = vec![0; 64];
+        data[8] = 1;
+        let message = ReconstructedMessage {
+            message: data.clone(),
+            reply_surb: None,
+        };
+        let returned = receiver.handle_message(message).unwrap();
+        assert_eq!(returned.message, data);
+        assert!(receiver.streams.is_empty());
+    }
+
+    #[test]
+    fn idle_streams_are_removed() {
+        let (receiver, mut incoming) = StreamReceiver::new();
+        let mut receiver = receiver.with_idle_timeout(Duration::ZERO);
+
+        let first = stream_message(0, StreamMessageKind::Data, b"hello ");
+        assert!(receiver.handle_message(first).is_none());
+        assert_eq!(receiver.streams.len(), 1);
+        assert_eq!(receiver.remove_idle_streams(), 1);
+        assert!(receiver.streams.is_empty());
+
+        // the data received so far is still available, but the stream is not just closed
+        let mut reader = incoming.try_next().unwrap().unwrap();
+        let mut received = [0; 6];
+        futures::executor::block_on(reader.read_exact(&mut received)).unwrap();
+        assert_eq!(&received, b"hello ");
+        let err = futures::executor::block_on(reader.read(&mut received)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+}
@@ -0,0 +1,156 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use super::message::{StreamId, StreamMessage, StreamMessageKind};
+use super::{DEFAULT_MAX_QUEUED_PACKETS, DEFAULT_STREAM_CHUNK_SIZE};
+use crate::client::inbound_messages::{InputMessage, InputMessageSender};
+use crate::client::real_messages_control::OutQueueLength;
+use futures::io::AsyncWrite;
+use futures::ready;
+use futures::task::{Context, Poll};
+use log::*;
+use nymsphinx::addressing::clients::Recipient;
+use nymsphinx::params::PacketSize;
+use ordered_buffer::OrderedMessageSender;
+use rand::rngs::OsRng;
+use std::io;
+use std::pin::Pin;
+
+/// Sending half of a stream to the specified `Recipient`.
+///
+/// The written bytes are buffered until `chunk_size` of them is accumulated (or the writer is
+/// flushed) and only then they are sent as a single mixnet message. Before pushing the next
+/// chunk, the writer waits until the number of packets queued for sending drops below
+/// the configured threshold, so the data is never read from the application faster than it can
+/// be sent into the mix network.
+pub struct MixnetStreamWriter {
+    stream_id: StreamId,
+    recipient: Recipient,
+    input_sender: InputMessageSender,
+    out_queue_length: OutQueueLength,
+
+    /// Number of bytes sent in a single mixnet message.
+    chunk_size: usize,
+
+    /// Maximum number of packets waiting to be sent out before the writer stops accepting more data.
+    max_queued_packets: usize,
+
+    /// Data written, but not yet sent.
+    buffer: Vec<u8>,
+    sequencer: OrderedMessageSender,
+    closed: bool,
+}
+
+impl MixnetStreamWriter {
+    pub fn new(
+        recipient: Recipient,
+        input_sender: InputMessageSender,
+        out_queue_length: OutQueueLength,
+    ) -> Self {
+        MixnetStreamWriter {
+            stream_id: StreamId::new_random(&mut OsRng),
+            recipient,
+            input_sender,
+            out_queue_length,
+            chunk_size: DEFAULT_STREAM_CHUNK_SIZE,
+            max_queued_packets: DEFAULT_MAX_QUEUED_PACKETS,
+            buffer: Vec::new(),
+            sequencer: OrderedMessageSender::new(),
+            closed: false,
+        }
+    }
+
+    #[must_use]
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    #[must_use]
+    pub fn with_max_queued_packets(mut self, max_queued_packets: usize) -> Self {
+        self.max_queued_packets = max_queued_packets.max(1);
+        self
+    }
+
+    pub fn stream_id(&self) -> StreamId {
+        self.stream_id
+    }
+
+    /// Waits for the out queue to have some space and sends the message of the specified kind
+    /// with all the currently buffered data.
+    fn poll_send(&mut self, cx: &mut Context<'_>, kind: StreamMessageKind) -> Poll<io::Result<()>> {
+        ready!(self
+            .out_queue_length
+            .poll_below(cx, self.max_queued_packets));
+
+        let data = std::mem::take(&mut self.buffer);
+        trace!(
+            "sending {} bytes of stream {} to {}",
+            data.len(),
+            self.stream_id,
+            self.recipient
+        );
+
+        let message = StreamMessage {
+            stream_id: self.stream_id,
+            kind,
+            chunk: self.sequencer.wrap_message(data),
+        };
+        let data = message.into_bytes();
+
+        // the message is going to be split into packets only once the client gets to it, so
+        // account for them right away, otherwise the queue length would not bound anything
+        let estimated_packets = data.len() / PacketSize::default().plaintext_size() + 1;
+        let reservation = self.out_queue_length.reserve(estimated_packets);
+        let input_message = InputMessage::new_fresh(self.recipient, data, false)
+            .with_queue_reservation(reservation);
+
+        self.input_sender
+            .unbounded_send(input_message)
+            .map_err(|_| {
+                io::Error::new(io::ErrorKind::BrokenPipe, "the mixnet client has stopped")
+            })?;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for MixnetStreamWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.closed {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+
+        if this.buffer.len() >= this.chunk_size {
+            ready!(this.poll_send(cx, StreamMessageKind::Data))?;
+        }
+
+        let written = buf.len().min(this.chunk_size - this.buffer.len());
+        this.buffer.extend_from_slice(&buf[..written]);
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.buffer.is_empty() {
+            return Poll::Ready(Ok(()));
+        }
+        this.poll_send(cx, StreamMessageKind::Data)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if self.closed {
+            return Poll::Ready(Ok(()));
+        }
+        ready!(self.as_mut().poll_flush(cx))?;
+
+        let this = self.get_mut();
+        ready!(this.poll_send(cx, StreamMessageKind::Close))?;
+        this.closed = true;
+        Poll::Ready(Ok(()))
+    }
+}
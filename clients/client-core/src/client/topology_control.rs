@@ -1,7 +1,9 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::client::topology_verification::{current_unix_timestamp, TopologyVerification};
 use crate::spawn_future;
+use futures::future::join_all;
 use log::*;
 use nymsphinx::addressing::clients::Recipient;
 use nymsphinx::params::DEFAULT_NUM_MIX_HOPS;
@@ -12,26 +14,35 @@ use std::sync::Arc;
 use std::time;
 use std::time::Duration;
 use tokio::sync::{RwLock, RwLockReadGuard};
-use topology::{nym_topology_from_detailed, NymTopology};
+use topology::{gateway, nym_topology_from_detailed, NymTopology};
 use url::Url;
 
 // I'm extremely curious why compiler NEVER complained about lack of Debug here before
 #[derive(Debug)]
-pub struct TopologyAccessorInner(Option<NymTopology>);
+pub struct TopologyAccessorInner {
+    topology: Option<NymTopology>,
+
+    /// Number of times the topology has been updated so far.
+    updates: u64,
+}
 
 impl AsRef<Option<NymTopology>> for TopologyAccessorInner {
     fn as_ref(&self) -> &Option<NymTopology> {
-        &self.0
+        &self.topology
     }
 }
 
 impl TopologyAccessorInner {
     fn new() -> Self {
-        TopologyAccessorInner(None)
+        TopologyAccessorInner {
+            topology: None,
+            updates: 0,
+        }
     }
 
     fn update(&mut self, new: Option<NymTopology>) {
-        self.0 = new;
+        self.topology = new;
+        self.updates += 1;
     }
 }
 
@@ -54,11 +65,12 @@ impl<'a> TopologyReadPermit<'a> {
         &'a self,
         ack_recipient: &Recipient,
         packet_recipient: Option<&Recipient>,
+        num_mix_hops: u8,
     ) -> Option<&'a NymTopology> {
         // Note: implicit deref with Deref for TopologyReadPermit is happening here
         let topology_ref_option = self.permit.as_ref();
         topology_ref_option.as_ref().filter(|topology_ref| {
-            !(!topology_ref.can_construct_path_through(DEFAULT_NUM_MIX_HOPS)
+            !(!topology_ref.can_construct_path_through(num_mix_hops)
                 || !topology_ref.gateway_exists(ack_recipient.gateway())
                 || if let Some(packet_recipient) = packet_recipient {
                     !topology_ref.gateway_exists(packet_recipient.gateway())
@@ -101,12 +113,28 @@ impl TopologyAccessor {
         self.inner.write().await.update(new_topology);
     }
 
+    /// Returns all gateways present in the current network topology (if any).
+    pub async fn current_gateways(&self) -> Vec<gateway::Node> {
+        self.current_gateways_with_update_count().await.0
+    }
+
+    /// Returns all gateways present in the current network topology (if any) alongside the number
+    /// of topology updates so far, which allows telling apart the subsequent refreshes.
+    pub async fn current_gateways_with_update_count(&self) -> (Vec<gateway::Node>, u64) {
+        let inner = self.inner.read().await;
+        let gateways = match &inner.topology {
+            None => Vec::new(),
+            Some(ref topology) => topology.gateways().to_vec(),
+        };
+        (gateways, inner.updates)
+    }
+
     // only used by the client at startup to get a slightly more reasonable error message
     // (currently displays as unused because health checker is disabled due to required changes)
-    pub async fn is_routable(&self) -> bool {
-        match &self.inner.read().await.0 {
+    pub async fn is_routable(&self, num_mix_hops: u8) -> bool {
+        match &self.inner.read().await.topology {
             None => false,
-            Some(ref topology) => topology.can_construct_path_through(DEFAULT_NUM_MIX_HOPS),
+            Some(ref topology) => topology.can_construct_path_through(num_mix_hops),
         }
     }
 }
@@ -121,6 +149,8 @@ pub struct TopologyRefresherConfig {
     validator_api_urls: Vec<Url>,
     refresh_rate: time::Duration,
     client_version: String,
+    num_mix_hops: u8,
+    verification: Option<TopologyVerification>,
 }
 
 impl TopologyRefresherConfig {
@@ -133,17 +163,43 @@ impl TopologyRefresherConfig {
             validator_api_urls,
             refresh_rate,
             client_version,
+            num_mix_hops: DEFAULT_NUM_MIX_HOPS,
+            verification: None,
         }
     }
+
+    /// Allows setting non-default number of mix hops the obtained topology has to support.
+    #[must_use]
+    pub fn with_mix_hops(mut self, hops: u8) -> Self {
+        self.num_mix_hops = hops;
+        self
+    }
+
+    /// Allows requiring the topology to be signed by the trusted validator APIs.
+    /// If `None` is provided, the obtained topology is not verified.
+    #[must_use]
+    pub fn with_topology_verification(
+        mut self,
+        verification: Option<TopologyVerification>,
+    ) -> Self {
+        self.verification = verification;
+        self
+    }
 }
 
 pub struct TopologyRefresher {
     validator_client: validator_client::ApiClient,
     client_version: String,
 
+    /// If specified, the topology is obtained from all validator APIs at once
+    /// (using `verifying_clients`) and has to be signed by the trusted ones.
+    verification: Option<TopologyVerification>,
+    verifying_clients: Vec<validator_client::ApiClient>,
+
     validator_api_urls: Vec<Url>,
     topology_accessor: TopologyAccessor,
     refresh_rate: Duration,
+    num_mix_hops: u8,
 
     currently_used_api: usize,
     was_latest_valid: bool,
@@ -153,12 +209,25 @@ impl TopologyRefresher {
     pub fn new(mut cfg: TopologyRefresherConfig, topology_accessor: TopologyAccessor) -> Self {
         cfg.validator_api_urls.shuffle(&mut thread_rng());
 
+        let verifying_clients = if cfg.verification.is_some() {
+            cfg.validator_api_urls
+                .iter()
+                .cloned()
+                .map(validator_client::ApiClient::new)
+                .collect()
+        } else {
+            Vec::new()
+        };
+
         TopologyRefresher {
             validator_client: validator_client::ApiClient::new(cfg.validator_api_urls[0].clone()),
             client_version: cfg.client_version,
+            verification: cfg.verification,
+            verifying_clients,
             validator_api_urls: cfg.validator_api_urls,
             topology_accessor,
             refresh_rate: cfg.refresh_rate,
+            num_mix_hops: cfg.num_mix_hops,
             currently_used_api: 0,
             was_latest_valid: true,
         }
@@ -234,7 +303,9 @@ impl TopologyRefresher {
         true
     }
 
-    async fn get_current_compatible_topology(&self) -> Option<NymTopology> {
+    /// Obtains the topology from the currently used validator API without verifying its origin.
+    /// Returns the topology alongside the total number of active mixnodes.
+    async fn get_unverified_topology(&self) -> Option<(NymTopology, usize)> {
         // TODO: optimization for the future:
         // only refresh mixnodes on timer and refresh gateways only when
         // we have to send to a new, unknown, gateway
@@ -255,9 +326,83 @@ impl TopologyRefresher {
             Ok(gateways) => gateways,
         };
 
+        // the epoch is only used for choosing the correct sphinx key of nodes that are in the
+        // middle of rotating them, so failing to obtain it is not critical
+        let current_epoch = match self.validator_client.get_cached_current_epoch().await {
+            Err(err) => {
+                warn!("failed to get the current epoch - {}", err);
+                None
+            }
+            Ok(interval) => interval.map(|interval| interval.current_epoch_absolute_id()),
+        };
+
         let mixnodes_count = mixnodes.len();
-        let topology = nym_topology_from_detailed(mixnodes, gateways)
+        let mut topology = nym_topology_from_detailed(mixnodes, gateways)
             .filter_system_version(&self.client_version);
+        if let Some(epoch) = current_epoch {
+            topology.set_epoch(epoch);
+        }
+
+        Some((topology, mixnodes_count))
+    }
+
+    /// Obtains the signed topology from all validator APIs and returns the one that
+    /// the required number of trusted validator APIs agree on.
+    /// Returns the topology alongside the total number of active mixnodes.
+    async fn get_verified_topology(
+        &self,
+        verification: &TopologyVerification,
+    ) -> Option<(NymTopology, usize)> {
+        let responses = join_all(
+            self.verifying_clients
+                .iter()
+                .map(|client| client.get_signed_topology()),
+        )
+        .await;
+
+        let now = current_unix_timestamp();
+        let verified = responses
+            .into_iter()
+            .zip(self.validator_api_urls.iter())
+            .filter_map(|(response, url)| match response {
+                Err(err) => {
+                    warn!("failed to get signed topology from {} - {}", url, err);
+                    None
+                }
+                Ok(response) => match verification.verify(&response, now) {
+                    Err(err) => {
+                        warn!("the topology obtained from {} is not valid - {}", url, err);
+                        None
+                    }
+                    Ok(verified) => Some(verified),
+                },
+            })
+            .collect();
+
+        let payload = match verification.select_agreed(verified) {
+            Some(payload) => payload,
+            None => {
+                error!(
+                    "fewer than {} trusted validator APIs agree on the current topology",
+                    verification.required_signatures()
+                );
+                return None;
+            }
+        };
+
+        let mixnodes_count = payload.mixnodes.len();
+        let mut topology = nym_topology_from_detailed(payload.mixnodes, payload.gateways)
+            .filter_system_version(&self.client_version);
+        topology.set_epoch(payload.absolute_epoch_id);
+
+        Some((topology, mixnodes_count))
+    }
+
+    async fn get_current_compatible_topology(&self) -> Option<NymTopology> {
+        let (topology, mixnodes_count) = match &self.verification {
+            Some(verification) => self.get_verified_topology(verification).await?,
+            None => self.get_unverified_topology().await?,
+        };
 
         if !self.check_layer_distribution(&topology, mixnodes_count) {
             warn!("The current filtered active topology has extremely skewed layer distribution. It cannot be used.");
@@ -291,7 +436,7 @@ impl TopologyRefresher {
     }
 
     pub async fn is_topology_routable(&self) -> bool {
-        self.topology_accessor.is_routable().await
+        self.topology_accessor.is_routable(self.num_mix_hops).await
     }
 
     #[cfg(not(target_arch = "wasm32"))]
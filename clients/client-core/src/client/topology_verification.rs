@@ -0,0 +1,342 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::config::Config;
+use crate::error::ClientCoreError;
+use config::NymConfig;
+use crypto::asymmetric::identity::{self, Ed25519RecoveryError};
+use std::collections::HashSet;
+use std::time::Duration;
+use validator_client::models::{SignedTopologyResponse, TopologyPayload};
+
+#[derive(Debug, thiserror::Error)]
+pub enum TopologyVerificationError {
+    #[error("the topology has been signed with an untrusted key {0}")]
+    UntrustedSigner(String),
+
+    #[error("the signer key is malformed - {0}")]
+    MalformedSigner(Ed25519RecoveryError),
+
+    #[error("the signature is malformed - {0}")]
+    MalformedSignature(Ed25519RecoveryError),
+
+    #[error("the signature is invalid")]
+    InvalidSignature,
+
+    #[error("the topology payload is malformed - {0}")]
+    MalformedPayload(#[from] serde_json::Error),
+
+    #[error("the topology is outdated - it was created {age:?} ago")]
+    OutdatedTopology { age: Duration },
+}
+
+pub(crate) fn current_unix_timestamp() -> i64 {
+    #[cfg(not(target_arch = "wasm32"))]
+    use std::time::{SystemTime, UNIX_EPOCH};
+    #[cfg(target_arch = "wasm32")]
+    use wasm_timer::{SystemTime, UNIX_EPOCH};
+
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("the system clock is set before the unix epoch")
+        .as_secs() as i64
+}
+
+/// Properties of a node that affect how packets are routed through it.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct RoutingNode {
+    identity_key: String,
+    sphinx_key: String,
+    pending_sphinx_key: Option<(String, u32)>,
+    layer: Option<u8>,
+    host: String,
+    mix_port: u16,
+    clients_port: Option<u16>,
+    version: String,
+}
+
+/// Part of the topology that has to be agreed upon by the validator APIs. The remaining data,
+/// such as stake or timestamps, is naturally going to differ between them and is irrelevant
+/// for the purposes of routing packets.
+#[derive(Debug, PartialEq, Eq)]
+struct RoutingView {
+    absolute_epoch_id: u32,
+    mixnodes: Vec<RoutingNode>,
+    gateways: Vec<RoutingNode>,
+}
+
+impl From<&TopologyPayload> for RoutingView {
+    fn from(payload: &TopologyPayload) -> Self {
+        let mut mixnodes = payload
+            .mixnodes
+            .iter()
+            .map(|details| {
+                let bond = &details.bond_information;
+                RoutingNode {
+                    identity_key: bond.mix_node.identity_key.clone(),
+                    sphinx_key: bond.mix_node.sphinx_key.clone(),
+                    pending_sphinx_key: bond
+                        .pending_sphinx_key
+                        .as_ref()
+                        .map(|pending| (pending.sphinx_key.clone(), pending.valid_from_epoch)),
+                    layer: Some(bond.layer as u8),
+                    host: bond.mix_node.host.clone(),
+                    mix_port: bond.mix_node.mix_port,
+                    clients_port: None,
+                    version: bond.mix_node.version.clone(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut gateways = payload
+            .gateways
+            .iter()
+            .map(|bond| RoutingNode {
+                identity_key: bond.gateway.identity_key.clone(),
+                sphinx_key: bond.gateway.sphinx_key.clone(),
+                pending_sphinx_key: bond
+                    .pending_sphinx_key
+                    .as_ref()
+                    .map(|pending| (pending.sphinx_key.clone(), pending.valid_from_epoch)),
+                layer: None,
+                host: bond.gateway.host.clone(),
+                mix_port: bond.gateway.mix_port,
+                clients_port: Some(bond.gateway.clients_port),
+                version: bond.gateway.version.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        // the validator APIs are not guaranteed to return the nodes in the same order
+        mixnodes.sort();
+        gateways.sort();
+
+        RoutingView {
+            absolute_epoch_id: payload.absolute_epoch_id,
+            mixnodes,
+            gateways,
+        }
+    }
+}
+
+/// Requirements that the topology obtained from the validator APIs has to satisfy before
+/// the client starts using it.
+#[derive(Clone, Debug)]
+pub struct TopologyVerification {
+    /// Identity keys of the validator APIs whose signatures are accepted.
+    trusted_keys: Vec<identity::PublicKey>,
+
+    /// Number of distinct trusted validator APIs that have to agree on the topology.
+    required_signatures: usize,
+
+    /// Maximum age of a signed topology for it to still be considered valid.
+    max_age: Duration,
+}
+
+impl TopologyVerification {
+    pub fn new(
+        trusted_keys: Vec<identity::PublicKey>,
+        required_signatures: usize,
+        max_age: Duration,
+    ) -> Self {
+        TopologyVerification {
+            trusted_keys,
+            required_signatures: required_signatures.max(1),
+            max_age,
+        }
+    }
+
+    /// Creates the verification requirements out of the client configuration.
+    /// Returns `None` if no trusted validator API keys are configured,
+    /// in which case the topology responses are not verified at all.
+    pub fn try_from_config<T: NymConfig>(
+        config: &Config<T>,
+    ) -> Result<Option<Self>, ClientCoreError> {
+        let raw_keys = config.get_trusted_validator_api_keys();
+        if raw_keys.is_empty() {
+            return Ok(None);
+        }
+
+        let trusted_keys = raw_keys
+            .iter()
+            .map(identity::PublicKey::from_base58_string)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // we'd never be able to accept any topology
+        let required_signatures = config.get_required_topology_signatures();
+        if required_signatures > trusted_keys.len() {
+            return Err(ClientCoreError::UnsatisfiableTopologySignatures {
+                required: required_signatures,
+                trusted: trusted_keys.len(),
+            });
+        }
+
+        Ok(Some(TopologyVerification::new(
+            trusted_keys,
+            required_signatures,
+            config.get_max_topology_age(),
+        )))
+    }
+
+    pub fn required_signatures(&self) -> usize {
+        self.required_signatures
+    }
+
+    /// Checks whether the response has been signed by one of the trusted validator APIs and
+    /// whether the topology it contains is recent enough.
+    pub(crate) fn verify(
+        &self,
+        response: &SignedTopologyResponse,
+        now: i64,
+    ) -> Result<(identity::PublicKey, TopologyPayload), TopologyVerificationError> {
+        let signer = identity::PublicKey::from_base58_string(&response.signer)
+            .map_err(TopologyVerificationError::MalformedSigner)?;
+        if !self.trusted_keys.contains(&signer) {
+            return Err(TopologyVerificationError::UntrustedSigner(
+                response.signer.clone(),
+            ));
+        }
+
+        let signature = identity::Signature::from_base58_string(&response.signature)
+            .map_err(TopologyVerificationError::MalformedSignature)?;
+        signer
+            .verify(response.payload.as_bytes(), &signature)
+            .map_err(|_| TopologyVerificationError::InvalidSignature)?;
+
+        let payload: TopologyPayload = serde_json::from_str(&response.payload)?;
+        let age = Duration::from_secs(now.saturating_sub(payload.timestamp).max(0) as u64);
+        if age > self.max_age {
+            return Err(TopologyVerificationError::OutdatedTopology { age });
+        }
+
+        Ok((signer, payload))
+    }
+
+    /// Out of the verified topologies, chooses the one that has been signed by at least the
+    /// required number of distinct validator APIs. If there are multiple such topologies,
+    /// the one from the most recent epoch is chosen.
+    pub(crate) fn select_agreed(
+        &self,
+        verified: Vec<(identity::PublicKey, TopologyPayload)>,
+    ) -> Option<TopologyPayload> {
+        let mut groups: Vec<(RoutingView, HashSet<String>, TopologyPayload)> = Vec::new();
+
+        for (signer, payload) in verified {
+            let view = RoutingView::from(&payload);
+            match groups.iter_mut().find(|(existing, ..)| existing == &view) {
+                Some((_, signers, latest)) => {
+                    signers.insert(signer.to_base58_string());
+                    if payload.timestamp > latest.timestamp {
+                        *latest = payload;
+                    }
+                }
+                None => {
+                    let signers = std::iter::once(signer.to_base58_string()).collect();
+                    groups.push((view, signers, payload))
+                }
+            }
+        }
+
+        groups
+            .into_iter()
+            .filter(|(_, signers, _)| signers.len() >= self.required_signatures)
+            .map(|(_, _, payload)| payload)
+            .max_by_key(|payload| (payload.absolute_epoch_id, payload.timestamp))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    const MAX_AGE: Duration = Duration::from_secs(600);
+    const NOW: i64 = 1_667_304_000;
+
+    fn payload(absolute_epoch_id: u32, timestamp: i64) -> TopologyPayload {
+        TopologyPayload {
+            absolute_epoch_id,
+            timestamp,
+            mixnodes: Vec::new(),
+            gateways: Vec::new(),
+        }
+    }
+
+    fn sign(keys: &identity::KeyPair, payload: &TopologyPayload) -> SignedTopologyResponse {
+        let payload = serde_json::to_string(payload).unwrap();
+        SignedTopologyResponse {
+            signature: keys
+                .private_key()
+                .sign(payload.as_bytes())
+                .to_base58_string(),
+            payload,
+            signer: keys.public_key().to_base58_string(),
+        }
+    }
+
+    fn verification(keys: &[&identity::KeyPair], required: usize) -> TopologyVerification {
+        TopologyVerification::new(
+            keys.iter().map(|keys| *keys.public_key()).collect(),
+            required,
+            MAX_AGE,
+        )
+    }
+
+    #[test]
+    fn only_fresh_topologies_signed_by_trusted_keys_are_accepted() {
+        let trusted = identity::KeyPair::new(&mut OsRng);
+        let untrusted = identity::KeyPair::new(&mut OsRng);
+        let verification = verification(&[&trusted], 1);
+
+        let valid = sign(&trusted, &payload(42, NOW - 10));
+        let (signer, verified) = verification.verify(&valid, NOW).unwrap();
+        assert_eq!(signer, *trusted.public_key());
+        assert_eq!(verified.absolute_epoch_id, 42);
+
+        assert!(matches!(
+            verification.verify(&sign(&untrusted, &payload(42, NOW)), NOW),
+            Err(TopologyVerificationError::UntrustedSigner(_))
+        ));
+
+        let mut tampered = valid;
+        tampered.payload = serde_json::to_string(&payload(43, NOW - 10)).unwrap();
+        assert!(matches!(
+            verification.verify(&tampered, NOW),
+            Err(TopologyVerificationError::InvalidSignature)
+        ));
+
+        let outdated = sign(&trusted, &payload(42, NOW - 601));
+        assert!(matches!(
+            verification.verify(&outdated, NOW),
+            Err(TopologyVerificationError::OutdatedTopology { .. })
+        ));
+    }
+
+    #[test]
+    fn topology_requires_agreement_of_distinct_signers() {
+        let first = identity::KeyPair::new(&mut OsRng);
+        let second = identity::KeyPair::new(&mut OsRng);
+        let verification = verification(&[&first, &second], 2);
+
+        // the same validator API behind multiple urls counts only once
+        let duplicated = vec![
+            (*first.public_key(), payload(42, NOW)),
+            (*first.public_key(), payload(42, NOW)),
+        ];
+        assert!(verification.select_agreed(duplicated).is_none());
+
+        let disagreeing = vec![
+            (*first.public_key(), payload(42, NOW)),
+            (*second.public_key(), payload(43, NOW)),
+        ];
+        assert!(verification.select_agreed(disagreeing).is_none());
+
+        // the timestamps are irrelevant for the agreement, but the latest payload is used
+        let agreeing = vec![
+            (*first.public_key(), payload(42, NOW - 20)),
+            (*second.public_key(), payload(42, NOW - 10)),
+        ];
+        let agreed = verification.select_agreed(agreeing).unwrap();
+        assert_eq!(agreed.absolute_epoch_id, 42);
+        assert_eq!(agreed.timestamp, NOW - 10);
+    }
+}
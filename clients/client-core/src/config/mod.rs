@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use config::NymConfig;
+use nymsphinx::params::{DEFAULT_NUM_MIX_HOPS, MAX_NUM_MIX_HOPS};
 use serde::{Deserialize, Serialize};
 use std::marker::PhantomData;
 use std::path::PathBuf;
@@ -28,11 +29,31 @@ const DEFAULT_TOPOLOGY_RESOLUTION_TIMEOUT: Duration = Duration::from_millis(5_00
 // bought bandwidth tokens to not have time to be spent; Once we remove the gateway from the
 // bandwidth bridging protocol, we can come back to a smaller timeout value
 const DEFAULT_GATEWAY_RESPONSE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+const DEFAULT_RECONSTRUCTION_MEMORY_BUDGET: usize = 256 * 1024 * 1024; // 256MB
+const DEFAULT_INCOMPLETE_MESSAGE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+const DEFAULT_MAXIMUM_RETRANSMISSIONS: usize = 10;
+const DEFAULT_GATEWAY_SELECTION_SAMPLE_SIZE: usize = 10;
+const DEFAULT_GATEWAY_PROBE_TIMEOUT: Duration = Duration::from_millis(2_000);
+const DEFAULT_GATEWAY_FAILOVER_THRESHOLD: usize = 10;
+const DEFAULT_MAX_TOPOLOGY_AGE: Duration = Duration::from_secs(10 * 60);
+const DEFAULT_REQUIRED_TOPOLOGY_SIGNATURES: usize = 1;
 
 pub fn missing_string_value() -> String {
     MISSING_VALUE.to_string()
 }
 
+fn default_required_topology_signatures() -> usize {
+    DEFAULT_REQUIRED_TOPOLOGY_SIGNATURES
+}
+
+/// Configuration of a particular client (like the native or the socks5 one) that embeds
+/// the base client configuration.
+pub trait ClientCoreConfigTrait: NymConfig {
+    fn get_base(&self) -> &Config<Self>;
+
+    fn get_base_mut(&mut self) -> &mut Config<Self>;
+}
+
 #[derive(Debug, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Config<T> {
@@ -109,6 +130,30 @@ impl<T: NymConfig> Config<T> {
             self.client.database_path = self::Client::<T>::default_database_path(&id);
         }
 
+        if self
+            .client
+            .reconstruction_buffer_directory
+            .as_os_str()
+            .is_empty()
+        {
+            self.client.reconstruction_buffer_directory =
+                self::Client::<T>::default_reconstruction_buffer_directory(&id);
+        }
+
+        if self
+            .client
+            .outbound_journal_directory
+            .as_os_str()
+            .is_empty()
+        {
+            self.client.outbound_journal_directory =
+                self::Client::<T>::default_outbound_journal_directory(&id);
+        }
+
+        if self.client.gateway_ranking_file.as_os_str().is_empty() {
+            self.client.gateway_ranking_file = self::Client::<T>::default_gateway_ranking_file(&id);
+        }
+
         self.client.id = id;
     }
 
@@ -182,6 +227,14 @@ impl<T: NymConfig> Config<T> {
         self.client.validator_api_urls.clone()
     }
 
+    pub fn get_trusted_validator_api_keys(&self) -> &[String] {
+        &self.client.trusted_validator_api_keys
+    }
+
+    pub fn get_required_topology_signatures(&self) -> usize {
+        self.client.required_topology_signatures
+    }
+
     pub fn get_gateway_id(&self) -> String {
         self.client.gateway_endpoint.gateway_id.clone()
     }
@@ -202,6 +255,49 @@ impl<T: NymConfig> Config<T> {
         self.client.database_path.clone()
     }
 
+    pub fn get_reconstruction_buffer_directory(&self) -> PathBuf {
+        // configs created before the field got introduced would not have it set
+        if self
+            .client
+            .reconstruction_buffer_directory
+            .as_os_str()
+            .is_empty()
+        {
+            self::Client::<T>::default_reconstruction_buffer_directory(&self.client.id)
+        } else {
+            self.client.reconstruction_buffer_directory.clone()
+        }
+    }
+
+    pub fn get_outbound_journal_directory(&self) -> PathBuf {
+        // configs created before the field got introduced would not have it set
+        if self
+            .client
+            .outbound_journal_directory
+            .as_os_str()
+            .is_empty()
+        {
+            self::Client::<T>::default_outbound_journal_directory(&self.client.id)
+        } else {
+            self.client.outbound_journal_directory.clone()
+        }
+    }
+
+    pub fn get_gateway_ranking_file(&self) -> PathBuf {
+        // configs created before the field got introduced would not have it set
+        if self.client.gateway_ranking_file.as_os_str().is_empty() {
+            self::Client::<T>::default_gateway_ranking_file(&self.client.id)
+        } else {
+            self.client.gateway_ranking_file.clone()
+        }
+    }
+
+    /// Path to the file containing the redirect record published when the client last migrated
+    /// to a different gateway.
+    pub fn get_gateway_redirect_file(&self) -> PathBuf {
+        T::default_data_directory(Some(&self.client.id)).join("gateway_redirect")
+    }
+
     // Debug getters
     pub fn get_average_packet_delay(&self) -> Duration {
         self.debug.average_packet_delay
@@ -251,6 +347,66 @@ impl<T: NymConfig> Config<T> {
         self.debug.use_extended_packet_size
     }
 
+    /// Returns the number of mix hops each sent packet is going to go through, clamped to
+    /// the range supported by the sphinx packet format.
+    pub fn get_num_mix_hops(&self) -> u8 {
+        self.debug.num_mix_hops.clamp(1, MAX_NUM_MIX_HOPS)
+    }
+
+    pub fn get_use_disk_backed_reconstruction(&self) -> bool {
+        self.debug.use_disk_backed_reconstruction
+    }
+
+    pub fn get_reconstruction_memory_budget(&self) -> usize {
+        self.debug.reconstruction_memory_budget
+    }
+
+    pub fn get_incomplete_message_timeout(&self) -> Duration {
+        self.debug.incomplete_message_timeout
+    }
+
+    pub fn get_gateway_selection_sample_size(&self) -> usize {
+        self.debug.gateway_selection_sample_size
+    }
+
+    pub fn get_gateway_probe_timeout(&self) -> Duration {
+        self.debug.gateway_probe_timeout
+    }
+
+    pub fn get_disabled_gateway_failover(&self) -> bool {
+        self.debug.disable_gateway_failover
+    }
+
+    pub fn get_gateway_failover_threshold(&self) -> usize {
+        self.debug.gateway_failover_threshold
+    }
+
+    pub fn get_max_topology_age(&self) -> Duration {
+        self.debug.max_topology_age
+    }
+
+    /// Returns the ratio of parity to data fragments of sent messages, if erasure coding is enabled.
+    pub fn get_erasure_coding_redundancy(&self) -> Option<f64> {
+        let redundancy = self.debug.erasure_coding_redundancy;
+        (redundancy > 0.0).then(|| redundancy)
+    }
+
+    /// Returns the number of retransmissions after which a message is abandoned, if there's any limit.
+    pub fn get_maximum_retransmissions(&self) -> Option<usize> {
+        let maximum = self.debug.maximum_retransmissions;
+        (maximum > 0).then(|| maximum)
+    }
+
+    /// Returns the amount of time after which unacknowledged messages are abandoned, if there's any limit.
+    pub fn get_message_timeout(&self) -> Option<Duration> {
+        let timeout = self.debug.message_timeout;
+        (!timeout.is_zero()).then(|| timeout)
+    }
+
+    pub fn get_use_outbound_journal(&self) -> bool {
+        self.debug.use_outbound_journal
+    }
+
     pub fn get_version(&self) -> &str {
         &self.client.version
     }
@@ -277,6 +433,7 @@ pub struct GatewayEndpoint {
     pub gateway_owner: String,
 
     /// Address of the gateway listener to which all client requests should be sent.
+    /// The `tcp://` scheme (rather than `ws://`) makes the client use raw TCP instead of WebSocket.
     pub gateway_listener: String,
 }
 
@@ -308,6 +465,16 @@ pub struct Client<T> {
     /// Addresses to APIs running on validator from which the client gets the view of the network.
     validator_api_urls: Vec<Url>,
 
+    /// Base58-encoded identity keys of the validator APIs whose signed topology responses
+    /// are trusted. If empty, the topology is used without verifying its origin.
+    #[serde(default)]
+    trusted_validator_api_keys: Vec<String>,
+
+    /// Number of distinct trusted validator APIs that have to agree on the network topology
+    /// before the client starts using it. Only applicable if any trusted keys are specified.
+    #[serde(default = "default_required_topology_signatures")]
+    required_topology_signatures: usize,
+
     /// Path to file containing private identity key.
     private_identity_key_file: PathBuf,
 
@@ -338,6 +505,21 @@ pub struct Client<T> {
     /// Path to the database containing bandwidth credentials of this client.
     database_path: PathBuf,
 
+    /// Path to the directory used for storing fragments of partially received messages
+    /// (if disk-backed reconstruction is enabled). The fragments are stored in plaintext.
+    #[serde(default)]
+    reconstruction_buffer_directory: PathBuf,
+
+    /// Path to the directory used for storing sent messages that have not yet been
+    /// acknowledged (if the outbound journal is enabled).
+    #[serde(default)]
+    outbound_journal_directory: PathBuf,
+
+    /// Path to the file containing the latest ranking of gateways, measured when the client
+    /// has automatically chosen its gateway.
+    #[serde(default)]
+    gateway_ranking_file: PathBuf,
+
     /// nym_home_directory specifies absolute path to the home nym Clients directory.
     /// It is expected to use default value and hence .toml file should not redefine this field.
     nym_root_directory: PathBuf,
@@ -354,6 +536,8 @@ impl<T: NymConfig> Default for Client<T> {
             id: "".to_string(),
             disabled_credentials_mode: true,
             validator_api_urls: vec![],
+            trusted_validator_api_keys: vec![],
+            required_topology_signatures: DEFAULT_REQUIRED_TOPOLOGY_SIGNATURES,
             private_identity_key_file: Default::default(),
             public_identity_key_file: Default::default(),
             private_encryption_key_file: Default::default(),
@@ -363,6 +547,9 @@ impl<T: NymConfig> Default for Client<T> {
             reply_encryption_key_store_path: Default::default(),
             gateway_endpoint: Default::default(),
             database_path: Default::default(),
+            reconstruction_buffer_directory: Default::default(),
+            outbound_journal_directory: Default::default(),
+            gateway_ranking_file: Default::default(),
             nym_root_directory: T::default_root_directory(),
             super_struct: Default::default(),
         }
@@ -400,6 +587,18 @@ impl<T: NymConfig> Client<T> {
     fn default_database_path(id: &str) -> PathBuf {
         T::default_data_directory(Some(id)).join("db.sqlite")
     }
+
+    fn default_reconstruction_buffer_directory(id: &str) -> PathBuf {
+        T::default_data_directory(Some(id)).join("reconstruction_buffer")
+    }
+
+    fn default_outbound_journal_directory(id: &str) -> PathBuf {
+        T::default_data_directory(Some(id)).join("outbound_journal")
+    }
+
+    fn default_gateway_ranking_file(id: &str) -> PathBuf {
+        T::default_data_directory(Some(id)).join("gateway_ranking.json")
+    }
 }
 
 #[derive(Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
@@ -472,6 +671,69 @@ pub struct Debug {
 
     /// Controls whether the sent sphinx packet use the NON-DEFAULT bigger size.
     pub use_extended_packet_size: bool,
+
+    /// Number of mix hops (excluding the gateway) each sent packet, acknowledgement and reply
+    /// SURB is going to go through. Values above 3 make the route revisit mix layers.
+    /// It can't exceed [MAX_NUM_MIX_HOPS].
+    pub num_mix_hops: u8,
+
+    /// Controls whether fragments of partially received messages should be stored on disk,
+    /// so that they would not need to be kept in memory and the reconstruction could be resumed
+    /// after the client restarts.
+    /// Note that the fragments are stored unencrypted, i.e. the content of the received messages
+    /// is written to disk in plaintext.
+    pub use_disk_backed_reconstruction: bool,
+
+    /// Maximum amount of memory (in bytes) used for holding fragments of partially received
+    /// messages. Once exceeded, the least recently updated fragment sets are moved to disk
+    /// or, if disk-backed reconstruction is disabled, dropped.
+    pub reconstruction_memory_budget: usize,
+
+    /// Amount of time after which a partially received message that has not received any new
+    /// fragments is assumed to be lost and is removed.
+    #[serde(with = "humantime_serde")]
+    pub incomplete_message_timeout: Duration,
+
+    /// Ratio of parity to data fragments attached to each sent message, allowing the recipient
+    /// to reconstruct it despite some of the packets being lost, without waiting for
+    /// retransmissions. For example 0.25 adds a single parity fragment for every four data ones.
+    /// Setting it to 0 disables the erasure coding.
+    pub erasure_coding_redundancy: f64,
+
+    /// Number of times a fragment is retransmitted before the client gives up on delivering
+    /// the message it belongs to. Setting it to 0 makes the client retransmit indefinitely.
+    pub maximum_retransmissions: usize,
+
+    /// Amount of time after which the client gives up on delivering a message that still has
+    /// not been acknowledged. Setting it to 0 disables the timeout.
+    #[serde(with = "humantime_serde")]
+    pub message_timeout: Duration,
+
+    /// Controls whether sent messages should be stored on disk until they're acknowledged,
+    /// so that the ones that were not delivered before the client got shut down would be sent
+    /// again once it restarts.
+    pub use_outbound_journal: bool,
+
+    /// Number of gateways probed when the client is choosing its gateway automatically.
+    pub gateway_selection_sample_size: usize,
+
+    /// Maximum amount of time we're willing to wait for a handshake with a probed gateway
+    /// before considering it unreachable.
+    #[serde(with = "humantime_serde")]
+    pub gateway_probe_timeout: Duration,
+
+    /// Controls whether the client should stick with its gateway even if it seems to be gone,
+    /// rather than moving to a different one.
+    pub disable_gateway_failover: bool,
+
+    /// Number of consecutive failures to send packets to the gateway after which it is assumed
+    /// to be gone and the client moves to a different one.
+    pub gateway_failover_threshold: usize,
+
+    /// Maximum age of a signed topology obtained from the validator APIs
+    /// for it to still be accepted.
+    #[serde(with = "humantime_serde")]
+    pub max_topology_age: Duration,
 }
 
 impl Default for Debug {
@@ -489,6 +751,19 @@ impl Default for Debug {
             disable_loop_cover_traffic_stream: false,
             disable_main_poisson_packet_distribution: false,
             use_extended_packet_size: false,
+            num_mix_hops: DEFAULT_NUM_MIX_HOPS,
+            use_disk_backed_reconstruction: false,
+            reconstruction_memory_budget: DEFAULT_RECONSTRUCTION_MEMORY_BUDGET,
+            incomplete_message_timeout: DEFAULT_INCOMPLETE_MESSAGE_TIMEOUT,
+            erasure_coding_redundancy: 0.0,
+            maximum_retransmissions: DEFAULT_MAXIMUM_RETRANSMISSIONS,
+            message_timeout: Duration::ZERO,
+            use_outbound_journal: false,
+            gateway_selection_sample_size: DEFAULT_GATEWAY_SELECTION_SAMPLE_SIZE,
+            gateway_probe_timeout: DEFAULT_GATEWAY_PROBE_TIMEOUT,
+            disable_gateway_failover: false,
+            gateway_failover_threshold: DEFAULT_GATEWAY_FAILOVER_THRESHOLD,
+            max_topology_age: DEFAULT_MAX_TOPOLOGY_AGE,
         }
     }
 }
@@ -1,6 +1,8 @@
 // Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+#[cfg(feature = "reply-surb")]
+use crate::client::reply_key_storage::ReplyKeyStorageError;
 use crypto::asymmetric::identity::Ed25519RecoveryError;
 use gateway_client::error::GatewayClientError;
 use validator_client::ValidatorClientError;
@@ -20,10 +22,19 @@ pub enum ClientCoreError {
     NoGatewayWithId(String),
     #[error("No gateways on network")]
     NoGatewaysOnNetwork,
+    #[error("None of the probed gateways could be reached")]
+    NoReachableGateways,
     #[error("List of validator apis is empty")]
     ListOfValidatorApisIsEmpty,
     #[error("Could not load existing gateway configuration: {0}")]
     CouldNotLoadExistingGatewayConfiguration(std::io::Error),
     #[error("The current network topology seem to be insufficient to route any packets through")]
     InsufficientNetworkTopology,
+    #[error("The gateway details are missing from the config - did you run `init`?")]
+    MissingGatewayDetails,
+    #[cfg(feature = "reply-surb")]
+    #[error("Could not load the reply key storage: {0:?}")]
+    ReplyKeyStorageError(ReplyKeyStorageError),
+    #[error("{required} topology signatures are required, but only {trusted} validator API keys are trusted")]
+    UnsatisfiableTopologySignatures { required: usize, trusted: usize },
 }
@@ -0,0 +1,356 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Automatic choice of the gateway, based on how quickly it responds to the client and how
+//! reliable it has been according to the network monitor.
+
+use crate::config::GatewayEndpoint;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+use topology::gateway;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Lower bound on the reliability used for scoring gateways, so that the ones without any
+/// recorded uptime are heavily penalised rather than excluded altogether.
+const MIN_RELIABILITY: f64 = 0.05;
+
+/// Period of the network monitor results taken into account when determining gateway reliability.
+#[cfg(not(target_arch = "wasm32"))]
+const RELIABILITY_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct RankedGateway {
+    pub endpoint: GatewayEndpoint,
+
+    /// Location of the gateway, as announced by its operator.
+    pub location: String,
+
+    /// Time it took to establish the connection with the gateway, in milliseconds.
+    pub rtt_ms: u64,
+
+    /// Reliability relative to the most reliable of the probed gateways, in the range [0, 1].
+    pub reliability: f64,
+
+    /// Score of the gateway used for the ranking. The lower the better.
+    pub score: f64,
+}
+
+/// Gateways that responded to the probes, ordered from the best to the worst one.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct GatewayRanking {
+    /// Region hint used when choosing the probed gateways, if any.
+    pub region: Option<String>,
+
+    /// Unix timestamp of when the ranking was created.
+    pub created_at: u64,
+
+    gateways: Vec<RankedGateway>,
+}
+
+impl GatewayRanking {
+    pub fn new(region: Option<String>, created_at: u64, mut gateways: Vec<RankedGateway>) -> Self {
+        gateways.sort_by(|a, b| a.score.total_cmp(&b.score));
+        GatewayRanking {
+            region,
+            created_at,
+            gateways,
+        }
+    }
+
+    pub fn gateways(&self) -> &[RankedGateway] {
+        &self.gateways
+    }
+
+    pub fn best(&self) -> Option<&RankedGateway> {
+        self.gateways.first()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        if let Some(parent) = path.as_ref().parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = File::create(path)?;
+        Ok(serde_json::to_writer_pretty(file, self)?)
+    }
+}
+
+/// Results of probing a single gateway.
+pub(crate) struct GatewayProbe {
+    pub(crate) gateway: gateway::Node,
+
+    /// Time it took to establish the connection, if it succeeded at all.
+    pub(crate) rtt: Option<Duration>,
+
+    /// Number of successful network monitor tests of the gateway within the reliability window,
+    /// if it could be retrieved.
+    pub(crate) uptime_count: Option<u32>,
+}
+
+/// Restricts the gateways to the ones whose location matches the provided region hint.
+/// If none of them match, all gateways are returned, so that the client could still work.
+pub fn filter_by_region(gateways: Vec<gateway::Node>, region: Option<&str>) -> Vec<gateway::Node> {
+    let region = match region.map(|region| region.trim().to_lowercase()) {
+        Some(region) if !region.is_empty() => region,
+        _ => return gateways,
+    };
+
+    let (matching, other): (Vec<_>, Vec<_>) = gateways
+        .into_iter()
+        .partition(|gateway| gateway.location.to_lowercase().contains(&region));
+
+    if matching.is_empty() {
+        log::warn!("There are no gateways in '{region}', considering all of them instead");
+        other
+    } else {
+        matching
+    }
+}
+
+/// Scores the reachable gateways by their connection round trip time, weighted by their
+/// reliability relative to the most reliable gateway probed.
+pub(crate) fn rank_probes(probes: Vec<GatewayProbe>) -> Vec<RankedGateway> {
+    let max_count = probes
+        .iter()
+        .filter(|probe| probe.rtt.is_some())
+        .filter_map(|probe| probe.uptime_count)
+        .max()
+        .unwrap_or_default();
+
+    probes
+        .into_iter()
+        .filter_map(|probe| {
+            let rtt = probe.rtt?;
+            // if no reliability data is available at all, rely purely on the latency
+            let reliability = if max_count == 0 {
+                1.0
+            } else {
+                probe.uptime_count.unwrap_or_default() as f64 / max_count as f64
+            };
+            let score = rtt.as_secs_f64() * 1000.0 / reliability.max(MIN_RELIABILITY);
+
+            Some(RankedGateway {
+                location: probe.gateway.location.clone(),
+                endpoint: probe.gateway.into(),
+                rtt_ms: rtt.as_millis() as u64,
+                reliability,
+                score,
+            })
+        })
+        .collect()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn measure_rtt(gateway: &gateway::Node, probe_timeout: Duration) -> Option<Duration> {
+    use crypto::asymmetric::identity;
+    use gateway_client::GatewayClient;
+    use std::sync::Arc;
+    use tokio::time::Instant;
+
+    // we're only timing the connection, so there's no point in revealing our actual identity
+    let ephemeral_identity = Arc::new(identity::KeyPair::new(&mut rand::rngs::OsRng));
+    let mut gateway_client = GatewayClient::new_init(
+        gateway.clients_address(),
+        gateway.identity_key,
+        gateway.owner.clone(),
+        ephemeral_identity,
+        probe_timeout,
+        None,
+    );
+
+    let start = Instant::now();
+    match tokio::time::timeout(probe_timeout, gateway_client.establish_connection()).await {
+        Ok(Ok(_)) => {
+            let rtt = start.elapsed();
+            let _ = gateway_client.close_connection().await;
+            Some(rtt)
+        }
+        Ok(Err(err)) => {
+            log::debug!(
+                "Failed to connect to gateway {}: {err}",
+                gateway.identity_key
+            );
+            None
+        }
+        Err(_) => {
+            log::debug!("Timed out connecting to gateway {}", gateway.identity_key);
+            None
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn probe_gateway(
+    validator_client: &validator_client::ApiClient,
+    gateway: gateway::Node,
+    probe_timeout: Duration,
+    since: i64,
+) -> GatewayProbe {
+    let identity = gateway.identity_key.to_base58_string();
+    let (rtt, status) = futures::join!(
+        measure_rtt(&gateway, probe_timeout),
+        validator_client.get_gateway_core_status_count(&identity, Some(since))
+    );
+
+    let uptime_count = match status {
+        Ok(status) => Some(status.count.max(0) as u32),
+        Err(err) => {
+            log::debug!("Failed to obtain the reliability of gateway {identity}: {err}");
+            None
+        }
+    };
+
+    GatewayProbe {
+        gateway,
+        rtt,
+        uptime_count,
+    }
+}
+
+/// Concurrently probes a random sample of the provided gateways (restricted to the region, if
+/// specified) and ranks the ones that responded.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn rank_gateways(
+    validator_client: &validator_client::ApiClient,
+    gateways: &[gateway::Node],
+    region: Option<&str>,
+    sample_size: usize,
+    probe_timeout: Duration,
+) -> GatewayRanking {
+    use rand::seq::SliceRandom;
+
+    let candidates = filter_by_region(gateways.to_vec(), region);
+    let sample = candidates
+        .choose_multiple(&mut rand::thread_rng(), sample_size.max(1))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("the system clock is set before the unix epoch");
+    let since = now.saturating_sub(RELIABILITY_WINDOW).as_secs() as i64;
+
+    log::info!("Probing {} gateways...", sample.len());
+    let probes = futures::future::join_all(
+        sample
+            .into_iter()
+            .map(|gateway| probe_gateway(validator_client, gateway, probe_timeout, since)),
+    )
+    .await;
+
+    GatewayRanking::new(
+        region.map(ToString::to_string),
+        now.as_secs(),
+        rank_probes(probes),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::asymmetric::{encryption, identity};
+    use rand::rngs::OsRng;
+
+    fn node(location: &str) -> gateway::Node {
+        let mut rng = OsRng;
+        gateway::Node {
+            owner: "n1owner".to_string(),
+            stake: 0,
+            location: location.to_string(),
+            host: "1.2.3.4".parse().unwrap(),
+            mix_host: "1.2.3.4:1789".parse().unwrap(),
+            clients_port: 9000,
+            identity_key: *identity::KeyPair::new(&mut rng).public_key(),
+            sphinx_key: *encryption::KeyPair::new(&mut rng).public_key(),
+            pending_sphinx_key: None,
+            version: "1.1.0".to_string(),
+        }
+    }
+
+    fn probe(rtt_ms: Option<u64>, uptime_count: Option<u32>) -> GatewayProbe {
+        GatewayProbe {
+            gateway: node("Neuchâtel, Switzerland"),
+            rtt: rtt_ms.map(Duration::from_millis),
+            uptime_count,
+        }
+    }
+
+    #[test]
+    fn unreliable_gateways_are_ranked_below_slower_reliable_ones() {
+        let fast_unreliable = probe(Some(20), Some(10));
+        let slow_reliable = probe(Some(60), Some(100));
+        let unreachable = probe(None, Some(100));
+        let unreliable_id = fast_unreliable.gateway.identity_key.to_base58_string();
+        let reliable_id = slow_reliable.gateway.identity_key.to_base58_string();
+
+        let ranking = GatewayRanking::new(
+            None,
+            0,
+            rank_probes(vec![fast_unreliable, slow_reliable, unreachable]),
+        );
+        let ranked = ranking.gateways();
+        assert_eq!(ranked.len(), 2);
+        assert_eq!(ranked[0].endpoint.gateway_id, reliable_id);
+        assert_eq!(ranked[1].endpoint.gateway_id, unreliable_id);
+        // relative to the reachable gateways only
+        assert_eq!(ranked[0].reliability, 1.0);
+        assert_eq!(ranked[1].reliability, 0.1);
+    }
+
+    #[test]
+    fn latency_decides_without_any_reliability_data() {
+        let slow = probe(Some(80), None);
+        let fast = probe(Some(30), None);
+        let fast_id = fast.gateway.identity_key.to_base58_string();
+
+        let ranking = GatewayRanking::new(None, 0, rank_probes(vec![slow, fast]));
+        assert_eq!(ranking.best().unwrap().endpoint.gateway_id, fast_id);
+        assert!(ranking.gateways().iter().all(|g| g.reliability == 1.0));
+    }
+
+    #[test]
+    fn region_hint_falls_back_to_all_gateways() {
+        let gateways = vec![node("Berlin, Germany"), node("Toronto, Canada")];
+
+        let filtered = filter_by_region(gateways.clone(), Some(" germany"));
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].location, "Berlin, Germany");
+
+        assert_eq!(filter_by_region(gateways.clone(), Some("Japan")).len(), 2);
+        assert_eq!(filter_by_region(gateways, None).len(), 2);
+    }
+
+    #[test]
+    fn ranking_survives_being_saved() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ranking").join("gateway_ranking.json");
+
+        let ranking = GatewayRanking::new(
+            Some("germany".to_string()),
+            1667304000,
+            rank_probes(vec![probe(Some(30), Some(5)), probe(Some(40), Some(7))]),
+        );
+        ranking.save(&path).unwrap();
+
+        let loaded = GatewayRanking::load(&path).unwrap();
+        assert_eq!(loaded.region, ranking.region);
+        assert_eq!(loaded.created_at, ranking.created_at);
+        let endpoints = |ranking: &GatewayRanking| {
+            ranking
+                .gateways()
+                .iter()
+                .map(|gateway| gateway.endpoint.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(endpoints(&loaded), endpoints(&ranking));
+    }
+}
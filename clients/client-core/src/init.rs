@@ -7,7 +7,7 @@ use std::{sync::Arc, time::Duration};
 
 use config::NymConfig;
 use crypto::asymmetric::{encryption, identity};
-use gateway_client::GatewayClient;
+use gateway_client::{GatewayClient, GatewayRedirect};
 use gateway_requests::registration::handshake::SharedKeys;
 use nymsphinx::addressing::clients::Recipient;
 use nymsphinx::addressing::nodes::NodeIdentity;
@@ -24,23 +24,37 @@ use crate::{
     error::ClientCoreError,
 };
 
-pub async fn query_gateway_details(
-    validator_servers: Vec<Url>,
-    chosen_gateway_id: Option<&str>,
-) -> Result<gateway::Node, ClientCoreError> {
+fn choose_validator_client(
+    validator_servers: &[Url],
+) -> Result<validator_client::ApiClient, ClientCoreError> {
     let validator_api = validator_servers
         .choose(&mut thread_rng())
         .ok_or(ClientCoreError::ListOfValidatorApisIsEmpty)?;
-    let validator_client = validator_client::ApiClient::new(validator_api.clone());
+    Ok(validator_client::ApiClient::new(validator_api.clone()))
+}
 
-    log::trace!("Fetching list of gateways from: {}", validator_api);
+async fn current_gateways(
+    validator_client: &validator_client::ApiClient,
+) -> Result<Vec<gateway::Node>, ClientCoreError> {
+    log::trace!(
+        "Fetching list of gateways from: {}",
+        validator_client.validator_api.current_url()
+    );
     let gateways = validator_client.get_cached_gateways().await?;
     let valid_gateways = gateways
         .into_iter()
         .filter_map(|gateway| gateway.try_into().ok())
         .collect::<Vec<gateway::Node>>();
 
-    let filtered_gateways = valid_gateways.filter_by_version(env!("CARGO_PKG_VERSION"));
+    Ok(valid_gateways.filter_by_version(env!("CARGO_PKG_VERSION")))
+}
+
+pub async fn query_gateway_details(
+    validator_servers: Vec<Url>,
+    chosen_gateway_id: Option<&str>,
+) -> Result<gateway::Node, ClientCoreError> {
+    let validator_client = choose_validator_client(&validator_servers)?;
+    let filtered_gateways = current_gateways(&validator_client).await?;
 
     // if we have chosen particular gateway - use it, otherwise choose a random one.
     // (remember that in active topology all gateways have at least 100 reputation so should
@@ -59,6 +73,53 @@ pub async fn query_gateway_details(
     }
 }
 
+/// Probes a sample of the available gateways, optionally restricted to the provided region,
+/// and returns the one with the best combination of latency and reliability.
+///
+/// The full ranking is stored in the gateway ranking file, so that the client could later
+/// fall back to the next best gateway without probing the network again.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn select_gateway<T>(
+    config: &Config<T>,
+    region: Option<&str>,
+) -> Result<gateway::Node, ClientCoreError>
+where
+    T: NymConfig,
+{
+    let validator_client = choose_validator_client(&config.get_validator_api_endpoints())?;
+    let gateways = current_gateways(&validator_client).await?;
+    if gateways.is_empty() {
+        return Err(ClientCoreError::NoGatewaysOnNetwork);
+    }
+
+    let ranking = crate::gateway_selection::rank_gateways(
+        &validator_client,
+        &gateways,
+        region,
+        config.get_gateway_selection_sample_size(),
+        config.get_gateway_probe_timeout(),
+    )
+    .await;
+
+    let best = ranking.best().ok_or(ClientCoreError::NoReachableGateways)?;
+    log::info!(
+        "Selected gateway {} ({}) with connection time of {}ms and relative reliability of {:.2}",
+        best.endpoint.gateway_id,
+        best.location,
+        best.rtt_ms,
+        best.reliability
+    );
+
+    if let Err(err) = ranking.save(config.get_gateway_ranking_file()) {
+        log::warn!("Failed to store the gateway ranking: {err}");
+    }
+
+    gateways
+        .into_iter()
+        .find(|gateway| gateway.identity_key.to_base58_string() == best.endpoint.gateway_id)
+        .ok_or_else(|| ClientCoreError::NoGatewayWithId(best.endpoint.gateway_id.clone()))
+}
+
 pub async fn register_with_gateway_and_store_keys<T>(
     gateway_details: gateway::Node,
     config: &Config<T>,
@@ -104,6 +165,122 @@ async fn register_with_gateway(
     Ok(shared_keys)
 }
 
+/// Moves the client to a different gateway while keeping its identity, and thus its address
+/// (apart from the gateway part of it), unchanged.
+///
+/// The client registers with the new gateway and publishes the returned redirect record, signed
+/// with its identity key, to its current gateway, which then, for as long as it remains online,
+/// forwards the packets it receives for the client to the new one. The record is also stored
+/// alongside the keys, so that the client would announce it to all of its peers once it runs,
+/// letting them update its address via [`GatewayRedirect::updated_recipient`].
+///
+/// The new gateway keys and the updated configuration are saved together: if the configuration
+/// could not be saved, the previous gateway keys are restored.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn migrate_to_gateway<C>(
+    new_gateway: gateway::Node,
+    config: &mut C,
+) -> Result<GatewayRedirect, ClientCoreError>
+where
+    C: crate::config::ClientCoreConfigTrait,
+{
+    use crate::client::redirects::store_own_redirect;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let base_config = config.get_base();
+    let pathfinder = ClientKeyPathfinder::new_from_config(base_config);
+    let mut key_manager = KeyManager::load_keys(&pathfinder)
+        .tap_err(|err| log::error!("Failed to load stored keys: {err}"))?;
+    let old_gateway = NodeIdentity::from_base58_string(base_config.get_gateway_id())?;
+    let old_endpoint = base_config.get_gateway_endpoint().clone();
+    let redirect_file = base_config.get_gateway_redirect_file();
+
+    let new_shared_keys =
+        register_with_gateway(&new_gateway, key_manager.identity_keypair()).await?;
+
+    let issued_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("the system clock is set before the unix epoch")
+        .as_secs();
+    let redirect = GatewayRedirect::new(
+        key_manager.identity_keypair().as_ref(),
+        old_gateway,
+        new_gateway.identity_key,
+        new_gateway.sphinx_key,
+        new_gateway.mix_host.into(),
+        issued_at,
+    );
+
+    // the old gateway might already be gone, in which case there's nobody to forward the packets
+    if let Err(err) = publish_redirect(base_config, &key_manager, &redirect).await {
+        log::warn!("Failed to publish the redirect record at the old gateway: {err}");
+    }
+
+    let old_shared_keys = key_manager.gateway_shared_key();
+    key_manager.insert_gateway_shared_key(new_shared_keys);
+    key_manager
+        .store_keys(&pathfinder)
+        .tap_err(|err| log::error!("Failed to store the new gateway keys: {err}"))?;
+
+    config
+        .get_base_mut()
+        .with_gateway_endpoint(new_gateway.into());
+    if let Err(err) = config.save_to_file(None) {
+        log::error!("Failed to save the config with the new gateway: {err}");
+        // make sure the stored keys keep matching the gateway in the config
+        config.get_base_mut().with_gateway_endpoint(old_endpoint);
+        key_manager.insert_gateway_shared_key(old_shared_keys);
+        if let Err(err) = key_manager.store_keys(&pathfinder) {
+            log::error!("Failed to restore the previous gateway keys: {err}");
+        }
+        return Err(err.into());
+    }
+
+    if let Err(err) = store_own_redirect(redirect_file, &redirect) {
+        log::warn!("Failed to store the redirect record, it won't be announced to peers: {err}");
+    }
+
+    Ok(redirect)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn publish_redirect<T>(
+    config: &Config<T>,
+    key_manager: &KeyManager,
+    redirect: &GatewayRedirect,
+) -> Result<(), ClientCoreError>
+where
+    T: NymConfig,
+{
+    use futures::channel::mpsc;
+
+    // the client is not going to receive anything over this connection
+    let (mix_tx, _) = mpsc::unbounded();
+    let (ack_tx, _) = mpsc::unbounded();
+    let mut gateway_client = GatewayClient::new(
+        config.get_gateway_listener(),
+        key_manager.identity_keypair(),
+        NodeIdentity::from_base58_string(config.get_gateway_id())?,
+        config.get_gateway_owner(),
+        Some(key_manager.gateway_shared_key()),
+        mix_tx,
+        ack_tx,
+        Duration::from_millis(1500),
+        None,
+        None,
+    );
+    gateway_client.with_reconnection_on_failure(false);
+
+    gateway_client.establish_connection().await?;
+    gateway_client.perform_initial_authentication().await?;
+    if !gateway_client.register_redirect(redirect).await? {
+        log::warn!("The old gateway already knows of a more recent redirect record");
+    }
+    // we don't care if the connection gets closed cleanly
+    let _ = gateway_client.close_connection().await;
+    Ok(())
+}
+
 pub fn show_address<T>(config: &Config<T>) -> Result<(), ClientCoreError>
 where
     T: config::NymConfig,
@@ -3,6 +3,7 @@ use std::future::Future;
 pub mod client;
 pub mod config;
 pub mod error;
+pub mod gateway_selection;
 pub mod init;
 
 #[cfg(target_arch = "wasm32")]
@@ -43,6 +43,7 @@ async fn send_file_with_reply() {
         recipient,
         message: read_data,
         with_reply_surb: true,
+        request_id: None,
     };
 
     println!("sending content of 'dummy_file' over the mix network...");
@@ -60,6 +61,7 @@ async fn send_file_with_reply() {
     let reply_request = ClientRequest::Reply {
         message: reply_message.clone(),
         reply_surb: received.reply_surb.unwrap(),
+        request_id: None,
     };
 
     println!(
@@ -91,6 +93,7 @@ async fn send_file_without_reply() {
         recipient,
         message: read_data,
         with_reply_surb: false,
+        request_id: None,
     };
 
     println!("sending content of 'dummy_file' over the mix network...");
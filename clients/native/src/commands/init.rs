@@ -20,6 +20,11 @@ pub(crate) struct Init {
     #[clap(long)]
     gateway: Option<String>,
 
+    /// Region (matched against the announced gateway locations, e.g. a country name) preferred
+    /// when the gateway is chosen automatically.
+    #[clap(long)]
+    gateway_region: Option<String>,
+
     /// Force register gateway. WARNING: this will overwrite any existing keys for the given id,
     /// potentially causing loss of access.
     #[clap(long)]
@@ -93,12 +98,18 @@ pub(crate) async fn execute(args: &Init) {
     let override_config_fields = OverrideConfig::from(args.clone());
     config = override_config(config, override_config_fields);
 
-    let gateway = setup_gateway(id, register_gateway, user_chosen_gateway_id, &config)
-        .await
-        .unwrap_or_else(|err| {
-            eprintln!("Failed to setup gateway\nError: {err}");
-            std::process::exit(1)
-        });
+    let gateway = setup_gateway(
+        id,
+        register_gateway,
+        user_chosen_gateway_id,
+        args.gateway_region.as_deref(),
+        &config,
+    )
+    .await
+    .unwrap_or_else(|err| {
+        eprintln!("Failed to setup gateway\nError: {err}");
+        std::process::exit(1)
+    });
     config.get_base_mut().with_gateway_endpoint(gateway);
 
     let config_save_location = config.get_config_file_save_location();
@@ -126,17 +137,23 @@ async fn setup_gateway(
     id: &str,
     register: bool,
     user_chosen_gateway_id: Option<&str>,
+    region: Option<&str>,
     config: &Config,
 ) -> Result<GatewayEndpoint, ClientCoreError> {
     if register {
-        // Get the gateway details by querying the validator-api. Either pick one at random or use
-        // the chosen one if it's among the available ones.
+        // Get the gateway details by querying the validator-api. Either use the chosen one if it's
+        // among the available ones or pick the best of the probed ones.
         println!("Configuring gateway");
-        let gateway = client_core::init::query_gateway_details(
-            config.get_base().get_validator_api_endpoints(),
-            user_chosen_gateway_id,
-        )
-        .await?;
+        let gateway = if user_chosen_gateway_id.is_some() {
+            client_core::init::query_gateway_details(
+                config.get_base().get_validator_api_endpoints(),
+                user_chosen_gateway_id,
+            )
+            .await?
+        } else {
+            println!("Probing gateways to find the best one");
+            client_core::init::select_gateway(config.get_base(), region).await?
+        };
         log::debug!("Querying gateway gives: {}", gateway);
 
         // Registering with gateway by setting up and writing shared keys to disk
@@ -8,6 +8,7 @@ use clap::{Parser, Subcommand};
 use completions::{fig_generate, ArgShell};
 
 pub(crate) mod init;
+pub(crate) mod migrate;
 pub(crate) mod run;
 pub(crate) mod upgrade;
 
@@ -65,6 +66,8 @@ pub(crate) enum Commands {
     Run(run::Run),
     /// Try to upgrade the client
     Upgrade(upgrade::Upgrade),
+    /// Move the client to a different gateway, keeping its identity
+    Migrate(migrate::Migrate),
 
     /// Generate shell completions
     Completions(ArgShell),
@@ -91,6 +94,7 @@ pub(crate) async fn execute(args: &Cli) -> Result<(), ClientError> {
         Commands::Init(m) => init::execute(m).await,
         Commands::Run(m) => run::execute(m).await?,
         Commands::Upgrade(m) => upgrade::execute(m),
+        Commands::Migrate(m) => migrate::execute(m).await,
         Commands::Completions(s) => s.generate(&mut Cli::into_app(), bin_name),
         Commands::GenerateFigSpec => fig_generate(&mut Cli::into_app(), bin_name),
     }
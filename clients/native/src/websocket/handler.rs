@@ -1,26 +1,70 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use super::rpc::RpcContext;
+use super::subscriptions::{
+    SubscriberEvent, SubscriberEventReceiver, SubscriberId, SubscriptionFilter, Subscriptions,
+};
 use client_core::client::{
+    delivery_status::DeliveryStatus,
     inbound_messages::{InputMessage, InputMessageSender},
-    received_buffer::{
-        ReceivedBufferMessage, ReceivedBufferRequestSender, ReconstructedMessagesReceiver,
-    },
+    self_address::SelfAddress,
 };
-use futures::channel::mpsc;
 use futures::{SinkExt, StreamExt};
 use log::*;
 use nymsphinx::addressing::clients::Recipient;
 use nymsphinx::anonymous_replies::ReplySurb;
 use nymsphinx::receiver::ReconstructedMessage;
+use std::collections::HashMap;
 use tokio::net::TcpStream;
 use tokio_tungstenite::{
-    accept_async,
-    tungstenite::{protocol::Message as WsMessage, Error as WsError},
+    accept_hdr_async,
+    tungstenite::{
+        handshake::server::{ErrorResponse, Request, Response},
+        http::StatusCode,
+        protocol::Message as WsMessage,
+        Error as WsError,
+    },
     WebSocketStream,
 };
+use websocket_requests::v2::{self, ClientCall, ErrorCode, ErrorObject, Notification, RequestId};
 use websocket_requests::{requests::ClientRequest, responses::ServerResponse};
 
+/// Version of the websocket protocol spoken over a particular connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ProtocolVersion {
+    V1,
+    V2,
+}
+
+impl Default for ProtocolVersion {
+    fn default() -> Self {
+        ProtocolVersion::V1
+    }
+}
+
+impl ProtocolVersion {
+    /// The version can be chosen with the `version` query parameter of the handshake request,
+    /// e.g. `ws://localhost:1977/?version=2`. If it's not specified, v1 is used.
+    fn from_query(query: Option<&str>) -> Result<Self, String> {
+        let requested = query.and_then(|query| {
+            url::form_urlencoded::parse(query.as_bytes())
+                .find(|(key, _)| key == "version")
+                .map(|(_, value)| value.into_owned())
+        });
+
+        match requested.as_deref() {
+            None | Some("1") => Ok(ProtocolVersion::V1),
+            Some("2") => Ok(ProtocolVersion::V2),
+            Some(other) => Err(format!(
+                "unsupported protocol version '{}'. Supported versions are: {:?}",
+                other,
+                v2::SUPPORTED_PROTOCOL_VERSIONS
+            )),
+        }
+    }
+}
+
 enum ReceivedResponseType {
     Binary,
     Text,
@@ -34,10 +78,16 @@ impl Default for ReceivedResponseType {
 
 pub(crate) struct Handler {
     msg_input: InputMessageSender,
-    buffer_requester: ReceivedBufferRequestSender,
-    self_full_address: Recipient,
+    subscriptions: Subscriptions,
+    subscriber_id: Option<SubscriberId>,
+    self_full_address: SelfAddress,
     socket: Option<WebSocketStream<TcpStream>>,
     received_response_type: ReceivedResponseType,
+    protocol_version: ProtocolVersion,
+    rpc_context: RpcContext,
+    // v2 requests are tracked under locally assigned ids that map back to the caller's ids
+    tracked_requests: HashMap<u64, RequestId>,
+    next_tracked_request: u64,
 }
 
 // clone is used to use handler on a new connection, which initially is `None`
@@ -45,34 +95,60 @@ impl Clone for Handler {
     fn clone(&self) -> Self {
         Handler {
             msg_input: self.msg_input.clone(),
-            buffer_requester: self.buffer_requester.clone(),
-            self_full_address: self.self_full_address,
+            subscriptions: self.subscriptions.clone(),
+            subscriber_id: None,
+            self_full_address: self.self_full_address.clone(),
             socket: None,
             received_response_type: Default::default(),
+            protocol_version: Default::default(),
+            rpc_context: self.rpc_context.clone(),
+            tracked_requests: HashMap::new(),
+            next_tracked_request: 0,
         }
     }
 }
 
 impl Drop for Handler {
     fn drop(&mut self) {
-        self.buffer_requester
-            .unbounded_send(ReceivedBufferMessage::ReceiverDisconnect)
-            .expect("the buffer request failed!")
+        if let Some(subscriber_id) = self.subscriber_id {
+            self.subscriptions.unsubscribe(subscriber_id)
+        }
     }
 }
 
 impl Handler {
     pub(crate) fn new(
         msg_input: InputMessageSender,
-        buffer_requester: ReceivedBufferRequestSender,
-        self_full_address: Recipient,
+        subscriptions: Subscriptions,
+        self_full_address: SelfAddress,
+        rpc_context: RpcContext,
     ) -> Self {
         Handler {
             msg_input,
-            buffer_requester,
+            subscriptions,
+            subscriber_id: None,
             self_full_address,
             socket: None,
             received_response_type: Default::default(),
+            protocol_version: Default::default(),
+            rpc_context,
+            tracked_requests: HashMap::new(),
+            next_tracked_request: 0,
+        }
+    }
+
+    // if the client wants to know what happens to its message, the router has to learn about it
+    // before the message is sent off
+    fn track_delivery(&self, input_msg: InputMessage, request_id: Option<u64>) -> InputMessage {
+        match (request_id, self.subscriber_id) {
+            (Some(request_id), Some(subscriber_id)) => {
+                let delivery_id = self.subscriptions.track_delivery(subscriber_id, request_id);
+                input_msg.with_request_id(delivery_id)
+            }
+            (Some(_), None) => {
+                panic!("impossible state - websocket handshake was somehow reverted")
+            }
+            (None, _) => input_msg,
         }
     }
 
@@ -81,27 +157,53 @@ impl Handler {
         recipient: Recipient,
         message: Vec<u8>,
         with_reply_surb: bool,
+        request_id: Option<u64>,
     ) -> Option<ServerResponse> {
         // the ack control is now responsible for chunking, etc.
         let input_msg = InputMessage::new_fresh(recipient, message, with_reply_surb);
+        let input_msg = self.track_delivery(input_msg, request_id);
         self.msg_input.unbounded_send(input_msg).unwrap();
 
         None
     }
 
-    fn handle_reply(&mut self, reply_surb: ReplySurb, message: Vec<u8>) -> Option<ServerResponse> {
+    fn check_reply_length(message: &[u8]) -> Result<(), String> {
         if message.len() > ReplySurb::max_msg_len(Default::default()) {
-            return Some(ServerResponse::new_error(format!("too long message to put inside a reply SURB. Received: {} bytes and maximum is {} bytes", message.len(), ReplySurb::max_msg_len(Default::default()))));
+            return Err(format!("too long message to put inside a reply SURB. Received: {} bytes and maximum is {} bytes", message.len(), ReplySurb::max_msg_len(Default::default())));
+        }
+        Ok(())
+    }
+
+    fn handle_reply(
+        &mut self,
+        reply_surb: ReplySurb,
+        message: Vec<u8>,
+        request_id: Option<u64>,
+    ) -> Option<ServerResponse> {
+        if let Err(err) = Self::check_reply_length(&message) {
+            return Some(ServerResponse::new_error(err));
         }
 
         let input_msg = InputMessage::new_reply(reply_surb, message);
+        let input_msg = self.track_delivery(input_msg, request_id);
         self.msg_input.unbounded_send(input_msg).unwrap();
 
         None
     }
 
     fn handle_self_address(&self) -> ServerResponse {
-        ServerResponse::SelfAddress(self.self_full_address)
+        ServerResponse::SelfAddress(self.self_full_address.get())
+    }
+
+    fn handle_subscribe(&mut self, prefix: Vec<u8>) -> Option<ServerResponse> {
+        match self.subscriber_id {
+            Some(subscriber_id) => self
+                .subscriptions
+                .update_filter(subscriber_id, SubscriptionFilter::new(prefix)),
+            None => panic!("impossible state - websocket handshake was somehow reverted"),
+        }
+
+        None
     }
 
     fn handle_request(&mut self, request: ClientRequest) -> Option<ServerResponse> {
@@ -110,15 +212,107 @@ impl Handler {
                 recipient,
                 message,
                 with_reply_surb,
-            } => self.handle_send(recipient, message, with_reply_surb),
+                request_id,
+            } => self.handle_send(recipient, message, with_reply_surb, request_id),
             ClientRequest::Reply {
                 message,
                 reply_surb,
-            } => self.handle_reply(reply_surb, message),
+                request_id,
+            } => self.handle_reply(reply_surb, message, request_id),
             ClientRequest::SelfAddress => Some(self.handle_self_address()),
+            ClientRequest::Subscribe { prefix } => self.handle_subscribe(prefix),
         }
     }
 
+    // delivery of v2 requests can only be tracked if the caller is able to correlate the
+    // notifications, i.e. if the request had an id
+    fn track_v2_request(&mut self, id: Option<RequestId>, track_delivery: bool) -> Option<u64> {
+        let id = id.filter(|_| track_delivery)?;
+        let tracked_request = self.next_tracked_request;
+        self.next_tracked_request = self.next_tracked_request.wrapping_add(1);
+        self.tracked_requests.insert(tracked_request, id);
+        Some(tracked_request)
+    }
+
+    async fn handle_v2_call(
+        &mut self,
+        id: Option<RequestId>,
+        call: ClientCall,
+    ) -> Result<serde_json::Value, ErrorObject> {
+        let self_address = self.self_full_address.get();
+        let result = match call {
+            ClientCall::Send {
+                recipient,
+                message,
+                with_reply_surb,
+                track_delivery,
+            } => {
+                let tracked_request = self.track_v2_request(id, track_delivery);
+                self.handle_send(recipient, message, with_reply_surb, tracked_request);
+                serde_json::Value::Null
+            }
+            ClientCall::Reply {
+                reply_surb,
+                message,
+                track_delivery,
+            } => {
+                Self::check_reply_length(&message)
+                    .map_err(|err| ErrorObject::new(ErrorCode::MessageTooLong, err))?;
+                let tracked_request = self.track_v2_request(id, track_delivery);
+                self.handle_reply(reply_surb, message, tracked_request);
+                serde_json::Value::Null
+            }
+            ClientCall::SelfAddress => serde_json::json!(v2::SelfAddressResult {
+                address: self_address.to_string(),
+            }),
+            ClientCall::Subscribe { prefix } => {
+                self.handle_subscribe(prefix);
+                serde_json::Value::Null
+            }
+            ClientCall::Version => serde_json::json!(v2::VersionResult {
+                protocol: v2::PROTOCOL_VERSION,
+                supported_protocols: v2::SUPPORTED_PROTOCOL_VERSIONS.to_vec(),
+                client_version: env!("CARGO_PKG_VERSION").to_string(),
+            }),
+            ClientCall::Health => serde_json::json!(self.rpc_context.health(&self_address).await),
+            ClientCall::GetTopology => serde_json::json!(self.rpc_context.topology().await?),
+            ClientCall::GetGateway => {
+                serde_json::json!(self.rpc_context.gateway(&self_address).await?)
+            }
+            ClientCall::CreateReplySurbs { count } => serde_json::json!(
+                self.rpc_context
+                    .create_reply_surbs(&self_address, count)
+                    .await?
+            ),
+            ClientCall::RevokeReplySurb { reply_surb } => {
+                serde_json::json!(self.rpc_context.revoke_reply_surb(&reply_surb)?)
+            }
+        };
+
+        Ok(result)
+    }
+
+    async fn handle_v2_text_message(&mut self, msg: String) -> Option<WsMessage> {
+        debug!("Handling v2 text message request");
+        trace!("Content: {:?}", msg);
+
+        let request = match v2::ClientRequest::try_from_text(msg) {
+            Ok(request) => request,
+            Err(err) => return Some(WsMessage::text(err.into_response().into_text())),
+        };
+
+        let id = request.id.clone();
+        let outcome = self.handle_v2_call(request.id, request.call).await;
+
+        // requests without an id are notifications and, as such, never get a response
+        let id = id?;
+        let response = match outcome {
+            Ok(result) => v2::ServerResponse::new_result(Some(id), result),
+            Err(err) => v2::ServerResponse::new_error(Some(id), err),
+        };
+        Some(WsMessage::text(response.into_text()))
+    }
+
     fn handle_text_message(&mut self, msg: String) -> Option<WsMessage> {
         debug!("Handling text message request");
         trace!("Content: {:?}", msg);
@@ -148,13 +342,37 @@ impl Handler {
         response.map(|resp| WsMessage::Binary(resp.into_binary()))
     }
 
-    fn handle_ws_request(&mut self, raw_request: WsMessage) -> Option<WsMessage> {
+    async fn handle_ws_request(&mut self, raw_request: WsMessage) -> Option<WsMessage> {
         // apparently tungstenite auto-handles ping/pong/close messages so for now let's ignore
         // them and let's test that claim. If that's not the case, just copy code from
         // old version of this file.
-        match raw_request {
-            WsMessage::Text(text_message) => self.handle_text_message(text_message),
-            WsMessage::Binary(binary_message) => self.handle_binary_message(binary_message),
+        match (self.protocol_version, raw_request) {
+            (ProtocolVersion::V1, WsMessage::Text(text_message)) => {
+                self.handle_text_message(text_message)
+            }
+            (ProtocolVersion::V1, WsMessage::Binary(binary_message)) => {
+                self.handle_binary_message(binary_message)
+            }
+            (ProtocolVersion::V2, WsMessage::Text(text_message)) => {
+                self.handle_v2_text_message(text_message).await
+            }
+            // the payloads are base64-encoded within the requests, so binary frames can only
+            // carry the same JSON as the text ones
+            (ProtocolVersion::V2, WsMessage::Binary(binary_message)) => {
+                match String::from_utf8(binary_message) {
+                    Ok(text_message) => self.handle_v2_text_message(text_message).await,
+                    Err(err) => {
+                        let response = v2::ServerResponse::new_error(
+                            None,
+                            ErrorObject::new(
+                                ErrorCode::ParseError,
+                                format!("the request is not valid UTF-8 - {}", err),
+                            ),
+                        );
+                        Some(WsMessage::text(response.into_text()))
+                    }
+                }
+            }
             _ => None,
         }
     }
@@ -192,11 +410,17 @@ impl Handler {
         // TODO: later there might be a flag on the reconstructed message itself to tell us
         // if it's text or binary, but for time being we use the naive assumption that if
         // client is sending Message::Text it expects text back. Same for Message::Binary
-        let response_messages = match self.received_response_type {
-            ReceivedResponseType::Binary => {
+        let response_messages = match (self.protocol_version, &self.received_response_type) {
+            (ProtocolVersion::V2, _) => reconstructed_messages
+                .into_iter()
+                .map(|msg| Ok(WsMessage::Text(Notification::received(msg).into_text())))
+                .collect(),
+            (ProtocolVersion::V1, ReceivedResponseType::Binary) => {
                 self.prepare_reconstructed_binary(reconstructed_messages)
             }
-            ReceivedResponseType::Text => self.prepare_reconstructed_text(reconstructed_messages),
+            (ProtocolVersion::V1, ReceivedResponseType::Text) => {
+                self.prepare_reconstructed_text(reconstructed_messages)
+            }
         };
 
         let mut send_stream = futures::stream::iter(response_messages);
@@ -207,6 +431,73 @@ impl Handler {
             .await
     }
 
+    // the tracked request is forgotten once no further updates are expected for it
+    fn v2_delivery_notification(
+        &mut self,
+        tracked_request: u64,
+        status: DeliveryStatus,
+    ) -> Option<Notification> {
+        let id = if status.is_final() {
+            self.tracked_requests.remove(&tracked_request)
+        } else {
+            self.tracked_requests.get(&tracked_request).cloned()
+        };
+        let id = match id {
+            Some(id) => id,
+            None => {
+                warn!("received delivery status of an unknown request {tracked_request}");
+                return None;
+            }
+        };
+
+        let status = match status {
+            DeliveryStatus::Sent => v2::DeliveryStatus::Sent,
+            DeliveryStatus::Acknowledged => v2::DeliveryStatus::Acknowledged,
+            DeliveryStatus::GaveUp => v2::DeliveryStatus::GaveUp,
+            DeliveryStatus::Expired => v2::DeliveryStatus::Expired,
+        };
+        Some(Notification::DeliveryStatus { id, status })
+    }
+
+    async fn push_websocket_v2_delivery_status(
+        &mut self,
+        tracked_request: u64,
+        status: DeliveryStatus,
+    ) -> Result<(), WsError> {
+        match self.v2_delivery_notification(tracked_request, status) {
+            Some(notification) => {
+                self.send_websocket_response(WsMessage::Text(notification.into_text()))
+                    .await
+            }
+            None => Ok(()),
+        }
+    }
+
+    async fn push_websocket_delivery_status(
+        &mut self,
+        request_id: u64,
+        status: DeliveryStatus,
+    ) -> Result<(), WsError> {
+        if self.protocol_version == ProtocolVersion::V2 {
+            return self
+                .push_websocket_v2_delivery_status(request_id, status)
+                .await;
+        }
+
+        let response = match status {
+            DeliveryStatus::Sent => ServerResponse::Sent { request_id },
+            DeliveryStatus::Acknowledged => ServerResponse::Acknowledged { request_id },
+            DeliveryStatus::GaveUp => ServerResponse::GaveUp { request_id },
+            DeliveryStatus::Expired => ServerResponse::Expired { request_id },
+        };
+
+        let response_message = match self.received_response_type {
+            ReceivedResponseType::Binary => WsMessage::Binary(response.into_binary()),
+            ReceivedResponseType::Text => WsMessage::Text(response.into_text()),
+        };
+        self.send_websocket_response(response_message).await
+    }
+
     async fn send_websocket_response(&mut self, msg: WsMessage) -> Result<(), WsError> {
         match self.socket {
             // TODO: more closely investigate difference between `Sink::send` and `Sink::send_all`
@@ -224,7 +515,7 @@ impl Handler {
         }
     }
 
-    async fn listen_for_requests(&mut self, mut msg_receiver: ReconstructedMessagesReceiver) {
+    async fn listen_for_requests(&mut self, mut event_receiver: SubscriberEventReceiver) {
         loop {
             tokio::select! {
                 // we can either get a client request from the websocket
@@ -244,7 +535,7 @@ impl Handler {
                         break;
                     }
 
-                    if let Some(response) = self.handle_ws_request(socket_msg) {
+                    if let Some(response) = self.handle_ws_request(socket_msg).await {
                         if let Err(err) = self.send_websocket_response(response).await {
                             warn!(
                                 "Failed to send message over websocket: {}. Assuming the connection is dead.",
@@ -254,13 +545,21 @@ impl Handler {
                         }
                     }
                 }
-                // or a reconstructed mix message that we need to push back to the client
-                mix_messages = msg_receiver.next() => {
-                    let mix_messages = mix_messages.expect(
-                        "mix messages sender was unexpectedly closed! this shouldn't have ever happened!",
+                // or an event, such as a reconstructed mix message, that we need to push back to the client
+                event = event_receiver.next() => {
+                    let event = event.expect(
+                        "subscriber event sender was unexpectedly closed! this shouldn't have ever happened!",
                     );
-                    if let Err(e) = self.push_websocket_received_plaintexts(mix_messages).await {
-                        warn!("failed to send sphinx packets back to the client - {:?}, assuming the connection is dead", e);
+                    let result = match event {
+                        SubscriberEvent::Received(mix_messages) => {
+                            self.push_websocket_received_plaintexts(mix_messages).await
+                        }
+                        SubscriberEvent::DeliveryStatus { request_id, status } => {
+                            self.push_websocket_delivery_status(request_id, status).await
+                        }
+                    };
+                    if let Err(e) = result {
+                        warn!("failed to push event back to the client - {:?}, assuming the connection is dead", e);
                         break;
                     }
                 }
@@ -270,7 +569,22 @@ impl Handler {
 
     // consume self to make sure `drop` is called after this is done
     pub(crate) async fn handle_connection(mut self, socket: TcpStream) {
-        let ws_stream = match accept_async(socket).await {
+        // the initial subscription filter can be specified as part of the handshake,
+        // so that the connection would never receive messages not meant for it
+        let mut filter = SubscriptionFilter::default();
+        let mut protocol_version = ProtocolVersion::default();
+        let ws_stream = match accept_hdr_async(socket, |request: &Request, response: Response| {
+            filter = SubscriptionFilter::from_query(request.uri().query());
+            protocol_version =
+                ProtocolVersion::from_query(request.uri().query()).map_err(|err| {
+                    let mut error_response = ErrorResponse::new(Some(err));
+                    *error_response.status_mut() = StatusCode::BAD_REQUEST;
+                    error_response
+                })?;
+            Ok(response)
+        })
+        .await
+        {
             Ok(ws_stream) => ws_stream,
             Err(err) => {
                 warn!("error while performing the websocket handshake - {:?}", err);
@@ -278,16 +592,210 @@ impl Handler {
             }
         };
         self.socket = Some(ws_stream);
+        self.protocol_version = protocol_version;
+
+        // tell the router to start sending stuff to us
+        let (subscriber_id, event_receiver) = self.subscriptions.subscribe(filter);
+        self.subscriber_id = Some(subscriber_id);
+
+        self.listen_for_requests(event_receiver).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::websocket::subscriptions::MessageRouter;
+    use client_core::client::delivery_status::DeliveryStatusSender;
+    use client_core::client::inbound_messages::InputMessageReceiver;
+    use client_core::client::received_buffer::ReceivedBufferRequestReceiver;
+    use client_core::client::reply_key_storage::ReplyKeyStorage;
+    use client_core::client::topology_control::TopologyAccessor;
+    use futures::channel::mpsc;
+    use nymsphinx::params::DEFAULT_NUM_MIX_HOPS;
+    use std::time::Duration;
+
+    const SELF_ADDRESS: &str = "CytBseW6yFXUMzz4SGAKdNLGR7q3sJLLYxyBGvutNEQV.4QXYyEVc5fUDjmmi8PrHN9tdUFV4PCvSJE1278cHyvoe@4sBbL1ngf1vtNqykydQKTFh26sQCw888GpUqvPvyNB4f";
+
+    struct Fixture {
+        handler: Handler,
+        input_receiver: InputMessageReceiver,
+        // the router stops once either of its channels gets closed
+        _buffer_receiver: ReceivedBufferRequestReceiver,
+        _delivery_status_sender: DeliveryStatusSender,
+        _reply_key_dir: tempfile::TempDir,
+    }
+
+    fn fixture() -> Fixture {
+        let (msg_input, input_receiver) = mpsc::unbounded();
+        let (buffer_requester, buffer_receiver) = mpsc::unbounded();
+        let (delivery_status_sender, delivery_status_receiver) = mpsc::unbounded();
+        let subscriptions = MessageRouter::new().start(&buffer_requester, delivery_status_receiver);
+
+        let reply_key_dir = tempfile::tempdir().unwrap();
+        let rpc_context = RpcContext::new(
+            TopologyAccessor::new(),
+            ReplyKeyStorage::load(reply_key_dir.path()).unwrap(),
+            Duration::from_millis(50),
+            DEFAULT_NUM_MIX_HOPS,
+        );
+        let self_address = Recipient::try_from_base58_string(SELF_ADDRESS).unwrap();
+
+        let mut handler = Handler::new(
+            msg_input,
+            subscriptions.clone(),
+            SelfAddress::new(self_address),
+            rpc_context,
+        );
+        handler.protocol_version = ProtocolVersion::V2;
+        handler.subscriber_id = Some(subscriptions.subscribe(Default::default()).0);
+
+        Fixture {
+            handler,
+            input_receiver,
+            _buffer_receiver: buffer_receiver,
+            _delivery_status_sender: delivery_status_sender,
+            _reply_key_dir: reply_key_dir,
+        }
+    }
+
+    fn response(message: Option<WsMessage>) -> v2::ServerResponse {
+        match message {
+            Some(WsMessage::Text(text)) => v2::ServerResponse::try_from_text(text).unwrap(),
+            other => panic!("unexpected response {:?}", other),
+        }
+    }
+
+    #[test]
+    fn protocol_version_is_negotiated_from_query() {
+        assert_eq!(ProtocolVersion::from_query(None), Ok(ProtocolVersion::V1));
+        assert_eq!(
+            ProtocolVersion::from_query(Some("prefix=chat%2F")),
+            Ok(ProtocolVersion::V1)
+        );
+        assert_eq!(
+            ProtocolVersion::from_query(Some("prefix=chat%2F&version=2")),
+            Ok(ProtocolVersion::V2)
+        );
+        assert!(ProtocolVersion::from_query(Some("version=3")).is_err());
+    }
+
+    #[tokio::test]
+    async fn v2_calls_are_dispatched() {
+        let mut fixture = fixture();
+        let handler = &mut fixture.handler;
+
+        let result = handler
+            .handle_v2_call(Some(RequestId::Number(1)), ClientCall::SelfAddress)
+            .await
+            .unwrap();
+        assert_eq!(result, serde_json::json!({ "address": SELF_ADDRESS }));
 
-        let (reconstructed_sender, reconstructed_receiver) = mpsc::unbounded();
+        let result = handler
+            .handle_v2_call(None, ClientCall::Version)
+            .await
+            .unwrap();
+        assert_eq!(result["protocol"], v2::PROTOCOL_VERSION);
 
-        // tell the buffer to start sending stuff to us
-        self.buffer_requester
-            .unbounded_send(ReceivedBufferMessage::ReceiverAnnounce(
-                reconstructed_sender,
-            ))
-            .expect("the buffer request failed!");
+        // without any topology the network related calls fail
+        let result = handler
+            .handle_v2_call(None, ClientCall::Health)
+            .await
+            .unwrap();
+        assert_eq!(result["topologyRoutable"], false);
+        let err = handler
+            .handle_v2_call(None, ClientCall::GetTopology)
+            .await
+            .unwrap_err();
+        assert_eq!(err.error_code(), Some(ErrorCode::NetworkUnavailable));
+
+        let recipient = Recipient::try_from_base58_string(SELF_ADDRESS).unwrap();
+        let send = |track_delivery| ClientCall::Send {
+            recipient,
+            message: vec![0, 255, 1],
+            with_reply_surb: false,
+            track_delivery,
+        };
+        let id = RequestId::String("foo".to_string());
+        let result = handler
+            .handle_v2_call(Some(id.clone()), send(true))
+            .await
+            .unwrap();
+        assert_eq!(result, serde_json::Value::Null);
+        assert_eq!(handler.tracked_requests.values().collect::<Vec<_>>(), [&id]);
+        match fixture.input_receiver.try_next().unwrap().unwrap() {
+            InputMessage::Fresh {
+                data, request_id, ..
+            } => {
+                assert_eq!(data, vec![0, 255, 1]);
+                assert!(request_id.is_some());
+            }
+            _ => panic!("expected a fresh message"),
+        }
 
-        self.listen_for_requests(reconstructed_receiver).await;
+        // delivery can't be reported without the id to correlate it with
+        let handler = &mut fixture.handler;
+        handler.handle_v2_call(None, send(true)).await.unwrap();
+        assert_eq!(handler.tracked_requests.len(), 1);
+        match fixture.input_receiver.try_next().unwrap().unwrap() {
+            InputMessage::Fresh { request_id, .. } => assert!(request_id.is_none()),
+            _ => panic!("expected a fresh message"),
+        }
+    }
+
+    #[tokio::test]
+    async fn v2_delivery_statuses_are_mapped_to_notifications() {
+        let mut handler = fixture().handler;
+        let id = RequestId::Number(42);
+        let tracked = handler.track_v2_request(Some(id.clone()), true).unwrap();
+
+        assert_eq!(
+            handler.v2_delivery_notification(tracked, DeliveryStatus::Sent),
+            Some(Notification::DeliveryStatus {
+                id: id.clone(),
+                status: v2::DeliveryStatus::Sent
+            })
+        );
+        assert_eq!(
+            handler.v2_delivery_notification(tracked, DeliveryStatus::GaveUp),
+            Some(Notification::DeliveryStatus {
+                id,
+                status: v2::DeliveryStatus::GaveUp
+            })
+        );
+        // the request is forgotten after the final status
+        assert!(handler.tracked_requests.is_empty());
+        assert_eq!(
+            handler.v2_delivery_notification(tracked, DeliveryStatus::Acknowledged),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn v2_requests_are_accepted_in_binary_frames() {
+        let mut handler = fixture().handler;
+
+        let request = v2::ClientRequest::new(Some(RequestId::Number(7)), ClientCall::SelfAddress);
+        let response = response(
+            handler
+                .handle_ws_request(WsMessage::Binary(request.into_text().into_bytes()))
+                .await,
+        );
+        assert_eq!(response.id, Some(RequestId::Number(7)));
+        assert!(matches!(response.outcome, v2::Outcome::Result(_)));
+
+        let response = response_error(
+            handler
+                .handle_ws_request(WsMessage::Binary(vec![0, 159, 146, 150]))
+                .await,
+        );
+        assert_eq!(response.error_code(), Some(ErrorCode::ParseError));
+    }
+
+    fn response_error(message: Option<WsMessage>) -> ErrorObject {
+        match response(message).outcome {
+            v2::Outcome::Error(err) => err,
+            other => panic!("unexpected outcome {:?}", other),
+        }
     }
 }
@@ -3,24 +3,11 @@
 
 use super::handler::Handler;
 use log::*;
-use std::{net::SocketAddr, process, sync::Arc};
-use tokio::io::AsyncWriteExt;
-use tokio::{sync::Notify, task::JoinHandle};
-
-enum State {
-    Connected,
-    AwaitingConnection,
-}
-
-impl State {
-    fn is_connected(&self) -> bool {
-        matches!(self, State::Connected)
-    }
-}
+use std::{net::SocketAddr, process};
+use tokio::task::JoinHandle;
 
 pub(crate) struct Listener {
     address: SocketAddr,
-    state: State,
 }
 
 impl Listener {
@@ -28,7 +15,6 @@ impl Listener {
         Listener {
             // unless we find compelling reason not to, just listen on local only
             address: SocketAddr::new("127.0.0.1".parse().unwrap(), port),
-            state: State::AwaitingConnection,
         }
     }
 
@@ -41,47 +27,16 @@ impl Listener {
             }
         };
 
-        let notify = Arc::new(Notify::new());
-
         loop {
-            tokio::select! {
-                _ = notify.notified() => {
-                    // our connection terminated - we are open to a new one now!
-                    self.state = State::AwaitingConnection;
-                }
-                new_conn = tcp_listener.accept() => {
-                    match new_conn {
-                        Ok((mut socket, remote_addr)) => {
-                            debug!("Received connection from {:?}", remote_addr);
-                            if self.state.is_connected() {
-                                warn!("tried to duplicate!");
-                                // if we've already got a connection, don't allow another one
-                                debug!("but there was already a connection present!");
-                                // while we only ever want to accept a single connection, we don't want
-                                // to leave clients hanging (and also allow for reconnection if it somehow
-                                // was dropped)
-                                match socket.shutdown().await {
-                                    Ok(_) => trace!(
-                                        "closed the connection between attempting websocket handshake"
-                                    ),
-                                    Err(e) => warn!("failed to cleanly close the connection - {:?}", e),
-                                };
-                            } else {
-                                // even though we're spawning a new task with the handler here, we will only ever spawn a single one.
-                                // it's done so that any new connections to this listener could be rejected rather than left
-                                // hanging because the executor doesn't come back here
-                                let notify_clone = Arc::clone(&notify);
-                                let fresh_handler = handler.clone();
-                                tokio::spawn(async move {
-                                    fresh_handler.handle_connection(socket).await;
-                                    notify_clone.notify_one();
-                                });
-                                self.state = State::Connected;
-                            }
-                        }
-                        Err(e) => warn!("failed to get client: {:?}", e),
-                    }
+            match tcp_listener.accept().await {
+                Ok((socket, remote_addr)) => {
+                    debug!("Received connection from {:?}", remote_addr);
+                    // every connection gets its own handler subscribed to the received messages,
+                    // so that multiple local services could share this client
+                    let fresh_handler = handler.clone();
+                    tokio::spawn(async move { fresh_handler.handle_connection(socket).await });
                 }
+                Err(e) => warn!("failed to get client: {:?}", e),
             }
         }
     }
@@ -3,6 +3,10 @@
 
 pub(crate) use handler::Handler;
 pub(crate) use listener::Listener;
+pub(crate) use rpc::RpcContext;
+pub(crate) use subscriptions::MessageRouter;
 
 pub(crate) mod handler;
 pub(crate) mod listener;
+pub(crate) mod rpc;
+pub(crate) mod subscriptions;
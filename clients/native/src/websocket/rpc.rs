@@ -0,0 +1,186 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use client_core::client::reply_key_storage::ReplyKeyStorage;
+use client_core::client::topology_control::TopologyAccessor;
+use nymsphinx::addressing::clients::Recipient;
+use nymsphinx::anonymous_replies::ReplySurb;
+use rand::rngs::OsRng;
+use std::time::Duration;
+use topology::{gateway, mix, NymTopology};
+use websocket_requests::v2::{
+    CreateReplySurbsResult, ErrorCode, ErrorObject, GatewayDetails, HealthResult, MixnodeDetails,
+    RevokeReplySurbResult, TopologyResult,
+};
+
+fn mixnode_details(layer: u8, node: &mix::Node) -> MixnodeDetails {
+    MixnodeDetails {
+        mix_id: node.mix_id,
+        identity_key: node.identity_key.to_base58_string(),
+        owner: node.owner.clone(),
+        host: node.host.to_string(),
+        layer,
+        version: node.version.clone(),
+    }
+}
+
+fn gateway_details(node: &gateway::Node) -> GatewayDetails {
+    GatewayDetails {
+        identity_key: node.identity_key.to_base58_string(),
+        owner: node.owner.clone(),
+        host: node.host.to_string(),
+        clients_port: node.clients_port,
+        version: node.version.clone(),
+    }
+}
+
+fn network_unavailable() -> ErrorObject {
+    ErrorObject::new(
+        ErrorCode::NetworkUnavailable,
+        "the client does not have a valid network topology",
+    )
+}
+
+/// Everything needed to answer the v2 requests that are not about sending messages.
+#[derive(Clone)]
+pub(crate) struct RpcContext {
+    topology_accessor: TopologyAccessor,
+    reply_key_storage: ReplyKeyStorage,
+    average_packet_delay: Duration,
+    num_mix_hops: u8,
+}
+
+impl RpcContext {
+    pub(crate) fn new(
+        topology_accessor: TopologyAccessor,
+        reply_key_storage: ReplyKeyStorage,
+        average_packet_delay: Duration,
+        num_mix_hops: u8,
+    ) -> Self {
+        RpcContext {
+            topology_accessor,
+            reply_key_storage,
+            average_packet_delay,
+            num_mix_hops,
+        }
+    }
+
+    pub(crate) async fn health(&self, self_address: &Recipient) -> HealthResult {
+        let permit = self.topology_accessor.get_read_permit().await;
+        let topology: &Option<NymTopology> = permit.as_ref();
+        match topology {
+            None => HealthResult {
+                topology_routable: false,
+                gateway_online: false,
+            },
+            Some(topology) => HealthResult {
+                topology_routable: topology.can_construct_path_through(self.num_mix_hops),
+                gateway_online: topology.gateway_exists(self_address.gateway()),
+            },
+        }
+    }
+
+    pub(crate) async fn topology(&self) -> Result<TopologyResult, ErrorObject> {
+        let permit = self.topology_accessor.get_read_permit().await;
+        let topology: &Option<NymTopology> = permit.as_ref();
+        let topology = topology.as_ref().ok_or_else(network_unavailable)?;
+
+        let mut layers: Vec<_> = topology.mixes().keys().copied().collect();
+        layers.sort_unstable();
+        let mixnodes = layers
+            .into_iter()
+            .flat_map(|layer| {
+                topology.mixes()[&layer]
+                    .iter()
+                    .map(move |node| mixnode_details(layer, node))
+            })
+            .collect();
+
+        Ok(TopologyResult {
+            mixnodes,
+            gateways: topology.gateways().iter().map(gateway_details).collect(),
+        })
+    }
+
+    pub(crate) async fn gateway(
+        &self,
+        self_address: &Recipient,
+    ) -> Result<GatewayDetails, ErrorObject> {
+        let permit = self.topology_accessor.get_read_permit().await;
+        let topology: &Option<NymTopology> = permit.as_ref();
+        let topology = topology.as_ref().ok_or_else(network_unavailable)?;
+
+        topology
+            .gateways()
+            .iter()
+            .find(|gateway| gateway.identity() == self_address.gateway())
+            .map(gateway_details)
+            .ok_or_else(|| {
+                ErrorObject::new(
+                    ErrorCode::NetworkUnavailable,
+                    format!(
+                        "gateway {} is not present in the current topology",
+                        self_address.gateway().to_base58_string()
+                    ),
+                )
+            })
+    }
+
+    pub(crate) async fn create_reply_surbs(
+        &mut self,
+        self_address: &Recipient,
+        count: usize,
+    ) -> Result<CreateReplySurbsResult, ErrorObject> {
+        let permit = self.topology_accessor.get_read_permit().await;
+        let topology: &Option<NymTopology> = permit.as_ref();
+        let topology = topology.as_ref().ok_or_else(network_unavailable)?;
+
+        let mut reply_surbs = Vec::with_capacity(count);
+        for _ in 0..count {
+            let reply_surb = ReplySurb::construct(
+                &mut OsRng,
+                self_address,
+                self.average_packet_delay,
+                topology,
+                self.num_mix_hops,
+            )
+            .map_err(|err| {
+                ErrorObject::new(
+                    ErrorCode::NetworkUnavailable,
+                    format!("failed to construct a reply SURB - {:?}", err),
+                )
+            })?;
+
+            // without the key, whatever is sent back with this SURB would be unreadable
+            self.reply_key_storage
+                .insert_encryption_key(reply_surb.encryption_key().clone())
+                .map_err(|err| {
+                    ErrorObject::new(
+                        ErrorCode::RequestFailed,
+                        format!("failed to store the reply SURB key - {:?}", err),
+                    )
+                })?;
+            reply_surbs.push(reply_surb.to_base58_string());
+        }
+
+        Ok(CreateReplySurbsResult { reply_surbs })
+    }
+
+    pub(crate) fn revoke_reply_surb(
+        &self,
+        reply_surb: &ReplySurb,
+    ) -> Result<RevokeReplySurbResult, ErrorObject> {
+        let digest = reply_surb.encryption_key().compute_digest();
+        self.reply_key_storage
+            .get_and_remove_encryption_key(digest)
+            .map(|key| RevokeReplySurbResult {
+                revoked: key.is_some(),
+            })
+            .map_err(|err| {
+                ErrorObject::new(
+                    ErrorCode::RequestFailed,
+                    format!("failed to remove the reply SURB key - {:?}", err),
+                )
+            })
+    }
+}
@@ -0,0 +1,528 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use client_core::client::delivery_status::{
+    DeliveryRequestId, DeliveryStatus, DeliveryStatusReceiver, DeliveryStatusUpdate,
+};
+use client_core::client::received_buffer::{
+    ReceivedBufferMessage, ReceivedBufferRequestSender, ReconstructedMessagesReceiver,
+};
+use futures::channel::mpsc;
+use futures::StreamExt;
+use log::*;
+use nymsphinx::receiver::ReconstructedMessage;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Maximum number of received messages kept while there's no subscriber to accept them.
+/// Once it's reached, the oldest messages get dropped.
+const MAX_UNDELIVERED_MESSAGES: usize = 1000;
+
+pub(crate) type SubscriberId = u64;
+
+pub(crate) type SubscriberEventSender = mpsc::UnboundedSender<SubscriberEvent>;
+pub(crate) type SubscriberEventReceiver = mpsc::UnboundedReceiver<SubscriberEvent>;
+
+type SubscriptionRequestSender = mpsc::UnboundedSender<SubscriptionRequest>;
+type SubscriptionRequestReceiver = mpsc::UnboundedReceiver<SubscriptionRequest>;
+
+/// Events pushed to a particular websocket connection.
+#[derive(Debug)]
+pub(crate) enum SubscriberEvent {
+    /// Messages received from the mix network that were routed to this connection.
+    Received(Vec<ReconstructedMessage>),
+
+    /// Change in the delivery status of a message sent by this connection,
+    /// identified by the request id the connection has attached to it.
+    DeliveryStatus {
+        request_id: u64,
+        status: DeliveryStatus,
+    },
+}
+
+/// Determines which of the received messages should be pushed to particular websocket connection.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct SubscriptionFilter {
+    /// Prefix the content of the message has to start with. Empty prefix matches all messages.
+    prefix: Vec<u8>,
+}
+
+impl SubscriptionFilter {
+    pub(crate) fn new(prefix: Vec<u8>) -> Self {
+        SubscriptionFilter { prefix }
+    }
+
+    /// Recovers the filter from the query of the websocket handshake request,
+    /// i.e. `ws://127.0.0.1:1977/?prefix=my-service`.
+    pub(crate) fn from_query(query: Option<&str>) -> Self {
+        let prefix = query
+            .and_then(|query| {
+                url::form_urlencoded::parse(query.as_bytes())
+                    .find(|(key, _)| key == "prefix")
+                    .map(|(_, value)| value.into_owned().into_bytes())
+            })
+            .unwrap_or_default();
+
+        SubscriptionFilter { prefix }
+    }
+
+    /// If the message is accepted by the filter, returns the length of the matched prefix,
+    /// so that the most specific subscription could be chosen.
+    fn matches(&self, message: &ReconstructedMessage) -> Option<usize> {
+        if message.message.starts_with(&self.prefix) {
+            Some(self.prefix.len())
+        } else {
+            None
+        }
+    }
+}
+
+enum SubscriptionRequest {
+    Subscribe {
+        id: SubscriberId,
+        filter: SubscriptionFilter,
+        sender: SubscriberEventSender,
+    },
+    UpdateFilter {
+        id: SubscriberId,
+        filter: SubscriptionFilter,
+    },
+    Unsubscribe {
+        id: SubscriberId,
+    },
+    TrackDelivery {
+        id: DeliveryRequestId,
+        subscriber: SubscriberId,
+        request_id: u64,
+    },
+}
+
+struct Subscriber {
+    filter: SubscriptionFilter,
+    sender: SubscriberEventSender,
+}
+
+/// Handle used by the websocket connections to (un)subscribe from the received messages.
+#[derive(Clone)]
+pub(crate) struct Subscriptions {
+    request_sender: SubscriptionRequestSender,
+    next_id: Arc<AtomicU64>,
+
+    /// Request ids chosen by the connections are only unique within them, so the messages
+    /// are sent off with globally unique ids instead.
+    next_delivery_id: Arc<AtomicU64>,
+}
+
+impl Subscriptions {
+    pub(crate) fn subscribe(
+        &self,
+        filter: SubscriptionFilter,
+    ) -> (SubscriberId, SubscriberEventReceiver) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = mpsc::unbounded();
+        self.send_request(SubscriptionRequest::Subscribe { id, filter, sender });
+        (id, receiver)
+    }
+
+    pub(crate) fn update_filter(&self, id: SubscriberId, filter: SubscriptionFilter) {
+        self.send_request(SubscriptionRequest::UpdateFilter { id, filter })
+    }
+
+    pub(crate) fn unsubscribe(&self, id: SubscriberId) {
+        self.send_request(SubscriptionRequest::Unsubscribe { id })
+    }
+
+    /// Makes sure the delivery status of the message is reported back to the subscriber under
+    /// its own request id. Returns the id the message should be sent off with.
+    pub(crate) fn track_delivery(
+        &self,
+        subscriber: SubscriberId,
+        request_id: u64,
+    ) -> DeliveryRequestId {
+        let id = self.next_delivery_id.fetch_add(1, Ordering::Relaxed);
+        self.send_request(SubscriptionRequest::TrackDelivery {
+            id,
+            subscriber,
+            request_id,
+        });
+        id
+    }
+
+    fn send_request(&self, request: SubscriptionRequest) {
+        self.request_sender
+            .unbounded_send(request)
+            .expect("the message router has stopped!")
+    }
+}
+
+/// Sits between the received messages buffer and all connected websocket clients, so that
+/// multiple local services could share the same Nym identity.
+///
+/// Each reconstructed message is pushed to exactly one subscriber - the one with the most
+/// specific (i.e. the longest) matching prefix, with ties resolved in favour of the longest
+/// connected one. Messages that do not match any subscriber are kept until one that accepts
+/// them connects, up to [MAX_UNDELIVERED_MESSAGES] of them.
+///
+/// It also pushes the delivery status of sent messages back to the connections that sent them.
+pub(crate) struct MessageRouter {
+    subscribers: BTreeMap<SubscriberId, Subscriber>,
+    undelivered: VecDeque<ReconstructedMessage>,
+    tracked_deliveries: HashMap<DeliveryRequestId, (SubscriberId, u64)>,
+}
+
+impl MessageRouter {
+    pub(crate) fn new() -> Self {
+        MessageRouter {
+            subscribers: BTreeMap::new(),
+            undelivered: VecDeque::new(),
+            tracked_deliveries: HashMap::new(),
+        }
+    }
+
+    fn subscribe(
+        &mut self,
+        id: SubscriberId,
+        filter: SubscriptionFilter,
+        sender: SubscriberEventSender,
+    ) {
+        debug!("Websocket subscriber {} connected", id);
+        self.subscribers.insert(id, Subscriber { filter, sender });
+        self.retry_undelivered();
+    }
+
+    fn update_filter(&mut self, id: SubscriberId, filter: SubscriptionFilter) {
+        match self.subscribers.get_mut(&id) {
+            Some(subscriber) => subscriber.filter = filter,
+            None => warn!("Tried to update filter of non-existent subscriber {}", id),
+        }
+        self.retry_undelivered();
+    }
+
+    fn unsubscribe(&mut self, id: SubscriberId) {
+        debug!("Websocket subscriber {} disconnected", id);
+        self.subscribers.remove(&id);
+        self.tracked_deliveries
+            .retain(|_, (subscriber, _)| *subscriber != id);
+    }
+
+    fn track_delivery(&mut self, id: DeliveryRequestId, subscriber: SubscriberId, request_id: u64) {
+        self.tracked_deliveries.insert(id, (subscriber, request_id));
+    }
+
+    fn report_delivery_status(&mut self, update: DeliveryStatusUpdate) {
+        let tracked = if update.status.is_final() {
+            self.tracked_deliveries.remove(&update.request_id)
+        } else {
+            self.tracked_deliveries.get(&update.request_id).copied()
+        };
+
+        let (subscriber_id, request_id) = match tracked {
+            Some(tracked) => tracked,
+            None => {
+                trace!(
+                    "Received delivery status of untracked message {}",
+                    update.request_id
+                );
+                return;
+            }
+        };
+
+        if let Some(subscriber) = self.subscribers.get(&subscriber_id) {
+            let event = SubscriberEvent::DeliveryStatus {
+                request_id,
+                status: update.status,
+            };
+            if subscriber.sender.unbounded_send(event).is_err() {
+                debug!(
+                    "Subscriber {} went away before learning about status of its message",
+                    subscriber_id
+                )
+            }
+        }
+    }
+
+    fn choose_subscriber(&self, message: &ReconstructedMessage) -> Option<SubscriberId> {
+        let mut chosen: Option<(SubscriberId, usize)> = None;
+        for (id, subscriber) in &self.subscribers {
+            if let Some(matched) = subscriber.filter.matches(message) {
+                // iteration goes in the order of subscription, so only replace on strictly better match
+                if chosen.map_or(true, |(_, best)| matched > best) {
+                    chosen = Some((*id, matched))
+                }
+            }
+        }
+        chosen.map(|(id, _)| id)
+    }
+
+    fn retry_undelivered(&mut self) {
+        if !self.undelivered.is_empty() {
+            let undelivered = std::mem::take(&mut self.undelivered);
+            self.route(undelivered.into());
+        }
+    }
+
+    fn route(&mut self, messages: Vec<ReconstructedMessage>) {
+        let mut batches: BTreeMap<SubscriberId, Vec<ReconstructedMessage>> = BTreeMap::new();
+        let mut dropped = 0;
+        for message in messages {
+            match self.choose_subscriber(&message) {
+                Some(id) => batches.entry(id).or_default().push(message),
+                None => {
+                    if self.undelivered.len() >= MAX_UNDELIVERED_MESSAGES {
+                        self.undelivered.pop_front();
+                        dropped += 1;
+                    }
+                    self.undelivered.push_back(message)
+                }
+            }
+        }
+        if dropped > 0 {
+            warn!(
+                "There are too many messages without any subscriber to accept them - dropped {} oldest",
+                dropped
+            );
+        }
+
+        let mut failed = Vec::new();
+        for (id, batch) in batches {
+            let subscriber = &self.subscribers[&id];
+            if let Err(err) = subscriber
+                .sender
+                .unbounded_send(SubscriberEvent::Received(batch))
+            {
+                warn!(
+                    "Subscriber {} went offline without explicit notification",
+                    id
+                );
+                if let SubscriberEvent::Received(batch) = err.into_inner() {
+                    failed.extend(batch)
+                }
+            }
+        }
+
+        if !failed.is_empty() {
+            // get rid of dead subscribers and attempt to deliver their messages to somebody else
+            self.subscribers
+                .retain(|_, subscriber| !subscriber.sender.is_closed());
+            self.route(failed);
+        }
+    }
+
+    fn handle_request(&mut self, request: SubscriptionRequest) {
+        match request {
+            SubscriptionRequest::Subscribe { id, filter, sender } => {
+                self.subscribe(id, filter, sender)
+            }
+            SubscriptionRequest::UpdateFilter { id, filter } => self.update_filter(id, filter),
+            SubscriptionRequest::Unsubscribe { id } => self.unsubscribe(id),
+            SubscriptionRequest::TrackDelivery {
+                id,
+                subscriber,
+                request_id,
+            } => self.track_delivery(id, subscriber, request_id),
+        }
+    }
+
+    async fn run(
+        mut self,
+        mut request_receiver: SubscriptionRequestReceiver,
+        mut reconstructed_receiver: ReconstructedMessagesReceiver,
+        mut delivery_status_receiver: DeliveryStatusReceiver,
+    ) {
+        loop {
+            tokio::select! {
+                // the deliveries have to be tracked before we could learn anything about them
+                biased;
+                request = request_receiver.next() => match request {
+                    Some(request) => self.handle_request(request),
+                    None => {
+                        trace!("MessageRouter: Stopping since the subscription channel closed");
+                        break;
+                    }
+                },
+                messages = reconstructed_receiver.next() => match messages {
+                    Some(messages) => self.route(messages),
+                    None => {
+                        trace!("MessageRouter: Stopping since the received buffer went away");
+                        break;
+                    }
+                },
+                update = delivery_status_receiver.next() => match update {
+                    Some(update) => self.report_delivery_status(update),
+                    None => {
+                        trace!("MessageRouter: Stopping since the delivery status channel closed");
+                        break;
+                    }
+                },
+            }
+        }
+    }
+
+    /// Announces the router to the received messages buffer and starts routing the messages
+    /// to the subscribed websocket connections.
+    pub(crate) fn start(
+        self,
+        buffer_requester: &ReceivedBufferRequestSender,
+        delivery_status_receiver: DeliveryStatusReceiver,
+    ) -> Subscriptions {
+        let (reconstructed_sender, reconstructed_receiver) = mpsc::unbounded();
+        buffer_requester
+            .unbounded_send(ReceivedBufferMessage::ReceiverAnnounce(
+                reconstructed_sender,
+            ))
+            .expect("the buffer request failed!");
+
+        let (request_sender, request_receiver) = mpsc::unbounded();
+        tokio::spawn(self.run(
+            request_receiver,
+            reconstructed_receiver,
+            delivery_status_receiver,
+        ));
+
+        Subscriptions {
+            request_sender,
+            next_id: Arc::new(AtomicU64::new(0)),
+            next_delivery_id: Arc::new(AtomicU64::new(0)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(content: &str) -> ReconstructedMessage {
+        ReconstructedMessage {
+            message: content.as_bytes().to_vec(),
+            reply_surb: None,
+        }
+    }
+
+    fn received(receiver: &mut SubscriberEventReceiver) -> Vec<String> {
+        let mut contents = Vec::new();
+        while let Ok(Some(event)) = receiver.try_next() {
+            if let SubscriberEvent::Received(messages) = event {
+                contents.extend(
+                    messages
+                        .into_iter()
+                        .map(|message| String::from_utf8(message.message).unwrap()),
+                )
+            }
+        }
+        contents
+    }
+
+    fn statuses(receiver: &mut SubscriberEventReceiver) -> Vec<(u64, DeliveryStatus)> {
+        let mut statuses = Vec::new();
+        while let Ok(Some(event)) = receiver.try_next() {
+            if let SubscriberEvent::DeliveryStatus { request_id, status } = event {
+                statuses.push((request_id, status))
+            }
+        }
+        statuses
+    }
+
+    #[test]
+    fn filter_is_recovered_from_handshake_query() {
+        assert_eq!(SubscriptionFilter::from_query(None), Default::default());
+        assert_eq!(
+            SubscriptionFilter::from_query(Some("foo=bar&prefix=chat%2F")),
+            SubscriptionFilter::new(b"chat/".to_vec())
+        );
+    }
+
+    #[test]
+    fn messages_are_routed_to_the_most_specific_subscriber() {
+        let mut router = MessageRouter::new();
+        let (catch_all_sender, mut catch_all) = mpsc::unbounded();
+        let (chat_sender, mut chat) = mpsc::unbounded();
+        let (other_chat_sender, mut other_chat) = mpsc::unbounded();
+
+        router.subscribe(0, Default::default(), catch_all_sender);
+        router.subscribe(1, SubscriptionFilter::new(b"chat/".to_vec()), chat_sender);
+        router.subscribe(
+            2,
+            SubscriptionFilter::new(b"chat/".to_vec()),
+            other_chat_sender,
+        );
+
+        router.route(vec![message("chat/hello"), message("wallet/balance")]);
+        assert_eq!(received(&mut catch_all), vec!["wallet/balance"]);
+        assert_eq!(received(&mut chat), vec!["chat/hello"]);
+        assert!(received(&mut other_chat).is_empty());
+
+        // once the first chat subscriber goes away, the other one takes over
+        router.unsubscribe(1);
+        router.route(vec![message("chat/bye")]);
+        assert_eq!(received(&mut other_chat), vec!["chat/bye"]);
+    }
+
+    #[test]
+    fn unmatched_messages_are_kept_until_matching_subscriber_appears() {
+        let mut router = MessageRouter::new();
+        let (chat_sender, mut chat) = mpsc::unbounded();
+        router.subscribe(0, SubscriptionFilter::new(b"chat/".to_vec()), chat_sender);
+
+        router.route(vec![message("wallet/balance"), message("chat/hello")]);
+        assert_eq!(received(&mut chat), vec!["chat/hello"]);
+
+        let (wallet_sender, mut wallet) = mpsc::unbounded();
+        router.subscribe(
+            1,
+            SubscriptionFilter::new(b"wallet/".to_vec()),
+            wallet_sender,
+        );
+        assert_eq!(received(&mut wallet), vec!["wallet/balance"]);
+
+        // messages of subscribers that went away without telling us are not lost either
+        drop(wallet);
+        router.update_filter(0, Default::default());
+        router.route(vec![message("wallet/history")]);
+        assert_eq!(received(&mut chat), vec!["wallet/history"]);
+    }
+
+    #[test]
+    fn oldest_undelivered_messages_are_dropped() {
+        let mut router = MessageRouter::new();
+        let messages = (0..MAX_UNDELIVERED_MESSAGES + 2)
+            .map(|i| message(&format!("wallet/{}", i)))
+            .collect();
+        router.route(messages);
+        assert_eq!(router.undelivered.len(), MAX_UNDELIVERED_MESSAGES);
+
+        let (wallet_sender, mut wallet) = mpsc::unbounded();
+        router.subscribe(0, Default::default(), wallet_sender);
+        let received = received(&mut wallet);
+        assert_eq!(received.len(), MAX_UNDELIVERED_MESSAGES);
+        assert_eq!(received[0], "wallet/2");
+        assert!(router.undelivered.is_empty());
+    }
+
+    #[test]
+    fn delivery_status_is_reported_to_the_sending_subscriber() {
+        let mut router = MessageRouter::new();
+        let (first_sender, mut first) = mpsc::unbounded();
+        let (second_sender, mut second) = mpsc::unbounded();
+        router.subscribe(0, Default::default(), first_sender);
+        router.subscribe(1, Default::default(), second_sender);
+
+        // both connections happened to choose the same request id
+        router.track_delivery(100, 0, 1);
+        router.track_delivery(101, 1, 1);
+
+        router.report_delivery_status(DeliveryStatusUpdate::new(100, DeliveryStatus::Sent));
+        router.report_delivery_status(DeliveryStatusUpdate::new(101, DeliveryStatus::GaveUp));
+        router.report_delivery_status(DeliveryStatusUpdate::new(100, DeliveryStatus::Acknowledged));
+        assert_eq!(
+            statuses(&mut first),
+            vec![(1, DeliveryStatus::Sent), (1, DeliveryStatus::Acknowledged)]
+        );
+        assert_eq!(statuses(&mut second), vec![(1, DeliveryStatus::GaveUp)]);
+
+        // nothing is reported after the final status
+        router.report_delivery_status(DeliveryStatusUpdate::new(100, DeliveryStatus::Acknowledged));
+        assert!(statuses(&mut first).is_empty());
+        assert!(router.tracked_deliveries.is_empty());
+    }
+}
@@ -5,3 +5,4 @@ pub mod error;
 pub mod requests;
 pub mod responses;
 mod text;
+pub mod v2;
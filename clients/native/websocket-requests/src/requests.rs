@@ -20,6 +20,15 @@ pub const REPLY_REQUEST_TAG: u8 = 0x01;
 /// Value tag representing [`SelfAddress`] variant of the [`ClientRequest`]
 pub const SELF_ADDRESS_REQUEST_TAG: u8 = 0x02;
 
+/// Value tag representing [`Subscribe`] variant of the [`ClientRequest`]
+pub const SUBSCRIBE_REQUEST_TAG: u8 = 0x03;
+
+/// Value tag representing [`Send`] variant of the [`ClientRequest`] with an attached request id
+pub const SEND_TRACKED_REQUEST_TAG: u8 = 0x04;
+
+/// Value tag representing [`Reply`] variant of the [`ClientRequest`] with an attached request id
+pub const REPLY_TRACKED_REQUEST_TAG: u8 = 0x05;
+
 #[allow(non_snake_case)]
 #[derive(Debug)]
 pub enum ClientRequest {
@@ -28,21 +37,66 @@ pub enum ClientRequest {
         message: Vec<u8>,
         // Perhaps we could change it to a number to indicate how many reply_SURBs we want to include?
         with_reply_surb: bool,
+        /// If set, the delivery status of the message is going to be reported back
+        /// under this id.
+        request_id: Option<u64>,
     },
     Reply {
         message: Vec<u8>,
         reply_surb: ReplySurb,
+        /// If set, the delivery status of the reply is going to be reported back
+        /// under this id.
+        request_id: Option<u64>,
     },
     SelfAddress,
+    /// Changes which of the received messages are pushed to this connection,
+    /// i.e. only those whose content starts with the specified prefix.
+    Subscribe {
+        prefix: Vec<u8>,
+    },
 }
 
 // we could have been parsing it directly TryFrom<WsMessage>, but we want to retain
 // information about whether it came from binary or text to send appropriate response back
 impl ClientRequest {
+    // the tracked variants of requests carry an additional request id straight after the tag
+    fn serialize_header(
+        untracked_tag: u8,
+        tracked_tag: u8,
+        request_id: Option<u64>,
+    ) -> impl Iterator<Item = u8> {
+        match request_id {
+            None => vec![untracked_tag],
+            Some(request_id) => std::iter::once(tracked_tag)
+                .chain(request_id.to_be_bytes().iter().cloned())
+                .collect(),
+        }
+        .into_iter()
+    }
+
+    // TAG || request_id || content
+    fn deserialize_request_id(b: &[u8]) -> Result<(u64, &[u8]), error::Error> {
+        if b.len() < 1 + size_of::<u64>() {
+            return Err(error::Error::new(
+                ErrorKind::TooShortRequest,
+                "not enough data provided to recover request id".to_string(),
+            ));
+        }
+
+        let request_id = u64::from_be_bytes(b[1..1 + size_of::<u64>()].try_into().unwrap());
+        Ok((request_id, &b[1 + size_of::<u64>()..]))
+    }
+
     // SEND_REQUEST_TAG || with_surb || recipient || data_len || data
-    fn serialize_send(recipient: Recipient, data: Vec<u8>, with_reply_surb: bool) -> Vec<u8> {
+    // SEND_TRACKED_REQUEST_TAG || request_id || with_surb || recipient || data_len || data
+    fn serialize_send(
+        recipient: Recipient,
+        data: Vec<u8>,
+        with_reply_surb: bool,
+        request_id: Option<u64>,
+    ) -> Vec<u8> {
         let data_len_bytes = (data.len() as u64).to_be_bytes();
-        std::iter::once(SEND_REQUEST_TAG)
+        Self::serialize_header(SEND_REQUEST_TAG, SEND_TRACKED_REQUEST_TAG, request_id)
             .chain(std::iter::once(with_reply_surb as u8))
             .chain(recipient.to_bytes().iter().cloned()) // will not be length prefixed because the length is constant
             .chain(data_len_bytes.iter().cloned())
@@ -50,20 +104,17 @@ impl ClientRequest {
             .collect()
     }
 
-    // SEND_REQUEST_TAG || with_reply || recipient || data_len || data
-    fn deserialize_send(b: &[u8]) -> Result<Self, error::Error> {
-        // we need to have at least 1 (tag) + 1 (reply flag) + Recipient::LEN + sizeof<u64> bytes
-        if b.len() < 2 + Recipient::LEN + size_of::<u64>() {
+    // with_reply || recipient || data_len || data
+    fn deserialize_send(b: &[u8], request_id: Option<u64>) -> Result<Self, error::Error> {
+        // we need to have at least 1 (reply flag) + Recipient::LEN + sizeof<u64> bytes
+        if b.len() < 1 + Recipient::LEN + size_of::<u64>() {
             return Err(error::Error::new(
                 ErrorKind::TooShortRequest,
                 "not enough data provided to recover 'send'".to_string(),
             ));
         }
 
-        // this MUST match because it was called by 'deserialize'
-        debug_assert_eq!(b[0], SEND_REQUEST_TAG);
-
-        let with_reply_surb = match b[1] {
+        let with_reply_surb = match b[0] {
             0 => false,
             1 => true,
             n => {
@@ -75,7 +126,7 @@ impl ClientRequest {
         };
 
         let mut recipient_bytes = [0u8; Recipient::LEN];
-        recipient_bytes.copy_from_slice(&b[2..2 + Recipient::LEN]);
+        recipient_bytes.copy_from_slice(&b[1..1 + Recipient::LEN]);
         let recipient = match Recipient::try_from_bytes(recipient_bytes) {
             Ok(recipient) => recipient,
             Err(err) => {
@@ -86,9 +137,9 @@ impl ClientRequest {
             }
         };
 
-        let data_len_bytes = &b[2 + Recipient::LEN..2 + Recipient::LEN + size_of::<u64>()];
+        let data_len_bytes = &b[1 + Recipient::LEN..1 + Recipient::LEN + size_of::<u64>()];
         let data_len = u64::from_be_bytes(data_len_bytes.try_into().unwrap());
-        let data = &b[2 + Recipient::LEN + size_of::<u64>()..];
+        let data = &b[1 + Recipient::LEN + size_of::<u64>()..];
         if data.len() as u64 != data_len {
             return Err(error::Error::new(
                 ErrorKind::MalformedRequest,
@@ -104,16 +155,22 @@ impl ClientRequest {
             with_reply_surb,
             recipient,
             message: data.to_vec(),
+            request_id,
         })
     }
 
     // REPLY_REQUEST_TAG || surb_len || surb || message_len || message
-    fn serialize_reply(message: Vec<u8>, reply_surb: ReplySurb) -> Vec<u8> {
+    // REPLY_TRACKED_REQUEST_TAG || request_id || surb_len || surb || message_len || message
+    fn serialize_reply(
+        message: Vec<u8>,
+        reply_surb: ReplySurb,
+        request_id: Option<u64>,
+    ) -> Vec<u8> {
         let reply_surb_bytes = reply_surb.to_bytes();
         let surb_len_bytes = (reply_surb_bytes.len() as u64).to_be_bytes();
         let message_len_bytes = (message.len() as u64).to_be_bytes();
 
-        std::iter::once(REPLY_REQUEST_TAG)
+        Self::serialize_header(REPLY_REQUEST_TAG, REPLY_TRACKED_REQUEST_TAG, request_id)
             .chain(surb_len_bytes.iter().cloned())
             .chain(reply_surb_bytes.into_iter())
             .chain(message_len_bytes.iter().cloned())
@@ -121,26 +178,22 @@ impl ClientRequest {
             .collect()
     }
 
-    // REPLY_REQUEST_TAG || surb_len || surb || message_len || message
-    fn deserialize_reply(b: &[u8]) -> Result<Self, error::Error> {
+    // surb_len || surb || message_len || message
+    fn deserialize_reply(b: &[u8], request_id: Option<u64>) -> Result<Self, error::Error> {
         // we need to have at the very least 2 * sizeof<u64> bytes (in case, for some peculiar reason
         // message and reply surb were 0 len - the request would still be malformed, but would in theory
         // be parse'able)
-        if b.len() < 1 + 2 * size_of::<u64>() {
+        if b.len() < 2 * size_of::<u64>() {
             return Err(error::Error::new(
                 ErrorKind::TooShortRequest,
                 "not enough data provided to recover 'reply'".to_string(),
             ));
         }
 
-        // this MUST match because it was called by 'deserialize'
-        debug_assert_eq!(b[0], REPLY_REQUEST_TAG);
-
-        let reply_surb_len =
-            u64::from_be_bytes(b[1..1 + size_of::<u64>()].as_ref().try_into().unwrap());
+        let reply_surb_len = u64::from_be_bytes(b[..size_of::<u64>()].as_ref().try_into().unwrap());
 
         // make sure we won't go out of bounds here
-        if reply_surb_len > (b.len() - 1 + 2 * size_of::<u64>()) as u64 {
+        if reply_surb_len > (b.len() - 2 * size_of::<u64>()) as u64 {
             return Err(error::Error::new(
                 ErrorKind::MalformedRequest,
                 format!(
@@ -150,9 +203,9 @@ impl ClientRequest {
             ));
         }
 
-        let surb_bound = 1 + size_of::<u64>() + reply_surb_len as usize;
+        let surb_bound = size_of::<u64>() + reply_surb_len as usize;
 
-        let reply_surb_bytes = &b[1 + size_of::<u64>()..surb_bound];
+        let reply_surb_bytes = &b[size_of::<u64>()..surb_bound];
         let reply_surb = match ReplySurb::from_bytes(reply_surb_bytes) {
             Ok(reply_surb) => reply_surb,
             Err(err) => {
@@ -186,6 +239,7 @@ impl ClientRequest {
         Ok(ClientRequest::Reply {
             reply_surb,
             message: message.to_vec(),
+            request_id,
         })
     }
 
@@ -202,20 +256,64 @@ impl ClientRequest {
         ClientRequest::SelfAddress
     }
 
+    // SUBSCRIBE_REQUEST_TAG || prefix_len || prefix
+    fn serialize_subscribe(prefix: Vec<u8>) -> Vec<u8> {
+        let prefix_len_bytes = (prefix.len() as u64).to_be_bytes();
+        std::iter::once(SUBSCRIBE_REQUEST_TAG)
+            .chain(prefix_len_bytes.iter().cloned())
+            .chain(prefix.into_iter())
+            .collect()
+    }
+
+    // SUBSCRIBE_REQUEST_TAG || prefix_len || prefix
+    fn deserialize_subscribe(b: &[u8]) -> Result<Self, error::Error> {
+        // we need to have at least 1 (tag) + sizeof<u64> bytes
+        if b.len() < 1 + size_of::<u64>() {
+            return Err(error::Error::new(
+                ErrorKind::TooShortRequest,
+                "not enough data provided to recover 'subscribe'".to_string(),
+            ));
+        }
+
+        // this MUST match because it was called by 'deserialize'
+        debug_assert_eq!(b[0], SUBSCRIBE_REQUEST_TAG);
+
+        let prefix_len = u64::from_be_bytes(b[1..1 + size_of::<u64>()].try_into().unwrap());
+        let prefix = &b[1 + size_of::<u64>()..];
+        if prefix.len() as u64 != prefix_len {
+            return Err(error::Error::new(
+                ErrorKind::MalformedRequest,
+                format!(
+                    "prefix len has inconsistent length. specified: {} got: {}",
+                    prefix_len,
+                    prefix.len()
+                ),
+            ));
+        }
+
+        Ok(ClientRequest::Subscribe {
+            prefix: prefix.to_vec(),
+        })
+    }
+
     pub fn serialize(self) -> Vec<u8> {
         match self {
             ClientRequest::Send {
                 recipient,
                 message,
                 with_reply_surb,
-            } => Self::serialize_send(recipient, message, with_reply_surb),
+                request_id,
+            } => Self::serialize_send(recipient, message, with_reply_surb, request_id),
 
             ClientRequest::Reply {
                 message,
                 reply_surb,
-            } => Self::serialize_reply(message, reply_surb),
+                request_id,
+            } => Self::serialize_reply(message, reply_surb, request_id),
 
             ClientRequest::SelfAddress => Self::serialize_self_address(),
+
+            ClientRequest::Subscribe { prefix } => Self::serialize_subscribe(prefix),
         }
     }
 
@@ -242,9 +340,18 @@ impl ClientRequest {
 
         // determine what kind of request that is and try to deserialize it
         match request_tag {
-            SEND_REQUEST_TAG => Self::deserialize_send(b),
-            REPLY_REQUEST_TAG => Self::deserialize_reply(b),
+            SEND_REQUEST_TAG => Self::deserialize_send(&b[1..], None),
+            REPLY_REQUEST_TAG => Self::deserialize_reply(&b[1..], None),
+            SEND_TRACKED_REQUEST_TAG => {
+                let (request_id, content) = Self::deserialize_request_id(b)?;
+                Self::deserialize_send(content, Some(request_id))
+            }
+            REPLY_TRACKED_REQUEST_TAG => {
+                let (request_id, content) = Self::deserialize_request_id(b)?;
+                Self::deserialize_reply(content, Some(request_id))
+            }
             SELF_ADDRESS_REQUEST_TAG => Ok(Self::deserialize_self_address(b)),
+            SUBSCRIBE_REQUEST_TAG => Self::deserialize_subscribe(b),
             n => Err(error::Error::new(
                 ErrorKind::UnknownRequest,
                 format!("type {}", n),
@@ -280,6 +387,7 @@ mod tests {
             recipient,
             message: b"foomp".to_vec(),
             with_reply_surb: false,
+            request_id: None,
         };
 
         let bytes = send_request_no_surb.serialize();
@@ -289,10 +397,12 @@ mod tests {
                 recipient,
                 message,
                 with_reply_surb,
+                request_id,
             } => {
                 assert_eq!(recipient.to_string(), recipient_string);
                 assert_eq!(message, b"foomp".to_vec());
-                assert!(!with_reply_surb)
+                assert!(!with_reply_surb);
+                assert!(request_id.is_none())
             }
             _ => unreachable!(),
         }
@@ -301,6 +411,7 @@ mod tests {
             recipient,
             message: b"foomp".to_vec(),
             with_reply_surb: true,
+            request_id: Some(42),
         };
 
         let bytes = send_request_surb.serialize();
@@ -310,10 +421,12 @@ mod tests {
                 recipient,
                 message,
                 with_reply_surb,
+                request_id,
             } => {
                 assert_eq!(recipient.to_string(), recipient_string);
                 assert_eq!(message, b"foomp".to_vec());
-                assert!(with_reply_surb)
+                assert!(with_reply_surb);
+                assert_eq!(request_id, Some(42))
             }
             _ => unreachable!(),
         }
@@ -326,6 +439,7 @@ mod tests {
         let reply_request = ClientRequest::Reply {
             message: b"foomp".to_vec(),
             reply_surb,
+            request_id: None,
         };
 
         let bytes = reply_request.serialize();
@@ -334,9 +448,33 @@ mod tests {
             ClientRequest::Reply {
                 reply_surb,
                 message,
+                request_id,
             } => {
                 assert_eq!(reply_surb.to_base58_string(), reply_surb_string);
                 assert_eq!(message, b"foomp".to_vec());
+                assert!(request_id.is_none());
+            }
+            _ => unreachable!(),
+        }
+
+        let tracked_reply_request = ClientRequest::Reply {
+            message: b"foomp".to_vec(),
+            reply_surb: ReplySurb::from_base58_string(reply_surb_string).unwrap(),
+            request_id: Some(42),
+        };
+
+        let bytes = tracked_reply_request.serialize();
+        assert_eq!(bytes[0], REPLY_TRACKED_REQUEST_TAG);
+        let recovered = ClientRequest::deserialize(&bytes).unwrap();
+        match recovered {
+            ClientRequest::Reply {
+                reply_surb,
+                message,
+                request_id,
+            } => {
+                assert_eq!(reply_surb.to_base58_string(), reply_surb_string);
+                assert_eq!(message, b"foomp".to_vec());
+                assert_eq!(request_id, Some(42));
             }
             _ => unreachable!(),
         }
@@ -352,4 +490,25 @@ mod tests {
             _ => unreachable!(),
         }
     }
+
+    #[test]
+    fn subscribe_request_serialization_works() {
+        let subscribe_request = ClientRequest::Subscribe {
+            prefix: b"chat/".to_vec(),
+        };
+        let bytes = subscribe_request.serialize();
+        let recovered = ClientRequest::deserialize(&bytes).unwrap();
+        match recovered {
+            ClientRequest::Subscribe { prefix } => assert_eq!(prefix, b"chat/".to_vec()),
+            _ => unreachable!(),
+        }
+
+        let empty_subscribe_request = ClientRequest::Subscribe { prefix: Vec::new() };
+        let bytes = empty_subscribe_request.serialize();
+        let recovered = ClientRequest::deserialize(&bytes).unwrap();
+        match recovered {
+            ClientRequest::Subscribe { prefix } => assert!(prefix.is_empty()),
+            _ => unreachable!(),
+        }
+    }
 }
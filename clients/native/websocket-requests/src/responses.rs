@@ -23,11 +23,41 @@ pub const RECEIVED_RESPONSE_TAG: u8 = 0x01;
 /// Value tag representing [`SelfAddress`] variant of the [`ServerResponse`]
 pub const SELF_ADDRESS_RESPONSE_TAG: u8 = 0x02;
 
+/// Value tag representing [`Sent`] variant of the [`ServerResponse`]
+pub const SENT_RESPONSE_TAG: u8 = 0x03;
+
+/// Value tag representing [`Acknowledged`] variant of the [`ServerResponse`]
+pub const ACKNOWLEDGED_RESPONSE_TAG: u8 = 0x04;
+
+/// Value tag representing [`GaveUp`] variant of the [`ServerResponse`]
+pub const GAVE_UP_RESPONSE_TAG: u8 = 0x05;
+
+/// Value tag representing [`Expired`] variant of the [`ServerResponse`]
+pub const EXPIRED_RESPONSE_TAG: u8 = 0x06;
+
 #[derive(Debug)]
 pub enum ServerResponse {
     Received(ReconstructedMessage),
     SelfAddress(Recipient),
     Error(error::Error),
+    /// All fragments of the message with the specified request id have been sent to the mix network.
+    Sent {
+        request_id: u64,
+    },
+    /// All fragments of the message with the specified request id have been acknowledged
+    /// by the recipient.
+    Acknowledged {
+        request_id: u64,
+    },
+    /// The message with the specified request id could not be delivered despite
+    /// the maximum number of retransmissions.
+    GaveUp {
+        request_id: u64,
+    },
+    /// The message with the specified request id has not been acknowledged before its deadline.
+    Expired {
+        request_id: u64,
+    },
 }
 
 impl ServerResponse {
@@ -193,6 +223,33 @@ impl ServerResponse {
         Ok(ServerResponse::SelfAddress(recipient))
     }
 
+    // TAG || request_id
+    fn serialize_delivery_status(tag: u8, request_id: u64) -> Vec<u8> {
+        std::iter::once(tag)
+            .chain(request_id.to_be_bytes().iter().cloned())
+            .collect()
+    }
+
+    // TAG || request_id
+    fn deserialize_delivery_status(b: &[u8]) -> Result<Self, error::Error> {
+        if b.len() != 1 + size_of::<u64>() {
+            return Err(error::Error::new(
+                ErrorKind::TooShortResponse,
+                "not enough data provided to recover delivery status".to_string(),
+            ));
+        }
+
+        let request_id = u64::from_be_bytes(b[1..].try_into().unwrap());
+        match b[0] {
+            SENT_RESPONSE_TAG => Ok(ServerResponse::Sent { request_id }),
+            ACKNOWLEDGED_RESPONSE_TAG => Ok(ServerResponse::Acknowledged { request_id }),
+            GAVE_UP_RESPONSE_TAG => Ok(ServerResponse::GaveUp { request_id }),
+            EXPIRED_RESPONSE_TAG => Ok(ServerResponse::Expired { request_id }),
+            // this MUST NOT happen because it was called by 'deserialize'
+            n => unreachable!("{} is not a delivery status tag", n),
+        }
+    }
+
     // ERROR_RESPONSE_TAG || err_code || msg_len || msg
     fn serialize_error(error: error::Error) -> Vec<u8> {
         let message_len_bytes = (error.message.len() as u64).to_be_bytes();
@@ -273,6 +330,18 @@ impl ServerResponse {
             }
             ServerResponse::SelfAddress(address) => Self::serialize_self_address(address),
             ServerResponse::Error(err) => Self::serialize_error(err),
+            ServerResponse::Sent { request_id } => {
+                Self::serialize_delivery_status(SENT_RESPONSE_TAG, request_id)
+            }
+            ServerResponse::Acknowledged { request_id } => {
+                Self::serialize_delivery_status(ACKNOWLEDGED_RESPONSE_TAG, request_id)
+            }
+            ServerResponse::GaveUp { request_id } => {
+                Self::serialize_delivery_status(GAVE_UP_RESPONSE_TAG, request_id)
+            }
+            ServerResponse::Expired { request_id } => {
+                Self::serialize_delivery_status(EXPIRED_RESPONSE_TAG, request_id)
+            }
         }
     }
 
@@ -303,6 +372,10 @@ impl ServerResponse {
             RECEIVED_RESPONSE_TAG => Self::deserialize_received(b),
             SELF_ADDRESS_RESPONSE_TAG => Self::deserialize_self_address(b),
             ERROR_RESPONSE_TAG => Self::deserialize_error(b),
+            SENT_RESPONSE_TAG
+            | ACKNOWLEDGED_RESPONSE_TAG
+            | GAVE_UP_RESPONSE_TAG
+            | EXPIRED_RESPONSE_TAG => Self::deserialize_delivery_status(b),
             n => Err(error::Error::new(
                 ErrorKind::UnknownResponse,
                 format!("type {}", n),
@@ -389,4 +462,31 @@ mod tests {
             _ => unreachable!(),
         }
     }
+
+    #[test]
+    fn delivery_status_response_serialization_works() {
+        let bytes = ServerResponse::Sent { request_id: 42 }.serialize();
+        match ServerResponse::deserialize(&bytes).unwrap() {
+            ServerResponse::Sent { request_id } => assert_eq!(request_id, 42),
+            _ => unreachable!(),
+        }
+
+        let bytes = ServerResponse::Acknowledged { request_id: 42 }.serialize();
+        match ServerResponse::deserialize(&bytes).unwrap() {
+            ServerResponse::Acknowledged { request_id } => assert_eq!(request_id, 42),
+            _ => unreachable!(),
+        }
+
+        let bytes = ServerResponse::GaveUp { request_id: 42 }.serialize();
+        match ServerResponse::deserialize(&bytes).unwrap() {
+            ServerResponse::GaveUp { request_id } => assert_eq!(request_id, 42),
+            _ => unreachable!(),
+        }
+
+        let bytes = ServerResponse::Expired { request_id: 42 }.serialize();
+        match ServerResponse::deserialize(&bytes).unwrap() {
+            ServerResponse::Expired { request_id } => assert_eq!(request_id, 42),
+            _ => unreachable!(),
+        }
+    }
 }
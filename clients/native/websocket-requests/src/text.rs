@@ -20,12 +20,19 @@ pub(super) enum ClientRequestText {
         message: String,
         recipient: String,
         with_reply_surb: bool,
+        #[serde(default)]
+        request_id: Option<u64>,
     },
     SelfAddress,
     #[serde(rename_all = "camelCase")]
     Reply {
         message: String,
         reply_surb: String,
+        #[serde(default)]
+        request_id: Option<u64>,
+    },
+    Subscribe {
+        prefix: String,
     },
 }
 
@@ -46,6 +53,7 @@ impl TryInto<ClientRequest> for ClientRequestText {
                 message,
                 recipient,
                 with_reply_surb,
+                request_id,
             } => {
                 let message_bytes = message.into_bytes();
                 let recipient = Recipient::try_from_base58_string(recipient).map_err(|err| {
@@ -56,12 +64,14 @@ impl TryInto<ClientRequest> for ClientRequestText {
                     message: message_bytes,
                     recipient,
                     with_reply_surb,
+                    request_id,
                 })
             }
             ClientRequestText::SelfAddress => Ok(ClientRequest::SelfAddress),
             ClientRequestText::Reply {
                 message,
                 reply_surb,
+                request_id,
             } => {
                 let message_bytes = message.into_bytes();
                 let reply_surb = ReplySurb::from_base58_string(reply_surb).map_err(|err| {
@@ -71,8 +81,12 @@ impl TryInto<ClientRequest> for ClientRequestText {
                 Ok(ClientRequest::Reply {
                     message: message_bytes,
                     reply_surb,
+                    request_id,
                 })
             }
+            ClientRequestText::Subscribe { prefix } => Ok(ClientRequest::Subscribe {
+                prefix: prefix.into_bytes(),
+            }),
         }
     }
 }
@@ -94,6 +108,22 @@ pub(super) enum ServerResponseText {
     Error {
         message: String,
     },
+    #[serde(rename_all = "camelCase")]
+    Sent {
+        request_id: u64,
+    },
+    #[serde(rename_all = "camelCase")]
+    Acknowledged {
+        request_id: u64,
+    },
+    #[serde(rename_all = "camelCase")]
+    GaveUp {
+        request_id: u64,
+    },
+    #[serde(rename_all = "camelCase")]
+    Expired {
+        request_id: u64,
+    },
 }
 
 impl TryFrom<String> for ServerResponseText {
@@ -135,6 +165,12 @@ impl From<ServerResponse> for ServerResponseText {
             ServerResponse::Error(err) => ServerResponseText::Error {
                 message: err.to_string(),
             },
+            ServerResponse::Sent { request_id } => ServerResponseText::Sent { request_id },
+            ServerResponse::Acknowledged { request_id } => {
+                ServerResponseText::Acknowledged { request_id }
+            }
+            ServerResponse::GaveUp { request_id } => ServerResponseText::GaveUp { request_id },
+            ServerResponse::Expired { request_id } => ServerResponseText::Expired { request_id },
         }
     }
 }
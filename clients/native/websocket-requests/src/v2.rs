@@ -0,0 +1,807 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Version 2 of the websocket protocol.
+//!
+//! Every message is a [JSON-RPC 2.0](https://www.jsonrpc.org/specification) object. Requests carry
+//! an `id` that is echoed back in the corresponding response, failures are reported with explicit
+//! error codes and anything pushed by the client on its own accord (received messages, delivery
+//! status of sent messages) is a notification, i.e. it has no `id`.
+//!
+//! Message payloads and subscription prefixes are arbitrary bytes and hence are always
+//! base64-encoded (using the standard alphabet with padding).
+//!
+//! The protocol version is negotiated during the websocket handshake, with v1 being the default.
+
+use crate::error::{self, ErrorKind};
+use nymsphinx::addressing::clients::Recipient;
+use nymsphinx::anonymous_replies::ReplySurb;
+use nymsphinx::receiver::ReconstructedMessage;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// Version of the protocol defined in this module.
+pub const PROTOCOL_VERSION: u8 = 2;
+
+/// All protocol versions understood by the client.
+pub const SUPPORTED_PROTOCOL_VERSIONS: [u8; 2] = [1, PROTOCOL_VERSION];
+
+/// Value of the `jsonrpc` member required in every message.
+pub const JSONRPC_VERSION: &str = "2.0";
+
+/// Maximum number of reply SURBs that can be created with a single request.
+pub const MAX_REPLY_SURBS_PER_REQUEST: usize = 100;
+
+/// Identifier chosen by the caller to correlate requests with responses and notifications.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RequestId {
+    Number(u64),
+    String(String),
+}
+
+/// Error codes defined by the JSON-RPC specification followed by the client-specific ones,
+/// which use the range reserved for implementation-defined server errors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// The received text is not valid JSON.
+    ParseError,
+
+    /// The received JSON is not a valid request object.
+    InvalidRequest,
+
+    /// The requested method does not exist.
+    MethodNotFound,
+
+    /// The method parameters are missing or invalid.
+    InvalidParams,
+
+    /// Something went wrong within the client itself.
+    InternalError,
+
+    /// The client does not currently know about a network topology it could use.
+    NetworkUnavailable,
+
+    /// The message does not fit in the available space.
+    MessageTooLong,
+
+    /// The request was valid, but the client failed to fulfil it.
+    RequestFailed,
+}
+
+impl ErrorCode {
+    pub fn code(&self) -> i64 {
+        match self {
+            ErrorCode::ParseError => -32700,
+            ErrorCode::InvalidRequest => -32600,
+            ErrorCode::MethodNotFound => -32601,
+            ErrorCode::InvalidParams => -32602,
+            ErrorCode::InternalError => -32603,
+            ErrorCode::NetworkUnavailable => -32000,
+            ErrorCode::MessageTooLong => -32001,
+            ErrorCode::RequestFailed => -32002,
+        }
+    }
+
+    pub fn from_code(code: i64) -> Option<Self> {
+        match code {
+            -32700 => Some(ErrorCode::ParseError),
+            -32600 => Some(ErrorCode::InvalidRequest),
+            -32601 => Some(ErrorCode::MethodNotFound),
+            -32602 => Some(ErrorCode::InvalidParams),
+            -32603 => Some(ErrorCode::InternalError),
+            -32000 => Some(ErrorCode::NetworkUnavailable),
+            -32001 => Some(ErrorCode::MessageTooLong),
+            -32002 => Some(ErrorCode::RequestFailed),
+            _ => None,
+        }
+    }
+}
+
+impl From<ErrorKind> for ErrorCode {
+    fn from(kind: ErrorKind) -> Self {
+        match kind {
+            ErrorKind::EmptyRequest | ErrorKind::TooShortRequest => ErrorCode::InvalidRequest,
+            ErrorKind::UnknownRequest => ErrorCode::MethodNotFound,
+            ErrorKind::MalformedRequest => ErrorCode::InvalidParams,
+            ErrorKind::EmptyResponse
+            | ErrorKind::TooShortResponse
+            | ErrorKind::UnknownResponse
+            | ErrorKind::MalformedResponse
+            | ErrorKind::Other => ErrorCode::InternalError,
+        }
+    }
+}
+
+/// The `error` member of a failed response.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorObject {
+    pub code: i64,
+    pub message: String,
+}
+
+impl ErrorObject {
+    pub fn new<S: Into<String>>(code: ErrorCode, message: S) -> Self {
+        ErrorObject {
+            code: code.code(),
+            message: message.into(),
+        }
+    }
+
+    /// Returns the known error code of this error, if any.
+    pub fn error_code(&self) -> Option<ErrorCode> {
+        ErrorCode::from_code(self.code)
+    }
+}
+
+impl From<error::Error> for ErrorObject {
+    fn from(err: error::Error) -> Self {
+        let message = err.to_string();
+        ErrorObject::new(err.kind.into(), message)
+    }
+}
+
+/// A request that could not be handled, alongside its id, if it could have been recovered.
+#[derive(Debug)]
+pub struct RequestError {
+    pub id: Option<RequestId>,
+    pub error: ErrorObject,
+}
+
+impl RequestError {
+    fn new<S: Into<String>>(id: Option<RequestId>, code: ErrorCode, message: S) -> Self {
+        RequestError {
+            id,
+            error: ErrorObject::new(code, message),
+        }
+    }
+
+    pub fn into_response(self) -> ServerResponse {
+        ServerResponse::new_error(self.id, self.error)
+    }
+}
+
+#[derive(Debug)]
+pub enum ClientCall {
+    /// Sends the message to the specified recipient.
+    Send {
+        recipient: Recipient,
+        message: Vec<u8>,
+        with_reply_surb: bool,
+        /// If set, the delivery status of the message is going to be reported back in
+        /// notifications referencing the id of the request.
+        track_delivery: bool,
+    },
+    /// Sends the message back using the provided reply SURB.
+    Reply {
+        reply_surb: ReplySurb,
+        message: Vec<u8>,
+        /// If set, the delivery status of the reply is going to be reported back in
+        /// notifications referencing the id of the request.
+        track_delivery: bool,
+    },
+    SelfAddress,
+    /// Changes which of the received messages are pushed to this connection,
+    /// i.e. only those whose content starts with the specified prefix.
+    Subscribe {
+        prefix: Vec<u8>,
+    },
+    /// Returns the protocol versions supported by the client.
+    Version,
+    /// Returns the current state of the client's connection to the network.
+    Health,
+    /// Returns the network topology currently used by the client.
+    GetTopology,
+    /// Returns details of the gateway the client is currently using, as seen in the topology.
+    GetGateway,
+    /// Creates new reply SURBs addressed to this client, so that they could be handed out
+    /// to other parties.
+    CreateReplySurbs {
+        count: usize,
+    },
+    /// Forgets the key of a previously created reply SURB, so that any reply using it
+    /// would no longer be readable.
+    RevokeReplySurb {
+        reply_surb: ReplySurb,
+    },
+}
+
+impl ClientCall {
+    fn method(&self) -> &'static str {
+        match self {
+            ClientCall::Send { .. } => "send",
+            ClientCall::Reply { .. } => "reply",
+            ClientCall::SelfAddress => "selfAddress",
+            ClientCall::Subscribe { .. } => "subscribe",
+            ClientCall::Version => "version",
+            ClientCall::Health => "health",
+            ClientCall::GetTopology => "getTopology",
+            ClientCall::GetGateway => "getGateway",
+            ClientCall::CreateReplySurbs { .. } => "createReplySurbs",
+            ClientCall::RevokeReplySurb { .. } => "revokeReplySurb",
+        }
+    }
+
+    fn params(&self) -> Option<Value> {
+        match self {
+            ClientCall::Send {
+                recipient,
+                message,
+                with_reply_surb,
+                track_delivery,
+            } => Some(json!(SendParams {
+                message: base64::encode(message),
+                recipient: recipient.to_string(),
+                with_reply_surb: *with_reply_surb,
+                track_delivery: *track_delivery,
+            })),
+            ClientCall::Reply {
+                reply_surb,
+                message,
+                track_delivery,
+            } => Some(json!(ReplyParams {
+                message: base64::encode(message),
+                reply_surb: reply_surb.to_base58_string(),
+                track_delivery: *track_delivery,
+            })),
+            ClientCall::Subscribe { prefix } => Some(json!(SubscribeParams {
+                prefix: base64::encode(prefix),
+            })),
+            ClientCall::CreateReplySurbs { count } => {
+                Some(json!(CreateReplySurbsParams { count: *count }))
+            }
+            ClientCall::RevokeReplySurb { reply_surb } => Some(json!(RevokeReplySurbParams {
+                reply_surb: reply_surb.to_base58_string(),
+            })),
+            ClientCall::SelfAddress
+            | ClientCall::Version
+            | ClientCall::Health
+            | ClientCall::GetTopology
+            | ClientCall::GetGateway => None,
+        }
+    }
+
+    fn from_method(method: &str, params: Value) -> Result<Self, (ErrorCode, String)> {
+        fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, (ErrorCode, String)> {
+            serde_json::from_value(params)
+                .map_err(|err| (ErrorCode::InvalidParams, err.to_string()))
+        }
+
+        fn malformed<E: ToString>(err: E) -> (ErrorCode, String) {
+            (ErrorCode::InvalidParams, err.to_string())
+        }
+
+        fn decode_bytes(field: &str, encoded: &str) -> Result<Vec<u8>, (ErrorCode, String)> {
+            base64::decode(encoded)
+                .map_err(|err| malformed(format!("'{}' is not valid base64 - {}", field, err)))
+        }
+
+        match method {
+            "send" => {
+                let params: SendParams = parse_params(params)?;
+                Ok(ClientCall::Send {
+                    recipient: Recipient::try_from_base58_string(params.recipient)
+                        .map_err(malformed)?,
+                    message: decode_bytes("message", &params.message)?,
+                    with_reply_surb: params.with_reply_surb,
+                    track_delivery: params.track_delivery,
+                })
+            }
+            "reply" => {
+                let params: ReplyParams = parse_params(params)?;
+                Ok(ClientCall::Reply {
+                    reply_surb: ReplySurb::from_base58_string(params.reply_surb)
+                        .map_err(malformed)?,
+                    message: decode_bytes("message", &params.message)?,
+                    track_delivery: params.track_delivery,
+                })
+            }
+            "selfAddress" => Ok(ClientCall::SelfAddress),
+            "subscribe" => {
+                let params: SubscribeParams = parse_params(params)?;
+                Ok(ClientCall::Subscribe {
+                    prefix: decode_bytes("prefix", &params.prefix)?,
+                })
+            }
+            "version" => Ok(ClientCall::Version),
+            "health" => Ok(ClientCall::Health),
+            "getTopology" => Ok(ClientCall::GetTopology),
+            "getGateway" => Ok(ClientCall::GetGateway),
+            "createReplySurbs" => {
+                let params: CreateReplySurbsParams = parse_params(params)?;
+                if params.count == 0 || params.count > MAX_REPLY_SURBS_PER_REQUEST {
+                    return Err(malformed(format!(
+                        "between 1 and {} reply SURBs can be created at once",
+                        MAX_REPLY_SURBS_PER_REQUEST
+                    )));
+                }
+                Ok(ClientCall::CreateReplySurbs {
+                    count: params.count,
+                })
+            }
+            "revokeReplySurb" => {
+                let params: RevokeReplySurbParams = parse_params(params)?;
+                Ok(ClientCall::RevokeReplySurb {
+                    reply_surb: ReplySurb::from_base58_string(params.reply_surb)
+                        .map_err(malformed)?,
+                })
+            }
+            other => Err((
+                ErrorCode::MethodNotFound,
+                format!("method '{}' does not exist", other),
+            )),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SendParams {
+    /// base64-encoded content of the message
+    message: String,
+    recipient: String,
+    #[serde(default)]
+    with_reply_surb: bool,
+    #[serde(default)]
+    track_delivery: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReplyParams {
+    /// base64-encoded content of the message
+    message: String,
+    reply_surb: String,
+    #[serde(default)]
+    track_delivery: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SubscribeParams {
+    /// base64-encoded prefix of the messages to push
+    prefix: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CreateReplySurbsParams {
+    count: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RevokeReplySurbParams {
+    reply_surb: String,
+}
+
+// the raw envelope of every request, before the method and its params are interpreted
+#[derive(Serialize, Deserialize)]
+struct RawRequest {
+    jsonrpc: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<RequestId>,
+    method: String,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    params: Value,
+}
+
+#[derive(Debug)]
+pub struct ClientRequest {
+    /// Requests without an id are notifications and never get a response.
+    pub id: Option<RequestId>,
+    pub call: ClientCall,
+}
+
+impl ClientRequest {
+    pub fn new(id: Option<RequestId>, call: ClientCall) -> Self {
+        ClientRequest { id, call }
+    }
+
+    pub fn try_from_text(raw_req: String) -> Result<Self, RequestError> {
+        let value: Value = serde_json::from_str(&raw_req)
+            .map_err(|err| RequestError::new(None, ErrorCode::ParseError, err.to_string()))?;
+
+        // try to recover the id first so that even invalid requests could be responded to
+        let id = value
+            .get("id")
+            .and_then(|id| RequestId::deserialize(id).ok());
+
+        let raw: RawRequest = serde_json::from_value(value).map_err(|err| {
+            RequestError::new(id.clone(), ErrorCode::InvalidRequest, err.to_string())
+        })?;
+
+        if raw.jsonrpc != JSONRPC_VERSION {
+            return Err(RequestError::new(
+                id,
+                ErrorCode::InvalidRequest,
+                format!("unsupported jsonrpc version '{}'", raw.jsonrpc),
+            ));
+        }
+
+        let call = ClientCall::from_method(&raw.method, raw.params)
+            .map_err(|(code, message)| RequestError::new(id.clone(), code, message))?;
+
+        Ok(ClientRequest { id, call })
+    }
+
+    pub fn into_text(self) -> String {
+        let raw = RawRequest {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            params: self.call.params().unwrap_or_default(),
+            method: self.call.method().to_string(),
+            id: self.id,
+        };
+        // this can't fail as all keys are strings
+        serde_json::to_string(&raw).unwrap()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Outcome {
+    Result(Value),
+    Error(ErrorObject),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ServerResponse {
+    jsonrpc: String,
+    /// The id of the request this is a response to. It is only ever `None` if the id
+    /// could not have been recovered from an invalid request.
+    pub id: Option<RequestId>,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+}
+
+impl ServerResponse {
+    pub fn new_result<T: Serialize>(id: Option<RequestId>, result: T) -> Self {
+        ServerResponse {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            // all our results are plain structs with string keys, so this can't fail
+            outcome: Outcome::Result(serde_json::to_value(result).unwrap()),
+        }
+    }
+
+    pub fn new_error(id: Option<RequestId>, error: ErrorObject) -> Self {
+        ServerResponse {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            outcome: Outcome::Error(error),
+        }
+    }
+
+    pub fn try_from_text(raw_resp: String) -> Result<Self, error::Error> {
+        serde_json::from_str(&raw_resp)
+            .map_err(|err| error::Error::new(ErrorKind::MalformedResponse, err.to_string()))
+    }
+
+    pub fn into_text(self) -> String {
+        // this can't fail as all keys are strings
+        serde_json::to_string(&self).unwrap()
+    }
+}
+
+/// Delivery status of a message sent with `trackDelivery` set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DeliveryStatus {
+    Sent,
+    Acknowledged,
+    GaveUp,
+    Expired,
+}
+
+/// Messages pushed by the client without being explicitly requested.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "camelCase")]
+pub enum Notification {
+    #[serde(rename_all = "camelCase")]
+    Received {
+        /// base64-encoded content of the message
+        message: String,
+        reply_surb: Option<String>,
+    },
+    DeliveryStatus {
+        id: RequestId,
+        status: DeliveryStatus,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct NotificationEnvelope {
+    jsonrpc: String,
+    #[serde(flatten)]
+    notification: Notification,
+}
+
+impl Notification {
+    pub fn received(reconstructed: ReconstructedMessage) -> Self {
+        Notification::Received {
+            message: base64::encode(&reconstructed.message),
+            reply_surb: reconstructed
+                .reply_surb
+                .map(|reply_surb| reply_surb.to_base58_string()),
+        }
+    }
+
+    pub fn try_from_text(raw_notification: String) -> Result<Self, error::Error> {
+        serde_json::from_str::<NotificationEnvelope>(&raw_notification)
+            .map(|envelope| envelope.notification)
+            .map_err(|err| error::Error::new(ErrorKind::MalformedResponse, err.to_string()))
+    }
+
+    pub fn into_text(self) -> String {
+        let envelope = NotificationEnvelope {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            notification: self,
+        };
+        // this can't fail as all keys are strings
+        serde_json::to_string(&envelope).unwrap()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SelfAddressResult {
+    pub address: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionResult {
+    pub protocol: u8,
+    pub supported_protocols: Vec<u8>,
+    pub client_version: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthResult {
+    /// Whether the current topology allows constructing routes through the mixnet.
+    pub topology_routable: bool,
+    /// Whether the gateway the client is registered with is present in the current topology.
+    pub gateway_online: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MixnodeDetails {
+    pub mix_id: u32,
+    pub identity_key: String,
+    pub owner: String,
+    pub host: String,
+    pub layer: u8,
+    pub version: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GatewayDetails {
+    pub identity_key: String,
+    pub owner: String,
+    pub host: String,
+    pub clients_port: u16,
+    pub version: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TopologyResult {
+    pub mixnodes: Vec<MixnodeDetails>,
+    pub gateways: Vec<GatewayDetails>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateReplySurbsResult {
+    pub reply_surbs: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RevokeReplySurbResult {
+    /// Whether the key of the reply SURB was known to the client.
+    pub revoked: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECIPIENT: &str = "CytBseW6yFXUMzz4SGAKdNLGR7q3sJLLYxyBGvutNEQV.4QXYyEVc5fUDjmmi8PrHN9tdUFV4PCvSJE1278cHyvoe@4sBbL1ngf1vtNqykydQKTFh26sQCw888GpUqvPvyNB4f";
+
+    fn request_error(raw: &str) -> RequestError {
+        ClientRequest::try_from_text(raw.to_string()).unwrap_err()
+    }
+
+    #[test]
+    fn send_request_is_parsed_with_its_id() {
+        let raw = format!(
+            r#"{{"jsonrpc":"2.0","id":"foo","method":"send","params":{{"message":"aGVsbG8=","recipient":"{}","trackDelivery":true}}}}"#,
+            RECIPIENT
+        );
+        let request = ClientRequest::try_from_text(raw).unwrap();
+        assert_eq!(request.id, Some(RequestId::String("foo".to_string())));
+        match request.call {
+            ClientCall::Send {
+                recipient,
+                message,
+                with_reply_surb,
+                track_delivery,
+            } => {
+                assert_eq!(recipient.to_string(), RECIPIENT);
+                assert_eq!(message, b"hello".to_vec());
+                assert!(!with_reply_surb);
+                assert!(track_delivery);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn request_serialization_works() {
+        let request = ClientRequest::new(
+            Some(RequestId::Number(42)),
+            ClientCall::CreateReplySurbs { count: 5 },
+        );
+        let recovered = ClientRequest::try_from_text(request.into_text()).unwrap();
+        assert_eq!(recovered.id, Some(RequestId::Number(42)));
+        match recovered.call {
+            ClientCall::CreateReplySurbs { count } => assert_eq!(count, 5),
+            _ => unreachable!(),
+        }
+
+        let notification = ClientRequest::new(None, ClientCall::Health);
+        let recovered = ClientRequest::try_from_text(notification.into_text()).unwrap();
+        assert!(recovered.id.is_none());
+        assert!(matches!(recovered.call, ClientCall::Health));
+    }
+
+    #[test]
+    fn invalid_requests_are_rejected_with_appropriate_codes() {
+        let err = request_error("{not json");
+        assert_eq!(err.id, None);
+        assert_eq!(err.error.error_code(), Some(ErrorCode::ParseError));
+
+        let err = request_error(r#"{"jsonrpc":"1.0","id":1,"method":"health"}"#);
+        assert_eq!(err.id, Some(RequestId::Number(1)));
+        assert_eq!(err.error.error_code(), Some(ErrorCode::InvalidRequest));
+
+        let err = request_error(r#"{"jsonrpc":"2.0","id":2}"#);
+        assert_eq!(err.id, Some(RequestId::Number(2)));
+        assert_eq!(err.error.error_code(), Some(ErrorCode::InvalidRequest));
+
+        let err = request_error(r#"{"jsonrpc":"2.0","id":3,"method":"foomp"}"#);
+        assert_eq!(err.error.error_code(), Some(ErrorCode::MethodNotFound));
+
+        let err = request_error(r#"{"jsonrpc":"2.0","id":4,"method":"send","params":{}}"#);
+        assert_eq!(err.error.error_code(), Some(ErrorCode::InvalidParams));
+
+        let err = request_error(
+            r#"{"jsonrpc":"2.0","id":5,"method":"createReplySurbs","params":{"count":0}}"#,
+        );
+        assert_eq!(err.error.error_code(), Some(ErrorCode::InvalidParams));
+
+        let response = err.into_response();
+        assert_eq!(response.id, Some(RequestId::Number(5)));
+    }
+
+    #[test]
+    fn binary_payloads_survive_the_roundtrip() {
+        let payload = vec![0, 159, 146, 150, 255, 254, 0x80, b'a'];
+        let recipient = Recipient::try_from_base58_string(RECIPIENT).unwrap();
+
+        let request = ClientRequest::new(
+            Some(RequestId::Number(1)),
+            ClientCall::Send {
+                recipient,
+                message: payload.clone(),
+                with_reply_surb: false,
+                track_delivery: false,
+            },
+        );
+        match ClientRequest::try_from_text(request.into_text())
+            .unwrap()
+            .call
+        {
+            ClientCall::Send { message, .. } => assert_eq!(message, payload),
+            _ => unreachable!(),
+        }
+
+        let request = ClientRequest::new(
+            Some(RequestId::Number(2)),
+            ClientCall::Subscribe {
+                prefix: payload.clone(),
+            },
+        );
+        match ClientRequest::try_from_text(request.into_text())
+            .unwrap()
+            .call
+        {
+            ClientCall::Subscribe { prefix } => assert_eq!(prefix, payload),
+            _ => unreachable!(),
+        }
+
+        let received = Notification::received(ReconstructedMessage {
+            message: payload.clone(),
+            reply_surb: None,
+        });
+        match Notification::try_from_text(received.into_text()).unwrap() {
+            Notification::Received { message, .. } => {
+                assert_eq!(base64::decode(message).unwrap(), payload)
+            }
+            _ => unreachable!(),
+        }
+
+        let err = request_error(&format!(
+            r#"{{"jsonrpc":"2.0","id":3,"method":"send","params":{{"message":"not base64!","recipient":"{}"}}}}"#,
+            RECIPIENT
+        ));
+        assert_eq!(err.error.error_code(), Some(ErrorCode::InvalidParams));
+    }
+
+    #[test]
+    fn error_kinds_map_to_error_codes() {
+        let err = error::Error::new(ErrorKind::UnknownRequest, "foomp".to_string());
+        assert_eq!(
+            ErrorObject::from(err).error_code(),
+            Some(ErrorCode::MethodNotFound)
+        );
+        assert_eq!(
+            ErrorCode::from(ErrorKind::MalformedRequest),
+            ErrorCode::InvalidParams
+        );
+        assert_eq!(ErrorCode::from(ErrorKind::Other), ErrorCode::InternalError);
+    }
+
+    #[test]
+    fn response_serialization_works() {
+        let result = ServerResponse::new_result(
+            Some(RequestId::Number(1)),
+            SelfAddressResult {
+                address: RECIPIENT.to_string(),
+            },
+        );
+        let text = result.clone().into_text();
+        assert_eq!(
+            text,
+            format!(
+                r#"{{"jsonrpc":"2.0","id":1,"result":{{"address":"{}"}}}}"#,
+                RECIPIENT
+            )
+        );
+        assert_eq!(ServerResponse::try_from_text(text).unwrap(), result);
+
+        let error =
+            ServerResponse::new_error(None, ErrorObject::new(ErrorCode::ParseError, "foomp"));
+        let text = error.clone().into_text();
+        assert_eq!(
+            text,
+            r#"{"jsonrpc":"2.0","id":null,"error":{"code":-32700,"message":"foomp"}}"#
+        );
+        assert_eq!(ServerResponse::try_from_text(text).unwrap(), error);
+    }
+
+    #[test]
+    fn notification_serialization_works() {
+        let status = Notification::DeliveryStatus {
+            id: RequestId::String("foo".to_string()),
+            status: DeliveryStatus::GaveUp,
+        };
+        let text = status.clone().into_text();
+        assert_eq!(
+            text,
+            r#"{"jsonrpc":"2.0","method":"deliveryStatus","params":{"id":"foo","status":"gaveUp"}}"#
+        );
+        assert_eq!(Notification::try_from_text(text).unwrap(), status);
+
+        let received = Notification::received(ReconstructedMessage {
+            message: b"foomp".to_vec(),
+            reply_surb: None,
+        });
+        let text = received.clone().into_text();
+        assert_eq!(
+            text,
+            r#"{"jsonrpc":"2.0","method":"received","params":{"message":"Zm9vbXA=","replySurb":null}}"#
+        );
+        assert_eq!(Notification::try_from_text(text).unwrap(), received);
+    }
+}
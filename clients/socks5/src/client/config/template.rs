@@ -30,6 +30,18 @@ validator_api_urls = [
     {{/each}}
 ]
 
+# Base58-encoded identity keys of the validator APIs whose signed topology responses
+# are trusted. If empty, the topology is used without verifying its origin.
+trusted_validator_api_keys = [
+    {{#each client.trusted_validator_api_keys }}
+        '{{this}}',
+    {{/each}}
+]
+
+# Number of distinct trusted validator APIs that have to agree on the network topology
+# before the client starts using it.
+required_topology_signatures = {{ client.required_topology_signatures }}
+
 # Path to file containing private identity key.
 private_identity_key_file = '{{ client.private_identity_key_file }}'
 
@@ -49,6 +61,18 @@ reply_encryption_key_store_path = '{{ client.reply_encryption_key_store_path }}'
 # Path to the database containing bandwidth credentials
 database_path = '{{ client.database_path }}'
 
+# Path to the directory used for storing fragments of partially received messages
+# (if disk-backed reconstruction is enabled). Note that the fragments are stored in plaintext.
+reconstruction_buffer_directory = '{{ client.reconstruction_buffer_directory }}'
+
+# Path to the directory used for storing sent messages that have not yet been
+# acknowledged (if the outbound journal is enabled).
+outbound_journal_directory = '{{ client.outbound_journal_directory }}'
+
+# Path to the file containing the latest ranking of gateways, measured when the client
+# has automatically chosen its gateway.
+gateway_ranking_file = '{{ client.gateway_ranking_file }}'
+
 ##### additional client config options #####
 
 # A gateway specific, optional, base58 stringified shared key used for
@@ -72,6 +96,8 @@ gateway_id = '{{ client.gateway_endpoint.gateway_id }}'
 gateway_owner = '{{ client.gateway_endpoint.gateway_owner }}'
 
 # Address of the gateway listener to which all client requests should be sent.
+# Use the 'tcp://' scheme (rather than 'ws://') to talk to the gateway over raw TCP
+# instead of WebSocket.
 gateway_listener = '{{ client.gateway_endpoint.gateway_listener }}'
 
 
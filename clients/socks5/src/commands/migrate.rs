@@ -0,0 +1,76 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use clap::Args;
+use config::NymConfig;
+
+use crate::client::config::Config;
+
+#[derive(Args, Clone)]
+pub(crate) struct Migrate {
+    /// Id of the socks5 client we want to move to a different gateway.
+    #[clap(long)]
+    id: String,
+
+    /// Id of the gateway we are going to move to. If omitted, the best of the probed gateways
+    /// is chosen.
+    #[clap(long)]
+    gateway: Option<String>,
+
+    /// Region (matched against the announced gateway locations, e.g. a country name) preferred
+    /// when the gateway is chosen automatically.
+    #[clap(long)]
+    gateway_region: Option<String>,
+}
+
+pub(crate) async fn execute(args: &Migrate) {
+    let mut config = Config::load_from_file(Some(&args.id)).unwrap_or_else(|err| {
+        eprintln!("Failed to load config for {}\nError: {err}", args.id);
+        std::process::exit(1)
+    });
+
+    let new_gateway = if args.gateway.is_some() {
+        client_core::init::query_gateway_details(
+            config.get_base().get_validator_api_endpoints(),
+            args.gateway.as_deref(),
+        )
+        .await
+    } else {
+        println!("Probing gateways to find the best one");
+        client_core::init::select_gateway(config.get_base(), args.gateway_region.as_deref()).await
+    }
+    .unwrap_or_else(|err| {
+        eprintln!("Failed to choose the new gateway\nError: {err}");
+        std::process::exit(1)
+    });
+
+    if new_gateway.identity_key.to_base58_string() == config.get_base().get_gateway_id() {
+        println!(
+            "The client is already using gateway {}",
+            new_gateway.identity_key.to_base58_string()
+        );
+        return;
+    }
+
+    println!(
+        "Migrating to gateway {}",
+        new_gateway.identity_key.to_base58_string()
+    );
+    let redirect = client_core::init::migrate_to_gateway(new_gateway, &mut config)
+        .await
+        .unwrap_or_else(|err| {
+            eprintln!("Failed to migrate to the new gateway\nError: {err}");
+            std::process::exit(1)
+        });
+
+    println!("Client migration completed.");
+    println!(
+        "Peers are notified automatically once they're sent a message. The redirect record is: {}",
+        redirect.to_base58_string()
+    );
+
+    client_core::init::show_address(config.get_base()).unwrap_or_else(|err| {
+        eprintln!("Failed to show address\nError: {err}");
+        std::process::exit(1)
+    });
+}
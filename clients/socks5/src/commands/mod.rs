@@ -9,6 +9,7 @@ use completions::{fig_generate, ArgShell};
 use config::parse_validators;
 
 pub mod init;
+pub(crate) mod migrate;
 pub(crate) mod run;
 pub(crate) mod upgrade;
 
@@ -66,6 +67,8 @@ pub(crate) enum Commands {
     Run(run::Run),
     /// Try to upgrade the client
     Upgrade(upgrade::Upgrade),
+    /// Move the client to a different gateway, keeping its identity
+    Migrate(migrate::Migrate),
 
     /// Generate shell completions
     Completions(ArgShell),
@@ -91,6 +94,7 @@ pub(crate) async fn execute(args: &Cli) -> Result<(), Socks5ClientError> {
         Commands::Init(m) => init::execute(m).await,
         Commands::Run(m) => run::execute(m).await?,
         Commands::Upgrade(m) => upgrade::execute(m),
+        Commands::Migrate(m) => migrate::execute(m).await,
         Commands::Completions(s) => s.generate(&mut Cli::into_app(), bin_name),
         Commands::GenerateFigSpec => fig_generate(&mut Cli::into_app(), bin_name),
     }
@@ -1,13 +1,17 @@
 #![forbid(unsafe_code)]
 
 use super::authentication::{AuthenticationMethods, Authenticator, User};
+use super::http::{self, HttpConnectRequest};
 use super::request::{SocksCommand, SocksRequest};
-use super::types::{ResponseCode, SocksProxyError};
+use super::socks4::{self, Socks4ReplyCode, Socks4Request};
+use super::types::{AddrType, ResponseCode, SocksProxyError};
+use super::udp::{self, MAX_DATAGRAM_SIZE};
 use super::{RESERVED, SOCKS_VERSION};
 use client_core::client::inbound_messages::InputMessage;
 use client_core::client::inbound_messages::InputMessageSender;
 use futures::channel::mpsc;
 use futures::task::{Context, Poll};
+use futures::StreamExt;
 use log::*;
 use nymsphinx::addressing::clients::Recipient;
 use pin_project::pin_project;
@@ -22,6 +26,7 @@ use std::net::SocketAddr;
 use std::pin::Pin;
 use task::ShutdownListener;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::net::UdpSocket;
 use tokio::{self, net::TcpStream};
 
 #[pin_project(project = StateProject)]
@@ -63,6 +68,17 @@ impl StreamState {
         }
     }
 
+    /// Returns the local address that this stream is bound to.
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        match self {
+            StreamState::RunningProxy => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "stream is being used to run the proxy",
+            )),
+            StreamState::Available(ref stream) => stream.local_addr(),
+        }
+    }
+
     async fn shutdown(&mut self) -> io::Result<()> {
         // shutdown should only be called if proxy is not being run. If it is, there's some bug
         // somewhere
@@ -126,6 +142,14 @@ impl AsyncWrite for StreamState {
     }
 }
 
+/// Protocol spoken by the client, determined from the first byte it sends.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ProxyProtocol {
+    Socks4,
+    Socks5,
+    HttpConnect,
+}
+
 /// A client connecting to the Socks proxy server, because
 /// it wants to make a Nym-protected outbound request. Typically, this is
 /// something like e.g. a wallet app running on your laptop connecting to
@@ -136,6 +160,7 @@ pub(crate) struct SocksClient {
     auth_nmethods: u8,
     authenticator: Authenticator,
     socks_version: u8,
+    protocol: ProxyProtocol,
     input_sender: InputMessageSender,
     connection_id: ConnectionId,
     service_provider: Recipient,
@@ -174,6 +199,7 @@ impl SocksClient {
             stream: StreamState::Available(stream),
             auth_nmethods: 0,
             socks_version: 0,
+            protocol: ProxyProtocol::Socks5,
             authenticator,
             input_sender,
             service_provider,
@@ -190,7 +216,15 @@ impl SocksClient {
 
     // Send an error back to the client
     pub async fn error(&mut self, r: ResponseCode) -> Result<(), SocksProxyError> {
-        self.stream.write_all(&[5, r as u8]).await?;
+        match self.protocol {
+            ProxyProtocol::Socks5 => self.stream.write_all(&[5, r as u8]).await?,
+            ProxyProtocol::Socks4 => {
+                self.stream
+                    .write_all(&socks4::reply(Socks4ReplyCode::Rejected))
+                    .await?
+            }
+            ProxyProtocol::HttpConnect => self.stream.write_all(http::BAD_GATEWAY).await?,
+        }
         Ok(())
     }
 
@@ -201,29 +235,119 @@ impl SocksClient {
         Ok(())
     }
 
-    /// Initializes the new client, checking that the correct Socks version (5)
-    /// is in use and that the client is authenticated, then runs the request.
+    /// Initializes the new client, determining which protocol it speaks from the first byte it
+    /// sends. SOCKS5 clients are authenticated before their request is run, while SOCKS4(a) and
+    /// HTTP CONNECT clients are only served if no authentication is required.
     pub async fn run(&mut self) -> Result<(), SocksProxyError> {
         debug!("New connection from: {}", self.stream.peer_addr()?.ip());
-        let mut header = [0u8; 2];
         // Read a byte from the stream and determine the version being requested
-        self.stream.read_exact(&mut header).await?;
+        let first_byte = self.stream.read_u8().await?;
+        self.socks_version = first_byte;
+
+        match first_byte {
+            SOCKS_VERSION => {
+                self.protocol = ProxyProtocol::Socks5;
+                self.auth_nmethods = self.stream.read_u8().await?;
+                // Authenticate w/ client
+                self.authenticate().await?;
+                // Handle requests
+                self.handle_request().await
+            }
+            socks4::SOCKS4_VERSION => {
+                self.protocol = ProxyProtocol::Socks4;
+                self.handle_socks4_request().await
+            }
+            byte if http::is_http_method_start(byte) => {
+                self.protocol = ProxyProtocol::HttpConnect;
+                self.handle_http_request(byte).await
+            }
+            _ => {
+                warn!("Init: Unsupported version: SOCKS{}", self.socks_version);
+                self.shutdown().await
+            }
+        }
+    }
 
-        self.socks_version = header[0];
-        self.auth_nmethods = header[1];
+    /// Checks whether clients that can't authenticate themselves are allowed to use the proxy.
+    fn allows_unauthenticated(&self) -> bool {
+        self.authenticator
+            .auth_methods
+            .contains(&(AuthenticationMethods::NoAuth as u8))
+    }
 
-        // Handle SOCKS4 requests
-        if header[0] != SOCKS_VERSION {
-            warn!("Init: Unsupported version: SOCKS{}", self.socks_version);
-            self.shutdown().await
+    /// Handles a SOCKS4 or SOCKS4a CONNECT request.
+    async fn handle_socks4_request(&mut self) -> Result<(), SocksProxyError> {
+        debug!("Handling SOCKS4 CONNECT Command");
+
+        let request = Socks4Request::from_stream(&mut self.stream).await?;
+        trace!(
+            "SOCKS4 request from user {:?} to: {}",
+            String::from_utf8_lossy(&request.user_id),
+            request.remote_address
+        );
+
+        // SOCKS4 has no notion of passwords, so there's no way to authenticate the user
+        if !self.allows_unauthenticated() {
+            warn!("Rejecting SOCKS4 request as authentication is required");
+            self.stream
+                .write_all(&socks4::reply(Socks4ReplyCode::Rejected))
+                .await?;
+            return self.shutdown().await;
         }
-        // Valid SOCKS5
-        else {
-            // Authenticate w/ client
-            self.authenticate().await?;
-            // Handle requests
-            self.handle_request().await
+
+        self.stream
+            .write_all(&socks4::reply(Socks4ReplyCode::Granted))
+            .await?;
+        self.proxy_connection(request.remote_address).await;
+        Ok(())
+    }
+
+    /// Handles an HTTP CONNECT request, establishing a tunnel to the requested host.
+    async fn handle_http_request(&mut self, first_byte: u8) -> Result<(), SocksProxyError> {
+        debug!("Handling HTTP CONNECT request");
+
+        let request = match HttpConnectRequest::from_stream(&mut self.stream, first_byte).await {
+            Ok(request) => request,
+            Err(err) => {
+                warn!("Invalid HTTP proxy request - {}", err);
+                self.stream.write_all(err.response()).await?;
+                return self.shutdown().await;
+            }
+        };
+
+        if !self.allows_unauthenticated() {
+            warn!("Rejecting HTTP CONNECT request as authentication is required");
+            self.stream
+                .write_all(http::PROXY_AUTHENTICATION_REQUIRED)
+                .await?;
+            return self.shutdown().await;
         }
+
+        self.stream.write_all(http::CONNECTION_ESTABLISHED).await?;
+        self.proxy_connection(request.remote_address).await;
+        Ok(())
+    }
+
+    /// Proxies the already acknowledged connection to the remote address through the mixnet.
+    async fn proxy_connection(&mut self, remote_address: RemoteAddress) {
+        // setup for receiving from the mixnet
+        let (mix_sender, mix_receiver) = mpsc::unbounded();
+
+        self.started_proxy = true;
+        self.controller_sender
+            .unbounded_send(ControllerCommand::Insert(self.connection_id, mix_sender))
+            .unwrap();
+
+        info!(
+            "Starting proxy for {} (id: {})",
+            remote_address.clone(),
+            self.connection_id
+        );
+        self.run_proxy(mix_receiver, remote_address.clone()).await;
+        info!(
+            "Proxy for {} is finished (id: {})",
+            remote_address, self.connection_id
+        );
     }
 
     async fn send_connect_to_mixnet(&mut self, remote_address: RemoteAddress) {
@@ -267,6 +391,81 @@ impl SocksClient {
         self.stream.finish_proxy(stream)
     }
 
+    /// Relays datagrams between the local application and the mixnet until the TCP connection,
+    /// over which the association was requested, gets closed.
+    async fn run_udp_association(
+        &mut self,
+        socket: UdpSocket,
+        mut mix_receiver: ConnectionReceiver,
+    ) {
+        let mut stream = self.stream.run_proxy();
+        let client_ip = stream
+            .peer_addr()
+            .expect("failed to extract peer address")
+            .ip();
+
+        let mut shutdown_listener = self.shutdown_listener.clone();
+        // address from which the local application is sending its datagrams
+        let mut client_address = None;
+        let mut datagram_buf = vec![0u8; MAX_DATAGRAM_SIZE];
+        let mut control_buf = [0u8; 64];
+
+        loop {
+            tokio::select! {
+                read = stream.read(&mut control_buf) => match read {
+                    // the association terminates when the TCP connection it arrived on terminates
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => trace!("ignoring data received on the UDP association control connection"),
+                },
+                received = socket.recv_from(&mut datagram_buf) => match received {
+                    Ok((len, source)) => {
+                        if source.ip() != client_ip {
+                            warn!("Received a datagram from unexpected address {} - it's going to be dropped", source);
+                            continue;
+                        }
+                        client_address = Some(source);
+
+                        let (remote_address, data) = match udp::parse_datagram(&datagram_buf[..len]) {
+                            Ok(parsed) => parsed,
+                            Err(err) => {
+                                warn!("Received an invalid datagram - {}", err);
+                                continue;
+                            }
+                        };
+                        let request = Request::new_datagram(self.connection_id, remote_address, data.to_vec(), self.self_address);
+                        let input_message = InputMessage::new_fresh(
+                            self.service_provider,
+                            Message::Request(request).into_bytes(),
+                            false,
+                        );
+                        self.input_sender.unbounded_send(input_message).unwrap();
+                    }
+                    Err(err) => {
+                        error!("Failed to receive a datagram - {}", err);
+                        break;
+                    }
+                },
+                message = mix_receiver.next() => match message {
+                    Some(message) => match client_address {
+                        Some(client_address) => {
+                            if let Err(err) = socket.send_to(&message.payload, client_address).await {
+                                warn!("Failed to send a datagram to {} - {}", client_address, err)
+                            }
+                        }
+                        None => debug!("Received a datagram before the local application has sent anything - it's going to be dropped"),
+                    },
+                    None => break,
+                },
+                _ = shutdown_listener.recv() => {
+                    log::trace!("UDP association: Received shutdown");
+                    break;
+                }
+            }
+        }
+
+        self.stream.finish_proxy(stream)
+    }
+
     /// Handles a client request.
     async fn handle_request(&mut self) -> Result<(), SocksProxyError> {
         debug!("Handling CONNECT Command");
@@ -274,34 +473,44 @@ impl SocksClient {
         let request = SocksRequest::from_stream(&mut self.stream).await?;
         let remote_address = request.to_string();
 
-        // setup for receiving from the mixnet
-        let (mix_sender, mix_receiver) = mpsc::unbounded();
-
         match request.command {
             // Use the Proxy to connect to the specified addr/port
             SocksCommand::Connect => {
                 trace!("Connecting to: {:?}", remote_address.clone());
                 self.acknowledge_socks5().await;
+                self.proxy_connection(remote_address).await;
+            }
 
+            SocksCommand::UdpAssociate => {
+                // the datagrams are going to be sent to the same interface the client
+                // has connected to
+                let local_ip = self.stream.local_addr()?.ip();
+                let socket = match UdpSocket::bind(SocketAddr::new(local_ip, 0)).await {
+                    Ok(socket) => socket,
+                    Err(err) => {
+                        error!("Failed to bind UDP socket for the association - {}", err);
+                        self.error(ResponseCode::Failure).await?;
+                        return Err(err.into());
+                    }
+                };
+                self.acknowledge_udp_associate(socket.local_addr()?).await?;
+
+                // setup for receiving from the mixnet
+                let (mix_sender, mix_receiver) = mpsc::unbounded();
                 self.started_proxy = true;
                 self.controller_sender
                     .unbounded_send(ControllerCommand::Insert(self.connection_id, mix_sender))
                     .unwrap();
 
-                info!(
-                    "Starting proxy for {} (id: {})",
-                    remote_address.clone(),
-                    self.connection_id
-                );
-                self.run_proxy(mix_receiver, remote_address.clone()).await;
-                info!(
-                    "Proxy for {} is finished (id: {})",
-                    remote_address, self.connection_id
-                );
+                info!("Starting UDP association (id: {})", self.connection_id);
+                self.run_udp_association(socket, mix_receiver).await;
+                info!("UDP association is finished (id: {})", self.connection_id);
             }
 
-            SocksCommand::Bind => unimplemented!(), // not handled
-            SocksCommand::UdpAssociate => unimplemented!(), // not handled
+            SocksCommand::Bind => {
+                warn!("BIND command is not supported");
+                self.error(ResponseCode::CommandNotSupported).await?;
+            }
         };
 
         Ok(())
@@ -327,6 +536,29 @@ impl SocksClient {
             .unwrap();
     }
 
+    /// Replies to the UDP ASSOCIATE request with the address of the socket the local application
+    /// should send its datagrams to.
+    async fn acknowledge_udp_associate(
+        &mut self,
+        bound: SocketAddr,
+    ) -> Result<(), SocksProxyError> {
+        let mut response = vec![SOCKS_VERSION, ResponseCode::Success as u8, RESERVED];
+        match bound {
+            SocketAddr::V4(address) => {
+                response.push(AddrType::V4 as u8);
+                response.extend_from_slice(&address.ip().octets());
+            }
+            SocketAddr::V6(address) => {
+                response.push(AddrType::V6 as u8);
+                response.extend_from_slice(&address.ip().octets());
+            }
+        }
+        response.extend_from_slice(&bound.port().to_be_bytes());
+
+        self.stream.write_all(&response).await?;
+        Ok(())
+    }
+
     /// Authenticate the incoming request. Each request is checked for its
     /// authentication method. A user/password request will extract the
     /// username and password from the stream, then check with the Authenticator
@@ -0,0 +1,162 @@
+#![forbid(unsafe_code)]
+
+//! HTTP `CONNECT` tunnels, as described in RFC 7231 section 4.3.6. Once the tunnel is established,
+//! the connection is proxied exactly like a SOCKS5 CONNECT one would be.
+
+use std::io;
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Maximum size of the request head (request line and all header fields) we're willing to read.
+const MAX_REQUEST_HEAD_SIZE: usize = 8 * 1024;
+
+const HEAD_TERMINATOR: &[u8] = b"\r\n\r\n";
+
+pub(crate) const CONNECTION_ESTABLISHED: &[u8] = b"HTTP/1.1 200 Connection established\r\n\r\n";
+pub(crate) const BAD_REQUEST: &[u8] = b"HTTP/1.1 400 Bad Request\r\n\r\n";
+pub(crate) const METHOD_NOT_ALLOWED: &[u8] =
+    b"HTTP/1.1 405 Method Not Allowed\r\nAllow: CONNECT\r\n\r\n";
+pub(crate) const PROXY_AUTHENTICATION_REQUIRED: &[u8] =
+    b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n";
+pub(crate) const BAD_GATEWAY: &[u8] = b"HTTP/1.1 502 Bad Gateway\r\n\r\n";
+
+#[derive(Debug, Error)]
+pub(crate) enum HttpError {
+    #[error("failed to read the request - {0}")]
+    Io(#[from] io::Error),
+
+    #[error("the request head exceeds {} bytes", MAX_REQUEST_HEAD_SIZE)]
+    RequestTooLarge,
+
+    #[error("the request is not valid HTTP")]
+    MalformedRequest,
+
+    #[error("HTTP method {0} is not supported - only CONNECT is allowed")]
+    UnsupportedMethod(String),
+}
+
+impl HttpError {
+    /// Response that should be sent back to the client if this error occurred.
+    pub(crate) fn response(&self) -> &'static [u8] {
+        match self {
+            HttpError::UnsupportedMethod(_) => METHOD_NOT_ALLOWED,
+            HttpError::Io(_) | HttpError::RequestTooLarge | HttpError::MalformedRequest => {
+                BAD_REQUEST
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct HttpConnectRequest {
+    pub(crate) remote_address: String,
+}
+
+impl HttpConnectRequest {
+    /// Reads the request head from the stream. The first byte of the request has already been
+    /// consumed while determining the protocol, so it has to be passed explicitly.
+    pub(crate) async fn from_stream<R>(stream: &mut R, first_byte: u8) -> Result<Self, HttpError>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut head = vec![first_byte];
+        // read byte by byte so that we would not consume any data sent after the request head
+        while !head.ends_with(HEAD_TERMINATOR) {
+            if head.len() == MAX_REQUEST_HEAD_SIZE {
+                return Err(HttpError::RequestTooLarge);
+            }
+            head.push(stream.read_u8().await?);
+        }
+
+        Self::parse_head(&head)
+    }
+
+    fn parse_head(head: &[u8]) -> Result<Self, HttpError> {
+        let head = std::str::from_utf8(head).map_err(|_| HttpError::MalformedRequest)?;
+        let request_line = head.lines().next().ok_or(HttpError::MalformedRequest)?;
+
+        let mut parts = request_line.split_whitespace();
+        let (method, target, version) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(method), Some(target), Some(version)) => (method, target, version),
+            _ => return Err(HttpError::MalformedRequest),
+        };
+        if parts.next().is_some() || !version.starts_with("HTTP/1.") {
+            return Err(HttpError::MalformedRequest);
+        }
+        if method != "CONNECT" {
+            return Err(HttpError::UnsupportedMethod(method.to_string()));
+        }
+
+        // the target has to be in the authority form, i.e. host:port
+        match target.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+                Ok(HttpConnectRequest {
+                    remote_address: target.to_string(),
+                })
+            }
+            _ => Err(HttpError::MalformedRequest),
+        }
+    }
+}
+
+/// Checks whether the first byte of the request could be the beginning of an HTTP method.
+pub(crate) fn is_http_method_start(byte: u8) -> bool {
+    byte.is_ascii_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_connect_request() {
+        let head = b"CONNECT nymtech.net:443 HTTP/1.1\r\nHost: nymtech.net:443\r\n\r\n";
+        assert_eq!(
+            HttpConnectRequest::parse_head(head).unwrap(),
+            HttpConnectRequest {
+                remote_address: "nymtech.net:443".to_string()
+            }
+        );
+
+        let head = b"CONNECT [::1]:8080 HTTP/1.0\r\n\r\n";
+        assert_eq!(
+            HttpConnectRequest::parse_head(head).unwrap().remote_address,
+            "[::1]:8080"
+        );
+    }
+
+    #[test]
+    fn parsing_fails_for_other_methods() {
+        let head = b"GET http://nymtech.net/ HTTP/1.1\r\n\r\n";
+        assert!(matches!(
+            HttpConnectRequest::parse_head(head),
+            Err(HttpError::UnsupportedMethod(method)) if method == "GET"
+        ));
+    }
+
+    #[test]
+    fn parsing_fails_for_malformed_requests() {
+        for head in [
+            &b"CONNECT nymtech.net HTTP/1.1\r\n\r\n"[..],
+            b"CONNECT nymtech.net:443\r\n\r\n",
+            b"CONNECT :443 HTTP/1.1\r\n\r\n",
+            b"CONNECT nymtech.net:443 SPDY/3\r\n\r\n",
+        ] {
+            assert!(matches!(
+                HttpConnectRequest::parse_head(head),
+                Err(HttpError::MalformedRequest)
+            ));
+        }
+    }
+
+    #[test]
+    fn reading_request_head_leaves_remaining_data_in_stream() {
+        let bytes = b"ONNECT nymtech.net:443 HTTP/1.1\r\n\r\nhello";
+        let mut stream = &bytes[..];
+        let request =
+            futures::executor::block_on(HttpConnectRequest::from_stream(&mut stream, b'C'))
+                .unwrap();
+        assert_eq!(request.remote_address, "nymtech.net:443");
+        assert_eq!(stream, b"hello");
+    }
+}
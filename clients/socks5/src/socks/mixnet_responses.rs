@@ -66,6 +66,15 @@ impl MixnetResponseListener {
                 );
                 return;
             }
+            Ok(Message::DatagramResponse(r)) => {
+                self.controller_sender
+                    .unbounded_send(ControllerCommand::SendDatagram(
+                        r.connection_id,
+                        super::udp::encode_datagram(&r.remote_addr, &r.data),
+                    ))
+                    .unwrap();
+                return;
+            }
         };
 
         self.controller_sender
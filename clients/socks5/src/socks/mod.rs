@@ -2,10 +2,13 @@
 
 pub mod authentication;
 mod client;
+mod http;
 pub(crate) mod mixnet_responses;
 mod request;
 pub mod server;
+mod socks4;
 pub mod types;
+mod udp;
 pub mod utils;
 
 /// Version of socks
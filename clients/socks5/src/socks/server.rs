@@ -6,6 +6,7 @@ use super::{
 };
 use client_core::client::{
     inbound_messages::InputMessageSender, received_buffer::ReceivedBufferRequestSender,
+    self_address::SelfAddress,
 };
 use log::*;
 use nymsphinx::addressing::clients::Recipient;
@@ -14,12 +15,13 @@ use std::net::SocketAddr;
 use task::ShutdownListener;
 use tokio::net::TcpListener;
 
-/// A Socks5 server that listens for connections.
+/// A Socks5 server that listens for connections. The same listener also accepts SOCKS4(a)
+/// and HTTP CONNECT requests.
 pub struct SphinxSocksServer {
     authenticator: Authenticator,
     listening_address: SocketAddr,
     service_provider: Recipient,
-    self_address: Recipient,
+    self_address: SelfAddress,
     shutdown: ShutdownListener,
 }
 
@@ -29,7 +31,7 @@ impl SphinxSocksServer {
         port: u16,
         authenticator: Authenticator,
         service_provider: Recipient,
-        self_address: Recipient,
+        self_address: SelfAddress,
         shutdown: ShutdownListener,
     ) -> Self {
         // hardcode ip as we (presumably) ONLY want to listen locally. If we change it, we can
@@ -82,7 +84,7 @@ impl SphinxSocksServer {
                         input_sender.clone(),
                         self.service_provider,
                         controller_sender.clone(),
-                        self.self_address,
+                        self.self_address.get(),
                         self.shutdown.clone(),
                     );
 
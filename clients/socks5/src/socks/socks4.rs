@@ -0,0 +1,152 @@
+#![forbid(unsafe_code)]
+
+//! SOCKS4 and SOCKS4a requests. Only the CONNECT command is supported. The request (following the
+//! version byte, which is read while determining the protocol) looks like this:
+//!
+//! +----+----+----+----+----+----+----+----+----+----+....+----+
+//! | VN | CD | DSTPORT |      DSTIP        | USERID       |NULL|
+//! +----+----+----+----+----+----+----+----+----+----+....+----+
+//!    1    1      2              4           variable       1
+//!
+//! With SOCKS4a, DSTIP is set to 0.0.0.x (with x != 0) and the domain name of the destination
+//! follows the USERID as another null-terminated string.
+
+use super::types::SocksProxyError;
+use std::net::Ipv4Addr;
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Version of socks4
+pub(crate) const SOCKS4_VERSION: u8 = 0x04;
+
+const CONNECT_COMMAND: u8 = 0x01;
+
+/// Maximum length of either the user id or the domain name we're willing to read.
+const MAX_FIELD_LENGTH: usize = 255;
+
+/// SOCKS4 reply codes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Socks4ReplyCode {
+    Granted = 0x5A,
+    Rejected = 0x5B,
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum Socks4Error {
+    #[error("SOCKS4 command {0} is not supported")]
+    UnsupportedCommand(u8),
+
+    #[error("SOCKS4 request field exceeds {} bytes", MAX_FIELD_LENGTH)]
+    FieldTooLong,
+
+    #[error("SOCKS4a domain name is not valid utf8")]
+    MalformedDomain,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Socks4Request {
+    pub(crate) user_id: Vec<u8>,
+    pub(crate) remote_address: String,
+}
+
+impl Socks4Request {
+    /// Reads the rest of the SOCKS4(a) request, after the version byte has already been consumed.
+    pub(crate) async fn from_stream<R>(stream: &mut R) -> Result<Self, SocksProxyError>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut packet = [0u8; 7];
+        stream.read_exact(&mut packet).await?;
+
+        let command = packet[0];
+        if command != CONNECT_COMMAND {
+            return Err(Socks4Error::UnsupportedCommand(command).into());
+        }
+        let port = u16::from_be_bytes([packet[1], packet[2]]);
+        let ip = Ipv4Addr::new(packet[3], packet[4], packet[5], packet[6]);
+
+        let user_id = read_null_terminated(stream).await?;
+
+        let remote_address = if is_socks4a(&ip) {
+            let domain = read_null_terminated(stream).await?;
+            let domain = String::from_utf8(domain).map_err(|_| Socks4Error::MalformedDomain)?;
+            format!("{}:{}", domain, port)
+        } else {
+            format!("{}:{}", ip, port)
+        };
+
+        Ok(Socks4Request {
+            user_id,
+            remote_address,
+        })
+    }
+}
+
+/// SOCKS4a requests use 0.0.0.x, with non-zero x, to signal the domain name follows the user id.
+fn is_socks4a(ip: &Ipv4Addr) -> bool {
+    let octets = ip.octets();
+    octets[..3] == [0, 0, 0] && octets[3] != 0
+}
+
+async fn read_null_terminated<R>(stream: &mut R) -> Result<Vec<u8>, SocksProxyError>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut field = Vec::new();
+    loop {
+        let byte = stream.read_u8().await?;
+        if byte == 0 {
+            return Ok(field);
+        }
+        if field.len() == MAX_FIELD_LENGTH {
+            return Err(Socks4Error::FieldTooLong.into());
+        }
+        field.push(byte);
+    }
+}
+
+/// Creates the reply to the SOCKS4 request. DSTPORT and DSTIP are ignored by the clients for CONNECT.
+pub(crate) fn reply(code: Socks4ReplyCode) -> [u8; 8] {
+    [0, code as u8, 0, 0, 0, 0, 0, 0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(bytes: &[u8]) -> Result<Socks4Request, SocksProxyError> {
+        let mut stream = bytes;
+        futures::executor::block_on(Socks4Request::from_stream(&mut stream))
+    }
+
+    #[test]
+    fn parsing_socks4_request() {
+        let request = parse(&[1, 0, 80, 1, 2, 3, 4, b'f', b'o', b'o', 0]).unwrap();
+        assert_eq!(request.remote_address, "1.2.3.4:80");
+        assert_eq!(request.user_id, b"foo");
+    }
+
+    #[test]
+    fn parsing_socks4a_request() {
+        let mut bytes = vec![1, 1, 187, 0, 0, 0, 1, 0];
+        bytes.extend_from_slice(b"nymtech.net\0");
+        let request = parse(&bytes).unwrap();
+        assert_eq!(request.remote_address, "nymtech.net:443");
+        assert!(request.user_id.is_empty());
+    }
+
+    #[test]
+    fn parsing_fails_for_bind_request() {
+        assert!(parse(&[2, 0, 80, 1, 2, 3, 4, 0]).is_err());
+    }
+
+    #[test]
+    fn parsing_fails_for_unterminated_user_id() {
+        assert!(parse(&[1, 0, 80, 1, 2, 3, 4, b'f', b'o', b'o']).is_err());
+
+        let mut bytes = vec![1, 0, 80, 1, 2, 3, 4];
+        bytes.extend_from_slice(&[b'a'; 300]);
+        bytes.push(0);
+        assert!(parse(&bytes).is_err());
+    }
+}
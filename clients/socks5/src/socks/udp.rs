@@ -0,0 +1,160 @@
+#![forbid(unsafe_code)]
+
+//! Encapsulation of UDP datagrams relayed through the proxy, as described in RFC 1928 section 7:
+//!
+//! +----+------+------+----------+----------+----------+
+//! |RSV | FRAG | ATYP | DST.ADDR | DST.PORT |   DATA   |
+//! +----+------+------+----------+----------+----------+
+//! | 2  |  1   |  1   | Variable |    2     | Variable |
+//! +----+------+------+----------+----------+----------+
+
+use super::types::AddrType;
+use super::utils as socks_utils;
+use socks5_requests::RemoteAddress;
+use std::net::SocketAddr;
+use thiserror::Error;
+
+/// Maximum size of a UDP datagram we're willing to receive.
+pub(crate) const MAX_DATAGRAM_SIZE: usize = 65535;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub(crate) enum DatagramError {
+    #[error("the datagram is too short to contain a valid header")]
+    TooShort,
+
+    #[error("fragmented datagrams are not supported")]
+    Fragmented,
+
+    #[error("{0} is not a valid address type")]
+    UnknownAddressType(u8),
+}
+
+/// Recovers the destination address and the data from a datagram sent by the local application.
+pub(crate) fn parse_datagram(packet: &[u8]) -> Result<(RemoteAddress, &[u8]), DatagramError> {
+    if packet.len() < 4 {
+        return Err(DatagramError::TooShort);
+    }
+    if packet[2] != 0 {
+        return Err(DatagramError::Fragmented);
+    }
+
+    let addr_type =
+        AddrType::from(packet[3] as usize).ok_or(DatagramError::UnknownAddressType(packet[3]))?;
+    let (addr, remaining) = match addr_type {
+        AddrType::V4 => split(&packet[4..], 4)?,
+        AddrType::V6 => split(&packet[4..], 16)?,
+        AddrType::Domain => {
+            let domain_length = *packet.get(4).ok_or(DatagramError::TooShort)? as usize;
+            split(&packet[5..], domain_length)?
+        }
+    };
+    let (port, data) = split(remaining, 2)?;
+    let port = u16::from_be_bytes([port[0], port[1]]);
+
+    let address = match addr_type {
+        // make sure to put ipv6 address in brackets so that the port could be told apart
+        AddrType::V6 => format!(
+            "[{}]:{}",
+            socks_utils::pretty_print_addr(&addr_type, addr),
+            port
+        ),
+        _ => format!(
+            "{}:{}",
+            socks_utils::pretty_print_addr(&addr_type, addr),
+            port
+        ),
+    };
+
+    Ok((address, data))
+}
+
+fn split(bytes: &[u8], at: usize) -> Result<(&[u8], &[u8]), DatagramError> {
+    if bytes.len() < at {
+        return Err(DatagramError::TooShort);
+    }
+    Ok(bytes.split_at(at))
+}
+
+/// Wraps data received from the `source` host so that it could be sent to the local application.
+pub(crate) fn encode_datagram(source: &str, data: &[u8]) -> Vec<u8> {
+    let mut packet = vec![0, 0, 0];
+    match source.parse::<SocketAddr>() {
+        Ok(SocketAddr::V4(address)) => {
+            packet.push(AddrType::V4 as u8);
+            packet.extend_from_slice(&address.ip().octets());
+            packet.extend_from_slice(&address.port().to_be_bytes());
+        }
+        Ok(SocketAddr::V6(address)) => {
+            packet.push(AddrType::V6 as u8);
+            packet.extend_from_slice(&address.ip().octets());
+            packet.extend_from_slice(&address.port().to_be_bytes());
+        }
+        Err(_) => {
+            // it must have been a domain then
+            let (domain, port) = source
+                .rsplit_once(':')
+                .and_then(|(domain, port)| port.parse::<u16>().ok().map(|port| (domain, port)))
+                .unwrap_or((source, 0));
+            let domain = &domain.as_bytes()[..domain.len().min(255)];
+            packet.push(AddrType::Domain as u8);
+            packet.push(domain.len() as u8);
+            packet.extend_from_slice(domain);
+            packet.extend_from_slice(&port.to_be_bytes());
+        }
+    }
+    packet.extend_from_slice(data);
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_ipv4_datagram() {
+        let packet = [0, 0, 0, 1, 1, 1, 1, 1, 0, 53, 42, 42];
+        let (address, data) = parse_datagram(&packet).unwrap();
+        assert_eq!(address, "1.1.1.1:53");
+        assert_eq!(data, &[42, 42]);
+    }
+
+    #[test]
+    fn parsing_domain_datagram() {
+        let mut packet = vec![0, 0, 0, 3, 7];
+        packet.extend_from_slice(b"foo.com");
+        packet.extend_from_slice(&[1, 187, 42]);
+        let (address, data) = parse_datagram(&packet).unwrap();
+        assert_eq!(address, "foo.com:443");
+        assert_eq!(data, &[42]);
+    }
+
+    #[test]
+    fn parsing_fails_for_malformed_datagrams() {
+        assert_eq!(parse_datagram(&[0, 0, 0]), Err(DatagramError::TooShort));
+        assert_eq!(
+            parse_datagram(&[0, 0, 1, 1, 1, 1, 1, 1, 0, 53]),
+            Err(DatagramError::Fragmented)
+        );
+        assert_eq!(
+            parse_datagram(&[0, 0, 0, 2, 1, 1, 1, 1, 0, 53]),
+            Err(DatagramError::UnknownAddressType(2))
+        );
+        assert_eq!(
+            parse_datagram(&[0, 0, 0, 1, 1, 1, 1, 1, 0]),
+            Err(DatagramError::TooShort)
+        );
+    }
+
+    #[test]
+    fn encoded_datagram_can_be_parsed() {
+        for source in ["1.1.1.1:53", "[2606:4700:4700::1111]:53", "foo.com:443"] {
+            let packet = encode_datagram(source, &[1, 2, 3]);
+            let (address, data) = parse_datagram(&packet).unwrap();
+            match source.parse::<SocketAddr>() {
+                Ok(expected) => assert_eq!(address.parse::<SocketAddr>().unwrap(), expected),
+                Err(_) => assert_eq!(address, source),
+            }
+            assert_eq!(data, &[1, 2, 3]);
+        }
+    }
+}
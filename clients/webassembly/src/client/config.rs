@@ -5,6 +5,7 @@
 #![allow(clippy::drop_non_drop)]
 
 use client_core::config::{Debug as ConfigDebug, GatewayEndpoint};
+use nymsphinx::params::MAX_NUM_MIX_HOPS;
 use std::time::Duration;
 use url::Url;
 use wasm_bindgen::prelude::*;
@@ -103,6 +104,14 @@ pub struct Debug {
 
     /// Controls whether the sent sphinx packet use the NON-DEFAULT bigger size.
     pub use_extended_packet_size: bool,
+
+    /// Number of mix hops (excluding the gateway) each sent packet is going to go through.
+    /// It's clamped between 1 and [MAX_NUM_MIX_HOPS], the same way as for the other clients.
+    pub num_mix_hops: u8,
+
+    /// Ratio of parity to data fragments attached to each sent message.
+    /// Setting it to 0 disables the erasure coding.
+    pub erasure_coding_redundancy: f64,
 }
 
 impl From<Debug> for ConfigDebug {
@@ -127,6 +136,10 @@ impl From<Debug> for ConfigDebug {
             disable_main_poisson_packet_distribution: debug
                 .disable_main_poisson_packet_distribution,
             use_extended_packet_size: debug.use_extended_packet_size,
+            num_mix_hops: debug.num_mix_hops.clamp(1, MAX_NUM_MIX_HOPS),
+            erasure_coding_redundancy: debug.erasure_coding_redundancy,
+            // fragment reconstruction in the browser is always kept in memory
+            ..ConfigDebug::default()
         }
     }
 }
@@ -149,6 +162,8 @@ impl From<ConfigDebug> for Debug {
             disable_main_poisson_packet_distribution: debug
                 .disable_main_poisson_packet_distribution,
             use_extended_packet_size: debug.use_extended_packet_size,
+            num_mix_hops: debug.num_mix_hops,
+            erasure_coding_redundancy: debug.erasure_coding_redundancy,
         }
     }
 }
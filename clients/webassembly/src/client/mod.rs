@@ -12,6 +12,7 @@ use client_core::client::{
         ReceivedBufferMessage, ReceivedBufferRequestReceiver, ReceivedBufferRequestSender,
         ReceivedMessagesBufferController,
     },
+    redirects::KnownRedirects,
     topology_control::{TopologyAccessor, TopologyRefresher, TopologyRefresherConfig},
 };
 use crypto::asymmetric::identity;
@@ -23,6 +24,7 @@ use gateway_client::{
 };
 use nymsphinx::addressing::clients::Recipient;
 use nymsphinx::params::PacketSize;
+use nymsphinx::receiver::MessageReceiver;
 use rand::rngs::OsRng;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen_futures::spawn_local;
@@ -43,6 +45,9 @@ pub struct NymClient {
     /// through the mix network.
     input_tx: Option<InputMessageSender>,
 
+    /// Redirect records announced by the peers that have migrated to different gateways.
+    known_redirects: KnownRedirects,
+
     // callbacks
     on_message: Option<js_sys::Function>,
     on_gateway_connect: Option<js_sys::Function>,
@@ -58,6 +63,7 @@ impl NymClient {
             on_message: None,
             on_gateway_connect: None,
             input_tx: None,
+            known_redirects: KnownRedirects::new(),
         }
     }
 
@@ -106,7 +112,7 @@ impl NymClient {
             self.config.debug.average_packet_delay,
             self.config.debug.loop_cover_traffic_average_delay,
             mix_tx,
-            self.as_mix_recipient(),
+            self.as_mix_recipient().into(),
             topology_accessor,
         );
 
@@ -114,6 +120,8 @@ impl NymClient {
             stream.set_custom_packet_size(PacketSize::ExtendedPacket)
         }
 
+        stream.set_num_mix_hops(self.config.debug.num_mix_hops);
+
         stream.start();
     }
 
@@ -132,13 +140,20 @@ impl NymClient {
             self.config.debug.message_sending_average_delay,
             self.config.debug.average_packet_delay,
             self.config.debug.disable_main_poisson_packet_distribution,
-            self.as_mix_recipient(),
+            self.as_mix_recipient().into(),
         );
 
         if self.config.debug.use_extended_packet_size {
             controller_config.set_custom_packet_size(PacketSize::ExtendedPacket)
         }
 
+        controller_config.set_num_mix_hops(self.config.debug.num_mix_hops);
+        if self.config.debug.erasure_coding_redundancy > 0.0 {
+            controller_config
+                .set_erasure_coding_redundancy(self.config.debug.erasure_coding_redundancy);
+        }
+        controller_config.set_known_redirects(self.known_redirects.clone());
+
         console_log!("Starting real traffic stream...");
 
         RealMessagesController::new(
@@ -163,6 +178,8 @@ impl NymClient {
             self.key_manager.encryption_keypair(),
             query_receiver,
             mixnet_receiver,
+            MessageReceiver::new().with_mix_hops(self.config.debug.num_mix_hops),
+            self.known_redirects.clone(),
         )
         .start()
     }
@@ -227,7 +244,8 @@ impl NymClient {
             vec![self.config.validator_api_url.clone()],
             self.config.debug.topology_refresh_rate,
             env!("CARGO_PKG_VERSION").to_string(),
-        );
+        )
+        .with_mix_hops(self.config.debug.num_mix_hops);
         let mut topology_refresher =
             TopologyRefresher::new(topology_refresher_config, topology_accessor);
         // before returning, block entire runtime to refresh the current network view so that any
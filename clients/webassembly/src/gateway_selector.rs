@@ -4,41 +4,18 @@
 use client_core::config::GatewayEndpoint;
 use wasm_bindgen::prelude::*;
 
+/// Chooses the gateway the same way `query_gateway_details` does for the other clients:
+/// the preferred one if specified, otherwise a random gateway compatible with this client.
+/// Note that the latency and reliability probing is not available in the browser.
 #[wasm_bindgen]
 pub async fn get_gateway(api_server: String, preferred: Option<String>) -> GatewayEndpoint {
-    let validator_client = validator_client::ApiClient::new(api_server.parse().unwrap());
+    let validator_api = api_server
+        .parse()
+        .expect("the provided validator API address is malformed");
 
-    let gateways = match validator_client.get_cached_gateways().await {
-        Err(err) => panic!("failed to obtain list of all gateways - {}", err),
-        Ok(gateways) => gateways,
-    };
-
-    if let Some(preferred) = preferred {
-        if let Some(details) = gateways
-            .iter()
-            .find(|g| g.gateway.identity_key == preferred)
-        {
-            return GatewayEndpoint {
-                gateway_id: details.gateway.identity_key.clone(),
-                gateway_owner: details.owner.to_string(),
-                gateway_listener: format!(
-                    "ws://{}:{}",
-                    details.gateway.host, details.gateway.clients_port
-                ),
-            };
-        }
-    }
-
-    let details = gateways
-        .first()
-        .expect("current topology holds no gateways");
-
-    GatewayEndpoint {
-        gateway_id: details.gateway.identity_key.clone(),
-        gateway_owner: details.owner.to_string(),
-        gateway_listener: format!(
-            "ws://{}:{}",
-            details.gateway.host, details.gateway.clients_port
-        ),
+    match client_core::init::query_gateway_details(vec![validator_api], preferred.as_deref()).await
+    {
+        Ok(gateway) => gateway.into(),
+        Err(err) => panic!("failed to choose the gateway - {}", err),
     }
 }
@@ -18,8 +18,12 @@ use credential_storage::PersistentStorage;
 use crypto::asymmetric::identity;
 use futures::{FutureExt, SinkExt, StreamExt};
 use gateway_requests::authentication::encrypted_address::EncryptedAddressBytes;
+use gateway_requests::device::DeviceId;
 use gateway_requests::iv::IV;
-use gateway_requests::registration::handshake::{client_handshake, SharedKeys};
+use gateway_requests::redirect::GatewayRedirect;
+#[cfg(target_arch = "wasm32")]
+use gateway_requests::registration::handshake::WsHandshakeTransport;
+use gateway_requests::registration::handshake::{client_handshake_over, SharedKeys};
 use gateway_requests::{BinaryRequest, ClientControlRequest, ServerResponse};
 use log::*;
 use network_defaults::{REMAINING_BANDWIDTH_THRESHOLD, TOKENS_TO_BURN};
@@ -33,7 +37,7 @@ use task::ShutdownListener;
 use tungstenite::protocol::Message;
 
 #[cfg(not(target_arch = "wasm32"))]
-use tokio_tungstenite::connect_async;
+use crate::connection::GatewayConnection;
 
 #[cfg(target_arch = "wasm32")]
 use wasm_timer;
@@ -51,6 +55,9 @@ pub struct GatewayClient {
     gateway_identity: identity::PublicKey,
     gateway_owner: String,
     local_identity: Arc<identity::KeyPair>,
+    /// Device of the client, for clients sharing their identity across several devices.
+    /// If not set, the gateway treats the client as using the default device.
+    device_id: Option<DeviceId>,
     shared_key: Option<Arc<SharedKeys>>,
     connection: SocketState,
     packet_router: PacketRouter,
@@ -94,6 +101,7 @@ impl GatewayClient {
             gateway_identity,
             gateway_owner,
             local_identity,
+            device_id: None,
             shared_key,
             connection: SocketState::NotConnected,
             packet_router: PacketRouter::new(
@@ -129,6 +137,13 @@ impl GatewayClient {
         self.reconnection_backoff = backoff
     }
 
+    /// Makes the client register or authenticate as the particular device of its identity.
+    /// Each device derives its own shared keys with the gateway, so an additional device can
+    /// be registered by using the same identity keys with a new device id and no shared keys.
+    pub fn with_device_id(&mut self, device_id: DeviceId) {
+        self.device_id = Some(device_id)
+    }
+
     pub fn new_init(
         gateway_address: String,
         gateway_identity: identity::PublicKey,
@@ -158,6 +173,7 @@ impl GatewayClient {
             gateway_identity,
             gateway_owner,
             local_identity,
+            device_id: None,
             shared_key: None,
             connection: SocketState::NotConnected,
             packet_router,
@@ -182,7 +198,7 @@ impl GatewayClient {
     #[cfg(not(target_arch = "wasm32"))]
     async fn _close_connection(&mut self) -> Result<(), GatewayClientError> {
         match std::mem::replace(&mut self.connection, SocketState::NotConnected) {
-            SocketState::Available(mut socket) => Ok((*socket).close(None).await?),
+            SocketState::Available(mut socket) => Ok((*socket).close().await?),
             SocketState::PartiallyDelegated(_) => {
                 unreachable!("this branch should have never been reached!")
             }
@@ -214,12 +230,9 @@ impl GatewayClient {
 
     #[cfg(not(target_arch = "wasm32"))]
     pub async fn establish_connection(&mut self) -> Result<(), GatewayClientError> {
-        let ws_stream = match connect_async(&self.gateway_address).await {
-            Ok((ws_stream, _)) => ws_stream,
-            Err(e) => return Err(GatewayClientError::NetworkError(e)),
-        };
+        let connection = GatewayConnection::connect(&self.gateway_address).await?;
 
-        self.connection = SocketState::Available(Box::new(ws_stream));
+        self.connection = SocketState::Available(Box::new(connection));
         Ok(())
     }
 
@@ -448,14 +461,21 @@ impl GatewayClient {
         let mut rng = OsRng;
 
         let shared_key = match &mut self.connection {
-            SocketState::Available(ws_stream) => client_handshake(
-                &mut rng,
-                ws_stream,
-                self.local_identity.as_ref(),
-                self.gateway_identity,
-            )
-            .await
-            .map_err(GatewayClientError::RegistrationFailure),
+            SocketState::Available(conn) => {
+                #[cfg(not(target_arch = "wasm32"))]
+                let transport = conn.as_mut();
+                #[cfg(target_arch = "wasm32")]
+                let transport = &mut WsHandshakeTransport::new(conn.as_mut());
+
+                client_handshake_over(
+                    &mut rng,
+                    transport,
+                    self.local_identity.as_ref(),
+                    self.gateway_identity,
+                )
+                .await
+                .map_err(GatewayClientError::RegistrationFailure)
+            }
             _ => unreachable!(),
         }?;
         self.authenticated = match self.read_control_response().await? {
@@ -513,10 +533,29 @@ impl GatewayClient {
         }
     }
 
+    async fn select_device(&mut self, device_id: DeviceId) -> Result<(), GatewayClientError> {
+        if !self.connection.is_established() {
+            return Err(GatewayClientError::ConnectionNotEstablished);
+        }
+
+        let msg = ClientControlRequest::new_select_device(device_id).into();
+        match self.send_websocket_message(msg).await? {
+            ServerResponse::SelectDevice { status: true } => Ok(()),
+            ServerResponse::SelectDevice { status: false } => {
+                Err(GatewayClientError::AuthenticationFailure)
+            }
+            ServerResponse::Error { message } => Err(GatewayClientError::GatewayError(message)),
+            _ => Err(GatewayClientError::UnexpectedResponse),
+        }
+    }
+
     /// Helper method to either call register or authenticate based on self.shared_key value
     pub async fn perform_initial_authentication(
         &mut self,
     ) -> Result<Arc<SharedKeys>, GatewayClientError> {
+        if let Some(device_id) = self.device_id {
+            self.select_device(device_id).await?;
+        }
         if self.shared_key.is_some() {
             self.authenticate(None).await?;
         } else {
@@ -530,6 +569,25 @@ impl GatewayClient {
         }
     }
 
+    /// Informs the gateway that the client has moved to another one, so that the packets it
+    /// receives for the client would get forwarded there. Returns whether the gateway has accepted
+    /// the record, i.e. whether it was more recent than the one it already had.
+    pub async fn register_redirect(
+        &mut self,
+        redirect: &GatewayRedirect,
+    ) -> Result<bool, GatewayClientError> {
+        if !self.authenticated {
+            return Err(GatewayClientError::NotAuthenticated);
+        }
+
+        let msg = ClientControlRequest::new_register_redirect(redirect).into();
+        match self.send_websocket_message(msg).await? {
+            ServerResponse::RegisterRedirect { status } => Ok(status),
+            ServerResponse::Error { message } => Err(GatewayClientError::GatewayError(message)),
+            _ => Err(GatewayClientError::UnexpectedResponse),
+        }
+    }
+
     #[cfg(feature = "coconut")]
     async fn claim_coconut_bandwidth(
         &mut self,
@@ -0,0 +1,130 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::error::GatewayClientError;
+use futures::future::BoxFuture;
+use futures::task::{Context, Poll};
+use futures::{Sink, SinkExt, Stream};
+use gateway_requests::framing::{FramedConnection, RAW_TCP_SCHEME};
+use gateway_requests::registration::handshake::error::HandshakeError;
+use gateway_requests::registration::handshake::{HandshakeTransport, WsHandshakeTransport};
+use std::pin::Pin;
+use tokio::net::TcpStream;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+use tungstenite::{Error as WsError, Message};
+
+/// Connection to the gateway, either upgraded to WebSocket or using the raw framing.
+/// Which one is used depends on the scheme of the gateway address, i.e. `ws://` (or `wss://`)
+/// and `tcp://` respectively.
+pub(crate) enum GatewayConnection {
+    WebSocket(WebSocketStream<MaybeTlsStream<TcpStream>>),
+    RawTcp(FramedConnection<TcpStream>),
+}
+
+impl GatewayConnection {
+    pub(crate) async fn connect(gateway_address: &str) -> Result<Self, GatewayClientError> {
+        if let Some(address) = gateway_address.strip_prefix(RAW_TCP_SCHEME) {
+            let conn = TcpStream::connect(address)
+                .await
+                .map_err(|err| GatewayClientError::NetworkError(WsError::Io(err)))?;
+            Ok(GatewayConnection::RawTcp(FramedConnection::new(conn)))
+        } else {
+            let (ws_stream, _) = connect_async(gateway_address).await?;
+            Ok(GatewayConnection::WebSocket(ws_stream))
+        }
+    }
+
+    pub(crate) async fn close(&mut self) -> Result<(), WsError> {
+        match self {
+            GatewayConnection::WebSocket(ws_stream) => ws_stream.close(None).await,
+            GatewayConnection::RawTcp(conn) => {
+                conn.send(Message::Close(None)).await?;
+                conn.close().await
+            }
+        }
+    }
+}
+
+impl Stream for GatewayConnection {
+    type Item = Result<Message, WsError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.get_mut() {
+            GatewayConnection::WebSocket(ws_stream) => Pin::new(ws_stream).poll_next(cx),
+            GatewayConnection::RawTcp(conn) => Pin::new(conn).poll_next(cx),
+        }
+    }
+}
+
+impl Sink<Message> for GatewayConnection {
+    type Error = WsError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.get_mut() {
+            GatewayConnection::WebSocket(ws_stream) => Pin::new(ws_stream).poll_ready(cx),
+            GatewayConnection::RawTcp(conn) => Pin::new(conn).poll_ready(cx),
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Message) -> Result<(), Self::Error> {
+        match self.get_mut() {
+            GatewayConnection::WebSocket(ws_stream) => Pin::new(ws_stream).start_send(item),
+            GatewayConnection::RawTcp(conn) => Pin::new(conn).start_send(item),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.get_mut() {
+            GatewayConnection::WebSocket(ws_stream) => Pin::new(ws_stream).poll_flush(cx),
+            GatewayConnection::RawTcp(conn) => Pin::new(conn).poll_flush(cx),
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.get_mut() {
+            GatewayConnection::WebSocket(ws_stream) => Pin::new(ws_stream).poll_close(cx),
+            GatewayConnection::RawTcp(conn) => Pin::new(conn).poll_close(cx),
+        }
+    }
+}
+
+impl HandshakeTransport for GatewayConnection {
+    fn send_handshake_payload(
+        &mut self,
+        payload: Vec<u8>,
+    ) -> BoxFuture<'_, Result<(), HandshakeError>> {
+        match self {
+            GatewayConnection::WebSocket(ws_stream) => Box::pin(async move {
+                WsHandshakeTransport::new(ws_stream)
+                    .send_handshake_payload(payload)
+                    .await
+            }),
+            GatewayConnection::RawTcp(conn) => conn.send_handshake_payload(payload),
+        }
+    }
+
+    fn send_handshake_error(
+        &mut self,
+        message: String,
+    ) -> BoxFuture<'_, Result<(), HandshakeError>> {
+        match self {
+            GatewayConnection::WebSocket(ws_stream) => Box::pin(async move {
+                WsHandshakeTransport::new(ws_stream)
+                    .send_handshake_error(message)
+                    .await
+            }),
+            GatewayConnection::RawTcp(conn) => conn.send_handshake_error(message),
+        }
+    }
+
+    fn receive_handshake_payload(&mut self) -> BoxFuture<'_, Result<Vec<u8>, HandshakeError>> {
+        match self {
+            GatewayConnection::WebSocket(ws_stream) => Box::pin(async move {
+                WsHandshakeTransport::new(ws_stream)
+                    .receive_handshake_payload()
+                    .await
+            }),
+            GatewayConnection::RawTcp(conn) => conn.receive_handshake_payload(),
+        }
+    }
+}
@@ -3,6 +3,8 @@
 
 use crate::error::GatewayClientError;
 pub use client::GatewayClient;
+pub use gateway_requests::device::DeviceId;
+pub use gateway_requests::redirect::GatewayRedirect;
 pub use packet_router::{
     AcknowledgementReceiver, AcknowledgementSender, MixnetMessageReceiver, MixnetMessageSender,
 };
@@ -10,6 +12,8 @@ use tungstenite::{protocol::Message, Error as WsError};
 
 pub mod bandwidth;
 pub mod client;
+#[cfg(not(target_arch = "wasm32"))]
+mod connection;
 pub mod error;
 pub mod packet_router;
 pub mod socket_state;
@@ -16,9 +16,7 @@ use task::ShutdownListener;
 use tungstenite::Message;
 
 #[cfg(not(target_arch = "wasm32"))]
-use tokio::net::TcpStream;
-#[cfg(not(target_arch = "wasm32"))]
-use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use crate::connection::GatewayConnection;
 
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen_futures;
@@ -28,7 +26,7 @@ use wasm_utils::websocket::JSWebsocket;
 // type alias for not having to type the whole thing every single time (and now it makes it easier
 // to use different types based on compilation target)
 #[cfg(not(target_arch = "wasm32"))]
-type WsConn = WebSocketStream<MaybeTlsStream<TcpStream>>;
+type WsConn = GatewayConnection;
 
 #[cfg(target_arch = "wasm32")]
 type WsConn = JSWebsocket;
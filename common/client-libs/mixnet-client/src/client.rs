@@ -1,10 +1,10 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::link::{LinkConfig, LinkConnection};
 use futures::channel::mpsc;
 use futures::StreamExt;
 use log::*;
-use nymsphinx::framing::codec::SphinxCodec;
 use nymsphinx::framing::packet::FramedSphinxPacket;
 use nymsphinx::params::PacketMode;
 use nymsphinx::{addressing::nodes::NymNodeRoutingAddress, SphinxPacket};
@@ -16,13 +16,15 @@ use std::sync::Arc;
 use std::time::Duration;
 use tokio::net::TcpStream;
 use tokio::time::sleep;
-use tokio_util::codec::Framed;
 
 pub struct Config {
     initial_reconnection_backoff: Duration,
     maximum_reconnection_backoff: Duration,
     initial_connection_timeout: Duration,
     maximum_connection_buffer_size: usize,
+
+    /// If specified, the connections are going to be established through the encrypted link.
+    link: Option<LinkConfig>,
 }
 
 impl Config {
@@ -37,8 +39,14 @@ impl Config {
             maximum_reconnection_backoff,
             initial_connection_timeout,
             maximum_connection_buffer_size,
+            link: None,
         }
     }
+
+    pub fn with_link(mut self, link: LinkConfig) -> Self {
+        self.link = Some(link);
+        self
+    }
 }
 
 pub trait SendWithoutResponse {
@@ -79,28 +87,27 @@ impl Client {
         }
     }
 
-    async fn manage_connection(
+    async fn open_stream(
         address: SocketAddr,
-        receiver: mpsc::Receiver<FramedSphinxPacket>,
         connection_timeout: Duration,
         current_reconnection: &AtomicU32,
-    ) {
+    ) -> Option<TcpStream> {
         let connection_fut = TcpStream::connect(address);
 
-        let conn = match tokio::time::timeout(connection_timeout, connection_fut).await {
+        match tokio::time::timeout(connection_timeout, connection_fut).await {
             Ok(stream_res) => match stream_res {
                 Ok(stream) => {
                     debug!("Managed to establish connection to {}", address);
                     // if we managed to connect, reset the reconnection count (whatever it might have been)
                     current_reconnection.store(0, Ordering::Release);
-                    Framed::new(stream, SphinxCodec)
+                    Some(stream)
                 }
                 Err(err) => {
                     debug!(
                         "failed to establish connection to {} (err: {})",
                         address, err
                     );
-                    return;
+                    None
                 }
             },
             Err(_) => {
@@ -111,15 +118,71 @@ impl Client {
 
                 // we failed to connect - increase reconnection attempt
                 current_reconnection.fetch_add(1, Ordering::SeqCst);
-                return;
+                None
             }
+        }
+    }
+
+    async fn open_connection(
+        address: SocketAddr,
+        connection_timeout: Duration,
+        current_reconnection: &AtomicU32,
+        link: Option<&LinkConfig>,
+    ) -> Option<LinkConnection<TcpStream>> {
+        let stream = Self::open_stream(address, connection_timeout, current_reconnection).await?;
+        let link = match link {
+            Some(link) => link,
+            None => return Some(LinkConnection::new_plaintext(stream)),
+        };
+
+        match link.initiate(stream, address).await {
+            Ok(conn) => Some(conn),
+            Err(err) if link.mode().allows_plaintext() => {
+                // the remote is most likely not upgraded yet and has dropped the connection
+                // upon receiving the handshake
+                debug!(
+                    "failed to establish encrypted link to {} (err: {}) - falling back to plaintext",
+                    address, err
+                );
+                Self::open_stream(address, connection_timeout, current_reconnection)
+                    .await
+                    .map(LinkConnection::new_plaintext)
+            }
+            Err(err) => {
+                warn!(
+                    "failed to establish encrypted link to {} - {}",
+                    address, err
+                );
+                current_reconnection.fetch_add(1, Ordering::SeqCst);
+                None
+            }
+        }
+    }
+
+    async fn manage_connection(
+        address: SocketAddr,
+        receiver: mpsc::Receiver<FramedSphinxPacket>,
+        connection_timeout: Duration,
+        current_reconnection: &AtomicU32,
+        link: Option<LinkConfig>,
+    ) {
+        let conn = match Self::open_connection(
+            address,
+            connection_timeout,
+            current_reconnection,
+            link.as_ref(),
+        )
+        .await
+        {
+            Some(conn) => conn,
+            None => return,
         };
 
         // Take whatever the receiver channel produces and put it on the connection.
         // We could have as well used conn.send_all(receiver.map(Ok)), but considering we don't care
         // about neither receiver nor the connection, it doesn't matter which one gets consumed
         if let Err(err) = receiver.map(Ok).forward(conn).await {
-            warn!("Failed to forward packets to {} - {:?}", address, err);
+            warn!("Failed to forward packets to {} - {}", address, err);
         }
 
         debug!(
@@ -172,8 +235,9 @@ impl Client {
         let reconnection_attempt = current_reconnection_attempt.load(Ordering::Acquire);
         let backoff = self.determine_backoff(reconnection_attempt);
 
-        // copy the value before moving into another task
+        // copy the values before moving into another task
         let initial_connection_timeout = self.config.initial_connection_timeout;
+        let link = self.config.link.clone();
 
         tokio::spawn(async move {
             // before executing the manager, wait for what was specified, if anything
@@ -187,6 +251,7 @@ impl Client {
                 receiver,
                 initial_connection_timeout,
                 &current_reconnection_attempt,
+                link,
             )
             .await
         });
@@ -259,6 +324,7 @@ mod tests {
             maximum_reconnection_backoff: Duration::from_millis(300_000),
             initial_connection_timeout: Duration::from_millis(1_500),
             maximum_connection_buffer_size: 128,
+            link: None,
         })
     }
 
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::client::{Client, Config, SendWithoutResponse};
+use crate::link::LinkConfig;
 use futures::channel::mpsc;
 use futures::StreamExt;
 use log::*;
@@ -24,13 +25,15 @@ impl PacketForwarder {
         maximum_reconnection_backoff: Duration,
         initial_connection_timeout: Duration,
         maximum_connection_buffer_size: usize,
+        link: LinkConfig,
     ) -> (PacketForwarder, MixForwardingSender) {
         let client_config = Config::new(
             initial_reconnection_backoff,
             maximum_reconnection_backoff,
             initial_connection_timeout,
             maximum_connection_buffer_size,
-        );
+        )
+        .with_link(link);
 
         let (packet_sender, packet_receiver) = mpsc::unbounded();
 
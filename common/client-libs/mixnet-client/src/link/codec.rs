@@ -0,0 +1,76 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::link::{LinkError, MAX_NOISE_MESSAGE_LEN, NOISE_TAG_LEN};
+use bytes::{Buf, BufMut, BytesMut};
+use nymsphinx::framing::codec::SphinxCodec;
+use nymsphinx::framing::packet::FramedSphinxPacket;
+use snow::TransportState;
+use tokio_util::codec::{Decoder, Encoder};
+
+const LEN_PREFIX_SIZE: usize = 2;
+
+/// Maximum amount of plaintext that can be put in a single Noise message.
+const MAX_PLAINTEXT_LEN: usize = MAX_NOISE_MESSAGE_LEN - NOISE_TAG_LEN;
+
+/// Codec encrypting the stream of sphinx packets, as produced by `SphinxCodec`, with the keys
+/// established during the link handshake. The stream is split into length-prefixed Noise messages,
+/// so a single packet might span multiple messages and vice versa.
+pub struct LinkCodec {
+    transport: TransportState,
+
+    /// Decrypted bytes that do not form a full sphinx packet yet.
+    plaintext: BytesMut,
+}
+
+impl LinkCodec {
+    pub(crate) fn new(transport: TransportState) -> Self {
+        LinkCodec {
+            transport,
+            plaintext: BytesMut::new(),
+        }
+    }
+}
+
+impl Encoder<FramedSphinxPacket> for LinkCodec {
+    type Error = LinkError;
+
+    fn encode(&mut self, item: FramedSphinxPacket, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let mut plaintext = BytesMut::new();
+        SphinxCodec.encode(item, &mut plaintext)?;
+
+        let mut ciphertext = vec![0u8; MAX_NOISE_MESSAGE_LEN];
+        for chunk in plaintext.chunks(MAX_PLAINTEXT_LEN) {
+            let len = self.transport.write_message(chunk, &mut ciphertext)?;
+            dst.reserve(LEN_PREFIX_SIZE + len);
+            dst.put_u16(len as u16);
+            dst.put_slice(&ciphertext[..len]);
+        }
+        Ok(())
+    }
+}
+
+impl Decoder for LinkCodec {
+    type Item = FramedSphinxPacket;
+    type Error = LinkError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        // decrypt all complete messages we have received so far
+        let mut decrypted = vec![0u8; MAX_NOISE_MESSAGE_LEN];
+        while src.len() >= LEN_PREFIX_SIZE {
+            let message_len = u16::from_be_bytes([src[0], src[1]]) as usize;
+            if src.len() < LEN_PREFIX_SIZE + message_len {
+                // we don't have enough bytes to read the rest of the message
+                src.reserve(LEN_PREFIX_SIZE + message_len - src.len());
+                break;
+            }
+
+            src.advance(LEN_PREFIX_SIZE);
+            let message = src.split_to(message_len);
+            let len = self.transport.read_message(&message, &mut decrypted)?;
+            self.plaintext.extend_from_slice(&decrypted[..len]);
+        }
+
+        Ok(SphinxCodec.decode(&mut self.plaintext)?)
+    }
+}
@@ -0,0 +1,111 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::link::{LinkCodec, LinkError};
+use bytes::BytesMut;
+use futures::task::{Context, Poll};
+use futures::{Sink, Stream};
+use nymsphinx::framing::codec::SphinxCodec;
+use nymsphinx::framing::packet::FramedSphinxPacket;
+use snow::TransportState;
+use std::pin::Pin;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::codec::{Framed, FramedParts};
+
+/// Connection between two nodes, over which sphinx packets are sent either in plaintext or
+/// through the encrypted link, depending on what was negotiated.
+pub enum LinkConnection<S> {
+    Plaintext(Framed<S, SphinxCodec>),
+    Encrypted(Framed<S, LinkCodec>),
+}
+
+impl<S> LinkConnection<S>
+where
+    S: AsyncRead + AsyncWrite,
+{
+    pub fn new_plaintext(conn: S) -> Self {
+        LinkConnection::Plaintext(Framed::new(conn, SphinxCodec))
+    }
+
+    /// Creates the plaintext connection, where the first byte has already been read from the
+    /// underlying stream.
+    pub(crate) fn new_plaintext_with_prefix(conn: S, first_byte: u8) -> Self {
+        let mut parts = FramedParts::new::<FramedSphinxPacket>(conn, SphinxCodec);
+        parts.read_buf = BytesMut::from(&[first_byte][..]);
+        LinkConnection::Plaintext(Framed::from_parts(parts))
+    }
+
+    pub(crate) fn new_encrypted(conn: S, transport: TransportState) -> Self {
+        LinkConnection::Encrypted(Framed::new(conn, LinkCodec::new(transport)))
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        matches!(self, LinkConnection::Encrypted(_))
+    }
+
+    pub fn into_inner(self) -> S {
+        match self {
+            LinkConnection::Plaintext(framed) => framed.into_inner(),
+            LinkConnection::Encrypted(framed) => framed.into_inner(),
+        }
+    }
+}
+
+impl<S> Stream for LinkConnection<S>
+where
+    S: AsyncRead + Unpin,
+{
+    type Item = Result<FramedSphinxPacket, LinkError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.get_mut() {
+            LinkConnection::Plaintext(framed) => Pin::new(framed)
+                .poll_next(cx)
+                .map(|item| item.map(|res| res.map_err(Into::into))),
+            LinkConnection::Encrypted(framed) => Pin::new(framed).poll_next(cx),
+        }
+    }
+}
+
+impl<S> Sink<FramedSphinxPacket> for LinkConnection<S>
+where
+    S: AsyncWrite + Unpin,
+{
+    type Error = LinkError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.get_mut() {
+            LinkConnection::Plaintext(framed) => {
+                Pin::new(framed).poll_ready(cx).map_err(Into::into)
+            }
+            LinkConnection::Encrypted(framed) => Pin::new(framed).poll_ready(cx),
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: FramedSphinxPacket) -> Result<(), Self::Error> {
+        match self.get_mut() {
+            LinkConnection::Plaintext(framed) => {
+                Pin::new(framed).start_send(item).map_err(Into::into)
+            }
+            LinkConnection::Encrypted(framed) => Pin::new(framed).start_send(item),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.get_mut() {
+            LinkConnection::Plaintext(framed) => {
+                Pin::new(framed).poll_flush(cx).map_err(Into::into)
+            }
+            LinkConnection::Encrypted(framed) => Pin::new(framed).poll_flush(cx),
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.get_mut() {
+            LinkConnection::Plaintext(framed) => {
+                Pin::new(framed).poll_close(cx).map_err(Into::into)
+            }
+            LinkConnection::Encrypted(framed) => Pin::new(framed).poll_close(cx),
+        }
+    }
+}
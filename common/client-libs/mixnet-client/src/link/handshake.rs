@@ -0,0 +1,121 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::link::{
+    LinkEncryption, LinkError, LinkPeers, LINK_PREAMBLE, MAX_NOISE_MESSAGE_LEN, NOISE_PARAMS,
+    NOISE_PROLOGUE,
+};
+use crypto::asymmetric::encryption;
+use log::*;
+use snow::{Builder, HandshakeState, TransportState};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+fn noise_builder<'a>(local_key: &'a [u8]) -> Builder<'a> {
+    // the params are hardcoded, so the parsing can't fail
+    Builder::new(NOISE_PARAMS.parse().unwrap())
+        .prologue(NOISE_PROLOGUE)
+        .local_private_key(local_key)
+}
+
+async fn write_noise_message<S>(conn: &mut S, message: &[u8]) -> Result<(), LinkError>
+where
+    S: AsyncWrite + Unpin,
+{
+    if message.len() > MAX_NOISE_MESSAGE_LEN {
+        return Err(LinkError::MessageTooLarge(message.len()));
+    }
+    conn.write_u16(message.len() as u16).await?;
+    conn.write_all(message).await?;
+    conn.flush().await?;
+    Ok(())
+}
+
+async fn read_noise_message<S>(conn: &mut S) -> Result<Vec<u8>, LinkError>
+where
+    S: AsyncRead + Unpin,
+{
+    let len = conn.read_u16().await? as usize;
+    let mut message = vec![0u8; len];
+    conn.read_exact(&mut message).await?;
+    Ok(message)
+}
+
+async fn send_handshake_message<S>(
+    conn: &mut S,
+    handshake: &mut HandshakeState,
+) -> Result<(), LinkError>
+where
+    S: AsyncWrite + Unpin,
+{
+    let mut buf = vec![0u8; MAX_NOISE_MESSAGE_LEN];
+    let len = handshake.write_message(&[], &mut buf)?;
+    write_noise_message(conn, &buf[..len]).await
+}
+
+async fn receive_handshake_message<S>(
+    conn: &mut S,
+    handshake: &mut HandshakeState,
+) -> Result<(), LinkError>
+where
+    S: AsyncRead + Unpin,
+{
+    let message = read_noise_message(conn).await?;
+    let mut buf = vec![0u8; MAX_NOISE_MESSAGE_LEN];
+    handshake.read_message(&message, &mut buf)?;
+    Ok(())
+}
+
+/// Performs the initiator side of the `IK` handshake, i.e. sends the preamble followed by
+/// `-> e, es, s, ss` and waits for `<- e, ee, se`.
+pub(crate) async fn initiator_handshake<S>(
+    conn: &mut S,
+    local_key: &encryption::PrivateKey,
+    remote_key: &encryption::PublicKey,
+) -> Result<TransportState, LinkError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let local_key = local_key.to_bytes();
+    let remote_key = remote_key.to_bytes();
+    let mut handshake = noise_builder(&local_key)
+        .remote_public_key(&remote_key)
+        .build_initiator()?;
+
+    conn.write_all(&LINK_PREAMBLE).await?;
+    send_handshake_message(conn, &mut handshake).await?;
+    receive_handshake_message(conn, &mut handshake).await?;
+
+    Ok(handshake.into_transport_mode()?)
+}
+
+/// Performs the responder side of the `IK` handshake, assuming the preamble has already been
+/// read from the connection.
+pub(crate) async fn responder_handshake<S>(
+    conn: &mut S,
+    local_key: &encryption::PrivateKey,
+    peers: &LinkPeers,
+    mode: LinkEncryption,
+) -> Result<TransportState, LinkError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let local_key = local_key.to_bytes();
+    let mut handshake = noise_builder(&local_key).build_responder()?;
+
+    receive_handshake_message(conn, &mut handshake).await?;
+
+    // the static key of the initiator is always known after the first message of `IK`
+    let remote_key = handshake.get_remote_static().unwrap_or_default();
+    if !peers.is_known(remote_key) {
+        if mode.allows_plaintext() {
+            // we'd have accepted the plaintext connection from that remote anyway
+            debug!("the remote has presented an unknown static key - proceeding regardless");
+        } else {
+            return Err(LinkError::UnknownRemoteKey);
+        }
+    }
+
+    send_handshake_message(conn, &mut handshake).await?;
+
+    Ok(handshake.into_transport_mode()?)
+}
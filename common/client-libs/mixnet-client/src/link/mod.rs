@@ -0,0 +1,449 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Authenticated and encrypted link layer used for sending sphinx packets between nodes.
+//!
+//! The link is established with the Noise `IK` handshake, where the static keys of both parties
+//! are the x25519 equivalents of their ed25519 identity keys. The initiator learns the static key
+//! of the responder from the network topology, while the responder checks whether the key
+//! presented by the initiator belongs to any node in the topology.
+//!
+//! The initiator starts the connection with the `LINK_PREAMBLE`, followed by the handshake
+//! messages and afterwards, the encrypted sphinx packets. Each Noise message is prefixed with
+//! its 2 byte big-endian length:
+//!
+//! | PREAMBLE (2 bytes) | LEN (u16, BE) | HANDSHAKE MESSAGE | ... | LEN (u16, BE) | TRANSPORT MESSAGE | ...
+//!
+//! As the first byte of the preamble is 0, which is never a valid `PacketSize`, the responder
+//! can tell the encrypted connections apart from the plaintext ones, which lets the nodes
+//! accept both during the rollout (see `LinkEncryption::Compatible`).
+
+use crate::link::handshake::{initiator_handshake, responder_handshake};
+use crypto::asymmetric::{encryption, identity};
+use log::*;
+use nymsphinx::framing::codec::SphinxCodecError;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
+
+pub use codec::LinkCodec;
+pub use connection::LinkConnection;
+pub use peers::LinkPeers;
+
+mod codec;
+mod connection;
+mod handshake;
+mod peers;
+
+/// Noise protocol used for establishing the link.
+const NOISE_PARAMS: &str = "Noise_IK_25519_ChaChaPoly_BLAKE2s";
+
+/// Prologue mixed into the handshake hash, so that both parties agree on the link version.
+const NOISE_PROLOGUE: &[u8] = b"nym-mixnet-link-v1";
+
+/// Version of the link protocol announced in the preamble.
+pub const LINK_VERSION: u8 = 1;
+
+/// Bytes sent by the initiator at the very beginning of an encrypted connection.
+pub const LINK_PREAMBLE: [u8; 2] = [0, LINK_VERSION];
+
+/// Maximum length of a single Noise message, as defined by the specification.
+pub const MAX_NOISE_MESSAGE_LEN: usize = 65535;
+
+/// Length of the authentication tag attached to every encrypted Noise message.
+const NOISE_TAG_LEN: usize = 16;
+
+const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_millis(5_000);
+
+#[derive(Debug, Error)]
+pub enum LinkError {
+    #[error("experienced an io error - {0}")]
+    IoError(#[from] io::Error),
+
+    #[error("the noise protocol failure - {0}")]
+    NoiseError(#[from] snow::Error),
+
+    #[error("failed to process sphinx packet framing - {0:?}")]
+    SphinxFramingError(SphinxCodecError),
+
+    #[error("the handshake has not finished within {0:?}")]
+    HandshakeTimeout(Duration),
+
+    #[error(
+        "received noise message of length {0}, while the maximum is {}",
+        MAX_NOISE_MESSAGE_LEN
+    )]
+    MessageTooLarge(usize),
+
+    #[error("the remote uses unsupported link version {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("the link key of {0} is not known")]
+    UnknownRemote(SocketAddr),
+
+    #[error("the remote has presented a static key that does not belong to any known node")]
+    UnknownRemoteKey,
+
+    #[error("received a plaintext connection while link encryption is required")]
+    PlaintextRejected,
+
+    #[error("received an encrypted connection while link encryption is disabled")]
+    EncryptionDisabled,
+}
+
+impl From<SphinxCodecError> for LinkError {
+    fn from(err: SphinxCodecError) -> Self {
+        match err {
+            SphinxCodecError::IoError(err) => LinkError::IoError(err),
+            other => LinkError::SphinxFramingError(other),
+        }
+    }
+}
+
+/// Determines whether the links between the nodes should be encrypted.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkEncryption {
+    /// All connections are plaintext, i.e. the behaviour from before the encrypted links were
+    /// introduced.
+    Disabled,
+
+    /// Outgoing connections are encrypted whenever the identity of the remote is known and the
+    /// remote supports it, otherwise they fall back to plaintext. Incoming connections are
+    /// accepted regardless of whether they are encrypted.
+    ///
+    /// Note that as the fallback is triggered by a failed handshake, an active attacker can
+    /// force the plaintext connection. This mode is meant only for the duration of the rollout.
+    Compatible,
+
+    /// All connections, both incoming and outgoing, must be encrypted and authenticated.
+    Required,
+}
+
+impl Default for LinkEncryption {
+    fn default() -> Self {
+        LinkEncryption::Compatible
+    }
+}
+
+impl LinkEncryption {
+    pub fn allows_plaintext(&self) -> bool {
+        !matches!(self, LinkEncryption::Required)
+    }
+
+    pub fn allows_encryption(&self) -> bool {
+        !matches!(self, LinkEncryption::Disabled)
+    }
+}
+
+/// Configuration of the link layer shared by all connections of the node, both incoming and
+/// outgoing.
+#[derive(Clone)]
+pub struct LinkConfig {
+    mode: LinkEncryption,
+
+    /// x25519 equivalent of the private identity key of this node.
+    local_key: Arc<encryption::PrivateKey>,
+
+    /// Link keys of all nodes in the network.
+    peers: LinkPeers,
+
+    handshake_timeout: Duration,
+}
+
+impl LinkConfig {
+    pub fn new(mode: LinkEncryption, identity_keys: &identity::KeyPair, peers: LinkPeers) -> Self {
+        LinkConfig {
+            mode,
+            local_key: Arc::new(identity_keys.private_key().to_x25519()),
+            peers,
+            handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+        }
+    }
+
+    pub fn with_handshake_timeout(mut self, handshake_timeout: Duration) -> Self {
+        self.handshake_timeout = handshake_timeout;
+        self
+    }
+
+    pub fn mode(&self) -> LinkEncryption {
+        self.mode
+    }
+
+    pub fn peers(&self) -> &LinkPeers {
+        &self.peers
+    }
+
+    /// Establishes the link on top of the outgoing connection to the node at `remote`.
+    /// If the link encryption is not required and the key of the remote is unknown,
+    /// the plaintext connection is returned instead.
+    pub async fn initiate<S>(
+        &self,
+        mut conn: S,
+        remote: SocketAddr,
+    ) -> Result<LinkConnection<S>, LinkError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        if !self.mode.allows_encryption() {
+            return Ok(LinkConnection::new_plaintext(conn));
+        }
+
+        let remote_key = match self.peers.link_key(&remote) {
+            Some(remote_key) => remote_key,
+            None if self.mode.allows_plaintext() => {
+                debug!(
+                    "the link key of {} is unknown - using plaintext connection",
+                    remote
+                );
+                return Ok(LinkConnection::new_plaintext(conn));
+            }
+            None => return Err(LinkError::UnknownRemote(remote)),
+        };
+
+        let handshake = initiator_handshake(&mut conn, &self.local_key, &remote_key);
+        match tokio::time::timeout(self.handshake_timeout, handshake).await {
+            Ok(transport) => Ok(LinkConnection::new_encrypted(conn, transport?)),
+            Err(_) => Err(LinkError::HandshakeTimeout(self.handshake_timeout)),
+        }
+    }
+
+    /// Establishes the link on top of the incoming connection, if the remote has started the
+    /// handshake, or returns the plaintext connection if it's allowed.
+    pub async fn accept<S>(&self, conn: S) -> Result<LinkConnection<S>, LinkError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        match tokio::time::timeout(self.handshake_timeout, self.accept_inner(conn)).await {
+            Ok(res) => res,
+            Err(_) => Err(LinkError::HandshakeTimeout(self.handshake_timeout)),
+        }
+    }
+
+    async fn accept_inner<S>(&self, mut conn: S) -> Result<LinkConnection<S>, LinkError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let first_byte = conn.read_u8().await?;
+        if first_byte != LINK_PREAMBLE[0] {
+            return if self.mode.allows_plaintext() {
+                Ok(LinkConnection::new_plaintext_with_prefix(conn, first_byte))
+            } else {
+                Err(LinkError::PlaintextRejected)
+            };
+        }
+
+        if !self.mode.allows_encryption() {
+            return Err(LinkError::EncryptionDisabled);
+        }
+
+        let version = conn.read_u8().await?;
+        if version != LINK_VERSION {
+            return Err(LinkError::UnsupportedVersion(version));
+        }
+
+        let transport =
+            responder_handshake(&mut conn, &self.local_key, &self.peers, self.mode).await?;
+        Ok(LinkConnection::new_encrypted(conn, transport))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{SinkExt, StreamExt};
+    use nymsphinx::builder::SphinxPacketBuilder;
+    use nymsphinx::framing::packet::FramedSphinxPacket;
+    use nymsphinx::params::packet_sizes::PacketSize;
+    use nymsphinx::params::PacketMode;
+    use nymsphinx::{
+        crypto, Delay as SphinxDelay, Destination, DestinationAddressBytes, Node as SphinxNode,
+        NodeAddressBytes, SphinxPacket, DESTINATION_ADDRESS_LENGTH, IDENTIFIER_LENGTH,
+        NODE_ADDRESS_LENGTH,
+    };
+    use rand::rngs::OsRng;
+    use tokio::io::DuplexStream;
+
+    const RESPONDER_ADDRESS: &str = "1.2.3.4:1789";
+    const INITIATOR_ADDRESS: &str = "5.6.7.8:1789";
+
+    fn make_valid_sphinx_packet(size: PacketSize) -> SphinxPacket {
+        let route = [5u8, 4, 2].map(|address| {
+            let (_, node_pk) = crypto::keygen();
+            SphinxNode::new(
+                NodeAddressBytes::from_bytes([address; NODE_ADDRESS_LENGTH]),
+                node_pk,
+            )
+        });
+        let destination = Destination::new(
+            DestinationAddressBytes::from_bytes([3u8; DESTINATION_ADDRESS_LENGTH]),
+            [4u8; IDENTIFIER_LENGTH],
+        );
+        let delays = vec![SphinxDelay::new_from_nanos(42); 3];
+        SphinxPacketBuilder::new()
+            .with_payload_size(size.payload_size())
+            .build_packet(b"foomp".to_vec(), &route, &destination, &delays)
+            .unwrap()
+    }
+
+    struct Node {
+        keys: identity::KeyPair,
+        address: SocketAddr,
+    }
+
+    impl Node {
+        fn new(address: &str) -> Self {
+            Node {
+                keys: identity::KeyPair::new(&mut OsRng),
+                address: address.parse().unwrap(),
+            }
+        }
+
+        fn link_config(&self, mode: LinkEncryption, known: &[&Node]) -> LinkConfig {
+            let peers = LinkPeers::default();
+            peers.update(
+                known
+                    .iter()
+                    .map(|node| (node.address, *node.keys.public_key())),
+            );
+            LinkConfig::new(mode, &self.keys, peers)
+        }
+    }
+
+    fn responder_address() -> SocketAddr {
+        RESPONDER_ADDRESS.parse().unwrap()
+    }
+
+    async fn establish(
+        initiator: LinkConfig,
+        responder: LinkConfig,
+    ) -> (
+        Result<LinkConnection<DuplexStream>, LinkError>,
+        Result<LinkConnection<DuplexStream>, LinkError>,
+    ) {
+        let (initiator_conn, responder_conn) = tokio::io::duplex(128 * 1024);
+        futures::join!(
+            initiator.initiate(initiator_conn, responder_address()),
+            responder.accept(responder_conn)
+        )
+    }
+
+    #[tokio::test]
+    async fn encrypted_link_can_be_established_between_known_nodes() {
+        let initiator = Node::new(INITIATOR_ADDRESS);
+        let responder = Node::new(RESPONDER_ADDRESS);
+
+        let (initiator_conn, responder_conn) = establish(
+            initiator.link_config(LinkEncryption::Required, &[&responder]),
+            responder.link_config(LinkEncryption::Required, &[&initiator]),
+        )
+        .await;
+        let mut initiator_conn = initiator_conn.unwrap();
+        let mut responder_conn = responder_conn.unwrap();
+        assert!(initiator_conn.is_encrypted());
+        assert!(responder_conn.is_encrypted());
+
+        for size in [
+            PacketSize::AckPacket,
+            PacketSize::RegularPacket,
+            PacketSize::ExtendedPacket,
+        ] {
+            let packet = make_valid_sphinx_packet(size);
+            let packet_bytes = packet.to_bytes();
+            initiator_conn
+                .send(FramedSphinxPacket::new(packet, PacketMode::Mix))
+                .await
+                .unwrap();
+
+            let received = responder_conn.next().await.unwrap().unwrap();
+            assert_eq!(received.packet_size(), size);
+            assert_eq!(received.into_inner().to_bytes(), packet_bytes);
+        }
+    }
+
+    #[tokio::test]
+    async fn unknown_initiators_are_rejected_when_encryption_is_required() {
+        let initiator = Node::new(INITIATOR_ADDRESS);
+        let responder = Node::new(RESPONDER_ADDRESS);
+
+        let (initiator_conn, responder_conn) = establish(
+            initiator.link_config(LinkEncryption::Required, &[&responder]),
+            responder.link_config(LinkEncryption::Required, &[]),
+        )
+        .await;
+        assert!(initiator_conn.is_err());
+        assert!(matches!(responder_conn, Err(LinkError::UnknownRemoteKey)));
+    }
+
+    #[tokio::test]
+    async fn handshake_fails_if_responder_key_does_not_match() {
+        let initiator = Node::new(INITIATOR_ADDRESS);
+        let responder = Node::new(RESPONDER_ADDRESS);
+        let impostor = Node::new(RESPONDER_ADDRESS);
+
+        let (initiator_conn, responder_conn) = establish(
+            initiator.link_config(LinkEncryption::Required, &[&responder]),
+            impostor.link_config(LinkEncryption::Required, &[&initiator]),
+        )
+        .await;
+        assert!(initiator_conn.is_err());
+        assert!(responder_conn.is_err());
+    }
+
+    #[tokio::test]
+    async fn plaintext_is_used_in_compatible_mode_if_remote_is_unknown() {
+        let initiator = Node::new(INITIATOR_ADDRESS);
+        let responder = Node::new(RESPONDER_ADDRESS);
+        let initiator_config = initiator.link_config(LinkEncryption::Compatible, &[]);
+        let responder_config = responder.link_config(LinkEncryption::Compatible, &[&initiator]);
+
+        let (initiator_conn, responder_conn) = tokio::io::duplex(128 * 1024);
+        let mut initiator_conn = initiator_config
+            .initiate(initiator_conn, responder_address())
+            .await
+            .unwrap();
+        assert!(!initiator_conn.is_encrypted());
+
+        let packet = make_valid_sphinx_packet(PacketSize::RegularPacket);
+        let packet_bytes = packet.to_bytes();
+        initiator_conn
+            .send(FramedSphinxPacket::new(packet, PacketMode::Mix))
+            .await
+            .unwrap();
+
+        let mut responder_conn = responder_config.accept(responder_conn).await.unwrap();
+        assert!(!responder_conn.is_encrypted());
+        let received = responder_conn.next().await.unwrap().unwrap();
+        assert_eq!(received.into_inner().to_bytes(), packet_bytes);
+    }
+
+    #[tokio::test]
+    async fn plaintext_is_rejected_when_encryption_is_required() {
+        let initiator = Node::new(INITIATOR_ADDRESS);
+        let responder = Node::new(RESPONDER_ADDRESS);
+        let initiator_config = initiator.link_config(LinkEncryption::Disabled, &[&responder]);
+        let responder_config = responder.link_config(LinkEncryption::Required, &[&initiator]);
+
+        let (initiator_conn, responder_conn) = tokio::io::duplex(128 * 1024);
+        let mut initiator_conn = initiator_config
+            .initiate(initiator_conn, responder_address())
+            .await
+            .unwrap();
+        initiator_conn
+            .send(FramedSphinxPacket::new(
+                make_valid_sphinx_packet(PacketSize::RegularPacket),
+                PacketMode::Mix,
+            ))
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            responder_config.accept(responder_conn).await,
+            Err(LinkError::PlaintextRejected)
+        ));
+    }
+}
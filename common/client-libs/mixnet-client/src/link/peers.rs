@@ -0,0 +1,75 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crypto::asymmetric::{encryption, identity};
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+
+#[derive(Debug, Default)]
+struct LinkPeersInner {
+    /// Link keys of the nodes, indexed by the address they're listening on for mix packets.
+    by_address: HashMap<SocketAddr, encryption::PublicKey>,
+
+    /// Link keys of all the nodes, regardless of their address.
+    known_keys: HashSet<[u8; encryption::PUBLIC_KEY_SIZE]>,
+}
+
+/// Link keys, i.e. x25519 equivalents of the identity keys, of all the nodes in the network.
+/// It's shared between all the connections of the node and is expected to be periodically
+/// updated with the current topology.
+#[derive(Debug, Clone, Default)]
+pub struct LinkPeers {
+    inner: Arc<RwLock<LinkPeersInner>>,
+}
+
+impl LinkPeers {
+    /// Replaces all the known peers with the provided ones.
+    pub fn update<I>(&self, peers: I)
+    where
+        I: IntoIterator<Item = (SocketAddr, identity::PublicKey)>,
+    {
+        let mut by_address = HashMap::new();
+        let mut known_keys = HashSet::new();
+        for (address, identity) in peers {
+            let link_key = identity.to_x25519();
+            known_keys.insert(link_key.to_bytes());
+            by_address.insert(address, link_key);
+        }
+
+        let mut inner = self.inner.write().expect("link peers lock got poisoned");
+        inner.by_address = by_address;
+        inner.known_keys = known_keys;
+    }
+
+    /// Returns the link key of the node listening on the provided address.
+    pub fn link_key(&self, address: &SocketAddr) -> Option<encryption::PublicKey> {
+        self.inner
+            .read()
+            .expect("link peers lock got poisoned")
+            .by_address
+            .get(address)
+            .copied()
+    }
+
+    /// Checks whether the provided link key belongs to any known node.
+    pub fn is_known(&self, link_key: &[u8]) -> bool {
+        self.inner
+            .read()
+            .expect("link peers lock got poisoned")
+            .known_keys
+            .contains(link_key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner
+            .read()
+            .expect("link peers lock got poisoned")
+            .by_address
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
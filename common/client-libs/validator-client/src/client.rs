@@ -4,15 +4,16 @@
 use crate::{validator_api, ValidatorClientError};
 use mixnet_contract_common::mixnode::MixNodeDetails;
 use mixnet_contract_common::MixId;
-use mixnet_contract_common::{GatewayBond, IdentityKeyRef};
+use mixnet_contract_common::{EpochId, GatewayBond, IdentityKeyRef, Interval};
 use url::Url;
 use validator_api_requests::coconut::{
     BlindSignRequestBody, BlindedSignatureResponse, CosmosAddressResponse, VerificationKeyResponse,
     VerifyCredentialBody, VerifyCredentialResponse,
 };
 use validator_api_requests::models::{
-    GatewayCoreStatusResponse, MixnodeCoreStatusResponse, MixnodeStatusResponse,
-    RewardEstimationResponse, StakeSaturationResponse,
+    EpochTopologyDiff, EpochTopologySnapshot, GatewayCoreStatusResponse, MixnodeCoreStatusResponse,
+    MixnodeStatusResponse, RewardEstimationResponse, SignedTopologyResponse,
+    StakeSaturationResponse,
 };
 
 #[cfg(feature = "nymd-client")]
@@ -619,6 +620,34 @@ impl ApiClient {
         Ok(self.validator_api.get_gateways().await?)
     }
 
+    pub async fn get_cached_current_epoch(&self) -> Result<Option<Interval>, ValidatorClientError> {
+        Ok(self.validator_api.get_current_epoch().await?)
+    }
+
+    pub async fn get_signed_topology(
+        &self,
+    ) -> Result<SignedTopologyResponse, ValidatorClientError> {
+        Ok(self.validator_api.get_signed_topology().await?)
+    }
+
+    pub async fn get_epoch_topology(
+        &self,
+        epoch_id: EpochId,
+    ) -> Result<EpochTopologySnapshot, ValidatorClientError> {
+        Ok(self.validator_api.get_epoch_topology(epoch_id).await?)
+    }
+
+    pub async fn get_epoch_topology_diff(
+        &self,
+        epoch_id: EpochId,
+        since: Option<EpochId>,
+    ) -> Result<EpochTopologyDiff, ValidatorClientError> {
+        Ok(self
+            .validator_api
+            .get_epoch_topology_diff(epoch_id, since)
+            .await?)
+    }
+
     pub async fn get_gateway_core_status_count(
         &self,
         identity: IdentityKeyRef<'_>,
@@ -11,7 +11,7 @@ use cosmrs::AccountId;
 use mixnet_contract_common::mixnode::{MixNodeConfigUpdate, MixNodeCostParams};
 use mixnet_contract_common::reward_params::{IntervalRewardingParamsUpdate, Performance};
 use mixnet_contract_common::{
-    ContractStateParams, ExecuteMsg as MixnetExecuteMsg, Gateway, MixId, MixNode,
+    ContractStateParams, ExecuteMsg as MixnetExecuteMsg, Gateway, MixId, MixNode, SphinxKey,
 };
 
 #[async_trait]
@@ -260,6 +260,19 @@ pub trait MixnetSigningClient {
         .await
     }
 
+    async fn announce_mixnode_sphinx_key(
+        &self,
+        sphinx_key: SphinxKey,
+        fee: Option<Fee>,
+    ) -> Result<ExecuteResult, NymdError> {
+        self.execute_mixnet_contract(
+            fee,
+            MixnetExecuteMsg::AnnounceMixnodeSphinxKey { sphinx_key },
+            vec![],
+        )
+        .await
+    }
+
     // gateway-related:
 
     async fn bond_gateway(
@@ -305,6 +318,19 @@ pub trait MixnetSigningClient {
             .await
     }
 
+    async fn announce_gateway_sphinx_key(
+        &self,
+        sphinx_key: SphinxKey,
+        fee: Option<Fee>,
+    ) -> Result<ExecuteResult, NymdError> {
+        self.execute_mixnet_contract(
+            fee,
+            MixnetExecuteMsg::AnnounceGatewaySphinxKey { sphinx_key },
+            vec![],
+        )
+        .await
+    }
+
     async fn unbond_gateway_on_behalf(
         &self,
         owner: AccountId,
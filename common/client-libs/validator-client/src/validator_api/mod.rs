@@ -4,7 +4,7 @@
 use crate::validator_api::error::ValidatorAPIError;
 use crate::validator_api::routes::{CORE_STATUS_COUNT, SINCE_ARG};
 use mixnet_contract_common::mixnode::MixNodeDetails;
-use mixnet_contract_common::{GatewayBond, IdentityKeyRef, MixId};
+use mixnet_contract_common::{EpochId, GatewayBond, IdentityKeyRef, Interval, MixId};
 use serde::{Deserialize, Serialize};
 use url::Url;
 use validator_api_requests::coconut::{
@@ -12,8 +12,9 @@ use validator_api_requests::coconut::{
     VerifyCredentialBody, VerifyCredentialResponse,
 };
 use validator_api_requests::models::{
-    GatewayCoreStatusResponse, InclusionProbabilityResponse, MixNodeBondAnnotated,
-    MixnodeCoreStatusResponse, MixnodeStatusResponse, RewardEstimationResponse,
+    EpochTopologyDiff, EpochTopologySnapshot, GatewayCoreStatusResponse,
+    InclusionProbabilityResponse, MixNodeBondAnnotated, MixnodeCoreStatusResponse,
+    MixnodeStatusResponse, RewardEstimationResponse, SignedTopologyResponse,
     StakeSaturationResponse, UptimeResponse,
 };
 
@@ -104,6 +105,58 @@ impl Client {
             .await
     }
 
+    pub async fn get_current_epoch(&self) -> Result<Option<Interval>, ValidatorAPIError> {
+        self.query_validator_api(
+            &[routes::API_VERSION, routes::EPOCH, routes::CURRENT],
+            NO_PARAMS,
+        )
+        .await
+    }
+
+    pub async fn get_signed_topology(&self) -> Result<SignedTopologyResponse, ValidatorAPIError> {
+        self.query_validator_api(
+            &[routes::API_VERSION, routes::TOPOLOGY, routes::SIGNED],
+            NO_PARAMS,
+        )
+        .await
+    }
+
+    pub async fn get_epoch_topology(
+        &self,
+        epoch_id: EpochId,
+    ) -> Result<EpochTopologySnapshot, ValidatorAPIError> {
+        self.query_validator_api(
+            &[
+                routes::API_VERSION,
+                routes::EPOCH,
+                &epoch_id.to_string(),
+                routes::TOPOLOGY,
+            ],
+            NO_PARAMS,
+        )
+        .await
+    }
+
+    pub async fn get_epoch_topology_diff(
+        &self,
+        epoch_id: EpochId,
+        since: Option<EpochId>,
+    ) -> Result<EpochTopologyDiff, ValidatorAPIError> {
+        let path = [
+            routes::API_VERSION,
+            routes::EPOCH,
+            &epoch_id.to_string(),
+            routes::TOPOLOGY,
+            routes::DIFF,
+        ];
+        if let Some(since) = since {
+            self.query_validator_api(&path, &[(SINCE_ARG, since.to_string())])
+                .await
+        } else {
+            self.query_validator_api(&path, NO_PARAMS).await
+        }
+    }
+
     pub async fn get_active_mixnodes(&self) -> Result<Vec<MixNodeDetails>, ValidatorAPIError> {
         self.query_validator_api(
             &[routes::API_VERSION, routes::MIXNODES, routes::ACTIVE],
@@ -11,6 +11,12 @@ pub const DETAILED: &str = "detailed";
 pub const ACTIVE: &str = "active";
 pub const REWARDED: &str = "rewarded";
 
+pub const EPOCH: &str = "epoch";
+pub const CURRENT: &str = "current";
+pub const TOPOLOGY: &str = "topology";
+pub const DIFF: &str = "diff";
+pub const SIGNED: &str = "signed";
+
 pub const COCONUT_ROUTES: &str = "coconut";
 pub const BANDWIDTH: &str = "bandwidth";
 
@@ -0,0 +1,25 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::context::SigningClient;
+use clap::Parser;
+use log::info;
+use validator_client::nymd::traits::MixnetSigningClient;
+
+#[derive(Debug, Parser)]
+pub struct Args {
+    /// The next sphinx key of the gateway that is going to become valid from the following epoch
+    #[clap(long)]
+    pub sphinx_key: String,
+}
+
+pub async fn announce_sphinx_key(args: Args, client: SigningClient) {
+    info!("Announcing the next gateway sphinx key!");
+
+    let res = client
+        .announce_gateway_sphinx_key(args.sphinx_key, None)
+        .await
+        .expect("failed to announce the sphinx key!");
+
+    info!("Announcement result: {:?}", res)
+}
@@ -3,6 +3,7 @@
 
 use clap::{Args, Subcommand};
 
+pub mod announce_sphinx_key;
 pub mod bond_gateway;
 pub mod unbond_gateway;
 pub mod vesting_bond_gateway;
@@ -25,4 +26,6 @@ pub enum MixnetOperatorsGatewayCommands {
     VestingBond(vesting_bond_gateway::Args),
     /// Unbound from a gateway (when originally using locked tokens)
     VestingUnbound(vesting_unbond_gateway::Args),
+    /// Announce the next sphinx key of the gateway
+    AnnounceSphinxKey(announce_sphinx_key::Args),
 }
@@ -3,6 +3,7 @@
 
 use clap::{Args, Subcommand};
 
+pub mod announce_sphinx_key;
 pub mod decode_mixnode_key;
 
 #[derive(Debug, Args)]
@@ -16,4 +17,6 @@ pub struct MixnetOperatorsMixnodeKeys {
 pub enum MixnetOperatorsMixnodeKeysCommands {
     /// Decode a mixnode key
     DecodeMixnodeKey(decode_mixnode_key::Args),
+    /// Announce the next sphinx key of the mixnode
+    AnnounceSphinxKey(announce_sphinx_key::Args),
 }
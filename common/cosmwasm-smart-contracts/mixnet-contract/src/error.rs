@@ -49,6 +49,9 @@ pub enum MixnetContractError {
     #[error("Gateway with this identity already exists. Its owner is {owner}")]
     DuplicateGateway { owner: Addr },
 
+    #[error("The provided sphinx key is already used by another node")]
+    DuplicateSphinxKey,
+
     #[error("Unauthorized")]
     Unauthorized,
 
@@ -73,6 +76,9 @@ pub enum MixnetContractError {
     #[error("Failed to recover ed25519 signature from its base58 representation - {0}")]
     MalformedEd25519Signature(String),
 
+    #[error("Failed to recover x25519 public key from its base58 representation - {0}")]
+    MalformedX25519SphinxKey(String),
+
     #[error("Provided ed25519 signature did not verify correctly")]
     InvalidEd25519Signature,
 
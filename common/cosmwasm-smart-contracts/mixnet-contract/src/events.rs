@@ -4,7 +4,9 @@
 use crate::mixnode::{MixNodeConfigUpdate, MixNodeCostParams};
 use crate::reward_params::{IntervalRewardParams, IntervalRewardingParamsUpdate};
 use crate::rewarding::RewardDistribution;
-use crate::{BlockHeight, ContractStateParams, IdentityKeyRef, Interval, Layer, MixId};
+use crate::{
+    BlockHeight, ContractStateParams, IdentityKeyRef, Interval, Layer, MixId, PendingSphinxKey,
+};
 pub use contracts_common::events::*;
 use cosmwasm_std::{Addr, Coin, Decimal, Event};
 
@@ -17,6 +19,8 @@ pub enum MixnetEventType {
     PendingMixnodeUnbonding,
     MixnodeUnbonding,
     MixnodeConfigUpdate,
+    MixnodeSphinxKeyAnnouncement,
+    GatewaySphinxKeyAnnouncement,
     PendingMixnodeCostParamsUpdate,
     MixnodeCostParamsUpdate,
     MixnodeRewarding,
@@ -55,6 +59,8 @@ impl ToString for MixnetEventType {
             MixnetEventType::GatewayUnbonding => "gateway_unbonding",
             MixnetEventType::PendingMixnodeUnbonding => "pending_mixnode_unbonding",
             MixnetEventType::MixnodeConfigUpdate => "mixnode_config_update",
+            MixnetEventType::MixnodeSphinxKeyAnnouncement => "mixnode_sphinx_key_announcement",
+            MixnetEventType::GatewaySphinxKeyAnnouncement => "gateway_sphinx_key_announcement",
             MixnetEventType::MixnodeUnbonding => "mixnode_unbonding",
             MixnetEventType::PendingMixnodeCostParamsUpdate => "pending_mixnode_cost_params_update",
             MixnetEventType::MixnodeCostParamsUpdate => "mixnode_cost_params_update",
@@ -118,6 +124,10 @@ pub const NEW_REWARDING_VALIDATOR_ADDRESS_KEY: &str = "new_rewarding_validator_a
 pub const UPDATED_MIXNODE_CONFIG_KEY: &str = "updated_mixnode_config";
 pub const UPDATED_MIXNODE_COST_PARAMS_KEY: &str = "updated_mixnode_cost_params";
 
+// sphinx key rotation
+pub const NEW_SPHINX_KEY_KEY: &str = "new_sphinx_key";
+pub const VALID_FROM_EPOCH_KEY: &str = "valid_from_epoch";
+
 // rewarding
 pub const INTERVAL_KEY: &str = "interval_details";
 pub const OPERATOR_REWARD_KEY: &str = "operator_reward";
@@ -362,6 +372,36 @@ pub fn new_mixnode_config_update_event(
         .add_attribute(UPDATED_MIXNODE_CONFIG_KEY, update.to_inline_json())
 }
 
+pub fn new_mixnode_sphinx_key_announcement_event(
+    mix_id: MixId,
+    owner: &Addr,
+    pending_key: &PendingSphinxKey,
+) -> Event {
+    Event::new(MixnetEventType::MixnodeSphinxKeyAnnouncement)
+        .add_attribute(MIX_ID_KEY, mix_id.to_string())
+        .add_attribute(OWNER_KEY, owner)
+        .add_attribute(NEW_SPHINX_KEY_KEY, &pending_key.sphinx_key)
+        .add_attribute(
+            VALID_FROM_EPOCH_KEY,
+            pending_key.valid_from_epoch.to_string(),
+        )
+}
+
+pub fn new_gateway_sphinx_key_announcement_event(
+    owner: &Addr,
+    identity: IdentityKeyRef<'_>,
+    pending_key: &PendingSphinxKey,
+) -> Event {
+    Event::new(MixnetEventType::GatewaySphinxKeyAnnouncement)
+        .add_attribute(OWNER_KEY, owner)
+        .add_attribute(NODE_IDENTITY_KEY, identity)
+        .add_attribute(NEW_SPHINX_KEY_KEY, &pending_key.sphinx_key)
+        .add_attribute(
+            VALID_FROM_EPOCH_KEY,
+            pending_key.valid_from_epoch.to_string(),
+        )
+}
+
 pub fn new_mixnode_pending_cost_params_update_event(
     mix_id: MixId,
     owner: &Addr,
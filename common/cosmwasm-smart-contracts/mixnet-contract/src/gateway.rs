@@ -4,7 +4,7 @@
 // due to code generated by JsonSchema
 #![allow(clippy::field_reassign_with_default)]
 
-use crate::{IdentityKey, SphinxKey};
+use crate::{EpochId, IdentityKey, PendingSphinxKey, SphinxKey};
 use cosmwasm_std::{Addr, Coin};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -30,6 +30,11 @@ pub struct GatewayBond {
     pub block_height: u64,
     pub gateway: Gateway,
     pub proxy: Option<Addr>,
+
+    /// Sphinx key announced by the gateway that is going to replace its current key
+    /// at the specified epoch.
+    #[serde(default)]
+    pub pending_sphinx_key: Option<PendingSphinxKey>,
 }
 
 impl GatewayBond {
@@ -46,6 +51,7 @@ impl GatewayBond {
             block_height,
             gateway,
             proxy,
+            pending_sphinx_key: None,
         }
     }
 
@@ -53,6 +59,14 @@ impl GatewayBond {
         &self.gateway.identity_key
     }
 
+    /// Returns the sphinx key that should be used by the gateway in the specified epoch.
+    pub fn sphinx_key_for_epoch(&self, epoch: EpochId) -> &SphinxKey {
+        match &self.pending_sphinx_key {
+            Some(pending) if pending.is_active(epoch) => &pending.sphinx_key,
+            _ => &self.gateway.sphinx_key,
+        }
+    }
+
     pub fn pledge_amount(&self) -> Coin {
         self.pledge_amount.clone()
     }
@@ -174,6 +188,7 @@ mod tests {
             block_height: 100,
             gateway: gateway_fixture(),
             proxy: None,
+            pending_sphinx_key: None,
         };
 
         let gate2 = GatewayBond {
@@ -182,6 +197,7 @@ mod tests {
             block_height: 120,
             gateway: gateway_fixture(),
             proxy: None,
+            pending_sphinx_key: None,
         };
 
         let gate3 = GatewayBond {
@@ -190,6 +206,7 @@ mod tests {
             block_height: 120,
             gateway: gateway_fixture(),
             proxy: None,
+            pending_sphinx_key: None,
         };
 
         let gate4 = GatewayBond {
@@ -198,6 +215,7 @@ mod tests {
             block_height: 120,
             gateway: gateway_fixture(),
             proxy: None,
+            pending_sphinx_key: None,
         };
 
         let gate5 = GatewayBond {
@@ -206,6 +224,7 @@ mod tests {
             block_height: 120,
             gateway: gateway_fixture(),
             proxy: None,
+            pending_sphinx_key: None,
         };
 
         // summary:
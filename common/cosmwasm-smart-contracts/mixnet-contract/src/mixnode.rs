@@ -10,7 +10,7 @@ use crate::helpers::IntoBaseDecimal;
 use crate::reward_params::{NodeRewardParams, RewardingParams};
 use crate::rewarding::helpers::truncate_reward;
 use crate::rewarding::RewardDistribution;
-use crate::{Delegation, EpochId, IdentityKey, MixId, Percent, SphinxKey};
+use crate::{Delegation, EpochId, IdentityKey, MixId, PendingSphinxKey, Percent, SphinxKey};
 use cosmwasm_std::{Addr, Coin, Decimal, StdResult, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -458,6 +458,11 @@ pub struct MixNodeBond {
     /// Flag to indicate whether this node is in the process of unbonding,
     /// that will conclude upon the epoch finishing.
     pub is_unbonding: bool,
+
+    /// Sphinx key announced by the node that is going to replace its current key
+    /// at the specified epoch.
+    #[serde(default)]
+    pub pending_sphinx_key: Option<PendingSphinxKey>,
 }
 
 impl MixNodeBond {
@@ -479,6 +484,7 @@ impl MixNodeBond {
             proxy,
             bonding_height,
             is_unbonding: false,
+            pending_sphinx_key: None,
         }
     }
 
@@ -486,6 +492,14 @@ impl MixNodeBond {
         &self.mix_node.identity_key
     }
 
+    /// Returns the sphinx key that should be used by the node in the specified epoch.
+    pub fn sphinx_key_for_epoch(&self, epoch: EpochId) -> &SphinxKey {
+        match &self.pending_sphinx_key {
+            Some(pending) if pending.is_active(epoch) => &pending.sphinx_key,
+            _ => &self.mix_node.sphinx_key,
+        }
+    }
+
     pub fn original_pledge(&self) -> &Coin {
         &self.original_pledge
     }
@@ -9,7 +9,7 @@ use crate::reward_params::{
     IntervalRewardParams, IntervalRewardingParamsUpdate, Performance, RewardingParams,
 };
 use crate::{delegation, ContractStateParams, MixId, Percent};
-use crate::{Gateway, IdentityKey, MixNode};
+use crate::{Gateway, IdentityKey, MixNode, SphinxKey};
 use cosmwasm_std::Decimal;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -129,6 +129,9 @@ pub enum ExecuteMsg {
         new_config: MixNodeConfigUpdate,
         owner: String,
     },
+    AnnounceMixnodeSphinxKey {
+        sphinx_key: SphinxKey,
+    },
 
     // gateway-related:
     BondGateway {
@@ -144,6 +147,9 @@ pub enum ExecuteMsg {
     UnbondGatewayOnBehalf {
         owner: String,
     },
+    AnnounceGatewaySphinxKey {
+        sphinx_key: SphinxKey,
+    },
 
     // delegation-related:
     DelegateToMixnode {
@@ -231,6 +237,9 @@ impl ExecuteMsg {
             ExecuteMsg::UpdateMixnodeConfigOnBehalf { .. } => {
                 "updating mixnode configuration on behalf".into()
             }
+            ExecuteMsg::AnnounceMixnodeSphinxKey { sphinx_key } => {
+                format!("announcing new mixnode sphinx key {}", sphinx_key)
+            }
             ExecuteMsg::BondGateway { gateway, .. } => {
                 format!("bonding gateway {}", gateway.identity_key)
             }
@@ -239,6 +248,9 @@ impl ExecuteMsg {
             }
             ExecuteMsg::UnbondGateway { .. } => "unbonding gateway".into(),
             ExecuteMsg::UnbondGatewayOnBehalf { .. } => "unbonding gateway on behalf".into(),
+            ExecuteMsg::AnnounceGatewaySphinxKey { sphinx_key } => {
+                format!("announcing new gateway sphinx key {}", sphinx_key)
+            }
             ExecuteMsg::DelegateToMixnode { mix_id } => format!("delegating to mixnode {}", mix_id),
             ExecuteMsg::DelegateToMixnodeOnBehalf { mix_id, .. } => {
                 format!("delegating to mixnode {} on behalf", mix_id)
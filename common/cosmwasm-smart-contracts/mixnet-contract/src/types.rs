@@ -105,6 +105,29 @@ where
     }
 }
 
+/// Sphinx key announced by a node that is going to replace its current key.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct PendingSphinxKey {
+    /// Base58-encoded x25519 public key used for sphinx key derivation.
+    pub sphinx_key: SphinxKey,
+
+    /// The first (absolute) epoch in which the key is meant to be used.
+    pub valid_from_epoch: EpochId,
+}
+
+impl PendingSphinxKey {
+    pub fn new(sphinx_key: SphinxKey, valid_from_epoch: EpochId) -> Self {
+        PendingSphinxKey {
+            sphinx_key,
+            valid_from_epoch,
+        }
+    }
+
+    pub fn is_active(&self, epoch: EpochId) -> bool {
+        epoch >= self.valid_from_epoch
+    }
+}
+
 #[derive(Debug, Default, Serialize, Deserialize, Copy, Clone, Eq, PartialEq)]
 pub struct LayerDistribution {
     pub layer1: u64,
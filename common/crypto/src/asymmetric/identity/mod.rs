@@ -1,6 +1,7 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::asymmetric::encryption;
 pub use ed25519_dalek::ed25519::signature::Signature as SignatureTrait;
 pub use ed25519_dalek::SignatureError;
 pub use ed25519_dalek::{Verifier, PUBLIC_KEY_LENGTH, SECRET_KEY_LENGTH, SIGNATURE_LENGTH};
@@ -8,6 +9,7 @@ use nymsphinx_types::{DestinationAddressBytes, DESTINATION_ADDRESS_LENGTH};
 use pemstore::traits::{PemStorableKey, PemStorableKeyPair};
 #[cfg(feature = "rand")]
 use rand::{CryptoRng, RngCore};
+use sha2::{Digest, Sha512};
 use std::fmt::{self, Display, Formatter};
 
 #[cfg(feature = "serde")]
@@ -141,6 +143,18 @@ impl PublicKey {
     pub fn verify(&self, message: &[u8], signature: &Signature) -> Result<(), SignatureError> {
         self.0.verify(message, &signature.0)
     }
+
+    /// Converts this ed25519 public key into its x25519 equivalent, i.e. the Montgomery form of the
+    /// same curve point, so that it could be used for Diffie-Hellman key exchange with the holder
+    /// of the corresponding private key (converted with [`PrivateKey::to_x25519`]).
+    pub fn to_x25519(&self) -> encryption::PublicKey {
+        let edwards_point = curve25519_dalek::edwards::CompressedEdwardsY(self.to_bytes())
+            .decompress()
+            // this can't fail as the key has already been validated upon construction
+            .expect("ed25519 public key is not a valid curve point");
+        encryption::PublicKey::from_bytes(edwards_point.to_montgomery().as_bytes())
+            .expect("montgomery point has invalid length")
+    }
 }
 
 #[cfg(feature = "serde")]
@@ -228,6 +242,14 @@ impl PrivateKey {
         let signature = bs58::encode(signature_bytes).into_string();
         signature
     }
+
+    /// Converts this ed25519 private key into its x25519 equivalent, i.e. the (unclamped) scalar
+    /// derived from the first half of the SHA-512 digest of the secret, as per RFC 8032.
+    pub fn to_x25519(&self) -> encryption::PrivateKey {
+        let digest = Sha512::digest(&self.to_bytes());
+        encryption::PrivateKey::from_bytes(&digest[..encryption::PRIVATE_KEY_SIZE])
+            .expect("sha512 digest is shorter than x25519 private key")
+    }
 }
 
 #[cfg(feature = "serde")]
@@ -310,3 +332,35 @@ impl<'d> Deserialize<'d> for Signature {
         Signature::from_bytes(bytes.as_ref()).map_err(SerdeError::custom)
     }
 }
+
+#[cfg(test)]
+mod x25519_conversion {
+    use super::*;
+
+    #[test]
+    fn converted_keys_form_valid_x25519_keypair() {
+        let mut rng = rand::rngs::OsRng;
+        let keys = KeyPair::new(&mut rng);
+
+        let x25519_private = keys.private_key().to_x25519();
+        let x25519_public = keys.public_key().to_x25519();
+        assert_eq!(encryption::PublicKey::from(&x25519_private), x25519_public);
+    }
+
+    #[test]
+    fn converted_keys_agree_on_shared_secret() {
+        let mut rng = rand::rngs::OsRng;
+        let keys1 = KeyPair::new(&mut rng);
+        let keys2 = KeyPair::new(&mut rng);
+
+        let secret1 = keys1
+            .private_key()
+            .to_x25519()
+            .diffie_hellman(&keys2.public_key().to_x25519());
+        let secret2 = keys2
+            .private_key()
+            .to_x25519()
+            .diffie_hellman(&keys1.public_key().to_x25519());
+        assert_eq!(secret1, secret2);
+    }
+}
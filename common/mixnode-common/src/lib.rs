@@ -1,5 +1,7 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod link_peers;
 pub mod packet_processor;
+pub mod sphinx_key_rotation;
 pub mod verloc;
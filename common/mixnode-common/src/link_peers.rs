@@ -0,0 +1,114 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Keeps the link keys of all nodes in the network up to date, so that the encrypted links
+//! could be established with (and accepted from) any of them.
+
+use crypto::asymmetric::identity;
+use log::*;
+use mixnet_client::LinkPeers;
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::time::Duration;
+use task::ShutdownListener;
+use tokio::time::sleep;
+use url::Url;
+
+fn resolve_peer(
+    host: &str,
+    mix_port: u16,
+    identity_key: &str,
+) -> Option<(SocketAddr, identity::PublicKey)> {
+    let identity = identity::PublicKey::from_base58_string(identity_key).ok()?;
+    let address = (host, mix_port).to_socket_addrs().ok()?.next()?;
+    Some((address, identity))
+}
+
+pub struct LinkPeersRefresher {
+    peers: LinkPeers,
+    validator_apis: Vec<Url>,
+    refresh_interval: Duration,
+    shutdown: ShutdownListener,
+}
+
+impl LinkPeersRefresher {
+    pub fn new(
+        peers: LinkPeers,
+        validator_apis: Vec<Url>,
+        refresh_interval: Duration,
+        shutdown: ShutdownListener,
+    ) -> Self {
+        LinkPeersRefresher {
+            peers,
+            validator_apis,
+            refresh_interval,
+            shutdown,
+        }
+    }
+
+    fn random_api_client(&self) -> validator_client::ApiClient {
+        let validator_api = self
+            .validator_apis
+            .choose(&mut thread_rng())
+            .expect("The list of validator apis is empty");
+
+        validator_client::ApiClient::new(validator_api.clone())
+    }
+
+    /// Obtains the current network topology and replaces the known peers with its nodes.
+    pub async fn refresh(&self) {
+        let validator_client = self.random_api_client();
+
+        let mixnodes = match validator_client.get_cached_mixnodes().await {
+            Ok(mixnodes) => mixnodes,
+            Err(err) => {
+                warn!("failed to obtain the list of mixnodes - {}", err);
+                return;
+            }
+        };
+        let gateways = match validator_client.get_cached_gateways().await {
+            Ok(gateways) => gateways,
+            Err(err) => {
+                warn!("failed to obtain the list of gateways - {}", err);
+                return;
+            }
+        };
+
+        // TODO: similarly to verloc, this does name resolution of every node, which might take
+        // a while if a lot of them are misconfigured
+        let mix_peers = mixnodes.into_iter().filter_map(|details| {
+            let mix_node = details.bond_information.mix_node;
+            resolve_peer(&mix_node.host, mix_node.mix_port, &mix_node.identity_key)
+        });
+        let gateway_peers = gateways.into_iter().filter_map(|bond| {
+            resolve_peer(
+                &bond.gateway.host,
+                bond.gateway.mix_port,
+                &bond.gateway.identity_key,
+            )
+        });
+
+        self.peers.update(mix_peers.chain(gateway_peers));
+        debug!("refreshed link keys of {} nodes", self.peers.len());
+    }
+
+    pub async fn run(&mut self) {
+        while !self.shutdown.is_shutdown() {
+            tokio::select! {
+                _ = sleep(self.refresh_interval) => self.refresh().await,
+                _ = self.shutdown.recv() => {
+                    trace!("LinkPeersRefresher: Received shutdown");
+                }
+            }
+        }
+
+        trace!("LinkPeersRefresher: Exiting");
+    }
+
+    /// Starts refreshing the peers in the background. Note that the first refresh happens only
+    /// after the interval elapses, so `refresh` should be called beforehand.
+    pub fn start(mut self) {
+        tokio::spawn(async move { self.run().await });
+    }
+}
@@ -12,6 +12,7 @@ pub enum MixProcessingError {
     InvalidHopAddress(NymNodeRoutingAddressError),
     NoSurbAckInFinalHop,
     MalformedSurbAck(SurbAckRecoveryError),
+    ReplayedPacket,
 
     ReceivedOldTypeVpnPacket,
 }
@@ -56,6 +57,9 @@ impl Display for MixProcessingError {
             MixProcessingError::MalformedSurbAck(surb_ack_err) => {
                 write!(f, "Malformed SURBAck - {:?}", surb_ack_err)
             }
+            MixProcessingError::ReplayedPacket => {
+                write!(f, "Received a replayed sphinx packet")
+            }
             MixProcessingError::ReceivedOldTypeVpnPacket => {
                 write!(f, "Received an old-type unsafe 'VPN' mode packet")
             }
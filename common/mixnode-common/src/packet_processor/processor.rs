@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::packet_processor::error::MixProcessingError;
+use crate::packet_processor::replay::{replay_tag, ReplayCache};
 use log::*;
 use nymsphinx_acknowledgements::surb_ack::SurbAck;
 use nymsphinx_addressing::nodes::NymNodeRoutingAddress;
@@ -13,7 +14,7 @@ use nymsphinx_types::{
     ProcessedPacket, SphinxPacket,
 };
 use std::convert::TryFrom;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 type ForwardAck = MixPacket;
 
@@ -31,29 +32,185 @@ pub enum MixProcessingResult {
     FinalHop(ProcessedFinalHop),
 }
 
+struct SphinxKeyState {
+    /// Private sphinx key of this node required to unwrap received sphinx packet.
+    sphinx_key: PrivateKey,
+
+    /// Tags of all packets processed with this sphinx key used for rejecting replays.
+    replay_cache: ReplayCache,
+}
+
+impl SphinxKeyState {
+    fn new(sphinx_key: PrivateKey, replay_cache: ReplayCache) -> Arc<Self> {
+        Arc::new(SphinxKeyState {
+            sphinx_key,
+            replay_cache,
+        })
+    }
+}
+
+struct SphinxKeys {
+    current: Arc<SphinxKeyState>,
+
+    /// The announced key that is going to be used after the next rotation. It is accepted ahead
+    /// of time so that packets constructed by clients that have already switched to it are
+    /// processed regardless of when exactly the node itself performs the rotation.
+    next: Option<Arc<SphinxKeyState>>,
+
+    /// The key that was used before the last rotation. It is kept around during the grace period
+    /// so that packets constructed by clients with slightly outdated topology are still processed.
+    previous: Option<Arc<SphinxKeyState>>,
+}
+
 #[derive(Clone)]
 pub struct SphinxPacketProcessor {
-    /// Private sphinx key of this node required to unwrap received sphinx packet.
-    sphinx_key: Arc<PrivateKey>,
+    keys: Arc<RwLock<SphinxKeys>>,
 }
 
 impl SphinxPacketProcessor {
-    /// Creates new instance of `CachedPacketProcessor`
+    /// Creates new instance of `SphinxPacketProcessor`
     pub fn new(sphinx_key: PrivateKey) -> Self {
+        Self::new_with_replay_cache(sphinx_key, ReplayCache::default())
+    }
+
+    /// Creates new instance of `SphinxPacketProcessor` using the provided replay cache.
+    pub fn new_with_replay_cache(sphinx_key: PrivateKey, replay_cache: ReplayCache) -> Self {
         SphinxPacketProcessor {
-            sphinx_key: Arc::new(sphinx_key),
+            keys: Arc::new(RwLock::new(SphinxKeys {
+                current: SphinxKeyState::new(sphinx_key, replay_cache),
+                next: None,
+                previous: None,
+            })),
         }
     }
 
+    /// Creates new instance of `SphinxPacketProcessor` that is in the middle of the key rotation,
+    /// i.e. it still accepts packets created for the previous sphinx key.
+    pub fn new_with_previous_key(sphinx_key: PrivateKey, previous_key: PrivateKey) -> Self {
+        let processor = Self::new(sphinx_key);
+        processor.write_keys().previous =
+            Some(SphinxKeyState::new(previous_key, ReplayCache::default()));
+        processor
+    }
+
+    fn read_keys(&self) -> RwLockReadGuard<'_, SphinxKeys> {
+        self.keys.read().expect("sphinx keys lock got poisoned")
+    }
+
+    fn write_keys(&self) -> RwLockWriteGuard<'_, SphinxKeys> {
+        self.keys.write().expect("sphinx keys lock got poisoned")
+    }
+
+    /// Starts accepting packets created for the announced sphinx key before it becomes
+    /// the current one. Any key that was previously accepted this way is replaced.
+    pub fn accept_next_key(&self, next_key: PrivateKey) {
+        let mut keys = self.write_keys();
+        let fresh_cache = keys.current.replay_cache.empty_copy();
+        keys.next = Some(SphinxKeyState::new(next_key, fresh_cache));
+    }
+
+    /// Stops accepting packets created for the key passed to `accept_next_key`.
+    pub fn discard_next_key(&self) {
+        self.write_keys().next = None;
+    }
+
+    /// Replaces the current sphinx key with the new one. The old key is still going to be accepted
+    /// until `retire_previous_key` is called. Any key that was previously kept for the grace
+    /// period is dropped alongside its replay cache.
+    pub fn rotate_key(&self, new_key: PrivateKey) {
+        let mut keys = self.write_keys();
+        let fresh_cache = keys.current.replay_cache.empty_copy();
+        Self::replace_current_key(&mut keys, SphinxKeyState::new(new_key, fresh_cache));
+    }
+
+    /// Works like `rotate_key`, but the new key is the one passed to `accept_next_key`.
+    /// It keeps its replay cache, so the packets processed before the rotation can't be replayed
+    /// after it. Returns `false` (without rotating) if no key has been accepted ahead of time.
+    pub fn rotate_to_next_key(&self) -> bool {
+        let mut keys = self.write_keys();
+        match keys.next.take() {
+            Some(next) => {
+                Self::replace_current_key(&mut keys, next);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn replace_current_key(keys: &mut SphinxKeys, new_state: Arc<SphinxKeyState>) {
+        let old = std::mem::replace(&mut keys.current, new_state);
+        keys.previous = Some(old);
+    }
+
+    /// Stops accepting packets created for the sphinx key used before the last rotation.
+    pub fn retire_previous_key(&self) {
+        self.write_keys().previous = None;
+    }
+
+    /// Checks whether packets created for the pre-rotation sphinx key are still accepted.
+    pub fn has_previous_key(&self) -> bool {
+        self.read_keys().previous.is_some()
+    }
+
     /// Performs a fresh sphinx unwrapping using no cache.
     fn perform_initial_sphinx_packet_processing(
         &self,
         packet: SphinxPacket,
     ) -> Result<ProcessedPacket, MixProcessingError> {
-        packet.process(&self.sphinx_key).map_err(|err| {
+        // don't hold the lock for the duration of the expensive unwrapping
+        let (current, alternatives) = {
+            let keys = self.read_keys();
+            (
+                Arc::clone(&keys.current),
+                [keys.next.clone(), keys.previous.clone()],
+            )
+        };
+
+        if alternatives.iter().all(Option::is_none) {
+            return Self::process_with_key(&current, packet);
+        }
+
+        // unfortunately the sphinx processing consumes the packet, so in the (rare) case of
+        // having to try multiple keys, we have to keep its copy around
+        let packet_bytes = packet.to_bytes();
+        let mut result = Self::process_with_key(&current, packet);
+        for alternative in alternatives.into_iter().flatten() {
+            if !matches!(result, Err(MixProcessingError::SphinxProcessingError(_))) {
+                break;
+            }
+            let packet = SphinxPacket::from_bytes(&packet_bytes)
+                .map_err(MixProcessingError::SphinxProcessingError)?;
+            trace!("Attempting to unwrap the packet with the next or pre-rotation sphinx key");
+            result = Self::process_with_key(&alternative, packet);
+        }
+        result
+    }
+
+    fn process_with_key(
+        key_state: &SphinxKeyState,
+        packet: SphinxPacket,
+    ) -> Result<ProcessedPacket, MixProcessingError> {
+        // cheap check to avoid the expensive unwrapping of packets we have already seen
+        let tag = replay_tag(&packet);
+        if key_state.replay_cache.contains(&tag) {
+            debug!("Received a replayed sphinx packet");
+            return Err(MixProcessingError::ReplayedPacket);
+        }
+
+        let processed = packet.process(&key_state.sphinx_key).map_err(|err| {
             debug!("Failed to unwrap Sphinx packet: {:?}", err);
             MixProcessingError::SphinxProcessingError(err)
-        })
+        })?;
+
+        // only remember tags of packets that were valid, otherwise anyone could fill up
+        // the cache with garbage. Note that the insertion is done atomically, so if the same
+        // packet was concurrently processed by another connection, only one of them will succeed
+        if !key_state.replay_cache.insert(tag) {
+            debug!("Received a replayed sphinx packet");
+            return Err(MixProcessingError::ReplayedPacket);
+        }
+
+        Ok(processed)
     }
 
     /// Takes the received framed packet and tries to unwrap it from the sphinx encryption.
@@ -189,13 +346,41 @@ impl SphinxPacketProcessor {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use nymsphinx_types::builder::SphinxPacketBuilder;
     use nymsphinx_types::crypto::keygen;
+    use nymsphinx_types::{
+        Destination, Node, PublicKey, DESTINATION_ADDRESS_LENGTH, IDENTIFIER_LENGTH,
+        NODE_ADDRESS_LENGTH,
+    };
 
     fn fixture() -> SphinxPacketProcessor {
         let local_keys = keygen();
         SphinxPacketProcessor::new(local_keys.0)
     }
 
+    fn make_forward_packet(first_hop_key: &PublicKey) -> SphinxPacket {
+        let node1 = Node::new(
+            NodeAddressBytes::from_bytes([5u8; NODE_ADDRESS_LENGTH]),
+            PublicKey::from(*first_hop_key.as_bytes()),
+        );
+        let node2 = Node::new(
+            NodeAddressBytes::from_bytes([4u8; NODE_ADDRESS_LENGTH]),
+            keygen().1,
+        );
+        let destination = Destination::new(
+            DestinationAddressBytes::from_bytes([3u8; DESTINATION_ADDRESS_LENGTH]),
+            [4u8; IDENTIFIER_LENGTH],
+        );
+        let delays = vec![
+            SphinxDelay::new_from_nanos(42),
+            SphinxDelay::new_from_nanos(42),
+        ];
+        SphinxPacketBuilder::new()
+            .with_payload_size(PacketSize::default().payload_size())
+            .build_packet(b"foomp".to_vec(), &[node1, node2], &destination, &delays)
+            .unwrap()
+    }
+
     #[tokio::test]
     async fn splitting_hop_data_works_for_sufficiently_long_payload() {
         let processor = fixture();
@@ -231,4 +416,148 @@ mod tests {
         assert!(ack.is_none());
         assert_eq!(data, message)
     }
+
+    #[test]
+    fn replayed_packets_are_rejected() {
+        let (private_key, public_key) = keygen();
+        let processor = SphinxPacketProcessor::new(private_key);
+
+        let packet = make_forward_packet(&public_key);
+        let packet_bytes = packet.to_bytes();
+
+        let framed = FramedSphinxPacket::new(packet, Default::default());
+        assert!(matches!(
+            processor.process_received(framed),
+            Ok(MixProcessingResult::ForwardHop(..))
+        ));
+
+        let replayed = SphinxPacket::from_bytes(&packet_bytes).unwrap();
+        let framed = FramedSphinxPacket::new(replayed, Default::default());
+        assert!(matches!(
+            processor.process_received(framed),
+            Err(MixProcessingError::ReplayedPacket)
+        ));
+
+        // but a fresh packet is still fine
+        let fresh = FramedSphinxPacket::new(make_forward_packet(&public_key), Default::default());
+        assert!(processor.process_received(fresh).is_ok());
+    }
+
+    #[test]
+    fn invalid_packets_are_not_remembered() {
+        let processor = fixture();
+
+        // packet created for a different key
+        let packet = make_forward_packet(&keygen().1);
+        let packet_bytes = packet.to_bytes();
+
+        let framed = FramedSphinxPacket::new(packet, Default::default());
+        assert!(matches!(
+            processor.process_received(framed),
+            Err(MixProcessingError::SphinxProcessingError(..))
+        ));
+
+        let resent = SphinxPacket::from_bytes(&packet_bytes).unwrap();
+        let framed = FramedSphinxPacket::new(resent, Default::default());
+        assert!(matches!(
+            processor.process_received(framed),
+            Err(MixProcessingError::SphinxProcessingError(..))
+        ));
+    }
+
+    #[test]
+    fn previous_key_is_accepted_until_retired() {
+        let (old_private, old_public) = keygen();
+        let (new_private, new_public) = keygen();
+        let processor = SphinxPacketProcessor::new(old_private);
+
+        processor.rotate_key(new_private);
+        assert!(processor.has_previous_key());
+
+        let new_packet =
+            FramedSphinxPacket::new(make_forward_packet(&new_public), Default::default());
+        assert!(processor.process_received(new_packet).is_ok());
+
+        let old_packet = make_forward_packet(&old_public);
+        let old_packet_bytes = old_packet.to_bytes();
+        let framed = FramedSphinxPacket::new(old_packet, Default::default());
+        assert!(processor.process_received(framed).is_ok());
+
+        // replays are still detected for the previous key
+        let replayed = SphinxPacket::from_bytes(&old_packet_bytes).unwrap();
+        let framed = FramedSphinxPacket::new(replayed, Default::default());
+        assert!(matches!(
+            processor.process_received(framed),
+            Err(MixProcessingError::ReplayedPacket)
+        ));
+
+        processor.retire_previous_key();
+        assert!(!processor.has_previous_key());
+
+        let old_packet =
+            FramedSphinxPacket::new(make_forward_packet(&old_public), Default::default());
+        assert!(matches!(
+            processor.process_received(old_packet),
+            Err(MixProcessingError::SphinxProcessingError(..))
+        ));
+    }
+
+    #[test]
+    fn next_key_is_accepted_ahead_of_the_rotation() {
+        let (current_private, current_public) = keygen();
+        let (next_private, next_public) = keygen();
+        let processor = SphinxPacketProcessor::new(current_private);
+
+        let next_packet = make_forward_packet(&next_public);
+        let next_packet_bytes = next_packet.to_bytes();
+        let framed = FramedSphinxPacket::new(next_packet, Default::default());
+        assert!(matches!(
+            processor.process_received(framed),
+            Err(MixProcessingError::SphinxProcessingError(..))
+        ));
+
+        processor.accept_next_key(next_private);
+        let framed = FramedSphinxPacket::new(
+            SphinxPacket::from_bytes(&next_packet_bytes).unwrap(),
+            Default::default(),
+        );
+        assert!(processor.process_received(framed).is_ok());
+
+        // the current key is still used
+        let current_packet =
+            FramedSphinxPacket::new(make_forward_packet(&current_public), Default::default());
+        assert!(processor.process_received(current_packet).is_ok());
+
+        // the replays are still detected after the rotation
+        assert!(processor.rotate_to_next_key());
+        assert!(!processor.rotate_to_next_key());
+        let replayed = FramedSphinxPacket::new(
+            SphinxPacket::from_bytes(&next_packet_bytes).unwrap(),
+            Default::default(),
+        );
+        assert!(matches!(
+            processor.process_received(replayed),
+            Err(MixProcessingError::ReplayedPacket)
+        ));
+
+        let fresh = FramedSphinxPacket::new(make_forward_packet(&next_public), Default::default());
+        assert!(processor.process_received(fresh).is_ok());
+    }
+
+    #[test]
+    fn discarded_next_key_is_no_longer_accepted() {
+        let (current_private, _) = keygen();
+        let (next_private, next_public) = keygen();
+        let processor = SphinxPacketProcessor::new(current_private);
+
+        processor.accept_next_key(next_private);
+        processor.discard_next_key();
+
+        let next_packet =
+            FramedSphinxPacket::new(make_forward_packet(&next_public), Default::default());
+        assert!(matches!(
+            processor.process_received(next_packet),
+            Err(MixProcessingError::SphinxProcessingError(..))
+        ));
+    }
 }
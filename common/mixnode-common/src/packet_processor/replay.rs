@@ -0,0 +1,187 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use nymsphinx_types::SphinxPacket;
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Default amount of time covered by a single bucket of the replay cache.
+pub const DEFAULT_REPLAY_BUCKET_DURATION: Duration = Duration::from_secs(10 * 60);
+
+/// Default number of buckets kept by the replay cache, i.e. by default tags are remembered
+/// for (at least) an hour.
+pub const DEFAULT_REPLAY_BUCKETS: usize = 6;
+
+/// Tag uniquely identifying given sphinx packet at this particular hop. It is derived from the
+/// shared secret (i.e. the group element) included in the sphinx header, which is freshly
+/// generated by the sender for every packet and gets blinded at every hop.
+pub type ReplayTag = [u8; 32];
+
+pub fn replay_tag(packet: &SphinxPacket) -> ReplayTag {
+    *packet.header.shared_secret.as_bytes()
+}
+
+/// Time-bucketed set of tags of all the packets seen with the current sphinx key.
+///
+/// Each bucket holds tags of packets received within `bucket_duration` and once there are more
+/// than `max_buckets` of them, the oldest one is dropped. Note that replays older than the
+/// retention window are not going to be detected, so the cache should be paired with sphinx key
+/// rotation, at which point it can be cleared completely, as packets created for the old key
+/// are no longer processable.
+pub struct ReplayCache {
+    bucket_duration: Duration,
+    max_buckets: usize,
+    inner: Mutex<ReplayCacheInner>,
+}
+
+struct ReplayCacheInner {
+    current_bucket_start: Instant,
+
+    /// Buckets of seen tags with the most recent one at the front.
+    buckets: VecDeque<HashSet<ReplayTag>>,
+}
+
+impl Default for ReplayCache {
+    fn default() -> Self {
+        ReplayCache::new(DEFAULT_REPLAY_BUCKET_DURATION, DEFAULT_REPLAY_BUCKETS)
+    }
+}
+
+impl ReplayCache {
+    pub fn new(bucket_duration: Duration, max_buckets: usize) -> Self {
+        assert!(
+            max_buckets > 0,
+            "replay cache must have at least a single bucket"
+        );
+
+        let mut buckets = VecDeque::with_capacity(max_buckets);
+        buckets.push_front(HashSet::new());
+
+        ReplayCache {
+            bucket_duration,
+            max_buckets,
+            inner: Mutex::new(ReplayCacheInner {
+                current_bucket_start: Instant::now(),
+                buckets,
+            }),
+        }
+    }
+
+    /// Creates a new, empty, cache with the same retention parameters.
+    pub fn empty_copy(&self) -> Self {
+        ReplayCache::new(self.bucket_duration, self.max_buckets)
+    }
+
+    fn rotate_buckets(&self, inner: &mut ReplayCacheInner, now: Instant) {
+        let mut elapsed = now.saturating_duration_since(inner.current_bucket_start);
+        if elapsed < self.bucket_duration {
+            return;
+        }
+
+        // push as many empty buckets as there were full periods since the last rotation
+        // (but no more than we'd retain anyway)
+        let mut pushed = 0;
+        while elapsed >= self.bucket_duration && pushed < self.max_buckets {
+            inner.buckets.push_front(HashSet::new());
+            elapsed -= self.bucket_duration;
+            pushed += 1;
+        }
+        inner.buckets.truncate(self.max_buckets);
+        inner.current_bucket_start = now;
+    }
+
+    fn contains_at(&self, tag: &ReplayTag, now: Instant) -> bool {
+        let mut inner = self.inner.lock().expect("replay cache mutex got poisoned");
+        self.rotate_buckets(&mut inner, now);
+        inner.buckets.iter().any(|bucket| bucket.contains(tag))
+    }
+
+    fn insert_at(&self, tag: ReplayTag, now: Instant) -> bool {
+        let mut inner = self.inner.lock().expect("replay cache mutex got poisoned");
+        self.rotate_buckets(&mut inner, now);
+        if inner.buckets.iter().any(|bucket| bucket.contains(&tag)) {
+            return false;
+        }
+
+        // we always have at least a single bucket
+        inner.buckets[0].insert(tag)
+    }
+
+    /// Checks whether the tag has already been seen within the retention window.
+    pub fn contains(&self, tag: &ReplayTag) -> bool {
+        self.contains_at(tag, Instant::now())
+    }
+
+    /// Attempts to insert the tag into the cache. Returns `false` if it was already present,
+    /// i.e. the packet is a replay.
+    pub fn insert(&self, tag: ReplayTag) -> bool {
+        self.insert_at(tag, Instant::now())
+    }
+
+    /// Removes all the stored tags.
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().expect("replay cache mutex got poisoned");
+        inner.buckets.clear();
+        inner.buckets.push_front(HashSet::new());
+        inner.current_bucket_start = Instant::now();
+    }
+
+    /// Returns the total number of tags currently stored in the cache.
+    pub fn len(&self) -> usize {
+        let inner = self.inner.lock().expect("replay cache mutex got poisoned");
+        inner.buckets.iter().map(HashSet::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_duplicate_tags() {
+        let cache = ReplayCache::default();
+        assert!(cache.insert([1u8; 32]));
+        assert!(cache.insert([2u8; 32]));
+        assert!(!cache.insert([1u8; 32]));
+        assert!(cache.contains(&[2u8; 32]));
+        assert!(!cache.contains(&[3u8; 32]));
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn forgets_tags_after_retention_window() {
+        let bucket_duration = Duration::from_secs(10);
+        let cache = ReplayCache::new(bucket_duration, 3);
+        let start = Instant::now();
+
+        assert!(cache.insert_at([1u8; 32], start));
+        assert!(cache.insert_at([2u8; 32], start + bucket_duration));
+
+        // still within the window
+        assert!(cache.contains_at(&[1u8; 32], start + bucket_duration * 2));
+        assert!(!cache.insert_at([1u8; 32], start + bucket_duration * 2));
+
+        // the first bucket got dropped, but the second one is still there
+        assert!(!cache.contains_at(&[1u8; 32], start + bucket_duration * 3));
+        assert!(cache.contains_at(&[2u8; 32], start + bucket_duration * 3));
+
+        // after a long pause everything should have been dropped
+        assert!(!cache.contains_at(&[2u8; 32], start + bucket_duration * 100));
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn clearing_removes_all_tags() {
+        let cache = ReplayCache::default();
+        cache.insert([1u8; 32]);
+        cache.insert([2u8; 32]);
+        cache.clear();
+        assert!(cache.is_empty());
+        assert!(cache.insert([1u8; 32]));
+    }
+}
@@ -0,0 +1,455 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Sphinx key rotation performed by mixnodes and gateways.
+//!
+//! The rotation happens in the following steps:
+//! 1. the operator generates the next sphinx keypair, which gets stored alongside the current one,
+//! 2. the operator announces the new public key in the mixnet contract, which schedules it to
+//!    become valid at the beginning of the following epoch. As soon as the node notices
+//!    the announcement, it starts accepting packets created for the new key, so that clients
+//!    switching to it at the beginning of that epoch are served regardless of when the node
+//!    polls the network next,
+//! 3. once that epoch begins, the node makes the new key its current one, but keeps accepting
+//!    packets created for the old one, so that clients using slightly stale topology are not
+//!    disrupted,
+//! 4. after the grace period (of a single epoch) passes, the old key is removed completely.
+//!
+//! The rotation is operator-driven: the node never generates nor announces a new key on its own,
+//! it only follows through with the steps 2-4 once the operator has performed the first two.
+
+use crate::packet_processor::processor::SphinxPacketProcessor;
+use crypto::asymmetric::encryption;
+use log::*;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use task::ShutdownListener;
+use tokio::time::sleep;
+use url::Url;
+
+/// Number of epochs for which the previous sphinx key is still accepted after the rotation.
+pub const SPHINX_KEY_GRACE_EPOCHS: u32 = 1;
+
+const NEXT_KEY_PREFIX: &str = "next_";
+const PREVIOUS_KEY_PREFIX: &str = "previous_";
+
+fn prefixed_path(path: &Path, prefix: &str) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!("{}{}", prefix, file_name))
+}
+
+#[derive(Debug, Clone)]
+pub struct SphinxKeyPaths {
+    private_key: PathBuf,
+    public_key: PathBuf,
+}
+
+impl SphinxKeyPaths {
+    pub fn new(private_key: PathBuf, public_key: PathBuf) -> Self {
+        SphinxKeyPaths {
+            private_key,
+            public_key,
+        }
+    }
+
+    fn with_prefix(&self, prefix: &str) -> Self {
+        SphinxKeyPaths {
+            private_key: prefixed_path(&self.private_key, prefix),
+            public_key: prefixed_path(&self.public_key, prefix),
+        }
+    }
+
+    fn exists(&self) -> bool {
+        self.private_key.exists() && self.public_key.exists()
+    }
+
+    fn as_pemstore_paths(&self) -> pemstore::KeyPairPath {
+        pemstore::KeyPairPath::new(self.private_key.clone(), self.public_key.clone())
+    }
+
+    fn load(&self) -> io::Result<encryption::KeyPair> {
+        pemstore::load_keypair(&self.as_pemstore_paths())
+    }
+
+    fn store(&self, keypair: &encryption::KeyPair) -> io::Result<()> {
+        pemstore::store_keypair(keypair, &self.as_pemstore_paths())
+    }
+
+    fn move_to(&self, other: &SphinxKeyPaths) -> io::Result<()> {
+        std::fs::rename(&self.private_key, &other.private_key)?;
+        std::fs::rename(&self.public_key, &other.public_key)
+    }
+
+    fn remove(&self) -> io::Result<()> {
+        std::fs::remove_file(&self.private_key)?;
+        std::fs::remove_file(&self.public_key)
+    }
+}
+
+/// Locations of all the sphinx keypairs the node might be holding during the rotation.
+/// Next and previous keys are stored next to the current key with appropriate file prefixes.
+#[derive(Debug, Clone)]
+pub struct SphinxKeyFiles {
+    current: SphinxKeyPaths,
+    next: SphinxKeyPaths,
+    previous: SphinxKeyPaths,
+}
+
+impl SphinxKeyFiles {
+    pub fn new(private_key: PathBuf, public_key: PathBuf) -> Self {
+        let current = SphinxKeyPaths::new(private_key, public_key);
+        SphinxKeyFiles {
+            next: current.with_prefix(NEXT_KEY_PREFIX),
+            previous: current.with_prefix(PREVIOUS_KEY_PREFIX),
+            current,
+        }
+    }
+
+    /// Stores the sphinx keypair that is going to be used after the rotation.
+    /// Any previously stored, but not yet used, keypair is overwritten.
+    pub fn store_next_keypair(&self, keypair: &encryption::KeyPair) -> io::Result<()> {
+        self.next.store(keypair)
+    }
+
+    pub fn load_next_keypair(&self) -> Option<io::Result<encryption::KeyPair>> {
+        self.next.exists().then(|| self.next.load())
+    }
+
+    pub fn load_previous_keypair(&self) -> Option<io::Result<encryption::KeyPair>> {
+        self.previous.exists().then(|| self.previous.load())
+    }
+
+    /// Makes the next keypair the current one, while preserving the current one as the previous.
+    fn promote_next_keypair(&self) -> io::Result<()> {
+        self.current.move_to(&self.previous)?;
+        self.next.move_to(&self.current)
+    }
+
+    fn remove_previous_keypair(&self) -> io::Result<()> {
+        self.previous.remove()
+    }
+}
+
+/// Type of the node whose sphinx keys are rotated. Used for finding its bond in the network.
+#[derive(Debug, Clone)]
+pub enum RotatingNode {
+    Mixnode { identity: String },
+    Gateway { identity: String },
+}
+
+/// Sphinx keys of the node as currently seen by the network.
+struct AnnouncedSphinxKeys {
+    current: String,
+    pending: Option<(String, u32)>,
+}
+
+pub struct SphinxKeyRotationController {
+    node: RotatingNode,
+    key_files: SphinxKeyFiles,
+    processor: SphinxPacketProcessor,
+    validator_client: validator_client::ApiClient,
+    check_interval: Duration,
+
+    /// Epoch during which the last rotation was observed. If the node was restarted in the middle
+    /// of the grace period, it's the first epoch observed afterwards.
+    rotation_epoch: Option<u32>,
+
+    /// Public key of the announced next keypair that the processor accepts ahead of the rotation.
+    accepted_next_key: Option<String>,
+
+    shutdown: ShutdownListener,
+}
+
+impl SphinxKeyRotationController {
+    pub fn new(
+        node: RotatingNode,
+        key_files: SphinxKeyFiles,
+        processor: SphinxPacketProcessor,
+        validator_api: Url,
+        check_interval: Duration,
+        shutdown: ShutdownListener,
+    ) -> Self {
+        SphinxKeyRotationController {
+            node,
+            key_files,
+            processor,
+            validator_client: validator_client::ApiClient::new(validator_api),
+            check_interval,
+            rotation_epoch: None,
+            accepted_next_key: None,
+            shutdown,
+        }
+    }
+
+    async fn get_announced_keys(&self) -> Option<AnnouncedSphinxKeys> {
+        match &self.node {
+            RotatingNode::Mixnode { identity } => {
+                let mixnodes = match self.validator_client.get_cached_mixnodes().await {
+                    Ok(mixnodes) => mixnodes,
+                    Err(err) => {
+                        warn!("failed to obtain the list of mixnodes - {}", err);
+                        return None;
+                    }
+                };
+                mixnodes
+                    .into_iter()
+                    .map(|details| details.bond_information)
+                    .find(|bond| &bond.mix_node.identity_key == identity)
+                    .map(|bond| AnnouncedSphinxKeys {
+                        current: bond.mix_node.sphinx_key,
+                        pending: bond
+                            .pending_sphinx_key
+                            .map(|pending| (pending.sphinx_key, pending.valid_from_epoch)),
+                    })
+            }
+            RotatingNode::Gateway { identity } => {
+                let gateways = match self.validator_client.get_cached_gateways().await {
+                    Ok(gateways) => gateways,
+                    Err(err) => {
+                        warn!("failed to obtain the list of gateways - {}", err);
+                        return None;
+                    }
+                };
+                gateways
+                    .into_iter()
+                    .find(|bond| &bond.gateway.identity_key == identity)
+                    .map(|bond| AnnouncedSphinxKeys {
+                        current: bond.gateway.sphinx_key,
+                        pending: bond
+                            .pending_sphinx_key
+                            .map(|pending| (pending.sphinx_key, pending.valid_from_epoch)),
+                    })
+            }
+        }
+    }
+
+    async fn get_current_epoch(&self) -> Option<u32> {
+        match self.validator_client.get_cached_current_epoch().await {
+            Ok(interval) => interval.map(|interval| interval.current_epoch_absolute_id()),
+            Err(err) => {
+                warn!("failed to obtain the current epoch - {}", err);
+                None
+            }
+        }
+    }
+
+    fn is_next_key_announced(next_key: &str, announced: &AnnouncedSphinxKeys) -> bool {
+        announced.current == next_key
+            || matches!(&announced.pending, Some((pending_key, _)) if pending_key == next_key)
+    }
+
+    fn should_use_next_key(
+        next_key: &encryption::PublicKey,
+        announced: &AnnouncedSphinxKeys,
+        current_epoch: u32,
+    ) -> bool {
+        let next_key = next_key.to_base58_string();
+
+        // the contract might have already promoted our pending key
+        if announced.current == next_key {
+            return true;
+        }
+
+        match &announced.pending {
+            Some((pending_key, valid_from)) => {
+                pending_key == &next_key && current_epoch >= *valid_from
+            }
+            None => false,
+        }
+    }
+
+    fn try_rotate(&mut self, announced: &AnnouncedSphinxKeys, current_epoch: u32) {
+        let next_keypair = match self.key_files.load_next_keypair() {
+            None => return,
+            Some(Ok(keypair)) => keypair,
+            Some(Err(err)) => {
+                error!("failed to load the next sphinx keypair - {}", err);
+                return;
+            }
+        };
+
+        let next_key = next_keypair.public_key().to_base58_string();
+        if !Self::is_next_key_announced(&next_key, announced) {
+            // the operator might have generated a different key in the meantime
+            if self.accepted_next_key.take().is_some() {
+                self.processor.discard_next_key();
+            }
+            return;
+        }
+
+        if self.accepted_next_key.as_ref() != Some(&next_key) {
+            info!(
+                "Accepting packets for the announced sphinx key {} ahead of the rotation",
+                next_key
+            );
+            self.processor
+                .accept_next_key(next_keypair.private_key().into());
+            self.accepted_next_key = Some(next_key);
+        }
+
+        if !Self::should_use_next_key(next_keypair.public_key(), announced, current_epoch) {
+            return;
+        }
+
+        if let Err(err) = self.key_files.promote_next_keypair() {
+            error!(
+                "failed to move the sphinx key files during the rotation - {}. The new key is NOT going to be used",
+                err
+            );
+            return;
+        }
+
+        info!(
+            "Rotating sphinx key to {} in epoch {}",
+            next_keypair.public_key().to_base58_string(),
+            current_epoch
+        );
+        self.processor.rotate_to_next_key();
+        self.accepted_next_key = None;
+        self.rotation_epoch = Some(current_epoch);
+    }
+
+    fn try_retire_previous_key(&mut self, current_epoch: u32) {
+        if !self.processor.has_previous_key() {
+            return;
+        }
+
+        let rotation_epoch = *self.rotation_epoch.get_or_insert(current_epoch);
+        if current_epoch < rotation_epoch + SPHINX_KEY_GRACE_EPOCHS {
+            return;
+        }
+
+        info!("The grace period has passed - removing the previous sphinx key");
+        self.processor.retire_previous_key();
+        self.rotation_epoch = None;
+        if let Err(err) = self.key_files.remove_previous_keypair() {
+            warn!("failed to remove the previous sphinx key files - {}", err)
+        }
+    }
+
+    async fn check_rotation(&mut self) {
+        let current_epoch = match self.get_current_epoch().await {
+            Some(epoch) => epoch,
+            None => return,
+        };
+
+        self.try_retire_previous_key(current_epoch);
+
+        match self.get_announced_keys().await {
+            Some(announced) => self.try_rotate(&announced, current_epoch),
+            None => {
+                debug!("our node does not seem to be bonded - not checking sphinx key rotation")
+            }
+        }
+    }
+
+    pub async fn run(&mut self) {
+        while !self.shutdown.is_shutdown() {
+            self.check_rotation().await;
+
+            tokio::select! {
+                _ = sleep(self.check_interval) => {},
+                _ = self.shutdown.recv() => {
+                    trace!("SphinxKeyRotationController: Received shutdown");
+                }
+            }
+        }
+
+        trace!("SphinxKeyRotationController: Exiting");
+    }
+
+    pub fn start(mut self) {
+        tokio::spawn(async move { self.run().await });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_and_previous_keys_are_stored_alongside_current_one() {
+        let files = SphinxKeyFiles::new(
+            PathBuf::from("/foo/bar/private_sphinx.pem"),
+            PathBuf::from("/foo/bar/public_sphinx.pem"),
+        );
+        assert_eq!(
+            files.next.private_key,
+            PathBuf::from("/foo/bar/next_private_sphinx.pem")
+        );
+        assert_eq!(
+            files.previous.public_key,
+            PathBuf::from("/foo/bar/previous_public_sphinx.pem")
+        );
+    }
+
+    #[test]
+    fn next_key_is_used_once_announced_and_active() {
+        let next_key = "C7cown6dYCLZpLiMFC1PaBmhvLvmJmLDJGeRTbPD45bX".to_string();
+        let next = encryption::PublicKey::from_base58_string(&next_key).unwrap();
+
+        let not_announced = AnnouncedSphinxKeys {
+            current: "foo".to_string(),
+            pending: None,
+        };
+        assert!(!SphinxKeyRotationController::should_use_next_key(
+            &next,
+            &not_announced,
+            42
+        ));
+
+        let pending = AnnouncedSphinxKeys {
+            current: "foo".to_string(),
+            pending: Some((next_key.clone(), 10)),
+        };
+        assert!(!SphinxKeyRotationController::should_use_next_key(
+            &next, &pending, 9
+        ));
+        assert!(SphinxKeyRotationController::should_use_next_key(
+            &next, &pending, 10
+        ));
+
+        let promoted = AnnouncedSphinxKeys {
+            current: next_key,
+            pending: None,
+        };
+        assert!(SphinxKeyRotationController::should_use_next_key(
+            &next, &promoted, 0
+        ));
+    }
+
+    #[test]
+    fn next_key_is_accepted_once_announced() {
+        let next_key = "C7cown6dYCLZpLiMFC1PaBmhvLvmJmLDJGeRTbPD45bX";
+
+        let not_announced = AnnouncedSphinxKeys {
+            current: "foo".to_string(),
+            pending: Some(("bar".to_string(), 10)),
+        };
+        assert!(!SphinxKeyRotationController::is_next_key_announced(
+            next_key,
+            &not_announced
+        ));
+
+        // regardless of the epoch it becomes valid from
+        let pending = AnnouncedSphinxKeys {
+            current: "foo".to_string(),
+            pending: Some((next_key.to_string(), 10)),
+        };
+        assert!(SphinxKeyRotationController::is_next_key_announced(
+            next_key, &pending
+        ));
+
+        let promoted = AnnouncedSphinxKeys {
+            current: next_key.to_string(),
+            pending: None,
+        };
+        assert!(SphinxKeyRotationController::is_next_key_announced(
+            next_key, &promoted
+        ));
+    }
+}
@@ -0,0 +1,228 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::fragment::Fragment;
+use log::*;
+use std::fmt::{self, Debug, Formatter};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Storage used by the `MessageReconstructor` for keeping `Fragment`s of sets that are not yet
+/// fully reconstructed. It allows the reconstructor to move the sets out of memory and to resume
+/// the reconstruction after a restart.
+///
+/// All operations are blocking and get performed while `Fragment`s are being inserted, so a
+/// `MessageReconstructor` with a backend should not be used directly on an async executor.
+pub trait ReconstructionBackend: Send + Sync {
+    /// Persists the received `Fragment`. If a fragment at the same position of the same set
+    /// already exists, it is overwritten.
+    fn store_fragment(&self, fragment: &Fragment) -> io::Result<()>;
+
+    /// Loads all stored `Fragment`s of the set with the provided id.
+    fn load_set(&self, set_id: i32) -> io::Result<Vec<Fragment>>;
+
+    /// Removes all stored `Fragment`s of the set with the provided id.
+    fn remove_set(&self, set_id: i32) -> io::Result<()>;
+
+    /// Returns ids of all sets that have at least a single `Fragment` stored.
+    fn stored_sets(&self) -> io::Result<Vec<i32>>;
+}
+
+/// Cheaply cloneable handle to a `ReconstructionBackend`, so that the `MessageReconstructor`
+/// could still be compared and cloned.
+#[derive(Clone)]
+pub(crate) struct BackendHandle(Arc<dyn ReconstructionBackend>);
+
+impl BackendHandle {
+    pub(crate) fn new(backend: Arc<dyn ReconstructionBackend>) -> Self {
+        BackendHandle(backend)
+    }
+}
+
+impl std::ops::Deref for BackendHandle {
+    type Target = dyn ReconstructionBackend;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+impl PartialEq for BackendHandle {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Debug for BackendHandle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "ReconstructionBackend")
+    }
+}
+
+const PARTIAL_FILE_EXTENSION: &str = "partial";
+
+/// `ReconstructionBackend` keeping each `Fragment` in a separate file, within a directory
+/// dedicated to its set, i.e. `<root>/<set_id>/<fragment_position>`.
+#[derive(Debug)]
+pub struct DiskReconstructionBackend {
+    root_directory: PathBuf,
+}
+
+impl DiskReconstructionBackend {
+    /// Creates new instance of the backend, creating the root directory if it does not exist yet.
+    pub fn new<P: Into<PathBuf>>(root_directory: P) -> io::Result<Self> {
+        let root_directory = root_directory.into();
+        fs::create_dir_all(&root_directory)?;
+        Ok(DiskReconstructionBackend { root_directory })
+    }
+
+    fn set_directory(&self, set_id: i32) -> PathBuf {
+        self.root_directory.join(set_id.to_string())
+    }
+}
+
+impl ReconstructionBackend for DiskReconstructionBackend {
+    fn store_fragment(&self, fragment: &Fragment) -> io::Result<()> {
+        let set_directory = self.set_directory(fragment.id());
+        fs::create_dir_all(&set_directory)?;
+
+        // write to a temporary file first so that we would never end up with a partially
+        // written fragment in case of a crash
+        let fragment_path = set_directory.join(fragment.current_fragment().to_string());
+        let partial_path = fragment_path.with_extension(PARTIAL_FILE_EXTENSION);
+        fs::write(&partial_path, fragment.clone().into_bytes())?;
+        fs::rename(partial_path, fragment_path)
+    }
+
+    fn load_set(&self, set_id: i32) -> io::Result<Vec<Fragment>> {
+        let set_directory = self.set_directory(set_id);
+        if !set_directory.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut fragments = Vec::new();
+        for entry in fs::read_dir(set_directory)? {
+            let path = entry?.path();
+            if path.extension().is_some() {
+                // leftover of an interrupted write
+                continue;
+            }
+
+            let fragment = Fragment::try_from_bytes(&fs::read(&path)?).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("malformed fragment at {:?} - {:?}", path, err),
+                )
+            })?;
+            if fragment.id() != set_id {
+                warn!(
+                    "fragment stored at {:?} does not belong to set {} - ignoring it",
+                    path, set_id
+                );
+                continue;
+            }
+            fragments.push(fragment);
+        }
+
+        Ok(fragments)
+    }
+
+    fn remove_set(&self, set_id: i32) -> io::Result<()> {
+        match fs::remove_dir_all(self.set_directory(set_id)) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            res => res,
+        }
+    }
+
+    fn stored_sets(&self) -> io::Result<Vec<i32>> {
+        let mut sets = Vec::new();
+        for entry in fs::read_dir(&self.root_directory)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            match entry.file_name().to_str().map(str::parse) {
+                Some(Ok(set_id)) => sets.push(set_id),
+                _ => warn!(
+                    "unexpected entry {:?} in the reconstruction storage",
+                    entry.path()
+                ),
+            }
+        }
+
+        Ok(sets)
+    }
+}
+
+#[cfg(test)]
+mod disk_backend {
+    use super::*;
+    use crate::set::split_into_sets;
+
+    // just some arbitrary value to use in tests
+    const AVAILABLE_PLAINTEXT_SIZE: usize = 1024;
+
+    fn fragments_fixture() -> Vec<Fragment> {
+        let mut rng = rand::rngs::OsRng;
+        split_into_sets(&mut rng, &[42u8; 5000], AVAILABLE_PLAINTEXT_SIZE)
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    #[test]
+    fn stored_fragments_can_be_loaded_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = DiskReconstructionBackend::new(dir.path()).unwrap();
+
+        let fragments = fragments_fixture();
+        let set_id = fragments[0].id();
+        for fragment in &fragments {
+            backend.store_fragment(fragment).unwrap();
+        }
+
+        assert_eq!(backend.stored_sets().unwrap(), vec![set_id]);
+
+        let mut loaded = backend.load_set(set_id).unwrap();
+        loaded.sort_by_key(|fragment| fragment.current_fragment());
+        assert_eq!(loaded, fragments);
+    }
+
+    #[test]
+    fn storage_survives_recreating_the_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        let fragments = fragments_fixture();
+        let set_id = fragments[0].id();
+
+        let backend = DiskReconstructionBackend::new(dir.path()).unwrap();
+        backend.store_fragment(&fragments[0]).unwrap();
+        drop(backend);
+
+        let backend = DiskReconstructionBackend::new(dir.path()).unwrap();
+        assert_eq!(
+            backend.load_set(set_id).unwrap(),
+            vec![fragments[0].clone()]
+        );
+    }
+
+    #[test]
+    fn removing_set_removes_all_its_fragments() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = DiskReconstructionBackend::new(dir.path()).unwrap();
+
+        let fragments = fragments_fixture();
+        let set_id = fragments[0].id();
+        for fragment in &fragments {
+            backend.store_fragment(fragment).unwrap();
+        }
+
+        backend.remove_set(set_id).unwrap();
+        assert!(backend.stored_sets().unwrap().is_empty());
+        assert!(backend.load_set(set_id).unwrap().is_empty());
+
+        // removing non-existent set is not an error
+        assert!(backend.remove_set(set_id).is_ok());
+    }
+}
@@ -0,0 +1,253 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Optional forward error correction of `FragmentSet`s.
+//!
+//! Each data `Fragment` of the set (including its header) is prefixed with its length
+//! and padded to a common size, forming a data shard of a Reed-Solomon code. The resulting parity
+//! shards are then sent as payloads of additional parity `Fragment`s, so that the recipient is
+//! able to recover the whole set from any `k` out of its `k + m` fragments.
+
+use crate::fragment::{Fragment, PARITY_FRAGMENT_HEADER_LEN};
+use crate::ChunkingError;
+use reed_solomon_erasure::galois_16::ReedSolomon;
+use std::convert::TryInto;
+
+/// Number of bytes used for encoding length of the data `Fragment` inside its shard.
+const SHARD_LENGTH_PREFIX: usize = 2;
+
+/// Amount of plaintext, per packet, that is lost by the data `Fragment`s when the set
+/// is erasure coded. It's the header of the parity `Fragment` alongside the length prefix
+/// and a potential extra padding byte, as the shards of the code consist of 2-byte symbols.
+pub const PARITY_FRAGMENT_OVERHEAD: usize = PARITY_FRAGMENT_HEADER_LEN + SHARD_LENGTH_PREFIX + 1;
+
+/// Determines number of parity `Fragment`s to attach to a set of `data_fragments` data
+/// `Fragment`s for the given redundancy, i.e. the ratio of parity to data `Fragment`s.
+/// Regardless of the redundancy, at least a single parity `Fragment` is always created.
+pub fn parity_fragments_count(data_fragments: usize, redundancy: f64) -> u8 {
+    let parity = (data_fragments as f64 * redundancy).ceil();
+    parity.clamp(1.0, u8::max_value() as f64) as u8
+}
+
+fn shard_symbols(bytes: &[u8], shard_len: usize) -> Vec<[u8; 2]> {
+    let mut padded = Vec::with_capacity(shard_len);
+    padded.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+    padded.extend_from_slice(bytes);
+    padded.resize(shard_len, 0);
+
+    padded
+        .chunks_exact(2)
+        .map(|symbol| [symbol[0], symbol[1]])
+        .collect()
+}
+
+fn symbols_bytes(symbols: &[[u8; 2]]) -> Vec<u8> {
+    symbols.iter().flatten().copied().collect()
+}
+
+fn data_shard(fragment: &Fragment, shard_len: usize) -> Result<Vec<[u8; 2]>, ChunkingError> {
+    let bytes = fragment.clone().into_bytes();
+    if bytes.len() + SHARD_LENGTH_PREFIX > shard_len {
+        return Err(ChunkingError::ErasureCodingError);
+    }
+    Ok(shard_symbols(&bytes, shard_len))
+}
+
+fn parity_shard(parity_fragment: &Fragment) -> Vec<[u8; 2]> {
+    parity_fragment
+        .clone()
+        .extract_payload()
+        .chunks_exact(2)
+        .map(|symbol| [symbol[0], symbol[1]])
+        .collect()
+}
+
+fn fragment_from_data_shard(symbols: &[[u8; 2]]) -> Result<Fragment, ChunkingError> {
+    let bytes = symbols_bytes(symbols);
+    let len = u16::from_be_bytes(bytes[..SHARD_LENGTH_PREFIX].try_into().unwrap()) as usize;
+    if SHARD_LENGTH_PREFIX + len > bytes.len() {
+        return Err(ChunkingError::ErasureCodingError);
+    }
+    Fragment::try_from_bytes(&bytes[SHARD_LENGTH_PREFIX..SHARD_LENGTH_PREFIX + len])
+}
+
+/// Creates `parity_fragments` parity `Fragment`s for the provided, complete, set of data `Fragment`s.
+pub(crate) fn encode_parity_fragments(
+    set: &[Fragment],
+    parity_fragments: u8,
+    max_plaintext_size: usize,
+) -> Result<Vec<Fragment>, ChunkingError> {
+    let set_id = set
+        .first()
+        .map(Fragment::id)
+        .ok_or(ChunkingError::ErasureCodingError)?;
+    let data_fragments = set.len();
+
+    let longest_fragment = set
+        .iter()
+        .map(|fragment| fragment.clone().into_bytes().len())
+        .max()
+        .unwrap_or_default();
+    let shard_len = SHARD_LENGTH_PREFIX + longest_fragment;
+    // make sure the shard consists of whole symbols
+    let shard_len = shard_len + shard_len % 2;
+
+    let mut shards = set
+        .iter()
+        .map(|fragment| data_shard(fragment, shard_len))
+        .collect::<Result<Vec<_>, _>>()?;
+    shards.resize(
+        data_fragments + parity_fragments as usize,
+        vec![[0; 2]; shard_len / 2],
+    );
+
+    let encoder = ReedSolomon::new(data_fragments, parity_fragments as usize)
+        .map_err(|_| ChunkingError::ErasureCodingError)?;
+    encoder
+        .encode(&mut shards)
+        .map_err(|_| ChunkingError::ErasureCodingError)?;
+
+    shards
+        .into_iter()
+        .skip(data_fragments)
+        .zip(1..=parity_fragments)
+        .map(|(shard, position)| {
+            Fragment::try_new_parity(
+                symbols_bytes(&shard),
+                set_id,
+                data_fragments as u8,
+                parity_fragments,
+                position,
+                max_plaintext_size,
+            )
+        })
+        .collect()
+}
+
+/// Attempts to recover the missing data `Fragment`s of a set using the received parity `Fragment`s.
+/// `data` and `parity` have to be ordered by the positions of the `Fragment`s and have lengths
+/// equal to the total number of data and parity `Fragment`s of the set respectively.
+/// Only the previously missing data `Fragment`s are returned.
+pub(crate) fn recover_data_fragments(
+    data: &[Option<Fragment>],
+    parity: &[Option<Fragment>],
+) -> Result<Vec<Fragment>, ChunkingError> {
+    let (set_id, shard_len) = parity
+        .iter()
+        .flatten()
+        .map(|fragment| (fragment.id(), fragment.payload_size()))
+        .next()
+        .ok_or(ChunkingError::ErasureCodingError)?;
+    if shard_len % 2 != 0 || shard_len < SHARD_LENGTH_PREFIX {
+        return Err(ChunkingError::ErasureCodingError);
+    }
+
+    let mut shards = Vec::with_capacity(data.len() + parity.len());
+    for fragment in data {
+        match fragment {
+            Some(fragment) => shards.push(Some(data_shard(fragment, shard_len)?)),
+            None => shards.push(None),
+        }
+    }
+    for fragment in parity {
+        match fragment {
+            Some(fragment) if fragment.payload_size() == shard_len => {
+                shards.push(Some(parity_shard(fragment)))
+            }
+            Some(_) => return Err(ChunkingError::ErasureCodingError),
+            None => shards.push(None),
+        }
+    }
+
+    let decoder = ReedSolomon::new(data.len(), parity.len())
+        .map_err(|_| ChunkingError::ErasureCodingError)?;
+    decoder
+        .reconstruct_data(&mut shards)
+        .map_err(|_| ChunkingError::ErasureCodingError)?;
+
+    let mut recovered = Vec::new();
+    for (position, shard) in shards.iter().take(data.len()).enumerate() {
+        if data[position].is_some() {
+            continue;
+        }
+        let shard = shard.as_ref().ok_or(ChunkingError::ErasureCodingError)?;
+        let fragment = fragment_from_data_shard(shard)?;
+        // make sure the recovered fragment is actually the one we were missing
+        if fragment.is_parity()
+            || fragment.id() != set_id
+            || fragment.current_fragment() as usize != position + 1
+        {
+            return Err(ChunkingError::ErasureCodingError);
+        }
+        recovered.push(fragment);
+    }
+
+    Ok(recovered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::set::split_into_sets;
+
+    // just some arbitrary value to use in tests
+    const AVAILABLE_PLAINTEXT_SIZE: usize = 1024;
+
+    fn set_fixture(message_len: usize) -> Vec<Fragment> {
+        let mut rng = rand::rngs::OsRng;
+        split_into_sets(
+            &mut rng,
+            &vec![42u8; message_len],
+            AVAILABLE_PLAINTEXT_SIZE - PARITY_FRAGMENT_OVERHEAD,
+        )
+        .pop()
+        .unwrap()
+    }
+
+    #[test]
+    fn parity_fragments_fit_in_the_available_plaintext() {
+        for message_len in [1, 100, 1013, 5000, 12345] {
+            let set = set_fixture(message_len);
+            for parity in encode_parity_fragments(&set, 3, AVAILABLE_PLAINTEXT_SIZE).unwrap() {
+                assert!(parity.into_bytes().len() <= AVAILABLE_PLAINTEXT_SIZE);
+            }
+        }
+    }
+
+    #[test]
+    fn missing_data_fragments_can_be_recovered() {
+        let set = set_fixture(10000);
+        let parity_count = 3;
+        let parity = encode_parity_fragments(&set, parity_count, AVAILABLE_PLAINTEXT_SIZE).unwrap();
+
+        // lose some data fragments and one of the parity ones
+        let mut data: Vec<_> = set.iter().cloned().map(Some).collect();
+        data[0] = None;
+        data[4] = None;
+        let mut parity: Vec<_> = parity.into_iter().map(Some).collect();
+        parity[1] = None;
+
+        let recovered = recover_data_fragments(&data, &parity).unwrap();
+        assert_eq!(recovered, vec![set[0].clone(), set[4].clone()]);
+    }
+
+    #[test]
+    fn recovery_fails_with_insufficient_number_of_fragments() {
+        let set = set_fixture(10000);
+        let parity = encode_parity_fragments(&set, 1, AVAILABLE_PLAINTEXT_SIZE).unwrap();
+
+        let mut data: Vec<_> = set.into_iter().map(Some).collect();
+        data[0] = None;
+        data[1] = None;
+        let parity: Vec<_> = parity.into_iter().map(Some).collect();
+
+        assert!(recover_data_fragments(&data, &parity).is_err());
+    }
+
+    #[test]
+    fn parity_fragments_count_is_bounded() {
+        assert_eq!(parity_fragments_count(10, 0.0), 1);
+        assert_eq!(parity_fragments_count(10, 0.25), 3);
+        assert_eq!(parity_fragments_count(255, 2.0), 255);
+    }
+}
@@ -36,6 +36,11 @@ pub const UNLINKED_FRAGMENTED_HEADER_LEN: usize = 7;
 /// `Fragment` in a `FragmentSet`.
 pub const LINKED_FRAGMENTED_HEADER_LEN: usize = 10;
 
+/// Parity `Fragment`s, used for the optional erasure coding of `FragmentSet`s, have a header of the
+/// same length as the unlinked data `Fragment`s. However, instead of the linking flag, the final
+/// byte holds the total number of parity `Fragment`s in the set.
+pub const PARITY_FRAGMENT_HEADER_LEN: usize = 7;
+
 /// Maximum size of payload of each fragment is always the maximum amount of plaintext data
 /// we can put into a sphinx packet minus length of respective fragment header.
 pub const fn unlinked_fragment_payload_max_len(max_plaintext_size: usize) -> usize {
@@ -54,25 +59,36 @@ pub const fn linked_fragment_payload_max_len(max_plaintext_size: usize) -> usize
 pub const COVER_FRAG_ID: FragmentIdentifier = FragmentIdentifier {
     set_id: 0,
     fragment_position: 0,
+    is_parity: false,
 };
 
 /// Identifier to uniquely identify a fragment. It represents 31bit ID of given `FragmentSet`
-/// and u8 position of the `Fragment` in the set.
+/// and u8 position of the `Fragment` in the set. Since parity `Fragment`s are enumerated
+/// separately, they are distinguished by the otherwise unused most significant bit of the id.
 // TODO: this should really be redesigned, especially how cover and reply messages are really
 // "abusing" this. They should work with it natively instead.
 #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
 pub struct FragmentIdentifier {
     set_id: i32,
     fragment_position: u8,
+    is_parity: bool,
 }
 
 impl fmt::Display for FragmentIdentifier {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "Fragment Identifier: id: {} position: {}",
-            self.set_id, self.fragment_position
-        )
+        if self.is_parity {
+            write!(
+                f,
+                "Fragment Identifier: id: {} parity position: {}",
+                self.set_id, self.fragment_position
+            )
+        } else {
+            write!(
+                f,
+                "Fragment Identifier: id: {} position: {}",
+                self.set_id, self.fragment_position
+            )
+        }
     }
 }
 
@@ -83,6 +99,7 @@ impl FragmentIdentifier {
         FragmentIdentifier {
             set_id: generate_set_id(rng),
             fragment_position: 0,
+            is_parity: false,
         }
     }
 
@@ -91,10 +108,25 @@ impl FragmentIdentifier {
         self.set_id > 0 && self.fragment_position == 0
     }
 
+    /// Id of the `FragmentSet` the identified `Fragment` belongs to.
+    pub fn set_id(self) -> i32 {
+        self.set_id
+    }
+
+    /// Whether the identified `Fragment` is a parity `Fragment` of its set.
+    pub fn is_parity(self) -> bool {
+        self.is_parity
+    }
+
     pub fn to_bytes(self) -> SerializedFragmentIdentifier {
         debug_assert_eq!(FRAG_ID_LEN, 5);
 
-        let set_id_bytes = self.set_id.to_be_bytes();
+        let flagged_set_id = if self.is_parity {
+            self.set_id | (1 << 31)
+        } else {
+            self.set_id
+        };
+        let set_id_bytes = flagged_set_id.to_be_bytes();
         [
             set_id_bytes[0],
             set_id_bytes[1],
@@ -107,15 +139,19 @@ impl FragmentIdentifier {
     pub fn try_from_bytes(b: SerializedFragmentIdentifier) -> Result<Self, ChunkingError> {
         debug_assert_eq!(FRAG_ID_LEN, 5);
 
-        let set_id = i32::from_be_bytes([b[0], b[1], b[2], b[3]]);
+        let flagged_set_id = i32::from_be_bytes([b[0], b[1], b[2], b[3]]);
+        let is_parity = ((flagged_set_id >> 31) & 1) == 1;
         // set_id == 0 is valid for COVER_FRAG_ID and replies
-        if set_id < 0 {
+        let set_id = flagged_set_id & !(1 << 31);
+        // but they can't possibly refer to parity fragments
+        if is_parity && (set_id == 0 || b[4] == 0) {
             return Err(ChunkingError::MalformedFragmentIdentifier);
         }
 
         Ok(FragmentIdentifier {
             set_id,
             fragment_position: b[4],
+            is_parity,
         })
     }
 }
@@ -179,6 +215,30 @@ impl Fragment {
         })
     }
 
+    /// Tries to encapsulate provided erasure coded payload into a parity `Fragment` of the set
+    /// consisting of `data_fragments` data `Fragment`s and `parity_fragments` parity ones.
+    pub(crate) fn try_new_parity(
+        payload: Vec<u8>,
+        id: i32,
+        data_fragments: u8,
+        parity_fragments: u8,
+        current_parity_fragment: u8,
+        max_plaintext_size: usize,
+    ) -> Result<Self, ChunkingError> {
+        let header = FragmentHeader::try_new_parity(
+            id,
+            data_fragments,
+            parity_fragments,
+            current_parity_fragment,
+        )?;
+
+        if payload.len() > max_plaintext_size - PARITY_FRAGMENT_HEADER_LEN {
+            return Err(ChunkingError::InvalidPayloadLengthError);
+        }
+
+        Ok(Fragment { header, payload })
+    }
+
     /// Convert this `Fragment` into vector of bytes which can be put into a sphinx packet.
     pub fn into_bytes(self) -> Vec<u8> {
         self.header
@@ -193,6 +253,7 @@ impl Fragment {
         FragmentIdentifier {
             set_id: self.header.id,
             fragment_position: self.header.current_fragment,
+            is_parity: self.is_parity(),
         }
     }
 
@@ -202,16 +263,28 @@ impl Fragment {
     }
 
     /// Extracts total number of fragments associated with this particular `Fragment` (belonging to
-    /// the same `FragmentSet`).
+    /// the same `FragmentSet`). For parity `Fragment`s this is the number of data `Fragment`s
+    /// they protect.
     pub fn total_fragments(&self) -> u8 {
         self.header.total_fragments
     }
 
-    /// Extracts position of this `Fragment` in a `FragmentSet`.
+    /// Extracts position of this `Fragment` in a `FragmentSet`. Note that parity `Fragment`s
+    /// are enumerated separately from the data ones.
     pub fn current_fragment(&self) -> u8 {
         self.header.current_fragment
     }
 
+    /// Checks whether this is a parity `Fragment` produced by the erasure coding of its set.
+    pub fn is_parity(&self) -> bool {
+        self.header.parity_fragments.is_some()
+    }
+
+    /// Extracts total number of parity `Fragment`s in the set if this is a parity `Fragment`.
+    pub fn parity_fragments(&self) -> Option<u8> {
+        self.header.parity_fragments
+    }
+
     /// Extracts information regarding id of pre-linked `FragmentSet`
     pub fn previous_fragments_set_id(&self) -> Option<i32> {
         self.header.previous_fragments_set_id
@@ -222,6 +295,11 @@ impl Fragment {
         self.header.next_fragments_set_id
     }
 
+    /// Returns size of the payload associated with this `Fragment`.
+    pub(crate) fn payload_size(&self) -> usize {
+        self.payload.len()
+    }
+
     /// Consumes `self` to obtain payload (i.e. part of original message) associated with this
     /// `Fragment`.
     pub(crate) fn extract_payload(self) -> Vec<u8> {
@@ -272,6 +350,11 @@ impl Fragment {
 /// there is 7 bytes of overhead inside each sphinx packet sent
 /// and for the longest messages, without upper bound, there is usually also only 7 bytes
 /// of overhead apart from first and last fragments in each set that instead have 10 bytes of overhead.
+///
+/// Finally, if the set is erasure coded, its parity fragments have the following 7 byte long header:
+/// '0'bit || 31-bit ID || 1-byte TF || 1 byte CF || 1 byte TPF
+/// where TF is the number of data fragments in the set, CF is the position of the parity fragment
+/// and TPF is the total number of parity fragments in the set (which is never 0).
 #[derive(PartialEq, Clone, Debug)]
 pub(crate) struct FragmentHeader {
     /// ID associated with `FragmentSet` to which this particular `Fragment` belongs.
@@ -296,6 +379,11 @@ pub(crate) struct FragmentHeader {
     /// Optional ID of next `FragmentSet` into which the original message was split.
     /// Note, this option is only valid of `current_fragment == total_fragments == u8::max_value()`
     next_fragments_set_id: Option<i32>,
+
+    /// If set, this is a header of a parity `Fragment` and the value represents the total number
+    /// of parity `Fragment`s in the set. In that case `current_fragment` is the position
+    /// of this `Fragment` among the parity ones and the set is never linked.
+    parity_fragments: Option<u8>,
 }
 
 impl FragmentHeader {
@@ -338,6 +426,31 @@ impl FragmentHeader {
             current_fragment,
             previous_fragments_set_id,
             next_fragments_set_id,
+            parity_fragments: None,
+        })
+    }
+
+    /// Tries to create a new `FragmentHeader` of a parity `Fragment`.
+    fn try_new_parity(
+        id: i32,
+        data_fragments: u8,
+        parity_fragments: u8,
+        current_parity_fragment: u8,
+    ) -> Result<Self, ChunkingError> {
+        if id <= 0 || data_fragments == 0 || parity_fragments == 0 {
+            return Err(ChunkingError::MalformedHeaderError);
+        }
+        if current_parity_fragment == 0 || current_parity_fragment > parity_fragments {
+            return Err(ChunkingError::MalformedHeaderError);
+        }
+
+        Ok(FragmentHeader {
+            id,
+            total_fragments: data_fragments,
+            current_fragment: current_parity_fragment,
+            previous_fragments_set_id: None,
+            next_fragments_set_id: None,
+            parity_fragments: Some(parity_fragments),
         })
     }
 
@@ -350,9 +463,12 @@ impl FragmentHeader {
             return Err(ChunkingError::TooShortFragmentData);
         }
         let frag_id = i32::from_be_bytes(b[0..4].try_into().unwrap());
-        // sanity check for the fragmentation flag
+        // if the fragmentation flag is not set, this has to be a parity fragment
         if ((frag_id >> 31) & 1) == 0 {
-            return Err(ChunkingError::MalformedHeaderError);
+            return Ok((
+                Self::try_new_parity(frag_id, b[4], b[6], b[5])?,
+                PARITY_FRAGMENT_HEADER_LEN,
+            ));
         }
 
         let id = frag_id & !(1 << 31); // make sure to clear the flag bit to parse id correctly
@@ -408,6 +524,16 @@ impl FragmentHeader {
 
     /// Marshal this `FragmentHeader` into vector of bytes which can be put into a sphinx packet.
     fn to_bytes(&self) -> Vec<u8> {
+        if let Some(parity_fragments) = self.parity_fragments {
+            let mut bytes = self.id.to_be_bytes().to_vec();
+            bytes.extend_from_slice(&[
+                self.total_fragments,
+                self.current_fragment,
+                parity_fragments,
+            ]);
+            return bytes;
+        }
+
         let frag_id = self.id | (1 << 31);
         let frag_id_bytes = frag_id.to_be_bytes();
         let bytes_prefix_iter = frag_id_bytes
@@ -997,6 +1123,7 @@ mod fragment_header {
                 current_fragment: 11,
                 previous_fragments_set_id: None,
                 next_fragments_set_id: None,
+                parity_fragments: None,
             };
             let header_bytes = header.to_bytes();
             assert!(FragmentHeader::try_from_bytes(&header_bytes).is_err());
@@ -1011,6 +1138,7 @@ mod fragment_header {
                 current_fragment: 0,
                 previous_fragments_set_id: None,
                 next_fragments_set_id: None,
+                parity_fragments: None,
             };
             let header_bytes = header.to_bytes();
             assert!(FragmentHeader::try_from_bytes(&header_bytes).is_err());
@@ -1112,4 +1240,52 @@ mod fragment_header {
             assert_eq!(LINKED_FRAGMENTED_HEADER_LEN, bytes_used);
         }
     }
+
+    #[cfg(test)]
+    mod parity_fragment {
+        use super::*;
+
+        #[test]
+        fn can_be_converted_to_and_from_bytes() {
+            let parity_header = FragmentHeader::try_new_parity(12345, 10, 3, 2).unwrap();
+
+            let mut header_bytes = parity_header.to_bytes();
+            assert_eq!(PARITY_FRAGMENT_HEADER_LEN, header_bytes.len());
+            header_bytes.append(vec![1, 2, 3, 4, 5].as_mut());
+
+            let (recovered_header, bytes_used) =
+                FragmentHeader::try_from_bytes(&header_bytes).unwrap();
+            assert_eq!(parity_header, recovered_header);
+            assert_eq!(PARITY_FRAGMENT_HEADER_LEN, bytes_used);
+        }
+
+        #[test]
+        fn creation_fails_for_invalid_parity_position() {
+            assert!(FragmentHeader::try_new_parity(12345, 10, 3, 0).is_err());
+            assert!(FragmentHeader::try_new_parity(12345, 10, 3, 4).is_err());
+            assert!(FragmentHeader::try_new_parity(12345, 10, 0, 0).is_err());
+            assert!(FragmentHeader::try_new_parity(12345, 0, 3, 1).is_err());
+        }
+
+        #[test]
+        fn identifier_is_distinct_from_data_fragment_identifier() {
+            let data_fragment =
+                Fragment::try_new(&[1, 2, 3], 12345, 3, 3, None, None, 100).unwrap();
+            let parity_fragment =
+                Fragment::try_new_parity(vec![1, 2, 3], 12345, 3, 3, 3, 100).unwrap();
+
+            let data_id = data_fragment.fragment_identifier();
+            let parity_id = parity_fragment.fragment_identifier();
+            assert_ne!(data_id, parity_id);
+            assert!(parity_id.is_parity());
+            assert_eq!(
+                parity_id,
+                FragmentIdentifier::try_from_bytes(parity_id.to_bytes()).unwrap()
+            );
+            assert_eq!(
+                data_id,
+                FragmentIdentifier::try_from_bytes(data_id.to_bytes()).unwrap()
+            );
+        }
+    }
 }
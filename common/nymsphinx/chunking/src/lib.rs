@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::fragment::{linked_fragment_payload_max_len, unlinked_fragment_payload_max_len};
-pub use set::split_into_sets;
+pub use set::{split_into_sets, split_into_sets_with_parity};
 
 // Future consideration: currently in a lot of places, the payloads have randomised content
 // which is not a perfect testing strategy as it might not detect some edge cases I never would
@@ -15,6 +15,8 @@ pub use set::split_into_sets;
 // they should definitely be revisited.
 // For instance there are not tests for the cases when we are padding the message
 
+pub mod backend;
+pub mod erasure;
 pub mod fragment;
 pub mod reconstruction;
 pub mod set;
@@ -57,6 +59,7 @@ pub enum ChunkingError {
     MalformedFragmentData,
     UnexpectedFragmentCount,
     MalformedFragmentIdentifier,
+    ErasureCodingError,
 }
 
 /// Returns number of fragments the message will be split to as well as number of available
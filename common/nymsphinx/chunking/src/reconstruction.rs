@@ -1,14 +1,22 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
+use crate::backend::{BackendHandle, ReconstructionBackend};
+use crate::erasure::recover_data_fragments;
 use crate::fragment::Fragment;
 use crate::ChunkingError;
 use log::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Upper bound on how often the `MessageReconstructor` looks for stale sets.
+const STALE_SETS_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Number of most recently reconstructed sets whose late parity `Fragment`s are recognised
+/// and dropped.
+const MAX_RECENTLY_COMPLETED_SETS: usize = 1024;
 
-// TODO: perhaps a more sophisticated approach with writing to disk periodically in case
-// we're receiving fast & furious in uncompressed 4K - we don't want to keep that in memory;
-// perhaps write whole sets to the disk if there are still more of them to recover?
-// Then either combine files on the disk to target destination or read everything to memory
 /// `ReconstructionBuffer` is a per data set structure used to reconstruct the underlying data
 /// and allows for relatively easy way of determining if the original message is split
 /// into multiple buffers.
@@ -82,6 +90,15 @@ impl ReconstructionBuffer {
         !self.fragments.contains(&None)
     }
 
+    /// Total size of payloads of all `Fragment`s currently held in the buffer.
+    fn payload_size(&self) -> usize {
+        self.fragments
+            .iter()
+            .flatten()
+            .map(|fragment| fragment.payload_size())
+            .sum()
+    }
+
     /// Inserts new `Fragment` data into an appropriate position in the buffer.
     ///
     /// (Note: currently there is no defined behaviour for dealing with duplicate
@@ -134,19 +151,143 @@ impl ReconstructionBuffer {
     }
 }
 
+/// Metadata of a set whose `Fragment`s are held only by the `ReconstructionBackend`.
+#[derive(PartialEq, Debug, Clone)]
+struct SpilledSet {
+    /// Number of `Fragment`s expected in the set.
+    total_fragments: u8,
+
+    /// Positions (0-indexed) of all `Fragment`s that were already received.
+    received: HashSet<u8>,
+
+    /// Copied from the first `Fragment` in the set once it's received.
+    previous_fragments_set_id: Option<i32>,
+
+    /// Copied from the last `Fragment` in the set once it's received (assuming the set is full).
+    next_fragments_set_id: Option<i32>,
+}
+
+impl SpilledSet {
+    fn new(total_fragments: u8) -> Self {
+        SpilledSet {
+            total_fragments,
+            received: HashSet::new(),
+            previous_fragments_set_id: None,
+            next_fragments_set_id: None,
+        }
+    }
+
+    fn from_fragments<'a, I>(total_fragments: u8, fragments: I) -> Self
+    where
+        I: IntoIterator<Item = &'a Fragment>,
+    {
+        let mut set = SpilledSet::new(total_fragments);
+        for fragment in fragments {
+            set.record_fragment(fragment);
+        }
+        set
+    }
+
+    fn from_buffer(buffer: &ReconstructionBuffer) -> Self {
+        SpilledSet::from_fragments(
+            buffer.fragments.len() as u8,
+            buffer.fragments.iter().flatten(),
+        )
+    }
+
+    fn record_fragment(&mut self, fragment: &Fragment) {
+        if fragment.total_fragments() != self.total_fragments
+            || fragment.current_fragment() > self.total_fragments
+        {
+            warn!(
+                "fragment {} is inconsistent with its set {} - ignoring it",
+                fragment.current_fragment(),
+                fragment.id()
+            );
+            return;
+        }
+
+        if fragment.current_fragment() == 1 {
+            self.previous_fragments_set_id = fragment.previous_fragments_set_id();
+        }
+        if fragment.current_fragment() == u8::max_value() {
+            self.next_fragments_set_id = fragment.next_fragments_set_id();
+        }
+        self.received.insert(fragment.current_fragment() - 1);
+    }
+
+    fn is_complete(&self) -> bool {
+        self.received.len() == self.total_fragments as usize
+    }
+}
+
+/// Information used for deciding which sets should be moved out of memory or removed completely.
+#[derive(PartialEq, Debug, Clone)]
+struct SetActivity {
+    /// Value of the global fragment counter at the time the set was last updated.
+    sequence: u64,
+
+    /// Time of the last update of the set. It's only tracked if stale sets are being removed.
+    last_update: Option<Instant>,
+}
+
 /// High level public structure used to buffer all received data `Fragment`s and eventually
 /// returning original messages that they encapsulate.
+///
+/// By default everything is kept in memory indefinitely. Optionally, the reconstructor might:
+/// - enforce a memory budget, in which case least recently updated sets are moved
+///   to the `ReconstructionBackend` (or dropped if there's none),
+/// - persist all received `Fragment`s in a `ReconstructionBackend`, so that the reconstruction
+///   could be resumed after a restart,
+/// - remove sets that have not received any `Fragment`s for a specified amount of time.
+///
+/// If the sets were erasure coded, any `k` out of `k + m` received `Fragment`s are sufficient
+/// for reconstructing a set of `k` data `Fragment`s.
 #[derive(Default, PartialEq, Debug, Clone)]
 pub struct MessageReconstructor {
-    // TODO: some cleaner thread/routine that if message is incomplete and
-    // we haven't received any fragments in X time, we assume they
-    // were lost and message can't be restored.
-    // Perhaps add 'latest_fragment_timestamp' to each buffer
-    // and after N fragments received globally, check all of buffer timestamps.
-    // otherwise we are vulnerable to heap overflow attacks -> somebody can keep on sending
-    // maximum sized sets but without one of required fragments. All of the received
-    // data will be kept on the heap indefinitely in the current implementation.
+    /// Sets that are currently held in memory.
     reconstructed_sets: HashMap<i32, ReconstructionBuffer>,
+
+    /// Sets that were moved out of memory and are only present in the backend.
+    spilled_sets: HashMap<i32, SpilledSet>,
+
+    /// Optional storage used for persisting received `Fragment`s.
+    backend: Option<BackendHandle>,
+
+    /// Sets that have at least a single `Fragment` that could not be persisted in the backend.
+    /// They can't be moved out of memory.
+    unpersisted_sets: HashSet<i32>,
+
+    /// Maximum total size of payloads of all `Fragment`s held in memory.
+    memory_budget: Option<usize>,
+
+    /// Current total size of payloads of all `Fragment`s held in memory.
+    memory_usage: usize,
+
+    /// Number of sets dropped because the memory budget got exceeded while there was no backend
+    /// they could have been moved to.
+    dropped_sets: u64,
+
+    /// Amount of time after which set that has not received any new `Fragment`s is removed.
+    stale_set_timeout: Option<Duration>,
+
+    /// Time of the last check for the stale sets.
+    last_stale_check: Option<Instant>,
+
+    /// Counter incremented with every received `Fragment`.
+    fragment_counter: u64,
+
+    /// Activity of all sets, either held in memory or spilled.
+    sets_activity: HashMap<i32, SetActivity>,
+
+    /// Received parity `Fragment`s of erasure coded sets, ordered by their positions.
+    /// They are always kept in memory, i.e. they are neither persisted in the backend nor
+    /// count towards the memory budget, and are dropped as soon as their set is complete.
+    parity_fragments: HashMap<i32, Vec<Option<Fragment>>>,
+
+    /// Ids of the most recently reconstructed sets, oldest first, so that parity `Fragment`s
+    /// arriving after their set was already reconstructed would not start it anew.
+    recently_completed_sets: VecDeque<i32>,
 }
 
 impl MessageReconstructor {
@@ -155,6 +296,265 @@ impl MessageReconstructor {
         Default::default()
     }
 
+    /// Makes the reconstructor persist all received `Fragment`s in the provided backend
+    /// and resumes reconstruction of all sets already stored there.
+    pub fn with_backend(mut self, backend: Arc<dyn ReconstructionBackend>) -> io::Result<Self> {
+        let backend = BackendHandle::new(backend);
+        for set_id in backend.stored_sets()? {
+            let fragments = backend.load_set(set_id)?;
+            let total_fragments = match fragments.first() {
+                Some(fragment) => fragment.total_fragments(),
+                None => continue,
+            };
+
+            self.spilled_sets.insert(
+                set_id,
+                SpilledSet::from_fragments(total_fragments, &fragments),
+            );
+            self.record_activity(set_id);
+        }
+
+        if !self.spilled_sets.is_empty() {
+            info!(
+                "resuming reconstruction of {} stored fragment sets",
+                self.spilled_sets.len()
+            )
+        }
+
+        self.backend = Some(backend);
+        Ok(self)
+    }
+
+    /// Limits total size of `Fragment`s payloads held in memory. Once it's exceeded, least recently
+    /// updated sets are moved to the backend, or, if there's none, they are dropped.
+    #[must_use]
+    pub fn with_memory_budget(mut self, memory_budget: usize) -> Self {
+        self.memory_budget = Some(memory_budget);
+        self
+    }
+
+    /// Makes the reconstructor remove sets that have not received any `Fragment`s
+    /// for the specified amount of time.
+    #[must_use]
+    pub fn with_stale_set_timeout(mut self, timeout: Duration) -> Self {
+        self.stale_set_timeout = Some(timeout);
+        self
+    }
+
+    /// Returns the number of sets that were dropped because the memory budget got exceeded
+    /// while there was no backend they could have been moved to.
+    pub fn dropped_sets(&self) -> u64 {
+        self.dropped_sets
+    }
+
+    /// Updates activity information of the specified set.
+    fn record_activity(&mut self, set_id: i32) {
+        self.fragment_counter += 1;
+        // note: we're only getting the current time if we really need it, as `Instant::now()`
+        // is not available in all environments (like wasm)
+        let last_update = self.stale_set_timeout.map(|_| Instant::now());
+        self.sets_activity.insert(
+            set_id,
+            SetActivity {
+                sequence: self.fragment_counter,
+                last_update,
+            },
+        );
+    }
+
+    /// Removes all data associated with the specified set, including the one in the backend.
+    fn remove_set(&mut self, set_id: i32) {
+        if let Some(buffer) = self.reconstructed_sets.remove(&set_id) {
+            self.memory_usage = self.memory_usage.saturating_sub(buffer.payload_size());
+        }
+        self.spilled_sets.remove(&set_id);
+        self.unpersisted_sets.remove(&set_id);
+        self.sets_activity.remove(&set_id);
+        self.parity_fragments.remove(&set_id);
+
+        if let Some(backend) = &self.backend {
+            if let Err(err) = backend.remove_set(set_id) {
+                warn!("failed to remove set {} from the backend - {}", set_id, err)
+            }
+        }
+    }
+
+    /// Removes all sets that have not received any `Fragment`s within the configured timeout.
+    /// Returns the number of removed sets.
+    pub fn remove_stale_sets(&mut self) -> usize {
+        let timeout = match self.stale_set_timeout {
+            Some(timeout) => timeout,
+            None => return 0,
+        };
+
+        let now = Instant::now();
+        self.last_stale_check = Some(now);
+
+        let stale_sets: Vec<_> = self
+            .sets_activity
+            .iter_mut()
+            .filter_map(|(set_id, activity)| {
+                // the activity might have been recorded before the timeout was set
+                let last_update = *activity.last_update.get_or_insert(now);
+                (now.saturating_duration_since(last_update) > timeout).then(|| *set_id)
+            })
+            .collect();
+
+        for set_id in &stale_sets {
+            debug!(
+                "set {} has not received any fragments in {:?} - removing it",
+                set_id, timeout
+            );
+            self.remove_set(*set_id);
+        }
+
+        stale_sets.len()
+    }
+
+    /// Checks for the stale sets if enough time has passed since the previous check.
+    fn maybe_remove_stale_sets(&mut self) {
+        let timeout = match self.stale_set_timeout {
+            Some(timeout) => timeout,
+            None => return,
+        };
+
+        let check_interval = timeout.min(STALE_SETS_CHECK_INTERVAL);
+        let is_due = self
+            .last_stale_check
+            .map(|last_check| last_check.elapsed() >= check_interval)
+            .unwrap_or(true);
+
+        if is_due {
+            let removed = self.remove_stale_sets();
+            if removed > 0 {
+                warn!(
+                    "removed {} incomplete fragment sets that did not receive any data in {:?}",
+                    removed, timeout
+                )
+            }
+        }
+    }
+
+    /// Moves the set out of memory. If there is no backend, the set is dropped.
+    fn evict_set(&mut self, set_id: i32) {
+        let buffer = match self.reconstructed_sets.remove(&set_id) {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        self.memory_usage = self.memory_usage.saturating_sub(buffer.payload_size());
+
+        if self.backend.is_some() {
+            debug!("moving set {} out of memory", set_id);
+            self.spilled_sets
+                .insert(set_id, SpilledSet::from_buffer(&buffer));
+        } else {
+            self.dropped_sets += 1;
+            warn!(
+                "the reconstruction memory budget of {} bytes got exceeded and there is no backend - dropping the set {} ({} bytes of received data). {} sets have been dropped so far",
+                self.memory_budget.unwrap_or_default(),
+                set_id,
+                buffer.payload_size(),
+                self.dropped_sets
+            );
+            self.sets_activity.remove(&set_id);
+            self.parity_fragments.remove(&set_id);
+        }
+    }
+
+    /// Moves least recently updated sets out of memory until the memory budget is satisfied.
+    fn enforce_memory_budget(&mut self) {
+        let memory_budget = match self.memory_budget {
+            Some(memory_budget) => memory_budget,
+            None => return,
+        };
+
+        while self.memory_usage > memory_budget {
+            let least_recently_updated = self
+                .reconstructed_sets
+                .keys()
+                .filter(|set_id| !self.unpersisted_sets.contains(set_id))
+                .min_by_key(|set_id| {
+                    self.sets_activity
+                        .get(set_id)
+                        .map(|activity| activity.sequence)
+                        .unwrap_or_default()
+                })
+                .copied();
+
+            match least_recently_updated {
+                Some(set_id) => self.evict_set(set_id),
+                None => {
+                    warn!("the reconstruction memory budget is exceeded, but there are no sets that could be moved out of memory");
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Loads the previously spilled set back into memory.
+    fn restore_spilled_set(&mut self, set_id: i32) -> io::Result<()> {
+        let total_fragments = match self.spilled_sets.get(&set_id) {
+            Some(spilled) => spilled.total_fragments,
+            None => return Ok(()),
+        };
+        let backend = self
+            .backend
+            .as_ref()
+            .expect("sets can only be spilled if the backend exists");
+
+        let mut buffer = ReconstructionBuffer::new(total_fragments);
+        for fragment in backend.load_set(set_id)? {
+            if fragment.total_fragments() == total_fragments
+                && fragment.current_fragment() <= total_fragments
+            {
+                buffer.insert_fragment(fragment);
+            }
+        }
+
+        if !buffer.is_complete {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("set {} is incomplete in the backend", set_id),
+            ));
+        }
+
+        self.spilled_sets.remove(&set_id);
+        self.memory_usage += buffer.payload_size();
+        self.reconstructed_sets.insert(set_id, buffer);
+        Ok(())
+    }
+
+    /// Given id of *any* one of the sets into which fully received message was divided,
+    /// loads all of its spilled sets back into memory.
+    fn restore_spilled_message(&mut self, set_id: i32) -> io::Result<()> {
+        debug_assert!(self.is_message_fully_received(set_id));
+        let starting_id = self.find_starting_set_id(set_id).unwrap();
+        let set_id_sequence: Vec<_> =
+            std::iter::successors(Some(starting_id), |&id| self.next_linked_set_id(id)).collect();
+
+        for id in set_id_sequence {
+            self.restore_spilled_set(id)?;
+        }
+        Ok(())
+    }
+
+    /// Given id of *any* one of the sets into which message was divided, removes all of them.
+    fn remove_message(&mut self, set_id: i32) {
+        let starting_id = self.find_starting_set_id(set_id).unwrap_or(set_id);
+        let set_id_sequence: Vec<_> = std::iter::successors(Some(starting_id), |&id| {
+            if self.is_set_fully_received(id) {
+                self.next_linked_set_id(id)
+            } else {
+                None
+            }
+        })
+        .collect();
+
+        for id in set_id_sequence {
+            self.remove_set(id);
+        }
+    }
+
     /// Given fully received set of given `id`, if it has any post-linked sets, recursively
     /// checks if all of them were also fully received.
     fn check_front_chain(&self, id: i32) -> bool {
@@ -186,6 +586,9 @@ impl MessageReconstructor {
     /// Check if set of given `id` is present in the `MessageReconstructor`, and if so,
     /// whether it has received all `Fragment`s it expected to get.
     fn is_set_fully_received(&self, id: i32) -> bool {
+        if let Some(spilled) = self.spilled_sets.get(&id) {
+            return spilled.is_complete();
+        }
         self.reconstructed_sets
             .get(&id)
             .map(|set_buf| set_buf.is_complete)
@@ -217,6 +620,9 @@ impl MessageReconstructor {
     /// Note, before you call this method, you *must* ensure set was fully received
     fn previous_linked_set_id(&self, id: i32) -> Option<i32> {
         debug_assert!(self.is_set_fully_received(id));
+        if let Some(spilled) = self.spilled_sets.get(&id) {
+            return spilled.previous_fragments_set_id;
+        }
         self.reconstructed_sets
             .get(&id)
             .unwrap()
@@ -227,6 +633,9 @@ impl MessageReconstructor {
     /// Note, before you call this method, you *must* ensure set was fully received
     fn next_linked_set_id(&self, id: i32) -> Option<i32> {
         debug_assert!(self.is_set_fully_received(id));
+        if let Some(spilled) = self.spilled_sets.get(&id) {
+            return spilled.next_fragments_set_id;
+        }
         self.reconstructed_sets
             .get(&id)
             .unwrap()
@@ -237,10 +646,9 @@ impl MessageReconstructor {
     /// Note, before you call this method, you *must* ensure set was fully received
     fn extract_set_payload(&mut self, set_id: i32) -> Vec<u8> {
         debug_assert!(self.is_set_fully_received(set_id));
-        self.reconstructed_sets
-            .remove(&set_id)
-            .unwrap()
-            .reconstruct_set_data()
+        let buffer = self.reconstructed_sets.remove(&set_id).unwrap();
+        self.memory_usage = self.memory_usage.saturating_sub(buffer.payload_size());
+        buffer.reconstruct_set_data()
     }
 
     // Future consideration: perhaps for long messages, rather than return whole data allocated
@@ -263,25 +671,235 @@ impl MessageReconstructor {
         (message_content, set_id_sequence)
     }
 
+    /// Remembers that the set has been reconstructed, forgetting the oldest such set if needed.
+    fn record_completed_set(&mut self, set_id: i32) {
+        if self.recently_completed_sets.len() >= MAX_RECENTLY_COMPLETED_SETS {
+            self.recently_completed_sets.pop_front();
+        }
+        self.recently_completed_sets.push_back(set_id);
+    }
+
+    /// Stores the received parity `Fragment` until its set is complete.
+    fn insert_parity_fragment(&mut self, fragment: Fragment) {
+        let set_id = fragment.id();
+        // parity is no longer of any use if we have already received all the data
+        if self.is_set_fully_received(set_id) {
+            return;
+        }
+        if self.recently_completed_sets.contains(&set_id) {
+            trace!(
+                "received parity fragment of already reconstructed set {} - dropping it",
+                set_id
+            );
+            return;
+        }
+
+        let parity_fragments = fragment.parity_fragments().unwrap_or_default() as usize;
+        let buffer = self
+            .parity_fragments
+            .entry(set_id)
+            .or_insert_with(|| vec![None; parity_fragments]);
+
+        if buffer.len() != parity_fragments {
+            warn!(
+                "parity fragment {} is inconsistent with its set {} - ignoring it",
+                fragment.current_fragment(),
+                set_id
+            );
+            return;
+        }
+
+        buffer[fragment.current_fragment() as usize - 1] = Some(fragment);
+        self.record_activity(set_id);
+    }
+
+    /// Gets all received data `Fragment`s of the set, ordered by their positions,
+    /// regardless of whether the set is held in memory or not.
+    fn received_data_fragments(&self, set_id: i32, total_fragments: u8) -> Vec<Option<Fragment>> {
+        if let Some(buffer) = self.reconstructed_sets.get(&set_id) {
+            return buffer.fragments.clone();
+        }
+
+        let mut fragments = vec![None; total_fragments as usize];
+        if self.spilled_sets.contains_key(&set_id) {
+            let backend = self
+                .backend
+                .as_ref()
+                .expect("sets can only be spilled if the backend exists");
+            match backend.load_set(set_id) {
+                Ok(stored) => {
+                    for fragment in stored {
+                        if fragment.total_fragments() == total_fragments
+                            && fragment.current_fragment() <= total_fragments
+                        {
+                            let position = fragment.current_fragment() as usize - 1;
+                            fragments[position] = Some(fragment);
+                        }
+                    }
+                }
+                Err(err) => warn!(
+                    "failed to load fragments of set {} from the backend - {}",
+                    set_id, err
+                ),
+            }
+        }
+        fragments
+    }
+
+    /// If enough data and parity `Fragment`s of the set were received, recovers the missing
+    /// data `Fragment`s.
+    fn recover_missing_fragments(&mut self, set_id: i32) -> Vec<Fragment> {
+        if !self.parity_fragments.contains_key(&set_id) {
+            return Vec::new();
+        }
+        if self.is_set_fully_received(set_id) {
+            self.parity_fragments.remove(&set_id);
+            return Vec::new();
+        }
+
+        let parity = &self.parity_fragments[&set_id];
+        let received_parity = parity.iter().flatten().count();
+        let data_fragments = match parity.iter().flatten().next() {
+            Some(fragment) => fragment.total_fragments(),
+            None => return Vec::new(),
+        };
+
+        // check whether we have enough fragments before potentially loading them from the backend
+        let received_data = match (
+            self.reconstructed_sets.get(&set_id),
+            self.spilled_sets.get(&set_id),
+        ) {
+            (Some(buffer), _) => buffer.fragments.iter().flatten().count(),
+            (None, Some(spilled)) => spilled.received.len(),
+            (None, None) => 0,
+        };
+        if received_data + received_parity < data_fragments as usize {
+            return Vec::new();
+        }
+
+        let data = self.received_data_fragments(set_id, data_fragments);
+        if data.len() != data_fragments as usize {
+            warn!(
+                "parity fragments of set {} are inconsistent with its data fragments - dropping them",
+                set_id
+            );
+            self.parity_fragments.remove(&set_id);
+            return Vec::new();
+        }
+
+        let recovered = match recover_data_fragments(&data, parity) {
+            Ok(recovered) => {
+                debug!(
+                    "recovered {} missing fragments of set {} using its parity fragments",
+                    recovered.len(),
+                    set_id
+                );
+                recovered
+            }
+            Err(err) => {
+                warn!(
+                    "failed to recover set {} using its parity fragments - {:?}. Dropping them",
+                    set_id, err
+                );
+                Vec::new()
+            }
+        };
+        self.parity_fragments.remove(&set_id);
+        recovered
+    }
+
     /// Given recovered `Fragment`, tries to insert it into an appropriate `ReconstructionBuffer`.
     /// If a buffer does not exist, a new instance is created.
     /// If it was last remaining `Fragment` for the original message, the message is reconstructed
     /// and returned alongside all (if applicable) set ids used in the message.
     pub fn insert_new_fragment(&mut self, fragment: Fragment) -> Option<ReconstructedMessage> {
+        self.maybe_remove_stale_sets();
+
+        let set_id = fragment.id();
+        let mut reconstructed = if fragment.is_parity() {
+            self.insert_parity_fragment(fragment);
+            None
+        } else {
+            self.insert_data_fragment(fragment)
+        };
+
+        // see if the received fragment allowed us to recover the rest of its set
+        for recovered in self.recover_missing_fragments(set_id) {
+            if let Some(message) = self.insert_data_fragment(recovered) {
+                reconstructed = Some(message);
+            }
+        }
+
+        reconstructed
+    }
+
+    /// Inserts the data `Fragment` into its set and reconstructs the message if it was
+    /// the last remaining `Fragment`.
+    fn insert_data_fragment(&mut self, fragment: Fragment) -> Option<ReconstructedMessage> {
         let set_id = fragment.id();
         let set_len = fragment.total_fragments();
+        self.record_activity(set_id);
+
+        let persisted = match &self.backend {
+            Some(backend) => match backend.store_fragment(&fragment) {
+                Ok(_) => true,
+                Err(err) => {
+                    error!("failed to persist fragment of set {} - {}", set_id, err);
+                    false
+                }
+            },
+            None => false,
+        };
 
-        let buf = self
-            .reconstructed_sets
-            .entry(set_id)
-            .or_insert_with(|| ReconstructionBuffer::new(set_len));
+        if let Some(spilled) = self.spilled_sets.get_mut(&set_id) {
+            if persisted {
+                spilled.record_fragment(&fragment);
+            } else {
+                error!(
+                    "set {} is no longer held in memory - its message is not going to be reconstructed",
+                    set_id
+                );
+            }
+        } else {
+            if self.backend.is_some() && !persisted {
+                self.unpersisted_sets.insert(set_id);
+            }
+
+            let buf = self
+                .reconstructed_sets
+                .entry(set_id)
+                .or_insert_with(|| ReconstructionBuffer::new(set_len));
+
+            let size_before = buf.payload_size();
+            buf.insert_fragment(fragment);
+            self.memory_usage = self.memory_usage.saturating_sub(size_before) + buf.payload_size();
+        }
 
-        buf.insert_fragment(fragment);
-        if self.is_message_fully_received(set_id) {
-            Some(self.reconstruct_message(set_id))
+        let reconstructed = if self.is_message_fully_received(set_id) {
+            match self.restore_spilled_message(set_id) {
+                Ok(_) => {
+                    let message = self.reconstruct_message(set_id);
+                    for id in &message.1 {
+                        self.remove_set(*id);
+                        self.record_completed_set(*id);
+                    }
+                    Some(message)
+                }
+                Err(err) => {
+                    error!(
+                        "failed to load fragments of message containing set {} - {}. It is not going to be reconstructed",
+                        set_id, err
+                    );
+                    self.remove_message(set_id);
+                    None
+                }
+            }
         } else {
             None
-        }
+        };
+
+        self.enforce_memory_budget();
+        reconstructed
     }
 
     /// Given raw `Fragment` data, tries to decode and return it.
@@ -1848,3 +2466,245 @@ mod message_reconstruction {
         }
     }
 }
+
+#[cfg(test)]
+mod bounded_reconstruction {
+    use super::*;
+    use crate::backend::DiskReconstructionBackend;
+    use crate::set::max_one_way_linked_set_payload_length;
+    use rand::seq::SliceRandom;
+    use rand::{thread_rng, RngCore};
+
+    // just some arbitrary value to use in tests
+    const AVAILABLE_PLAINTEXT_SIZE: usize = 1024;
+
+    fn fragmented_message(len: usize) -> (Vec<u8>, Vec<Fragment>) {
+        let mut rng = thread_rng();
+        let mut message = vec![0u8; len];
+        rng.fill_bytes(&mut message);
+
+        let mut fragments: Vec<_> =
+            crate::split_into_sets(&mut rand::rngs::OsRng, &message, AVAILABLE_PLAINTEXT_SIZE)
+                .into_iter()
+                .flatten()
+                .collect();
+        fragments.shuffle(&mut rng);
+        (message, fragments)
+    }
+
+    fn two_sets_message() -> (Vec<u8>, Vec<Fragment>) {
+        fragmented_message(max_one_way_linked_set_payload_length(AVAILABLE_PLAINTEXT_SIZE) + 12345)
+    }
+
+    #[test]
+    fn exceeding_memory_budget_without_backend_drops_least_recently_updated_sets() {
+        let (_, first_fragments) = fragmented_message(5000);
+        let (_, second_fragments) = fragmented_message(5000);
+
+        let mut reconstructor = MessageReconstructor::new().with_memory_budget(1500);
+        assert!(reconstructor
+            .insert_new_fragment(first_fragments[0].clone())
+            .is_none());
+        assert!(reconstructor
+            .insert_new_fragment(second_fragments[0].clone())
+            .is_none());
+
+        assert!(!reconstructor
+            .reconstructed_sets
+            .contains_key(&first_fragments[0].id()));
+        assert!(reconstructor
+            .reconstructed_sets
+            .contains_key(&second_fragments[0].id()));
+        assert!(reconstructor.memory_usage <= 1500);
+        assert_eq!(reconstructor.dropped_sets(), 1);
+    }
+
+    #[test]
+    fn spilled_sets_are_still_reconstructed() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = DiskReconstructionBackend::new(dir.path()).unwrap();
+
+        let (message, fragments) = two_sets_message();
+        let mut reconstructor = MessageReconstructor::new()
+            .with_backend(Arc::new(backend))
+            .unwrap()
+            .with_memory_budget(10 * AVAILABLE_PLAINTEXT_SIZE);
+
+        let mut reconstructed = None;
+        for fragment in fragments {
+            assert!(reconstructed.is_none());
+            reconstructed = reconstructor.insert_new_fragment(fragment);
+            assert!(reconstructor.memory_usage <= 10 * AVAILABLE_PLAINTEXT_SIZE);
+        }
+
+        let reconstructed = reconstructed.unwrap();
+        assert_eq!(reconstructed.0, message);
+        assert_eq!(reconstructed.1.len(), 2);
+
+        // nothing should be left behind
+        assert!(reconstructor.reconstructed_sets.is_empty());
+        assert!(reconstructor.spilled_sets.is_empty());
+        let backend = DiskReconstructionBackend::new(dir.path()).unwrap();
+        assert!(backend.stored_sets().unwrap().is_empty());
+    }
+
+    #[test]
+    fn reconstruction_is_resumed_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let (message, mut fragments) = two_sets_message();
+        let remaining = fragments.split_off(fragments.len() / 2);
+
+        let mut reconstructor = MessageReconstructor::new()
+            .with_backend(Arc::new(
+                DiskReconstructionBackend::new(dir.path()).unwrap(),
+            ))
+            .unwrap();
+        for fragment in fragments {
+            assert!(reconstructor.insert_new_fragment(fragment).is_none());
+        }
+        drop(reconstructor);
+
+        let mut reconstructor = MessageReconstructor::new()
+            .with_backend(Arc::new(
+                DiskReconstructionBackend::new(dir.path()).unwrap(),
+            ))
+            .unwrap();
+        let mut reconstructed = None;
+        for fragment in remaining {
+            assert!(reconstructed.is_none());
+            reconstructed = reconstructor.insert_new_fragment(fragment);
+        }
+
+        assert_eq!(reconstructed.unwrap().0, message);
+    }
+
+    #[test]
+    fn stale_sets_are_removed() {
+        let (_, fragments) = fragmented_message(5000);
+
+        let mut reconstructor =
+            MessageReconstructor::new().with_stale_set_timeout(Duration::from_millis(10));
+        assert!(reconstructor
+            .insert_new_fragment(fragments[0].clone())
+            .is_none());
+        assert_eq!(reconstructor.remove_stale_sets(), 0);
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(reconstructor.remove_stale_sets(), 1);
+        assert!(reconstructor.reconstructed_sets.is_empty());
+        assert_eq!(reconstructor.memory_usage, 0);
+    }
+}
+
+#[cfg(test)]
+mod erasure_coded_reconstruction {
+    use super::*;
+    use crate::backend::DiskReconstructionBackend;
+    use crate::set::max_one_way_linked_set_payload_length;
+    use rand::{thread_rng, RngCore};
+
+    // just some arbitrary value to use in tests
+    const AVAILABLE_PLAINTEXT_SIZE: usize = 1024;
+
+    fn erasure_coded_message(len: usize, redundancy: f64) -> (Vec<u8>, Vec<Vec<Fragment>>) {
+        let mut message = vec![0u8; len];
+        thread_rng().fill_bytes(&mut message);
+
+        let sets = crate::split_into_sets_with_parity(
+            &mut rand::rngs::OsRng,
+            &message,
+            AVAILABLE_PLAINTEXT_SIZE,
+            redundancy,
+        );
+        (message, sets)
+    }
+
+    #[test]
+    fn message_is_reconstructed_with_missing_data_fragments() {
+        let (message, sets) = erasure_coded_message(10000, 0.3);
+        let set = &sets[0];
+        assert!(set.iter().any(Fragment::is_parity));
+
+        let mut reconstructor = MessageReconstructor::new();
+        // drop the first two data fragments
+        let (reconstructed_message, used_sets) = set
+            .iter()
+            .skip(2)
+            .find_map(|fragment| reconstructor.insert_new_fragment(fragment.clone()))
+            .unwrap();
+        assert_eq!(reconstructed_message, message);
+        assert_eq!(used_sets, vec![set[0].id()]);
+        assert!(reconstructor.parity_fragments.is_empty());
+        assert!(reconstructor.reconstructed_sets.is_empty());
+    }
+
+    #[test]
+    fn parity_fragments_are_dropped_once_set_is_complete() {
+        let (message, sets) = erasure_coded_message(
+            max_one_way_linked_set_payload_length(AVAILABLE_PLAINTEXT_SIZE) + 1234,
+            0.1,
+        );
+        assert_eq!(sets.len(), 2);
+
+        let mut reconstructor = MessageReconstructor::new();
+        // deliver all data fragments of the first set before any of its parity
+        let (first_data, first_parity): (Vec<_>, Vec<_>) = sets[0]
+            .iter()
+            .cloned()
+            .partition(|fragment| !fragment.is_parity());
+        for fragment in first_data.into_iter().chain(first_parity) {
+            assert!(reconstructor.insert_new_fragment(fragment).is_none());
+        }
+        assert!(reconstructor.parity_fragments.is_empty());
+
+        let reconstructed = sets[1]
+            .iter()
+            .skip(1)
+            .find_map(|fragment| reconstructor.insert_new_fragment(fragment.clone()))
+            .unwrap();
+        assert_eq!(reconstructed.0, message);
+    }
+
+    #[test]
+    fn late_parity_fragments_of_reconstructed_sets_are_dropped() {
+        let (message, sets) = erasure_coded_message(10000, 0.3);
+        let (data, parity): (Vec<_>, Vec<_>) = sets[0]
+            .iter()
+            .cloned()
+            .partition(|fragment| !fragment.is_parity());
+        assert!(!parity.is_empty());
+
+        let mut reconstructor = MessageReconstructor::new();
+        let reconstructed = data
+            .into_iter()
+            .find_map(|fragment| reconstructor.insert_new_fragment(fragment))
+            .unwrap();
+        assert_eq!(reconstructed.0, message);
+
+        for fragment in parity {
+            assert!(reconstructor.insert_new_fragment(fragment).is_none());
+        }
+        assert!(reconstructor.parity_fragments.is_empty());
+        assert!(reconstructor.sets_activity.is_empty());
+    }
+
+    #[test]
+    fn spilled_sets_can_be_recovered_with_parity_fragments() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = Arc::new(DiskReconstructionBackend::new(dir.path()).unwrap());
+
+        let (message, sets) = erasure_coded_message(10000, 0.3);
+        let mut reconstructor = MessageReconstructor::new()
+            .with_backend(backend)
+            .unwrap()
+            .with_memory_budget(0);
+
+        let reconstructed = sets[0]
+            .iter()
+            .skip(1)
+            .find_map(|fragment| reconstructor.insert_new_fragment(fragment.clone()))
+            .unwrap();
+        assert_eq!(reconstructed.0, message);
+        assert!(reconstructor.spilled_sets.is_empty());
+    }
+}
@@ -1,6 +1,7 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::erasure::{encode_parity_fragments, parity_fragments_count, PARITY_FRAGMENT_OVERHEAD};
 use crate::fragment::{
     linked_fragment_payload_max_len, unlinked_fragment_payload_max_len, Fragment,
     LINKED_FRAGMENTED_HEADER_LEN, UNLINKED_FRAGMENTED_HEADER_LEN,
@@ -293,6 +294,33 @@ pub fn split_into_sets<R: Rng>(
     }
 }
 
+/// Splits the message into [`Set`]s just like [`split_into_sets`], but additionally attaches
+/// parity `Fragment`s to each of them, so that any `k` out of `k + m` `Fragment`s
+/// are sufficient to reconstruct a set of `k` data `Fragment`s.
+/// The `redundancy` determines number of parity `Fragment`s relative to the data ones,
+/// i.e. `m = ceil(k * redundancy)`, but there's always at least a single one.
+///
+/// Note that the data `Fragment`s carry `PARITY_FRAGMENT_OVERHEAD` bytes less payload
+/// than they would have without the erasure coding.
+pub fn split_into_sets_with_parity<R: Rng>(
+    rng: &mut R,
+    message: &[u8],
+    max_plaintext_size: usize,
+    redundancy: f64,
+) -> Vec<FragmentSet> {
+    split_into_sets(rng, message, max_plaintext_size - PARITY_FRAGMENT_OVERHEAD)
+        .into_iter()
+        .map(|mut set| {
+            let parity_fragments = parity_fragments_count(set.len(), redundancy);
+            // this can only fail if the data fragments were malformed, but we've just created them
+            let parity = encode_parity_fragments(&set, parity_fragments, max_plaintext_size)
+                .expect("failed to erasure code a freshly created fragment set");
+            set.extend(parity);
+            set
+        })
+        .collect()
+}
+
 // reason for top level tests module is to be able to use the helper functions to verify sets payloads
 #[cfg(test)]
 mod tests {
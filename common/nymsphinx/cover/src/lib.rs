@@ -10,9 +10,7 @@ use nymsphinx_addressing::nodes::{NymNodeRoutingAddress, NymNodeRoutingAddressEr
 use nymsphinx_chunking::fragment::COVER_FRAG_ID;
 use nymsphinx_forwarding::packet::MixPacket;
 use nymsphinx_params::packet_sizes::PacketSize;
-use nymsphinx_params::{
-    PacketEncryptionAlgorithm, PacketHkdfAlgorithm, PacketMode, DEFAULT_NUM_MIX_HOPS,
-};
+use nymsphinx_params::{PacketEncryptionAlgorithm, PacketHkdfAlgorithm, PacketMode};
 use nymsphinx_types::builder::SphinxPacketBuilder;
 use nymsphinx_types::{delays, Error as SphinxError};
 use rand::{CryptoRng, RngCore};
@@ -55,6 +53,7 @@ pub fn generate_loop_cover_surb_ack<R>(
     ack_key: &AckKey,
     full_address: &Recipient,
     average_ack_delay: time::Duration,
+    num_mix_hops: u8,
 ) -> Result<SurbAck, CoverMessageError>
 where
     R: RngCore + CryptoRng,
@@ -66,6 +65,7 @@ where
         COVER_FRAG_ID.to_bytes(),
         average_ack_delay,
         topology,
+        num_mix_hops,
     )?)
 }
 
@@ -77,14 +77,21 @@ pub fn generate_loop_cover_packet<R>(
     average_ack_delay: time::Duration,
     average_packet_delay: time::Duration,
     packet_size: PacketSize,
+    num_mix_hops: u8,
 ) -> Result<MixPacket, CoverMessageError>
 where
     R: RngCore + CryptoRng,
 {
     // we don't care about total ack delay - we will not be retransmitting it anyway
-    let (_, ack_bytes) =
-        generate_loop_cover_surb_ack(rng, topology, ack_key, full_address, average_ack_delay)?
-            .prepare_for_sending();
+    let (_, ack_bytes) = generate_loop_cover_surb_ack(
+        rng,
+        topology,
+        ack_key,
+        full_address,
+        average_ack_delay,
+        num_mix_hops,
+    )?
+    .prepare_for_sending();
 
     // cover message can't be distinguishable from a normal traffic so we have to go through
     // all the effort of key generation, encryption, etc. Note here we are generating shared key
@@ -122,8 +129,7 @@ where
         .chain(cover_content.into_iter())
         .collect();
 
-    let route =
-        topology.random_route_to_gateway(rng, DEFAULT_NUM_MIX_HOPS, full_address.gateway())?;
+    let route = topology.random_route_to_gateway(rng, num_mix_hops, full_address.gateway())?;
     let delays = delays::generate_from_average_duration(route.len(), average_packet_delay);
     let destination = full_address.as_sphinx_destination();
 
@@ -106,6 +106,13 @@ impl MixPacket {
         }
     }
 
+    pub fn to_bytes(&self) -> Vec<u8> {
+        std::iter::once(self.packet_mode as u8)
+            .chain(self.next_hop.as_bytes().into_iter())
+            .chain(self.sphinx_packet.to_bytes().into_iter())
+            .collect()
+    }
+
     pub fn into_bytes(self) -> Vec<u8> {
         std::iter::once(self.packet_mode as u8)
             .chain(self.next_hop.as_bytes().into_iter())
@@ -18,6 +18,10 @@ pub mod packet_sizes;
 // I will change this to [`usize`]
 pub const DEFAULT_NUM_MIX_HOPS: u8 = 3;
 
+/// Maximum number of mix hops a packet can take. It's limited by the sphinx header that has to also
+/// accommodate the final gateway hop.
+pub const MAX_NUM_MIX_HOPS: u8 = nymsphinx_types::MAX_PATH_LENGTH as u8 - 1;
+
 // TODO: not entirely sure how to feel about those being defined here, ideally it'd be where [`Fragment`]
 // is defined, but that'd introduce circular dependencies as the acknowledgements crate also needs
 // access to that
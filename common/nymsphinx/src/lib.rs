@@ -5,6 +5,12 @@ pub mod preparer;
 pub mod receiver;
 pub mod utils;
 
+/// Prefix of the messages that are processed by the client of their recipient itself, rather than
+/// being delivered to its application. The regular messages are prefixed with either 0 or 1,
+/// depending on whether they carry a reply SURB, so the clients unaware of the control messages
+/// reject them as malformed.
+pub(crate) const CONTROL_MESSAGE_PREFIX: u8 = 2;
+
 // re-export sub-crates
 pub use nymsphinx_acknowledgements as acknowledgements;
 pub use nymsphinx_addressing as addressing;
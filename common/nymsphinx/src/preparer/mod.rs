@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::chunking;
+use crate::CONTROL_MESSAGE_PREFIX;
 use crypto::asymmetric::encryption;
 use crypto::shared_key::new_ephemeral_shared_key;
 use crypto::symmetric::stream_cipher;
@@ -12,6 +13,7 @@ use nymsphinx_addressing::clients::Recipient;
 use nymsphinx_addressing::nodes::{NymNodeRoutingAddress, MAX_NODE_ADDRESS_UNPADDED_LEN};
 use nymsphinx_anonymous_replies::encryption_key::SurbEncryptionKey;
 use nymsphinx_anonymous_replies::reply_surb::ReplySurb;
+use nymsphinx_chunking::erasure::PARITY_FRAGMENT_OVERHEAD;
 use nymsphinx_chunking::fragment::{Fragment, FragmentIdentifier};
 use nymsphinx_forwarding::packet::MixPacket;
 use nymsphinx_params::packet_sizes::PacketSize;
@@ -76,6 +78,10 @@ pub struct MessagePreparer<R: CryptoRng + Rng> {
     /// Number of mix hops each packet ('real' message, ack, reply) is expected to take.
     /// Note that it does not include gateway hops.
     num_mix_hops: u8,
+
+    /// If set, each set of fragments is erasure coded with the specified ratio of parity
+    /// to data fragments.
+    erasure_coding_redundancy: Option<f64>,
 }
 
 impl<R> MessagePreparer<R>
@@ -95,6 +101,7 @@ where
             average_packet_delay,
             average_ack_delay,
             num_mix_hops: DEFAULT_NUM_MIX_HOPS,
+            erasure_coding_redundancy: None,
         }
     }
 
@@ -104,6 +111,19 @@ where
         self
     }
 
+    /// Returns the number of mix hops each prepared packet is going to go through.
+    pub fn num_mix_hops(&self) -> u8 {
+        self.num_mix_hops
+    }
+
+    /// Makes the preparer attach parity fragments to each set the message is split into,
+    /// so that the recipient could reconstruct it despite some of the packets being lost.
+    /// The redundancy is the ratio of parity to data fragments.
+    pub fn with_erasure_coding(mut self, redundancy: f64) -> Self {
+        self.erasure_coding_redundancy = Some(redundancy);
+        self
+    }
+
     /// Allows setting non-default size of the sphinx packets sent out.
     pub fn with_custom_real_message_packet_size(mut self, packet_size: PacketSize) -> Self {
         self.packet_size = packet_size;
@@ -132,6 +152,15 @@ where
         self.packet_size.plaintext_size() - ack_overhead - ephemeral_public_key_overhead
     }
 
+    /// Length of plaintext data of the underlying message that can be put in each data fragment.
+    /// It's smaller than `available_plaintext_per_packet` if the message is erasure coded.
+    fn available_plaintext_per_data_fragment(&self) -> usize {
+        match self.erasure_coding_redundancy {
+            Some(_) => self.available_plaintext_per_packet() - PARITY_FRAGMENT_OVERHEAD,
+            None => self.available_plaintext_per_packet(),
+        }
+    }
+
     /// Pads the message so that after it gets chunked, it will occupy exactly N sphinx packets.
     /// Produces new_message = message || 1 || 0000....
     fn pad_message(&self, message: Vec<u8>) -> Vec<u8> {
@@ -139,7 +168,7 @@ where
         // to be able to later distinguish the actual padding from the underlying message
         let (_, space_left) = chunking::number_of_required_fragments(
             message.len() + 1,
-            self.available_plaintext_per_packet(),
+            self.available_plaintext_per_data_fragment(),
         );
 
         message
@@ -166,6 +195,7 @@ where
                 &self.sender_address,
                 self.average_packet_delay,
                 topology,
+                self.num_mix_hops,
             )?;
 
             let reply_key = reply_surb.encryption_key();
@@ -191,8 +221,16 @@ where
     /// Splits the message into [`Fragment`] that are going to be put later put into sphinx packets.
     fn split_message(&mut self, message: Vec<u8>) -> Vec<Fragment> {
         let plaintext_per_packet = self.available_plaintext_per_packet();
-        chunking::split_into_sets(&mut self.rng, &message, plaintext_per_packet)
-            .into_iter()
+        let sets = match self.erasure_coding_redundancy {
+            Some(redundancy) => chunking::split_into_sets_with_parity(
+                &mut self.rng,
+                &message,
+                plaintext_per_packet,
+                redundancy,
+            ),
+            None => chunking::split_into_sets(&mut self.rng, &message, plaintext_per_packet),
+        };
+        sets.into_iter()
             .flat_map(|fragment_set| fragment_set.into_iter())
             .collect()
     }
@@ -307,9 +345,25 @@ where
             fragment_id.to_bytes(),
             self.average_ack_delay,
             topology,
+            self.num_mix_hops,
         )
     }
 
+    /// Marks the message as a control message, meant for the client of the recipient rather than
+    /// its application, attaches correct padding and splits it into [`Fragment`] that can be
+    /// later packed into sphinx packets to be sent through the mix network.
+    /// Results in:
+    /// new_message = 2 || message || padding
+    pub fn prepare_and_split_control_message(&mut self, message: Vec<u8>) -> Vec<Fragment> {
+        let message = std::iter::once(CONTROL_MESSAGE_PREFIX)
+            .chain(message.into_iter())
+            .collect();
+
+        let message = self.pad_message(message);
+
+        self.split_message(message)
+    }
+
     /// Attaches an optional reply-surb and correct padding to the underlying message
     /// and splits it into [`Fragment`] that can be later packed into sphinx packets to be
     /// sent through the mix network.
@@ -416,6 +470,7 @@ where
             average_packet_delay: Default::default(),
             average_ack_delay: Default::default(),
             num_mix_hops: DEFAULT_NUM_MIX_HOPS,
+            erasure_coding_redundancy: None,
         }
     }
 }
@@ -1,6 +1,7 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::CONTROL_MESSAGE_PREFIX;
 use crypto::asymmetric::encryption;
 use crypto::shared_key::recompute_shared_key;
 use crypto::symmetric::stream_cipher;
@@ -19,6 +20,17 @@ pub struct ReconstructedMessage {
     pub reply_surb: Option<ReplySurb>,
 }
 
+/// Message reconstructed from the received fragments.
+#[derive(Debug)]
+pub enum RecoveredMessage {
+    /// Message meant for the application of the client.
+    Application(ReconstructedMessage),
+
+    /// Message meant for the client itself, such as a notice of the sender having moved
+    /// to a different gateway.
+    Control(Vec<u8>),
+}
+
 #[derive(Debug)]
 pub enum MessageRecoveryError {
     InvalidSurbPrefixError,
@@ -64,6 +76,14 @@ impl MessageReceiver {
         self
     }
 
+    /// Allows using a non-default `MessageReconstructor`, for example one that is backed
+    /// by a persistent storage or has a bounded memory usage.
+    #[must_use]
+    pub fn with_reconstructor(mut self, reconstructor: MessageReconstructor) -> Self {
+        self.reconstructor = reconstructor;
+        self
+    }
+
     /// Parses the message to strip and optionally recover reply SURB.
     fn recover_reply_surb_from_message(
         &self,
@@ -139,13 +159,22 @@ impl MessageReceiver {
     /// and returned alongside all (if applicable) set ids used in the message.
     ///
     /// # Returns:
-    /// - The reconstructed message alongside optional reply SURB,
+    /// - The reconstructed message, which is either a control message or an application message
+    ///   alongside optional reply SURB,
     /// - List of ids of all the [`Set`]s used during reconstruction to detect stale retransmissions.
     pub fn insert_new_fragment(
         &mut self,
         fragment: Fragment,
-    ) -> Result<Option<(ReconstructedMessage, Vec<i32>)>, MessageRecoveryError> {
+    ) -> Result<Option<(RecoveredMessage, Vec<i32>)>, MessageRecoveryError> {
         if let Some((mut message, used_sets)) = self.reconstructor.insert_new_fragment(fragment) {
+            if message.first() == Some(&CONTROL_MESSAGE_PREFIX) {
+                message.remove(0);
+                Self::remove_padding(&mut message).map_err(|_| {
+                    MessageRecoveryError::MalformedReconstructedMessage(used_sets.clone())
+                })?;
+                return Ok(Some((RecoveredMessage::Control(message), used_sets)));
+            }
+
             // Split message into plaintext and reply-SURB
             let reply_surb = match self.recover_reply_surb_from_message(&mut message) {
                 Ok(reply_surb) => reply_surb,
@@ -162,10 +191,10 @@ impl MessageReceiver {
             })?;
 
             Ok(Some((
-                ReconstructedMessage {
+                RecoveredMessage::Application(ReconstructedMessage {
                     message,
                     reply_surb,
-                },
+                }),
                 used_sets,
             )))
         } else {
@@ -215,6 +244,7 @@ mod message_receiver {
                     "B3GzG62aXAZNg14RoMCp3BhELNBrySLr2JqrwyfYFzRc",
                 )
                 .unwrap(),
+                pending_sphinx_key: None,
                 layer: Layer::One,
                 version: "0.8.0-dev".to_string(),
             }],
@@ -235,6 +265,7 @@ mod message_receiver {
                     "5Z1VqYwM2xeKxd8H7fJpGWasNiDFijYBAee7MErkZ5QT",
                 )
                 .unwrap(),
+                pending_sphinx_key: None,
                 layer: Layer::Two,
                 version: "0.8.0-dev".to_string(),
             }],
@@ -255,6 +286,7 @@ mod message_receiver {
                     "9EyjhCggr2QEA2nakR88YHmXgpy92DWxoe2draDRkYof",
                 )
                 .unwrap(),
+                pending_sphinx_key: None,
                 layer: Layer::Three,
                 version: "0.8.0-dev".to_string(),
             }],
@@ -278,6 +310,7 @@ mod message_receiver {
                     "EB42xvMFMD5rUCstE2CDazgQQJ22zLv8SPm1Luxni44c",
                 )
                 .unwrap(),
+                pending_sphinx_key: None,
                 version: "0.8.0-dev".to_string(),
             }],
         )
@@ -293,8 +326,14 @@ mod message_receiver {
         let average_delay = Duration::from_millis(500);
         let topology = topology_fixture();
 
-        let reply_surb =
-            ReplySurb::construct(&mut OsRng, &dummy_recipient, average_delay, &topology).unwrap();
+        let reply_surb = ReplySurb::construct(
+            &mut OsRng,
+            &dummy_recipient,
+            average_delay,
+            &topology,
+            DEFAULT_NUM_MIX_HOPS,
+        )
+        .unwrap();
 
         let reply_surb_bytes = reply_surb.to_bytes();
 
@@ -319,4 +358,20 @@ mod message_receiver {
         assert_eq!(received_with_surb, message);
         assert_eq!(reply_surb_bytes, reply_surb.unwrap().to_bytes());
     }
+    #[test]
+    fn control_messages_are_told_apart_from_application_messages() {
+        let mut preparer = crate::preparer::MessagePreparer::<OsRng>::test_fixture();
+        let mut message_receiver = MessageReceiver::new();
+        let message = b"foomp".to_vec();
+
+        let mut recovered = None;
+        for fragment in preparer.prepare_and_split_control_message(message.clone()) {
+            recovered = message_receiver.insert_new_fragment(fragment).unwrap();
+        }
+
+        match recovered.unwrap().0 {
+            RecoveredMessage::Control(control) => assert_eq!(control, message),
+            other => panic!("expected a control message, got {:?}", other),
+        }
+    }
 }
@@ -34,6 +34,9 @@ pub enum ControllerCommand {
     Insert(ConnectionId, ConnectionSender),
     Remove(ConnectionId),
     Send(ConnectionId, Vec<u8>, bool),
+
+    /// Forwards an unordered datagram to the connection, bypassing its `OrderedMessageBuffer`.
+    SendDatagram(ConnectionId, Vec<u8>),
 }
 
 struct ActiveConnection {
@@ -180,6 +183,30 @@ impl Controller {
         }
     }
 
+    fn send_datagram_to_connection(&mut self, conn_id: ConnectionId, payload: Vec<u8>) {
+        // datagrams are independent of each other, so there's no point in buffering them
+        // if the connection doesn't exist (anymore)
+        match self.active_connections.get_mut(&conn_id) {
+            Some(active_connection) => {
+                if let Some(connection_sender) = active_connection.connection_sender.as_mut() {
+                    if connection_sender
+                        .unbounded_send(ConnectionMessage {
+                            payload,
+                            socket_closed: false,
+                        })
+                        .is_err()
+                    {
+                        debug!("Connection {} is no longer receiving datagrams", conn_id)
+                    }
+                }
+            }
+            None => debug!(
+                "Received a datagram for unknown connection {} - it's going to be dropped",
+                conn_id
+            ),
+        }
+    }
+
     pub async fn run(&mut self) {
         loop {
             tokio::select! {
@@ -191,6 +218,9 @@ impl Controller {
                         self.insert_connection(conn_id, sender)
                     }
                     Some(ControllerCommand::Remove(conn_id)) => self.remove_connection(conn_id),
+                    Some(ControllerCommand::SendDatagram(conn_id, data)) => {
+                        self.send_datagram_to_connection(conn_id, data)
+                    }
                     None => {
                         log::trace!("SOCKS5 Controller: Stopping since channel closed");
                         break;
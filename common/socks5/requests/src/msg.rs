@@ -5,7 +5,7 @@ use thiserror::Error;
 
 use crate::network_requester_response::{Error as NrError, NetworkRequesterResponse};
 use crate::request::{Request, RequestError};
-use crate::response::{Response, ResponseError};
+use crate::response::{DatagramResponse, Response, ResponseError};
 
 #[derive(Debug, Error)]
 pub enum MessageError {
@@ -29,21 +29,25 @@ pub enum Message {
     Request(Request),
     Response(Response),
     NetworkRequesterResponse(NetworkRequesterResponse),
+    DatagramResponse(DatagramResponse),
 }
 
 impl Message {
     const REQUEST_FLAG: u8 = 0;
     const RESPONSE_FLAG: u8 = 1;
     const NR_RESPONSE_FLAG: u8 = 2;
+    const DATAGRAM_RESPONSE_FLAG: u8 = 3;
 
     pub fn conn_id(&self) -> u64 {
         match self {
             Message::Request(req) => match req {
                 Request::Connect(c) => c.conn_id,
                 Request::Send(conn_id, _, _) => *conn_id,
+                Request::Datagram(d) => d.conn_id,
             },
             Message::Response(resp) => resp.connection_id,
             Message::NetworkRequesterResponse(resp) => resp.connection_id,
+            Message::DatagramResponse(resp) => resp.connection_id,
         }
     }
 
@@ -52,9 +56,11 @@ impl Message {
             Message::Request(req) => match req {
                 Request::Connect(_) => 0,
                 Request::Send(_, data, _) => data.len(),
+                Request::Datagram(d) => d.data.len(),
             },
             Message::Response(resp) => resp.data.len(),
             Message::NetworkRequesterResponse(_) => 0,
+            Message::DatagramResponse(resp) => resp.data.len(),
         }
     }
 
@@ -75,6 +81,10 @@ impl Message {
             NetworkRequesterResponse::try_from_bytes(&b[1..])
                 .map(Message::NetworkRequesterResponse)
                 .map_err(MessageError::NetworkRequesterResponseError)
+        } else if b[0] == Self::DATAGRAM_RESPONSE_FLAG {
+            DatagramResponse::try_from_bytes(&b[1..])
+                .map(Message::DatagramResponse)
+                .map_err(MessageError::Response)
         } else {
             Err(MessageError::UnknownMessageType)
         }
@@ -91,6 +101,9 @@ impl Message {
             Self::NetworkRequesterResponse(r) => std::iter::once(Self::NR_RESPONSE_FLAG)
                 .chain(r.into_bytes().iter().cloned())
                 .collect(),
+            Self::DatagramResponse(r) => std::iter::once(Self::DATAGRAM_RESPONSE_FLAG)
+                .chain(r.into_bytes().iter().cloned())
+                .collect(),
         }
     }
 }
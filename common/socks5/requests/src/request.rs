@@ -13,6 +13,7 @@ pub type RemoteAddress = String;
 pub enum RequestFlag {
     Connect = 0,
     Send = 1,
+    Datagram = 2,
 }
 
 #[derive(Debug, Error)]
@@ -52,6 +53,7 @@ impl TryFrom<u8> for RequestFlag {
         match value {
             _ if value == (RequestFlag::Connect as u8) => Ok(Self::Connect),
             _ if value == (RequestFlag::Send as u8) => Ok(Self::Send),
+            _ if value == (RequestFlag::Datagram as u8) => Ok(Self::Datagram),
             _ => Err(RequestError::UnknownRequestFlag),
         }
     }
@@ -64,6 +66,14 @@ pub struct ConnectRequest {
     pub return_address: Recipient,
 }
 
+#[derive(Debug)]
+pub struct DatagramRequest {
+    pub conn_id: ConnectionId,
+    pub remote_addr: RemoteAddress,
+    pub return_address: Recipient,
+    pub data: Vec<u8>,
+}
+
 /// A request from a SOCKS5 client that a Nym Socks5 service provider should
 /// take an action for an application using a (probably local) Nym Socks5 proxy.
 #[derive(Debug)]
@@ -75,6 +85,11 @@ pub enum Request {
 
     /// Re-use an existing TCP connection, sending more request data up it.
     Send(ConnectionId, Vec<u8>, bool),
+
+    /// Send a single UDP datagram to the specified `RemoteAddress`. Datagrams are independent
+    /// of each other and are not ordered.
+    /// All datagrams received back on this `ConnectionId` should be sent to the specified `Recipient`
+    Datagram(Box<DatagramRequest>),
 }
 
 impl Request {
@@ -96,6 +111,49 @@ impl Request {
         Request::Send(conn_id, data, local_closed)
     }
 
+    /// Construct a new Request::Datagram instance
+    pub fn new_datagram(
+        conn_id: ConnectionId,
+        remote_addr: RemoteAddress,
+        data: Vec<u8>,
+        return_address: Recipient,
+    ) -> Request {
+        Request::Datagram(Box::new(DatagramRequest {
+            conn_id,
+            remote_addr,
+            return_address,
+            data,
+        }))
+    }
+
+    /// Recovers the remote address, prefixed with its length, from the beginning of the provided
+    /// bytes. The remaining bytes are returned alongside it.
+    fn parse_remote_address(b: &[u8]) -> Result<(RemoteAddress, &[u8]), RequestError> {
+        // we need to be able to read at least 2 bytes that specify address length
+        if b.len() < 2 {
+            return Err(RequestError::AddressLengthTooShort);
+        }
+
+        let address_length = u16::from_be_bytes([b[0], b[1]]) as usize;
+
+        if b.len() < 2 + address_length {
+            return Err(RequestError::AddressTooShort);
+        }
+
+        let address_start = 2;
+        let address_end = address_start + address_length;
+        let address_bytes = &b[address_start..address_end];
+        let remote_address = String::from_utf8_lossy(address_bytes).to_string();
+
+        Ok((remote_address, &b[address_end..]))
+    }
+
+    fn parse_return_address(b: &[u8]) -> Result<Recipient, RequestError> {
+        let mut return_bytes = [0u8; Recipient::LEN];
+        return_bytes.copy_from_slice(&b[..Recipient::LEN]);
+        Recipient::try_from_bytes(return_bytes).map_err(RequestError::MalformedReturnAddress)
+    }
+
     /// Deserialize the request type, connection id, destination address and port,
     /// and the request body from bytes.
     ///
@@ -109,6 +167,14 @@ impl Request {
     /// The request_flag tells us whether this is a new connection request (`new_connect`),
     /// an already-established connection we should send up (`new_send`), or
     /// a request to close an established connection (`new_close`).
+    ///
+    /// Datagram requests (`new_datagram`) are laid out slightly differently, as the return
+    /// address precedes the data:
+    ///
+    /// ------------------------------------------------------------------------------------------------
+    ///  request_flag | connection_id | address_length | remote_address_bytes | return_address | data |
+    ///        1      |       8       |      2         |    address_length    | Recipient::LEN | ...  |
+    /// ------------------------------------------------------------------------------------------------
     pub fn try_from_bytes(b: &[u8]) -> Result<Request, RequestError> {
         // each request needs to at least contain flag and ConnectionId
         if b.is_empty() {
@@ -121,37 +187,12 @@ impl Request {
         let connection_id = u64::from_be_bytes([b[1], b[2], b[3], b[4], b[5], b[6], b[7], b[8]]);
         match RequestFlag::try_from(b[0])? {
             RequestFlag::Connect => {
-                let connect_request_bytes = &b[9..];
-
-                // we need to be able to read at least 2 bytes that specify address length
-                if connect_request_bytes.len() < 2 {
-                    return Err(RequestError::AddressLengthTooShort);
-                }
-
-                let address_length =
-                    u16::from_be_bytes([connect_request_bytes[0], connect_request_bytes[1]])
-                        as usize;
-
-                if connect_request_bytes.len() < 2 + address_length {
-                    return Err(RequestError::AddressTooShort);
-                }
-
-                let address_start = 2;
-                let address_end = address_start + address_length;
-                let address_bytes = &connect_request_bytes[address_start..address_end];
-                let remote_address = String::from_utf8_lossy(address_bytes).to_string();
-
-                // just a temporary reference to mid-slice for ease of use
-                let recipient_data_bytes = &connect_request_bytes[address_end..];
+                let (remote_address, recipient_data_bytes) = Self::parse_remote_address(&b[9..])?;
 
                 if recipient_data_bytes.len() != Recipient::LEN {
                     return Err(RequestError::ReturnAddressTooShort);
                 }
-
-                let mut return_bytes = [0u8; Recipient::LEN];
-                return_bytes.copy_from_slice(&recipient_data_bytes[..Recipient::LEN]);
-                let return_address = Recipient::try_from_bytes(return_bytes)
-                    .map_err(RequestError::MalformedReturnAddress)?;
+                let return_address = Self::parse_return_address(recipient_data_bytes)?;
 
                 Ok(Request::new_connect(
                     connection_id,
@@ -165,6 +206,22 @@ impl Request {
 
                 Ok(Request::Send(connection_id, data, local_closed))
             }
+            RequestFlag::Datagram => {
+                let (remote_address, remaining) = Self::parse_remote_address(&b[9..])?;
+
+                if remaining.len() < Recipient::LEN {
+                    return Err(RequestError::ReturnAddressTooShort);
+                }
+                let return_address = Self::parse_return_address(remaining)?;
+                let data = remaining[Recipient::LEN..].to_vec();
+
+                Ok(Request::new_datagram(
+                    connection_id,
+                    remote_address,
+                    data,
+                    return_address,
+                ))
+            }
         }
     }
 
@@ -190,6 +247,19 @@ impl Request {
                 .chain(std::iter::once(local_closed as u8))
                 .chain(data.into_iter())
                 .collect(),
+            // datagram is: DATAGRAM_FLAG || CONN_ID || REMOTE_LEN || REMOTE || RETURN || DATA
+            Request::Datagram(req) => {
+                let remote_address_bytes = req.remote_addr.into_bytes();
+                let remote_address_bytes_len = remote_address_bytes.len() as u16;
+
+                std::iter::once(RequestFlag::Datagram as u8)
+                    .chain(req.conn_id.to_be_bytes().iter().cloned())
+                    .chain(remote_address_bytes_len.to_be_bytes().iter().cloned())
+                    .chain(remote_address_bytes.into_iter())
+                    .chain(req.return_address.to_bytes().iter().cloned())
+                    .chain(req.data.into_iter())
+                    .collect()
+            }
         }
     }
 }
@@ -476,4 +546,53 @@ mod request_deserialization_tests {
             }
         }
     }
+
+    #[cfg(test)]
+    mod sending_datagrams {
+        use super::*;
+
+        fn recipient_fixture() -> Recipient {
+            Recipient::try_from_base58_string("CytBseW6yFXUMzz4SGAKdNLGR7q3sJLLYxyBGvutNEQV.4QXYyEVc5fUDjmmi8PrHN9tdUFV4PCvSJE1278cHyvoe@4sBbL1ngf1vtNqykydQKTFh26sQCw888GpUqvPvyNB4f").unwrap()
+        }
+
+        #[test]
+        fn returns_error_for_when_return_address_is_too_short() {
+            let request_bytes: Vec<_> = Request::new_datagram(
+                42,
+                "1.1.1.1:53".to_string(),
+                Vec::new(),
+                recipient_fixture(),
+            )
+            .into_bytes()
+            .into_iter()
+            .take(9 + 2 + 10 + 40)
+            .collect();
+
+            match Request::try_from_bytes(&request_bytes).unwrap_err() {
+                RequestError::ReturnAddressTooShort => {}
+                _ => unreachable!(),
+            }
+        }
+
+        #[test]
+        fn can_be_recovered_from_bytes() {
+            let recipient = recipient_fixture();
+            let request_bytes =
+                Request::new_datagram(42, "1.1.1.1:53".to_string(), vec![1, 2, 3], recipient)
+                    .into_bytes();
+
+            match Request::try_from_bytes(&request_bytes).unwrap() {
+                Request::Datagram(req) => {
+                    assert_eq!(42, req.conn_id);
+                    assert_eq!("1.1.1.1:53".to_string(), req.remote_addr);
+                    assert_eq!(vec![1, 2, 3], req.data);
+                    assert_eq!(
+                        req.return_address.to_bytes().to_vec(),
+                        recipient.to_bytes().to_vec()
+                    );
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
 }
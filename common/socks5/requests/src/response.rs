@@ -3,7 +3,7 @@
 
 use thiserror::Error;
 
-use crate::ConnectionId;
+use crate::{ConnectionId, RemoteAddress};
 
 #[derive(Debug, Error, PartialEq, Eq)]
 pub enum ResponseError {
@@ -11,6 +11,10 @@ pub enum ResponseError {
     ConnectionIdTooShort,
     #[error("no data provided")]
     NoData,
+    #[error("not enough bytes to recover the length of the address")]
+    AddressLengthTooShort,
+    #[error("not enough bytes to recover the address")]
+    AddressTooShort,
 }
 /// A remote network response retrieved by the Socks5 service provider. This
 /// can be serialized and sent back through the mixnet to the requesting
@@ -71,6 +75,70 @@ impl Response {
     }
 }
 
+/// A UDP datagram received by the Socks5 service provider from a remote host.
+/// Unlike `Response`, datagrams are not ordered and are forwarded as soon as they're received.
+#[derive(Debug)]
+pub struct DatagramResponse {
+    pub connection_id: ConnectionId,
+    /// Address of the host that has sent the datagram.
+    pub remote_addr: RemoteAddress,
+    pub data: Vec<u8>,
+}
+
+impl DatagramResponse {
+    pub fn new(connection_id: ConnectionId, remote_addr: RemoteAddress, data: Vec<u8>) -> Self {
+        DatagramResponse {
+            connection_id,
+            remote_addr,
+            data,
+        }
+    }
+
+    pub fn try_from_bytes(b: &[u8]) -> Result<DatagramResponse, ResponseError> {
+        if b.is_empty() {
+            return Err(ResponseError::NoData);
+        }
+
+        if b.len() < 8 {
+            return Err(ResponseError::ConnectionIdTooShort);
+        }
+        let connection_id = u64::from_be_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]);
+
+        if b.len() < 10 {
+            return Err(ResponseError::AddressLengthTooShort);
+        }
+        let address_length = u16::from_be_bytes([b[8], b[9]]) as usize;
+        let address_end = 10 + address_length;
+        if b.len() < address_end {
+            return Err(ResponseError::AddressTooShort);
+        }
+        let remote_addr = String::from_utf8_lossy(&b[10..address_end]).to_string();
+
+        Ok(DatagramResponse::new(
+            connection_id,
+            remote_addr,
+            b[address_end..].to_vec(),
+        ))
+    }
+
+    /// Serializes the datagram into bytes so that it can be sent back through
+    /// the mixnet to the requesting application.
+    /// The format is: CONN_ID || REMOTE_LEN || REMOTE || DATA
+    pub fn into_bytes(self) -> Vec<u8> {
+        let remote_address_bytes = self.remote_addr.into_bytes();
+        let remote_address_bytes_len = remote_address_bytes.len() as u16;
+
+        self.connection_id
+            .to_be_bytes()
+            .iter()
+            .cloned()
+            .chain(remote_address_bytes_len.to_be_bytes().iter().cloned())
+            .chain(remote_address_bytes.into_iter())
+            .chain(self.data.into_iter())
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod constructing_socks5_responses_from_bytes {
     use super::*;
@@ -122,3 +190,27 @@ mod constructing_socks5_responses_from_bytes {
         assert_eq!(expected.is_closed, actual.is_closed);
     }
 }
+
+#[cfg(test)]
+mod constructing_datagram_responses_from_bytes {
+    use super::*;
+
+    #[test]
+    fn fails_when_address_is_too_short() {
+        let response_bytes = vec![0, 1, 2, 3, 4, 5, 6, 7, 0, 5, 1, 2];
+        assert_eq!(
+            ResponseError::AddressTooShort,
+            DatagramResponse::try_from_bytes(&response_bytes).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn works_when_there_is_data() {
+        let response = DatagramResponse::new(42, "1.1.1.1:53".to_string(), vec![255, 255, 255]);
+        let actual = DatagramResponse::try_from_bytes(&response.into_bytes()).unwrap();
+
+        assert_eq!(42, actual.connection_id);
+        assert_eq!("1.1.1.1:53".to_string(), actual.remote_addr);
+        assert_eq!(vec![255, 255, 255], actual.data);
+    }
+}
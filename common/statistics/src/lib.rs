@@ -36,6 +36,13 @@ pub enum StatsData {
 pub struct StatsGatewayData {
     pub gateway_id: String,
     pub inbox_count: u32,
+    /// Number of stored messages removed within the interval because they got too old.
+    #[serde(default)]
+    pub expired_messages: u64,
+    /// Number of stored messages removed within the interval because their client
+    /// exceeded its storage quota.
+    #[serde(default)]
+    pub over_quota_messages: u64,
 }
 
 impl StatsGatewayData {
@@ -43,8 +50,17 @@ impl StatsGatewayData {
         StatsGatewayData {
             gateway_id,
             inbox_count,
+            expired_messages: 0,
+            over_quota_messages: 0,
         }
     }
+
+    #[must_use]
+    pub fn with_evicted_messages(mut self, expired: u64, over_quota: u64) -> Self {
+        self.expired_messages = expired;
+        self.over_quota_messages = over_quota;
+        self
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -2,9 +2,10 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::filter::VersionFilterable;
+use crypto::asymmetric::encryption;
 use log::warn;
 use mixnet_contract_common::mixnode::MixNodeDetails;
-use mixnet_contract_common::GatewayBond;
+use mixnet_contract_common::{EpochId, GatewayBond};
 use nymsphinx_addressing::nodes::NodeIdentity;
 use nymsphinx_types::Node as SphinxNode;
 use rand::Rng;
@@ -69,15 +70,74 @@ impl Display for NetworkAddress {
 
 pub type MixLayer = u8;
 
+/// Number of distinct mix layers in the network.
+pub const NUM_MIX_LAYERS: MixLayer = 3;
+
+/// Sphinx key announced by a node that is going to replace its current key starting from
+/// the specified epoch.
+#[derive(Debug, Clone)]
+pub struct PendingSphinxKey {
+    pub sphinx_key: encryption::PublicKey,
+    pub valid_from_epoch: EpochId,
+}
+
+impl PendingSphinxKey {
+    pub(crate) fn try_from_contract(
+        pending: &mixnet_contract_common::PendingSphinxKey,
+    ) -> Result<Self, encryption::KeyRecoveryError> {
+        Ok(PendingSphinxKey {
+            sphinx_key: encryption::PublicKey::from_base58_string(&pending.sphinx_key)?,
+            valid_from_epoch: pending.valid_from_epoch,
+        })
+    }
+
+    /// Chooses the appropriate sphinx key to use during the specified epoch. If the epoch
+    /// is unknown, the currently active key is always used.
+    pub(crate) fn choose_key<'a>(
+        pending: Option<&'a PendingSphinxKey>,
+        current: &'a encryption::PublicKey,
+        epoch: Option<EpochId>,
+    ) -> &'a encryption::PublicKey {
+        match (pending, epoch) {
+            (Some(pending), Some(epoch)) if epoch >= pending.valid_from_epoch => {
+                &pending.sphinx_key
+            }
+            _ => current,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct NymTopology {
     mixes: HashMap<MixLayer, Vec<mix::Node>>,
     gateways: Vec<gateway::Node>,
+
+    /// Epoch during which the topology is going to be used. It determines which of the announced
+    /// sphinx keys should be used for constructing the routes.
+    epoch: Option<EpochId>,
 }
 
 impl NymTopology {
     pub fn new(mixes: HashMap<MixLayer, Vec<mix::Node>>, gateways: Vec<gateway::Node>) -> Self {
-        NymTopology { mixes, gateways }
+        NymTopology {
+            mixes,
+            gateways,
+            epoch: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_epoch(mut self, epoch: EpochId) -> Self {
+        self.epoch = Some(epoch);
+        self
+    }
+
+    pub fn set_epoch(&mut self, epoch: EpochId) {
+        self.epoch = Some(epoch)
+    }
+
+    pub fn epoch(&self) -> Option<EpochId> {
+        self.epoch
     }
 
     pub fn mixes(&self) -> &HashMap<MixLayer, Vec<mix::Node>> {
@@ -94,8 +154,7 @@ impl NymTopology {
     }
 
     pub fn mixes_in_layer(&self, layer: MixLayer) -> Vec<mix::Node> {
-        assert!(vec![1, 2, 3].contains(&layer));
-        self.mixes.get(&layer).unwrap().to_owned()
+        self.mixes.get(&layer).cloned().unwrap_or_default()
     }
 
     pub fn gateways(&self) -> &[gateway::Node] {
@@ -116,8 +175,15 @@ impl NymTopology {
         self.gateways = gateways
     }
 
+    /// Layer used by the specified (0-indexed) hop of a route. If there are more hops than layers,
+    /// the layers are revisited in order, i.e. 4th hop goes through layer 1 again.
+    fn layer_for_hop(hop: u8) -> MixLayer {
+        hop % NUM_MIX_LAYERS + 1
+    }
+
     /// Returns a vec of size of `num_mix_hops` of mixnodes, such that each subsequent node is on
-    /// next layer, starting from layer 1
+    /// next layer, starting from layer 1. If `num_mix_hops` exceeds the number of layers,
+    /// the route goes through layer 1 again (and so on).
     pub fn random_mix_route<R>(
         &self,
         rng: &mut R,
@@ -129,13 +195,12 @@ impl NymTopology {
     {
         use rand::seq::SliceRandom;
 
-        if self.mixes.len() < num_mix_hops as usize {
-            return Err(NymTopologyError::InvalidNumberOfHopsError);
-        }
         let mut route = Vec::with_capacity(num_mix_hops as usize);
+        let mut used_mixes: Vec<&mix::Node> = Vec::with_capacity(num_mix_hops as usize);
+
+        for hop in 0..num_mix_hops {
+            let layer = Self::layer_for_hop(hop);
 
-        // there is no "layer 0"
-        for layer in 1..=num_mix_hops {
             // get all mixes on particular layer
             let layer_mixes = self
                 .mixes
@@ -144,10 +209,27 @@ impl NymTopology {
 
             // choose a random mix from the above list
             // this can return a 'None' only if slice is empty
-            let random_mix = layer_mixes
-                .choose(rng)
-                .ok_or(NymTopologyError::NoMixesOnLayerAvailable(layer))?;
-            route.push(random_mix.into());
+            let random_mix = if hop < NUM_MIX_LAYERS {
+                layer_mixes.choose(rng)
+            } else {
+                // when revisiting a layer, try to avoid going through the same node twice
+                let unused = layer_mixes
+                    .iter()
+                    .filter(|mix| {
+                        !used_mixes
+                            .iter()
+                            .any(|used| used.identity_key == mix.identity_key)
+                    })
+                    .collect::<Vec<_>>();
+                match unused.choose(rng) {
+                    Some(mix) => Some(*mix),
+                    None => layer_mixes.choose(rng),
+                }
+            }
+            .ok_or(NymTopologyError::NoMixesOnLayerAvailable(layer))?;
+
+            route.push(random_mix.to_sphinx_node(self.epoch));
+            used_mixes.push(random_mix);
         }
 
         Ok(route)
@@ -172,7 +254,7 @@ impl NymTopology {
         Ok(self
             .random_mix_route(rng, num_mix_hops)?
             .into_iter()
-            .chain(std::iter::once(gateway.into()))
+            .chain(std::iter::once(gateway.to_sphinx_node(self.epoch)))
             .collect())
     }
 
@@ -193,8 +275,8 @@ impl NymTopology {
             return false;
         }
 
-        // make sure there's at least one mix per layer
-        for i in 1..=num_mix_hops {
+        // make sure there's at least one mix per layer the route is going through
+        for i in 1..=num_mix_hops.min(NUM_MIX_LAYERS) {
             match self.mixes.get(&i) {
                 None => return false,
                 Some(layer_entry) => {
@@ -221,6 +303,7 @@ impl NymTopology {
         NymTopology {
             mixes: self.mixes.filter_by_version(expected_mix_version),
             gateways: self.gateways.filter_by_version(expected_gateway_version),
+            epoch: self.epoch,
         }
     }
 }
@@ -296,6 +379,7 @@ mod converting_mixes_to_vec {
                     "C7cown6dYCLZpLiMFC1PaBmhvLvmJmLDJGeRTbPD45bX",
                 )
                 .unwrap(),
+                pending_sphinx_key: None,
                 layer: Layer::One,
                 version: "0.x.0".to_string(),
             };
@@ -332,3 +416,116 @@ mod converting_mixes_to_vec {
         }
     }
 }
+
+#[cfg(test)]
+mod choosing_sphinx_keys {
+    use super::*;
+
+    #[test]
+    fn pending_key_is_only_used_once_it_becomes_valid() {
+        let current = encryption::PublicKey::from_base58_string(
+            "C7cown6dYCLZpLiMFC1PaBmhvLvmJmLDJGeRTbPD45bX",
+        )
+        .unwrap();
+        let pending = PendingSphinxKey {
+            sphinx_key: encryption::PublicKey::from_base58_string(
+                "B3GzG62aXAZNg14RoMCp3BhELNBrySLr2JqrwyfYFzRc",
+            )
+            .unwrap(),
+            valid_from_epoch: 10,
+        };
+
+        let chosen = PendingSphinxKey::choose_key(None, &current, Some(42));
+        assert_eq!(chosen, &current);
+
+        let chosen = PendingSphinxKey::choose_key(Some(&pending), &current, None);
+        assert_eq!(chosen, &current);
+
+        let chosen = PendingSphinxKey::choose_key(Some(&pending), &current, Some(9));
+        assert_eq!(chosen, &current);
+
+        let chosen = PendingSphinxKey::choose_key(Some(&pending), &current, Some(10));
+        assert_eq!(chosen, &pending.sphinx_key);
+
+        let chosen = PendingSphinxKey::choose_key(Some(&pending), &current, Some(11));
+        assert_eq!(chosen, &pending.sphinx_key);
+    }
+}
+
+#[cfg(test)]
+mod constructing_routes {
+    use super::*;
+    use crypto::asymmetric::identity;
+    use mixnet_contract_common::Layer;
+    use nymsphinx_addressing::nodes::NymNodeRoutingAddress;
+    use std::convert::TryFrom;
+
+    fn mix_fixture(mix_id: u32, layer: Layer) -> mix::Node {
+        let identity_secret = identity::PrivateKey::from_bytes(&[mix_id as u8; 32]).unwrap();
+
+        mix::Node {
+            mix_id,
+            owner: "N/A".to_string(),
+            host: "3.3.3.3".parse().unwrap(),
+            mix_host: format!("3.3.3.3:{}", 1000 + mix_id).parse().unwrap(),
+            identity_key: identity::PublicKey::from(&identity_secret),
+            sphinx_key: encryption::PublicKey::from_base58_string(
+                "C7cown6dYCLZpLiMFC1PaBmhvLvmJmLDJGeRTbPD45bX",
+            )
+            .unwrap(),
+            pending_sphinx_key: None,
+            layer,
+            version: "0.x.0".to_string(),
+        }
+    }
+
+    fn topology_fixture() -> NymTopology {
+        let mut mixes = HashMap::new();
+        mixes.insert(
+            1,
+            vec![mix_fixture(1, Layer::One), mix_fixture(2, Layer::One)],
+        );
+        mixes.insert(2, vec![mix_fixture(3, Layer::Two)]);
+        mixes.insert(3, vec![mix_fixture(4, Layer::Three)]);
+        NymTopology::new(mixes, vec![])
+    }
+
+    fn route_ports(route: &[SphinxNode]) -> Vec<u16> {
+        route
+            .iter()
+            .map(|node| {
+                SocketAddr::from(NymNodeRoutingAddress::try_from(node.address).unwrap()).port()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn routes_shorter_than_number_of_layers_use_first_layers() {
+        let topology = topology_fixture();
+        let route = topology
+            .random_mix_route(&mut rand::thread_rng(), 2)
+            .unwrap();
+        let ports = route_ports(&route);
+        assert_eq!(ports.len(), 2);
+        assert!(ports[0] == 1001 || ports[0] == 1002);
+        assert_eq!(ports[1], 1003);
+    }
+
+    #[test]
+    fn longer_routes_revisit_layers_without_reusing_nodes() {
+        let topology = topology_fixture();
+        assert!(topology.mixes.len() < 4);
+
+        let route = topology
+            .random_mix_route(&mut rand::thread_rng(), 4)
+            .unwrap();
+        let ports = route_ports(&route);
+        assert_eq!(ports.len(), 4);
+        assert_eq!(ports[1], 1003);
+        assert_eq!(ports[2], 1004);
+
+        // the 4th hop is on layer 1 again, but on a different node than the first hop
+        assert!(ports[3] == 1001 || ports[3] == 1002);
+        assert_ne!(ports[0], ports[3]);
+    }
+}
@@ -1,9 +1,9 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{filter, NetworkAddress};
+use crate::{filter, NetworkAddress, PendingSphinxKey};
 use crypto::asymmetric::{encryption, identity};
-use mixnet_contract_common::{Layer, MixId, MixNodeBond};
+use mixnet_contract_common::{EpochId, Layer, MixId, MixNodeBond};
 use nymsphinx_addressing::nodes::NymNodeRoutingAddress;
 use nymsphinx_types::Node as SphinxNode;
 use std::convert::{TryFrom, TryInto};
@@ -76,10 +76,26 @@ pub struct Node {
     pub mix_host: SocketAddr,
     pub identity_key: identity::PublicKey,
     pub sphinx_key: encryption::PublicKey, // TODO: or nymsphinx::PublicKey? both are x25519
+    pub pending_sphinx_key: Option<PendingSphinxKey>,
     pub layer: Layer,
     pub version: String,
 }
 
+impl Node {
+    /// Returns the sphinx key the node is going to be using during the specified epoch.
+    pub fn sphinx_key_for_epoch(&self, epoch: Option<EpochId>) -> &encryption::PublicKey {
+        PendingSphinxKey::choose_key(self.pending_sphinx_key.as_ref(), &self.sphinx_key, epoch)
+    }
+
+    pub fn to_sphinx_node(&self, epoch: Option<EpochId>) -> SphinxNode {
+        let node_address_bytes = NymNodeRoutingAddress::from(self.mix_host)
+            .try_into()
+            .unwrap();
+
+        SphinxNode::new(node_address_bytes, self.sphinx_key_for_epoch(epoch).into())
+    }
+}
+
 impl filter::Versioned for Node {
     fn version(&self) -> String {
         self.version.clone()
@@ -88,11 +104,7 @@ impl filter::Versioned for Node {
 
 impl<'a> From<&'a Node> for SphinxNode {
     fn from(node: &'a Node) -> Self {
-        let node_address_bytes = NymNodeRoutingAddress::from(node.mix_host)
-            .try_into()
-            .unwrap();
-
-        SphinxNode::new(node_address_bytes, (&node.sphinx_key).into())
+        node.to_sphinx_node(None)
     }
 }
 
@@ -119,6 +131,11 @@ impl<'a> TryFrom<&'a MixNodeBond> for Node {
             mix_host,
             identity_key: identity::PublicKey::from_base58_string(&bond.mix_node.identity_key)?,
             sphinx_key: encryption::PublicKey::from_base58_string(&bond.mix_node.sphinx_key)?,
+            pending_sphinx_key: bond
+                .pending_sphinx_key
+                .as_ref()
+                .map(PendingSphinxKey::try_from_contract)
+                .transpose()?,
             layer: bond.layer,
             version: bond.mix_node.version.clone(),
         })
@@ -42,6 +42,8 @@ pub(crate) const DELEGATION_MIXNODE_IDX_NAMESPACE: &str = "dlm";
 
 pub(crate) const GATEWAYS_PK_NAMESPACE: &str = "gt";
 pub(crate) const GATEWAYS_OWNER_IDX_NAMESPACE: &str = "gto";
+pub(crate) const GATEWAYS_SPHINX_IDX_NAMESPACE: &str = "gts";
+pub(crate) const GATEWAYS_PENDING_SPHINX_IDX_NAMESPACE: &str = "gtps";
 
 pub(crate) const REWARDED_SET_KEY: &str = "rs";
 pub(crate) const CURRENT_INTERVAL_KEY: &str = "ci";
@@ -61,6 +63,7 @@ pub(crate) const MIXNODES_PK_NAMESPACE: &str = "mnn";
 pub(crate) const MIXNODES_OWNER_IDX_NAMESPACE: &str = "mno";
 pub(crate) const MIXNODES_IDENTITY_IDX_NAMESPACE: &str = "mni";
 pub(crate) const MIXNODES_SPHINX_IDX_NAMESPACE: &str = "mns";
+pub(crate) const MIXNODES_PENDING_SPHINX_IDX_NAMESPACE: &str = "mnps";
 
 pub(crate) const UNBONDED_MIXNODES_PK_NAMESPACE: &str = "ubm";
 pub(crate) const UNBONDED_MIXNODES_OWNER_IDX_NAMESPACE: &str = "umo";
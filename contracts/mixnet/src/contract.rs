@@ -192,6 +192,9 @@ pub fn execute(
                 deps, info, new_config, owner,
             )
         }
+        ExecuteMsg::AnnounceMixnodeSphinxKey { sphinx_key } => {
+            crate::mixnodes::transactions::try_announce_mixnode_sphinx_key(deps, info, sphinx_key)
+        }
 
         // gateway-related:
         ExecuteMsg::BondGateway {
@@ -222,6 +225,9 @@ pub fn execute(
         ExecuteMsg::UnbondGatewayOnBehalf { owner } => {
             crate::gateways::transactions::try_remove_gateway_on_behalf(deps, info, owner)
         }
+        ExecuteMsg::AnnounceGatewaySphinxKey { sphinx_key } => {
+            crate::gateways::transactions::try_announce_gateway_sphinx_key(deps, info, sphinx_key)
+        }
 
         // delegation-related:
         ExecuteMsg::DelegateToMixnode { mix_id } => {
@@ -467,10 +473,12 @@ pub fn query(
 
 #[entry_point]
 pub fn migrate(
-    _deps: DepsMut<'_>,
+    deps: DepsMut<'_>,
     _env: Env,
     _msg: MigrateMsg,
 ) -> Result<Response, MixnetContractError> {
+    crate::queued_migrations::index_sphinx_keys(deps)?;
+
     Ok(Default::default())
 }
 
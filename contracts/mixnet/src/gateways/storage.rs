@@ -1,20 +1,29 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::constants::{GATEWAYS_OWNER_IDX_NAMESPACE, GATEWAYS_PK_NAMESPACE};
+use crate::constants::{
+    GATEWAYS_OWNER_IDX_NAMESPACE, GATEWAYS_PENDING_SPHINX_IDX_NAMESPACE, GATEWAYS_PK_NAMESPACE,
+    GATEWAYS_SPHINX_IDX_NAMESPACE,
+};
 use cosmwasm_std::Addr;
-use cw_storage_plus::{Index, IndexList, IndexedMap, UniqueIndex};
-use mixnet_contract_common::{GatewayBond, IdentityKeyRef};
+use cw_storage_plus::{Index, IndexList, IndexedMap, MultiIndex, UniqueIndex};
+use mixnet_contract_common::{GatewayBond, IdentityKey, IdentityKeyRef, SphinxKey};
 
 pub(crate) struct GatewayBondIndex<'a> {
     pub(crate) owner: UniqueIndex<'a, Addr, GatewayBond>,
+
+    pub(crate) sphinx_key: MultiIndex<'a, SphinxKey, GatewayBond, IdentityKey>,
+
+    // gateways without an announced key are indexed under an empty string
+    pub(crate) pending_sphinx_key: MultiIndex<'a, SphinxKey, GatewayBond, IdentityKey>,
 }
 
 // IndexList is just boilerplate code for fetching a struct's indexes
 // note that from my understanding this will be converted into a macro at some point in the future
 impl<'a> IndexList<GatewayBond> for GatewayBondIndex<'a> {
     fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<GatewayBond>> + '_> {
-        let v: Vec<&dyn Index<GatewayBond>> = vec![&self.owner];
+        let v: Vec<&dyn Index<GatewayBond>> =
+            vec![&self.owner, &self.sphinx_key, &self.pending_sphinx_key];
         Box::new(v.into_iter())
     }
 }
@@ -24,6 +33,21 @@ pub(crate) fn gateways<'a>() -> IndexedMap<'a, IdentityKeyRef<'a>, GatewayBond,
 {
     let indexes = GatewayBondIndex {
         owner: UniqueIndex::new(|d| d.owner.clone(), GATEWAYS_OWNER_IDX_NAMESPACE),
+        sphinx_key: MultiIndex::new(
+            |d| d.gateway.sphinx_key.clone(),
+            GATEWAYS_PK_NAMESPACE,
+            GATEWAYS_SPHINX_IDX_NAMESPACE,
+        ),
+        pending_sphinx_key: MultiIndex::new(
+            |d| {
+                d.pending_sphinx_key
+                    .as_ref()
+                    .map(|pending| pending.sphinx_key.clone())
+                    .unwrap_or_default()
+            },
+            GATEWAYS_PK_NAMESPACE,
+            GATEWAYS_PENDING_SPHINX_IDX_NAMESPACE,
+        ),
     };
     IndexedMap::new(GATEWAYS_PK_NAMESPACE, indexes)
 }
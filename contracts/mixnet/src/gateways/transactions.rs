@@ -2,14 +2,19 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::storage;
+use crate::interval::storage as interval_storage;
 use crate::mixnet_contract_settings::storage as mixnet_params_storage;
 use crate::support::helpers::{
-    ensure_no_existing_bond, validate_node_identity_signature, validate_pledge,
+    ensure_no_existing_bond, ensure_sphinx_key_not_in_use, validate_node_identity_signature,
+    validate_pledge, validate_sphinx_key,
 };
 use cosmwasm_std::{wasm_execute, Addr, BankMsg, Coin, DepsMut, Env, MessageInfo, Response};
 use mixnet_contract_common::error::MixnetContractError;
-use mixnet_contract_common::events::{new_gateway_bonding_event, new_gateway_unbonding_event};
-use mixnet_contract_common::{Gateway, GatewayBond};
+use mixnet_contract_common::events::{
+    new_gateway_bonding_event, new_gateway_sphinx_key_announcement_event,
+    new_gateway_unbonding_event,
+};
+use mixnet_contract_common::{Gateway, GatewayBond, PendingSphinxKey, SphinxKey};
 use vesting_contract_common::messages::ExecuteMsg as VestingContractExecuteMsg;
 
 pub fn try_add_gateway(
@@ -78,6 +83,9 @@ pub(crate) fn _try_add_gateway(
         }
     }
 
+    // make sure nobody is already using (or about to use) this sphinx key
+    ensure_sphinx_key_not_in_use(deps.storage, &gateway.sphinx_key)?;
+
     // check if this sender actually owns the gateway by checking the signature
     validate_node_identity_signature(
         deps.as_ref(),
@@ -175,11 +183,55 @@ pub(crate) fn _try_remove_gateway(
     )))
 }
 
+// note: unlike other operations, the announcement is always made directly by the owner (even if the
+// gateway was bonded via a proxy), as the sphinx key has no bearing on any of the funds involved
+pub fn try_announce_gateway_sphinx_key(
+    deps: DepsMut<'_>,
+    info: MessageInfo,
+    sphinx_key: SphinxKey,
+) -> Result<Response, MixnetContractError> {
+    let owner = info.sender;
+    let mut gateway_bond = match storage::gateways()
+        .idx
+        .owner
+        .item(deps.storage, owner.clone())?
+    {
+        Some(record) => record.1,
+        None => return Err(MixnetContractError::NoAssociatedGatewayBond { owner }),
+    };
+
+    validate_sphinx_key(&sphinx_key)?;
+    // make sure nobody is already using (or about to use) this key
+    ensure_sphinx_key_not_in_use(deps.storage, &sphinx_key)?;
+
+    let current_epoch =
+        interval_storage::current_interval(deps.storage)?.current_epoch_absolute_id();
+
+    // if the previously announced key is already in use, it becomes the current key
+    if let Some(previous_pending) = gateway_bond.pending_sphinx_key.take() {
+        if previous_pending.is_active(current_epoch) {
+            gateway_bond.gateway.sphinx_key = previous_pending.sphinx_key;
+        }
+    }
+
+    // the new key is going to be used starting from the next epoch so that clients would have
+    // enough time to learn about it
+    let pending_key = PendingSphinxKey::new(sphinx_key, current_epoch + 1);
+    let announcement_event =
+        new_gateway_sphinx_key_announcement_event(&owner, gateway_bond.identity(), &pending_key);
+    gateway_bond.pending_sphinx_key = Some(pending_key);
+
+    storage::gateways().save(deps.storage, gateway_bond.identity(), &gateway_bond)?;
+
+    Ok(Response::new().add_event(announcement_event))
+}
+
 #[cfg(test)]
 pub mod tests {
     use crate::contract::execute;
-    use crate::gateways::transactions::try_add_gateway;
+    use crate::gateways::transactions::{try_add_gateway, try_announce_gateway_sphinx_key};
     use crate::interval::pending_events;
+    use crate::interval::storage as interval_storage;
     use crate::mixnet_contract_settings::storage::minimum_gateway_pledge;
     use crate::support::tests;
     use crate::support::tests::fixtures::TEST_COIN_DENOM;
@@ -188,7 +240,7 @@ pub mod tests {
     use cosmwasm_std::{coin, Addr, BankMsg, Response, Uint128};
     use mixnet_contract_common::error::MixnetContractError;
     use mixnet_contract_common::events::new_gateway_unbonding_event;
-    use mixnet_contract_common::ExecuteMsg;
+    use mixnet_contract_common::{ExecuteMsg, Gateway};
 
     #[test]
     fn gateway_add() {
@@ -367,4 +419,160 @@ pub mod tests {
         assert_eq!(1, gateway_bonds.len());
         assert_eq!(&Addr::unchecked("bob"), gateway_bonds[0].owner());
     }
+
+    #[test]
+    fn announcing_gateway_sphinx_key() {
+        let mut deps = test_helpers::init_contract();
+        let env = mock_env();
+        let mut rng = test_helpers::test_rng();
+
+        let sender = "alice";
+        let info = mock_info(sender, &[]);
+        let new_key = crypto::asymmetric::encryption::KeyPair::new(&mut rng)
+            .public_key()
+            .to_base58_string();
+
+        // try announcing for a non existing gateway bond
+        let res = try_announce_gateway_sphinx_key(deps.as_mut(), info.clone(), new_key.clone());
+        assert_eq!(
+            res,
+            Err(MixnetContractError::NoAssociatedGatewayBond {
+                owner: Addr::unchecked(sender)
+            })
+        );
+
+        let identity = test_helpers::add_gateway(
+            &mut rng,
+            deps.as_mut(),
+            env.clone(),
+            sender,
+            fixtures::good_gateway_pledge(),
+        );
+        test_helpers::add_gateway(
+            &mut rng,
+            deps.as_mut(),
+            env.clone(),
+            "bob",
+            fixtures::good_gateway_pledge(),
+        );
+        let mix_id = test_helpers::add_mixnode(
+            &mut rng,
+            deps.as_mut(),
+            env,
+            "carol",
+            fixtures::good_mixnode_pledge(),
+        );
+
+        // the key has to be a valid x25519 public key
+        let res = try_announce_gateway_sphinx_key(
+            deps.as_mut(),
+            info.clone(),
+            "new-sphinx-key".to_string(),
+        );
+        assert!(matches!(
+            res,
+            Err(MixnetContractError::MalformedX25519SphinxKey(..))
+        ));
+
+        // cannot announce key that's already used by another node
+        let mix_key = crate::mixnodes::storage::mixnode_bonds()
+            .load(deps.as_ref().storage, mix_id)
+            .unwrap()
+            .mix_node
+            .sphinx_key;
+        let res = try_announce_gateway_sphinx_key(deps.as_mut(), info.clone(), mix_key);
+        assert_eq!(res, Err(MixnetContractError::DuplicateSphinxKey));
+
+        let current_epoch = interval_storage::current_interval(deps.as_ref().storage)
+            .unwrap()
+            .current_epoch_absolute_id();
+
+        let res = try_announce_gateway_sphinx_key(deps.as_mut(), info, new_key.clone());
+        assert!(res.is_ok());
+
+        // nor can another node announce a key that's about to be used
+        let res =
+            try_announce_gateway_sphinx_key(deps.as_mut(), mock_info("bob", &[]), new_key.clone());
+        assert_eq!(res, Err(MixnetContractError::DuplicateSphinxKey));
+
+        let bond = crate::gateways::storage::gateways()
+            .load(deps.as_ref().storage, identity.as_str())
+            .unwrap();
+        let pending = bond.pending_sphinx_key.clone().unwrap();
+        assert_eq!(pending.sphinx_key, new_key);
+        assert_eq!(pending.valid_from_epoch, current_epoch + 1);
+
+        assert_ne!(bond.sphinx_key_for_epoch(current_epoch), &new_key);
+        assert_eq!(bond.sphinx_key_for_epoch(current_epoch + 1), &new_key);
+    }
+
+    #[test]
+    fn bonding_gateway_with_sphinx_key_of_another_node_errors_out() {
+        let mut deps = test_helpers::init_contract();
+        let env = mock_env();
+        let mut rng = test_helpers::test_rng();
+
+        let mix_id = test_helpers::add_mixnode(
+            &mut rng,
+            deps.as_mut(),
+            env.clone(),
+            "alice",
+            fixtures::good_mixnode_pledge(),
+        );
+        let mix_key = crate::mixnodes::storage::mixnode_bonds()
+            .load(deps.as_ref().storage, mix_id)
+            .unwrap()
+            .mix_node
+            .sphinx_key;
+
+        test_helpers::add_gateway(
+            &mut rng,
+            deps.as_mut(),
+            env.clone(),
+            "bob",
+            fixtures::good_gateway_pledge(),
+        );
+        let announced_key = crypto::asymmetric::encryption::KeyPair::new(&mut rng)
+            .public_key()
+            .to_base58_string();
+        try_announce_gateway_sphinx_key(
+            deps.as_mut(),
+            mock_info("bob", &[]),
+            announced_key.clone(),
+        )
+        .unwrap();
+
+        let sender = "carol";
+        let info = mock_info(sender, &fixtures::good_gateway_pledge());
+        let (gateway, sig) = test_helpers::gateway_with_signature(&mut rng, sender);
+
+        // the key is used by a mixnode
+        let res = try_add_gateway(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            Gateway {
+                sphinx_key: mix_key,
+                ..gateway.clone()
+            },
+            sig.clone(),
+        );
+        assert_eq!(res, Err(MixnetContractError::DuplicateSphinxKey));
+
+        // the key has been announced by another gateway
+        let res = try_add_gateway(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            Gateway {
+                sphinx_key: announced_key,
+                ..gateway.clone()
+            },
+            sig.clone(),
+        );
+        assert_eq!(res, Err(MixnetContractError::DuplicateSphinxKey));
+
+        let res = try_add_gateway(deps.as_mut(), env, info, gateway, sig);
+        assert!(res.is_ok());
+    }
 }
@@ -3,9 +3,9 @@
 
 use crate::constants::{
     LAYER_DISTRIBUTION_KEY, MIXNODES_IDENTITY_IDX_NAMESPACE, MIXNODES_OWNER_IDX_NAMESPACE,
-    MIXNODES_PK_NAMESPACE, MIXNODES_SPHINX_IDX_NAMESPACE, NODE_ID_COUNTER_KEY,
-    UNBONDED_MIXNODES_IDENTITY_IDX_NAMESPACE, UNBONDED_MIXNODES_OWNER_IDX_NAMESPACE,
-    UNBONDED_MIXNODES_PK_NAMESPACE,
+    MIXNODES_PENDING_SPHINX_IDX_NAMESPACE, MIXNODES_PK_NAMESPACE, MIXNODES_SPHINX_IDX_NAMESPACE,
+    NODE_ID_COUNTER_KEY, UNBONDED_MIXNODES_IDENTITY_IDX_NAMESPACE,
+    UNBONDED_MIXNODES_OWNER_IDX_NAMESPACE, UNBONDED_MIXNODES_PK_NAMESPACE,
 };
 use cosmwasm_std::{StdResult, Storage};
 use cw_storage_plus::{Index, IndexList, IndexedMap, Item, MultiIndex, UniqueIndex};
@@ -57,14 +57,21 @@ pub(crate) struct MixnodeBondIndex<'a> {
     pub(crate) identity_key: UniqueIndex<'a, IdentityKey, MixNodeBond>,
 
     pub(crate) sphinx_key: UniqueIndex<'a, SphinxKey, MixNodeBond>,
+
+    // nodes without an announced key are indexed under an empty string
+    pub(crate) pending_sphinx_key: MultiIndex<'a, SphinxKey, MixNodeBond, MixId>,
 }
 
 // IndexList is just boilerplate code for fetching a struct's indexes
 // note that from my understanding this will be converted into a macro at some point in the future
 impl<'a> IndexList<MixNodeBond> for MixnodeBondIndex<'a> {
     fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<MixNodeBond>> + '_> {
-        let v: Vec<&dyn Index<MixNodeBond>> =
-            vec![&self.owner, &self.identity_key, &self.sphinx_key];
+        let v: Vec<&dyn Index<MixNodeBond>> = vec![
+            &self.owner,
+            &self.identity_key,
+            &self.sphinx_key,
+            &self.pending_sphinx_key,
+        ];
         Box::new(v.into_iter())
     }
 }
@@ -81,6 +88,16 @@ pub(crate) fn mixnode_bonds<'a>() -> IndexedMap<'a, MixId, MixNodeBond, MixnodeB
             |d| d.mix_node.sphinx_key.clone(),
             MIXNODES_SPHINX_IDX_NAMESPACE,
         ),
+        pending_sphinx_key: MultiIndex::new(
+            |d| {
+                d.pending_sphinx_key
+                    .as_ref()
+                    .map(|pending| pending.sphinx_key.clone())
+                    .unwrap_or_default()
+            },
+            MIXNODES_PK_NAMESPACE,
+            MIXNODES_PENDING_SPHINX_IDX_NAMESPACE,
+        ),
     };
     IndexedMap::new(MIXNODES_PK_NAMESPACE, indexes)
 }
@@ -7,18 +7,19 @@ use crate::interval::storage::push_new_interval_event;
 use crate::mixnet_contract_settings::storage as mixnet_params_storage;
 use crate::mixnodes::helpers::{must_get_mixnode_bond_by_owner, save_new_mixnode};
 use crate::support::helpers::{
-    ensure_bonded, ensure_no_existing_bond, ensure_proxy_match, validate_node_identity_signature,
-    validate_pledge,
+    ensure_bonded, ensure_no_existing_bond, ensure_proxy_match, ensure_sphinx_key_not_in_use,
+    validate_node_identity_signature, validate_pledge, validate_sphinx_key,
 };
 use cosmwasm_std::{Addr, Coin, DepsMut, Env, MessageInfo, Response};
 use mixnet_contract_common::error::MixnetContractError;
 use mixnet_contract_common::events::{
     new_mixnode_bonding_event, new_mixnode_config_update_event,
-    new_mixnode_pending_cost_params_update_event, new_pending_mixnode_unbonding_event,
+    new_mixnode_pending_cost_params_update_event, new_mixnode_sphinx_key_announcement_event,
+    new_pending_mixnode_unbonding_event,
 };
 use mixnet_contract_common::mixnode::{MixNodeConfigUpdate, MixNodeCostParams};
 use mixnet_contract_common::pending_events::{PendingEpochEventKind, PendingIntervalEventKind};
-use mixnet_contract_common::MixNode;
+use mixnet_contract_common::{MixNode, PendingSphinxKey, SphinxKey};
 
 pub fn try_add_mixnode(
     deps: DepsMut<'_>,
@@ -85,8 +86,10 @@ fn _try_add_mixnode(
     ensure_no_existing_bond(deps.storage, &owner)?;
 
     // there's no need to explicitly check whether there already exists mixnode with the same
-    // identity or sphinx keys as this is going to be done implicitly when attempting to save
-    // the bond information due to `UniqueIndex` constraint defined on those fields.
+    // identity as this is going to be done implicitly when attempting to save the bond
+    // information due to `UniqueIndex` constraint defined on that field.
+    // however, the sphinx key can't be used (or announced) by any other node, including gateways
+    ensure_sphinx_key_not_in_use(deps.storage, &mixnode.sphinx_key)?;
 
     // check if this sender actually owns the mixnode by checking the signature
     validate_node_identity_signature(
@@ -230,6 +233,51 @@ pub(crate) fn _try_update_mixnode_config(
     Ok(Response::new().add_event(cfg_update_event))
 }
 
+// note: unlike other updates, the announcement is always made directly by the owner (even if the
+// node was bonded via a proxy), as the sphinx key has no bearing on any of the funds involved
+pub(crate) fn try_announce_mixnode_sphinx_key(
+    deps: DepsMut<'_>,
+    info: MessageInfo,
+    sphinx_key: SphinxKey,
+) -> Result<Response, MixnetContractError> {
+    let owner = info.sender;
+    let existing_bond = must_get_mixnode_bond_by_owner(deps.storage, &owner)?;
+
+    ensure_bonded(&existing_bond)?;
+
+    validate_sphinx_key(&sphinx_key)?;
+    // make sure nobody is already using (or about to use) this key
+    ensure_sphinx_key_not_in_use(deps.storage, &sphinx_key)?;
+
+    let current_epoch =
+        interval_storage::current_interval(deps.storage)?.current_epoch_absolute_id();
+
+    let mut updated_bond = existing_bond.clone();
+
+    // if the previously announced key is already in use, it becomes the current key
+    if let Some(previous_pending) = updated_bond.pending_sphinx_key.take() {
+        if previous_pending.is_active(current_epoch) {
+            updated_bond.mix_node.sphinx_key = previous_pending.sphinx_key;
+        }
+    }
+
+    // the new key is going to be used starting from the next epoch so that clients would have
+    // enough time to learn about it
+    let pending_key = PendingSphinxKey::new(sphinx_key, current_epoch + 1);
+    let announcement_event =
+        new_mixnode_sphinx_key_announcement_event(existing_bond.mix_id, &owner, &pending_key);
+    updated_bond.pending_sphinx_key = Some(pending_key);
+
+    storage::mixnode_bonds().replace(
+        deps.storage,
+        existing_bond.mix_id,
+        Some(&updated_bond),
+        Some(&existing_bond),
+    )?;
+
+    Ok(Response::new().add_event(announcement_event))
+}
+
 pub(crate) fn try_update_mixnode_cost_params(
     deps: DepsMut<'_>,
     env: Env,
@@ -541,6 +589,86 @@ pub mod tests {
         assert_eq!(res, Err(MixnetContractError::MixnodeIsUnbonding { mix_id }))
     }
 
+    #[test]
+    fn announcing_mixnode_sphinx_key() {
+        let mut deps = test_helpers::init_contract();
+        let env = mock_env();
+        let mut rng = test_helpers::test_rng();
+
+        let sender = "alice";
+        let info = mock_info(sender, &[]);
+        let new_key = crypto::asymmetric::encryption::KeyPair::new(&mut rng)
+            .public_key()
+            .to_base58_string();
+
+        // try announcing for a non existing mixnode bond
+        let res = try_announce_mixnode_sphinx_key(deps.as_mut(), info.clone(), new_key.clone());
+        assert_eq!(
+            res,
+            Err(MixnetContractError::NoAssociatedMixNodeBond {
+                owner: Addr::unchecked(sender)
+            })
+        );
+
+        test_helpers::add_mixnode(
+            &mut rng,
+            deps.as_mut(),
+            env.clone(),
+            sender,
+            tests::fixtures::good_mixnode_pledge(),
+        );
+        let other_mix = test_helpers::add_mixnode(
+            &mut rng,
+            deps.as_mut(),
+            env,
+            "bob",
+            tests::fixtures::good_mixnode_pledge(),
+        );
+
+        // cannot announce key that's already used by another node
+        let other_key = get_mixnode_details_by_id(deps.as_ref().storage, other_mix)
+            .unwrap()
+            .unwrap()
+            .bond_information
+            .mix_node
+            .sphinx_key;
+        let res = try_announce_mixnode_sphinx_key(deps.as_mut(), info.clone(), other_key);
+        assert_eq!(res, Err(MixnetContractError::DuplicateSphinxKey));
+
+        // the key has to be a valid x25519 public key
+        let res = try_announce_mixnode_sphinx_key(
+            deps.as_mut(),
+            info.clone(),
+            "new-sphinx-key".to_string(),
+        );
+        assert!(matches!(
+            res,
+            Err(MixnetContractError::MalformedX25519SphinxKey(..))
+        ));
+
+        let current_epoch = interval_storage::current_interval(deps.as_ref().storage)
+            .unwrap()
+            .current_epoch_absolute_id();
+
+        let res = try_announce_mixnode_sphinx_key(deps.as_mut(), info, new_key.clone());
+        assert!(res.is_ok());
+
+        // nor can another node announce a key that's about to be used
+        let res =
+            try_announce_mixnode_sphinx_key(deps.as_mut(), mock_info("bob", &[]), new_key.clone());
+        assert_eq!(res, Err(MixnetContractError::DuplicateSphinxKey));
+
+        let mix = must_get_mixnode_bond_by_owner(deps.as_ref().storage, &Addr::unchecked(sender))
+            .unwrap();
+        let pending = mix.pending_sphinx_key.clone().unwrap();
+        assert_eq!(pending.sphinx_key, new_key);
+        assert_eq!(pending.valid_from_epoch, current_epoch + 1);
+
+        // old key is still used in the current epoch, while the new one is used from the next one
+        assert_ne!(mix.sphinx_key_for_epoch(current_epoch), &new_key);
+        assert_eq!(mix.sphinx_key_for_epoch(current_epoch + 1), &new_key);
+    }
+
     #[test]
     fn updating_mixnode_cost_params() {
         let mut deps = test_helpers::init_contract();
@@ -680,4 +808,77 @@ pub mod tests {
         )
         .is_err());
     }
+
+    #[test]
+    fn bonding_mixnode_with_sphinx_key_of_another_node_errors_out() {
+        let mut deps = test_helpers::init_contract();
+        let env = mock_env();
+        let mut rng = test_helpers::test_rng();
+
+        let gateway_identity = test_helpers::add_gateway(
+            &mut rng,
+            deps.as_mut(),
+            env.clone(),
+            "alice",
+            tests::fixtures::good_gateway_pledge(),
+        );
+        let gateway_key = crate::gateways::storage::gateways()
+            .load(deps.as_ref().storage, gateway_identity.as_str())
+            .unwrap()
+            .gateway
+            .sphinx_key;
+
+        test_helpers::add_mixnode(
+            &mut rng,
+            deps.as_mut(),
+            env.clone(),
+            "bob",
+            good_mixnode_pledge(),
+        );
+        let announced_key = crypto::asymmetric::encryption::KeyPair::new(&mut rng)
+            .public_key()
+            .to_base58_string();
+        try_announce_mixnode_sphinx_key(
+            deps.as_mut(),
+            mock_info("bob", &[]),
+            announced_key.clone(),
+        )
+        .unwrap();
+
+        let sender = "carol";
+        let info = mock_info(sender, &good_mixnode_pledge());
+        let (mixnode, sig) = test_helpers::mixnode_with_signature(&mut rng, sender);
+        let cost_params = fixtures::mix_node_cost_params_fixture();
+
+        // the key is used by a gateway
+        let res = try_add_mixnode(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            MixNode {
+                sphinx_key: gateway_key,
+                ..mixnode.clone()
+            },
+            cost_params.clone(),
+            sig.clone(),
+        );
+        assert_eq!(res, Err(MixnetContractError::DuplicateSphinxKey));
+
+        // the key has been announced by another mixnode
+        let res = try_add_mixnode(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            MixNode {
+                sphinx_key: announced_key,
+                ..mixnode.clone()
+            },
+            cost_params.clone(),
+            sig.clone(),
+        );
+        assert_eq!(res, Err(MixnetContractError::DuplicateSphinxKey));
+
+        let res = try_add_mixnode(deps.as_mut(), env, info, mixnode, cost_params, sig);
+        assert!(res.is_ok());
+    }
 }
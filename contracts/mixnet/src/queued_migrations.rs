@@ -1,2 +1,26 @@
 // Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
+
+use crate::gateways::storage as gateways_storage;
+use crate::mixnodes::storage as mixnodes_storage;
+use cosmwasm_std::{DepsMut, Order, StdResult};
+use mixnet_contract_common::error::MixnetContractError;
+
+// re-saving the bonds populates the sphinx key indexes that did not exist when they were created
+pub(crate) fn index_sphinx_keys(deps: DepsMut<'_>) -> Result<(), MixnetContractError> {
+    let gateways = gateways_storage::gateways()
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (identity, bond) in gateways {
+        gateways_storage::gateways().save(deps.storage, &identity, &bond)?;
+    }
+
+    let mixnodes = mixnodes_storage::mixnode_bonds()
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (mix_id, bond) in mixnodes {
+        mixnodes_storage::mixnode_bonds().save(deps.storage, mix_id, &bond)?;
+    }
+
+    Ok(())
+}
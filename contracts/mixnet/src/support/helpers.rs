@@ -3,9 +3,9 @@
 
 use crate::gateways::storage as gateways_storage;
 use crate::mixnodes::storage as mixnodes_storage;
-use cosmwasm_std::{Addr, BankMsg, Coin, CosmosMsg, Deps, Response, Storage};
+use cosmwasm_std::{Addr, BankMsg, Coin, CosmosMsg, Deps, Order, Response, Storage};
 use mixnet_contract_common::error::MixnetContractError;
-use mixnet_contract_common::{IdentityKeyRef, MixNodeBond};
+use mixnet_contract_common::{IdentityKeyRef, MixNodeBond, SphinxKeyRef};
 
 // helper trait to attach `Msg` to a response if it's provided
 pub(crate) trait AttachOptionalMessage<T> {
@@ -194,6 +194,63 @@ pub(crate) fn ensure_no_existing_bond(
     Ok(())
 }
 
+pub(crate) fn validate_sphinx_key(sphinx_key: SphinxKeyRef<'_>) -> Result<(), MixnetContractError> {
+    let mut key_bytes = [0u8; 32];
+    let used_bytes = bs58::decode(sphinx_key)
+        .into(&mut key_bytes)
+        .map_err(|err| MixnetContractError::MalformedX25519SphinxKey(err.to_string()))?;
+
+    if used_bytes != 32 {
+        return Err(MixnetContractError::MalformedX25519SphinxKey(
+            "Too few bytes provided for the public key".into(),
+        ));
+    }
+
+    Ok(())
+}
+
+// checks the current and the announced keys of all mixnodes and gateways
+pub(crate) fn ensure_sphinx_key_not_in_use(
+    storage: &dyn Storage,
+    sphinx_key: SphinxKeyRef<'_>,
+) -> Result<(), MixnetContractError> {
+    let mixnodes = mixnodes_storage::mixnode_bonds();
+    let gateways = gateways_storage::gateways();
+
+    let in_use = mixnodes
+        .idx
+        .sphinx_key
+        .item(storage, sphinx_key.to_owned())?
+        .is_some()
+        || mixnodes
+            .idx
+            .pending_sphinx_key
+            .prefix(sphinx_key.to_owned())
+            .keys_raw(storage, None, None, Order::Ascending)
+            .next()
+            .is_some()
+        || gateways
+            .idx
+            .sphinx_key
+            .prefix(sphinx_key.to_owned())
+            .keys_raw(storage, None, None, Order::Ascending)
+            .next()
+            .is_some()
+        || gateways
+            .idx
+            .pending_sphinx_key
+            .prefix(sphinx_key.to_owned())
+            .keys_raw(storage, None, None, Order::Ascending)
+            .next()
+            .is_some();
+
+    if in_use {
+        return Err(MixnetContractError::DuplicateSphinxKey);
+    }
+
+    Ok(())
+}
+
 pub(crate) fn validate_node_identity_signature(
     deps: Deps<'_>,
     owner: &Addr,
@@ -13,11 +13,14 @@ pub(crate) fn valid_bond_gateway_msg(
         .sign(sender.as_bytes())
         .to_base58_string();
 
+    let legit_sphinx_key = crypto::asymmetric::encryption::KeyPair::new(&mut rng);
+
     let identity_key = keypair.public_key().to_base58_string();
     (
         ExecuteMsg::BondGateway {
             gateway: Gateway {
                 identity_key: identity_key.clone(),
+                sphinx_key: legit_sphinx_key.public_key().to_base58_string(),
                 ..tests::fixtures::gateway_fixture()
             },
             owner_signature,
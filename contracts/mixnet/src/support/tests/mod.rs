@@ -745,6 +745,8 @@ pub mod test_helpers {
             .sign(sender.as_bytes())
             .to_base58_string();
 
+        let legit_sphinx_key = crypto::asymmetric::encryption::KeyPair::new(&mut rng);
+
         let info = mock_info(sender, &stake);
         let key = keypair.public_key().to_base58_string();
         try_add_gateway(
@@ -753,6 +755,7 @@ pub mod test_helpers {
             info,
             Gateway {
                 identity_key: key.clone(),
+                sphinx_key: legit_sphinx_key.public_key().to_base58_string(),
                 ..tests::fixtures::gateway_fixture()
             },
             owner_signature,
@@ -0,0 +1,90 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use rand::{CryptoRng, RngCore};
+use std::fmt::{self, Display, Formatter};
+use thiserror::Error;
+
+pub const DEVICE_ID_LENGTH: usize = 16;
+
+/// Identifier of one of possibly many devices sharing the same client identity (and thus the same
+/// `Recipient` address) at a gateway. Each device derives its own shared keys with the gateway
+/// and keeps track of its own position in the client's inbox.
+///
+/// Clients that do not select any device, such as those registered before multiple devices were
+/// supported, are treated as using the default, i.e. all-zero, id.
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct DeviceId([u8; DEVICE_ID_LENGTH]);
+
+#[derive(Debug, Error)]
+pub enum DeviceIdConversionError {
+    #[error("Failed to decode the device id - {0}")]
+    DecodeError(#[from] bs58::decode::Error),
+
+    #[error("The decoded device id has invalid length")]
+    StringOfInvalidLengthError,
+}
+
+impl DeviceId {
+    pub fn new_random<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        let mut id = [0u8; DEVICE_ID_LENGTH];
+        rng.fill_bytes(&mut id);
+        DeviceId(id)
+    }
+
+    pub fn from_bytes(bytes: [u8; DEVICE_ID_LENGTH]) -> Self {
+        DeviceId(bytes)
+    }
+
+    pub fn to_bytes(self) -> [u8; DEVICE_ID_LENGTH] {
+        self.0
+    }
+
+    pub fn try_from_base58_string<S: Into<String>>(
+        val: S,
+    ) -> Result<Self, DeviceIdConversionError> {
+        let decoded = bs58::decode(val.into()).into_vec()?;
+        if decoded.len() != DEVICE_ID_LENGTH {
+            return Err(DeviceIdConversionError::StringOfInvalidLengthError);
+        }
+
+        let mut id = [0u8; DEVICE_ID_LENGTH];
+        id.copy_from_slice(&decoded);
+        Ok(DeviceId(id))
+    }
+
+    pub fn to_base58_string(self) -> String {
+        bs58::encode(self.0).into_string()
+    }
+}
+
+impl Display for DeviceId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_base58_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn base58_conversion_roundtrip() {
+        let device = DeviceId::new_random(&mut OsRng);
+        let recovered = DeviceId::try_from_base58_string(device.to_base58_string()).unwrap();
+        assert_eq!(device, recovered);
+    }
+
+    #[test]
+    fn default_device_has_stable_encoding() {
+        // the encoding is used as the default value of the database columns of the gateway
+        assert_eq!(DeviceId::default().to_base58_string(), "1111111111111111");
+    }
+
+    #[test]
+    fn ids_of_invalid_length_are_rejected() {
+        let too_short = bs58::encode([1u8; DEVICE_ID_LENGTH - 1]).into_string();
+        assert!(DeviceId::try_from_base58_string(too_short).is_err());
+    }
+}
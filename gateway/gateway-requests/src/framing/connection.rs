@@ -0,0 +1,224 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::framing::{FramingError, RawFrame, RawFrameCodec};
+use crate::registration::handshake::error::HandshakeError;
+use crate::registration::handshake::HandshakeTransport;
+use crate::types::RegistrationHandshake;
+use futures::future::BoxFuture;
+use futures::task::{Context, Poll};
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use log::*;
+use std::convert::TryInto;
+use std::pin::Pin;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::codec::Framed;
+use tungstenite::{Error as WsError, Message as WsMessage};
+
+/// Connection using the raw framing, which, apart from being usable for the registration
+/// handshake, exposes the same `Stream` and `Sink` of WebSocket messages as the WebSocket
+/// connection would, so that the requests and responses could be handled without caring about
+/// the underlying transport.
+pub struct FramedConnection<S> {
+    inner: Framed<S, RawFrameCodec>,
+}
+
+impl<S> FramedConnection<S>
+where
+    S: AsyncRead + AsyncWrite,
+{
+    pub fn new(conn: S) -> Self {
+        FramedConnection {
+            inner: Framed::new(conn, RawFrameCodec),
+        }
+    }
+}
+
+fn into_ws_error(err: FramingError) -> WsError {
+    WsError::Io(err.into())
+}
+
+impl<S> Stream for FramedConnection<S>
+where
+    S: AsyncRead + Unpin,
+{
+    type Item = Result<WsMessage, WsError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let frame = match futures::ready!(Pin::new(&mut self.inner).poll_next(cx)) {
+            None => return Poll::Ready(None),
+            Some(Err(err)) => return Poll::Ready(Some(Err(into_ws_error(err)))),
+            Some(Ok(frame)) => frame,
+        };
+
+        // handshake frames received outside of the handshake itself (i.e. the init message)
+        // are surfaced as their WebSocket equivalents
+        let msg = match frame {
+            RawFrame::Text(text) => WsMessage::Text(text),
+            RawFrame::Binary(data) => WsMessage::Binary(data),
+            RawFrame::Close => WsMessage::Close(None),
+            RawFrame::HandshakePayload(data) => {
+                WsMessage::Text(RegistrationHandshake::new_payload(data).try_into().unwrap())
+            }
+            RawFrame::HandshakeError(message) => WsMessage::Text(
+                RegistrationHandshake::new_error(message)
+                    .try_into()
+                    .unwrap(),
+            ),
+        };
+        Poll::Ready(Some(Ok(msg)))
+    }
+}
+
+impl<S> Sink<WsMessage> for FramedConnection<S>
+where
+    S: AsyncWrite + Unpin,
+{
+    type Error = WsError;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.inner)
+            .poll_ready(cx)
+            .map_err(into_ws_error)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: WsMessage) -> Result<(), Self::Error> {
+        match RawFrame::from_ws_message(item) {
+            Some(frame) => Pin::new(&mut self.inner)
+                .start_send(frame)
+                .map_err(into_ws_error),
+            None => {
+                trace!("ignoring message without raw frame equivalent");
+                Ok(())
+            }
+        }
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.inner)
+            .poll_flush(cx)
+            .map_err(into_ws_error)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.inner)
+            .poll_close(cx)
+            .map_err(into_ws_error)
+    }
+}
+
+impl<S> HandshakeTransport for FramedConnection<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    fn send_handshake_payload(
+        &mut self,
+        payload: Vec<u8>,
+    ) -> BoxFuture<'_, Result<(), HandshakeError>> {
+        Box::pin(async move {
+            self.inner
+                .send(RawFrame::HandshakePayload(payload))
+                .await
+                .map_err(|_| HandshakeError::ClosedStream)
+        })
+    }
+
+    fn send_handshake_error(
+        &mut self,
+        message: String,
+    ) -> BoxFuture<'_, Result<(), HandshakeError>> {
+        Box::pin(async move {
+            self.inner
+                .send(RawFrame::HandshakeError(message))
+                .await
+                .map_err(|_| HandshakeError::ClosedStream)
+        })
+    }
+
+    fn receive_handshake_payload(&mut self) -> BoxFuture<'_, Result<Vec<u8>, HandshakeError>> {
+        Box::pin(async move {
+            loop {
+                match self.inner.next().await {
+                    None => return Err(HandshakeError::ClosedStream),
+                    Some(Err(_)) => return Err(HandshakeError::NetworkError),
+                    Some(Ok(RawFrame::HandshakePayload(data))) => return Ok(data),
+                    Some(Ok(RawFrame::HandshakeError(message))) => {
+                        return Err(HandshakeError::RemoteError(message))
+                    }
+                    Some(Ok(_)) => error!("Received a non-handshake frame during the registration handshake! It's getting dropped."),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registration::handshake::{client_handshake_over, gateway_handshake_over};
+    use crypto::asymmetric::identity;
+    use rand::rngs::OsRng;
+    use std::convert::TryFrom;
+
+    #[tokio::test]
+    async fn registration_handshake_can_be_performed_over_raw_connection() {
+        let mut rng = OsRng;
+        let client_identity = identity::KeyPair::new(&mut rng);
+        let gateway_identity = identity::KeyPair::new(&mut rng);
+        let gateway_pubkey = *gateway_identity.public_key();
+
+        let (client_conn, gateway_conn) = tokio::io::duplex(4096);
+        let mut client_conn = FramedConnection::new(client_conn);
+        let mut gateway_conn = FramedConnection::new(gateway_conn);
+
+        let client = async {
+            client_handshake_over(
+                &mut OsRng,
+                &mut client_conn,
+                &client_identity,
+                gateway_pubkey,
+            )
+            .await
+        };
+        let gateway = async {
+            // the init message is the first thing received on the connection
+            let init_msg = match gateway_conn.next().await.unwrap().unwrap() {
+                WsMessage::Text(text) => match RegistrationHandshake::try_from(text).unwrap() {
+                    RegistrationHandshake::HandshakePayload { data } => data,
+                    _ => panic!("received unexpected handshake message"),
+                },
+                _ => panic!("received unexpected message"),
+            };
+            gateway_handshake_over(&mut OsRng, &mut gateway_conn, &gateway_identity, init_msg).await
+        };
+
+        let (client_keys, gateway_keys) = futures::join!(client, gateway);
+        assert_eq!(
+            client_keys.unwrap().to_base58_string(),
+            gateway_keys.unwrap().to_base58_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn messages_are_passed_through_unchanged() {
+        let (conn_a, conn_b) = tokio::io::duplex(4096);
+        let mut conn_a = FramedConnection::new(conn_a);
+        let mut conn_b = FramedConnection::new(conn_b);
+
+        conn_a
+            .send(WsMessage::Text("foomp".to_string()))
+            .await
+            .unwrap();
+        conn_a.send(WsMessage::Ping(vec![1, 2, 3])).await.unwrap();
+        conn_a.send(WsMessage::Binary(vec![1, 2, 3])).await.unwrap();
+
+        assert_eq!(
+            conn_b.next().await.unwrap().unwrap(),
+            WsMessage::Text("foomp".to_string())
+        );
+        assert_eq!(
+            conn_b.next().await.unwrap().unwrap(),
+            WsMessage::Binary(vec![1, 2, 3])
+        );
+    }
+}
@@ -0,0 +1,286 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Length-prefixed framing used by clients talking to the gateway over a raw TCP connection,
+//! i.e. without performing the HTTP upgrade to WebSocket.
+//!
+//! Every frame consists of a 4 byte big-endian length, followed by a single byte indicating
+//! the kind of the frame and its payload:
+//!
+//! | LEN (u32, BE) | KIND (u8) | PAYLOAD (LEN - 1 bytes) |
+//!
+//! Text and binary frames carry exactly the same requests and responses as the equivalent
+//! WebSocket messages, while the registration handshake messages are sent as raw bytes rather
+//! than being wrapped in JSON.
+//!
+//! As the length of a frame can never exceed `MAX_FRAME_LEN`, the first byte sent on a raw
+//! connection is always 0, which lets the gateway tell it apart from an HTTP request.
+
+use bytes::{Buf, BufMut, BytesMut};
+use std::convert::TryFrom;
+use std::io;
+use thiserror::Error;
+use tokio_util::codec::{Decoder, Encoder};
+use tungstenite::Message as WsMessage;
+
+pub use self::connection::FramedConnection;
+
+mod connection;
+
+/// Scheme of the gateway address indicating the client should use the raw TCP transport.
+pub const RAW_TCP_SCHEME: &str = "tcp://";
+
+/// Maximum length of a single frame (excluding the length prefix itself).
+pub const MAX_FRAME_LEN: usize = 1024 * 1024;
+
+const LEN_PREFIX_SIZE: usize = 4;
+
+#[derive(Debug, Error)]
+pub enum FramingError {
+    #[error("experienced an io error - {0}")]
+    IoError(#[from] io::Error),
+
+    #[error("received frame of length {0}, while the maximum is {}", MAX_FRAME_LEN)]
+    FrameTooLarge(usize),
+
+    #[error("received an empty frame")]
+    EmptyFrame,
+
+    #[error("received frame of unknown kind {0}")]
+    UnknownFrameKind(u8),
+
+    #[error("received text frame that is not valid utf8")]
+    MalformedText,
+}
+
+impl From<FramingError> for io::Error {
+    fn from(err: FramingError) -> Self {
+        match err {
+            FramingError::IoError(err) => err,
+            other => io::Error::new(io::ErrorKind::InvalidData, other),
+        }
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameKind {
+    Text = 1,
+    Binary = 2,
+    Close = 3,
+    HandshakePayload = 4,
+    HandshakeError = 5,
+}
+
+impl TryFrom<u8> for FrameKind {
+    type Error = FramingError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(FrameKind::Text),
+            2 => Ok(FrameKind::Binary),
+            3 => Ok(FrameKind::Close),
+            4 => Ok(FrameKind::HandshakePayload),
+            5 => Ok(FrameKind::HandshakeError),
+            n => Err(FramingError::UnknownFrameKind(n)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RawFrame {
+    /// Equivalent of a WebSocket text message, i.e. a control request or response.
+    Text(String),
+
+    /// Equivalent of a WebSocket binary message, i.e. a sphinx packet or pushed mix message.
+    Binary(Vec<u8>),
+
+    /// Indicates the remote is about to close the connection.
+    Close,
+
+    /// Single message of the registration handshake.
+    HandshakePayload(Vec<u8>),
+
+    /// Indication of the registration handshake failure.
+    HandshakeError(String),
+}
+
+impl RawFrame {
+    fn kind(&self) -> FrameKind {
+        match self {
+            RawFrame::Text(_) => FrameKind::Text,
+            RawFrame::Binary(_) => FrameKind::Binary,
+            RawFrame::Close => FrameKind::Close,
+            RawFrame::HandshakePayload(_) => FrameKind::HandshakePayload,
+            RawFrame::HandshakeError(_) => FrameKind::HandshakeError,
+        }
+    }
+
+    fn payload(&self) -> &[u8] {
+        match self {
+            RawFrame::Text(text) | RawFrame::HandshakeError(text) => text.as_bytes(),
+            RawFrame::Binary(data) | RawFrame::HandshakePayload(data) => data,
+            RawFrame::Close => &[],
+        }
+    }
+
+    fn from_parts(kind: FrameKind, payload: Vec<u8>) -> Result<Self, FramingError> {
+        let text = |payload| String::from_utf8(payload).map_err(|_| FramingError::MalformedText);
+
+        Ok(match kind {
+            FrameKind::Text => RawFrame::Text(text(payload)?),
+            FrameKind::Binary => RawFrame::Binary(payload),
+            FrameKind::Close => RawFrame::Close,
+            FrameKind::HandshakePayload => RawFrame::HandshakePayload(payload),
+            FrameKind::HandshakeError => RawFrame::HandshakeError(text(payload)?),
+        })
+    }
+
+    /// Converts the WebSocket message into the equivalent frame. Pings and pongs do not have
+    /// any equivalents as the raw transport relies on TCP keepalive instead.
+    pub fn from_ws_message(msg: WsMessage) -> Option<Self> {
+        match msg {
+            WsMessage::Text(text) => Some(RawFrame::Text(text)),
+            WsMessage::Binary(data) => Some(RawFrame::Binary(data)),
+            WsMessage::Close(_) => Some(RawFrame::Close),
+            WsMessage::Ping(_) | WsMessage::Pong(_) => None,
+        }
+    }
+}
+
+/// Codec for `RawFrame`s, to be used with `tokio_util::codec::Framed`.
+#[derive(Debug, Default)]
+pub struct RawFrameCodec;
+
+impl Encoder<RawFrame> for RawFrameCodec {
+    type Error = FramingError;
+
+    fn encode(&mut self, item: RawFrame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let payload = item.payload();
+        let frame_len = 1 + payload.len();
+        if frame_len > MAX_FRAME_LEN {
+            return Err(FramingError::FrameTooLarge(frame_len));
+        }
+
+        dst.reserve(LEN_PREFIX_SIZE + frame_len);
+        dst.put_u32(frame_len as u32);
+        dst.put_u8(item.kind() as u8);
+        dst.put_slice(payload);
+        Ok(())
+    }
+}
+
+impl Decoder for RawFrameCodec {
+    type Item = RawFrame;
+    type Error = FramingError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if src.len() < LEN_PREFIX_SIZE {
+            return Ok(None);
+        }
+
+        let mut len_bytes = [0u8; LEN_PREFIX_SIZE];
+        len_bytes.copy_from_slice(&src[..LEN_PREFIX_SIZE]);
+        let frame_len = u32::from_be_bytes(len_bytes) as usize;
+
+        if frame_len == 0 {
+            return Err(FramingError::EmptyFrame);
+        }
+        if frame_len > MAX_FRAME_LEN {
+            return Err(FramingError::FrameTooLarge(frame_len));
+        }
+
+        if src.len() < LEN_PREFIX_SIZE + frame_len {
+            // we don't have enough bytes to read the rest of frame
+            src.reserve(LEN_PREFIX_SIZE + frame_len - src.len());
+            return Ok(None);
+        }
+
+        src.advance(LEN_PREFIX_SIZE);
+        let kind = FrameKind::try_from(src.get_u8())?;
+        let payload = src.split_to(frame_len - 1).to_vec();
+
+        RawFrame::from_parts(kind, payload).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(frame: RawFrame) -> BytesMut {
+        let mut bytes = BytesMut::new();
+        RawFrameCodec.encode(frame, &mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn frames_can_be_encoded_and_decoded() {
+        let frames = vec![
+            RawFrame::Text("foomp".to_string()),
+            RawFrame::Binary(vec![1, 2, 3, 4, 5]),
+            RawFrame::Close,
+            RawFrame::HandshakePayload(vec![42; 96]),
+            RawFrame::HandshakeError("bad signature".to_string()),
+        ];
+
+        let mut bytes = BytesMut::new();
+        for frame in frames.clone() {
+            RawFrameCodec.encode(frame, &mut bytes).unwrap();
+        }
+
+        let mut decoded = Vec::new();
+        while let Some(frame) = RawFrameCodec.decode(&mut bytes).unwrap() {
+            decoded.push(frame)
+        }
+        assert_eq!(frames, decoded);
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn partial_frames_are_not_decoded() {
+        let mut bytes = encode(RawFrame::Binary(vec![1, 2, 3, 4, 5]));
+        let mut partial = bytes.split_to(bytes.len() - 1);
+
+        assert!(RawFrameCodec.decode(&mut partial).unwrap().is_none());
+        partial.unsplit(bytes);
+        assert_eq!(
+            RawFrameCodec.decode(&mut partial).unwrap(),
+            Some(RawFrame::Binary(vec![1, 2, 3, 4, 5]))
+        );
+    }
+
+    #[test]
+    fn raw_connections_start_with_zero_byte() {
+        let bytes = encode(RawFrame::HandshakePayload(vec![1; MAX_FRAME_LEN - 1]));
+        assert_eq!(bytes[0], 0);
+    }
+
+    #[test]
+    fn oversized_frames_are_rejected() {
+        let mut bytes = BytesMut::new();
+        bytes.put_u32(MAX_FRAME_LEN as u32 + 1);
+        bytes.put_u8(FrameKind::Binary as u8);
+
+        assert!(matches!(
+            RawFrameCodec.decode(&mut bytes),
+            Err(FramingError::FrameTooLarge(_))
+        ));
+        assert!(matches!(
+            RawFrameCodec.encode(RawFrame::Binary(vec![0; MAX_FRAME_LEN]), &mut bytes),
+            Err(FramingError::FrameTooLarge(_))
+        ));
+    }
+
+    #[test]
+    fn unknown_frame_kinds_are_rejected() {
+        let mut bytes = BytesMut::new();
+        bytes.put_u32(1);
+        bytes.put_u8(42);
+
+        assert!(matches!(
+            RawFrameCodec.decode(&mut bytes),
+            Err(FramingError::UnknownFrameKind(42))
+        ));
+    }
+}
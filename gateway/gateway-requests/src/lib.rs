@@ -8,7 +8,11 @@ use nymsphinx::params::GatewayIntegrityHmacAlgorithm;
 pub use types::*;
 
 pub mod authentication;
+pub mod device;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod framing;
 pub mod iv;
+pub mod redirect;
 pub mod registration;
 pub mod types;
 
@@ -0,0 +1,328 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crypto::asymmetric::{encryption, identity};
+use nymsphinx::addressing::clients::Recipient;
+use nymsphinx::addressing::nodes::{
+    NodeIdentity, NymNodeRoutingAddress, MAX_NODE_ADDRESS_UNPADDED_LEN,
+};
+use nymsphinx::DestinationAddressBytes;
+use std::convert::TryInto;
+use thiserror::Error;
+
+/// Prefix of the signed bytes, so that the signature could not be reused in any other context.
+const REDIRECT_DOMAIN: &[u8] = b"nym-gateway-redirect";
+
+const KEY_LEN: usize = identity::PUBLIC_KEY_LENGTH;
+const ISSUED_AT_LEN: usize = 8;
+const BODY_LEN: usize = 4 * KEY_LEN + MAX_NODE_ADDRESS_UNPADDED_LEN + ISSUED_AT_LEN;
+
+#[derive(Debug, Error)]
+pub enum GatewayRedirectError {
+    #[error("The redirect record has invalid length. Got {received}, expected {expected}")]
+    InvalidLength { received: usize, expected: usize },
+
+    #[error("The redirect record contains a malformed identity key - {0}")]
+    MalformedIdentityKey(#[from] identity::Ed25519RecoveryError),
+
+    #[error("The redirect record contains a malformed sphinx key - {0}")]
+    MalformedSphinxKey(#[from] encryption::KeyRecoveryError),
+
+    #[error("The redirect record contains a malformed mix address of the new gateway")]
+    MalformedMixAddress,
+
+    #[error("Failed to decode the redirect record - {0}")]
+    DecodeError(#[from] bs58::decode::Error),
+
+    #[error("The signature on the redirect record is invalid")]
+    InvalidSignature,
+}
+
+/// Record published by a client that has moved to a different gateway, signed with its identity
+/// key. Its old gateway uses it to forward the received packets to the new one, while the peers
+/// of the client can use it to update the `Recipient` address they know, since the client
+/// identity (and thus its destination address) remains unchanged.
+#[derive(Debug, Clone)]
+pub struct GatewayRedirect {
+    client_identity: identity::PublicKey,
+    old_gateway: NodeIdentity,
+    new_gateway: NodeIdentity,
+    new_gateway_sphinx_key: encryption::PublicKey,
+    new_gateway_mix_address: NymNodeRoutingAddress,
+
+    /// Unix timestamp of when the record was created, so that the newer records would replace
+    /// the older ones.
+    issued_at: u64,
+    signature: identity::Signature,
+}
+
+impl GatewayRedirect {
+    /// Creates a new record pointing from the old gateway to the new one and signs it with the
+    /// provided client identity keys.
+    pub fn new(
+        client_keys: &identity::KeyPair,
+        old_gateway: NodeIdentity,
+        new_gateway: NodeIdentity,
+        new_gateway_sphinx_key: encryption::PublicKey,
+        new_gateway_mix_address: NymNodeRoutingAddress,
+        issued_at: u64,
+    ) -> Self {
+        let client_identity = *client_keys.public_key();
+        let body = Self::body_bytes(
+            &client_identity,
+            &old_gateway,
+            &new_gateway,
+            &new_gateway_sphinx_key,
+            &new_gateway_mix_address,
+            issued_at,
+        );
+        let signature = client_keys.private_key().sign(&Self::signed_message(&body));
+
+        GatewayRedirect {
+            client_identity,
+            old_gateway,
+            new_gateway,
+            new_gateway_sphinx_key,
+            new_gateway_mix_address,
+            issued_at,
+            signature,
+        }
+    }
+
+    fn body_bytes(
+        client_identity: &identity::PublicKey,
+        old_gateway: &NodeIdentity,
+        new_gateway: &NodeIdentity,
+        new_gateway_sphinx_key: &encryption::PublicKey,
+        new_gateway_mix_address: &NymNodeRoutingAddress,
+        issued_at: u64,
+    ) -> Vec<u8> {
+        client_identity
+            .to_bytes()
+            .iter()
+            .chain(old_gateway.to_bytes().iter())
+            .chain(new_gateway.to_bytes().iter())
+            .chain(new_gateway_sphinx_key.to_bytes().iter())
+            .chain(
+                new_gateway_mix_address
+                    .as_zero_padded_bytes(MAX_NODE_ADDRESS_UNPADDED_LEN)
+                    .iter(),
+            )
+            .chain(issued_at.to_be_bytes().iter())
+            .copied()
+            .collect()
+    }
+
+    fn signed_message(body: &[u8]) -> Vec<u8> {
+        REDIRECT_DOMAIN.iter().chain(body.iter()).copied().collect()
+    }
+
+    /// Checks whether the record has been signed by the client it refers to.
+    pub fn verify(&self) -> Result<(), GatewayRedirectError> {
+        let body = Self::body_bytes(
+            &self.client_identity,
+            &self.old_gateway,
+            &self.new_gateway,
+            &self.new_gateway_sphinx_key,
+            &self.new_gateway_mix_address,
+            self.issued_at,
+        );
+        self.client_identity
+            .verify(&Self::signed_message(&body), &self.signature)
+            .map_err(|_| GatewayRedirectError::InvalidSignature)
+    }
+
+    pub fn client_identity(&self) -> &identity::PublicKey {
+        &self.client_identity
+    }
+
+    /// Destination address of the client, under which its packets arrive at the gateways.
+    pub fn client_address(&self) -> DestinationAddressBytes {
+        self.client_identity.derive_destination_address()
+    }
+
+    pub fn old_gateway(&self) -> &NodeIdentity {
+        &self.old_gateway
+    }
+
+    pub fn new_gateway(&self) -> &NodeIdentity {
+        &self.new_gateway
+    }
+
+    pub fn new_gateway_sphinx_key(&self) -> &encryption::PublicKey {
+        &self.new_gateway_sphinx_key
+    }
+
+    pub fn new_gateway_mix_address(&self) -> NymNodeRoutingAddress {
+        self.new_gateway_mix_address
+    }
+
+    pub fn issued_at(&self) -> u64 {
+        self.issued_at
+    }
+
+    /// Returns the updated address of the recipient, assuming the record refers to it and to
+    /// the gateway it was known to be using. Note that the signature is verified beforehand.
+    pub fn updated_recipient(&self, recipient: &Recipient) -> Option<Recipient> {
+        if recipient.identity() != &self.client_identity
+            || recipient.gateway() != &self.old_gateway
+            || self.verify().is_err()
+        {
+            return None;
+        }
+
+        Some(Recipient::new(
+            self.client_identity,
+            *recipient.encryption_key(),
+            self.new_gateway,
+        ))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Self::body_bytes(
+            &self.client_identity,
+            &self.old_gateway,
+            &self.new_gateway,
+            &self.new_gateway_sphinx_key,
+            &self.new_gateway_mix_address,
+            self.issued_at,
+        );
+        bytes.extend_from_slice(&self.signature.to_bytes());
+        bytes
+    }
+
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, GatewayRedirectError> {
+        let expected = BODY_LEN + identity::SIGNATURE_LENGTH;
+        if bytes.len() != expected {
+            return Err(GatewayRedirectError::InvalidLength {
+                received: bytes.len(),
+                expected,
+            });
+        }
+
+        let mut i = 0;
+        let client_identity = identity::PublicKey::from_bytes(&bytes[i..i + KEY_LEN])?;
+        i += KEY_LEN;
+        let old_gateway = NodeIdentity::from_bytes(&bytes[i..i + KEY_LEN])?;
+        i += KEY_LEN;
+        let new_gateway = NodeIdentity::from_bytes(&bytes[i..i + KEY_LEN])?;
+        i += KEY_LEN;
+        let new_gateway_sphinx_key = encryption::PublicKey::from_bytes(&bytes[i..i + KEY_LEN])?;
+        i += KEY_LEN;
+        let new_gateway_mix_address =
+            NymNodeRoutingAddress::try_from_bytes(&bytes[i..i + MAX_NODE_ADDRESS_UNPADDED_LEN])
+                .map_err(|_| GatewayRedirectError::MalformedMixAddress)?;
+        i += MAX_NODE_ADDRESS_UNPADDED_LEN;
+        // the slice has exactly 8 bytes, so the conversion can't fail
+        let issued_at = u64::from_be_bytes(bytes[i..i + ISSUED_AT_LEN].try_into().unwrap());
+        i += ISSUED_AT_LEN;
+        let signature = identity::Signature::from_bytes(&bytes[i..])?;
+
+        Ok(GatewayRedirect {
+            client_identity,
+            old_gateway,
+            new_gateway,
+            new_gateway_sphinx_key,
+            new_gateway_mix_address,
+            issued_at,
+            signature,
+        })
+    }
+
+    pub fn to_base58_string(&self) -> String {
+        bs58::encode(self.to_bytes()).into_string()
+    }
+
+    pub fn try_from_base58_string<S: Into<String>>(val: S) -> Result<Self, GatewayRedirectError> {
+        let bytes = bs58::decode(val.into()).into_vec()?;
+        Self::try_from_bytes(&bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+    use std::net::SocketAddr;
+
+    struct Fixture {
+        client_keys: identity::KeyPair,
+        client_encryption_keys: encryption::KeyPair,
+        old_gateway: identity::KeyPair,
+        redirect: GatewayRedirect,
+    }
+
+    fn fixture() -> Fixture {
+        let mut rng = OsRng;
+        let client_keys = identity::KeyPair::new(&mut rng);
+        let client_encryption_keys = encryption::KeyPair::new(&mut rng);
+        let old_gateway = identity::KeyPair::new(&mut rng);
+        let new_gateway = identity::KeyPair::new(&mut rng);
+        let new_gateway_sphinx = encryption::KeyPair::new(&mut rng);
+        let mix_address: SocketAddr = "[2001:db8::1]:1789".parse().unwrap();
+
+        let redirect = GatewayRedirect::new(
+            &client_keys,
+            *old_gateway.public_key(),
+            *new_gateway.public_key(),
+            *new_gateway_sphinx.public_key(),
+            mix_address.into(),
+            1667304000,
+        );
+
+        Fixture {
+            client_keys,
+            client_encryption_keys,
+            old_gateway,
+            redirect,
+        }
+    }
+
+    #[test]
+    fn base58_conversion_roundtrip() {
+        let redirect = fixture().redirect;
+        let recovered =
+            GatewayRedirect::try_from_base58_string(redirect.to_base58_string()).unwrap();
+        assert_eq!(redirect.to_bytes(), recovered.to_bytes());
+        assert!(recovered.verify().is_ok());
+    }
+
+    #[test]
+    fn tampered_records_fail_verification() {
+        let redirect = fixture().redirect;
+        let mut bytes = redirect.to_bytes();
+        // flip a bit of the new gateway identity
+        bytes[2 * KEY_LEN] ^= 1;
+
+        match GatewayRedirect::try_from_bytes(&bytes) {
+            Ok(tampered) => assert!(tampered.verify().is_err()),
+            // the modified bytes might no longer represent a valid key
+            Err(err) => assert!(matches!(err, GatewayRedirectError::MalformedIdentityKey(_))),
+        }
+    }
+
+    #[test]
+    fn recipient_is_updated_only_if_the_record_refers_to_it() {
+        let fixture = fixture();
+        let recipient = Recipient::new(
+            *fixture.client_keys.public_key(),
+            *fixture.client_encryption_keys.public_key(),
+            *fixture.old_gateway.public_key(),
+        );
+
+        let updated = fixture.redirect.updated_recipient(&recipient).unwrap();
+        assert_eq!(updated.identity(), recipient.identity());
+        assert_eq!(updated.encryption_key(), recipient.encryption_key());
+        assert_eq!(updated.gateway(), fixture.redirect.new_gateway());
+
+        // the record refers to the previous gateway of the client
+        assert!(fixture.redirect.updated_recipient(&updated).is_none());
+
+        let other_client = Recipient::new(
+            *identity::KeyPair::new(&mut OsRng).public_key(),
+            *fixture.client_encryption_keys.public_key(),
+            *fixture.old_gateway.public_key(),
+        );
+        assert!(fixture.redirect.updated_recipient(&other_client).is_none());
+    }
+}
@@ -3,44 +3,43 @@
 
 use crate::registration::handshake::shared_key::SharedKeys;
 use crate::registration::handshake::state::State;
-use crate::registration::handshake::{error::HandshakeError, WsItem};
+use crate::registration::handshake::{error::HandshakeError, HandshakeTransport};
 use crypto::asymmetric::encryption::PUBLIC_KEY_SIZE;
 use crypto::asymmetric::identity::SIGNATURE_LENGTH;
 use crypto::asymmetric::{encryption, identity};
 use futures::future::BoxFuture;
 use futures::task::{Context, Poll};
-use futures::{Future, Sink, Stream};
+use futures::Future;
 use rand::{CryptoRng, RngCore};
 use std::pin::Pin;
-use tungstenite::Message as WsMessage;
 
 pub(crate) struct ClientHandshake<'a> {
     handshake_future: BoxFuture<'a, Result<SharedKeys, HandshakeError>>,
 }
 
 impl<'a> ClientHandshake<'a> {
-    pub(crate) fn new<S>(
+    pub(crate) fn new<T>(
         rng: &mut (impl RngCore + CryptoRng),
-        ws_stream: &'a mut S,
+        transport: &'a mut T,
         identity: &'a crypto::asymmetric::identity::KeyPair,
         gateway_pubkey: identity::PublicKey,
     ) -> Self
     where
-        S: Stream<Item = WsItem> + Sink<WsMessage> + Unpin + Send + 'a,
+        T: HandshakeTransport + ?Sized + 'a,
     {
-        let mut state = State::new(rng, ws_stream, identity, Some(gateway_pubkey));
+        let mut state = State::new(rng, transport, identity, Some(gateway_pubkey));
 
         ClientHandshake {
             handshake_future: Box::pin(async move {
                 // If any step along the way failed (that are non-network related),
                 // try to send 'error' message to the remote
                 // party to indicate handshake should be terminated
-                pub(crate) async fn check_processing_error<T, S>(
-                    result: Result<T, HandshakeError>,
-                    state: &mut State<'_, S>,
-                ) -> Result<T, HandshakeError>
+                pub(crate) async fn check_processing_error<R, T>(
+                    result: Result<R, HandshakeError>,
+                    state: &mut State<'_, T>,
+                ) -> Result<R, HandshakeError>
                 where
-                    S: Sink<WsMessage> + Unpin,
+                    T: HandshakeTransport + ?Sized,
                 {
                     match result {
                         Ok(ok) => Ok(ok),
@@ -6,6 +6,7 @@ use self::error::HandshakeError;
 #[cfg(not(target_arch = "wasm32"))]
 use self::gateway::GatewayHandshake;
 pub use self::shared_key::{SharedKeySize, SharedKeys};
+pub use self::transport::{HandshakeTransport, WsHandshakeTransport};
 use crypto::asymmetric::identity;
 use futures::{Sink, Stream};
 use rand::{CryptoRng, RngCore};
@@ -19,11 +20,10 @@ pub mod error;
 mod gateway;
 pub mod shared_key;
 mod state;
+mod transport;
 
-// Note: the handshake is built on top of WebSocket, but in principle it shouldn't be too difficult
-// to remove that restriction, by just changing Sink<WsMessage> and Stream<Item = WsMessage> into
-// AsyncWrite and AsyncRead and slightly adjusting the implementation. But right now
-// we do not need to worry about that.
+// Note: the handshake itself is transport-agnostic, the below functions are just a convenience
+// for the most common case of performing it over WebSocket.
 
 pub async fn client_handshake<'a, S>(
     rng: &mut (impl RngCore + CryptoRng),
@@ -34,7 +34,25 @@ pub async fn client_handshake<'a, S>(
 where
     S: Stream<Item = WsItem> + Sink<WsMessage> + Unpin + Send + 'a,
 {
-    ClientHandshake::new(rng, ws_stream, identity, gateway_pubkey).await
+    client_handshake_over(
+        rng,
+        &mut WsHandshakeTransport::new(ws_stream),
+        identity,
+        gateway_pubkey,
+    )
+    .await
+}
+
+pub async fn client_handshake_over<'a, T>(
+    rng: &mut (impl RngCore + CryptoRng),
+    transport: &'a mut T,
+    identity: &'a identity::KeyPair,
+    gateway_pubkey: identity::PublicKey,
+) -> Result<SharedKeys, HandshakeError>
+where
+    T: HandshakeTransport + ?Sized + 'a,
+{
+    ClientHandshake::new(rng, transport, identity, gateway_pubkey).await
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -47,7 +65,26 @@ pub async fn gateway_handshake<'a, S>(
 where
     S: Stream<Item = WsItem> + Sink<WsMessage> + Unpin + Send + 'a,
 {
-    GatewayHandshake::new(rng, ws_stream, identity, received_init_payload).await
+    gateway_handshake_over(
+        rng,
+        &mut WsHandshakeTransport::new(ws_stream),
+        identity,
+        received_init_payload,
+    )
+    .await
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn gateway_handshake_over<'a, T>(
+    rng: &mut (impl RngCore + CryptoRng),
+    transport: &'a mut T,
+    identity: &'a identity::KeyPair,
+    received_init_payload: Vec<u8>,
+) -> Result<SharedKeys, HandshakeError>
+where
+    T: HandshakeTransport + ?Sized + 'a,
+{
+    GatewayHandshake::new(rng, transport, identity, received_init_payload).await
 }
 
 /*
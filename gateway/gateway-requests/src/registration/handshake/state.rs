@@ -3,25 +3,20 @@
 
 use crate::registration::handshake::error::HandshakeError;
 use crate::registration::handshake::shared_key::{SharedKeySize, SharedKeys};
-use crate::registration::handshake::WsItem;
-use crate::types;
+use crate::registration::handshake::HandshakeTransport;
 use crypto::{
     asymmetric::{encryption, identity},
     generic_array::typenum::Unsigned,
     hkdf,
     symmetric::stream_cipher,
 };
-use futures::{Sink, SinkExt, Stream, StreamExt};
-use log::*;
 use nymsphinx::params::{GatewayEncryptionAlgorithm, GatewaySharedKeyHkdfAlgorithm};
 use rand::{CryptoRng, RngCore};
-use std::convert::{TryFrom, TryInto};
-use tungstenite::Message as WsMessage;
 
 /// Handshake state.
-pub(crate) struct State<'a, S> {
-    /// The underlying WebSocket stream.
-    ws_stream: &'a mut S,
+pub(crate) struct State<'a, T: ?Sized> {
+    /// The underlying transport over which the handshake messages are exchanged.
+    transport: &'a mut T,
 
     /// Identity of the local "node" (client or gateway) which is used
     /// during the handshake.
@@ -38,16 +33,19 @@ pub(crate) struct State<'a, S> {
     remote_pubkey: Option<identity::PublicKey>,
 }
 
-impl<'a, S> State<'a, S> {
+impl<'a, T> State<'a, T>
+where
+    T: HandshakeTransport + ?Sized,
+{
     pub(crate) fn new(
         rng: &mut (impl RngCore + CryptoRng),
-        ws_stream: &'a mut S,
+        transport: &'a mut T,
         identity: &'a identity::KeyPair,
         remote_pubkey: Option<identity::PublicKey>,
     ) -> Self {
         let ephemeral_keypair = encryption::KeyPair::new(rng);
         State {
-            ws_stream,
+            transport,
             ephemeral_keypair,
             identity,
             remote_pubkey,
@@ -200,59 +198,23 @@ impl<'a, S> State<'a, S> {
         self.remote_pubkey = Some(remote_pubkey)
     }
 
-    pub(crate) async fn receive_handshake_message(&mut self) -> Result<Vec<u8>, HandshakeError>
-    where
-        S: Stream<Item = WsItem> + Unpin,
-    {
-        loop {
-            if let Some(msg) = self.ws_stream.next().await {
-                if let Ok(msg) = msg {
-                    match msg {
-                        WsMessage::Text(ws_msg) => match types::RegistrationHandshake::try_from(ws_msg) {
-                            Ok(reg_handshake_msg) => return match reg_handshake_msg {
-                                types::RegistrationHandshake::HandshakePayload { data } => Ok(data),
-                                types::RegistrationHandshake::HandshakeError { message } => Err(HandshakeError::RemoteError(message)),
-                            },
-                            Err(_) => error!("Received a non-handshake message during the registration handshake! It's getting dropped."),
-                        },
-                        _ => error!("Received non-text message during registration handshake"),
-                    }
-                } else {
-                    return Err(HandshakeError::NetworkError);
-                }
-            } else {
-                return Err(HandshakeError::ClosedStream);
-            }
-        }
+    pub(crate) async fn receive_handshake_message(&mut self) -> Result<Vec<u8>, HandshakeError> {
+        self.transport.receive_handshake_payload().await
     }
 
     // upon receiving this, the receiver should terminate the handshake
     pub(crate) async fn send_handshake_error<M: Into<String>>(
         &mut self,
         message: M,
-    ) -> Result<(), HandshakeError>
-    where
-        S: Sink<WsMessage> + Unpin,
-    {
-        let handshake_message = types::RegistrationHandshake::new_error(message);
-        self.ws_stream
-            .send(WsMessage::Text(handshake_message.try_into().unwrap()))
-            .await
-            .map_err(|_| HandshakeError::ClosedStream)
+    ) -> Result<(), HandshakeError> {
+        self.transport.send_handshake_error(message.into()).await
     }
 
     pub(crate) async fn send_handshake_data(
         &mut self,
         payload: Vec<u8>,
-    ) -> Result<(), HandshakeError>
-    where
-        S: Sink<WsMessage> + Unpin,
-    {
-        let handshake_message = types::RegistrationHandshake::new_payload(payload);
-        self.ws_stream
-            .send(WsMessage::Text(handshake_message.try_into().unwrap()))
-            .await
-            .map_err(|_| HandshakeError::ClosedStream)
+    ) -> Result<(), HandshakeError> {
+        self.transport.send_handshake_payload(payload).await
     }
 
     /// Finish the handshake, yielding the derived shared key and implicitly dropping all borrowed
@@ -0,0 +1,99 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::registration::handshake::error::HandshakeError;
+use crate::registration::handshake::WsItem;
+use crate::types;
+use futures::future::BoxFuture;
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use log::*;
+use std::convert::{TryFrom, TryInto};
+use tungstenite::Message as WsMessage;
+
+/// Transport over which the messages of the registration handshake are exchanged.
+pub trait HandshakeTransport: Send {
+    /// Sends a single message of the handshake to the remote.
+    fn send_handshake_payload(
+        &mut self,
+        payload: Vec<u8>,
+    ) -> BoxFuture<'_, Result<(), HandshakeError>>;
+
+    /// Informs the remote about the handshake failure. Upon receiving this, the remote should
+    /// terminate the handshake.
+    fn send_handshake_error(
+        &mut self,
+        message: String,
+    ) -> BoxFuture<'_, Result<(), HandshakeError>>;
+
+    /// Waits for the next message of the handshake, ignoring anything unrelated to it.
+    fn receive_handshake_payload(&mut self) -> BoxFuture<'_, Result<Vec<u8>, HandshakeError>>;
+}
+
+/// `HandshakeTransport` on top of a WebSocket stream, where each handshake message is sent as
+/// JSON-encoded `RegistrationHandshake` inside a text message.
+pub struct WsHandshakeTransport<'a, S> {
+    ws_stream: &'a mut S,
+}
+
+impl<'a, S> WsHandshakeTransport<'a, S> {
+    pub fn new(ws_stream: &'a mut S) -> Self {
+        WsHandshakeTransport { ws_stream }
+    }
+
+    async fn send_handshake_message(
+        &mut self,
+        handshake_message: types::RegistrationHandshake,
+    ) -> Result<(), HandshakeError>
+    where
+        S: Sink<WsMessage> + Unpin,
+    {
+        self.ws_stream
+            .send(WsMessage::Text(handshake_message.try_into().unwrap()))
+            .await
+            .map_err(|_| HandshakeError::ClosedStream)
+    }
+}
+
+impl<'a, S> HandshakeTransport for WsHandshakeTransport<'a, S>
+where
+    S: Stream<Item = WsItem> + Sink<WsMessage> + Unpin + Send,
+{
+    fn send_handshake_payload(
+        &mut self,
+        payload: Vec<u8>,
+    ) -> BoxFuture<'_, Result<(), HandshakeError>> {
+        Box::pin(self.send_handshake_message(types::RegistrationHandshake::new_payload(payload)))
+    }
+
+    fn send_handshake_error(
+        &mut self,
+        message: String,
+    ) -> BoxFuture<'_, Result<(), HandshakeError>> {
+        Box::pin(self.send_handshake_message(types::RegistrationHandshake::new_error(message)))
+    }
+
+    fn receive_handshake_payload(&mut self) -> BoxFuture<'_, Result<Vec<u8>, HandshakeError>> {
+        Box::pin(async move {
+            loop {
+                if let Some(msg) = self.ws_stream.next().await {
+                    if let Ok(msg) = msg {
+                        match msg {
+                            WsMessage::Text(ws_msg) => match types::RegistrationHandshake::try_from(ws_msg) {
+                                Ok(reg_handshake_msg) => return match reg_handshake_msg {
+                                    types::RegistrationHandshake::HandshakePayload { data } => Ok(data),
+                                    types::RegistrationHandshake::HandshakeError { message } => Err(HandshakeError::RemoteError(message)),
+                                },
+                                Err(_) => error!("Received a non-handshake message during the registration handshake! It's getting dropped."),
+                            },
+                            _ => error!("Received non-text message during registration handshake"),
+                        }
+                    } else {
+                        return Err(HandshakeError::NetworkError);
+                    }
+                } else {
+                    return Err(HandshakeError::ClosedStream);
+                }
+            }
+        })
+    }
+}
@@ -2,7 +2,9 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::authentication::encrypted_address::EncryptedAddressBytes;
+use crate::device::DeviceId;
 use crate::iv::IV;
+use crate::redirect::GatewayRedirect;
 use crate::registration::handshake::SharedKeys;
 use crate::GatewayMacSize;
 use crypto::generic_array::typenum::Unsigned;
@@ -126,6 +128,16 @@ pub enum ClientControlRequest {
         iv: Vec<u8>,
     },
     ClaimFreeTestnetBandwidth,
+    /// Selects the device of the client that is going to register or authenticate over this
+    /// connection. It has to be sent before either of those requests.
+    SelectDevice {
+        device_id: String,
+    },
+    /// Registers the record informing the gateway that the client has moved elsewhere, so that
+    /// the packets received for it would get forwarded to its new gateway instead.
+    RegisterRedirect {
+        record: String,
+    },
 }
 
 impl ClientControlRequest {
@@ -141,6 +153,18 @@ impl ClientControlRequest {
         }
     }
 
+    pub fn new_select_device(device_id: DeviceId) -> Self {
+        ClientControlRequest::SelectDevice {
+            device_id: device_id.to_base58_string(),
+        }
+    }
+
+    pub fn new_register_redirect(record: &GatewayRedirect) -> Self {
+        ClientControlRequest::RegisterRedirect {
+            record: record.to_base58_string(),
+        }
+    }
+
     #[cfg(feature = "coconut")]
     pub fn new_enc_coconut_bandwidth_credential(
         credential: &Credential,
@@ -227,6 +251,12 @@ pub enum ServerResponse {
     Register {
         status: bool,
     },
+    SelectDevice {
+        status: bool,
+    },
+    RegisterRedirect {
+        status: bool,
+    },
     Bandwidth {
         available_total: i64,
     },
@@ -0,0 +1,12 @@
+/*
+ * Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+-- unix timestamp (in seconds) of when the message was received by the gateway
+ALTER TABLE message_store ADD COLUMN received_at INTEGER NOT NULL DEFAULT 0;
+
+-- we don't know when the already stored messages were received, so start their retention period now
+UPDATE message_store SET received_at = CAST(strftime('%s', 'now') AS INTEGER);
+
+CREATE INDEX `message_store_received_at_index` ON `message_store` (`received_at`);
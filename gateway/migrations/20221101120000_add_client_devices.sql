@@ -0,0 +1,30 @@
+/*
+ * Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+-- every device of the client derives its own shared keys. The keys stored so far belong to the default device,
+-- whose (all-zero) id is encoded as '1111111111111111'
+CREATE TABLE shared_keys_with_devices
+(
+    client_address_bs58                      TEXT NOT NULL,
+    device_id_bs58                           TEXT NOT NULL DEFAULT '1111111111111111',
+    derived_aes128_ctr_blake3_hmac_keys_bs58 TEXT NOT NULL,
+    PRIMARY KEY (client_address_bs58, device_id_bs58)
+);
+
+INSERT INTO shared_keys_with_devices(client_address_bs58, derived_aes128_ctr_blake3_hmac_keys_bs58)
+SELECT client_address_bs58, derived_aes128_ctr_blake3_hmac_keys_bs58
+FROM shared_keys;
+
+DROP TABLE shared_keys;
+ALTER TABLE shared_keys_with_devices RENAME TO shared_keys;
+
+-- id of the last stored message delivered to the particular device of the client
+CREATE TABLE inbox_cursors
+(
+    client_address_bs58 TEXT    NOT NULL,
+    device_id_bs58      TEXT    NOT NULL,
+    last_delivered_id   INTEGER NOT NULL,
+    PRIMARY KEY (client_address_bs58, device_id_bs58)
+);
@@ -0,0 +1,28 @@
+/*
+ * Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+CREATE TABLE shared_keys
+(
+    client_address_bs58                      TEXT NOT NULL PRIMARY KEY,
+    derived_aes128_ctr_blake3_hmac_keys_bs58 TEXT NOT NULL
+);
+
+CREATE TABLE message_store
+(
+    id                  BIGSERIAL PRIMARY KEY,
+    client_address_bs58 TEXT   NOT NULL,
+    content             BYTEA  NOT NULL,
+    -- unix timestamp (in seconds) of when the message was received by the gateway
+    received_at         BIGINT NOT NULL
+);
+
+CREATE TABLE available_bandwidth
+(
+    client_address_bs58 TEXT   NOT NULL PRIMARY KEY,
+    available           BIGINT NOT NULL
+);
+
+CREATE INDEX message_store_index ON message_store (client_address_bs58, id);
+CREATE INDEX message_store_received_at_index ON message_store (received_at);
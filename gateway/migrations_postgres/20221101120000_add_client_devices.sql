@@ -0,0 +1,19 @@
+/*
+ * Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+-- every device of the client derives its own shared keys. The keys stored so far belong to the default device,
+-- whose (all-zero) id is encoded as '1111111111111111'
+ALTER TABLE shared_keys ADD COLUMN device_id_bs58 TEXT NOT NULL DEFAULT '1111111111111111';
+ALTER TABLE shared_keys DROP CONSTRAINT shared_keys_pkey;
+ALTER TABLE shared_keys ADD PRIMARY KEY (client_address_bs58, device_id_bs58);
+
+-- id of the last stored message delivered to the particular device of the client
+CREATE TABLE inbox_cursors
+(
+    client_address_bs58 TEXT   NOT NULL,
+    device_id_bs58      TEXT   NOT NULL,
+    last_delivered_id   BIGINT NOT NULL,
+    PRIMARY KEY (client_address_bs58, device_id_bs58)
+);
@@ -0,0 +1,12 @@
+/*
+ * Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+-- signed records of clients that have moved to another gateway, whose packets should be forwarded there
+CREATE TABLE gateway_redirects
+(
+    client_address_bs58 TEXT   NOT NULL PRIMARY KEY,
+    record_bs58         TEXT   NOT NULL,
+    issued_at           BIGINT NOT NULL
+);
@@ -17,6 +17,7 @@ use network_defaults::var_names::{
 
 pub(crate) mod init;
 pub(crate) mod node_details;
+pub(crate) mod rotate_sphinx_key;
 pub(crate) mod run;
 pub(crate) mod sign;
 pub(crate) mod upgrade;
@@ -29,6 +30,10 @@ pub(crate) enum Commands {
     /// Show details of this gateway
     NodeDetails(node_details::NodeDetails),
 
+    /// Generate the next sphinx key of this gateway that is going to be used once announced.
+    /// The sphinx keys are never rotated automatically
+    RotateSphinxKey(rotate_sphinx_key::RotateSphinxKey),
+
     /// Starts the gateway
     Run(run::Run),
 
@@ -69,6 +74,7 @@ pub(crate) async fn execute(args: Cli) {
     match &args.command {
         Commands::Init(m) => init::execute(m).await,
         Commands::NodeDetails(m) => node_details::execute(m).await,
+        Commands::RotateSphinxKey(m) => rotate_sphinx_key::execute(m),
         Commands::Run(m) => run::execute(m).await,
         Commands::Sign(m) => sign::execute(m),
         Commands::Upgrade(m) => upgrade::execute(m).await,
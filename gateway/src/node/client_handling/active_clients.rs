@@ -3,11 +3,51 @@
 
 use crate::node::client_handling::websocket::message_receiver::MixMessageSender;
 use dashmap::DashMap;
+use gateway_requests::device::DeviceId;
 use nymsphinx::DestinationAddressBytes;
 use std::sync::Arc;
 
+/// Handles to all the currently connected devices of a single client.
 #[derive(Clone)]
-pub(crate) struct ActiveClientsStore(Arc<DashMap<DestinationAddressBytes, MixMessageSender>>);
+pub(crate) struct ClientSessions {
+    devices: Vec<(DeviceId, MixMessageSender)>,
+
+    /// Number of devices registered with the gateway for this client, whether connected or not.
+    registered_devices: usize,
+}
+
+impl ClientSessions {
+    fn remove_stale(&mut self) {
+        self.devices.retain(|(_, handle)| !handle.is_closed())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    pub(crate) fn connected_devices(&self) -> usize {
+        self.devices.len()
+    }
+
+    /// Checks whether any of the registered devices of the client is currently not connected,
+    /// i.e. whether received messages still have to be stored for it.
+    pub(crate) fn has_offline_devices(&self) -> bool {
+        self.devices.len() < self.registered_devices
+    }
+
+    /// Pushes the message to every connected device, returning ids of those to which it was
+    /// successfully sent.
+    pub(crate) fn push_message(&self, message: &[u8]) -> Vec<DeviceId> {
+        self.devices
+            .iter()
+            .filter(|(_, handle)| handle.unbounded_send(vec![message.to_vec()]).is_ok())
+            .map(|(device, _)| *device)
+            .collect()
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct ActiveClientsStore(Arc<DashMap<DestinationAddressBytes, ClientSessions>>);
 
 impl ActiveClientsStore {
     /// Creates new instance of `ActiveClientsStore` to store in-memory handles to all currently connected clients.
@@ -15,45 +55,74 @@ impl ActiveClientsStore {
         ActiveClientsStore(Arc::new(DashMap::new()))
     }
 
-    /// Tries to obtain sending channel to specified client. Note that if stale entry existed, it is
-    /// removed and a `None` is returned instead.
+    /// Tries to obtain sending channels to all connected devices of the specified client.
+    /// Note that stale handles are removed and if none are left, a `None` is returned instead.
     ///
     /// # Arguments
     ///
-    /// * `client`: address of the client for which to obtain the handle.
-    pub(crate) fn get(&self, client: DestinationAddressBytes) -> Option<MixMessageSender> {
-        let entry = self.0.get(&client)?;
-        let handle = entry.value();
+    /// * `client`: address of the client for which to obtain the handles.
+    pub(crate) fn get(&self, client: DestinationAddressBytes) -> Option<ClientSessions> {
+        let mut entry = self.0.get_mut(&client)?;
+        entry.remove_stale();
 
-        // if the entry is stale, remove it from the map
-        // if handle.is_valid() {
-        if !handle.is_closed() {
-            Some(handle.clone())
+        if !entry.is_empty() {
+            Some(entry.value().clone())
         } else {
             // drop the reference to the map to prevent deadlocks
             drop(entry);
-            self.0.remove(&client);
+            self.0.remove_if(&client, |_, sessions| sessions.is_empty());
             None
         }
     }
 
-    /// Indicates particular client has disconnected from the gateway and its handle should get removed.
+    /// Checks whether the particular device of the client is currently connected.
+    ///
+    /// # Arguments
+    ///
+    /// * `client`: address of the client.
+    /// * `device`: id of the device of the client.
+    pub(crate) fn is_connected(&self, client: DestinationAddressBytes, device: DeviceId) -> bool {
+        self.get(client)
+            .map(|sessions| sessions.devices.iter().any(|(id, _)| *id == device))
+            .unwrap_or_default()
+    }
+
+    /// Indicates particular device of the client has disconnected from the gateway and its
+    /// handle should get removed.
     ///
     /// # Arguments
     ///
     /// * `client`: address of the client for which to remove the handle.
-    pub(crate) fn disconnect(&self, client: DestinationAddressBytes) {
-        self.0.remove(&client);
+    /// * `device`: id of the disconnected device.
+    pub(crate) fn disconnect(&self, client: DestinationAddressBytes, device: DeviceId) {
+        if let Some(mut entry) = self.0.get_mut(&client) {
+            entry.devices.retain(|(id, _)| *id != device);
+        }
+        self.0.remove_if(&client, |_, sessions| sessions.is_empty());
     }
 
-    /// Insert new client handle into the store.
+    /// Insert new device handle into the store.
     ///
     /// # Arguments
     ///
     /// * `client`: address of the client for which to insert the handle.
+    /// * `device`: id of the connected device.
     /// * `handle`: the sender channel for all mix packets to be pushed back onto the websocket
-    pub(crate) fn insert(&self, client: DestinationAddressBytes, handle: MixMessageSender) {
-        self.0.insert(client, handle);
+    /// * `registered_devices`: total number of devices registered for the client.
+    pub(crate) fn insert(
+        &self,
+        client: DestinationAddressBytes,
+        device: DeviceId,
+        handle: MixMessageSender,
+        registered_devices: usize,
+    ) {
+        let mut entry = self.0.entry(client).or_insert_with(|| ClientSessions {
+            devices: Vec::new(),
+            registered_devices,
+        });
+        entry.devices.retain(|(id, _)| *id != device);
+        entry.devices.push((device, handle));
+        entry.registered_devices = registered_devices;
     }
 
     /// Get number of active clients in store
@@ -61,3 +130,70 @@ impl ActiveClientsStore {
         self.0.len()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::mpsc;
+    use futures::StreamExt;
+
+    fn address() -> DestinationAddressBytes {
+        DestinationAddressBytes::from_bytes([42; 32])
+    }
+
+    fn device(id: u8) -> DeviceId {
+        DeviceId::from_bytes([id; 16])
+    }
+
+    #[tokio::test]
+    async fn messages_are_pushed_to_all_connected_devices() {
+        let store = ActiveClientsStore::new();
+        let (sender1, mut receiver1) = mpsc::unbounded();
+        let (sender2, mut receiver2) = mpsc::unbounded();
+        store.insert(address(), device(1), sender1, 2);
+        store.insert(address(), device(2), sender2, 2);
+
+        let sessions = store.get(address()).unwrap();
+        assert!(!sessions.has_offline_devices());
+        assert_eq!(
+            sessions.push_message(&[1, 2, 3]),
+            vec![device(1), device(2)]
+        );
+        assert_eq!(receiver1.next().await.unwrap(), vec![vec![1, 2, 3]]);
+        assert_eq!(receiver2.next().await.unwrap(), vec![vec![1, 2, 3]]);
+    }
+
+    #[test]
+    fn disconnecting_one_device_keeps_the_others() {
+        let store = ActiveClientsStore::new();
+        let (sender1, _receiver1) = mpsc::unbounded();
+        let (sender2, _receiver2) = mpsc::unbounded();
+        store.insert(address(), device(1), sender1, 2);
+        store.insert(address(), device(2), sender2, 2);
+
+        store.disconnect(address(), device(1));
+        assert!(!store.is_connected(address(), device(1)));
+        assert!(store.is_connected(address(), device(2)));
+        assert!(store.get(address()).unwrap().has_offline_devices());
+
+        store.disconnect(address(), device(2));
+        assert!(store.get(address()).is_none());
+        assert_eq!(store.size(), 0);
+    }
+
+    #[test]
+    fn stale_handles_are_removed() {
+        let store = ActiveClientsStore::new();
+        let (sender1, receiver1) = mpsc::unbounded();
+        let (sender2, _receiver2) = mpsc::unbounded();
+        store.insert(address(), device(1), sender1, 2);
+        store.insert(address(), device(2), sender2, 2);
+
+        drop(receiver1);
+        assert!(!store.is_connected(address(), device(1)));
+        assert_eq!(
+            store.get(address()).unwrap().push_message(&[1]),
+            vec![device(2)]
+        );
+    }
+}
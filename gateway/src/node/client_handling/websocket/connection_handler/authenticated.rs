@@ -7,6 +7,7 @@ use crate::node::storage::error::StorageError;
 use crate::node::storage::Storage;
 use futures::StreamExt;
 use gateway_requests::iv::IVConversionError;
+use gateway_requests::redirect::{GatewayRedirect, GatewayRedirectError};
 use gateway_requests::types::{BinaryRequest, ServerResponse};
 use gateway_requests::{ClientControlRequest, GatewayRequestsError};
 use log::*;
@@ -39,6 +40,14 @@ pub(crate) enum RequestHandlingError {
     #[error("The received request is not valid in the current context")]
     IllegalRequest,
 
+    #[error("Provided redirect record is invalid - {0}")]
+    InvalidRedirect(#[from] GatewayRedirectError),
+
+    #[error(
+        "Provided redirect record does not refer to this client moving away from this gateway"
+    )]
+    UnexpectedRedirect,
+
     #[error("Provided bandwidth credential asks for more bandwidth than it is supported to add at once (credential value: {0}, supported: {}). Try to split it before attempting again", i64::MAX)]
     UnsupportedBandwidthValue(u64),
 
@@ -100,7 +109,7 @@ impl<R, S, St> Drop for AuthenticatedHandler<R, S, St> {
     fn drop(&mut self) {
         self.inner
             .active_clients_store
-            .disconnect(self.client.address)
+            .disconnect(self.client.address, self.client.device)
     }
 }
 
@@ -135,7 +144,7 @@ where
     fn disconnect(self) {
         self.inner
             .active_clients_store
-            .disconnect(self.client.address)
+            .disconnect(self.client.address, self.client.device)
     }
 
     /// Checks the amount of bandwidth available for the connected client.
@@ -320,6 +329,39 @@ where
         Ok(ServerResponse::Bandwidth { available_total })
     }
 
+    /// Tries to handle the request to register the record of the client having moved to another
+    /// gateway, after which packets received for it are going to get forwarded there.
+    ///
+    /// # Arguments
+    ///
+    /// * `record`: base58-encoded redirect record signed by the client.
+    async fn handle_register_redirect(
+        &self,
+        record: String,
+    ) -> Result<ServerResponse, RequestHandlingError> {
+        let redirect = GatewayRedirect::try_from_base58_string(record)?;
+        redirect.verify()?;
+
+        // the client can only redirect its own packets away from this gateway
+        if redirect.client_address() != self.client.address
+            || !self.inner.check_local_identity(redirect.old_gateway())
+            || self.inner.check_local_identity(redirect.new_gateway())
+        {
+            return Err(RequestHandlingError::UnexpectedRedirect);
+        }
+
+        let status = self.inner.storage.store_redirect(&redirect).await?;
+        if status {
+            info!(
+                "{} has moved to gateway {}",
+                self.client.address,
+                redirect.new_gateway()
+            );
+        }
+
+        Ok(ServerResponse::RegisterRedirect { status })
+    }
+
     /// Tries to handle request to forward sphinx packet into the network. The request can only succeed
     /// if the client has enough available bandwidth.
     ///
@@ -371,7 +413,8 @@ where
 
     /// Attempts to handle a text data frame websocket message.
     ///
-    /// Currently the bandwidth credential request is the only one we can receive after authentication.
+    /// Currently the bandwidth credential and the redirect requests are the only ones we can receive
+    /// after authentication.
     ///
     /// # Arguments
     ///
@@ -388,6 +431,10 @@ where
                     .handle_claim_testnet_bandwidth()
                     .await
                     .into_ws_message(),
+                ClientControlRequest::RegisterRedirect { record } => self
+                    .handle_register_redirect(record)
+                    .await
+                    .into_ws_message(),
                 _ => RequestHandlingError::IllegalRequest.into_error_message(),
             },
         }
@@ -5,7 +5,7 @@ use crate::node::client_handling::active_clients::ActiveClientsStore;
 #[cfg(feature = "coconut")]
 use crate::node::client_handling::websocket::connection_handler::coconut::CoconutVerifier;
 use crate::node::client_handling::websocket::connection_handler::{
-    AuthenticatedHandler, ClientDetails, InitialAuthResult, SocketStream,
+    AuthenticatedHandler, ClientDetails, ClientTransport, InitialAuthResult, SocketStream,
 };
 use crate::node::storage::error::StorageError;
 use crate::node::storage::Storage;
@@ -14,9 +14,13 @@ use futures::{channel::mpsc, SinkExt, StreamExt};
 use gateway_requests::authentication::encrypted_address::{
     EncryptedAddressBytes, EncryptedAddressConversionError,
 };
+use gateway_requests::device::{DeviceId, DeviceIdConversionError};
+use gateway_requests::framing::FramedConnection;
 use gateway_requests::iv::{IVConversionError, IV};
 use gateway_requests::registration::handshake::error::HandshakeError;
-use gateway_requests::registration::handshake::{gateway_handshake, SharedKeys};
+use gateway_requests::registration::handshake::{
+    gateway_handshake, gateway_handshake_over, SharedKeys,
+};
 use gateway_requests::types::{ClientControlRequest, ServerResponse};
 use gateway_requests::BinaryResponse;
 use log::*;
@@ -44,13 +48,16 @@ enum InitialAuthenticationError {
     #[error("Provided encrypted client address is malformed - {0}")]
     MalformedEncryptedAddress(#[from] EncryptedAddressConversionError),
 
-    #[error("There is already an open connection to this client")]
+    #[error("Provided device id is malformed - {0}")]
+    MalformedDeviceId(#[from] DeviceIdConversionError),
+
+    #[error("There is already an open connection to this device of the client")]
     DuplicateConnection,
 
     #[error("Provided authentication IV is malformed - {0}")]
     MalformedIV(#[from] IVConversionError),
 
-    #[error("Only 'Register' or 'Authenticate' requests are allowed")]
+    #[error("Only 'SelectDevice', 'Register' or 'Authenticate' requests are allowed")]
     InvalidRequest,
 
     #[error("Experienced connection error - {0}")]
@@ -73,6 +80,9 @@ pub(crate) struct FreshHandler<R, S, St> {
     pub(crate) socket_connection: SocketStream<S>,
     pub(crate) storage: St,
 
+    /// Device of the client that is going to register or authenticate over this connection.
+    device: DeviceId,
+
     #[cfg(feature = "coconut")]
     pub(crate) coconut_verifier: Arc<CoconutVerifier>,
 }
@@ -105,6 +115,7 @@ where
             socket_connection: SocketStream::RawTcp(conn),
             local_identity,
             storage,
+            device: DeviceId::default(),
             #[cfg(feature = "coconut")]
             coconut_verifier,
         }
@@ -138,6 +149,19 @@ where
         Ok(())
     }
 
+    /// Wraps the raw TCP socket with the length-prefixed framing, for clients that do not use
+    /// WebSocket.
+    pub(crate) fn use_raw_framing(&mut self)
+    where
+        S: AsyncRead + AsyncWrite,
+    {
+        self.socket_connection =
+            match std::mem::replace(&mut self.socket_connection, SocketStream::Invalid) {
+                SocketStream::RawTcp(conn) => SocketStream::Framed(FramedConnection::new(conn)),
+                other => other,
+            };
+    }
+
     /// Using received `init_msg` tries to continue the registration handshake with the connected
     /// client to establish shared keys.
     ///
@@ -151,7 +175,7 @@ where
     where
         S: AsyncRead + AsyncWrite + Unpin + Send,
     {
-        debug_assert!(self.socket_connection.is_websocket());
+        debug_assert!(self.socket_connection.is_established());
         match &mut self.socket_connection {
             SocketStream::UpgradedWebSocket(ws_stream) => {
                 gateway_handshake(
@@ -162,6 +186,10 @@ where
                 )
                 .await
             }
+            SocketStream::Framed(conn) => {
+                gateway_handshake_over(&mut self.rng, conn, self.local_identity.as_ref(), init_msg)
+                    .await
+            }
             _ => unreachable!(),
         }
     }
@@ -173,6 +201,7 @@ where
     {
         match self.socket_connection {
             SocketStream::UpgradedWebSocket(ref mut ws_stream) => ws_stream.next().await,
+            SocketStream::Framed(ref mut conn) => conn.next().await,
             _ => panic!("impossible state - websocket handshake was somehow reverted"),
         }
     }
@@ -191,6 +220,7 @@ where
             // it got something to do with batching and flushing - it might be important if it
             // turns out somehow we've got a bottleneck here
             SocketStream::UpgradedWebSocket(ref mut ws_stream) => ws_stream.send(msg).await,
+            SocketStream::Framed(ref mut conn) => conn.send(msg).await,
             _ => panic!("impossible state - websocket handshake was somehow reverted"),
         }
     }
@@ -224,6 +254,7 @@ where
             SocketStream::UpgradedWebSocket(ref mut ws_stream) => {
                 ws_stream.send_all(&mut send_stream).await
             }
+            SocketStream::Framed(ref mut conn) => conn.send_all(&mut send_stream).await,
             _ => panic!("impossible state - websocket handshake was somehow reverted"),
         }
     }
@@ -250,21 +281,28 @@ where
     }
 
     /// Attempts to retrieve all messages currently stored in the persistent database to the client,
-    /// which was offline at the time of their receipt.
+    /// which was offline at the time of their receipt. Only the messages not yet delivered to the
+    /// particular device are pushed and those already delivered to all devices of the client are
+    /// removed afterwards.
     ///
     /// # Arguments
     ///
     /// * `client_address`: address of the client that is going to receive the messages.
+    /// * `device`: device of the client that is going to receive the messages.
     /// * `shared_keys`: shared keys derived between the client and the gateway used to encrypt and tag the messages.
     async fn push_stored_messages_to_client(
         &mut self,
         client_address: DestinationAddressBytes,
+        device: DeviceId,
         shared_keys: SharedKeys,
     ) -> Result<(), InitialAuthenticationError>
     where
         S: AsyncRead + AsyncWrite + Unpin,
     {
-        let mut start_next_after = None;
+        let mut start_next_after = self
+            .storage
+            .get_inbox_cursor(client_address, device)
+            .await?;
         loop {
             // retrieve some messages
             let (messages, new_start_next_after) = self
@@ -272,10 +310,8 @@ where
                 .retrieve_messages(client_address, start_next_after)
                 .await?;
 
-            let (messages, ids) = messages
-                .into_iter()
-                .map(|msg| (msg.content, msg.id))
-                .unzip();
+            let last_id = messages.last().map(|msg| msg.id);
+            let messages = messages.into_iter().map(|msg| msg.content).collect();
 
             // push them to the client
             if let Err(err) = self.push_packets_to_client(shared_keys, messages).await {
@@ -284,9 +320,11 @@ where
                     err
                 );
                 return Err(InitialAuthenticationError::ConnectionError(err));
-            } else {
-                // if it was successful - remove them from the store
-                self.storage.remove_messages(ids).await?;
+            } else if let Some(last_id) = last_id {
+                // if it was successful - mark them as delivered to this device
+                self.storage
+                    .set_inbox_cursor(client_address, device, last_id)
+                    .await?;
             }
 
             // no more messages to grab
@@ -297,6 +335,11 @@ where
             }
         }
 
+        // and get rid of the messages that no other device is waiting for
+        self.storage
+            .remove_delivered_messages(client_address)
+            .await?;
+
         Ok(())
     }
 
@@ -316,7 +359,10 @@ where
         encrypted_address: EncryptedAddressBytes,
         iv: IV,
     ) -> Result<Option<SharedKeys>, InitialAuthenticationError> {
-        let shared_keys = self.storage.get_shared_keys(client_address).await?;
+        let shared_keys = self
+            .storage
+            .get_shared_keys(client_address, self.device)
+            .await?;
 
         if let Some(shared_keys) = shared_keys {
             // the unwrap here is fine as we only ever construct persisted shared keys ourselves when inserting
@@ -372,7 +418,7 @@ where
             .await?;
 
         if let Some(shared_keys) = shared_keys {
-            self.push_stored_messages_to_client(client_address, shared_keys)
+            self.push_stored_messages_to_client(client_address, self.device, shared_keys)
                 .await?;
             Ok(Some(shared_keys))
         } else {
@@ -401,7 +447,7 @@ where
         let encrypted_address = EncryptedAddressBytes::try_from_base58_string(enc_address)?;
         let iv = IV::try_from_base58_string(iv)?;
 
-        if self.active_clients_store.get(address).is_some() {
+        if self.active_clients_store.is_connected(address, self.device) {
             return Err(InitialAuthenticationError::DuplicateConnection);
         }
 
@@ -414,11 +460,17 @@ where
             .get_available_bandwidth(address)
             .await?
             .unwrap_or(0);
+        let registered_devices = if status {
+            self.storage.count_registered_devices(address).await?
+        } else {
+            0
+        };
         let client_details =
-            shared_keys.map(|shared_keys| ClientDetails::new(address, shared_keys));
+            shared_keys.map(|shared_keys| ClientDetails::new(address, self.device, shared_keys));
 
         Ok(InitialAuthResult::new(
             client_details,
+            registered_devices,
             ServerResponse::Authenticate {
                 status,
                 bandwidth_remaining,
@@ -447,9 +499,12 @@ where
         );
 
         self.storage
-            .insert_shared_keys(client.address, client.shared_keys)
+            .insert_shared_keys(client.address, client.device, client.shared_keys)
             .await?;
 
+        // if the client has previously moved away from this gateway, it has now come back
+        self.storage.remove_redirect(client.address).await?;
+
         // see if we have bandwidth entry for the client already, if not, create one with zero value
         if self
             .storage
@@ -460,7 +515,7 @@ where
             self.storage.create_bandwidth_entry(client.address).await?;
         }
 
-        self.push_stored_messages_to_client(client.address, client.shared_keys)
+        self.push_stored_messages_to_client(client.address, client.device, client.shared_keys)
             .await?;
 
         Ok(true)
@@ -482,21 +537,48 @@ where
         let remote_identity = Self::extract_remote_identity_from_register_init(&init_data)?;
         let remote_address = remote_identity.derive_destination_address();
 
-        if self.active_clients_store.get(remote_address).is_some() {
+        if self
+            .active_clients_store
+            .is_connected(remote_address, self.device)
+        {
             return Err(InitialAuthenticationError::DuplicateConnection);
         }
 
         let shared_keys = self.perform_registration_handshake(init_data).await?;
-        let client_details = ClientDetails::new(remote_address, shared_keys);
+        let client_details = ClientDetails::new(remote_address, self.device, shared_keys);
 
         let status = self.register_client(client_details).await?;
+        let registered_devices = self
+            .storage
+            .count_registered_devices(remote_address)
+            .await?;
 
         Ok(InitialAuthResult::new(
             Some(client_details),
+            registered_devices,
             ServerResponse::Register { status },
         ))
     }
 
+    /// Selects the device of the client that is going to register or authenticate afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `device_id`: base58-encoded id of the device.
+    fn handle_select_device(
+        &mut self,
+        device_id: String,
+    ) -> Result<InitialAuthResult, InitialAuthenticationError> {
+        self.device = DeviceId::try_from_base58_string(device_id)?;
+        debug!("Client has selected device {}", self.device);
+
+        Ok(InitialAuthResult::new(
+            None,
+            0,
+            ServerResponse::SelectDevice { status: true },
+        ))
+    }
+
     /// Handles data that resembles request to either start registration handshake or perform
     /// authentication.
     ///
@@ -520,6 +602,9 @@ where
                 ClientControlRequest::RegisterHandshakeInitRequest { data } => {
                     self.handle_register(data).await
                 }
+                ClientControlRequest::SelectDevice { device_id } => {
+                    self.handle_select_device(device_id)
+                }
                 // won't accept anything else (like bandwidth) without prior authentication
                 _ => Err(InitialAuthenticationError::InvalidRequest),
             }
@@ -529,7 +614,8 @@ where
     }
 
     /// Listens for only a subset of possible client requests, i.e. for those that can either
-    /// result in client getting registered or authenticated. All other requests, such as forwarding
+    /// result in client getting registered or authenticated, possibly preceded by the selection
+    /// of the device of the client. All other requests, such as forwarding
     /// sphinx packets considered an error and terminate the connection.
     // TODO: somehow cleanup this method
     pub(crate) async fn perform_initial_authentication(
@@ -568,6 +654,10 @@ where
                             }
                         }
                         Ok(auth_result) => {
+                            let selected_device = matches!(
+                                auth_result.server_response,
+                                ServerResponse::SelectDevice { .. }
+                            );
                             if let Err(err) = self
                                 .send_websocket_message(auth_result.server_response.into())
                                 .await
@@ -576,9 +666,18 @@ where
                                 return None;
                             }
 
+                            // the actual registration or authentication is still to come
+                            if selected_device {
+                                continue;
+                            }
+
                             return if let Some(client_details) = auth_result.client_details {
-                                self.active_clients_store
-                                    .insert(client_details.address, mix_sender);
+                                self.active_clients_store.insert(
+                                    client_details.address,
+                                    client_details.device,
+                                    mix_sender,
+                                    auth_result.registered_devices,
+                                );
                                 Some(AuthenticatedHandler::upgrade(
                                     self,
                                     client_details,
@@ -617,10 +716,10 @@ where
         None
     }
 
-    pub(crate) async fn start_handling(self)
+    pub(crate) async fn start_handling(self, transport: ClientTransport)
     where
         S: AsyncRead + AsyncWrite + Unpin + Send,
     {
-        super::handle_connection(self).await
+        super::handle_connection(self, transport).await
     }
 }
@@ -1,12 +1,16 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use gateway_requests::device::DeviceId;
+use gateway_requests::framing::FramedConnection;
 use gateway_requests::registration::handshake::SharedKeys;
 use gateway_requests::ServerResponse;
 use log::{trace, warn};
 use nymsphinx::DestinationAddressBytes;
 use rand::{CryptoRng, Rng};
+use std::io;
 use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
 use tokio_tungstenite::WebSocketStream;
 
 use crate::node::storage::Storage;
@@ -28,25 +32,63 @@ mod fresh;
 pub(crate) enum SocketStream<S> {
     RawTcp(S),
     UpgradedWebSocket(WebSocketStream<S>),
+    Framed(FramedConnection<S>),
     Invalid,
 }
 
 impl<S> SocketStream<S> {
-    fn is_websocket(&self) -> bool {
-        matches!(self, SocketStream::UpgradedWebSocket(_))
+    fn is_established(&self) -> bool {
+        matches!(
+            self,
+            SocketStream::UpgradedWebSocket(_) | SocketStream::Framed(_)
+        )
+    }
+}
+
+/// Transport chosen by the connected client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ClientTransport {
+    /// The connection is going to get upgraded to WebSocket.
+    WebSocket,
+
+    /// The client talks to the gateway using the raw length-prefixed frames.
+    RawFramed,
+}
+
+impl ClientTransport {
+    /// Determines the transport used by the client without consuming any data. Raw framed
+    /// connections always start with a zero byte (i.e. the most significant byte of the frame
+    /// length), while WebSocket ones start with an HTTP request.
+    pub(crate) async fn detect(socket: &TcpStream) -> io::Result<Self> {
+        let mut first_byte = [0u8; 1];
+        if socket.peek(&mut first_byte).await? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        if first_byte[0] == 0 {
+            Ok(ClientTransport::RawFramed)
+        } else {
+            Ok(ClientTransport::WebSocket)
+        }
     }
 }
 
 #[derive(Copy, Clone)]
 pub(crate) struct ClientDetails {
     pub(crate) address: DestinationAddressBytes,
+    pub(crate) device: DeviceId,
     pub(crate) shared_keys: SharedKeys,
 }
 
 impl ClientDetails {
-    pub(crate) fn new(address: DestinationAddressBytes, shared_keys: SharedKeys) -> Self {
+    pub(crate) fn new(
+        address: DestinationAddressBytes,
+        device: DeviceId,
+        shared_keys: SharedKeys,
+    ) -> Self {
         ClientDetails {
             address,
+            device,
             shared_keys,
         }
     }
@@ -54,33 +96,50 @@ impl ClientDetails {
 
 pub(crate) struct InitialAuthResult {
     pub(crate) client_details: Option<ClientDetails>,
+    /// Number of devices the authenticated client has registered with the gateway.
+    pub(crate) registered_devices: usize,
     pub(crate) server_response: ServerResponse,
 }
 
 impl InitialAuthResult {
-    fn new(client_details: Option<ClientDetails>, server_response: ServerResponse) -> Self {
+    fn new(
+        client_details: Option<ClientDetails>,
+        registered_devices: usize,
+        server_response: ServerResponse,
+    ) -> Self {
         InitialAuthResult {
             client_details,
+            registered_devices,
             server_response,
         }
     }
 }
 
-pub(crate) async fn handle_connection<R, S, St>(mut handle: FreshHandler<R, S, St>)
-where
+pub(crate) async fn handle_connection<R, S, St>(
+    mut handle: FreshHandler<R, S, St>,
+    transport: ClientTransport,
+) where
     R: Rng + CryptoRng,
     S: AsyncRead + AsyncWrite + Unpin + Send,
     St: Storage,
 {
-    if let Err(err) = handle.perform_websocket_handshake().await {
-        warn!(
-            "Failed to complete WebSocket handshake - {}. Stopping the handler",
-            err
-        );
-        return;
-    }
+    match transport {
+        ClientTransport::WebSocket => {
+            if let Err(err) = handle.perform_websocket_handshake().await {
+                warn!(
+                    "Failed to complete WebSocket handshake - {}. Stopping the handler",
+                    err
+                );
+                return;
+            }
 
-    trace!("Managed to perform websocket handshake!");
+            trace!("Managed to perform websocket handshake!");
+        }
+        ClientTransport::RawFramed => {
+            handle.use_raw_framing();
+            trace!("Using raw framing for the connection");
+        }
+    }
 
     if let Some(auth_handle) = handle.perform_initial_authentication().await {
         auth_handle.listen_for_requests().await
@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::node::client_handling::active_clients::ActiveClientsStore;
-use crate::node::client_handling::websocket::connection_handler::FreshHandler;
+use crate::node::client_handling::websocket::connection_handler::{ClientTransport, FreshHandler};
 use crate::node::storage::Storage;
 use crypto::asymmetric::identity;
 use log::*;
@@ -51,7 +51,7 @@ impl Listener {
     ) where
         St: Storage + Clone + 'static,
     {
-        info!("Starting websocket listener at {}", self.address);
+        info!("Starting client listener at {}", self.address);
         let tcp_listener = match tokio::net::TcpListener::bind(self.address).await {
             Ok(listener) => listener,
             Err(err) => {
@@ -66,18 +66,41 @@ impl Listener {
                     trace!("received a socket connection from {}", remote_addr);
                     // TODO: I think we *REALLY* need a mechanism for having a maximum number of connected
                     // clients or spawned tokio tasks -> perhaps a worker system?
-                    let handle = FreshHandler::new(
-                        OsRng,
-                        socket,
-                        self.disabled_credentials_mode,
-                        outbound_mix_sender.clone(),
-                        Arc::clone(&self.local_identity),
-                        storage.clone(),
-                        active_clients_store.clone(),
-                        #[cfg(feature = "coconut")]
-                        Arc::clone(&self.coconut_verifier),
-                    );
-                    tokio::spawn(async move { handle.start_handling().await });
+                    let outbound_mix_sender = outbound_mix_sender.clone();
+                    let local_identity = Arc::clone(&self.local_identity);
+                    let disabled_credentials_mode = self.disabled_credentials_mode;
+                    let storage = storage.clone();
+                    let active_clients_store = active_clients_store.clone();
+                    #[cfg(feature = "coconut")]
+                    let coconut_verifier = Arc::clone(&self.coconut_verifier);
+
+                    tokio::spawn(async move {
+                        // the transport is determined in the spawned task so that a slow client
+                        // couldn't block accepting new connections
+                        let transport = match ClientTransport::detect(&socket).await {
+                            Ok(transport) => transport,
+                            Err(err) => {
+                                debug!(
+                                    "Failed to determine transport used by {} - {}",
+                                    remote_addr, err
+                                );
+                                return;
+                            }
+                        };
+
+                        let handle = FreshHandler::new(
+                            OsRng,
+                            socket,
+                            disabled_credentials_mode,
+                            outbound_mix_sender,
+                            local_identity,
+                            storage,
+                            active_clients_store,
+                            #[cfg(feature = "coconut")]
+                            coconut_verifier,
+                        );
+                        handle.start_handling(transport).await
+                    });
                 }
                 Err(e) => warn!("failed to get client: {:?}", e),
             }
@@ -2,54 +2,33 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::node::client_handling::active_clients::ActiveClientsStore;
-use crate::node::client_handling::websocket::message_receiver::MixMessageSender;
 use crate::node::mixnet_handling::receiver::packet_processing::PacketProcessor;
+use crate::node::mixnet_handling::receiver::redirect::redirect_packet;
 use crate::node::storage::error::StorageError;
 use crate::node::storage::Storage;
 use futures::StreamExt;
+use gateway_requests::device::DeviceId;
+use gateway_requests::redirect::GatewayRedirect;
 use log::*;
 use mixnet_client::forwarder::MixForwardingSender;
+use mixnet_client::LinkConfig;
 use mixnode_common::packet_processor::processor::ProcessedFinalHop;
 use nymsphinx::forwarding::packet::MixPacket;
-use nymsphinx::framing::codec::SphinxCodec;
 use nymsphinx::framing::packet::FramedSphinxPacket;
 use nymsphinx::DestinationAddressBytes;
-use std::collections::HashMap;
 use std::net::SocketAddr;
 use tokio::net::TcpStream;
-use tokio_util::codec::Framed;
 
+// note: there used to be a per-connection cache of the client handles here, but, with multiple
+// devices per client, it couldn't tell when another device connected without going through
+// the `ActiveClientsStore` anyway
+#[derive(Clone)]
 pub(crate) struct ConnectionHandler<St: Storage> {
     packet_processor: PacketProcessor,
-
-    // TODO: investigate performance trade-offs for whether this cache even makes sense
-    // at this point.
-    // keep the following in mind: each action on ActiveClientsStore requires going through RwLock
-    // and each `get` internally copies the channel, however, is it really that expensive?
-    clients_store_cache: HashMap<DestinationAddressBytes, MixMessageSender>,
     active_clients_store: ActiveClientsStore,
     storage: St,
     ack_sender: MixForwardingSender,
-}
-
-impl<St: Storage + Clone> Clone for ConnectionHandler<St> {
-    fn clone(&self) -> Self {
-        // remove stale entries from the cache while cloning
-        let mut clients_store_cache = HashMap::with_capacity(self.clients_store_cache.capacity());
-        for (k, v) in self.clients_store_cache.iter() {
-            if !v.is_closed() {
-                clients_store_cache.insert(*k, v.clone());
-            }
-        }
-
-        ConnectionHandler {
-            packet_processor: self.packet_processor.clone(),
-            clients_store_cache,
-            active_clients_store: self.active_clients_store.clone(),
-            storage: self.storage.clone(),
-            ack_sender: self.ack_sender.clone(),
-        }
-    }
+    link: LinkConfig,
 }
 
 impl<St: Storage> ConnectionHandler<St> {
@@ -58,64 +37,38 @@ impl<St: Storage> ConnectionHandler<St> {
         storage: St,
         ack_sender: MixForwardingSender,
         active_clients_store: ActiveClientsStore,
+        link: LinkConfig,
     ) -> Self {
         ConnectionHandler {
             packet_processor,
-            clients_store_cache: HashMap::new(),
             storage,
             active_clients_store,
             ack_sender,
+            link,
         }
     }
 
-    fn update_clients_store_cache_entry(&mut self, client_address: DestinationAddressBytes) {
-        if let Some(client_sender) = self.active_clients_store.get(client_address) {
-            self.clients_store_cache
-                .insert(client_address, client_sender);
-        }
-    }
-
-    fn check_cache(&mut self, client_address: DestinationAddressBytes) {
-        match self.clients_store_cache.get(&client_address) {
-            None => self.update_clients_store_cache_entry(client_address),
-            Some(entry) => {
-                if entry.is_closed() {
-                    self.update_clients_store_cache_entry(client_address)
-                }
-            }
-        }
-    }
-
-    fn try_push_message_to_client(
-        &mut self,
-        client_address: DestinationAddressBytes,
-        message: Vec<u8>,
-    ) -> Result<(), Vec<u8>> {
-        self.check_cache(client_address);
-
-        match self.clients_store_cache.get(&client_address) {
-            None => Err(message),
-            Some(sender_channel) => {
-                sender_channel
-                    .unbounded_send(vec![message])
-                    // right now it's a "simpler" case here as we're only ever sending 1 message
-                    // at the time, but the channel itself could accept arbitrary many messages at once
-                    .map_err(|try_send_err| try_send_err.into_inner().pop().unwrap())
-            }
-        }
-    }
-
+    /// Stores the message for the devices of the client that are currently offline. The devices
+    /// that have already received it are marked as such, so that it wouldn't get pushed to
+    /// them again once they reconnect.
     pub(crate) async fn store_processed_packet_payload(
         &self,
         client_address: DestinationAddressBytes,
         message: Vec<u8>,
+        delivered_to: Vec<DeviceId>,
     ) -> Result<(), StorageError> {
         debug!(
             "Storing received message for {} on the disk...",
             client_address
         );
 
-        self.storage.store_message(client_address, message).await
+        let id = self.storage.store_message(client_address, message).await?;
+        for device in delivered_to {
+            self.storage
+                .set_inbox_cursor(client_address, device, id)
+                .await?;
+        }
+        Ok(())
     }
 
     fn forward_ack(&self, forward_ack: Option<MixPacket>, client_address: DestinationAddressBytes) {
@@ -130,22 +83,79 @@ impl<St: Storage> ConnectionHandler<St> {
         }
     }
 
-    async fn handle_processed_packet(&mut self, processed_final_hop: ProcessedFinalHop) {
+    /// Forwards the packet to the new gateway of the client. Note that the ack is not sent back
+    /// here, as it gets forwarded alongside the message and is going to be sent by the new
+    /// gateway upon receiving it.
+    fn forward_to_new_gateway(
+        &self,
+        redirect: &GatewayRedirect,
+        forward_ack: Option<MixPacket>,
+        message: Vec<u8>,
+    ) {
+        match redirect_packet(redirect, forward_ack, message) {
+            Ok(redirected) => {
+                trace!(
+                    "Forwarding packet for {} to its new gateway at {}",
+                    redirect.client_address(),
+                    redirected.next_hop()
+                );
+                self.ack_sender.unbounded_send(redirected).unwrap();
+            }
+            Err(err) => debug!(
+                "Failed to redirect packet for {} - {:?}",
+                redirect.client_address(),
+                err
+            ),
+        }
+    }
+
+    async fn handle_processed_packet(&self, processed_final_hop: ProcessedFinalHop) {
         let client_address = processed_final_hop.destination;
         let message = processed_final_hop.message;
         let forward_ack = processed_final_hop.forward_ack;
 
-        // we failed to push message directly to the client - it's probably offline.
-        // we should store it on the disk instead.
-        match self.try_push_message_to_client(client_address, message) {
-            Err(unsent_plaintext) => match self
-                .store_processed_packet_payload(client_address, unsent_plaintext)
+        // push the message directly to all connected devices of the client. If any of them is
+        // offline (or we failed to push it), we should store it on the disk instead.
+        let (delivered_to, should_store) = match self.active_clients_store.get(client_address) {
+            Some(sessions) => {
+                let delivered_to = sessions.push_message(&message);
+                let should_store = sessions.has_offline_devices()
+                    || delivered_to.len() < sessions.connected_devices();
+                (delivered_to, should_store)
+            }
+            None => {
+                // if none of the devices is connected, the client might have moved elsewhere
+                match self.storage.get_redirect(client_address).await {
+                    Ok(Some(redirect)) => {
+                        self.forward_to_new_gateway(&redirect, forward_ack, message);
+                        return;
+                    }
+                    Ok(None) => (),
+                    Err(err) => error!(
+                        "Failed to retrieve redirect of {} - {}",
+                        client_address, err
+                    ),
+                }
+                (Vec::new(), true)
+            }
+        };
+
+        if !delivered_to.is_empty() {
+            trace!(
+                "Pushed received packet to {} device(s) of {}",
+                delivered_to.len(),
+                client_address
+            );
+        }
+
+        if should_store {
+            match self
+                .store_processed_packet_payload(client_address, message, delivered_to)
                 .await
             {
                 Err(err) => error!("Failed to store client data - {}", err),
                 Ok(_) => trace!("Stored packet for {}", client_address),
-            },
-            Ok(_) => trace!("Pushed received packet to {}", client_address),
+            }
         }
 
         // if we managed to either push message directly to the [online] client or store it at
@@ -154,7 +164,7 @@ impl<St: Storage> ConnectionHandler<St> {
         self.forward_ack(forward_ack, client_address);
     }
 
-    async fn handle_received_packet(&mut self, framed_sphinx_packet: FramedSphinxPacket) {
+    async fn handle_received_packet(&self, framed_sphinx_packet: FramedSphinxPacket) {
         //
         // TODO: here be replay attack detection - it will require similar key cache to the one in
         // packet processor for vpn packets,
@@ -173,9 +183,15 @@ impl<St: Storage> ConnectionHandler<St> {
         self.handle_processed_packet(processed_final_hop).await
     }
 
-    pub(crate) async fn handle_connection(mut self, conn: TcpStream, remote: SocketAddr) {
+    pub(crate) async fn handle_connection(self, conn: TcpStream, remote: SocketAddr) {
         debug!("Starting connection handler for {:?}", remote);
-        let mut framed_conn = Framed::new(conn, SphinxCodec);
+        let mut framed_conn = match self.link.accept(conn).await {
+            Ok(framed_conn) => framed_conn,
+            Err(err) => {
+                debug!("Failed to establish the link with {:?} - {}", remote, err);
+                return;
+            }
+        };
         while let Some(framed_sphinx_packet) = framed_conn.next().await {
             match framed_sphinx_packet {
                 Ok(framed_sphinx_packet) => {
@@ -189,7 +205,7 @@ impl<St: Storage> ConnectionHandler<St> {
                 }
                 Err(err) => {
                     error!(
-                        "The socket connection got corrupted with error: {:?}. Closing the socket",
+                        "The socket connection got corrupted with error: {}. Closing the socket",
                         err
                     );
                     return;
@@ -4,3 +4,4 @@
 pub(crate) mod connection_handler;
 pub(crate) mod listener;
 pub(crate) mod packet_processing;
+pub(crate) mod redirect;
@@ -0,0 +1,127 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use gateway_requests::redirect::GatewayRedirect;
+use nymsphinx::addressing::nodes::{NymNodeRoutingAddressError, MAX_NODE_ADDRESS_UNPADDED_LEN};
+use nymsphinx::builder::SphinxPacketBuilder;
+use nymsphinx::forwarding::packet::MixPacket;
+use nymsphinx::params::packet_sizes::PacketSize;
+use nymsphinx::params::PacketMode;
+use nymsphinx::{Delay, Destination, Error as SphinxError, Node};
+use std::convert::TryInto;
+
+#[derive(Debug)]
+pub enum RedirectError {
+    PacketTooLarge,
+    InvalidNextHop(NymNodeRoutingAddressError),
+    SphinxError(SphinxError),
+}
+
+impl From<NymNodeRoutingAddressError> for RedirectError {
+    fn from(err: NymNodeRoutingAddressError) -> Self {
+        RedirectError::InvalidNextHop(err)
+    }
+}
+
+impl From<SphinxError> for RedirectError {
+    fn from(err: SphinxError) -> Self {
+        RedirectError::SphinxError(err)
+    }
+}
+
+/// Wraps the data recovered from the final hop of a packet destined for a client that has moved
+/// to another gateway into a new, single-hop, packet sent directly to that gateway.
+///
+/// The SURB-ack is put back in front of the message, exactly as the sender has put it, so that
+/// it would only be sent back once the packet reaches the new gateway.
+pub(crate) fn redirect_packet(
+    redirect: &GatewayRedirect,
+    forward_ack: Option<MixPacket>,
+    message: Vec<u8>,
+) -> Result<MixPacket, RedirectError> {
+    let (payload, packet_size, packet_mode) = match forward_ack {
+        // the received packet was an ack itself
+        None => (message, PacketSize::AckPacket, PacketMode::Mix),
+        Some(forward_ack) => {
+            let packet_mode = forward_ack.packet_mode();
+            let payload: Vec<_> = forward_ack
+                .next_hop()
+                .as_zero_padded_bytes(MAX_NODE_ADDRESS_UNPADDED_LEN)
+                .into_iter()
+                .chain(forward_ack.into_sphinx_packet().to_bytes().into_iter())
+                .chain(message.into_iter())
+                .collect();
+
+            let packet_size = [PacketSize::RegularPacket, PacketSize::ExtendedPacket]
+                .into_iter()
+                .find(|size| size.plaintext_size() >= payload.len())
+                .ok_or(RedirectError::PacketTooLarge)?;
+            (payload, packet_size, packet_mode)
+        }
+    };
+
+    let next_hop = redirect.new_gateway_mix_address();
+    let route = [Node::new(
+        next_hop.try_into()?,
+        redirect.new_gateway_sphinx_key().into(),
+    )];
+    let destination = Destination::new(redirect.client_address(), Default::default());
+    // the packet has already been delayed on its way to us
+    let delays = [Delay::new_from_nanos(0)];
+
+    let packet = SphinxPacketBuilder::new()
+        .with_payload_size(packet_size.payload_size())
+        .build_packet(payload, &route, &destination, &delays)?;
+
+    Ok(MixPacket::new(next_hop, packet, packet_mode))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::asymmetric::{encryption, identity};
+    use mixnode_common::packet_processor::processor::{MixProcessingResult, SphinxPacketProcessor};
+    use nymsphinx::framing::packet::FramedSphinxPacket;
+    use rand::rngs::OsRng;
+    use std::net::SocketAddr;
+
+    #[test]
+    fn redirected_packets_are_recovered_by_the_new_gateway() {
+        let mut rng = OsRng;
+        let client_keys = identity::KeyPair::new(&mut rng);
+        let old_gateway = identity::KeyPair::new(&mut rng);
+        let new_gateway = identity::KeyPair::new(&mut rng);
+        let new_gateway_sphinx = encryption::KeyPair::new(&mut rng);
+        let mix_address: SocketAddr = "127.0.0.1:1789".parse().unwrap();
+
+        let redirect = GatewayRedirect::new(
+            &client_keys,
+            *old_gateway.public_key(),
+            *new_gateway.public_key(),
+            *new_gateway_sphinx.public_key(),
+            mix_address.into(),
+            1667304000,
+        );
+
+        // pretend the sender's ack is just another packet going through the redirect
+        let ack = redirect_packet(&redirect, None, vec![1; 10]).unwrap();
+        let ack_next_hop = ack.next_hop();
+        let ack_bytes = ack.sphinx_packet().to_bytes();
+        let message = vec![42; 100];
+        let redirected = redirect_packet(&redirect, Some(ack), message.clone()).unwrap();
+        assert_eq!(redirected.next_hop(), mix_address.into());
+
+        let processor = SphinxPacketProcessor::new(new_gateway_sphinx.private_key().into());
+        let framed = FramedSphinxPacket::new(redirected.into_sphinx_packet(), PacketMode::Mix);
+        match processor.process_received(framed).unwrap() {
+            MixProcessingResult::FinalHop(final_hop) => {
+                assert_eq!(final_hop.destination, redirect.client_address());
+                assert_eq!(final_hop.message, message);
+                let recovered_ack = final_hop.forward_ack.unwrap();
+                assert_eq!(recovered_ack.next_hop(), ack_next_hop);
+                assert_eq!(recovered_ack.sphinx_packet().to_bytes(), ack_bytes);
+            }
+            MixProcessingResult::ForwardHop(..) => panic!("expected the final hop"),
+        }
+    }
+}
@@ -3,15 +3,20 @@
 
 use crate::commands::sign::load_identity_keys;
 use crate::commands::validate_bech32_address_or_exit;
-use crate::config::Config;
+use crate::config::{Config, StorageDatabase};
 use crate::node::client_handling::active_clients::ActiveClientsStore;
 use crate::node::client_handling::websocket;
 use crate::node::mixnet_handling::receiver::connection_handler::ConnectionHandler;
 use crate::node::statistics::collector::GatewayStatisticsCollector;
+use crate::node::storage::retention::{InboxPruner, SharedEvictionMetrics};
 use crate::node::storage::Storage;
 use crypto::asymmetric::{encryption, identity};
 use log::*;
 use mixnet_client::forwarder::{MixForwardingSender, PacketForwarder};
+use mixnet_client::{LinkConfig, LinkPeers};
+use mixnode_common::link_peers::LinkPeersRefresher;
+use mixnode_common::packet_processor::processor::SphinxPacketProcessor;
+use mixnode_common::sphinx_key_rotation::{RotatingNode, SphinxKeyRotationController};
 #[cfg(feature = "coconut")]
 use network_defaults::NymNetworkDetails;
 use rand::seq::SliceRandom;
@@ -20,6 +25,7 @@ use statistics_common::collector::StatisticsSender;
 use std::net::SocketAddr;
 use std::process;
 use std::sync::Arc;
+use task::{ShutdownListener, ShutdownNotifier};
 
 use crate::config::persistence::pathfinder::GatewayPathfinder;
 #[cfg(feature = "coconut")]
@@ -43,9 +49,16 @@ pub(crate) async fn create_gateway(config: Config) -> Gateway<PersistentStorage>
 }
 
 async fn initialise_storage(config: &Config) -> PersistentStorage {
-    let path = config.get_persistent_store_path();
     let retrieval_limit = config.get_message_retrieval_limit();
-    match PersistentStorage::init(path, retrieval_limit).await {
+    let storage = match config.get_storage_backend() {
+        StorageDatabase::Sqlite => {
+            PersistentStorage::init(config.get_persistent_store_path(), retrieval_limit).await
+        }
+        StorageDatabase::Postgres => {
+            PersistentStorage::init_postgres(config.get_postgres_url(), retrieval_limit).await
+        }
+    };
+    match storage {
         Err(err) => panic!("failed to initialise gateway storage - {}", err),
         Ok(storage) => storage,
     }
@@ -151,21 +164,109 @@ where
         );
     }
 
+    /// Creates the sphinx packet processor, which, if the gateway was restarted in the middle of
+    /// the key rotation, is still going to accept packets created for the previous key.
+    fn create_sphinx_processor(&self) -> SphinxPacketProcessor {
+        let pathfinder = GatewayPathfinder::new_from_config(&self.config);
+        let current_key = self.sphinx_keypair.private_key().into();
+
+        match pathfinder.sphinx_key_files().load_previous_keypair() {
+            Some(Ok(previous)) => {
+                info!("Found the pre-rotation sphinx key - it is going to be accepted for the remainder of the grace period");
+                SphinxPacketProcessor::new_with_previous_key(
+                    current_key,
+                    previous.private_key().into(),
+                )
+            }
+            Some(Err(err)) => {
+                warn!("Failed to load the pre-rotation sphinx key - {}", err);
+                SphinxPacketProcessor::new(current_key)
+            }
+            None => SphinxPacketProcessor::new(current_key),
+        }
+    }
+
+    fn start_sphinx_key_rotation_controller(
+        &self,
+        sphinx_processor: SphinxPacketProcessor,
+        shutdown: ShutdownListener,
+    ) {
+        info!("Starting sphinx key rotation controller...");
+
+        let pathfinder = GatewayPathfinder::new_from_config(&self.config);
+        let validator_api = self
+            .config
+            .get_validator_api_endpoints()
+            .choose(&mut thread_rng())
+            .expect("The list of validator apis is empty")
+            .clone();
+
+        SphinxKeyRotationController::new(
+            RotatingNode::Gateway {
+                identity: self.identity_keypair.public_key().to_base58_string(),
+            },
+            pathfinder.sphinx_key_files(),
+            sphinx_processor,
+            validator_api,
+            self.config.get_sphinx_key_rotation_check_interval(),
+            shutdown,
+        )
+        .start();
+    }
+
+    fn start_inbox_pruner(
+        &self,
+        evicted_messages: SharedEvictionMetrics,
+        shutdown: ShutdownListener,
+    ) {
+        info!("Starting stored messages pruner...");
+
+        InboxPruner::new(
+            self.storage.clone(),
+            self.config.get_stored_messages_max_age(),
+            self.config.get_stored_messages_max_client_bytes(),
+            self.config.get_stored_messages_pruning_interval(),
+            evicted_messages,
+            shutdown,
+        )
+        .start();
+    }
+
+    /// Obtains the link keys of all nodes in the network and starts keeping them up to date.
+    async fn start_link_peers_refresher(&self, shutdown: ShutdownListener) -> LinkConfig {
+        let link_encryption = self.config.get_link_encryption();
+        info!("Starting link peers refresher (link encryption: {link_encryption:?})...");
+
+        let peers = LinkPeers::default();
+        let refresher = LinkPeersRefresher::new(
+            peers.clone(),
+            self.config.get_validator_api_endpoints(),
+            self.config.get_link_peers_refresh_interval(),
+            shutdown,
+        );
+        refresher.refresh().await;
+        refresher.start();
+
+        LinkConfig::new(link_encryption, &self.identity_keypair, peers)
+    }
+
     fn start_mix_socket_listener(
         &self,
+        sphinx_processor: SphinxPacketProcessor,
         ack_sender: MixForwardingSender,
         active_clients_store: ActiveClientsStore,
+        link: LinkConfig,
     ) {
         info!("Starting mix socket listener...");
 
-        let packet_processor =
-            mixnet_handling::PacketProcessor::new(self.sphinx_keypair.private_key());
+        let packet_processor = mixnet_handling::PacketProcessor::new(sphinx_processor);
 
         let connection_handler = ConnectionHandler::new(
             packet_processor,
             self.storage.clone(),
             ack_sender,
             active_clients_store,
+            link,
         );
 
         let listening_address = SocketAddr::new(
@@ -203,7 +304,7 @@ where
         );
     }
 
-    fn start_packet_forwarder(&self) -> MixForwardingSender {
+    fn start_packet_forwarder(&self, link: LinkConfig) -> MixForwardingSender {
         info!("Starting mix packet forwarder...");
 
         let (mut packet_forwarder, packet_sender) = PacketForwarder::new(
@@ -211,6 +312,7 @@ where
             self.config.get_packet_forwarding_maximum_backoff(),
             self.config.get_initial_connection_timeout(),
             self.config.get_maximum_connection_buffer_size(),
+            link,
         );
 
         tokio::spawn(async move { packet_forwarder.run().await });
@@ -320,12 +422,23 @@ where
         )
         .expect("Could not create coconut verifier");
 
-        let mix_forwarding_channel = self.start_packet_forwarder();
+        // currently only used by the link peers refresher, the sphinx key rotation controller
+        // and the inbox pruner
+        let shutdown = ShutdownNotifier::default();
+
+        let link = self.start_link_peers_refresher(shutdown.subscribe()).await;
+        let mix_forwarding_channel = self.start_packet_forwarder(link.clone());
 
         let active_clients_store = ActiveClientsStore::new();
+        let sphinx_processor = self.create_sphinx_processor();
+        self.start_sphinx_key_rotation_controller(sphinx_processor.clone(), shutdown.subscribe());
+        let evicted_messages = SharedEvictionMetrics::new();
+        self.start_inbox_pruner(evicted_messages.clone(), shutdown.subscribe());
         self.start_mix_socket_listener(
+            sphinx_processor,
             mix_forwarding_channel.clone(),
             active_clients_store.clone(),
+            link,
         );
 
         if self.config.get_enabled_statistics() {
@@ -333,6 +446,7 @@ where
             let stats_collector = GatewayStatisticsCollector::new(
                 self.identity_keypair.public_key().to_base58_string(),
                 active_clients_store.clone(),
+                evicted_messages,
                 statistics_service_url,
             );
             let mut stats_sender = StatisticsSender::new(stats_collector);
@@ -350,6 +464,7 @@ where
 
         info!("Finished nym gateway startup procedure - it should now be able to receive mix and client traffic!");
 
-        self.wait_for_interrupt().await
+        self.wait_for_interrupt().await;
+        shutdown.signal_shutdown().ok();
     }
 }
@@ -12,10 +12,12 @@ use statistics_common::{
 };
 
 use crate::node::client_handling::active_clients::ActiveClientsStore;
+use crate::node::storage::retention::SharedEvictionMetrics;
 
 pub(crate) struct GatewayStatisticsCollector {
     gateway_id: String,
     active_clients_store: ActiveClientsStore,
+    evicted_messages: SharedEvictionMetrics,
     statistics_service_url: Url,
 }
 
@@ -23,11 +25,13 @@ impl GatewayStatisticsCollector {
     pub fn new(
         gateway_id: String,
         active_clients_store: ActiveClientsStore,
+        evicted_messages: SharedEvictionMetrics,
         statistics_service_url: Url,
     ) -> Self {
         GatewayStatisticsCollector {
             gateway_id,
             active_clients_store,
+            evicted_messages,
             statistics_service_url,
         }
     }
@@ -41,10 +45,12 @@ impl StatisticsCollector for GatewayStatisticsCollector {
         timestamp: DateTime<Utc>,
     ) -> StatsMessage {
         let inbox_count = self.active_clients_store.size() as u32;
-        let stats_data = vec![StatsData::Gateway(StatsGatewayData::new(
-            self.gateway_id.clone(),
-            inbox_count,
-        ))];
+        // taken rather than reset afterwards, so that nothing evicted in the meantime is lost
+        let evicted = self.evicted_messages.take();
+        let stats_data = vec![StatsData::Gateway(
+            StatsGatewayData::new(self.gateway_id.clone(), inbox_count)
+                .with_evicted_messages(evicted.expired, evicted.over_quota),
+        )];
         StatsMessage {
             stats_data,
             interval_seconds: interval.as_secs() as u32,
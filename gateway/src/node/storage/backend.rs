@@ -0,0 +1,199 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::node::storage::models::{
+    PersistedBandwidth, PersistedRedirect, PersistedSharedKeys, StoredMessage,
+};
+use async_trait::async_trait;
+
+/// Database operations backing the `PersistentStorage`, i.e. those of the shared keys, inbox and
+/// bandwidth managers. It is implemented for each supported database, so that the gateway could
+/// choose one at runtime.
+#[async_trait]
+pub(crate) trait StorageBackend: Send + Sync {
+    /// Inserts provided derived shared keys into the database.
+    /// If keys previously existed for the provided device of the client, they are overwritten with the new data.
+    ///
+    /// # Arguments
+    ///
+    /// * `shared_keys`: shared encryption (AES128CTR) and mac (hmac-blake3) derived shared keys to store.
+    async fn insert_shared_keys(&self, shared_keys: PersistedSharedKeys)
+        -> Result<(), sqlx::Error>;
+
+    /// Tries to retrieve shared keys stored for the particular device of the client.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address_bs58`: base58-encoded address of the client
+    /// * `device_id_bs58`: base58-encoded id of the device of the client
+    async fn get_shared_keys(
+        &self,
+        client_address_bs58: &str,
+        device_id_bs58: &str,
+    ) -> Result<Option<PersistedSharedKeys>, sqlx::Error>;
+
+    /// Removes from the database shared keys derived with the particular device of the client.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address_bs58`: base58-encoded address of the client
+    /// * `device_id_bs58`: base58-encoded id of the device of the client
+    async fn remove_shared_keys(
+        &self,
+        client_address_bs58: &str,
+        device_id_bs58: &str,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Counts the devices of the client for which shared keys are stored.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address_bs58`: base58-encoded address of the client
+    async fn count_devices(&self, client_address_bs58: &str) -> Result<i64, sqlx::Error>;
+
+    /// Inserts new message to the storage for an offline client for future retrieval,
+    /// returning its id.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address_bs58`: base58-encoded address of the client
+    /// * `content`: raw content of the message to store.
+    async fn insert_message(
+        &self,
+        client_address_bs58: &str,
+        content: Vec<u8>,
+    ) -> Result<i64, sqlx::Error>;
+
+    /// Retrieves messages stored for the particular client specified by the provided address,
+    /// respecting the retrieval limit of the backend.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address_bs58`: base58-encoded address of the client
+    /// * `start_after`: optional starting id of the messages to grab
+    ///
+    /// returns the retrieved messages alongside optional id of the last message retrieved if
+    /// there are more messages to retrieve.
+    async fn get_messages(
+        &self,
+        client_address_bs58: &str,
+        start_after: Option<i64>,
+    ) -> Result<(Vec<StoredMessage>, Option<i64>), sqlx::Error>;
+
+    /// Retrieves the id of the last message delivered to the particular device of the client.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address_bs58`: base58-encoded address of the client
+    /// * `device_id_bs58`: base58-encoded id of the device of the client
+    async fn get_inbox_cursor(
+        &self,
+        client_address_bs58: &str,
+        device_id_bs58: &str,
+    ) -> Result<Option<i64>, sqlx::Error>;
+
+    /// Sets the id of the last message delivered to the particular device of the client.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address_bs58`: base58-encoded address of the client
+    /// * `device_id_bs58`: base58-encoded id of the device of the client
+    /// * `last_delivered_id`: id of the last message delivered to the device
+    async fn set_inbox_cursor(
+        &self,
+        client_address_bs58: &str,
+        device_id_bs58: &str,
+        last_delivered_id: i64,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Removes messages of the client that have been delivered to all devices it has registered,
+    /// returning their number.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address_bs58`: base58-encoded address of the client
+    async fn remove_delivered_messages(
+        &self,
+        client_address_bs58: &str,
+    ) -> Result<u64, sqlx::Error>;
+
+    /// Removes all messages received before the specified time, returning their number.
+    ///
+    /// # Arguments
+    ///
+    /// * `received_before`: unix timestamp (in seconds) of the oldest message to keep
+    async fn remove_messages_received_before(
+        &self,
+        received_before: i64,
+    ) -> Result<u64, sqlx::Error>;
+
+    /// Removes the oldest messages of every client whose stored messages exceed the specified
+    /// size, returning their number.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_client_bytes`: maximum total size of messages stored for a single client
+    async fn remove_messages_exceeding_quota(
+        &self,
+        max_client_bytes: i64,
+    ) -> Result<u64, sqlx::Error>;
+
+    /// Inserts the redirect record of the client, unless a record issued at the same time or
+    /// later is already stored. Returns whether the record got inserted.
+    ///
+    /// # Arguments
+    ///
+    /// * `redirect`: the redirect record to store.
+    async fn insert_redirect(&self, redirect: PersistedRedirect) -> Result<bool, sqlx::Error>;
+
+    /// Retrieves all stored redirect records.
+    async fn get_all_redirects(&self) -> Result<Vec<PersistedRedirect>, sqlx::Error>;
+
+    /// Removes the redirect record of the particular client.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address_bs58`: base58-encoded address of the client
+    async fn remove_redirect(&self, client_address_bs58: &str) -> Result<(), sqlx::Error>;
+
+    /// Creates a new bandwidth entry for the particular client.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address_bs58`: base58-encoded address of the client.
+    async fn insert_new_client(&self, client_address_bs58: &str) -> Result<(), sqlx::Error>;
+
+    /// Tries to retrieve available bandwidth for the particular client.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address_bs58`: base58-encoded address of the client.
+    async fn get_available_bandwidth(
+        &self,
+        client_address_bs58: &str,
+    ) -> Result<Option<PersistedBandwidth>, sqlx::Error>;
+
+    /// Increases available bandwidth of the particular client by the specified amount.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address_bs58`: base58-encoded address of the client.
+    /// * `amount`: amount of available bandwidth to be added to the client.
+    async fn increase_available_bandwidth(
+        &self,
+        client_address_bs58: &str,
+        amount: i64,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Decreases available bandwidth of the particular client by the specified amount.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address_bs58`: base58-encoded address of the client.
+    /// * `amount`: amount of available bandwidth to be removed from the client.
+    async fn decrease_available_bandwidth(
+        &self,
+        client_address_bs58: &str,
+        amount: i64,
+    ) -> Result<(), sqlx::Error>;
+}
@@ -1,6 +1,7 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use gateway_requests::redirect::GatewayRedirectError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -10,4 +11,7 @@ pub(crate) enum StorageError {
 
     #[error("Failed to perform database migration - {0}")]
     MigrationError(#[from] sqlx::migrate::MigrateError),
+
+    #[error("The stored redirect record is malformed - {0}")]
+    MalformedRedirect(#[from] GatewayRedirectError),
 }
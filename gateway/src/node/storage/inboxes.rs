@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::node::storage::models::StoredMessage;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Clone)]
 pub(crate) struct InboxManager {
@@ -31,19 +32,27 @@ impl InboxManager {
     ///
     /// * `client_address_bs58`: base58-encoded address of the client
     /// * `content`: raw content of the message to store.
+    ///
+    /// returns the id of the stored message.
     pub(crate) async fn insert_message(
         &self,
         client_address_bs58: &str,
         content: Vec<u8>,
-    ) -> Result<(), sqlx::Error> {
-        sqlx::query!(
-            "INSERT INTO message_store(client_address_bs58, content) VALUES (?, ?)",
+    ) -> Result<i64, sqlx::Error> {
+        let received_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("the system clock is set before the unix epoch")
+            .as_secs() as i64;
+
+        let result = sqlx::query!(
+            "INSERT INTO message_store(client_address_bs58, content, received_at) VALUES (?, ?, ?)",
             client_address_bs58,
             content,
+            received_at,
         )
         .execute(&self.connection_pool)
         .await?;
-        Ok(())
+        Ok(result.last_insert_rowid())
     }
 
     /// Retrieves messages stored for the particular client specified by the provided address.
@@ -108,15 +117,133 @@ impl InboxManager {
         }
     }
 
-    /// Removes message with the specified id
+    /// Retrieves the id of the last stored message delivered to the particular device of the client.
     ///
     /// # Arguments
     ///
-    /// * `id`: id of the message to remove
-    pub(crate) async fn remove_message(&self, id: i64) -> Result<(), sqlx::Error> {
-        sqlx::query!("DELETE FROM message_store WHERE id = ?", id)
-            .execute(&self.connection_pool)
-            .await?;
+    /// * `client_address_bs58`: base58-encoded address of the client
+    /// * `device_id_bs58`: base58-encoded id of the device of the client
+    pub(crate) async fn get_inbox_cursor(
+        &self,
+        client_address_bs58: &str,
+        device_id_bs58: &str,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        let cursor = sqlx::query!(
+            "SELECT last_delivered_id FROM inbox_cursors WHERE client_address_bs58 = ? AND device_id_bs58 = ?",
+            client_address_bs58,
+            device_id_bs58
+        )
+        .fetch_optional(&self.connection_pool)
+        .await?;
+        Ok(cursor.map(|cursor| cursor.last_delivered_id))
+    }
+
+    /// Sets the id of the last stored message delivered to the particular device of the client.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address_bs58`: base58-encoded address of the client
+    /// * `device_id_bs58`: base58-encoded id of the device of the client
+    /// * `last_delivered_id`: id of the last message delivered to the device
+    pub(crate) async fn set_inbox_cursor(
+        &self,
+        client_address_bs58: &str,
+        device_id_bs58: &str,
+        last_delivered_id: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT OR REPLACE INTO inbox_cursors(client_address_bs58, device_id_bs58, last_delivered_id) VALUES (?, ?, ?)",
+            client_address_bs58,
+            device_id_bs58,
+            last_delivered_id
+        )
+        .execute(&self.connection_pool)
+        .await?;
         Ok(())
     }
+
+    /// Removes messages of the client that have been delivered to every device it has registered,
+    /// i.e. those preceding the inbox cursors of all of its devices. Devices that have never
+    /// received any stored message keep all of them.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address_bs58`: base58-encoded address of the client
+    ///
+    /// returns the number of removed messages.
+    pub(crate) async fn remove_delivered_messages(
+        &self,
+        client_address_bs58: &str,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+                DELETE FROM message_store
+                WHERE client_address_bs58 = ? AND id <= (
+                    SELECT MIN(COALESCE(inbox_cursors.last_delivered_id, 0))
+                    FROM shared_keys
+                    LEFT JOIN inbox_cursors
+                        ON inbox_cursors.client_address_bs58 = shared_keys.client_address_bs58
+                        AND inbox_cursors.device_id_bs58 = shared_keys.device_id_bs58
+                    WHERE shared_keys.client_address_bs58 = ?
+                );
+            "#,
+            client_address_bs58,
+            client_address_bs58
+        )
+        .execute(&self.connection_pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Removes all messages received before the specified time.
+    ///
+    /// # Arguments
+    ///
+    /// * `received_before`: unix timestamp (in seconds) of the oldest message to keep
+    ///
+    /// returns the number of removed messages.
+    pub(crate) async fn remove_messages_received_before(
+        &self,
+        received_before: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            "DELETE FROM message_store WHERE received_at < ?",
+            received_before
+        )
+        .execute(&self.connection_pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// For each client whose stored messages take more than `max_client_bytes` in total, removes
+    /// their oldest messages, so that only the most recent ones, fitting within the limit, are kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_client_bytes`: maximum total size of messages stored for a single client
+    ///
+    /// returns the number of removed messages.
+    pub(crate) async fn remove_messages_exceeding_quota(
+        &self,
+        max_client_bytes: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+                DELETE FROM message_store
+                WHERE id IN (
+                    SELECT id FROM (
+                        SELECT id, SUM(LENGTH(content)) OVER (
+                            PARTITION BY client_address_bs58 ORDER BY id DESC
+                        ) AS newer_bytes
+                        FROM message_store
+                    )
+                    WHERE newer_bytes > ?
+                );
+            "#,
+            max_client_bytes
+        )
+        .execute(&self.connection_pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
 }
@@ -1,73 +1,101 @@
 // Copyright 2020 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::node::storage::bandwidth::BandwidthManager;
+use crate::node::storage::backend::StorageBackend;
 use crate::node::storage::error::StorageError;
-use crate::node::storage::inboxes::InboxManager;
-use crate::node::storage::models::{PersistedSharedKeys, StoredMessage};
-use crate::node::storage::shared_keys::SharedKeysManager;
+use crate::node::storage::models::{PersistedRedirect, PersistedSharedKeys, StoredMessage};
+use crate::node::storage::postgres::PostgresBackend;
+use crate::node::storage::sqlite::SqliteBackend;
 use async_trait::async_trait;
+use gateway_requests::device::DeviceId;
+use gateway_requests::redirect::GatewayRedirect;
 use gateway_requests::registration::handshake::SharedKeys;
-use log::{debug, error};
+use log::warn;
 use nymsphinx::DestinationAddressBytes;
-use sqlx::ConnectOptions;
+use std::collections::HashMap;
 use std::path::Path;
+use std::sync::{Arc, RwLock};
 
+mod backend;
 mod bandwidth;
 pub(crate) mod error;
 mod inboxes;
 mod models;
+mod postgres;
+mod redirects;
+pub(crate) mod retention;
 mod shared_keys;
+mod sqlite;
+#[cfg(test)]
+pub(crate) mod test_utils;
 
 #[async_trait]
 pub(crate) trait Storage: Send + Sync {
     /// Inserts provided derived shared keys into the database.
-    /// If keys previously existed for the provided client, they are overwritten with the new data.
+    /// If keys previously existed for the provided device of the client, they are overwritten with the new data.
     ///
     /// # Arguments
     ///
     /// * `client_address`: address of the client
+    /// * `device`: id of the device of the client that derived the keys
     /// * `shared_keys`: shared encryption (AES128CTR) and mac (hmac-blake3) derived shared keys to store.
     async fn insert_shared_keys(
         &self,
         client_address: DestinationAddressBytes,
+        device: DeviceId,
         shared_keys: SharedKeys,
     ) -> Result<(), StorageError>;
 
-    /// Tries to retrieve shared keys stored for the particular client.
+    /// Tries to retrieve shared keys stored for the particular device of the client.
     ///
     /// # Arguments
     ///
     /// * `client_address`: address of the client
+    /// * `device`: id of the device of the client
     async fn get_shared_keys(
         &self,
         client_address: DestinationAddressBytes,
+        device: DeviceId,
     ) -> Result<Option<PersistedSharedKeys>, StorageError>;
 
-    /// Removes from the database shared keys derived with the particular client.
+    /// Removes from the database shared keys derived with the particular device of the client.
     ///
     /// # Arguments
     ///
     /// * `client_address`: address of the client
+    /// * `device`: id of the device of the client
     // currently there is no code flow that causes removal (not overwriting)
     // of the stored keys. However, retain the function for consistency and completion sake
     #[allow(dead_code)]
     async fn remove_shared_keys(
         &self,
         client_address: DestinationAddressBytes,
+        device: DeviceId,
     ) -> Result<(), StorageError>;
 
+    /// Counts the devices of the client that have registered with the gateway.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address`: address of the client
+    async fn count_registered_devices(
+        &self,
+        client_address: DestinationAddressBytes,
+    ) -> Result<usize, StorageError>;
+
     /// Inserts new message to the storage for an offline client for future retrieval.
     ///
     /// # Arguments
     ///
     /// * `client_address`: address of the client
     /// * `message`: raw message to store.
+    ///
+    /// returns the id of the stored message.
     async fn store_message(
         &self,
         client_address: DestinationAddressBytes,
         message: Vec<u8>,
-    ) -> Result<(), StorageError>;
+    ) -> Result<i64, StorageError>;
 
     /// Retrieves messages stored for the particular client specified by the provided address.
     ///
@@ -84,12 +112,99 @@ pub(crate) trait Storage: Send + Sync {
         start_after: Option<i64>,
     ) -> Result<(Vec<StoredMessage>, Option<i64>), StorageError>;
 
-    /// Removes messages with the specified ids
+    /// Retrieves the id of the last stored message delivered to the particular device of the client.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address`: address of the client
+    /// * `device`: id of the device of the client
+    async fn get_inbox_cursor(
+        &self,
+        client_address: DestinationAddressBytes,
+        device: DeviceId,
+    ) -> Result<Option<i64>, StorageError>;
+
+    /// Marks all stored messages up to (and including) the specified one as delivered to the
+    /// particular device of the client.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address`: address of the client
+    /// * `device`: id of the device of the client
+    /// * `last_delivered_id`: id of the last message delivered to the device
+    async fn set_inbox_cursor(
+        &self,
+        client_address: DestinationAddressBytes,
+        device: DeviceId,
+        last_delivered_id: i64,
+    ) -> Result<(), StorageError>;
+
+    /// Removes stored messages of the client that have been delivered to all of its registered devices.
     ///
     /// # Arguments
     ///
-    /// * `ids`: ids of the messages to remove
-    async fn remove_messages(&self, ids: Vec<i64>) -> Result<(), StorageError>;
+    /// * `client_address`: address of the client
+    ///
+    /// returns the number of removed messages.
+    async fn remove_delivered_messages(
+        &self,
+        client_address: DestinationAddressBytes,
+    ) -> Result<u64, StorageError>;
+
+    /// Removes all messages received before the specified time.
+    ///
+    /// # Arguments
+    ///
+    /// * `received_before`: unix timestamp (in seconds) of the oldest message to keep
+    ///
+    /// returns the number of removed messages.
+    async fn remove_messages_received_before(
+        &self,
+        received_before: i64,
+    ) -> Result<u64, StorageError>;
+
+    /// Removes the oldest messages of every client whose stored messages exceed the specified size.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_client_bytes`: maximum total size of messages stored for a single client
+    ///
+    /// returns the number of removed messages.
+    async fn remove_messages_exceeding_quota(
+        &self,
+        max_client_bytes: i64,
+    ) -> Result<u64, StorageError>;
+
+    /// Stores the (already verified) record of the client that has moved to another gateway,
+    /// unless a record issued at the same time or later is already stored.
+    ///
+    /// # Arguments
+    ///
+    /// * `redirect`: the redirect record of the client
+    ///
+    /// returns whether the record got stored.
+    async fn store_redirect(&self, redirect: &GatewayRedirect) -> Result<bool, StorageError>;
+
+    /// Tries to retrieve the redirect record of the particular client.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address`: address of the client
+    async fn get_redirect(
+        &self,
+        client_address: DestinationAddressBytes,
+    ) -> Result<Option<GatewayRedirect>, StorageError>;
+
+    /// Removes the redirect record of the particular client, for example once it registers
+    /// with the gateway again.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address`: address of the client
+    async fn remove_redirect(
+        &self,
+        client_address: DestinationAddressBytes,
+    ) -> Result<(), StorageError>;
 
     /// Creates a new bandwidth entry for the particular client.
     ///
@@ -136,16 +251,56 @@ pub(crate) trait Storage: Send + Sync {
     ) -> Result<(), StorageError>;
 }
 
-// note that clone here is fine as upon cloning the same underlying pool will be used
+// note that clone here is fine as upon cloning the same underlying backend will be used
 #[derive(Clone)]
 pub(crate) struct PersistentStorage {
-    shared_key_manager: SharedKeysManager,
-    inbox_manager: InboxManager,
-    bandwidth_manager: BandwidthManager,
+    backend: Arc<dyn StorageBackend>,
+
+    /// Redirect records of all clients, keyed by their base58-encoded addresses. They're looked
+    /// up for every packet received for an offline client, so they're loaded into memory upon
+    /// the first lookup and kept in sync with the database afterwards.
+    redirects: Arc<RwLock<Option<HashMap<String, GatewayRedirect>>>>,
 }
 
 impl PersistentStorage {
-    /// Initialises `PersistentStorage` using the provided path.
+    fn new(backend: Arc<dyn StorageBackend>) -> Self {
+        PersistentStorage {
+            backend,
+            redirects: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    async fn load_redirects(&self) -> Result<(), StorageError> {
+        if self
+            .redirects
+            .read()
+            .expect("redirects lock got poisoned")
+            .is_some()
+        {
+            return Ok(());
+        }
+
+        let mut redirects = HashMap::new();
+        for persisted in self.backend.get_all_redirects().await? {
+            match GatewayRedirect::try_from_base58_string(persisted.record_bs58) {
+                Ok(redirect) => {
+                    redirects.insert(persisted.client_address_bs58, redirect);
+                }
+                Err(err) => warn!(
+                    "The stored redirect record of {} is malformed - {err}",
+                    persisted.client_address_bs58
+                ),
+            }
+        }
+
+        let mut guard = self.redirects.write().expect("redirects lock got poisoned");
+        if guard.is_none() {
+            *guard = Some(redirects);
+        }
+        Ok(())
+    }
+
+    /// Initialises `PersistentStorage` backed by SQLite database at the provided path.
     ///
     /// # Arguments
     ///
@@ -155,40 +310,31 @@ impl PersistentStorage {
         database_path: P,
         message_retrieval_limit: i64,
     ) -> Result<Self, StorageError> {
-        debug!(
-            "Attempting to connect to database {:?}",
-            database_path.as_ref().as_os_str()
-        );
-
-        // TODO: we can inject here more stuff based on our gateway global config
-        // struct. Maybe different pool size or timeout intervals?
-        let mut opts = sqlx::sqlite::SqliteConnectOptions::new()
-            .filename(database_path)
-            .create_if_missing(true);
-
-        // TODO: do we want auto_vacuum ?
-
-        opts.disable_statement_logging();
-
-        let connection_pool = match sqlx::SqlitePool::connect_with(opts).await {
-            Ok(db) => db,
-            Err(err) => {
-                error!("Failed to connect to SQLx database: {}", err);
-                return Err(err.into());
-            }
-        };
+        let backend = SqliteBackend::init(database_path, message_retrieval_limit).await?;
+        Ok(PersistentStorage::new(Arc::new(backend)))
+    }
 
-        if let Err(err) = sqlx::migrate!("./migrations").run(&connection_pool).await {
-            error!("Failed to perform migration on the SQLx database: {}", err);
-            return Err(err.into());
-        }
+    /// Initialises `PersistentStorage` backed by Postgres database at the provided url.
+    ///
+    /// # Arguments
+    ///
+    /// * `database_url`: connection string of the database.
+    /// * `message_retrieval_limit`: maximum number of stored client messages that can be retrieved at once.
+    pub async fn init_postgres(
+        database_url: &str,
+        message_retrieval_limit: i64,
+    ) -> Result<Self, StorageError> {
+        let backend = PostgresBackend::init(database_url, message_retrieval_limit).await?;
+        Ok(PersistentStorage::new(Arc::new(backend)))
+    }
 
-        // the cloning here are cheap as connection pool is stored behind an Arc
-        Ok(PersistentStorage {
-            shared_key_manager: SharedKeysManager::new(connection_pool.clone()),
-            inbox_manager: InboxManager::new(connection_pool.clone(), message_retrieval_limit),
-            bandwidth_manager: BandwidthManager::new(connection_pool),
-        })
+    #[cfg(test)]
+    pub(crate) async fn init_postgres_with(
+        opts: sqlx::postgres::PgConnectOptions,
+        message_retrieval_limit: i64,
+    ) -> Result<Self, StorageError> {
+        let backend = PostgresBackend::init_with(opts, message_retrieval_limit).await?;
+        Ok(PersistentStorage::new(Arc::new(backend)))
     }
 }
 
@@ -197,13 +343,15 @@ impl Storage for PersistentStorage {
     async fn insert_shared_keys(
         &self,
         client_address: DestinationAddressBytes,
+        device: DeviceId,
         shared_keys: SharedKeys,
     ) -> Result<(), StorageError> {
         let persisted_shared_keys = PersistedSharedKeys {
             client_address_bs58: client_address.as_base58_string(),
+            device_id_bs58: device.to_base58_string(),
             derived_aes128_ctr_blake3_hmac_keys_bs58: shared_keys.to_base58_string(),
         };
-        self.shared_key_manager
+        self.backend
             .insert_shared_keys(persisted_shared_keys)
             .await?;
         Ok(())
@@ -212,10 +360,14 @@ impl Storage for PersistentStorage {
     async fn get_shared_keys(
         &self,
         client_address: DestinationAddressBytes,
+        device: DeviceId,
     ) -> Result<Option<PersistedSharedKeys>, StorageError> {
         let keys = self
-            .shared_key_manager
-            .get_shared_keys(&client_address.as_base58_string())
+            .backend
+            .get_shared_keys(
+                &client_address.as_base58_string(),
+                &device.to_base58_string(),
+            )
             .await?;
         Ok(keys)
     }
@@ -224,22 +376,38 @@ impl Storage for PersistentStorage {
     async fn remove_shared_keys(
         &self,
         client_address: DestinationAddressBytes,
+        device: DeviceId,
     ) -> Result<(), StorageError> {
-        self.shared_key_manager
-            .remove_shared_keys(&client_address.as_base58_string())
+        self.backend
+            .remove_shared_keys(
+                &client_address.as_base58_string(),
+                &device.to_base58_string(),
+            )
             .await?;
         Ok(())
     }
 
+    async fn count_registered_devices(
+        &self,
+        client_address: DestinationAddressBytes,
+    ) -> Result<usize, StorageError> {
+        let devices = self
+            .backend
+            .count_devices(&client_address.as_base58_string())
+            .await?;
+        Ok(devices as usize)
+    }
+
     async fn store_message(
         &self,
         client_address: DestinationAddressBytes,
         message: Vec<u8>,
-    ) -> Result<(), StorageError> {
-        self.inbox_manager
+    ) -> Result<i64, StorageError> {
+        let id = self
+            .backend
             .insert_message(&client_address.as_base58_string(), message)
             .await?;
-        Ok(())
+        Ok(id)
     }
 
     async fn retrieve_messages(
@@ -248,15 +416,127 @@ impl Storage for PersistentStorage {
         start_after: Option<i64>,
     ) -> Result<(Vec<StoredMessage>, Option<i64>), StorageError> {
         let messages = self
-            .inbox_manager
+            .backend
             .get_messages(&client_address.as_base58_string(), start_after)
             .await?;
         Ok(messages)
     }
 
-    async fn remove_messages(&self, ids: Vec<i64>) -> Result<(), StorageError> {
-        for id in ids {
-            self.inbox_manager.remove_message(id).await?;
+    async fn get_inbox_cursor(
+        &self,
+        client_address: DestinationAddressBytes,
+        device: DeviceId,
+    ) -> Result<Option<i64>, StorageError> {
+        let cursor = self
+            .backend
+            .get_inbox_cursor(
+                &client_address.as_base58_string(),
+                &device.to_base58_string(),
+            )
+            .await?;
+        Ok(cursor)
+    }
+
+    async fn set_inbox_cursor(
+        &self,
+        client_address: DestinationAddressBytes,
+        device: DeviceId,
+        last_delivered_id: i64,
+    ) -> Result<(), StorageError> {
+        self.backend
+            .set_inbox_cursor(
+                &client_address.as_base58_string(),
+                &device.to_base58_string(),
+                last_delivered_id,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn remove_delivered_messages(
+        &self,
+        client_address: DestinationAddressBytes,
+    ) -> Result<u64, StorageError> {
+        let removed = self
+            .backend
+            .remove_delivered_messages(&client_address.as_base58_string())
+            .await?;
+        Ok(removed)
+    }
+
+    async fn remove_messages_received_before(
+        &self,
+        received_before: i64,
+    ) -> Result<u64, StorageError> {
+        let removed = self
+            .backend
+            .remove_messages_received_before(received_before)
+            .await?;
+        Ok(removed)
+    }
+
+    async fn remove_messages_exceeding_quota(
+        &self,
+        max_client_bytes: i64,
+    ) -> Result<u64, StorageError> {
+        let removed = self
+            .backend
+            .remove_messages_exceeding_quota(max_client_bytes)
+            .await?;
+        Ok(removed)
+    }
+
+    async fn store_redirect(&self, redirect: &GatewayRedirect) -> Result<bool, StorageError> {
+        // make sure the cache is in place before it's updated
+        self.load_redirects().await?;
+
+        let client_address = redirect.client_address().as_base58_string();
+        let persisted_redirect = PersistedRedirect {
+            client_address_bs58: client_address.clone(),
+            record_bs58: redirect.to_base58_string(),
+            issued_at: redirect.issued_at() as i64,
+        };
+        let stored = self.backend.insert_redirect(persisted_redirect).await?;
+        if stored {
+            if let Some(redirects) = self
+                .redirects
+                .write()
+                .expect("redirects lock got poisoned")
+                .as_mut()
+            {
+                redirects.insert(client_address, redirect.clone());
+            }
+        }
+        Ok(stored)
+    }
+
+    async fn get_redirect(
+        &self,
+        client_address: DestinationAddressBytes,
+    ) -> Result<Option<GatewayRedirect>, StorageError> {
+        self.load_redirects().await?;
+        let redirect = self
+            .redirects
+            .read()
+            .expect("redirects lock got poisoned")
+            .as_ref()
+            .and_then(|redirects| redirects.get(&client_address.as_base58_string()).cloned());
+        Ok(redirect)
+    }
+
+    async fn remove_redirect(
+        &self,
+        client_address: DestinationAddressBytes,
+    ) -> Result<(), StorageError> {
+        let client_address = client_address.as_base58_string();
+        self.backend.remove_redirect(&client_address).await?;
+        if let Some(redirects) = self
+            .redirects
+            .write()
+            .expect("redirects lock got poisoned")
+            .as_mut()
+        {
+            redirects.remove(&client_address);
         }
         Ok(())
     }
@@ -265,7 +545,7 @@ impl Storage for PersistentStorage {
         &self,
         client_address: DestinationAddressBytes,
     ) -> Result<(), StorageError> {
-        self.bandwidth_manager
+        self.backend
             .insert_new_client(&client_address.as_base58_string())
             .await?;
         Ok(())
@@ -276,7 +556,7 @@ impl Storage for PersistentStorage {
         client_address: DestinationAddressBytes,
     ) -> Result<Option<i64>, StorageError> {
         let res = self
-            .bandwidth_manager
+            .backend
             .get_available_bandwidth(&client_address.as_base58_string())
             .await
             .map(|bandwidth_option| bandwidth_option.map(|bandwidth| bandwidth.available))?;
@@ -288,7 +568,7 @@ impl Storage for PersistentStorage {
         client_address: DestinationAddressBytes,
         amount: i64,
     ) -> Result<(), StorageError> {
-        self.bandwidth_manager
+        self.backend
             .increase_available_bandwidth(&client_address.as_base58_string(), amount)
             .await?;
         Ok(())
@@ -299,7 +579,7 @@ impl Storage for PersistentStorage {
         client_address: DestinationAddressBytes,
         amount: i64,
     ) -> Result<(), StorageError> {
-        self.bandwidth_manager
+        self.backend
             .decrease_available_bandwidth(&client_address.as_base58_string(), amount)
             .await?;
         Ok(())
@@ -325,6 +605,7 @@ impl Storage for InMemStorage {
     async fn insert_shared_keys(
         &self,
         _client_address: DestinationAddressBytes,
+        _device: DeviceId,
         _shared_keys: SharedKeys,
     ) -> Result<(), StorageError> {
         todo!()
@@ -333,6 +614,7 @@ impl Storage for InMemStorage {
     async fn get_shared_keys(
         &self,
         _client_address: DestinationAddressBytes,
+        _device: DeviceId,
     ) -> Result<Option<PersistedSharedKeys>, StorageError> {
         todo!()
     }
@@ -340,15 +622,23 @@ impl Storage for InMemStorage {
     async fn remove_shared_keys(
         &self,
         _client_address: DestinationAddressBytes,
+        _device: DeviceId,
     ) -> Result<(), StorageError> {
         todo!()
     }
 
+    async fn count_registered_devices(
+        &self,
+        _client_address: DestinationAddressBytes,
+    ) -> Result<usize, StorageError> {
+        todo!()
+    }
+
     async fn store_message(
         &self,
         _client_address: DestinationAddressBytes,
         _message: Vec<u8>,
-    ) -> Result<(), StorageError> {
+    ) -> Result<i64, StorageError> {
         todo!()
     }
 
@@ -360,7 +650,59 @@ impl Storage for InMemStorage {
         todo!()
     }
 
-    async fn remove_messages(&self, _ids: Vec<i64>) -> Result<(), StorageError> {
+    async fn get_inbox_cursor(
+        &self,
+        _client_address: DestinationAddressBytes,
+        _device: DeviceId,
+    ) -> Result<Option<i64>, StorageError> {
+        todo!()
+    }
+
+    async fn set_inbox_cursor(
+        &self,
+        _client_address: DestinationAddressBytes,
+        _device: DeviceId,
+        _last_delivered_id: i64,
+    ) -> Result<(), StorageError> {
+        todo!()
+    }
+
+    async fn remove_delivered_messages(
+        &self,
+        _client_address: DestinationAddressBytes,
+    ) -> Result<u64, StorageError> {
+        todo!()
+    }
+
+    async fn remove_messages_received_before(
+        &self,
+        _received_before: i64,
+    ) -> Result<u64, StorageError> {
+        todo!()
+    }
+
+    async fn remove_messages_exceeding_quota(
+        &self,
+        _max_client_bytes: i64,
+    ) -> Result<u64, StorageError> {
+        todo!()
+    }
+
+    async fn store_redirect(&self, _redirect: &GatewayRedirect) -> Result<bool, StorageError> {
+        todo!()
+    }
+
+    async fn get_redirect(
+        &self,
+        _client_address: DestinationAddressBytes,
+    ) -> Result<Option<GatewayRedirect>, StorageError> {
+        todo!()
+    }
+
+    async fn remove_redirect(
+        &self,
+        _client_address: DestinationAddressBytes,
+    ) -> Result<(), StorageError> {
         todo!()
     }
 
@@ -394,3 +736,263 @@ impl Storage for InMemStorage {
         todo!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::storage::test_utils::{temporary_storages, TEST_RETRIEVAL_LIMIT};
+    use crypto::asymmetric::{encryption, identity};
+    use std::net::SocketAddr;
+
+    fn shared_keys(byte: u8) -> SharedKeys {
+        SharedKeys::try_from_bytes(&[byte; 32]).unwrap()
+    }
+
+    fn device(id: u8) -> DeviceId {
+        DeviceId::from_bytes([id; 16])
+    }
+
+    #[tokio::test]
+    async fn shared_keys_are_stored_per_device() {
+        for storage in temporary_storages().await {
+            shared_keys_are_stored_per_device_with(&storage).await
+        }
+    }
+
+    async fn shared_keys_are_stored_per_device_with(storage: &PersistentStorage) {
+        let client = DestinationAddressBytes::from_bytes([1; 32]);
+        storage
+            .insert_shared_keys(client, device(1), shared_keys(1))
+            .await
+            .unwrap();
+        storage
+            .insert_shared_keys(client, device(2), shared_keys(2))
+            .await
+            .unwrap();
+
+        let keys = storage
+            .get_shared_keys(client, device(2))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            keys.derived_aes128_ctr_blake3_hmac_keys_bs58,
+            shared_keys(2).to_base58_string()
+        );
+        assert!(storage
+            .get_shared_keys(client, device(3))
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(storage.count_registered_devices(client).await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn shared_keys_can_be_overwritten_and_removed() {
+        for storage in temporary_storages().await {
+            shared_keys_can_be_overwritten_and_removed_with(&storage).await
+        }
+    }
+
+    async fn shared_keys_can_be_overwritten_and_removed_with(storage: &PersistentStorage) {
+        let client = DestinationAddressBytes::from_bytes([1; 32]);
+        storage
+            .insert_shared_keys(client, device(1), shared_keys(1))
+            .await
+            .unwrap();
+        storage
+            .insert_shared_keys(client, device(1), shared_keys(2))
+            .await
+            .unwrap();
+
+        let keys = storage
+            .get_shared_keys(client, device(1))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(keys.client_address_bs58, client.as_base58_string());
+        assert_eq!(keys.device_id_bs58, device(1).to_base58_string());
+        assert_eq!(
+            keys.derived_aes128_ctr_blake3_hmac_keys_bs58,
+            shared_keys(2).to_base58_string()
+        );
+        assert_eq!(storage.count_registered_devices(client).await.unwrap(), 1);
+
+        storage.remove_shared_keys(client, device(1)).await.unwrap();
+        assert!(storage
+            .get_shared_keys(client, device(1))
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(storage.count_registered_devices(client).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn messages_are_retrieved_in_batches() {
+        for storage in temporary_storages().await {
+            messages_are_retrieved_in_batches_with(&storage).await
+        }
+    }
+
+    async fn messages_are_retrieved_in_batches_with(storage: &PersistentStorage) {
+        let client = DestinationAddressBytes::from_bytes([1; 32]);
+        let other_client = DestinationAddressBytes::from_bytes([2; 32]);
+
+        let total = TEST_RETRIEVAL_LIMIT as usize + 10;
+        for i in 0..total {
+            storage
+                .store_message(client, (i as u32).to_be_bytes().to_vec())
+                .await
+                .unwrap();
+        }
+        storage
+            .store_message(other_client, vec![42; 10])
+            .await
+            .unwrap();
+
+        let (first_batch, start_after) = storage.retrieve_messages(client, None).await.unwrap();
+        assert_eq!(first_batch.len(), TEST_RETRIEVAL_LIMIT as usize);
+        assert_eq!(start_after, Some(first_batch.last().unwrap().id));
+
+        let (second_batch, start_after) = storage
+            .retrieve_messages(client, start_after)
+            .await
+            .unwrap();
+        assert_eq!(second_batch.len(), 10);
+        assert!(start_after.is_none());
+
+        let contents: Vec<_> = first_batch
+            .into_iter()
+            .chain(second_batch)
+            .map(|message| message.content)
+            .collect();
+        let expected: Vec<_> = (0..total)
+            .map(|i| (i as u32).to_be_bytes().to_vec())
+            .collect();
+        assert_eq!(contents, expected);
+
+        let (other_messages, _) = storage.retrieve_messages(other_client, None).await.unwrap();
+        assert_eq!(other_messages.len(), 1);
+        assert_eq!(other_messages[0].content, vec![42; 10]);
+    }
+
+    #[tokio::test]
+    async fn bandwidth_can_be_increased_and_consumed() {
+        for storage in temporary_storages().await {
+            bandwidth_can_be_increased_and_consumed_with(&storage).await
+        }
+    }
+
+    async fn bandwidth_can_be_increased_and_consumed_with(storage: &PersistentStorage) {
+        let client = DestinationAddressBytes::from_bytes([1; 32]);
+        assert!(storage
+            .get_available_bandwidth(client)
+            .await
+            .unwrap()
+            .is_none());
+
+        storage.create_bandwidth_entry(client).await.unwrap();
+        assert_eq!(
+            storage.get_available_bandwidth(client).await.unwrap(),
+            Some(0)
+        );
+
+        storage.increase_bandwidth(client, 100).await.unwrap();
+        storage.consume_bandwidth(client, 30).await.unwrap();
+        assert_eq!(
+            storage.get_available_bandwidth(client).await.unwrap(),
+            Some(70)
+        );
+    }
+
+    #[tokio::test]
+    async fn messages_are_kept_until_delivered_to_all_devices() {
+        for storage in temporary_storages().await {
+            messages_are_kept_until_delivered_to_all_devices_with(&storage).await
+        }
+    }
+
+    async fn messages_are_kept_until_delivered_to_all_devices_with(storage: &PersistentStorage) {
+        let client = DestinationAddressBytes::from_bytes([1; 32]);
+        for id in 1..=2 {
+            storage
+                .insert_shared_keys(client, device(id), shared_keys(id))
+                .await
+                .unwrap();
+        }
+
+        let mut last_id = 0;
+        for i in 0..3u8 {
+            last_id = storage.store_message(client, vec![i; 10]).await.unwrap();
+        }
+
+        // the first device goes through its inbox
+        assert!(storage
+            .get_inbox_cursor(client, device(1))
+            .await
+            .unwrap()
+            .is_none());
+        storage
+            .set_inbox_cursor(client, device(1), last_id)
+            .await
+            .unwrap();
+        assert_eq!(storage.remove_delivered_messages(client).await.unwrap(), 0);
+
+        // the messages are no longer returned for the first device, but still are for the second
+        let cursor = storage.get_inbox_cursor(client, device(1)).await.unwrap();
+        let (pending, _) = storage.retrieve_messages(client, cursor).await.unwrap();
+        assert!(pending.is_empty());
+        let cursor = storage.get_inbox_cursor(client, device(2)).await.unwrap();
+        let (pending, _) = storage.retrieve_messages(client, cursor).await.unwrap();
+        assert_eq!(pending.len(), 3);
+
+        storage
+            .set_inbox_cursor(client, device(2), last_id)
+            .await
+            .unwrap();
+        assert_eq!(storage.remove_delivered_messages(client).await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn only_newer_redirects_replace_stored_ones() {
+        for storage in temporary_storages().await {
+            only_newer_redirects_replace_stored_ones_with(&storage).await
+        }
+    }
+
+    async fn only_newer_redirects_replace_stored_ones_with(storage: &PersistentStorage) {
+        let mut rng = rand::rngs::OsRng;
+        let client_keys = identity::KeyPair::new(&mut rng);
+        let old_gateway = *identity::KeyPair::new(&mut rng).public_key();
+        let redirect = |issued_at| {
+            GatewayRedirect::new(
+                &client_keys,
+                old_gateway,
+                *identity::KeyPair::new(&mut rand::rngs::OsRng).public_key(),
+                *encryption::KeyPair::new(&mut rand::rngs::OsRng).public_key(),
+                "127.0.0.1:1789".parse::<SocketAddr>().unwrap().into(),
+                issued_at,
+            )
+        };
+        let client = client_keys.public_key().derive_destination_address();
+
+        let current = redirect(100);
+        assert!(storage.store_redirect(&current).await.unwrap());
+        assert!(!storage.store_redirect(&redirect(50)).await.unwrap());
+        let stored = storage.get_redirect(client).await.unwrap().unwrap();
+        assert_eq!(stored.new_gateway(), current.new_gateway());
+
+        let newer = redirect(150);
+        assert!(storage.store_redirect(&newer).await.unwrap());
+        let stored = storage.get_redirect(client).await.unwrap().unwrap();
+        assert_eq!(stored.new_gateway(), newer.new_gateway());
+
+        // the records are loaded from the database into a fresh cache
+        let reloaded = PersistentStorage::new(Arc::clone(&storage.backend));
+        let stored = reloaded.get_redirect(client).await.unwrap().unwrap();
+        assert_eq!(stored.new_gateway(), newer.new_gateway());
+
+        storage.remove_redirect(client).await.unwrap();
+        assert!(storage.get_redirect(client).await.unwrap().is_none());
+    }
+}
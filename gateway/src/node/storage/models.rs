@@ -1,20 +1,33 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+#[derive(sqlx::FromRow)]
 pub(crate) struct PersistedSharedKeys {
     pub(crate) client_address_bs58: String,
     pub(crate) derived_aes128_ctr_blake3_hmac_keys_bs58: String,
+    pub(crate) device_id_bs58: String,
 }
 
+#[derive(sqlx::FromRow)]
 pub(crate) struct StoredMessage {
     pub(crate) id: i64,
     #[allow(dead_code)]
     pub(crate) client_address_bs58: String,
     pub(crate) content: Vec<u8>,
+    #[allow(dead_code)]
+    pub(crate) received_at: i64,
 }
 
+#[derive(sqlx::FromRow)]
 pub(crate) struct PersistedBandwidth {
     #[allow(dead_code)]
     pub(crate) client_address_bs58: String,
     pub(crate) available: i64,
 }
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct PersistedRedirect {
+    pub(crate) client_address_bs58: String,
+    pub(crate) record_bs58: String,
+    pub(crate) issued_at: i64,
+}
@@ -0,0 +1,74 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::node::storage::models::PersistedRedirect;
+
+#[derive(Clone)]
+pub(crate) struct RedirectsManager {
+    connection_pool: sqlx::SqlitePool,
+}
+
+impl RedirectsManager {
+    /// Creates new instance of the `RedirectsManager` with the provided sqlite connection pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `connection_pool`: database connection pool to use.
+    pub(crate) fn new(connection_pool: sqlx::SqlitePool) -> Self {
+        RedirectsManager { connection_pool }
+    }
+
+    /// Inserts the redirect record of the client, unless a record issued at the same time or
+    /// later is already stored. Returns whether the record got inserted.
+    ///
+    /// # Arguments
+    ///
+    /// * `redirect`: the redirect record to store.
+    pub(crate) async fn insert_redirect(
+        &self,
+        redirect: PersistedRedirect,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+                INSERT INTO gateway_redirects(client_address_bs58, record_bs58, issued_at) VALUES (?, ?, ?)
+                ON CONFLICT(client_address_bs58)
+                DO UPDATE SET record_bs58 = excluded.record_bs58, issued_at = excluded.issued_at
+                WHERE excluded.issued_at > gateway_redirects.issued_at
+            "#,
+            redirect.client_address_bs58,
+            redirect.record_bs58,
+            redirect.issued_at,
+        )
+        .execute(&self.connection_pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Retrieves all stored redirect records.
+    pub(crate) async fn get_all_redirects(&self) -> Result<Vec<PersistedRedirect>, sqlx::Error> {
+        sqlx::query_as!(
+            PersistedRedirect,
+            "SELECT client_address_bs58, record_bs58, issued_at FROM gateway_redirects"
+        )
+        .fetch_all(&self.connection_pool)
+        .await
+    }
+
+    /// Removes the redirect record of the particular client.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address_bs58`: base58-encoded address of the client
+    pub(crate) async fn remove_redirect(
+        &self,
+        client_address_bs58: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "DELETE FROM gateway_redirects WHERE client_address_bs58 = ?",
+            client_address_bs58
+        )
+        .execute(&self.connection_pool)
+        .await?;
+        Ok(())
+    }
+}
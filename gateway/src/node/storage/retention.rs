@@ -0,0 +1,246 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::node::storage::Storage;
+use log::*;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use task::ShutdownListener;
+use tokio::time::sleep;
+
+/// Number of messages removed from the storage because of the retention policy.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct EvictionMetrics {
+    /// Messages removed because they were stored for longer than the maximum age.
+    pub(crate) expired: u64,
+
+    /// Messages removed because their client exceeded its storage quota.
+    pub(crate) over_quota: u64,
+}
+
+impl EvictionMetrics {
+    fn total(&self) -> u64 {
+        self.expired + self.over_quota
+    }
+
+    fn add(&mut self, other: EvictionMetrics) {
+        self.expired += other.expired;
+        self.over_quota += other.over_quota;
+    }
+}
+
+/// Messages evicted since they were last reported by the statistics collector.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct SharedEvictionMetrics {
+    inner: Arc<Mutex<EvictionMetrics>>,
+}
+
+impl SharedEvictionMetrics {
+    pub(crate) fn new() -> Self {
+        Default::default()
+    }
+
+    fn record(&self, evicted: EvictionMetrics) {
+        self.inner
+            .lock()
+            .expect("eviction metrics lock got poisoned")
+            .add(evicted)
+    }
+
+    /// Returns the messages evicted since the previous call.
+    pub(crate) fn take(&self) -> EvictionMetrics {
+        std::mem::take(
+            &mut *self
+                .inner
+                .lock()
+                .expect("eviction metrics lock got poisoned"),
+        )
+    }
+}
+
+/// Periodically removes messages stored for offline clients that are either too old or that
+/// exceed the storage quota of their client, so that clients that never come back
+/// could not fill up the disk of the gateway.
+pub(crate) struct InboxPruner<St: Storage> {
+    storage: St,
+    /// Messages older than this are removed. Zero disables the check.
+    max_age: Duration,
+    /// Maximum total size of messages stored for a single client. Zero disables the check.
+    max_client_bytes: i64,
+    pruning_interval: Duration,
+    /// Messages evicted since the gateway has started.
+    total_evicted: EvictionMetrics,
+    /// Messages evicted since they were last reported in the gateway statistics.
+    evicted: SharedEvictionMetrics,
+    shutdown: ShutdownListener,
+}
+
+impl<St> InboxPruner<St>
+where
+    St: Storage + 'static,
+{
+    pub(crate) fn new(
+        storage: St,
+        max_age: Duration,
+        max_client_bytes: i64,
+        pruning_interval: Duration,
+        evicted: SharedEvictionMetrics,
+        shutdown: ShutdownListener,
+    ) -> Self {
+        InboxPruner {
+            storage,
+            max_age,
+            max_client_bytes,
+            pruning_interval,
+            total_evicted: EvictionMetrics::default(),
+            evicted,
+            shutdown,
+        }
+    }
+
+    /// Removes all messages violating the retention policy, returning how many of them
+    /// were removed in this round.
+    async fn prune(&mut self) -> EvictionMetrics {
+        let mut evicted = EvictionMetrics::default();
+
+        if !self.max_age.is_zero() {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("the system clock is set before the unix epoch");
+            let received_before = now.saturating_sub(self.max_age).as_secs() as i64;
+            match self
+                .storage
+                .remove_messages_received_before(received_before)
+                .await
+            {
+                Ok(removed) => evicted.expired = removed,
+                Err(err) => error!("Failed to remove expired messages - {}", err),
+            }
+        }
+
+        if self.max_client_bytes > 0 {
+            match self
+                .storage
+                .remove_messages_exceeding_quota(self.max_client_bytes)
+                .await
+            {
+                Ok(removed) => evicted.over_quota = removed,
+                Err(err) => error!("Failed to remove messages exceeding the quota - {}", err),
+            }
+        }
+
+        self.total_evicted.add(evicted);
+        self.evicted.record(evicted);
+        evicted
+    }
+
+    pub(crate) async fn run(&mut self) {
+        while !self.shutdown.is_shutdown() {
+            let evicted = self.prune().await;
+            if evicted.total() > 0 {
+                info!(
+                    "Removed {} expired and {} over quota stored messages ({} expired and {} over quota in total)",
+                    evicted.expired,
+                    evicted.over_quota,
+                    self.total_evicted.expired,
+                    self.total_evicted.over_quota
+                );
+            } else {
+                debug!("No stored messages had to be removed");
+            }
+
+            tokio::select! {
+                _ = sleep(self.pruning_interval) => {},
+                _ = self.shutdown.recv() => {
+                    trace!("InboxPruner: Received shutdown");
+                }
+            }
+        }
+
+        trace!("InboxPruner: Exiting");
+    }
+
+    pub(crate) fn start(mut self) {
+        tokio::spawn(async move { self.run().await });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::storage::test_utils::temporary_storages;
+    use crate::node::storage::PersistentStorage;
+    use nymsphinx::DestinationAddressBytes;
+    use task::ShutdownNotifier;
+
+    #[tokio::test]
+    async fn oldest_messages_are_removed_when_quota_is_exceeded() {
+        for storage in temporary_storages().await {
+            oldest_messages_are_removed_when_quota_is_exceeded_with(&storage).await
+        }
+    }
+
+    async fn oldest_messages_are_removed_when_quota_is_exceeded_with(storage: &PersistentStorage) {
+        let client = DestinationAddressBytes::from_bytes([1; 32]);
+        let other_client = DestinationAddressBytes::from_bytes([2; 32]);
+
+        for i in 0..5u8 {
+            storage.store_message(client, vec![i; 100]).await.unwrap();
+        }
+        storage
+            .store_message(other_client, vec![42; 100])
+            .await
+            .unwrap();
+
+        let shutdown = ShutdownNotifier::default();
+        let reported = SharedEvictionMetrics::new();
+        let mut pruner = InboxPruner::new(
+            storage.clone(),
+            Duration::ZERO,
+            250,
+            Duration::from_secs(60),
+            reported.clone(),
+            shutdown.subscribe(),
+        );
+        let evicted = pruner.prune().await;
+        assert_eq!(
+            evicted,
+            EvictionMetrics {
+                expired: 0,
+                over_quota: 3
+            }
+        );
+        assert_eq!(reported.take(), evicted);
+        assert_eq!(reported.take(), EvictionMetrics::default());
+
+        let (kept, _) = storage.retrieve_messages(client, None).await.unwrap();
+        let kept: Vec<_> = kept.into_iter().map(|message| message.content).collect();
+        assert_eq!(kept, vec![vec![3; 100], vec![4; 100]]);
+
+        let (kept, _) = storage.retrieve_messages(other_client, None).await.unwrap();
+        assert_eq!(kept.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn messages_received_before_the_cutoff_are_removed() {
+        for storage in temporary_storages().await {
+            messages_received_before_the_cutoff_are_removed_with(&storage).await
+        }
+    }
+
+    async fn messages_received_before_the_cutoff_are_removed_with(storage: &PersistentStorage) {
+        let client = DestinationAddressBytes::from_bytes([1; 32]);
+        for i in 0..3u8 {
+            storage.store_message(client, vec![i; 10]).await.unwrap();
+        }
+
+        assert_eq!(storage.remove_messages_received_before(0).await.unwrap(), 0);
+        assert_eq!(
+            storage
+                .remove_messages_received_before(i64::MAX)
+                .await
+                .unwrap(),
+            3
+        );
+    }
+}
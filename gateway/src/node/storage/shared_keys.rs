@@ -19,7 +19,7 @@ impl SharedKeysManager {
     }
 
     /// Inserts provided derived shared keys into the database.
-    /// If keys previously existed for the provided client, they are overwritten with the new data.
+    /// If keys previously existed for the provided device of the client, they are overwritten with the new data.
     ///
     /// # Arguments
     ///
@@ -28,46 +28,71 @@ impl SharedKeysManager {
         &self,
         shared_keys: PersistedSharedKeys,
     ) -> Result<(), sqlx::Error> {
-        sqlx::query!("INSERT OR REPLACE INTO shared_keys(client_address_bs58, derived_aes128_ctr_blake3_hmac_keys_bs58) VALUES (?, ?)",
+        sqlx::query!("INSERT OR REPLACE INTO shared_keys(client_address_bs58, device_id_bs58, derived_aes128_ctr_blake3_hmac_keys_bs58) VALUES (?, ?, ?)",
             shared_keys.client_address_bs58,
+            shared_keys.device_id_bs58,
             shared_keys.derived_aes128_ctr_blake3_hmac_keys_bs58,
         ).execute(&self.connection_pool).await?;
         Ok(())
     }
 
-    /// Tries to retrieve shared keys stored for the particular client.
+    /// Tries to retrieve shared keys stored for the particular device of the client.
     ///
     /// # Arguments
     ///
     /// * `client_address_bs58`: base58-encoded address of the client
+    /// * `device_id_bs58`: base58-encoded id of the device of the client
     pub(crate) async fn get_shared_keys(
         &self,
         client_address_bs58: &str,
+        device_id_bs58: &str,
     ) -> Result<Option<PersistedSharedKeys>, sqlx::Error> {
         sqlx::query_as!(
             PersistedSharedKeys,
-            "SELECT * FROM shared_keys WHERE client_address_bs58 = ?",
-            client_address_bs58
+            "SELECT * FROM shared_keys WHERE client_address_bs58 = ? AND device_id_bs58 = ?",
+            client_address_bs58,
+            device_id_bs58
         )
         .fetch_optional(&self.connection_pool)
         .await
     }
 
-    /// Removes from the database shared keys derived with the particular client.
+    /// Counts the devices of the client for which shared keys are stored.
     ///
     /// # Arguments
     ///
     /// * `client_address_bs58`: base58-encoded address of the client
+    pub(crate) async fn count_devices(
+        &self,
+        client_address_bs58: &str,
+    ) -> Result<i64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"SELECT COUNT(*) AS "devices!: i64" FROM shared_keys WHERE client_address_bs58 = ?"#,
+            client_address_bs58
+        )
+        .fetch_one(&self.connection_pool)
+        .await?;
+        Ok(result.devices)
+    }
+
+    /// Removes from the database shared keys derived with the particular device of the client.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address_bs58`: base58-encoded address of the client
+    /// * `device_id_bs58`: base58-encoded id of the device of the client
     // currently there is no code flow that causes removal (not overwriting)
     // of the stored keys. However, retain the function for consistency and completion sake
     #[allow(dead_code)]
     pub(crate) async fn remove_shared_keys(
         &self,
         client_address_bs58: &str,
+        device_id_bs58: &str,
     ) -> Result<(), sqlx::Error> {
         sqlx::query!(
-            "DELETE FROM shared_keys WHERE client_address_bs58 = ?",
-            client_address_bs58
+            "DELETE FROM shared_keys WHERE client_address_bs58 = ? AND device_id_bs58 = ?",
+            client_address_bs58,
+            device_id_bs58
         )
         .execute(&self.connection_pool)
         .await?;
@@ -0,0 +1,228 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::node::storage::backend::StorageBackend;
+use crate::node::storage::bandwidth::BandwidthManager;
+use crate::node::storage::error::StorageError;
+use crate::node::storage::inboxes::InboxManager;
+use crate::node::storage::models::{
+    PersistedBandwidth, PersistedRedirect, PersistedSharedKeys, StoredMessage,
+};
+use crate::node::storage::redirects::RedirectsManager;
+use crate::node::storage::shared_keys::SharedKeysManager;
+use async_trait::async_trait;
+use log::{debug, error};
+use sqlx::ConnectOptions;
+use std::path::Path;
+
+/// `StorageBackend` using a local SQLite database.
+pub(crate) struct SqliteBackend {
+    shared_key_manager: SharedKeysManager,
+    inbox_manager: InboxManager,
+    bandwidth_manager: BandwidthManager,
+    redirects_manager: RedirectsManager,
+}
+
+impl SqliteBackend {
+    /// Initialises `SqliteBackend` using the provided path.
+    ///
+    /// # Arguments
+    ///
+    /// * `database_path`: path to the database.
+    /// * `message_retrieval_limit`: maximum number of stored client messages that can be retrieved at once.
+    pub(crate) async fn init<P: AsRef<Path> + Send>(
+        database_path: P,
+        message_retrieval_limit: i64,
+    ) -> Result<Self, StorageError> {
+        debug!(
+            "Attempting to connect to database {:?}",
+            database_path.as_ref().as_os_str()
+        );
+
+        // TODO: we can inject here more stuff based on our gateway global config
+        // struct. Maybe different pool size or timeout intervals?
+        let mut opts = sqlx::sqlite::SqliteConnectOptions::new()
+            .filename(database_path)
+            .create_if_missing(true);
+
+        // TODO: do we want auto_vacuum ?
+
+        opts.disable_statement_logging();
+
+        let connection_pool = match sqlx::SqlitePool::connect_with(opts).await {
+            Ok(db) => db,
+            Err(err) => {
+                error!("Failed to connect to SQLx database: {}", err);
+                return Err(err.into());
+            }
+        };
+
+        if let Err(err) = sqlx::migrate!("./migrations").run(&connection_pool).await {
+            error!("Failed to perform migration on the SQLx database: {}", err);
+            return Err(err.into());
+        }
+
+        // the cloning here are cheap as connection pool is stored behind an Arc
+        Ok(SqliteBackend {
+            shared_key_manager: SharedKeysManager::new(connection_pool.clone()),
+            inbox_manager: InboxManager::new(connection_pool.clone(), message_retrieval_limit),
+            bandwidth_manager: BandwidthManager::new(connection_pool.clone()),
+            redirects_manager: RedirectsManager::new(connection_pool),
+        })
+    }
+}
+
+#[async_trait]
+impl StorageBackend for SqliteBackend {
+    async fn insert_shared_keys(
+        &self,
+        shared_keys: PersistedSharedKeys,
+    ) -> Result<(), sqlx::Error> {
+        self.shared_key_manager
+            .insert_shared_keys(shared_keys)
+            .await
+    }
+
+    async fn get_shared_keys(
+        &self,
+        client_address_bs58: &str,
+        device_id_bs58: &str,
+    ) -> Result<Option<PersistedSharedKeys>, sqlx::Error> {
+        self.shared_key_manager
+            .get_shared_keys(client_address_bs58, device_id_bs58)
+            .await
+    }
+
+    async fn remove_shared_keys(
+        &self,
+        client_address_bs58: &str,
+        device_id_bs58: &str,
+    ) -> Result<(), sqlx::Error> {
+        self.shared_key_manager
+            .remove_shared_keys(client_address_bs58, device_id_bs58)
+            .await
+    }
+
+    async fn count_devices(&self, client_address_bs58: &str) -> Result<i64, sqlx::Error> {
+        self.shared_key_manager
+            .count_devices(client_address_bs58)
+            .await
+    }
+
+    async fn insert_message(
+        &self,
+        client_address_bs58: &str,
+        content: Vec<u8>,
+    ) -> Result<i64, sqlx::Error> {
+        self.inbox_manager
+            .insert_message(client_address_bs58, content)
+            .await
+    }
+
+    async fn get_messages(
+        &self,
+        client_address_bs58: &str,
+        start_after: Option<i64>,
+    ) -> Result<(Vec<StoredMessage>, Option<i64>), sqlx::Error> {
+        self.inbox_manager
+            .get_messages(client_address_bs58, start_after)
+            .await
+    }
+
+    async fn get_inbox_cursor(
+        &self,
+        client_address_bs58: &str,
+        device_id_bs58: &str,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        self.inbox_manager
+            .get_inbox_cursor(client_address_bs58, device_id_bs58)
+            .await
+    }
+
+    async fn set_inbox_cursor(
+        &self,
+        client_address_bs58: &str,
+        device_id_bs58: &str,
+        last_delivered_id: i64,
+    ) -> Result<(), sqlx::Error> {
+        self.inbox_manager
+            .set_inbox_cursor(client_address_bs58, device_id_bs58, last_delivered_id)
+            .await
+    }
+
+    async fn remove_delivered_messages(
+        &self,
+        client_address_bs58: &str,
+    ) -> Result<u64, sqlx::Error> {
+        self.inbox_manager
+            .remove_delivered_messages(client_address_bs58)
+            .await
+    }
+
+    async fn remove_messages_received_before(
+        &self,
+        received_before: i64,
+    ) -> Result<u64, sqlx::Error> {
+        self.inbox_manager
+            .remove_messages_received_before(received_before)
+            .await
+    }
+
+    async fn remove_messages_exceeding_quota(
+        &self,
+        max_client_bytes: i64,
+    ) -> Result<u64, sqlx::Error> {
+        self.inbox_manager
+            .remove_messages_exceeding_quota(max_client_bytes)
+            .await
+    }
+
+    async fn insert_redirect(&self, redirect: PersistedRedirect) -> Result<bool, sqlx::Error> {
+        self.redirects_manager.insert_redirect(redirect).await
+    }
+
+    async fn get_all_redirects(&self) -> Result<Vec<PersistedRedirect>, sqlx::Error> {
+        self.redirects_manager.get_all_redirects().await
+    }
+
+    async fn remove_redirect(&self, client_address_bs58: &str) -> Result<(), sqlx::Error> {
+        self.redirects_manager
+            .remove_redirect(client_address_bs58)
+            .await
+    }
+
+    async fn insert_new_client(&self, client_address_bs58: &str) -> Result<(), sqlx::Error> {
+        self.bandwidth_manager
+            .insert_new_client(client_address_bs58)
+            .await
+    }
+
+    async fn get_available_bandwidth(
+        &self,
+        client_address_bs58: &str,
+    ) -> Result<Option<PersistedBandwidth>, sqlx::Error> {
+        self.bandwidth_manager
+            .get_available_bandwidth(client_address_bs58)
+            .await
+    }
+
+    async fn increase_available_bandwidth(
+        &self,
+        client_address_bs58: &str,
+        amount: i64,
+    ) -> Result<(), sqlx::Error> {
+        self.bandwidth_manager
+            .increase_available_bandwidth(client_address_bs58, amount)
+            .await
+    }
+
+    async fn decrease_available_bandwidth(
+        &self,
+        client_address_bs58: &str,
+        amount: i64,
+    ) -> Result<(), sqlx::Error> {
+        self.bandwidth_manager
+            .decrease_available_bandwidth(client_address_bs58, amount)
+            .await
+    }
+}
@@ -18,6 +18,7 @@ use crypto::bech32_address_validation;
 mod describe;
 mod init;
 mod node_details;
+mod rotate_sphinx_key;
 mod run;
 mod sign;
 mod upgrade;
@@ -42,6 +43,10 @@ pub(crate) enum Commands {
     /// Show details of this mixnode
     NodeDetails(node_details::NodeDetails),
 
+    /// Generate the next sphinx key of this mixnode that is going to be used once announced.
+    /// The sphinx keys are never rotated automatically
+    RotateSphinxKey(rotate_sphinx_key::RotateSphinxKey),
+
     /// Generate shell completions
     Completions(ArgShell),
 
@@ -71,6 +76,7 @@ pub(crate) async fn execute(args: Cli) {
         Commands::Sign(m) => sign::execute(m),
         Commands::Upgrade(m) => upgrade::execute(m),
         Commands::NodeDetails(m) => node_details::execute(m),
+        Commands::RotateSphinxKey(m) => rotate_sphinx_key::execute(m),
         Commands::Completions(s) => s.generate(&mut crate::Cli::into_app(), bin_name),
         Commands::GenerateFigSpec => fig_generate(&mut crate::Cli::into_app(), bin_name),
     }
@@ -0,0 +1,52 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::config::{persistence::pathfinder::MixNodePathfinder, Config};
+use clap::Args;
+use config::NymConfig;
+use crypto::asymmetric::encryption;
+use log::error;
+
+#[derive(Args)]
+pub(crate) struct RotateSphinxKey {
+    /// The id of the mixnode you want to generate the next sphinx key for
+    #[clap(long)]
+    id: String,
+}
+
+pub(crate) fn execute(args: &RotateSphinxKey) {
+    let config = match Config::load_from_file(Some(&args.id)) {
+        Ok(cfg) => cfg,
+        Err(err) => {
+            error!(
+                "Failed to load config for {}. Are you sure you have run `init` before? (Error was: {})",
+                args.id,
+                err,
+            );
+            return;
+        }
+    };
+
+    let mut rng = rand::rngs::OsRng;
+    let next_sphinx_keys = encryption::KeyPair::new(&mut rng);
+
+    let pathfinder = MixNodePathfinder::new_from_config(&config);
+    if let Err(err) = pathfinder
+        .sphinx_key_files()
+        .store_next_keypair(&next_sphinx_keys)
+    {
+        error!("Failed to save the next sphinx key - {}", err);
+        return;
+    }
+
+    println!(
+        "Next Sphinx Key: {}\n",
+        next_sphinx_keys.public_key().to_base58_string()
+    );
+    println!(
+        "Announce it in the mixnet contract (for example with `nym-cli mixnet operators mixnode keys announce-sphinx-key`). \
+        Your running node is going to accept packets for it as soon as it notices the announcement, \
+        switch to it at the beginning of the following epoch \
+        and keep accepting packets for the current key for one more epoch afterwards."
+    );
+}
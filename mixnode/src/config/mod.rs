@@ -6,6 +6,7 @@ use config::defaults::{
     DEFAULT_HTTP_API_LISTENING_PORT, DEFAULT_MIX_LISTENING_PORT, DEFAULT_VERLOC_LISTENING_PORT,
 };
 use config::NymConfig;
+use mixnet_client::LinkEncryption;
 use serde::{Deserialize, Deserializer, Serialize};
 use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
@@ -34,6 +35,8 @@ const DEFAULT_PACKET_FORWARDING_INITIAL_BACKOFF: Duration = Duration::from_milli
 const DEFAULT_PACKET_FORWARDING_MAXIMUM_BACKOFF: Duration = Duration::from_millis(300_000);
 const DEFAULT_INITIAL_CONNECTION_TIMEOUT: Duration = Duration::from_millis(1_500);
 const DEFAULT_MAXIMUM_CONNECTION_BUFFER_SIZE: usize = 128;
+const DEFAULT_SPHINX_KEY_ROTATION_CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const DEFAULT_LINK_PEERS_REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
 
 pub fn missing_string_value<T: From<String>>() -> T {
     MISSING_VALUE.to_string().into()
@@ -244,6 +247,18 @@ impl Config {
         self.debug.node_stats_updating_delay
     }
 
+    pub fn get_sphinx_key_rotation_check_interval(&self) -> Duration {
+        self.debug.sphinx_key_rotation_check_interval
+    }
+
+    pub fn get_link_encryption(&self) -> LinkEncryption {
+        self.mixnode.link_encryption
+    }
+
+    pub fn get_link_peers_refresh_interval(&self) -> Duration {
+        self.debug.link_peers_refresh_interval
+    }
+
     pub fn get_listening_address(&self) -> IpAddr {
         self.mixnode.listening_address
     }
@@ -350,6 +365,11 @@ struct MixNode {
     #[serde(default = "default_http_api_port")]
     http_api_port: u16,
 
+    /// Determines whether the mixnet traffic exchanged with other nodes goes through
+    /// the authenticated and encrypted links. Either `disabled`, `compatible` or `required`.
+    #[serde(default)]
+    link_encryption: LinkEncryption,
+
     /// Path to file containing private identity key.
     #[serde(default = "missing_string_value")]
     private_identity_key_file: PathBuf,
@@ -403,6 +423,7 @@ impl Default for MixNode {
             mix_port: DEFAULT_MIX_LISTENING_PORT,
             verloc_port: DEFAULT_VERLOC_LISTENING_PORT,
             http_api_port: DEFAULT_HTTP_API_LISTENING_PORT,
+            link_encryption: Default::default(),
             private_identity_key_file: Default::default(),
             public_identity_key_file: Default::default(),
             private_sphinx_key_file: Default::default(),
@@ -485,6 +506,15 @@ struct Debug {
 
     /// Maximum number of packets that can be stored waiting to get sent to a particular connection.
     maximum_connection_buffer_size: usize,
+
+    /// Delay between subsequent checks whether the announced next sphinx key should start
+    /// being used or whether the previous key can be removed.
+    #[serde(with = "humantime_serde")]
+    sphinx_key_rotation_check_interval: Duration,
+
+    /// Delay between subsequent refreshes of the link keys of other nodes in the network.
+    #[serde(with = "humantime_serde")]
+    link_peers_refresh_interval: Duration,
 }
 
 impl Default for Debug {
@@ -496,6 +526,8 @@ impl Default for Debug {
             packet_forwarding_maximum_backoff: DEFAULT_PACKET_FORWARDING_MAXIMUM_BACKOFF,
             initial_connection_timeout: DEFAULT_INITIAL_CONNECTION_TIMEOUT,
             maximum_connection_buffer_size: DEFAULT_MAXIMUM_CONNECTION_BUFFER_SIZE,
+            sphinx_key_rotation_check_interval: DEFAULT_SPHINX_KEY_ROTATION_CHECK_INTERVAL,
+            link_peers_refresh_interval: DEFAULT_LINK_PEERS_REFRESH_INTERVAL,
         }
     }
 }
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::config::Config;
+use mixnode_common::sphinx_key_rotation::SphinxKeyFiles;
 use std::path::{Path, PathBuf};
 
 #[derive(Debug)]
@@ -37,4 +38,11 @@ impl MixNodePathfinder {
     pub fn public_encryption_key(&self) -> &Path {
         &self.public_sphinx_key
     }
+
+    pub fn sphinx_key_files(&self) -> SphinxKeyFiles {
+        SphinxKeyFiles::new(
+            self.private_sphinx_key.clone(),
+            self.public_sphinx_key.clone(),
+        )
+    }
 }
@@ -53,6 +53,11 @@ verloc_port = {{ mixnode.verloc_port }}
 # (default: 8000)
 http_api_port = {{ mixnode.http_api_port }}
 
+# Determines whether the mixnet traffic exchanged with other nodes goes through
+# the authenticated and encrypted links. Either 'disabled', 'compatible' or 'required'.
+# (default: 'compatible')
+link_encryption = '{{ mixnode.link_encryption }}'
+
 # Addresses to APIs running on validator from which the node gets the view of the network.
 validator_api_urls = [
     {{#each mixnode.validator_api_urls }}
@@ -8,14 +8,13 @@ use crate::node::packet_delayforwarder::PacketDelayForwardSender;
 use crate::node::ShutdownListener;
 use futures::StreamExt;
 use log::{error, info};
+use mixnet_client::LinkConfig;
 use nymsphinx::forwarding::packet::MixPacket;
-use nymsphinx::framing::codec::SphinxCodec;
 use nymsphinx::framing::packet::FramedSphinxPacket;
 use nymsphinx::Delay as SphinxDelay;
 use std::net::SocketAddr;
 use tokio::net::TcpStream;
 use tokio::time::Instant;
-use tokio_util::codec::Framed;
 
 pub(crate) mod packet_processing;
 
@@ -23,16 +22,19 @@ pub(crate) mod packet_processing;
 pub(crate) struct ConnectionHandler {
     packet_processor: PacketProcessor,
     delay_forwarding_channel: PacketDelayForwardSender,
+    link: LinkConfig,
 }
 
 impl ConnectionHandler {
     pub(crate) fn new(
         packet_processor: PacketProcessor,
         delay_forwarding_channel: PacketDelayForwardSender,
+        link: LinkConfig,
     ) -> Self {
         ConnectionHandler {
             packet_processor,
             delay_forwarding_channel,
+            link,
         }
     }
 
@@ -49,13 +51,7 @@ impl ConnectionHandler {
     }
 
     fn handle_received_packet(&self, framed_sphinx_packet: FramedSphinxPacket) {
-        //
-        // TODO: here be replay attack detection - it will require similar key cache to the one in
-        // packet processor for vpn packets,
-        // question: can it also be per connection vs global?
-        //
-
-        // all processing such, key caching, etc. was done.
+        // all processing such as replay detection, key caching, etc. was done.
         // however, if it was a forward hop, we still need to delay it
         match self.packet_processor.process_received(framed_sphinx_packet) {
             Err(e) => debug!("We failed to process received sphinx packet - {:?}", e),
@@ -77,7 +73,13 @@ impl ConnectionHandler {
         mut shutdown: ShutdownListener,
     ) {
         debug!("Starting connection handler for {:?}", remote);
-        let mut framed_conn = Framed::new(conn, SphinxCodec);
+        let mut framed_conn = match self.link.accept(conn).await {
+            Ok(framed_conn) => framed_conn,
+            Err(err) => {
+                debug!("Failed to establish the link with {:?} - {}", remote, err);
+                return;
+            }
+        };
         while !shutdown.is_shutdown() {
             tokio::select! {
                 Some(framed_sphinx_packet) = framed_conn.next() => {
@@ -95,7 +97,7 @@ impl ConnectionHandler {
                         }
                         Err(err) => {
                             error!(
-                                "The socket connection got corrupted with error: {:?}. Closing the socket",
+                                "The socket connection got corrupted with error: {}. Closing the socket",
                                 err
                             );
                             return;
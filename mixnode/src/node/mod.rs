@@ -20,6 +20,10 @@ use crate::node::packet_delayforwarder::{DelayForwarder, PacketDelayForwardSende
 use ::crypto::asymmetric::{encryption, identity};
 use config::NymConfig;
 use log::{error, info, warn};
+use mixnet_client::{LinkConfig, LinkPeers};
+use mixnode_common::link_peers::LinkPeersRefresher;
+use mixnode_common::packet_processor::processor::SphinxPacketProcessor;
+use mixnode_common::sphinx_key_rotation::{RotatingNode, SphinxKeyRotationController};
 use mixnode_common::verloc::{self, AtomicVerlocResult, VerlocMeasurer};
 use rand::seq::SliceRandom;
 use rand::thread_rng;
@@ -167,18 +171,88 @@ impl MixNode {
         (node_stats_pointer, update_sender)
     }
 
+    /// Creates the sphinx packet processor, which, if the node was restarted in the middle of
+    /// the key rotation, is still going to accept packets created for the previous key.
+    fn create_sphinx_processor(&self) -> SphinxPacketProcessor {
+        let pathfinder = MixNodePathfinder::new_from_config(&self.config);
+        let current_key = self.sphinx_keypair.private_key().into();
+
+        match pathfinder.sphinx_key_files().load_previous_keypair() {
+            Some(Ok(previous)) => {
+                info!("Found the pre-rotation sphinx key - it is going to be accepted for the remainder of the grace period");
+                SphinxPacketProcessor::new_with_previous_key(
+                    current_key,
+                    previous.private_key().into(),
+                )
+            }
+            Some(Err(err)) => {
+                warn!("Failed to load the pre-rotation sphinx key - {}", err);
+                SphinxPacketProcessor::new(current_key)
+            }
+            None => SphinxPacketProcessor::new(current_key),
+        }
+    }
+
+    fn start_sphinx_key_rotation_controller(
+        &self,
+        sphinx_processor: SphinxPacketProcessor,
+        shutdown: ShutdownListener,
+    ) {
+        info!("Starting sphinx key rotation controller...");
+
+        let pathfinder = MixNodePathfinder::new_from_config(&self.config);
+        let validator_api = self
+            .config
+            .get_validator_api_endpoints()
+            .choose(&mut thread_rng())
+            .expect("The list of validator apis is empty")
+            .clone();
+
+        SphinxKeyRotationController::new(
+            RotatingNode::Mixnode {
+                identity: self.identity_keypair.public_key().to_base58_string(),
+            },
+            pathfinder.sphinx_key_files(),
+            sphinx_processor,
+            validator_api,
+            self.config.get_sphinx_key_rotation_check_interval(),
+            shutdown,
+        )
+        .start();
+    }
+
+    /// Obtains the link keys of all nodes in the network and starts keeping them up to date.
+    async fn start_link_peers_refresher(&self, shutdown: ShutdownListener) -> LinkConfig {
+        let link_encryption = self.config.get_link_encryption();
+        info!("Starting link peers refresher (link encryption: {link_encryption:?})...");
+
+        let peers = LinkPeers::default();
+        let refresher = LinkPeersRefresher::new(
+            peers.clone(),
+            self.config.get_validator_api_endpoints(),
+            self.config.get_link_peers_refresh_interval(),
+            shutdown,
+        );
+        refresher.refresh().await;
+        refresher.start();
+
+        LinkConfig::new(link_encryption, &self.identity_keypair, peers)
+    }
+
     fn start_socket_listener(
         &self,
+        sphinx_processor: SphinxPacketProcessor,
         node_stats_update_sender: node_statistics::UpdateSender,
         delay_forwarding_channel: PacketDelayForwardSender,
+        link: LinkConfig,
         shutdown: ShutdownListener,
     ) {
         info!("Starting socket listener...");
 
-        let packet_processor =
-            PacketProcessor::new(self.sphinx_keypair.private_key(), node_stats_update_sender);
+        let packet_processor = PacketProcessor::new(sphinx_processor, node_stats_update_sender);
 
-        let connection_handler = ConnectionHandler::new(packet_processor, delay_forwarding_channel);
+        let connection_handler =
+            ConnectionHandler::new(packet_processor, delay_forwarding_channel, link);
 
         let listening_address = SocketAddr::new(
             self.config.get_listening_address(),
@@ -191,6 +265,7 @@ impl MixNode {
     fn start_packet_delay_forwarder(
         &mut self,
         node_stats_update_sender: node_statistics::UpdateSender,
+        link: LinkConfig,
         shutdown: ShutdownListener,
     ) -> PacketDelayForwardSender {
         info!("Starting packet delay-forwarder...");
@@ -200,7 +275,8 @@ impl MixNode {
             self.config.get_packet_forwarding_maximum_backoff(),
             self.config.get_initial_connection_timeout(),
             self.config.get_maximum_connection_buffer_size(),
-        );
+        )
+        .with_link(link);
 
         let mut packet_forwarder = DelayForwarder::new(
             mixnet_client::Client::new(client_config),
@@ -318,11 +394,19 @@ impl MixNode {
 
         let (node_stats_pointer, node_stats_update_sender) =
             self.start_node_stats_controller(shutdown.subscribe());
-        let delay_forwarding_channel = self
-            .start_packet_delay_forwarder(node_stats_update_sender.clone(), shutdown.subscribe());
+        let link = self.start_link_peers_refresher(shutdown.subscribe()).await;
+        let delay_forwarding_channel = self.start_packet_delay_forwarder(
+            node_stats_update_sender.clone(),
+            link.clone(),
+            shutdown.subscribe(),
+        );
+        let sphinx_processor = self.create_sphinx_processor();
+        self.start_sphinx_key_rotation_controller(sphinx_processor.clone(), shutdown.subscribe());
         self.start_socket_listener(
+            sphinx_processor,
             node_stats_update_sender,
             delay_forwarding_channel,
+            link,
             shutdown.subscribe(),
         );
         let atomic_verloc_results = self.start_verloc_measurements(shutdown.subscribe());
@@ -0,0 +1,28 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use futures::StreamExt;
+use nym_sdk::mixnet;
+
+#[tokio::main]
+async fn main() {
+    let config = mixnet::Config::new("sdk-example-client");
+    let mut client = mixnet::MixnetClient::connect(config)
+        .await
+        .expect("failed to connect to the mixnet");
+
+    let our_address = client.nym_address();
+    println!("Our client nym address is: {}", our_address);
+
+    // send a message through the mixnet to ourselves
+    client
+        .send(our_address, b"hello there".to_vec())
+        .expect("failed to send the message");
+
+    println!("Waiting for message (ctrl-c to exit)");
+    if let Some(received) = client.next().await {
+        println!("Received: {}", String::from_utf8_lossy(&received.message));
+    }
+
+    client.disconnect().await;
+}
@@ -0,0 +1,12 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Rust SDK for the Nym platform.
+//!
+//! The `mixnet` module allows embedding a mixnet client directly in a Rust application,
+//! without having to run a separate `nym-client` and talk to it over the websocket.
+
+pub mod error;
+pub mod mixnet;
+
+pub use error::{Error, Result};
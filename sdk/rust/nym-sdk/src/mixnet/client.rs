@@ -0,0 +1,350 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::error::{Error, Result};
+use crate::mixnet::config::Config;
+use client_core::client::base_client::BaseClientBuilder;
+use client_core::client::events::ClientEventReceiver;
+use client_core::client::inbound_messages::{InputMessage, InputMessageSender};
+use client_core::client::key_manager::KeyManager;
+use client_core::client::real_messages_control::OutQueueLength;
+use client_core::client::received_buffer::{
+    ReceivedBufferMessage, ReceivedBufferRequestSender, ReconstructedMessagesReceiver,
+};
+use client_core::client::self_address::SelfAddress;
+use client_core::client::streams::MixnetStreamWriter;
+use client_core::config::persistence::key_pathfinder::ClientKeyPathfinder;
+use config::NymConfig;
+use futures::channel::mpsc;
+use futures::task::{Context, Poll};
+use futures::{ready, Stream, StreamExt};
+use log::*;
+use nymsphinx::addressing::clients::Recipient;
+use nymsphinx::addressing::nodes::NodeIdentity;
+use nymsphinx::anonymous_replies::ReplySurb;
+use nymsphinx::receiver::ReconstructedMessage;
+use std::collections::VecDeque;
+use std::pin::Pin;
+use task::ShutdownNotifier;
+
+/// Client connected to the mix network.
+///
+/// It's also a `Stream` of all messages received from the mix network.
+/// Note that all of its components are spawned as tokio tasks, so it has to be used
+/// from within a tokio runtime.
+pub struct MixnetClient {
+    /// Address of this client in the mix network, which changes if the client fails over
+    /// to another gateway.
+    nym_address: SelfAddress,
+
+    /// Channel used for transforming 'raw' messages into sphinx packets and sending them
+    /// through the mix network.
+    input_sender: InputMessageSender,
+
+    /// Channel used for obtaining reconstructed messages received from the mix network.
+    reconstructed_receiver: ReconstructedMessagesReceiver,
+
+    /// Channel used for controlling the received messages buffer. Note that the buffer controller
+    /// stops once this channel gets closed.
+    received_buffer_request_sender: ReceivedBufferRequestSender,
+
+    /// Messages that were already received, but not yet returned from the stream.
+    received: VecDeque<ReconstructedMessage>,
+
+    /// Number of real packets waiting to be sent out, used for applying backpressure on streams.
+    out_queue_length: OutQueueLength,
+
+    /// Channel used for notifying about changes in the client state, such as its address.
+    /// It is only available if the gateway failover is enabled.
+    client_events: Option<ClientEventReceiver>,
+
+    /// Notifier used for gracefully stopping all the tasks of the client.
+    shutdown: ShutdownNotifier,
+}
+
+impl MixnetClient {
+    /// Connects to the mix network.
+    ///
+    /// If the client with this id has not been registered before, new keys are generated
+    /// and registered with either the configured or a random gateway. The configuration is then
+    /// saved so that subsequent connections reuse the same keys and gateway.
+    pub async fn connect(mut config: Config) -> Result<Self> {
+        setup_gateway(&mut config).await?;
+
+        let pathfinder = ClientKeyPathfinder::new_from_config(config.get_base());
+        let key_manager = KeyManager::load_keys(&pathfinder)?;
+        let nym_address = SelfAddress::new(Recipient::new(
+            *key_manager.identity_keypair().public_key(),
+            *key_manager.encryption_keypair().public_key(),
+            // TODO: below only works under assumption that gateway address == gateway id
+            // (which currently is true)
+            NodeIdentity::from_base58_string(config.get_base().get_gateway_id())?,
+        ));
+
+        info!("Connecting to the mixnet");
+        let base_client = BaseClientBuilder::new(&config, key_manager, nym_address)
+            .start_base()
+            .await?;
+
+        // announce ourselves to the buffer so that it would start sending us received messages
+        let (reconstructed_sender, reconstructed_receiver) = mpsc::unbounded();
+        base_client
+            .received_buffer_request_sender
+            .unbounded_send(ReceivedBufferMessage::ReceiverAnnounce(
+                reconstructed_sender,
+            ))
+            .map_err(|_| Error::Disconnected)?;
+
+        info!(
+            "The address of this client is: {}",
+            base_client.self_address.get()
+        );
+
+        Ok(MixnetClient {
+            nym_address: base_client.self_address,
+            input_sender: base_client.input_sender,
+            reconstructed_receiver,
+            received_buffer_request_sender: base_client.received_buffer_request_sender,
+            received: VecDeque::new(),
+            out_queue_length: base_client.out_queue_length,
+            client_events: base_client.client_events,
+            shutdown: base_client.shutdown,
+        })
+    }
+
+    /// Address of this client in the mix network.
+    pub fn nym_address(&self) -> Recipient {
+        self.nym_address.get()
+    }
+
+    /// Returns the channel notifying about changes in the client state, such as its address
+    /// changing after failing over to another gateway. It is only available once, and only
+    /// if the gateway failover is enabled.
+    pub fn client_events(&mut self) -> Option<ClientEventReceiver> {
+        self.client_events.take()
+    }
+
+    fn send_input_message(&self, message: InputMessage) -> Result<()> {
+        self.input_sender
+            .unbounded_send(message)
+            .map_err(|_| Error::Disconnected)
+    }
+
+    /// Sends the message to the specified recipient.
+    pub fn send(&self, recipient: Recipient, message: Vec<u8>) -> Result<()> {
+        self.send_input_message(InputMessage::new_fresh(recipient, message, false))
+    }
+
+    /// Sends the message to the specified recipient, attaching a single use reply block
+    /// that the recipient can use to respond without learning our address.
+    pub fn send_with_reply_surb(&self, recipient: Recipient, message: Vec<u8>) -> Result<()> {
+        self.send_input_message(InputMessage::new_fresh(recipient, message, true))
+    }
+
+    /// Sends the message back to the client that provided the reply block.
+    pub fn send_reply(&self, reply_surb: ReplySurb, message: Vec<u8>) -> Result<()> {
+        self.send_input_message(InputMessage::new_reply(reply_surb, message))
+    }
+
+    /// Opens a new stream to the specified recipient. The written data is sent in chunks
+    /// as soon as the client is able to push them into the mix network.
+    pub fn open_stream(&self, recipient: Recipient) -> MixnetStreamWriter {
+        MixnetStreamWriter::new(
+            recipient,
+            self.input_sender.clone(),
+            self.out_queue_length.clone(),
+        )
+    }
+
+    /// Signals all the tasks of the client to stop and waits for them to finish.
+    pub async fn disconnect(mut self) {
+        info!("Disconnecting from the mixnet");
+        self.received_buffer_request_sender
+            .unbounded_send(ReceivedBufferMessage::ReceiverDisconnect)
+            .ok();
+        self.shutdown.signal_shutdown().ok();
+        self.shutdown.wait_for_shutdown().await;
+    }
+}
+
+impl Stream for MixnetClient {
+    type Item = ReconstructedMessage;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(message) = self.received.pop_front() {
+                return Poll::Ready(Some(message));
+            }
+
+            match ready!(self.reconstructed_receiver.poll_next_unpin(cx)) {
+                Some(messages) => self.received.extend(messages),
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+/// Makes sure the client has keys registered with a gateway, reusing the previous registration
+/// if one exists.
+async fn setup_gateway(config: &mut Config) -> Result<()> {
+    let pathfinder = ClientKeyPathfinder::new_from_config(config.get_base());
+    let id = config.get_base().get_id();
+
+    if pathfinder.gateway_shared_key().exists() {
+        if let Ok(existing_config) = Config::load_from_file(Some(&id)) {
+            debug!(
+                "Reusing existing registration with gateway {}",
+                existing_config.get_base().get_gateway_id()
+            );
+            config
+                .get_base_mut()
+                .with_gateway_endpoint(existing_config.get_base().get_gateway_endpoint().clone());
+            return Ok(());
+        }
+    }
+
+    info!("Registering with a gateway");
+    let chosen_gateway_id = config.get_base().get_gateway_id();
+    let chosen_gateway_id = if chosen_gateway_id.is_empty() {
+        None
+    } else {
+        Some(chosen_gateway_id)
+    };
+
+    let gateway = client_core::init::query_gateway_details(
+        config.get_base().get_validator_api_endpoints(),
+        chosen_gateway_id.as_deref(),
+    )
+    .await?;
+    client_core::init::register_with_gateway_and_store_keys(gateway.clone(), config.get_base())
+        .await?;
+    config.get_base_mut().with_gateway_endpoint(gateway.into());
+
+    config.save_to_file(None)?;
+    info!(
+        "Saved configuration file to {:?}",
+        config.get_config_file_save_location()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use client_core::client::inbound_messages::InputMessageReceiver;
+    use client_core::client::received_buffer::{
+        ReceivedBufferRequestReceiver, ReconstructedMessagesSender,
+    };
+    use crypto::asymmetric::{encryption, identity};
+    use rand::rngs::OsRng;
+
+    fn recipient() -> Recipient {
+        let mut rng = OsRng;
+        Recipient::new(
+            *identity::KeyPair::new(&mut rng).public_key(),
+            *encryption::KeyPair::new(&mut rng).public_key(),
+            *identity::KeyPair::new(&mut rng).public_key(),
+        )
+    }
+
+    fn message(content: &[u8]) -> ReconstructedMessage {
+        ReconstructedMessage {
+            message: content.to_vec(),
+            reply_surb: None,
+        }
+    }
+
+    fn test_client() -> (
+        MixnetClient,
+        InputMessageReceiver,
+        ReconstructedMessagesSender,
+        ReceivedBufferRequestReceiver,
+    ) {
+        let (input_sender, input_receiver) = mpsc::unbounded();
+        let (reconstructed_sender, reconstructed_receiver) = mpsc::unbounded();
+        let (received_buffer_request_sender, received_buffer_request_receiver) = mpsc::unbounded();
+
+        let client = MixnetClient {
+            nym_address: SelfAddress::new(recipient()),
+            input_sender,
+            reconstructed_receiver,
+            received_buffer_request_sender,
+            received: VecDeque::new(),
+            out_queue_length: Default::default(),
+            client_events: None,
+            shutdown: ShutdownNotifier::default(),
+        };
+        (
+            client,
+            input_receiver,
+            reconstructed_sender,
+            received_buffer_request_receiver,
+        )
+    }
+
+    #[tokio::test]
+    async fn received_messages_are_returned_one_by_one() {
+        let (mut client, _input_receiver, reconstructed_sender, _buffer_receiver) = test_client();
+
+        reconstructed_sender
+            .unbounded_send(vec![message(b"foo"), message(b"bar")])
+            .unwrap();
+        reconstructed_sender
+            .unbounded_send(vec![message(b"baz")])
+            .unwrap();
+        drop(reconstructed_sender);
+
+        let received: Vec<_> = (&mut client)
+            .map(|received| received.message)
+            .collect()
+            .await;
+        assert_eq!(
+            received,
+            vec![b"foo".to_vec(), b"bar".to_vec(), b"baz".to_vec()]
+        );
+    }
+
+    #[tokio::test]
+    async fn sent_messages_are_passed_to_the_mixnet() {
+        let (client, mut input_receiver, _reconstructed_sender, _buffer_receiver) = test_client();
+        let recipient = recipient();
+
+        client.send(recipient, b"foo".to_vec()).unwrap();
+        client
+            .send_with_reply_surb(recipient, b"bar".to_vec())
+            .unwrap();
+
+        for (expected_data, expected_reply_surb) in [(b"foo", false), (b"bar", true)] {
+            match input_receiver.next().await.unwrap() {
+                InputMessage::Fresh {
+                    recipient: sent_to,
+                    data,
+                    with_reply_surb,
+                    ..
+                } => {
+                    assert_eq!(sent_to.to_bytes(), recipient.to_bytes());
+                    assert_eq!(data, expected_data.to_vec());
+                    assert_eq!(with_reply_surb, expected_reply_surb);
+                }
+                InputMessage::Reply { .. } => panic!("sent a reply instead of a fresh message"),
+            }
+        }
+
+        drop(input_receiver);
+        assert!(matches!(
+            client.send(recipient, b"baz".to_vec()),
+            Err(Error::Disconnected)
+        ));
+    }
+
+    #[tokio::test]
+    async fn disconnecting_detaches_from_the_received_messages_buffer() {
+        let (client, _input_receiver, _reconstructed_sender, mut buffer_receiver) = test_client();
+
+        client.disconnect().await;
+        assert!(matches!(
+            buffer_receiver.next().await,
+            Some(ReceivedBufferMessage::ReceiverDisconnect)
+        ));
+    }
+}
@@ -0,0 +1,123 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::mixnet::config::template::config_template;
+use client_core::config::{ClientCoreConfigTrait, Config as BaseConfig};
+use config::NymConfig;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use url::Url;
+
+mod template;
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(flatten)]
+    base: BaseConfig<Config>,
+}
+
+impl NymConfig for Config {
+    fn template() -> &'static str {
+        config_template()
+    }
+
+    fn default_root_directory() -> PathBuf {
+        dirs::home_dir()
+            .expect("Failed to evaluate $HOME value")
+            .join(".nym")
+            .join("sdk-clients")
+    }
+
+    fn try_default_root_directory() -> Option<PathBuf> {
+        dirs::home_dir().map(|path| path.join(".nym").join("sdk-clients"))
+    }
+
+    fn root_directory(&self) -> PathBuf {
+        self.base.get_nym_root_directory()
+    }
+
+    fn config_directory(&self) -> PathBuf {
+        self.root_directory()
+            .join(self.base.get_id())
+            .join("config")
+    }
+
+    fn data_directory(&self) -> PathBuf {
+        self.root_directory().join(self.base.get_id()).join("data")
+    }
+}
+
+impl ClientCoreConfigTrait for Config {
+    fn get_base(&self) -> &BaseConfig<Self> {
+        &self.base
+    }
+
+    fn get_base_mut(&mut self) -> &mut BaseConfig<Self> {
+        &mut self.base
+    }
+}
+
+impl Config {
+    /// Creates configuration of the client with the given id. Keys and the details of the gateway
+    /// of the client are stored under this id, so that they could be reused on the next connection.
+    pub fn new<S: Into<String>>(id: S) -> Self {
+        Config {
+            base: BaseConfig::new(id),
+        }
+    }
+
+    /// Gateway to register with when the client connects for the first time.
+    /// If not specified, a random gateway is chosen.
+    #[must_use]
+    pub fn with_gateway<S: Into<String>>(mut self, gateway_id: S) -> Self {
+        self.base.with_gateway_id(gateway_id);
+        self
+    }
+
+    #[must_use]
+    pub fn with_validator_api_endpoints(mut self, validator_api_urls: Vec<Url>) -> Self {
+        self.base.set_custom_validator_apis(validator_api_urls);
+        self
+    }
+
+    #[must_use]
+    pub fn with_disabled_credentials(mut self, disabled_credentials_mode: bool) -> Self {
+        self.base
+            .with_disabled_credentials(disabled_credentials_mode);
+        self
+    }
+
+    // getters
+    pub fn get_config_file_save_location(&self) -> PathBuf {
+        self.config_directory().join(Self::config_file_name())
+    }
+
+    pub fn get_base(&self) -> &BaseConfig<Self> {
+        &self.base
+    }
+
+    pub fn get_base_mut(&mut self) -> &mut BaseConfig<Self> {
+        &mut self.base
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_survives_saving_and_loading() {
+        let mut config = Config::new("sdk-config-test")
+            .with_gateway("gateway")
+            .with_disabled_credentials(true);
+        config.get_base_mut().set_high_default_traffic_volume();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(Config::config_file_name());
+        config.save_to_file(Some(path.clone())).unwrap();
+
+        let loaded: Config = toml::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(config, loaded);
+    }
+}
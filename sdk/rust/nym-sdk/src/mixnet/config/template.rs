@@ -0,0 +1,162 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+pub(crate) fn config_template() -> &'static str {
+    // While using normal toml marshalling would have been way simpler with less overhead,
+    // I think it's useful to have comments attached to the saved config file to explain behaviour of
+    // particular fields.
+    // Note: any changes to the template must be reflected in the appropriate structs.
+    r#"
+# This is a TOML config file.
+# For more information, see https://github.com/toml-lang/toml
+
+##### main base client config options #####
+
+[client]
+# Version of the client for which this configuration was created.
+version = '{{ client.version }}'
+
+# Human readable ID of this particular client.
+id = '{{ client.id }}'
+
+# Indicates whether this client is running in a disabled credentials mode, thus attempting
+# to claim bandwidth without presenting bandwidth credentials.
+disabled_credentials_mode = {{ client.disabled_credentials_mode }}
+
+# Addresses to APIs running on validator from which the client gets the view of the network.
+validator_api_urls = [
+    {{#each client.validator_api_urls }}
+        '{{this}}',
+    {{/each}}
+]
+
+# Base58-encoded identity keys of the validator APIs whose signed topology responses
+# are trusted. If empty, the topology is used without verifying its origin.
+trusted_validator_api_keys = [
+    {{#each client.trusted_validator_api_keys }}
+        '{{this}}',
+    {{/each}}
+]
+
+# Number of distinct trusted validator APIs that have to agree on the network topology
+# before the client starts using it.
+required_topology_signatures = {{ client.required_topology_signatures }}
+
+# Path to file containing private identity key.
+private_identity_key_file = '{{ client.private_identity_key_file }}'
+
+# Path to file containing public identity key.
+public_identity_key_file = '{{ client.public_identity_key_file }}'
+
+# Path to file containing private encryption key.
+private_encryption_key_file = '{{ client.private_encryption_key_file }}'
+
+# Path to file containing public encryption key.
+public_encryption_key_file = '{{ client.public_encryption_key_file }}'
+
+# Full path to file containing reply encryption keys of all reply-SURBs we have ever
+# sent but not received back.
+reply_encryption_key_store_path = '{{ client.reply_encryption_key_store_path }}'
+
+# Path to the database containing bandwidth credentials
+database_path = '{{ client.database_path }}'
+
+# Path to the directory used for storing fragments of partially received messages
+# (if disk-backed reconstruction is enabled). Note that the fragments are stored in plaintext.
+reconstruction_buffer_directory = '{{ client.reconstruction_buffer_directory }}'
+
+# Path to the directory used for storing sent messages that have not yet been
+# acknowledged (if the outbound journal is enabled).
+outbound_journal_directory = '{{ client.outbound_journal_directory }}'
+
+# Path to the file containing the latest ranking of gateways, measured when the client
+# has automatically chosen its gateway.
+gateway_ranking_file = '{{ client.gateway_ranking_file }}'
+
+##### additional client config options #####
+
+# A gateway specific, optional, base58 stringified shared key used for
+# communication with particular gateway.
+gateway_shared_key_file = '{{ client.gateway_shared_key_file }}'
+
+# Path to file containing key used for encrypting and decrypting the content of an
+# acknowledgement so that nobody besides the client knows which packet it refers to.
+ack_key_file = '{{ client.ack_key_file }}'
+    
+##### advanced configuration options #####
+
+# Absolute path to the home Nym Clients directory.
+nym_root_directory = '{{ client.nym_root_directory }}'
+
+[client.gateway_endpoint]
+# ID of the gateway from which the client should be fetching messages.
+gateway_id = '{{ client.gateway_endpoint.gateway_id }}'
+
+# Address of the gateway owner to which the client should send messages.
+gateway_owner = '{{ client.gateway_endpoint.gateway_owner }}'
+
+# Address of the gateway listener to which all client requests should be sent.
+# Use the 'tcp://' scheme (rather than 'ws://') to talk to the gateway over raw TCP
+# instead of WebSocket.
+gateway_listener = '{{ client.gateway_endpoint.gateway_listener }}'
+
+
+
+##### logging configuration options #####
+
+[logging]
+
+# TODO
+
+
+##### debug configuration options #####
+# The following options should not be modified unless you know EXACTLY what you are doing
+# as if set incorrectly, they may impact your anonymity.
+
+[debug]
+
+average_packet_delay = '{{ debug.average_packet_delay }}'
+average_ack_delay = '{{ debug.average_ack_delay }}'
+loop_cover_traffic_average_delay = '{{ debug.loop_cover_traffic_average_delay }}'
+message_sending_average_delay = '{{ debug.message_sending_average_delay }}'
+
+# Value multiplied with and added to the expected round trip time of an acknowledgement
+# before the packet is assumed to be lost and gets retransmitted.
+ack_wait_multiplier = {{ debug.ack_wait_multiplier }}
+ack_wait_addition = '{{ debug.ack_wait_addition }}'
+
+gateway_response_timeout = '{{ debug.gateway_response_timeout }}'
+topology_refresh_rate = '{{ debug.topology_refresh_rate }}'
+topology_resolution_timeout = '{{ debug.topology_resolution_timeout }}'
+
+disable_loop_cover_traffic_stream = {{ debug.disable_loop_cover_traffic_stream }}
+disable_main_poisson_packet_distribution = {{ debug.disable_main_poisson_packet_distribution }}
+use_extended_packet_size = {{ debug.use_extended_packet_size }}
+
+# Number of mix hops (excluding the gateway) each sent packet is going to go through.
+num_mix_hops = {{ debug.num_mix_hops }}
+
+# Note that with the disk-backed reconstruction enabled, fragments of partially received
+# messages are stored in plaintext.
+use_disk_backed_reconstruction = {{ debug.use_disk_backed_reconstruction }}
+reconstruction_memory_budget = {{ debug.reconstruction_memory_budget }}
+incomplete_message_timeout = '{{ debug.incomplete_message_timeout }}'
+
+# Ratio of parity to data fragments attached to each sent message (0 disables erasure coding).
+erasure_coding_redundancy = {{ debug.erasure_coding_redundancy }}
+
+# Limits on delivering a message before the client gives up on it (0 disables the limit).
+maximum_retransmissions = {{ debug.maximum_retransmissions }}
+message_timeout = '{{ debug.message_timeout }}'
+
+use_outbound_journal = {{ debug.use_outbound_journal }}
+
+gateway_selection_sample_size = {{ debug.gateway_selection_sample_size }}
+gateway_probe_timeout = '{{ debug.gateway_probe_timeout }}'
+disable_gateway_failover = {{ debug.disable_gateway_failover }}
+gateway_failover_threshold = {{ debug.gateway_failover_threshold }}
+
+max_topology_age = '{{ debug.max_topology_age }}'
+
+"#
+}
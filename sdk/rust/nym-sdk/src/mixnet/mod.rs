@@ -0,0 +1,35 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Mixnet client that can be embedded in a Rust application.
+//!
+//! ```no_run
+//! use futures::StreamExt;
+//! use nym_sdk::mixnet;
+//!
+//! # async fn run() -> nym_sdk::Result<()> {
+//! let mut client = mixnet::MixnetClient::connect(mixnet::Config::new("my-client")).await?;
+//! let our_address = client.nym_address();
+//!
+//! client.send(our_address, b"hello world".to_vec())?;
+//! if let Some(received) = client.next().await {
+//!     println!("received {}", String::from_utf8_lossy(&received.message));
+//! }
+//!
+//! client.disconnect().await;
+//! # Ok(())
+//! # }
+//! ```
+
+mod client;
+mod config;
+
+pub use self::config::Config;
+pub use client::MixnetClient;
+pub use client_core::client::events::{ClientEvent, ClientEventReceiver};
+pub use client_core::client::streams::{
+    IncomingStreams, MixnetStreamReader, MixnetStreamWriter, StreamReceiver,
+};
+pub use nymsphinx::addressing::clients::Recipient;
+pub use nymsphinx::anonymous_replies::ReplySurb;
+pub use nymsphinx::receiver::ReconstructedMessage;
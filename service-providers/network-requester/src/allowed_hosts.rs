@@ -1,10 +1,10 @@
 // Copyright 2020 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::public_suffix;
 use fs::OpenOptions;
 use io::BufReader;
 use ipnetwork::IpNetwork;
-use publicsuffix::{errors, List};
 use std::collections::HashSet;
 use std::fs;
 use std::fs::File;
@@ -13,6 +13,11 @@ use std::io::BufRead;
 use std::net::{IpAddr, SocketAddr};
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often the storefile of the allowed hosts is checked for modifications.
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(1);
 
 /// Filters outbound requests based on what's in an `allowed_hosts` list.
 ///
@@ -25,7 +30,11 @@ use std::path::PathBuf;
 /// We rely on the list of domains at https://publicsuffix.org/ to figure out what the root
 /// domain is for a given request. This allows us to distinguish all the rules for e.g.
 /// .com, .co.uk, .co.jp, uk.com, etc, so that we can distinguish correct root-ish
-/// domains as allowed. That list is loaded once at startup from the network.
+/// domains as allowed. A copy of that list is bundled with the binary, unless a more recent one
+/// has been cached in the storage directory (see `public_suffix`).
+///
+/// Changes made to the `allowed_hosts` list are picked up without having to restart the
+/// service provider.
 pub(crate) struct OutboundRequestFilter {
     allowed_hosts: HostsStore,
     domain_list: publicsuffix::List,
@@ -37,10 +46,7 @@ impl OutboundRequestFilter {
         allowed_hosts: HostsStore,
         unknown_hosts: HostsStore,
     ) -> OutboundRequestFilter {
-        let domain_list = match Self::fetch_domain_list() {
-            Ok(list) => list,
-            Err(e) => panic!("Couldn't fetch domain list for request filtering, do you have an internet connection?: {:?}", e),
-        };
+        let domain_list = public_suffix::load(allowed_hosts.storage_dir());
         OutboundRequestFilter {
             allowed_hosts,
             domain_list,
@@ -48,40 +54,36 @@ impl OutboundRequestFilter {
         }
     }
 
-    fn fetch_domain_list() -> Result<List, errors::Error> {
-        publicsuffix::List::fetch()
-    }
-
-    /// Returns `true` if a host's root domain is in the `allowed_hosts` list.
+    /// Returns `true` if a host is allowed by any of the rules in the `allowed_hosts` list
+    /// and it's not explicitly denied.
     ///
     /// If it's not in the list, return `false` and write it to the `unknown_hosts` storefile.
     pub(crate) fn check(&mut self, host: &str) -> bool {
+        self.allowed_hosts.reload_if_changed();
+
         // first check if it's a socket address (ip:port)
         // (this check is performed to not incorrectly strip what we think might be a port
         // from ipv6 address, as for example ::1 contains colons but has no port
         let allowed = if let Ok(socketaddr) = host.parse::<SocketAddr>() {
-            if !self.allowed_hosts.contains_ip_address(socketaddr.ip()) {
-                self.unknown_hosts.maybe_add_ip(socketaddr.ip());
-                return false;
-            }
-            true
+            self.check_ip_address(socketaddr.ip(), Some(socketaddr.port()))
         } else if let Ok(ipaddr) = host.parse::<IpAddr>() {
             // then check if it was an ip address
-            if !self.allowed_hosts.contains_ip_address(ipaddr) {
-                self.unknown_hosts.maybe_add_ip(ipaddr);
-                return false;
-            }
-            true
+            self.check_ip_address(ipaddr, None)
         } else {
             // finally, then assume it might be a domain
             let trimmed = Self::trim_port(host);
+            let port = Self::extract_port(host);
             if let Some(domain_root) = self.get_domain_root(&trimmed) {
                 // it's a domain
-                if !self.allowed_hosts.contains_domain(&domain_root) {
+                if !self
+                    .allowed_hosts
+                    .allows_domain(&trimmed, &domain_root, port)
+                {
                     self.unknown_hosts.maybe_add_domain(&trimmed);
-                    return false;
+                    false
+                } else {
+                    true
                 }
-                true
             } else {
                 // it's something else, no idea what, probably some nonsense
                 false
@@ -98,6 +100,26 @@ impl OutboundRequestFilter {
         allowed
     }
 
+    /// Returns the networks that are currently denied, so that the addresses the allowed hosts
+    /// resolve to could be checked against them right before connecting.
+    pub(crate) fn denied_networks(&mut self) -> DeniedNetworks {
+        self.allowed_hosts.reload_if_changed();
+        self.allowed_hosts.denied_ip_nets.clone()
+    }
+
+    fn check_ip_address(&mut self, address: IpAddr, port: Option<u16>) -> bool {
+        // denied networks always take precedence
+        if self.allowed_hosts.denies_ip_address(address) {
+            log::debug!("{} belongs to one of the denied networks", address);
+            return false;
+        }
+        if !self.allowed_hosts.allows_ip_address(address, port) {
+            self.unknown_hosts.maybe_add_ip(address);
+            return false;
+        }
+        true
+    }
+
     fn trim_port(host: &str) -> String {
         let mut tmp: Vec<_> = host.split(':').collect();
         if tmp.len() > 1 {
@@ -108,6 +130,11 @@ impl OutboundRequestFilter {
         }
     }
 
+    fn extract_port(host: &str) -> Option<u16> {
+        host.rsplit_once(':')
+            .and_then(|(_, port)| port.parse().ok())
+    }
+
     /// Attempts to get the root domain, shorn of subdomains, using publicsuffix.
     /// If the domain is itself a suffix, then just use the full address as root.
     fn get_domain_root(&self, host: &str) -> Option<String> {
@@ -126,70 +153,226 @@ impl OutboundRequestFilter {
 }
 
 // used for parsing file content
+#[derive(Debug)]
 enum Host {
     Domain(String),
+    // any subdomain of the specified domain, e.g. `*.nymtech.net`
+    WildcardDomain(String),
     IpNetwork(IpNetwork),
 }
 
 // TODO: perphaps in the future it should do some domain validation?
 // so for example if somebody put some nonsense in the whitelist file like "foomp", it would get
 // rejected?
-impl From<String> for Host {
-    fn from(raw: String) -> Self {
+impl From<&str> for Host {
+    fn from(raw: &str) -> Self {
         if let Ok(ipnet) = raw.parse() {
             Host::IpNetwork(ipnet)
+        } else if let Some(domain) = raw.strip_prefix("*.") {
+            Host::WildcardDomain(domain.to_string())
         } else {
-            Host::Domain(raw)
+            Host::Domain(raw.to_string())
         }
     }
 }
 
-impl Host {
-    fn is_domain(&self) -> bool {
-        matches!(self, Host::Domain(..))
+/// Host, optionally restricted to a single port, that requests are allowed to be made to.
+#[derive(Debug)]
+struct AllowRule {
+    host: Host,
+    port: Option<u16>,
+}
+
+impl AllowRule {
+    fn allows_port(&self, port: Option<u16>) -> bool {
+        match self.port {
+            Some(allowed) => port == Some(allowed),
+            None => true,
+        }
+    }
+
+    fn matches_domain(&self, host: &str, domain_root: &str, port: Option<u16>) -> bool {
+        let matches = match &self.host {
+            Host::Domain(domain) => domain == domain_root,
+            Host::WildcardDomain(parent) => host
+                .strip_suffix(parent.as_str())
+                .map(|subdomain| subdomain.len() > 1 && subdomain.ends_with('.'))
+                .unwrap_or_default(),
+            Host::IpNetwork(_) => false,
+        };
+        matches && self.allows_port(port)
+    }
+
+    fn matches_ip_address(&self, address: IpAddr, port: Option<u16>) -> bool {
+        match self.host {
+            Host::IpNetwork(ipnet) => ipnet.contains(address) && self.allows_port(port),
+            _ => false,
+        }
     }
+}
+
+/// A single line of the storefile. Supported formats are:
+///
+/// - `nymtech.net` - the domain and all of its subdomains,
+/// - `*.nymtech.net` - all subdomains of the domain (useful for suffixes, like `*.s3.amazonaws.com`),
+/// - `1.2.3.4` or `1.2.3.0/24` - the address or the whole network,
+/// - any of the above followed by `:port` to only allow that port. IPv6 addresses have to be
+///   put in brackets, e.g. `[2001:db8::/32]:443`,
+/// - `!1.2.3.0/24` - deny the network even if it was allowed by other rules.
+///
+/// Empty lines and lines starting with `#` or `//` are ignored.
+#[derive(Debug)]
+enum Rule {
+    Allow(AllowRule),
+    Deny(IpNetwork),
+}
 
-    fn extract_domain(self) -> String {
-        match self {
-            Host::Domain(domain) => domain,
-            _ => panic!("called extract domain on an ipnet!"),
+impl Rule {
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+            return None;
+        }
+
+        if let Some(denied) = line.strip_prefix('!') {
+            return match denied.trim().parse() {
+                Ok(ipnet) => Some(Rule::Deny(ipnet)),
+                Err(_) => {
+                    log::warn!("{:?} is not a valid network to deny - ignoring it", line);
+                    None
+                }
+            };
+        }
+
+        match Self::split_port(line) {
+            Some((host, port)) => Some(Rule::Allow(AllowRule {
+                host: Host::from(host),
+                port,
+            })),
+            None => {
+                log::warn!("{:?} has an invalid port - ignoring it", line);
+                None
+            }
         }
     }
 
-    fn extract_ipnetwork(self) -> IpNetwork {
-        match self {
-            Host::IpNetwork(ipnet) => ipnet,
-            _ => panic!("called extract ipnet on a domain!"),
+    /// Separates the optional port from the host, returning `None` if the port is malformed.
+    fn split_port(raw: &str) -> Option<(&str, Option<u16>)> {
+        if let Some(bracketed) = raw.strip_prefix('[') {
+            let (host, remaining) = bracketed.split_once(']')?;
+            return match remaining {
+                "" => Some((host, None)),
+                _ => Some((host, Some(remaining.strip_prefix(':')?.parse().ok()?))),
+            };
+        }
+
+        match raw.split_once(':') {
+            // more than a single colon means it must be an ipv6 address without a port
+            Some((host, port)) if !port.contains(':') => Some((host, Some(port.parse().ok()?))),
+            _ => Some((raw, None)),
         }
     }
 }
 
 /// A simple file-based store for information about allowed / unknown hosts.
-/// Currently it completely ignores any port information.
-// TODO: in the future allow filtering by port, so for example 1.1.1.1:80 would be a valid filter,
-// which would allow connections to the port :80 while any requests to say 1.1.1.1:1234 would be denied.
+/// Plain domains and networks, which are the most common, are kept separately from
+/// the more complex rules for faster lookups.
 #[derive(Debug)]
 pub(crate) struct HostsStore {
     storefile: PathBuf,
+    storefile_version: Option<(SystemTime, u64)>,
+    last_reload_check: Instant,
 
     domains: HashSet<String>,
     ip_nets: Vec<IpNetwork>,
+    restricted: Vec<AllowRule>,
+    denied_ip_nets: DeniedNetworks,
 }
 
 impl HostsStore {
     /// Constructs a new HostsStore
     pub(crate) fn new(base_dir: PathBuf, filename: PathBuf) -> HostsStore {
         let storefile = HostsStore::setup_storefile(base_dir, filename);
-        let hosts = HostsStore::load_from_storefile(&storefile)
+        let storefile_version = HostsStore::storefile_version(&storefile);
+        let rules = HostsStore::load_from_storefile(&storefile)
             .unwrap_or_else(|_| panic!("Could not load hosts from storefile at {:?}", storefile));
 
-        let (domains, ip_nets): (Vec<_>, Vec<_>) =
-            hosts.into_iter().partition(|host| host.is_domain());
-
-        HostsStore {
+        let mut store = HostsStore {
             storefile,
-            domains: domains.into_iter().map(Host::extract_domain).collect(),
-            ip_nets: ip_nets.into_iter().map(Host::extract_ipnetwork).collect(),
+            storefile_version,
+            last_reload_check: Instant::now(),
+            domains: HashSet::new(),
+            ip_nets: Vec::new(),
+            restricted: Vec::new(),
+            denied_ip_nets: Default::default(),
+        };
+        store.set_rules(rules);
+        store
+    }
+
+    fn set_rules(&mut self, rules: Vec<Rule>) {
+        self.domains.clear();
+        self.ip_nets.clear();
+        self.restricted.clear();
+
+        let mut denied_ip_nets = Vec::new();
+        for rule in rules {
+            match rule {
+                Rule::Allow(AllowRule {
+                    host: Host::Domain(domain),
+                    port: None,
+                }) => {
+                    self.domains.insert(domain);
+                }
+                Rule::Allow(AllowRule {
+                    host: Host::IpNetwork(ipnet),
+                    port: None,
+                }) => self.ip_nets.push(ipnet),
+                Rule::Allow(rule) => self.restricted.push(rule),
+                Rule::Deny(ipnet) => denied_ip_nets.push(ipnet),
+            }
+        }
+        self.denied_ip_nets = DeniedNetworks(Arc::new(denied_ip_nets));
+    }
+
+    fn storage_dir(&self) -> &Path {
+        self.storefile
+            .parent()
+            .expect("the storefile is not located in any directory")
+    }
+
+    fn storefile_version(storefile: &Path) -> Option<(SystemTime, u64)> {
+        let metadata = fs::metadata(storefile).ok()?;
+        Some((metadata.modified().ok()?, metadata.len()))
+    }
+
+    /// Reloads the rules if the storefile has been modified since it was last loaded, checking it
+    /// at most once per `RELOAD_CHECK_INTERVAL`.
+    fn reload_if_changed(&mut self) {
+        if self.last_reload_check.elapsed() < RELOAD_CHECK_INTERVAL {
+            return;
+        }
+        self.last_reload_check = Instant::now();
+        self.reload();
+    }
+
+    fn reload(&mut self) {
+        let version = HostsStore::storefile_version(&self.storefile);
+        if version == self.storefile_version {
+            return;
+        }
+
+        match HostsStore::load_from_storefile(&self.storefile) {
+            Ok(rules) => {
+                log::info!("Reloading hosts from {:?}", self.storefile);
+                self.set_rules(rules);
+                self.storefile_version = version;
+            }
+            Err(err) => log::warn!(
+                "Failed to reload hosts from {:?} - {}. The previous rules are still in use",
+                self.storefile,
+                err
+            ),
         }
     }
 
@@ -214,6 +397,14 @@ impl HostsStore {
         self.domains.contains(&host.to_string())
     }
 
+    fn allows_domain(&self, host: &str, domain_root: &str, port: Option<u16>) -> bool {
+        self.contains_domain(domain_root)
+            || self
+                .restricted
+                .iter()
+                .any(|rule| rule.matches_domain(host, domain_root, port))
+    }
+
     fn contains_ip_address(&self, address: IpAddr) -> bool {
         // I'm not sure it's possible to achieve the same functionality without iterating through
         // the whole thing. Maybe by some clever usage of tries? But I doubt we're going to have
@@ -227,6 +418,18 @@ impl HostsStore {
         false
     }
 
+    fn allows_ip_address(&self, address: IpAddr, port: Option<u16>) -> bool {
+        self.contains_ip_address(address)
+            || self
+                .restricted
+                .iter()
+                .any(|rule| rule.matches_ip_address(address, port))
+    }
+
+    fn denies_ip_address(&self, address: IpAddr) -> bool {
+        self.denied_ip_nets.contains(address)
+    }
+
     /// Returns the default base directory for the storefile.
     ///
     /// This is split out so we can easily inject our own base_dir for unit tests.
@@ -236,6 +439,11 @@ impl HostsStore {
             .join(".nym")
     }
 
+    /// Returns the directory the storefiles, and the cached public suffix list, are kept in.
+    pub(crate) fn storage_dir_for(base_dir: PathBuf) -> PathBuf {
+        base_dir.join("service-providers").join("network-requester")
+    }
+
     fn maybe_add_ip(&mut self, ip: IpAddr) {
         if !self.contains_ip_address(ip) {
             self.ip_nets.push(ip.into());
@@ -251,7 +459,7 @@ impl HostsStore {
     }
 
     fn setup_storefile(base_dir: PathBuf, filename: PathBuf) -> PathBuf {
-        let dirpath = base_dir.join("service-providers").join("network-requester");
+        let dirpath = HostsStore::storage_dir_for(base_dir);
         fs::create_dir_all(&dirpath)
             .unwrap_or_else(|_| panic!("could not create storage directory at {:?}", dirpath));
         let storefile = dirpath.join(filename);
@@ -263,16 +471,46 @@ impl HostsStore {
     }
 
     /// Loads the storefile contents into memory.
-    fn load_from_storefile<P>(filename: P) -> io::Result<Vec<Host>>
+    fn load_from_storefile<P>(filename: P) -> io::Result<Vec<Rule>>
     where
         P: AsRef<Path>,
     {
         let file = File::open(filename)?;
         let reader = BufReader::new(&file);
-        Ok(reader
-            .lines()
-            .map(|line| Host::from(line.expect("failed to read input file line!")))
-            .collect())
+        let lines = reader.lines().collect::<io::Result<Vec<_>>>()?;
+        Ok(lines.iter().filter_map(|line| Rule::parse(line)).collect())
+    }
+}
+
+/// Networks that must never be connected to, even if the requested host is allowed. Since hosts
+/// are allowed by their domains, the addresses they resolve to have to be checked as well.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct DeniedNetworks(Arc<Vec<IpNetwork>>);
+
+impl DeniedNetworks {
+    fn contains(&self, address: IpAddr) -> bool {
+        self.0.iter().any(|ip_net| ip_net.contains(address))
+    }
+
+    /// Resolves the `host:port` address, failing if any of the addresses it resolves to belongs
+    /// to a denied network. The returned addresses are the ones that should be connected to,
+    /// so that the host could not resolve to something else in the meantime.
+    pub(crate) async fn resolve(&self, address: &str) -> io::Result<Vec<SocketAddr>> {
+        let resolved: Vec<_> = tokio::net::lookup_host(address).await?.collect();
+        if let Some(denied) = resolved
+            .iter()
+            .find(|resolved| self.contains(resolved.ip()))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "{} resolves to {}, which belongs to one of the denied networks",
+                    address,
+                    denied.ip()
+                ),
+            ));
+        }
+        Ok(resolved)
     }
 }
 
@@ -520,6 +758,140 @@ mod tests {
         }
     }
 
+    #[cfg(test)]
+    mod requests_to_hosts_allowed_by_extended_rules {
+        use super::*;
+
+        fn setup(allowed: &[&str]) -> OutboundRequestFilter {
+            let (allowed_storefile, base_dir1, allowed_filename) = create_test_storefile();
+            let (_, base_dir2, unknown_filename) = create_test_storefile();
+
+            for allowed_host in allowed {
+                HostsStore::append(&allowed_storefile, allowed_host)
+            }
+
+            let allowed = HostsStore::new(base_dir1, allowed_filename);
+            let unknown = HostsStore::new(base_dir2, unknown_filename);
+            OutboundRequestFilter::new(allowed, unknown)
+        }
+
+        #[test]
+        fn are_allowed_for_wildcard_subdomains() {
+            let mut filter = setup(&["*.s3.amazonaws.com"]);
+            assert!(filter.check("bucket.s3.amazonaws.com"));
+            assert!(filter.check("foomp.bucket.s3.amazonaws.com:443"));
+            assert!(!filter.check("s3.amazonaws.com"));
+            assert!(!filter.check("bucket.nots3.amazonaws.com"));
+        }
+
+        #[test]
+        fn are_only_allowed_for_specified_ports() {
+            let mut filter = setup(&[
+                "nymtech.net:443",
+                "1.1.1.1:53",
+                "1.2.3.0/24:80",
+                "[2620:0:2d0:200::7/32]:443",
+            ]);
+            assert!(filter.check("nymtech.net:443"));
+            assert!(filter.check("foomp.nymtech.net:443"));
+            assert!(!filter.check("nymtech.net:80"));
+            assert!(!filter.check("nymtech.net"));
+
+            assert!(filter.check("1.1.1.1:53"));
+            assert!(!filter.check("1.1.1.1:54"));
+            assert!(!filter.check("1.1.1.1"));
+
+            assert!(filter.check("1.2.3.42:80"));
+            assert!(!filter.check("1.2.3.42:443"));
+
+            assert!(filter.check("[2620:0:42::42]:443"));
+            assert!(!filter.check("[2620:0:42::42]:80"));
+        }
+
+        #[test]
+        fn are_not_allowed_if_in_denied_network() {
+            let mut filter = setup(&["10.0.0.0/8", "!10.1.0.0/16", "1.1.1.1:53", "!1.1.1.1"]);
+            assert!(filter.check("10.2.3.4"));
+            assert!(!filter.check("10.1.2.3"));
+            assert!(!filter.check("10.1.2.3:1234"));
+            assert!(!filter.check("1.1.1.1:53"));
+        }
+
+        #[tokio::test]
+        async fn are_not_allowed_if_resolved_into_denied_network() {
+            let mut filter = setup(&["localhost", "!127.0.0.0/8", "!::1"]);
+            let err = filter
+                .denied_networks()
+                .resolve("localhost:80")
+                .await
+                .unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+            let mut filter = setup(&["localhost", "!10.0.0.0/8"]);
+            let resolved = filter
+                .denied_networks()
+                .resolve("localhost:80")
+                .await
+                .unwrap();
+            assert!(!resolved.is_empty());
+        }
+
+        #[test]
+        fn ignore_comments_and_malformed_lines() {
+            let filter = setup(&["// comment", "# another comment", "", "nymtech.net:foomp"]);
+            assert!(filter.allowed_hosts.domains.is_empty());
+            assert!(filter.allowed_hosts.restricted.is_empty());
+        }
+
+        #[test]
+        fn are_picked_up_after_the_list_is_modified() {
+            let mut filter = setup(&["nymtech.net"]);
+            assert!(!filter.check("edwardsnowden.com"));
+
+            HostsStore::append(&filter.allowed_hosts.storefile, "edwardsnowden.com");
+            HostsStore::append(&filter.allowed_hosts.storefile, "!1.2.3.4");
+            filter.allowed_hosts.reload();
+
+            assert!(filter.check("edwardsnowden.com"));
+            assert!(filter.check("nymtech.net"));
+            assert!(filter
+                .allowed_hosts
+                .denies_ip_address("1.2.3.4".parse().unwrap()));
+        }
+    }
+
+    #[cfg(test)]
+    mod splitting_port_from_rules {
+        use super::*;
+
+        #[test]
+        fn works_for_domains_and_ipv4_addresses() {
+            assert_eq!(Some(("nymtech.net", None)), Rule::split_port("nymtech.net"));
+            assert_eq!(
+                Some(("nymtech.net", Some(443))),
+                Rule::split_port("nymtech.net:443")
+            );
+            assert_eq!(
+                Some(("1.2.3.0/24", Some(80))),
+                Rule::split_port("1.2.3.0/24:80")
+            );
+        }
+
+        #[test]
+        fn requires_brackets_for_ipv6_ports() {
+            assert_eq!(Some(("5:6:7::/48", None)), Rule::split_port("5:6:7::/48"));
+            assert_eq!(Some(("::1", None)), Rule::split_port("[::1]"));
+            assert_eq!(Some(("::1", Some(8080))), Rule::split_port("[::1]:8080"));
+        }
+
+        #[test]
+        fn fails_for_malformed_ports() {
+            assert_eq!(None, Rule::split_port("nymtech.net:foomp"));
+            assert_eq!(None, Rule::split_port("nymtech.net:99999"));
+            assert_eq!(None, Rule::split_port("[::1]8080"));
+        }
+    }
+
     fn random_string() -> String {
         format!("{:?}", rand::random::<u32>())
     }
@@ -1,6 +1,7 @@
 // Copyright 2020 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::allowed_hosts::DeniedNetworks;
 use futures::channel::mpsc;
 use nymsphinx::addressing::clients::Recipient;
 use proxy_helpers::connection_controller::ConnectionReceiver;
@@ -26,8 +27,10 @@ impl Connection {
         id: ConnectionId,
         address: RemoteAddress,
         return_address: Recipient,
+        denied_networks: &DeniedNetworks,
     ) -> io::Result<Self> {
-        let conn = TcpStream::connect(&address).await?;
+        let resolved = denied_networks.resolve(&address).await?;
+        let conn = TcpStream::connect(&*resolved).await?;
 
         Ok(Connection {
             id,
@@ -1,10 +1,11 @@
 // Copyright 2020 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::allowed_hosts::{HostsStore, OutboundRequestFilter};
+use crate::allowed_hosts::{DeniedNetworks, HostsStore, OutboundRequestFilter};
 use crate::connection::Connection;
 use crate::error::NetworkRequesterError;
 use crate::statistics::ServiceStatisticsCollector;
+use crate::udp::{self, DatagramSender};
 use crate::websocket;
 use crate::websocket::TSWebsocketStream;
 use futures::channel::mpsc;
@@ -15,9 +16,11 @@ use nymsphinx::addressing::clients::Recipient;
 use nymsphinx::receiver::ReconstructedMessage;
 use proxy_helpers::connection_controller::{Controller, ControllerCommand, ControllerSender};
 use socks5_requests::{
-    ConnectionId, Message as Socks5Message, NetworkRequesterResponse, Request, Response,
+    ConnectionId, DatagramRequest, Message as Socks5Message, NetworkRequesterResponse, Request,
+    Response,
 };
 use statistics_common::collector::StatisticsSender;
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use task::ShutdownListener;
@@ -33,6 +36,7 @@ pub struct ServiceProvider {
     open_proxy: bool,
     enable_statistics: bool,
     stats_provider_addr: Option<Recipient>,
+    udp_associations: HashMap<ConnectionId, DatagramSender>,
 }
 
 impl ServiceProvider {
@@ -59,6 +63,7 @@ impl ServiceProvider {
             open_proxy,
             enable_statistics,
             stats_provider_addr,
+            udp_associations: HashMap::new(),
         }
     }
 
@@ -90,6 +95,7 @@ impl ServiceProvider {
                 recipient: return_address,
                 message: msg.into_bytes(),
                 with_reply_surb: false,
+                request_id: None,
             };
 
             let message = Message::Binary(response_message.serialize());
@@ -135,9 +141,17 @@ impl ServiceProvider {
         return_address: Recipient,
         controller_sender: ControllerSender,
         mix_input_sender: mpsc::UnboundedSender<(Socks5Message, Recipient)>,
+        denied_networks: DeniedNetworks,
         shutdown: ShutdownListener,
     ) {
-        let mut conn = match Connection::new(conn_id, remote_addr.clone(), return_address).await {
+        let mut conn = match Connection::new(
+            conn_id,
+            remote_addr.clone(),
+            return_address,
+            &denied_networks,
+        )
+        .await
+        {
             Ok(conn) => conn,
             Err(err) => {
                 error!(
@@ -188,6 +202,15 @@ impl ServiceProvider {
         );
     }
 
+    // open proxies don't consult the allowed list at all
+    fn denied_networks(&mut self) -> DeniedNetworks {
+        if self.open_proxy {
+            DeniedNetworks::default()
+        } else {
+            self.outbound_request_filter.denied_networks()
+        }
+    }
+
     fn handle_proxy_connect(
         &mut self,
         controller_sender: &mut ControllerSender,
@@ -211,6 +234,7 @@ impl ServiceProvider {
             return;
         }
 
+        let denied_networks = self.denied_networks();
         let controller_sender_clone = controller_sender.clone();
         let mix_input_sender_clone = mix_input_sender.clone();
 
@@ -222,6 +246,58 @@ impl ServiceProvider {
                 return_address,
                 controller_sender_clone,
                 mix_input_sender_clone,
+                denied_networks,
+                shutdown,
+            )
+            .await
+        });
+    }
+
+    fn handle_proxy_datagram(
+        &mut self,
+        mix_input_sender: &mpsc::UnboundedSender<(Socks5Message, Recipient)>,
+        req: DatagramRequest,
+        shutdown: ShutdownListener,
+    ) {
+        if !self.open_proxy && !self.outbound_request_filter.check(&req.remote_addr) {
+            let log_msg = format!("Domain {:?} failed filter check", req.remote_addr);
+            log::info!("{}", log_msg);
+            mix_input_sender
+                .unbounded_send((
+                    Socks5Message::NetworkRequesterResponse(NetworkRequesterResponse::new(
+                        req.conn_id,
+                        log_msg,
+                    )),
+                    req.return_address,
+                ))
+                .unwrap();
+            return;
+        }
+
+        let mut datagram = (req.remote_addr, req.data);
+        if let Some(association) = self.udp_associations.get(&req.conn_id) {
+            match association.unbounded_send(datagram) {
+                Ok(_) => return,
+                // the association has timed out - we're going to start a fresh one
+                Err(err) => datagram = err.into_inner(),
+            }
+        }
+        self.udp_associations
+            .retain(|_, association| !association.is_closed());
+
+        let (datagram_sender, datagram_receiver) = mpsc::unbounded();
+        datagram_sender.unbounded_send(datagram).unwrap();
+        self.udp_associations.insert(req.conn_id, datagram_sender);
+
+        let denied_networks = self.denied_networks();
+        let mix_input_sender_clone = mix_input_sender.clone();
+        tokio::spawn(async move {
+            udp::run_association(
+                req.conn_id,
+                req.return_address,
+                datagram_receiver,
+                mix_input_sender_clone,
+                denied_networks,
                 shutdown,
             )
             .await
@@ -292,8 +368,21 @@ impl ServiceProvider {
                     }
                     self.handle_proxy_send(controller_sender, conn_id, data, closed)
                 }
+
+                Request::Datagram(req) => {
+                    if let Some(stats_collector) = stats_collector {
+                        stats_collector
+                            .request_stats_data
+                            .write()
+                            .await
+                            .processed(&req.remote_addr, req.data.len() as u32);
+                    }
+                    self.handle_proxy_datagram(mix_input_sender, *req, shutdown)
+                }
             },
-            Socks5Message::Response(_) | Socks5Message::NetworkRequesterResponse(_) => {}
+            Socks5Message::Response(_)
+            | Socks5Message::NetworkRequesterResponse(_)
+            | Socks5Message::DatagramResponse(_) => {}
         }
     }
 
@@ -6,6 +6,7 @@ use clap::Subcommand;
 use clap::{Args, Parser};
 use completions::{fig_generate, ArgShell};
 
+use allowed_hosts::HostsStore;
 use error::NetworkRequesterError;
 use network_defaults::DEFAULT_WEBSOCKET_LISTENING_PORT;
 use nymsphinx::addressing::clients::Recipient;
@@ -14,7 +15,9 @@ mod allowed_hosts;
 mod connection;
 mod core;
 mod error;
+mod public_suffix;
 mod statistics;
+mod udp;
 mod websocket;
 
 const ENABLE_STATISTICS: &str = "enable-statistics";
@@ -32,6 +35,9 @@ struct Run {
 
     /// Mixnet client address where a statistics aggregator is running. The default value is a Nym aggregator client
     statistics_recipient: Option<String>,
+
+    /// Download the latest public suffix list before starting up, rather than relying on the bundled (or previously downloaded) one
+    refresh_suffix_list: bool,
 }
 
 impl Run {
@@ -58,6 +64,17 @@ impl Run {
                 .unwrap_or(&DEFAULT_WEBSOCKET_LISTENING_PORT.to_string())
         );
 
+        if self.refresh_suffix_list {
+            let storage_dir = HostsStore::storage_dir_for(HostsStore::default_base_dir());
+            match public_suffix::refresh(&storage_dir).await {
+                Ok(_) => log::info!("Refreshed the public suffix list"),
+                Err(err) => log::warn!(
+                    "Failed to refresh the public suffix list, the existing one is going to be used - {}",
+                    err
+                ),
+            }
+        }
+
         println!("Starting socks5 service provider:");
         let mut server = core::ServiceProvider::new(
             uri,
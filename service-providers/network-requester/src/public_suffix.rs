@@ -0,0 +1,104 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! The list of public suffixes (https://publicsuffix.org/) used for determining root domains
+//! of the requested hosts.
+//!
+//! A copy of the list is bundled with the binary so that the network requester does not depend on
+//! network access to start up. The list can optionally be refreshed, in which case the downloaded
+//! copy is cached in the storage directory and preferred over the bundled one from then on.
+
+use publicsuffix::List;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const BUNDLED_LIST: &str = include_str!("../data/public_suffix_list.dat");
+
+const CACHED_LIST_FILENAME: &str = "public_suffix_list.dat";
+
+#[derive(Debug, thiserror::Error)]
+pub enum PublicSuffixListError {
+    #[error("failed to download the public suffix list - {0}")]
+    DownloadError(#[from] reqwest::Error),
+
+    #[error("the downloaded public suffix list is invalid - {0}")]
+    InvalidList(String),
+
+    #[error("failed to cache the public suffix list - {0}")]
+    IoError(#[from] io::Error),
+}
+
+/// Loads the list cached in `storage_dir`, falling back to the bundled copy if nothing has been
+/// cached yet or if the cached list can't be used.
+pub(crate) fn load(storage_dir: &Path) -> List {
+    let cached = storage_dir.join(CACHED_LIST_FILENAME);
+    if cached.exists() {
+        match List::from_path(&cached) {
+            Ok(list) => {
+                log::debug!("Using public suffix list cached at {:?}", cached);
+                return list;
+            }
+            Err(err) => log::warn!(
+                "Failed to load public suffix list cached at {:?}, using the bundled one instead: {:?}",
+                cached,
+                err
+            ),
+        }
+    }
+
+    List::from_str(BUNDLED_LIST).expect("the bundled public suffix list is malformed")
+}
+
+/// Downloads the latest version of the list and caches it in `storage_dir`, so that it would be
+/// used the next time the list is loaded.
+pub(crate) async fn refresh(storage_dir: &Path) -> Result<(), PublicSuffixListError> {
+    let raw_list = reqwest::get(publicsuffix::LIST_URL)
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+
+    // make sure we're not going to replace a working list with something we can't parse
+    List::from_str(&raw_list)
+        .map_err(|err| PublicSuffixListError::InvalidList(format!("{:?}", err)))?;
+
+    fs::create_dir_all(storage_dir)?;
+    let temp_file = storage_dir.join(format!("{}.tmp", CACHED_LIST_FILENAME));
+    fs::write(&temp_file, raw_list)?;
+    fs::rename(temp_file, storage_dir.join(CACHED_LIST_FILENAME))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn bundled_list_is_used_if_nothing_is_cached() {
+        let storage_dir = PathBuf::from(format!(
+            "/tmp/nym-tests/public-suffix-{}",
+            rand::random::<u32>()
+        ));
+        let list = load(&storage_dir);
+
+        let domain = list.parse_domain("foomp.domain.co.uk").unwrap();
+        assert_eq!(Some("domain.co.uk"), domain.root());
+    }
+
+    #[test]
+    fn bundled_list_is_used_if_cached_one_is_unreadable() {
+        let storage_dir = PathBuf::from(format!(
+            "/tmp/nym-tests/public-suffix-{}",
+            rand::random::<u32>()
+        ));
+        // a directory can't be read as a list
+        fs::create_dir_all(storage_dir.join(CACHED_LIST_FILENAME)).unwrap();
+        let list = load(&storage_dir);
+
+        let domain = list.parse_domain("foomp.domain.com").unwrap();
+        assert_eq!(Some("domain.com"), domain.root());
+    }
+}
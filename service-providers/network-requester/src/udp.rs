@@ -0,0 +1,91 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::allowed_hosts::DeniedNetworks;
+use futures::channel::mpsc;
+use futures::StreamExt;
+use log::*;
+use nymsphinx::addressing::clients::Recipient;
+use socks5_requests::{ConnectionId, DatagramResponse, Message as Socks5Message, RemoteAddress};
+use std::time::Duration;
+use task::ShutdownListener;
+use tokio::net::UdpSocket;
+
+/// Association gets closed if no datagrams were relayed in either direction for this long.
+const ASSOCIATION_IDLE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Maximum size of a UDP datagram we're willing to receive.
+const MAX_DATAGRAM_SIZE: usize = 65535;
+
+/// Channel used for passing datagrams received from the mixnet to the relevant association.
+pub(crate) type DatagramSender = mpsc::UnboundedSender<(RemoteAddress, Vec<u8>)>;
+pub(crate) type DatagramReceiver = mpsc::UnboundedReceiver<(RemoteAddress, Vec<u8>)>;
+
+/// Relays datagrams of a single SOCKS5 UDP association between the mixnet and the remote hosts.
+/// Since datagrams can be sent to multiple remote hosts, a single unconnected socket is used for
+/// all of them.
+pub(crate) async fn run_association(
+    conn_id: ConnectionId,
+    return_address: Recipient,
+    mut datagram_receiver: DatagramReceiver,
+    mix_sender: mpsc::UnboundedSender<(Socks5Message, Recipient)>,
+    denied_networks: DeniedNetworks,
+    mut shutdown: ShutdownListener,
+) {
+    let socket = match UdpSocket::bind("0.0.0.0:0").await {
+        Ok(socket) => socket,
+        Err(err) => {
+            error!(
+                "Failed to bind UDP socket for association {} - {}",
+                conn_id, err
+            );
+            return;
+        }
+    };
+
+    let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+    loop {
+        tokio::select! {
+            datagram = datagram_receiver.next() => match datagram {
+                Some((remote_addr, data)) => {
+                    let resolved = match denied_networks.resolve(&remote_addr).await {
+                        Ok(resolved) => resolved,
+                        Err(err) => {
+                            warn!("Not sending a datagram to {} - {}", remote_addr, err);
+                            continue;
+                        }
+                    };
+                    if let Err(err) = socket.send_to(&data, &*resolved).await {
+                        warn!("Failed to send a datagram to {} - {}", remote_addr, err)
+                    }
+                }
+                None => break,
+            },
+            received = socket.recv_from(&mut buf) => match received {
+                Ok((len, source)) => {
+                    let response = DatagramResponse::new(conn_id, source.to_string(), buf[..len].to_vec());
+                    if mix_sender
+                        .unbounded_send((Socks5Message::DatagramResponse(response), return_address))
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+                Err(err) => {
+                    error!("Failed to receive a datagram for association {} - {}", conn_id, err);
+                    break;
+                }
+            },
+            _ = tokio::time::sleep(ASSOCIATION_IDLE_TIMEOUT) => {
+                debug!("UDP association {} has been idle for too long", conn_id);
+                break;
+            }
+            _ = shutdown.recv() => {
+                log::trace!("UDP association: Received shutdown");
+                break;
+            }
+        }
+    }
+
+    debug!("UDP association {} is finished", conn_id);
+}
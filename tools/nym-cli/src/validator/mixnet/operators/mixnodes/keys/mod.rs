@@ -1,13 +1,21 @@
 // Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use network_defaults::NymNetworkDetails;
+use nym_cli_commands::context::{create_signing_client, ClientArgs};
+
 pub(crate) async fn execute(
+    global_args: ClientArgs,
     keys: nym_cli_commands::validator::mixnet::operators::mixnode::keys::MixnetOperatorsMixnodeKeys,
+    network_details: &NymNetworkDetails,
 ) -> anyhow::Result<()> {
     match keys.command {
         nym_cli_commands::validator::mixnet::operators::mixnode::keys::MixnetOperatorsMixnodeKeysCommands::DecodeMixnodeKey(args) => {
             nym_cli_commands::validator::mixnet::operators::mixnode::keys::decode_mixnode_key::decode_mixnode_key(args)
         }
+        nym_cli_commands::validator::mixnet::operators::mixnode::keys::MixnetOperatorsMixnodeKeysCommands::AnnounceSphinxKey(args) => {
+            nym_cli_commands::validator::mixnet::operators::mixnode::keys::announce_sphinx_key::announce_sphinx_key(args, create_signing_client(global_args, network_details)?).await
+        }
     }
     Ok(())
 }
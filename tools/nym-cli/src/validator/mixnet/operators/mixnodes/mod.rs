@@ -15,7 +15,7 @@ pub(crate) async fn execute(
 ) -> anyhow::Result<()> {
     match mixnode.command {
         nym_cli_commands::validator::mixnet::operators::mixnode::MixnetOperatorsMixnodeCommands::Keys(keys) => {
-            keys::execute(keys).await?
+            keys::execute(global_args, keys, network_details).await?
         }
         nym_cli_commands::validator::mixnet::operators::mixnode::MixnetOperatorsMixnodeCommands::Rewards(rewards) => {
             rewards::execute(global_args, rewards, network_details).await?
@@ -0,0 +1,39 @@
+/*
+ * Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+CREATE TABLE topology_snapshot
+(
+    absolute_epoch_id INTEGER NOT NULL PRIMARY KEY,
+    taken_at          INTEGER NOT NULL
+);
+
+CREATE TABLE topology_snapshot_mixnode
+(
+    absolute_epoch_id INTEGER NOT NULL,
+    mix_id            INTEGER NOT NULL,
+    identity_key      VARCHAR NOT NULL,
+    sphinx_key        VARCHAR NOT NULL,
+    owner             VARCHAR NOT NULL,
+    host              VARCHAR NOT NULL,
+    mix_port          INTEGER NOT NULL,
+    layer             INTEGER NOT NULL,
+    active            BOOLEAN NOT NULL,
+
+    PRIMARY KEY (absolute_epoch_id, mix_id)
+);
+
+CREATE TABLE topology_snapshot_gateway
+(
+    absolute_epoch_id INTEGER NOT NULL,
+    identity_key      VARCHAR NOT NULL,
+    sphinx_key        VARCHAR NOT NULL,
+    owner             VARCHAR NOT NULL,
+    host              VARCHAR NOT NULL,
+    mix_port          INTEGER NOT NULL,
+    clients_port      INTEGER NOT NULL,
+    location          VARCHAR NOT NULL,
+
+    PRIMARY KEY (absolute_epoch_id, identity_key)
+);
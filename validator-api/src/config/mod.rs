@@ -47,6 +47,9 @@ pub struct Config {
     #[serde(default)]
     topology_cacher: TopologyCacher,
 
+    #[serde(default)]
+    topology_signer: TopologySigner,
+
     #[serde(default)]
     rewarding: Rewarding,
 
@@ -241,6 +244,38 @@ impl Default for TopologyCacher {
     }
 }
 
+#[derive(Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(default)]
+pub struct TopologySigner {
+    /// Path to file containing private identity key used for signing the topology responses.
+    private_identity_key_file: PathBuf,
+
+    /// Path to file containing public identity key used for signing the topology responses.
+    public_identity_key_file: PathBuf,
+}
+
+impl TopologySigner {
+    pub const PRIVATE_IDENTITY_KEY_FILE: &'static str = "private_identity.pem";
+    pub const PUBLIC_IDENTITY_KEY_FILE: &'static str = "public_identity.pem";
+
+    fn default_private_identity_key_file(id: Option<&str>) -> PathBuf {
+        Config::default_data_directory(id).join(Self::PRIVATE_IDENTITY_KEY_FILE)
+    }
+
+    fn default_public_identity_key_file(id: Option<&str>) -> PathBuf {
+        Config::default_data_directory(id).join(Self::PUBLIC_IDENTITY_KEY_FILE)
+    }
+}
+
+impl Default for TopologySigner {
+    fn default() -> Self {
+        TopologySigner {
+            private_identity_key_file: Self::default_private_identity_key_file(None),
+            public_identity_key_file: Self::default_public_identity_key_file(None),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, PartialEq, Eq, Serialize)]
 #[serde(default)]
 pub struct Rewarding {
@@ -288,6 +323,10 @@ impl Config {
             Config::default_data_directory(Some(id)).join(NodeStatusAPI::DB_FILE);
         self.network_monitor.credentials_database_path =
             Config::default_data_directory(Some(id)).join(NetworkMonitor::DB_FILE);
+        self.topology_signer.private_identity_key_file =
+            TopologySigner::default_private_identity_key_file(Some(id));
+        self.topology_signer.public_identity_key_file =
+            TopologySigner::default_public_identity_key_file(Some(id));
         self
     }
 
@@ -450,6 +489,14 @@ impl Config {
         self.node_status_api.database_path.clone()
     }
 
+    pub fn get_private_identity_key_file(&self) -> PathBuf {
+        self.topology_signer.private_identity_key_file.clone()
+    }
+
+    pub fn get_public_identity_key_file(&self) -> PathBuf {
+        self.topology_signer.public_identity_key_file.clone()
+    }
+
     // fix dead code warnings as this method is only ever used with coconut feature
     #[cfg(feature = "coconut")]
     pub fn get_all_validator_api_endpoints(&self) -> Vec<Url> {
@@ -76,6 +76,14 @@ per_node_test_packets = {{ network_monitor.per_node_test_packets }}
 # Path to the database file containing uptime statuses for all mixnodes and gateways.
 database_path = '{{ node_status_api.database_path }}'
 
+[topology_signer]
+
+# Path to file containing private identity key used for signing the topology responses.
+private_identity_key_file = '{{ topology_signer.private_identity_key_file }}'
+
+# Path to file containing public identity key used for signing the topology responses.
+public_identity_key_file = '{{ topology_signer.public_identity_key_file }}'
+
 ##### rewarding config options #####
 
 [rewarding]
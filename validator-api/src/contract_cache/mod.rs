@@ -3,6 +3,7 @@
 
 use crate::nymd_client::Client;
 use crate::storage::ValidatorApiStorage;
+use crate::topology_snapshots;
 use ::time::OffsetDateTime;
 use anyhow::Result;
 use mixnet_contract_common::mixnode::MixNodeDetails;
@@ -216,6 +217,53 @@ impl<C> ValidatorCacheRefresher<C> {
         (rewarded_set, active_set)
     }
 
+    // persists the topology of the current epoch, unless we have already done so
+    async fn snapshot_topology_if_new(
+        &self,
+        current_interval: Interval,
+        mixnodes: &[MixNodeBondAnnotated],
+        rewarded_set: &HashMap<MixId, RewardedSetNodeStatus>,
+        gateways: &[GatewayBond],
+    ) {
+        let storage = match &self.storage {
+            Some(storage) => storage,
+            None => return,
+        };
+
+        let epoch_id = current_interval.current_epoch_absolute_id();
+        match storage.has_topology_snapshot(epoch_id).await {
+            Ok(true) => return,
+            Ok(false) => (),
+            Err(err) => {
+                warn!(
+                    "Failed to check for the topology snapshot of epoch {} - {}",
+                    epoch_id, err
+                );
+                return;
+            }
+        }
+
+        let snapshot = topology_snapshots::build_snapshot(
+            epoch_id,
+            current_unix_timestamp(),
+            mixnodes,
+            rewarded_set,
+            gateways,
+        );
+        info!(
+            "Storing topology snapshot of epoch {} with {} mixnodes and {} gateways",
+            epoch_id,
+            snapshot.mixnodes.len(),
+            snapshot.gateways.len()
+        );
+        if let Err(err) = storage.insert_topology_snapshot(&snapshot).await {
+            warn!(
+                "Failed to store the topology snapshot of epoch {} - {}",
+                epoch_id, err
+            );
+        }
+    }
+
     async fn refresh_cache(&self) -> Result<()>
     where
         C: CosmWasmClient + Sync + Send,
@@ -226,14 +274,22 @@ impl<C> ValidatorCacheRefresher<C> {
         let mixnodes = self.nymd_client.get_mixnodes().await?;
         let gateways = self.nymd_client.get_gateways().await?;
 
-        let rewarded_set = self.get_rewarded_set_map().await;
+        let rewarded_set_map = self.get_rewarded_set_map().await;
 
         let mixnodes = self
-            .annotate_node_with_details(mixnodes, rewarding_params, current_interval, &rewarded_set)
+            .annotate_node_with_details(
+                mixnodes,
+                rewarding_params,
+                current_interval,
+                &rewarded_set_map,
+            )
+            .await;
+
+        self.snapshot_topology_if_new(current_interval, &mixnodes, &rewarded_set_map, &gateways)
             .await;
 
         let (rewarded_set, active_set) =
-            Self::collect_rewarded_and_active_set_details(&mixnodes, &rewarded_set);
+            Self::collect_rewarded_and_active_set_details(&mixnodes, &rewarded_set_map);
 
         info!(
             "Updating validator cache. There are {} mixnodes and {} gateways",
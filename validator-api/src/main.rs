@@ -9,6 +9,7 @@ use crate::contract_cache::ValidatorCacheRefresher;
 use crate::network_monitor::NetworkMonitorBuilder;
 use crate::node_status_api::uptime_updater::HistoricalUptimeUpdater;
 use crate::nymd_client::Client;
+use crate::signed_topology::TopologySigner;
 use crate::storage::ValidatorApiStorage;
 use ::config::defaults::mainnet::read_var_if_not_default;
 use ::config::defaults::setup_env;
@@ -49,8 +50,10 @@ mod epoch_operations;
 mod network_monitor;
 mod node_status_api;
 pub(crate) mod nymd_client;
+mod signed_topology;
 pub(crate) mod storage;
 mod swagger;
+mod topology_snapshots;
 
 #[cfg(feature = "coconut")]
 mod coconut;
@@ -448,14 +451,23 @@ async fn setup_rocket(
         "/" => custom_route_spec,
         "" => contract_cache::validator_cache_routes(&openapi_settings),
         "/status" => node_status_api::node_status_routes(&openapi_settings, config.get_network_monitor_enabled()),
+        "" => topology_snapshots::topology_snapshot_routes(&openapi_settings, config.get_network_monitor_enabled()),
+        "" => signed_topology::signed_topology_routes(&openapi_settings),
     }
 
+    let topology_signer = TopologySigner::load_or_generate(config)?;
+    info!(
+        "Topology responses are going to be signed with identity key {}",
+        topology_signer.public_key().to_base58_string()
+    );
+
     let rocket = rocket
         .mount("/swagger", make_swagger_ui(&swagger::get_docs()))
         .attach(setup_cors()?)
         .attach(setup_liftoff_notify(liftoff_notify))
         .attach(ValidatorCache::stage())
-        .attach(NodeStatusCache::stage());
+        .attach(NodeStatusCache::stage())
+        .attach(topology_signer.stage());
 
     // This is not a very nice approach. A lazy value would be more suitable, but that's still
     // a nightly feature: https://github.com/rust-lang/rust/issues/74465
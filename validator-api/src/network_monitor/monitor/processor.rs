@@ -9,7 +9,7 @@ use futures::channel::mpsc;
 use futures::lock::{Mutex, MutexGuard};
 use futures::{SinkExt, StreamExt};
 use log::warn;
-use nymsphinx::receiver::MessageReceiver;
+use nymsphinx::receiver::{MessageReceiver, RecoveredMessage};
 use std::fmt::{self, Display, Formatter};
 use std::mem;
 use std::sync::Arc;
@@ -88,6 +88,10 @@ impl ReceivedProcessorInner {
             .insert_new_fragment(fragment)
             .map_err(|_| ProcessingError::MalformedPacketReceived)?
             .ok_or(ProcessingError::NonTestPacketReceived)?; // if it's a test packet it MUST BE reconstructed with single fragment
+        let recovered = match recovered {
+            RecoveredMessage::Application(recovered) => recovered,
+            RecoveredMessage::Control(_) => return Err(ProcessingError::NonTestPacketReceived),
+        };
         let test_packet = TestPacket::try_from_bytes(&recovered.message)
             .map_err(|_| ProcessingError::MalformedPacketReceived)?;
 
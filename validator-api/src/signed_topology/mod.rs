@@ -0,0 +1,82 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Topology responses signed with the identity key of this validator-api, so that the clients
+//! could verify they have not been tampered with, or served by an untrusted party.
+
+use crate::config::Config;
+use crypto::asymmetric::identity;
+use okapi::openapi3::OpenApi;
+use rocket::fairing::AdHoc;
+use rocket::Route;
+use rocket_okapi::{openapi_get_routes_spec, settings::OpenApiSettings};
+use std::{fs, io};
+use validator_api_requests::models::{SignedTopologyResponse, TopologyPayload};
+
+pub(crate) mod routes;
+
+pub(crate) fn signed_topology_routes(settings: &OpenApiSettings) -> (Vec<Route>, OpenApi) {
+    openapi_get_routes_spec![settings: routes::get_signed_topology]
+}
+
+pub(crate) struct TopologySigner {
+    identity_keypair: identity::KeyPair,
+}
+
+impl TopologySigner {
+    /// Loads the identity keys of this validator-api from the files specified in the config.
+    /// If they don't exist yet, a fresh pair of keys gets generated and stored instead.
+    pub(crate) fn load_or_generate(config: &Config) -> io::Result<Self> {
+        let private_key_file = config.get_private_identity_key_file();
+        let public_key_file = config.get_public_identity_key_file();
+        let paths = pemstore::KeyPairPath::new(private_key_file.clone(), public_key_file.clone());
+
+        let identity_keypair = match pemstore::load_keypair(&paths) {
+            Ok(keypair) => keypair,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                info!("Generating new identity keys for signing the topology responses");
+                let mut rng = rand_07::rngs::OsRng;
+                let keypair = identity::KeyPair::new(&mut rng);
+
+                for path in [&private_key_file, &public_key_file] {
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                }
+                pemstore::store_keypair(&keypair, &paths)?;
+                keypair
+            }
+            Err(err) => return Err(err),
+        };
+
+        Ok(TopologySigner { identity_keypair })
+    }
+
+    pub(crate) fn public_key(&self) -> &identity::PublicKey {
+        self.identity_keypair.public_key()
+    }
+
+    pub(crate) fn sign(
+        &self,
+        payload: &TopologyPayload,
+    ) -> Result<SignedTopologyResponse, serde_json::Error> {
+        let payload = serde_json::to_string(payload)?;
+        let signature = self
+            .identity_keypair
+            .private_key()
+            .sign(payload.as_bytes())
+            .to_base58_string();
+
+        Ok(SignedTopologyResponse {
+            payload,
+            signature,
+            signer: self.public_key().to_base58_string(),
+        })
+    }
+
+    pub(crate) fn stage(self) -> AdHoc {
+        AdHoc::on_ignite("Topology Signer Stage", |rocket| async {
+            rocket.manage(self)
+        })
+    }
+}
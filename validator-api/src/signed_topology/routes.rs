@@ -0,0 +1,44 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::contract_cache::ValidatorCache;
+use crate::node_status_api::models::ErrorResponse;
+use crate::signed_topology::TopologySigner;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket_okapi::openapi;
+use validator_api_requests::models::{SignedTopologyResponse, TopologyPayload};
+
+/// The active set and all the (non-blacklisted) gateways of the current epoch,
+/// signed with the identity key of this validator-api.
+#[openapi(tag = "topology")]
+#[get("/topology/signed")]
+pub(crate) async fn get_signed_topology(
+    cache: &State<ValidatorCache>,
+    signer: &State<TopologySigner>,
+) -> Result<Json<SignedTopologyResponse>, ErrorResponse> {
+    let current_interval = cache.current_interval().await.into_inner().ok_or_else(|| {
+        ErrorResponse::new(
+            "the validator cache hasn't been initialised yet",
+            Status::ServiceUnavailable,
+        )
+    })?;
+
+    let active_set = cache.active_set_detailed().await;
+    let payload = TopologyPayload {
+        absolute_epoch_id: current_interval.current_epoch_absolute_id(),
+        timestamp: active_set.timestamp(),
+        mixnodes: active_set
+            .into_inner()
+            .into_iter()
+            .map(|bond| bond.mixnode_details)
+            .collect(),
+        gateways: cache.gateways().await,
+    };
+
+    signer
+        .sign(&payload)
+        .map(Json)
+        .map_err(|err| ErrorResponse::new(err.to_string(), Status::InternalServerError))
+}
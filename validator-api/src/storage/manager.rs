@@ -5,9 +5,11 @@ use crate::node_status_api::models::{HistoricalUptime, Uptime};
 use crate::node_status_api::utils::{ActiveGatewayStatuses, ActiveMixnodeStatuses};
 use crate::storage::models::{
     ActiveGateway, ActiveMixnode, NodeStatus, RewardingReport, TestingRoute,
+    TopologySnapshotMixnode,
 };
 use mixnet_contract_common::{EpochId, IdentityKey, MixId};
 use std::convert::TryFrom;
+use validator_api_requests::models::{SnapshotGateway, SnapshotMixnode};
 
 #[derive(Clone)]
 pub(crate) struct StorageManager {
@@ -884,6 +886,160 @@ impl StorageManager {
         .await
     }
 
+    /// Inserts the snapshot of the network topology of the specified epoch into the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `absolute_epoch_id`: absolute id of the epoch the snapshot was taken in.
+    /// * `taken_at`: unix timestamp indicating when the snapshot was taken.
+    /// * `mixnodes`: mixnodes of the rewarded set during the epoch.
+    /// * `gateways`: gateways bonded during the epoch.
+    pub(super) async fn insert_topology_snapshot(
+        &self,
+        absolute_epoch_id: EpochId,
+        taken_at: i64,
+        mixnodes: &[SnapshotMixnode],
+        gateways: &[SnapshotGateway],
+    ) -> Result<(), sqlx::Error> {
+        // insert it all in a transaction so that we'd never end up with a partial snapshot
+        let mut tx = self.connection_pool.begin().await?;
+
+        sqlx::query!(
+            "INSERT INTO topology_snapshot(absolute_epoch_id, taken_at) VALUES (?, ?)",
+            absolute_epoch_id,
+            taken_at
+        )
+        .execute(&mut tx)
+        .await?;
+
+        for mixnode in mixnodes {
+            let active = mixnode.status.is_active();
+            sqlx::query!(
+                r#"
+                    INSERT INTO topology_snapshot_mixnode
+                    (absolute_epoch_id, mix_id, identity_key, sphinx_key, owner, host, mix_port, layer, active)
+                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?);
+                "#,
+                absolute_epoch_id,
+                mixnode.mix_id,
+                mixnode.identity_key,
+                mixnode.sphinx_key,
+                mixnode.owner,
+                mixnode.host,
+                mixnode.mix_port,
+                mixnode.layer,
+                active,
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+
+        for gateway in gateways {
+            sqlx::query!(
+                r#"
+                    INSERT INTO topology_snapshot_gateway
+                    (absolute_epoch_id, identity_key, sphinx_key, owner, host, mix_port, clients_port, location)
+                    VALUES (?, ?, ?, ?, ?, ?, ?, ?);
+                "#,
+                absolute_epoch_id,
+                gateway.identity_key,
+                gateway.sphinx_key,
+                gateway.owner,
+                gateway.host,
+                gateway.mix_port,
+                gateway.clients_port,
+                gateway.location,
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+
+        // finally commit the transaction
+        tx.commit().await
+    }
+
+    /// Tries to obtain the time at which the topology snapshot of the specified epoch was taken.
+    /// Returns `None` if the snapshot does not exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `absolute_epoch_id`: absolute id of the epoch of the snapshot.
+    pub(super) async fn get_topology_snapshot_timestamp(
+        &self,
+        absolute_epoch_id: EpochId,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        let taken_at = sqlx::query!(
+            "SELECT taken_at FROM topology_snapshot WHERE absolute_epoch_id = ?",
+            absolute_epoch_id
+        )
+        .fetch_optional(&self.connection_pool)
+        .await?
+        .map(|row| row.taken_at);
+
+        Ok(taken_at)
+    }
+
+    /// Obtains all mixnodes included in the topology snapshot of the specified epoch.
+    ///
+    /// # Arguments
+    ///
+    /// * `absolute_epoch_id`: absolute id of the epoch of the snapshot.
+    pub(super) async fn get_topology_snapshot_mixnodes(
+        &self,
+        absolute_epoch_id: EpochId,
+    ) -> Result<Vec<TopologySnapshotMixnode>, sqlx::Error> {
+        sqlx::query_as!(
+            TopologySnapshotMixnode,
+            r#"
+                SELECT
+                    mix_id as "mix_id: MixId",
+                    identity_key,
+                    sphinx_key,
+                    owner,
+                    host,
+                    mix_port as "mix_port: u16",
+                    layer as "layer: u8",
+                    active as "active: bool"
+                FROM topology_snapshot_mixnode
+                WHERE absolute_epoch_id = ?
+                ORDER BY mix_id
+            "#,
+            absolute_epoch_id
+        )
+        .fetch_all(&self.connection_pool)
+        .await
+    }
+
+    /// Obtains all gateways included in the topology snapshot of the specified epoch.
+    ///
+    /// # Arguments
+    ///
+    /// * `absolute_epoch_id`: absolute id of the epoch of the snapshot.
+    pub(super) async fn get_topology_snapshot_gateways(
+        &self,
+        absolute_epoch_id: EpochId,
+    ) -> Result<Vec<SnapshotGateway>, sqlx::Error> {
+        sqlx::query_as!(
+            SnapshotGateway,
+            r#"
+                SELECT
+                    identity_key,
+                    sphinx_key,
+                    owner,
+                    host,
+                    mix_port as "mix_port: u16",
+                    clients_port as "clients_port: u16",
+                    location
+                FROM topology_snapshot_gateway
+                WHERE absolute_epoch_id = ?
+                ORDER BY identity_key
+            "#,
+            absolute_epoch_id
+        )
+        .fetch_all(&self.connection_pool)
+        .await
+    }
+
     /// Obtains all statuses of active mixnodes from the specified time interval.
     ///
     /// # Arguments
@@ -15,6 +15,7 @@ use rocket::fairing::AdHoc;
 use sqlx::ConnectOptions;
 use std::path::PathBuf;
 use time::OffsetDateTime;
+use validator_api_requests::models::EpochTopologySnapshot;
 
 use self::manager::{AvgGatewayReliability, AvgMixnodeReliability};
 
@@ -729,6 +730,75 @@ impl ValidatorApiStorage {
             .map_err(|e| ValidatorApiStorageError::InternalDatabaseError(e.to_string()))
     }
 
+    pub(crate) async fn has_topology_snapshot(
+        &self,
+        absolute_epoch_id: EpochId,
+    ) -> Result<bool, ValidatorApiStorageError> {
+        Ok(self
+            .manager
+            .get_topology_snapshot_timestamp(absolute_epoch_id)
+            .await
+            .map_err(|e| ValidatorApiStorageError::InternalDatabaseError(e.to_string()))?
+            .is_some())
+    }
+
+    pub(crate) async fn insert_topology_snapshot(
+        &self,
+        snapshot: &EpochTopologySnapshot,
+    ) -> Result<(), ValidatorApiStorageError> {
+        self.manager
+            .insert_topology_snapshot(
+                snapshot.absolute_epoch_id,
+                snapshot.taken_at,
+                &snapshot.mixnodes,
+                &snapshot.gateways,
+            )
+            .await
+            .map_err(|e| ValidatorApiStorageError::InternalDatabaseError(e.to_string()))
+    }
+
+    /// Obtains the snapshot of the network topology taken during the specified epoch.
+    ///
+    /// # Arguments
+    ///
+    /// * `absolute_epoch_id`: absolute id of the epoch of the snapshot.
+    pub(crate) async fn get_topology_snapshot(
+        &self,
+        absolute_epoch_id: EpochId,
+    ) -> Result<Option<EpochTopologySnapshot>, ValidatorApiStorageError> {
+        let taken_at = match self
+            .manager
+            .get_topology_snapshot_timestamp(absolute_epoch_id)
+            .await
+            .map_err(|e| ValidatorApiStorageError::InternalDatabaseError(e.to_string()))?
+        {
+            Some(taken_at) => taken_at,
+            None => return Ok(None),
+        };
+
+        let mixnodes = self
+            .manager
+            .get_topology_snapshot_mixnodes(absolute_epoch_id)
+            .await
+            .map_err(|e| ValidatorApiStorageError::InternalDatabaseError(e.to_string()))?
+            .into_iter()
+            .map(Into::into)
+            .collect();
+
+        let gateways = self
+            .manager
+            .get_topology_snapshot_gateways(absolute_epoch_id)
+            .await
+            .map_err(|e| ValidatorApiStorageError::InternalDatabaseError(e.to_string()))?;
+
+        Ok(Some(EpochTopologySnapshot {
+            absolute_epoch_id,
+            taken_at,
+            mixnodes,
+            gateways,
+        }))
+    }
+
     #[cfg(feature = "coconut")]
     pub(crate) async fn get_blinded_signature_response(
         &self,
@@ -1,7 +1,8 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
-use mixnet_contract_common::MixId;
+use mixnet_contract_common::{MixId, RewardedSetNodeStatus};
+use validator_api_requests::models::SnapshotMixnode;
 
 // Internally used struct to catch results from the database to calculate uptimes for given mixnode/gateway
 pub(crate) struct NodeStatus {
@@ -47,3 +48,33 @@ pub(crate) struct RewardingReport {
 
     pub(crate) eligible_mixnodes: u32,
 }
+
+pub(crate) struct TopologySnapshotMixnode {
+    pub(crate) mix_id: MixId,
+    pub(crate) identity_key: String,
+    pub(crate) sphinx_key: String,
+    pub(crate) owner: String,
+    pub(crate) host: String,
+    pub(crate) mix_port: u16,
+    pub(crate) layer: u8,
+    pub(crate) active: bool,
+}
+
+impl From<TopologySnapshotMixnode> for SnapshotMixnode {
+    fn from(mixnode: TopologySnapshotMixnode) -> Self {
+        SnapshotMixnode {
+            mix_id: mixnode.mix_id,
+            identity_key: mixnode.identity_key,
+            sphinx_key: mixnode.sphinx_key,
+            owner: mixnode.owner,
+            host: mixnode.host,
+            mix_port: mixnode.mix_port,
+            layer: mixnode.layer,
+            status: if mixnode.active {
+                RewardedSetNodeStatus::Active
+            } else {
+                RewardedSetNodeStatus::Standby
+            },
+        }
+    }
+}
@@ -0,0 +1,260 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Per-epoch snapshots of the network topology, i.e. the rewarded set with its layer assignments
+//! and all bonded gateways, alongside the sphinx keys that were valid at the time.
+//! They allow reproducing past routing decisions and debugging delivery failures after the fact.
+
+use mixnet_contract_common::{
+    EpochId, GatewayBond, MixId, PendingSphinxKey, RewardedSetNodeStatus, SphinxKey,
+};
+use okapi::openapi3::OpenApi;
+use rocket::Route;
+use rocket_okapi::{openapi_get_routes_spec, settings::OpenApiSettings};
+use std::collections::{BTreeMap, HashMap};
+use validator_api_requests::models::{
+    EpochTopologyDiff, EpochTopologySnapshot, MixNodeBondAnnotated, SnapshotGateway,
+    SnapshotMixnode,
+};
+
+pub(crate) mod routes;
+
+pub(crate) fn topology_snapshot_routes(
+    settings: &OpenApiSettings,
+    enabled: bool,
+) -> (Vec<Route>, OpenApi) {
+    if enabled {
+        openapi_get_routes_spec![
+            settings: routes::get_epoch_topology,
+            routes::get_epoch_topology_diff,
+        ]
+    } else {
+        // the snapshots are only ever persisted if we have the storage available,
+        // which is currently tied to the network monitor
+        (Vec::new(), OpenApi::new())
+    }
+}
+
+fn sphinx_key_in_epoch(
+    current: &SphinxKey,
+    pending: Option<&PendingSphinxKey>,
+    epoch: EpochId,
+) -> SphinxKey {
+    match pending {
+        Some(pending) if pending.is_active(epoch) => pending.sphinx_key.clone(),
+        _ => current.clone(),
+    }
+}
+
+pub(crate) fn build_snapshot(
+    absolute_epoch_id: EpochId,
+    taken_at: i64,
+    mixnodes: &[MixNodeBondAnnotated],
+    rewarded_set: &HashMap<MixId, RewardedSetNodeStatus>,
+    gateways: &[GatewayBond],
+) -> EpochTopologySnapshot {
+    let mixnodes = mixnodes
+        .iter()
+        .filter_map(|mixnode| {
+            let status = *rewarded_set.get(&mixnode.mix_id())?;
+            let bond = &mixnode.mixnode_details.bond_information;
+            Some(SnapshotMixnode {
+                mix_id: bond.mix_id,
+                identity_key: bond.mix_node.identity_key.clone(),
+                sphinx_key: sphinx_key_in_epoch(
+                    &bond.mix_node.sphinx_key,
+                    bond.pending_sphinx_key.as_ref(),
+                    absolute_epoch_id,
+                ),
+                owner: bond.owner.to_string(),
+                host: bond.mix_node.host.clone(),
+                mix_port: bond.mix_node.mix_port,
+                layer: bond.layer as u8,
+                status,
+            })
+        })
+        .collect();
+
+    let gateways = gateways
+        .iter()
+        .map(|bond| SnapshotGateway {
+            identity_key: bond.gateway.identity_key.clone(),
+            sphinx_key: sphinx_key_in_epoch(
+                &bond.gateway.sphinx_key,
+                bond.pending_sphinx_key.as_ref(),
+                absolute_epoch_id,
+            ),
+            owner: bond.owner.to_string(),
+            host: bond.gateway.host.clone(),
+            mix_port: bond.gateway.mix_port,
+            clients_port: bond.gateway.clients_port,
+            location: bond.gateway.location.clone(),
+        })
+        .collect();
+
+    EpochTopologySnapshot {
+        absolute_epoch_id,
+        taken_at,
+        mixnodes,
+        gateways,
+    }
+}
+
+pub(crate) fn diff_snapshots(
+    from: &EpochTopologySnapshot,
+    to: &EpochTopologySnapshot,
+) -> EpochTopologyDiff {
+    let mut diff = EpochTopologyDiff {
+        from_epoch: from.absolute_epoch_id,
+        to_epoch: to.absolute_epoch_id,
+        ..Default::default()
+    };
+
+    let old_mixnodes: BTreeMap<_, _> = from
+        .mixnodes
+        .iter()
+        .map(|mixnode| (mixnode.mix_id, mixnode))
+        .collect();
+    let new_mixnodes: BTreeMap<_, _> = to
+        .mixnodes
+        .iter()
+        .map(|mixnode| (mixnode.mix_id, mixnode))
+        .collect();
+
+    for (mix_id, mixnode) in &new_mixnodes {
+        match old_mixnodes.get(mix_id) {
+            None => diff.added_mixnodes.push((*mixnode).clone()),
+            Some(old) if old != mixnode => diff.changed_mixnodes.push((*mixnode).clone()),
+            _ => (),
+        }
+    }
+    diff.removed_mixnodes = old_mixnodes
+        .keys()
+        .filter(|mix_id| !new_mixnodes.contains_key(mix_id))
+        .copied()
+        .collect();
+
+    let old_gateways: BTreeMap<_, _> = from
+        .gateways
+        .iter()
+        .map(|gateway| (gateway.identity_key.as_str(), gateway))
+        .collect();
+    let new_gateways: BTreeMap<_, _> = to
+        .gateways
+        .iter()
+        .map(|gateway| (gateway.identity_key.as_str(), gateway))
+        .collect();
+
+    for (identity, gateway) in &new_gateways {
+        match old_gateways.get(identity) {
+            None => diff.added_gateways.push((*gateway).clone()),
+            Some(old) if old != gateway => diff.changed_gateways.push((*gateway).clone()),
+            _ => (),
+        }
+    }
+    diff.removed_gateways = old_gateways
+        .keys()
+        .filter(|identity| !new_gateways.contains_key(*identity))
+        .map(|identity| identity.to_string())
+        .collect();
+
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mixnode(mix_id: MixId, layer: u8, sphinx_key: &str) -> SnapshotMixnode {
+        SnapshotMixnode {
+            mix_id,
+            identity_key: format!("identity{}", mix_id),
+            sphinx_key: sphinx_key.to_string(),
+            owner: format!("owner{}", mix_id),
+            host: "1.2.3.4".to_string(),
+            mix_port: 1789,
+            layer,
+            status: RewardedSetNodeStatus::Active,
+        }
+    }
+
+    fn gateway(identity: &str) -> SnapshotGateway {
+        SnapshotGateway {
+            identity_key: identity.to_string(),
+            sphinx_key: "sphinx".to_string(),
+            owner: "owner".to_string(),
+            host: "1.2.3.4".to_string(),
+            mix_port: 1789,
+            clients_port: 9000,
+            location: "Earth".to_string(),
+        }
+    }
+
+    fn snapshot(
+        absolute_epoch_id: EpochId,
+        mixnodes: Vec<SnapshotMixnode>,
+        gateways: Vec<SnapshotGateway>,
+    ) -> EpochTopologySnapshot {
+        EpochTopologySnapshot {
+            absolute_epoch_id,
+            taken_at: 0,
+            mixnodes,
+            gateways,
+        }
+    }
+
+    #[test]
+    fn diff_of_identical_snapshots_is_empty() {
+        let from = snapshot(1, vec![mixnode(1, 1, "key1")], vec![gateway("gw1")]);
+        let to = snapshot(2, vec![mixnode(1, 1, "key1")], vec![gateway("gw1")]);
+
+        let diff = diff_snapshots(&from, &to);
+        assert_eq!(diff.from_epoch, 1);
+        assert_eq!(diff.to_epoch, 2);
+        assert!(diff.added_mixnodes.is_empty());
+        assert!(diff.removed_mixnodes.is_empty());
+        assert!(diff.changed_mixnodes.is_empty());
+        assert!(diff.added_gateways.is_empty());
+        assert!(diff.removed_gateways.is_empty());
+        assert!(diff.changed_gateways.is_empty());
+    }
+
+    #[test]
+    fn diff_detects_added_removed_and_changed_nodes() {
+        let mut standby = mixnode(3, 3, "key3");
+        let mut rotated_gateway = gateway("gw2");
+        let from = snapshot(
+            5,
+            vec![
+                mixnode(1, 1, "key1"),
+                mixnode(2, 2, "key2"),
+                standby.clone(),
+            ],
+            vec![gateway("gw1"), rotated_gateway.clone()],
+        );
+
+        standby.status = RewardedSetNodeStatus::Standby;
+        rotated_gateway.sphinx_key = "rotated".to_string();
+        let to = snapshot(
+            6,
+            vec![
+                mixnode(1, 1, "key1"),
+                mixnode(2, 3, "key2"),
+                standby.clone(),
+                mixnode(4, 1, "key4"),
+            ],
+            vec![rotated_gateway.clone(), gateway("gw3")],
+        );
+
+        let diff = diff_snapshots(&from, &to);
+        assert_eq!(diff.added_mixnodes, vec![mixnode(4, 1, "key4")]);
+        assert!(diff.removed_mixnodes.is_empty());
+        assert_eq!(diff.changed_mixnodes, vec![mixnode(2, 3, "key2"), standby]);
+        assert_eq!(diff.added_gateways, vec![gateway("gw3")]);
+        assert_eq!(diff.removed_gateways, vec!["gw1".to_string()]);
+        assert_eq!(diff.changed_gateways, vec![rotated_gateway]);
+
+        let reverse = diff_snapshots(&to, &from);
+        assert_eq!(reverse.removed_mixnodes, vec![4]);
+    }
+}
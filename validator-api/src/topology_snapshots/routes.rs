@@ -0,0 +1,53 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::node_status_api::models::ErrorResponse;
+use crate::storage::ValidatorApiStorage;
+use crate::topology_snapshots::diff_snapshots;
+use mixnet_contract_common::EpochId;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket_okapi::openapi;
+use validator_api_requests::models::{EpochTopologyDiff, EpochTopologySnapshot};
+
+async fn get_snapshot(
+    storage: &ValidatorApiStorage,
+    epoch_id: EpochId,
+) -> Result<EpochTopologySnapshot, ErrorResponse> {
+    storage
+        .get_topology_snapshot(epoch_id)
+        .await
+        .map_err(|err| ErrorResponse::new(err.to_string(), Status::InternalServerError))?
+        .ok_or_else(|| {
+            ErrorResponse::new(
+                format!("no topology snapshot exists for epoch {}", epoch_id),
+                Status::NotFound,
+            )
+        })
+}
+
+#[openapi(tag = "topology")]
+#[get("/epoch/<epoch_id>/topology")]
+pub(crate) async fn get_epoch_topology(
+    storage: &State<ValidatorApiStorage>,
+    epoch_id: EpochId,
+) -> Result<Json<EpochTopologySnapshot>, ErrorResponse> {
+    Ok(Json(get_snapshot(storage, epoch_id).await?))
+}
+
+/// Changes to the topology of the specified epoch in relation to the `since` epoch,
+/// which defaults to the one directly preceding it.
+#[openapi(tag = "topology")]
+#[get("/epoch/<epoch_id>/topology/diff?<since>")]
+pub(crate) async fn get_epoch_topology_diff(
+    storage: &State<ValidatorApiStorage>,
+    epoch_id: EpochId,
+    since: Option<EpochId>,
+) -> Result<Json<EpochTopologyDiff>, ErrorResponse> {
+    let since = since.unwrap_or_else(|| epoch_id.saturating_sub(1));
+    let from = get_snapshot(storage, since).await?;
+    let to = get_snapshot(storage, epoch_id).await?;
+
+    Ok(Json(diff_snapshots(&from, &to)))
+}
@@ -5,7 +5,10 @@ use cosmwasm_std::{Coin, Decimal};
 use mixnet_contract_common::mixnode::MixNodeDetails;
 use mixnet_contract_common::reward_params::{Performance, RewardingParams};
 use mixnet_contract_common::rewarding::RewardEstimate;
-use mixnet_contract_common::{Interval, MixId, MixNode, Percent, RewardedSetNodeStatus};
+use mixnet_contract_common::{
+    EpochId, GatewayBond, IdentityKey, Interval, MixId, MixNode, Percent, RewardedSetNodeStatus,
+    SphinxKey,
+};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::{fmt, time::Duration};
@@ -219,3 +222,78 @@ pub struct InclusionProbability {
     pub in_active: f64,
     pub in_reserve: f64,
 }
+
+/// Mixnode as it was present in the rewarded set at the time of taking a topology snapshot.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+pub struct SnapshotMixnode {
+    pub mix_id: MixId,
+    pub identity_key: IdentityKey,
+    /// Sphinx key that was valid during the epoch of the snapshot.
+    pub sphinx_key: SphinxKey,
+    pub owner: String,
+    pub host: String,
+    pub mix_port: u16,
+    pub layer: u8,
+    pub status: RewardedSetNodeStatus,
+}
+
+/// Gateway as it was bonded at the time of taking a topology snapshot.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+pub struct SnapshotGateway {
+    pub identity_key: IdentityKey,
+    /// Sphinx key that was valid during the epoch of the snapshot.
+    pub sphinx_key: SphinxKey,
+    pub owner: String,
+    pub host: String,
+    pub mix_port: u16,
+    pub clients_port: u16,
+    pub location: String,
+}
+
+/// Network topology, i.e. the rewarded set with its layer assignments and all the gateways,
+/// as it was during the particular epoch.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct EpochTopologySnapshot {
+    pub absolute_epoch_id: EpochId,
+    /// Unix timestamp of when the snapshot was taken.
+    pub taken_at: i64,
+    pub mixnodes: Vec<SnapshotMixnode>,
+    pub gateways: Vec<SnapshotGateway>,
+}
+
+/// Changes to the network topology between two epochs. Nodes are considered to have changed
+/// if any of their snapshotted properties, such as the layer or the sphinx key, differ.
+/// Added and changed nodes are presented as they were in the later epoch.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub struct EpochTopologyDiff {
+    pub from_epoch: EpochId,
+    pub to_epoch: EpochId,
+    pub added_mixnodes: Vec<SnapshotMixnode>,
+    pub removed_mixnodes: Vec<MixId>,
+    pub changed_mixnodes: Vec<SnapshotMixnode>,
+    pub added_gateways: Vec<SnapshotGateway>,
+    pub removed_gateways: Vec<IdentityKey>,
+    pub changed_gateways: Vec<SnapshotGateway>,
+}
+
+/// Current network topology, i.e. the active set and the gateways, as seen by the validator-api.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct TopologyPayload {
+    pub absolute_epoch_id: EpochId,
+    /// Unix timestamp of when the validator-api last refreshed its view of the network.
+    pub timestamp: i64,
+    pub mixnodes: Vec<MixNodeDetails>,
+    pub gateways: Vec<GatewayBond>,
+}
+
+/// [`TopologyPayload`] signed with the identity key of the validator-api that served it.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SignedTopologyResponse {
+    /// JSON-serialized [`TopologyPayload`]. It's kept as a string so that the signature
+    /// could be verified against the exact bytes that got signed.
+    pub payload: String,
+    /// Base58-encoded ed25519 signature on the payload.
+    pub signature: String,
+    /// Base58-encoded identity key of the validator-api.
+    pub signer: IdentityKey,
+}